@@ -1,6 +1,6 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use serde_json::json;
-use transform_rules::{parse_rule_file, transform};
+use transform_rules::{parse_rule_file, transform, transform_with_warnings_parallel};
 
 const EXTENDED_RULES: &str = include_str!("../tests/fixtures/t13_expr_extended/rules.yaml");
 
@@ -45,6 +45,32 @@ mappings:
         - "value"
 "#;
 
+const REGEX_RULES: &str = r#"
+version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  - target: "id"
+    source: "input.id"
+  - target: "code"
+    expr:
+      op: "replace"
+      args: [ { ref: "input.text" }, "([a-z]+)-(\\d+)", "$2:$1", "regex_all" ]
+  - target: "digits"
+    expr:
+      op: "replace"
+      args: [ { ref: "input.text" }, "[^0-9]+", "", "regex_all" ]
+  - target: "user_name"
+    expr:
+      op: "lookup_first"
+      args:
+        - { ref: "context.users" }
+        - "id"
+        - { ref: "input.user_id" }
+        - "name"
+"#;
+
 fn bench_simple_transform(c: &mut Criterion) {
     let rule = parse_rule_file(SIMPLE_RULES).expect("failed to parse rules");
     let input = build_simple_input(5000);
@@ -85,6 +111,26 @@ fn bench_extended_transform_with_rule_parse(c: &mut Criterion) {
     });
 }
 
+fn bench_parallel_transform(c: &mut Criterion) {
+    let rule = parse_rule_file(REGEX_RULES).expect("failed to parse rules");
+    let input = build_regex_input(20000, 100);
+    let context = build_context(100, 1);
+
+    let mut group = c.benchmark_group("transform_regex_threads");
+    group.sample_size(10);
+    for threads in [1, 2, 4, 8] {
+        group.bench_function(threads.to_string(), |b| {
+            b.iter(|| {
+                let input = black_box(&input);
+                let output = transform_with_warnings_parallel(&rule, input, Some(&context), threads)
+                    .expect("transform failed");
+                black_box(output);
+            })
+        });
+    }
+    group.finish();
+}
+
 fn build_simple_input(count: usize) -> String {
     let mut records = Vec::with_capacity(count);
     for i in 0..count {
@@ -132,6 +178,18 @@ fn build_context(user_count: usize, tag_count: usize) -> serde_json::Value {
     })
 }
 
+fn build_regex_input(count: usize, user_count: usize) -> String {
+    let mut records = Vec::with_capacity(count);
+    for i in 0..count {
+        records.push(json!({
+            "id": i as i64,
+            "text": format!("abc-{}-def-{}-ghi-{} lorem ipsum dolor sit amet", i, i * 7, i % 13),
+            "user_id": (i % user_count) as i64,
+        }));
+    }
+    serde_json::to_string(&records).expect("failed to serialize input")
+}
+
 fn build_extended_input(count: usize) -> String {
     let mut records = Vec::with_capacity(count);
     for _ in 0..count {
@@ -157,6 +215,7 @@ criterion_group!(
     benches,
     bench_simple_transform,
     bench_lookup_transform,
-    bench_extended_transform_with_rule_parse
+    bench_extended_transform_with_rule_parse,
+    bench_parallel_transform
 );
 criterion_main!(benches);
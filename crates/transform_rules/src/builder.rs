@@ -0,0 +1,266 @@
+use serde_json::Value as JsonValue;
+
+use crate::error::RuleError;
+use crate::model::{
+    Column, CsvInput, Expr, ExprChain, ExprOp, ExprRef, GroupSpec, InputFormat, InputSpec,
+    JsonInput, Mapping, MappingSource, OnError, OutputFormat, OutputSpec, RuleFile,
+};
+use crate::validator::validate_rule_file;
+
+/// Builds a [`RuleFile`] in code instead of YAML.
+///
+/// `build` runs the same validation as `validate_rule_file`, so a built rule is always valid;
+/// use [`RuleFile::to_yaml`] to store it.
+#[derive(Debug, Clone)]
+pub struct RuleFileBuilder {
+    rule: RuleFile,
+}
+
+impl RuleFileBuilder {
+    /// Starts a version 1 rule; `csv` and `json` input get their sections with default options.
+    pub fn new(format: InputFormat) -> Self {
+        let input = InputSpec {
+            format,
+            csv: matches!(format, InputFormat::Csv).then(|| CsvInput {
+                has_header: true,
+                delimiter: ",".to_string(),
+                columns: None,
+            }),
+            json: matches!(format, InputFormat::Json).then(|| JsonInput {
+                records_path: None,
+                max_depth: None,
+            }),
+        };
+        Self {
+            rule: RuleFile {
+                version: 1,
+                input,
+                output: None,
+                tables: None,
+                constants: None,
+                record_when: None,
+                group: None,
+                mappings: Vec::new(),
+                mapping_groups: None,
+                mapping_defaults: None,
+                on_error: OnError::Abort,
+                origins: None,
+            },
+        }
+    }
+
+    pub fn records_path(mut self, path: impl Into<String>) -> Self {
+        self.json_input().records_path = Some(path.into());
+        self
+    }
+
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.json_input().max_depth = Some(max_depth);
+        self
+    }
+
+    pub fn has_header(mut self, has_header: bool) -> Self {
+        self.csv_input().has_header = has_header;
+        self
+    }
+
+    pub fn delimiter(mut self, delimiter: impl Into<String>) -> Self {
+        self.csv_input().delimiter = delimiter.into();
+        self
+    }
+
+    /// Adds an `input.csv.columns` entry; `value_type` is one of `string|int|float|bool|auto`.
+    pub fn csv_column(mut self, name: impl Into<String>, value_type: Option<&str>) -> Self {
+        self.csv_input()
+            .columns
+            .get_or_insert_with(Vec::new)
+            .push(Column {
+                name: name.into(),
+                value_type: value_type.map(str::to_string),
+            });
+        self
+    }
+
+    pub fn output_name(mut self, name: impl Into<String>) -> Self {
+        self.output().name = Some(name.into());
+        self
+    }
+
+    pub fn output_format(mut self, format: OutputFormat) -> Self {
+        self.output().format = format;
+        self
+    }
+
+    pub fn output_columns<I, S>(mut self, columns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.output().columns = Some(columns.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Adds a `constants` entry, read by mappings as `const.<name>`.
+    pub fn constant(mut self, name: impl Into<String>, expr: Expr) -> Self {
+        self.rule
+            .constants
+            .get_or_insert_with(Default::default)
+            .insert(name.into(), expr);
+        self
+    }
+
+    pub fn record_when(mut self, expr: Expr) -> Self {
+        self.rule.record_when = Some(expr);
+        self
+    }
+
+    pub fn group(mut self, key: Expr, record_when: Option<Expr>) -> Self {
+        self.rule.group = Some(GroupSpec { key, record_when });
+        self
+    }
+
+    pub fn on_error(mut self, on_error: OnError) -> Self {
+        self.rule.on_error = on_error;
+        self
+    }
+
+    pub fn mapping(mut self, mapping: MappingBuilder) -> Self {
+        self.rule.mappings.push(mapping.mapping);
+        self
+    }
+
+    /// Validates the rule; errors are the ones `validate_rule_file` reports.
+    pub fn build(self) -> Result<RuleFile, Vec<RuleError>> {
+        validate_rule_file(&self.rule)?;
+        Ok(self.rule)
+    }
+
+    fn json_input(&mut self) -> &mut JsonInput {
+        self.rule.input.json.get_or_insert(JsonInput {
+            records_path: None,
+            max_depth: None,
+        })
+    }
+
+    fn csv_input(&mut self) -> &mut CsvInput {
+        self.rule.input.csv.get_or_insert(CsvInput {
+            has_header: true,
+            delimiter: ",".to_string(),
+            columns: None,
+        })
+    }
+
+    fn output(&mut self) -> &mut OutputSpec {
+        self.rule.output.get_or_insert(OutputSpec {
+            name: None,
+            format: OutputFormat::Json,
+            columns: None,
+        })
+    }
+}
+
+/// A mapping with exactly one of `source`, `value` or `expr`.
+///
+/// Start with [`MappingBuilder::target`] and pick the value with `source`, `value` or `expr`.
+#[derive(Debug, Clone)]
+pub struct MappingBuilder {
+    mapping: Mapping,
+}
+
+/// A mapping target still waiting for its value; see [`MappingBuilder`].
+#[derive(Debug, Clone)]
+pub struct MappingTarget {
+    target: String,
+}
+
+impl MappingBuilder {
+    pub fn target(target: impl Into<String>) -> MappingTarget {
+        MappingTarget {
+            target: target.into(),
+        }
+    }
+
+    pub fn when(mut self, when: Expr) -> Self {
+        self.mapping.when = Some(when);
+        self
+    }
+
+    /// The mapping `type`, e.g. `int` or `decimal(2)`.
+    pub fn value_type(mut self, value_type: impl Into<String>) -> Self {
+        self.mapping.value_type = Some(value_type.into());
+        self
+    }
+
+    pub fn required(mut self, required: bool) -> Self {
+        self.mapping.required = required;
+        self
+    }
+
+    pub fn default(mut self, default: Expr) -> Self {
+        self.mapping.default = Some(default);
+        self
+    }
+
+    pub fn treat_empty_as_missing(mut self, treat: bool) -> Self {
+        self.mapping.treat_empty_as_missing = treat;
+        self
+    }
+}
+
+impl MappingTarget {
+    /// Reads the value from a path such as `input.id` or `context.rates.usd`; a
+    /// `Vec<String>` tries each path in order, as `coalesce` does.
+    pub fn source(self, source: impl Into<MappingSource>) -> MappingBuilder {
+        self.with(|mapping| mapping.source = Some(source.into()))
+    }
+
+    pub fn value(self, value: JsonValue) -> MappingBuilder {
+        self.with(|mapping| mapping.value = Some(value))
+    }
+
+    pub fn expr(self, expr: Expr) -> MappingBuilder {
+        self.with(|mapping| mapping.expr = Some(expr))
+    }
+
+    fn with(self, set: impl FnOnce(&mut Mapping)) -> MappingBuilder {
+        let mut mapping = Mapping {
+            target: self.target,
+            source: None,
+            value: None,
+            expr: None,
+            when: None,
+            value_type: None,
+            required: false,
+            default: None,
+            treat_empty_as_missing: false,
+        };
+        set(&mut mapping);
+        MappingBuilder { mapping }
+    }
+}
+
+impl Expr {
+    /// `{ ref: path }`
+    pub fn reference(path: impl Into<String>) -> Self {
+        Expr::Ref(ExprRef {
+            ref_path: path.into(),
+        })
+    }
+
+    /// `{ op: name, args: [...] }`
+    pub fn op(name: impl Into<String>, args: Vec<Expr>) -> Self {
+        Expr::Op(ExprOp {
+            op: name.into(),
+            args,
+        })
+    }
+
+    /// `{ chain: [...] }`
+    pub fn chain(steps: Vec<Expr>) -> Self {
+        Expr::Chain(ExprChain { chain: steps })
+    }
+
+    pub fn literal(value: impl Into<JsonValue>) -> Self {
+        Expr::Literal(value.into())
+    }
+}
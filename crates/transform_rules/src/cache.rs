@@ -1,5 +1,8 @@
 use std::collections::{HashMap, VecDeque};
 use std::hash::Hash;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+
+use crate::model::RuleFile;
 
 pub struct LruCache<K, V> {
     map: HashMap<K, V>,
@@ -39,16 +42,25 @@ where
             return;
         }
 
-        if self.map.len() >= self.capacity {
-            if let Some(evicted) = self.order.pop_front() {
-                self.map.remove(&evicted);
-            }
+        if self.map.len() >= self.capacity
+            && let Some(evicted) = self.order.pop_front()
+        {
+            self.map.remove(&evicted);
         }
 
         self.order.push_back(key.clone());
         self.map.insert(key, value);
     }
 
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.order.clear();
+    }
+
     fn touch(&mut self, key: &K) {
         if let Some(pos) = self.order.iter().position(|k| k == key) {
             self.order.remove(pos);
@@ -56,3 +68,108 @@ where
         self.order.push_back(key.clone());
     }
 }
+
+pub(crate) const RULE_CACHE_CAPACITY: usize = 128;
+
+/// Counters of the parsed-rule cache behind `parse_rule_file`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RuleCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+// Rules are keyed by a hash of the YAML plus its length, so lookups never copy the text. The
+// hash is not collision resistant, so each entry keeps its YAML and a hit must match it too.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct RuleKey {
+    hash: u64,
+    len: usize,
+}
+
+impl RuleKey {
+    pub(crate) fn new(yaml: &str) -> Self {
+        Self {
+            hash: fnv1a(yaml.as_bytes()),
+            len: yaml.len(),
+        }
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(PRIME)
+    })
+}
+
+#[derive(Clone)]
+struct CachedRule {
+    yaml: Arc<str>,
+    rule: RuleFile,
+}
+
+pub(crate) struct RuleCache {
+    entries: LruCache<RuleKey, CachedRule>,
+    hits: u64,
+    misses: u64,
+}
+
+impl RuleCache {
+    pub(crate) fn get(&mut self, key: &RuleKey, yaml: &str) -> Option<RuleFile> {
+        let rule = self
+            .entries
+            .get_cloned(key)
+            .filter(|cached| &*cached.yaml == yaml)
+            .map(|cached| cached.rule);
+        match rule {
+            Some(_) => self.hits += 1,
+            None => self.misses += 1,
+        }
+        rule
+    }
+
+    // A colliding YAML replaces the entry under its key.
+    pub(crate) fn insert(&mut self, key: RuleKey, yaml: &str, rule: RuleFile) {
+        self.entries.insert(
+            key,
+            CachedRule {
+                yaml: yaml.into(),
+                rule,
+            },
+        );
+    }
+}
+
+pub(crate) fn rule_cache() -> MutexGuard<'static, RuleCache> {
+    static RULE_CACHE: OnceLock<Mutex<RuleCache>> = OnceLock::new();
+    RULE_CACHE
+        .get_or_init(|| {
+            Mutex::new(RuleCache {
+                entries: LruCache::new(RULE_CACHE_CAPACITY),
+                hits: 0,
+                misses: 0,
+            })
+        })
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+}
+
+/// Hit/miss counts since start-up (or the last `clear_rule_cache`) and the current size.
+pub fn rule_cache_stats() -> RuleCacheStats {
+    let cache = rule_cache();
+    RuleCacheStats {
+        hits: cache.hits,
+        misses: cache.misses,
+        entries: cache.entries.len(),
+    }
+}
+
+/// Drops every cached rule and resets the counters.
+pub fn clear_rule_cache() {
+    let mut cache = rule_cache();
+    cache.entries.clear();
+    cache.hits = 0;
+    cache.misses = 0;
+}
@@ -0,0 +1,353 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_yaml::{Mapping as YamlMap, Value as YamlValue};
+
+use crate::error::{ErrorCode, RuleError};
+use crate::model::RuleFile;
+
+// Top-level sections merged key by key; every other key is replaced as a whole.
+const MERGED_SECTIONS: &[&str] = &["input", "output", "mapping_defaults"];
+
+/// The files a rule was loaded from, and which of them declared each part of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleOrigins {
+    /// Every file that contributed, parents first; the loaded file is last.
+    pub files: Vec<RuleSource>,
+    // (YAML path in the composed rule, file index, YAML path in that file)
+    owners: Vec<(String, usize, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleSource {
+    pub path: PathBuf,
+    pub yaml: String,
+}
+
+impl RuleOrigins {
+    /// Whether the rule came from more than one file.
+    pub fn is_composed(&self) -> bool {
+        self.files.len() > 1
+    }
+
+    /// Index of the file that declared `yaml_path`, and the same node's path inside that file.
+    pub fn resolve(&self, yaml_path: &str) -> Option<(usize, String)> {
+        let (prefix, file, own_path) = self
+            .owners
+            .iter()
+            .filter(|(prefix, _, _)| is_path_prefix(prefix, yaml_path))
+            .max_by_key(|(prefix, _, _)| prefix.len())?;
+        Some((*file, format!("{}{}", own_path, &yaml_path[prefix.len()..])))
+    }
+}
+
+fn is_path_prefix(prefix: &str, path: &str) -> bool {
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('.') || rest.starts_with('['),
+        None => false,
+    }
+}
+
+/// Reads a rule file, resolving `extends` relative to the file that declares it.
+///
+/// Parents are merged in order and the file itself last: mappings replace earlier ones with
+/// the same `target` and are otherwise appended, `input`, `output` and `mapping_defaults` are
+/// merged key by key, and any other key replaces the earlier value.
+#[allow(clippy::result_large_err)]
+pub fn load_rule_file(path: &Path) -> Result<RuleFile, RuleError> {
+    let mut loader = Loader::default();
+    let composed = loader.load(path)?;
+    let (value, owners) = composed.into_value();
+    let mut rule: RuleFile = serde_yaml::from_value(value).map_err(|err| {
+        let message = format!("failed to parse rules: {}: {}", path.display(), err);
+        RuleError::new(ErrorCode::RulesParseFailed, message).with_file(path.display().to_string())
+    })?;
+    rule.origins = Some(RuleOrigins {
+        files: loader.files,
+        owners,
+    });
+    Ok(rule)
+}
+
+#[derive(Default)]
+struct Loader {
+    files: Vec<RuleSource>,
+    // (canonical, as given) paths of the files being loaded, outermost first.
+    stack: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Loader {
+    #[allow(clippy::result_large_err)]
+    fn load(&mut self, path: &Path) -> Result<Composed, RuleError> {
+        let file_error = |code, message: String| {
+            RuleError::new(code, message).with_file(path.display().to_string())
+        };
+        let yaml = fs::read_to_string(path).map_err(|err| {
+            file_error(
+                ErrorCode::RulesReadFailed,
+                format!("failed to read rules: {}: {}", path.display(), err),
+            )
+        })?;
+        let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if self.stack.iter().any(|(seen, _)| *seen == canonical) {
+            let chain: Vec<_> = self
+                .stack
+                .iter()
+                .map(|(_, shown)| shown.display().to_string())
+                .chain([path.display().to_string()])
+                .collect();
+            return Err(file_error(
+                ErrorCode::ExtendsCycle,
+                format!("extends cycle: {}", chain.join(" -> ")),
+            ));
+        }
+        let value: YamlValue = serde_yaml::from_str(&yaml).map_err(|err| {
+            file_error(
+                ErrorCode::RulesParseFailed,
+                format!("failed to parse rules: {}: {}", path.display(), err),
+            )
+        })?;
+        let YamlValue::Mapping(mut map) = value else {
+            return Err(file_error(
+                ErrorCode::RulesParseFailed,
+                format!("failed to parse rules: {}: expected a mapping", path.display()),
+            ));
+        };
+        let parents = match map.remove("extends") {
+            None => Vec::new(),
+            Some(YamlValue::String(parent)) => vec![parent],
+            Some(YamlValue::Sequence(items)) => items
+                .into_iter()
+                .map(|item| match item {
+                    YamlValue::String(parent) => Ok(parent),
+                    _ => Err(()),
+                })
+                .collect::<Result<_, _>>()
+                .map_err(|_| {
+                    file_error(ErrorCode::InvalidExtends, extends_message(path))
+                })?,
+            Some(_) => return Err(file_error(ErrorCode::InvalidExtends, extends_message(path))),
+        };
+
+        self.stack.push((canonical, path.to_path_buf()));
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut composed = Composed::default();
+        for parent in parents {
+            let parent = self.load(&dir.join(parent))?;
+            composed.merge(parent);
+        }
+        self.stack.pop();
+
+        let index = self.files.len();
+        self.files.push(RuleSource {
+            path: path.to_path_buf(),
+            yaml,
+        });
+        composed.merge(Composed::from_file(map, index));
+        Ok(composed)
+    }
+}
+
+fn extends_message(path: &Path) -> String {
+    format!(
+        "extends must be a path or a list of paths in {}",
+        path.display()
+    )
+}
+
+// A mapping and where it was declared.
+struct Entry {
+    value: YamlValue,
+    file: usize,
+    path: String,
+}
+
+impl Entry {
+    fn target(&self) -> Option<&str> {
+        self.value.get("target")?.as_str()
+    }
+}
+
+#[derive(Default)]
+struct Composed {
+    top: YamlMap,
+    // (YAML path, file index) of the file that last wrote each top-level node.
+    top_owners: Vec<(String, usize)>,
+    mappings: Option<Vec<Entry>>,
+    mapping_groups: Option<Vec<(String, Vec<Entry>)>>,
+}
+
+impl Composed {
+    fn from_file(mut map: YamlMap, file: usize) -> Self {
+        let entries = |items: Vec<YamlValue>, base: &str| -> Vec<Entry> {
+            items
+                .into_iter()
+                .enumerate()
+                .map(|(index, value)| Entry {
+                    value,
+                    file,
+                    path: format!("{}[{}]", base, index),
+                })
+                .collect()
+        };
+        let mappings = match map.remove("mappings") {
+            Some(YamlValue::Sequence(items)) => Some(entries(items, "mappings")),
+            Some(other) => {
+                map.insert("mappings".into(), other);
+                None
+            }
+            None => None,
+        };
+        let mapping_groups = match map.remove("mapping_groups") {
+            Some(YamlValue::Mapping(groups))
+                if groups
+                    .iter()
+                    .all(|(name, items)| name.is_string() && items.is_sequence()) =>
+            {
+                Some(
+                    groups
+                        .into_iter()
+                        .filter_map(|(name, items)| match (name, items) {
+                            (YamlValue::String(name), YamlValue::Sequence(items)) => {
+                                let base = format!("mapping_groups.{}", name);
+                                Some((name, entries(items, &base)))
+                            }
+                            _ => None,
+                        })
+                        .collect(),
+                )
+            }
+            Some(other) => {
+                map.insert("mapping_groups".into(), other);
+                None
+            }
+            None => None,
+        };
+        let top_owners = map
+            .keys()
+            .filter_map(|key| key.as_str())
+            .map(|key| (key.to_string(), file))
+            .collect();
+        Self {
+            top: map,
+            top_owners,
+            mappings,
+            mapping_groups,
+        }
+    }
+
+    fn merge(&mut self, child: Composed) {
+        for (key, value) in child.top {
+            let name = key.as_str().unwrap_or_default().to_string();
+            let file = child
+                .top_owners
+                .iter()
+                .find(|(path, _)| *path == name)
+                .map_or(0, |(_, file)| *file);
+            if MERGED_SECTIONS.contains(&name.as_str())
+                && let Some(existing) = self.top.get_mut(&key)
+            {
+                merge_section(existing, value, &name, file, &mut self.top_owners);
+                continue;
+            }
+            self.top_owners.retain(|(path, _)| !is_path_prefix(&name, path));
+            self.top_owners.push((name, file));
+            self.top.insert(key, value);
+        }
+        // Child owners below the top level come from its own section merges.
+        for (path, file) in child.top_owners {
+            if path.contains('.') {
+                self.top_owners.retain(|(owned, _)| !is_path_prefix(&path, owned));
+                self.top_owners.push((path, file));
+            }
+        }
+
+        if let Some(entries) = child.mappings {
+            merge_entries(self.mappings.get_or_insert_with(Vec::new), entries);
+        }
+        if let Some(groups) = child.mapping_groups {
+            let merged = self.mapping_groups.get_or_insert_with(Vec::new);
+            for (name, entries) in groups {
+                match merged.iter_mut().find(|(existing, _)| *existing == name) {
+                    Some((_, existing)) => merge_entries(existing, entries),
+                    None => merged.push((name, entries)),
+                }
+            }
+        }
+    }
+
+    fn into_value(self) -> (YamlValue, Vec<(String, usize, String)>) {
+        let mut map = self.top;
+        let mut owners: Vec<_> = self
+            .top_owners
+            .into_iter()
+            .map(|(path, file)| (path.clone(), file, path))
+            .collect();
+        if let Some(entries) = self.mappings {
+            let mut items = Vec::with_capacity(entries.len());
+            for (index, entry) in entries.into_iter().enumerate() {
+                owners.push((format!("mappings[{}]", index), entry.file, entry.path));
+                items.push(entry.value);
+            }
+            map.insert("mappings".into(), YamlValue::Sequence(items));
+        }
+        if let Some(groups) = self.mapping_groups {
+            let mut group_map = YamlMap::new();
+            for (name, entries) in groups {
+                let mut items = Vec::with_capacity(entries.len());
+                for (index, entry) in entries.into_iter().enumerate() {
+                    let path = format!("mapping_groups.{}[{}]", name, index);
+                    owners.push((path, entry.file, entry.path));
+                    items.push(entry.value);
+                }
+                group_map.insert(name.into(), YamlValue::Sequence(items));
+            }
+            map.insert("mapping_groups".into(), YamlValue::Mapping(group_map));
+        }
+        (YamlValue::Mapping(map), owners)
+    }
+}
+
+fn merge_section(
+    existing: &mut YamlValue,
+    incoming: YamlValue,
+    path: &str,
+    file: usize,
+    owners: &mut Vec<(String, usize)>,
+) {
+    match (existing, incoming) {
+        (YamlValue::Mapping(existing), YamlValue::Mapping(incoming)) => {
+            for (key, value) in incoming {
+                let Some(name) = key.as_str() else {
+                    existing.insert(key, value);
+                    continue;
+                };
+                let child_path = format!("{}.{}", path, name);
+                match existing.get_mut(&key) {
+                    Some(current) => merge_section(current, value, &child_path, file, owners),
+                    None => {
+                        owners.push((child_path, file));
+                        existing.insert(key, value);
+                    }
+                }
+            }
+        }
+        (existing, incoming) => {
+            owners.retain(|(owned, _)| !is_path_prefix(path, owned));
+            owners.push((path.to_string(), file));
+            *existing = incoming;
+        }
+    }
+}
+
+fn merge_entries(existing: &mut Vec<Entry>, incoming: Vec<Entry>) {
+    for entry in incoming {
+        let replaced = entry
+            .target()
+            .and_then(|target| existing.iter().position(|item| item.target() == Some(target)));
+        match replaced {
+            Some(index) => existing[index] = entry,
+            None => existing.push(entry),
+        }
+    }
+}
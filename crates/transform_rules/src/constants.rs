@@ -0,0 +1,39 @@
+use std::cell::RefCell;
+
+use serde_json::Value as JsonValue;
+
+use crate::path::{get_path, PathToken};
+
+thread_local! {
+    static ACTIVE_CONSTANTS: RefCell<Option<JsonValue>> = const { RefCell::new(None) };
+}
+
+/// Installs a stream's evaluated `constants` (an object keyed by name) for the duration of
+/// each record, like `IndexScope` does for lookup indexes.
+pub(crate) struct ConstScope<'a> {
+    slot: &'a mut JsonValue,
+}
+
+impl<'a> ConstScope<'a> {
+    pub(crate) fn enter(slot: &'a mut JsonValue) -> Self {
+        let constants = std::mem::take(slot);
+        ACTIVE_CONSTANTS.with(|cell| *cell.borrow_mut() = Some(constants));
+        Self { slot }
+    }
+}
+
+impl Drop for ConstScope<'_> {
+    fn drop(&mut self) {
+        if let Some(constants) = ACTIVE_CONSTANTS.with(|cell| cell.borrow_mut().take()) {
+            *self.slot = constants;
+        }
+    }
+}
+
+/// The value at `const.<tokens>`, or `None` when it is missing or no stream is running.
+pub(crate) fn lookup(tokens: &[PathToken]) -> Option<JsonValue> {
+    ACTIVE_CONSTANTS.with(|cell| {
+        let cell = cell.borrow();
+        get_path(cell.as_ref()?, tokens).cloned()
+    })
+}
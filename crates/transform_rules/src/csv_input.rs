@@ -0,0 +1,131 @@
+use std::io::Read;
+
+use csv::ReaderBuilder;
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+/// Parses CSV text with a header row into one JSON object per row.
+///
+/// Blank header names become `column_<n>`. Cells are trimmed; empty cells become `null`, and
+/// `true`/`false` and numbers are converted, everything else stays a string.
+pub fn parse_csv_records(text: &str) -> Result<Vec<JsonValue>, String> {
+    let mut records = Vec::new();
+    read_csv_records(text.as_bytes(), |record| {
+        records.push(record);
+        Ok(())
+    })?;
+    Ok(records)
+}
+
+// `parse_csv_records` one row at a time; `each` may stop the read with an error.
+pub(crate) fn read_csv_records<R: Read>(
+    reader: R,
+    mut each: impl FnMut(JsonValue) -> Result<(), String>,
+) -> Result<(), String> {
+    let mut reader = ReaderBuilder::new().has_headers(true).from_reader(reader);
+    let headers = reader
+        .headers()
+        .map_err(|err| err.to_string())?
+        .iter()
+        .enumerate()
+        .map(|(index, name)| {
+            let trimmed = name.trim();
+            if trimmed.is_empty() {
+                format!("column_{}", index + 1)
+            } else {
+                trimmed.to_string()
+            }
+        })
+        .collect::<Vec<_>>();
+
+    for result in reader.records() {
+        let record = result.map_err(|err| err.to_string())?;
+        let mut obj = JsonMap::new();
+        for (index, value) in record.iter().enumerate() {
+            if let Some(key) = headers.get(index) {
+                obj.insert(key.clone(), csv_cell_to_value(value));
+            }
+        }
+        each(JsonValue::Object(obj))?;
+    }
+    Ok(())
+}
+
+/// The `type` of an `input.csv.columns` entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CsvColumnType {
+    String,
+    Int,
+    Float,
+    Bool,
+    Auto,
+}
+
+pub(crate) const CSV_COLUMN_TYPES_MESSAGE: &str =
+    "column type must be one of string|int|float|bool|auto";
+
+impl CsvColumnType {
+    pub(crate) fn parse(name: &str) -> Option<Self> {
+        match name {
+            "string" => Some(Self::String),
+            "int" => Some(Self::Int),
+            "float" => Some(Self::Float),
+            "bool" => Some(Self::Bool),
+            "auto" => Some(Self::Auto),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::String => "string",
+            Self::Int => "int",
+            Self::Float => "float",
+            Self::Bool => "bool",
+            Self::Auto => "auto",
+        }
+    }
+
+    // `None` when the cell does not parse as the type; blank cells of a typed column are null.
+    pub(crate) fn convert(self, cell: &str) -> Option<JsonValue> {
+        let trimmed = cell.trim();
+        match self {
+            Self::String => Some(JsonValue::String(cell.to_string())),
+            Self::Auto => Some(csv_cell_to_value(cell)),
+            _ if trimmed.is_empty() => Some(JsonValue::Null),
+            Self::Int => trimmed.parse::<i64>().ok().map(|n| JsonValue::Number(n.into())),
+            Self::Float => trimmed
+                .parse::<f64>()
+                .ok()
+                .and_then(serde_json::Number::from_f64)
+                .map(JsonValue::Number),
+            Self::Bool => match trimmed.to_ascii_lowercase().as_str() {
+                "true" => Some(JsonValue::Bool(true)),
+                "false" => Some(JsonValue::Bool(false)),
+                _ => None,
+            },
+        }
+    }
+}
+
+fn csv_cell_to_value(value: &str) -> JsonValue {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return JsonValue::Null;
+    }
+    let lower = trimmed.to_ascii_lowercase();
+    if lower == "true" {
+        return JsonValue::Bool(true);
+    }
+    if lower == "false" {
+        return JsonValue::Bool(false);
+    }
+    if let Ok(number) = trimmed.parse::<i64>() {
+        return JsonValue::Number(number.into());
+    }
+    if let Ok(number) = trimmed.parse::<f64>()
+        && let Some(number) = serde_json::Number::from_f64(number)
+    {
+        return JsonValue::Number(number);
+    }
+    JsonValue::String(trimmed.to_string())
+}
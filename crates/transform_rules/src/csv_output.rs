@@ -0,0 +1,73 @@
+use csv::WriterBuilder;
+use serde_json::Value as JsonValue;
+
+use crate::error::{TransformError, TransformErrorCode, TransformErrorKind};
+
+/// Serializes output records as CSV with a header row.
+///
+/// Without `columns`, the header is the union of top-level keys in first-seen order. Missing
+/// keys and `null` become empty cells; nested objects and arrays are written as JSON text.
+pub fn records_to_csv(
+    records: &[JsonValue],
+    columns: Option<&[String]>,
+) -> Result<String, TransformError> {
+    let mut objects = Vec::with_capacity(records.len());
+    for (index, record) in records.iter().enumerate() {
+        match record {
+            JsonValue::Object(map) => objects.push(map),
+            _ => {
+                return Err(TransformError::new(
+                    TransformErrorKind::InvalidTarget,
+                    "csv output records must be objects",
+                )
+                .with_code(TransformErrorCode::CsvOutput)
+                .with_record_index(index));
+            }
+        }
+    }
+
+    let columns: Vec<&str> = match columns {
+        Some(columns) => columns.iter().map(String::as_str).collect(),
+        None => {
+            let mut columns: Vec<&str> = Vec::new();
+            for map in &objects {
+                for key in map.keys() {
+                    if !columns.contains(&key.as_str()) {
+                        columns.push(key);
+                    }
+                }
+            }
+            columns
+        }
+    };
+
+    let mut writer = WriterBuilder::new().from_writer(Vec::new());
+    writer.write_record(&columns).map_err(csv_write_error)?;
+    for map in objects {
+        let row = columns
+            .iter()
+            .map(|column| map.get(*column).map(csv_cell).unwrap_or_default());
+        writer.write_record(row).map_err(csv_write_error)?;
+    }
+    let bytes = writer
+        .into_inner()
+        .map_err(|err| csv_write_error(err.into_error().into()))?;
+    // Every cell comes from a `String`, so the bytes are valid UTF-8.
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn csv_cell(value: &JsonValue) -> String {
+    match value {
+        JsonValue::Null => String::new(),
+        JsonValue::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn csv_write_error(err: csv::Error) -> TransformError {
+    TransformError::new(
+        TransformErrorKind::InvalidTarget,
+        format!("failed to write csv output: {}", err),
+    )
+    .with_code(TransformErrorCode::CsvOutput)
+}
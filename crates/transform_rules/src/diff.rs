@@ -0,0 +1,392 @@
+use std::collections::{HashMap, HashSet};
+
+use serde_json::{json, Value as JsonValue};
+
+use crate::path::{format_path, PathToken};
+
+/// How [`diff_json`] pairs up the elements of two arrays.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ArrayMatch {
+    /// Element `i` of the expected array is compared with element `i` of the actual one.
+    #[default]
+    Ordered,
+    /// Elements are paired by the value of this field. Arrays whose elements are not all
+    /// objects with a distinct scalar value for it are compared in order.
+    Key(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffKind {
+    /// Both sides have the path with different values.
+    Changed,
+    /// Only the expected value has the path.
+    Missing,
+    /// Only the actual value has the path.
+    Extra,
+}
+
+impl DiffKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiffKind::Changed => "changed",
+            DiffKind::Missing => "missing",
+            DiffKind::Extra => "extra",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffEntry {
+    pub kind: DiffKind,
+    /// Path of the differing value, e.g. `[0].customer.name`; with [`ArrayMatch::Key`] paired
+    /// elements are written as `[id=1]`.
+    pub path: String,
+    pub expected: Option<JsonValue>,
+    pub actual: Option<JsonValue>,
+}
+
+impl DiffEntry {
+    pub fn to_json(&self) -> JsonValue {
+        let mut value = json!({
+            "type": "diff",
+            "kind": self.kind.as_str(),
+            "path": self.path,
+        });
+        if let Some(expected) = &self.expected {
+            value["expected"] = expected.clone();
+        }
+        if let Some(actual) = &self.actual {
+            value["actual"] = actual.clone();
+        }
+        value
+    }
+}
+
+/// Compares `actual` against `expected` and lists every difference.
+///
+/// Entries follow the expected value, with keys and elements only `actual` has after them.
+/// Numbers compare by value, so `1` and `1.0` are equal.
+pub fn diff_json(expected: &JsonValue, actual: &JsonValue, arrays: &ArrayMatch) -> Vec<DiffEntry> {
+    let mut entries = Vec::new();
+    diff_value(expected, actual, arrays, &mut String::new(), &mut entries);
+    entries
+}
+
+fn diff_value(
+    expected: &JsonValue,
+    actual: &JsonValue,
+    arrays: &ArrayMatch,
+    path: &mut String,
+    entries: &mut Vec<DiffEntry>,
+) {
+    match (expected, actual) {
+        (JsonValue::Object(expected), JsonValue::Object(actual)) => {
+            for (key, expected_value) in expected {
+                with_segment(path, &PathToken::Key(key.clone()), |path| {
+                    match actual.get(key) {
+                        Some(actual_value) => {
+                            diff_value(expected_value, actual_value, arrays, path, entries)
+                        }
+                        None => entries.push(missing(path, expected_value)),
+                    }
+                });
+            }
+            for (key, actual_value) in actual {
+                if !expected.contains_key(key) {
+                    with_segment(path, &PathToken::Key(key.clone()), |path| {
+                        entries.push(extra(path, actual_value))
+                    });
+                }
+            }
+        }
+        (JsonValue::Array(expected), JsonValue::Array(actual)) => {
+            let keyed = match arrays {
+                ArrayMatch::Key(key) => key_array(expected, key)
+                    .zip(key_array(actual, key))
+                    .map(|keys| (key, keys)),
+                ArrayMatch::Ordered => None,
+            };
+            match keyed {
+                Some((key, (expected_keys, actual_keys))) => diff_keyed(
+                    (expected, &expected_keys),
+                    (actual, &actual_keys),
+                    key,
+                    arrays,
+                    path,
+                    entries,
+                ),
+                None => diff_ordered(expected, actual, arrays, path, entries),
+            }
+        }
+        (JsonValue::Number(expected_number), JsonValue::Number(actual_number))
+            if expected_number == actual_number
+                || expected_number.as_f64() == actual_number.as_f64() => {}
+        _ if expected == actual => {}
+        _ => entries.push(DiffEntry {
+            kind: DiffKind::Changed,
+            path: path.clone(),
+            expected: Some(expected.clone()),
+            actual: Some(actual.clone()),
+        }),
+    }
+}
+
+fn diff_ordered(
+    expected: &[JsonValue],
+    actual: &[JsonValue],
+    arrays: &ArrayMatch,
+    path: &mut String,
+    entries: &mut Vec<DiffEntry>,
+) {
+    for index in 0..expected.len().max(actual.len()) {
+        with_segment(path, &PathToken::Index(index), |path| {
+            match (expected.get(index), actual.get(index)) {
+                (Some(expected), Some(actual)) => diff_value(expected, actual, arrays, path, entries),
+                (Some(expected), None) => entries.push(missing(path, expected)),
+                (None, Some(actual)) => entries.push(extra(path, actual)),
+                (None, None) => {}
+            }
+        });
+    }
+}
+
+fn diff_keyed(
+    (expected, expected_keys): (&[JsonValue], &[String]),
+    (actual, actual_keys): (&[JsonValue], &[String]),
+    key: &str,
+    arrays: &ArrayMatch,
+    path: &mut String,
+    entries: &mut Vec<DiffEntry>,
+) {
+    let actual_index: HashMap<&str, usize> = actual_keys
+        .iter()
+        .enumerate()
+        .map(|(index, value)| (value.as_str(), index))
+        .collect();
+    for (expected_value, value) in expected.iter().zip(expected_keys) {
+        let len = path.len();
+        path.push_str(&format!("[{}={}]", key, value));
+        match actual_index.get(value.as_str()) {
+            Some(&index) => diff_value(expected_value, &actual[index], arrays, path, entries),
+            None => entries.push(missing(path, expected_value)),
+        }
+        path.truncate(len);
+    }
+    let expected_set: HashSet<&str> = expected_keys.iter().map(String::as_str).collect();
+    for (actual_value, value) in actual.iter().zip(actual_keys) {
+        if !expected_set.contains(value.as_str()) {
+            let len = path.len();
+            path.push_str(&format!("[{}={}]", key, value));
+            entries.push(extra(path, actual_value));
+            path.truncate(len);
+        }
+    }
+}
+
+// The key of every element as JSON text, or `None` when the array cannot be keyed.
+fn key_array(items: &[JsonValue], key: &str) -> Option<Vec<String>> {
+    let mut seen = HashSet::with_capacity(items.len());
+    let mut keys = Vec::with_capacity(items.len());
+    for item in items {
+        let value = item.as_object()?.get(key)?;
+        if value.is_object() || value.is_array() {
+            return None;
+        }
+        let text = value.to_string();
+        if !seen.insert(text.clone()) {
+            return None;
+        }
+        keys.push(text);
+    }
+    Some(keys)
+}
+
+fn with_segment(path: &mut String, token: &PathToken, f: impl FnOnce(&mut String)) {
+    let len = path.len();
+    let segment = format_path(std::slice::from_ref(token));
+    if len > 0 && matches!(token, PathToken::Key(_)) && !segment.starts_with('[') {
+        path.push('.');
+    }
+    path.push_str(&segment);
+    f(path);
+    path.truncate(len);
+}
+
+fn missing(path: &str, expected: &JsonValue) -> DiffEntry {
+    DiffEntry {
+        kind: DiffKind::Missing,
+        path: path.to_string(),
+        expected: Some(expected.clone()),
+        actual: None,
+    }
+}
+
+fn extra(path: &str, actual: &JsonValue) -> DiffEntry {
+    DiffEntry {
+        kind: DiffKind::Extra,
+        path: path.to_string(),
+        expected: None,
+        actual: Some(actual.clone()),
+    }
+}
+
+/// A unified diff of two texts by line, with three lines of context around each change.
+///
+/// Lines are paired by patience diff, anchored on lines found once in each text. Returns an
+/// empty string when both texts have the same lines.
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let mut ops = Vec::new();
+    diff_lines(&old_lines, &new_lines, &mut ops);
+
+    // Each line of the diff with the index it has in the old and the new text.
+    let mut rows = Vec::with_capacity(ops.len());
+    let (mut old_index, mut new_index) = (0, 0);
+    for op in ops {
+        rows.push((op, old_index, new_index));
+        match op {
+            LineOp::Equal => {
+                old_index += 1;
+                new_index += 1;
+            }
+            LineOp::Delete => old_index += 1,
+            LineOp::Insert => new_index += 1,
+        }
+    }
+    let changes: Vec<usize> = (0..rows.len())
+        .filter(|&index| rows[index].0 != LineOp::Equal)
+        .collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", old_name, new_name);
+    let mut index = 0;
+    while index < changes.len() {
+        let first = changes[index];
+        let mut last = first;
+        while changes
+            .get(index + 1)
+            .is_some_and(|&next| next - last <= 2 * DIFF_CONTEXT + 1)
+        {
+            index += 1;
+            last = changes[index];
+        }
+        index += 1;
+        let end = (last + DIFF_CONTEXT + 1).min(rows.len());
+        let hunk = &rows[first.saturating_sub(DIFF_CONTEXT)..end];
+        let old_count = hunk.iter().filter(|row| row.0 != LineOp::Insert).count();
+        let new_count = hunk.iter().filter(|row| row.0 != LineOp::Delete).count();
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(hunk[0].1, old_count),
+            hunk_range(hunk[0].2, new_count)
+        ));
+        for &(op, old_index, new_index) in hunk {
+            let (marker, line) = match op {
+                LineOp::Equal => (' ', old_lines[old_index]),
+                LineOp::Delete => ('-', old_lines[old_index]),
+                LineOp::Insert => ('+', new_lines[new_index]),
+            };
+            out.push(marker);
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+const DIFF_CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineOp {
+    Equal,
+    Delete,
+    Insert,
+}
+
+fn diff_lines(old: &[&str], new: &[&str], ops: &mut Vec<LineOp>) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    ops.extend(std::iter::repeat_n(LineOp::Equal, prefix));
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+    let anchors = unique_anchors(old_middle, new_middle);
+    if anchors.is_empty() {
+        ops.extend(std::iter::repeat_n(LineOp::Delete, old_middle.len()));
+        ops.extend(std::iter::repeat_n(LineOp::Insert, new_middle.len()));
+    } else {
+        let (mut old_start, mut new_start) = (0, 0);
+        for (old_index, new_index) in anchors {
+            diff_lines(&old_middle[old_start..old_index], &new_middle[new_start..new_index], ops);
+            ops.push(LineOp::Equal);
+            old_start = old_index + 1;
+            new_start = new_index + 1;
+        }
+        diff_lines(&old_middle[old_start..], &new_middle[new_start..], ops);
+    }
+    ops.extend(std::iter::repeat_n(LineOp::Equal, suffix));
+}
+
+// Lines found once in each text, as the longest list of pairs in the order of both.
+fn unique_anchors(old: &[&str], new: &[&str]) -> Vec<(usize, usize)> {
+    // Per line: its count in `old`, its count in `new` and its index in `old`.
+    let mut counts: HashMap<&str, (usize, usize, usize)> = HashMap::new();
+    for (index, line) in old.iter().enumerate() {
+        let entry = counts.entry(line).or_default();
+        entry.0 += 1;
+        entry.2 = index;
+    }
+    for line in new {
+        if let Some(entry) = counts.get_mut(line) {
+            entry.1 += 1;
+        }
+    }
+    let pairs: Vec<(usize, usize)> = new
+        .iter()
+        .enumerate()
+        .filter_map(|(new_index, line)| match counts.get(line) {
+            Some(&(1, 1, old_index)) => Some((old_index, new_index)),
+            _ => None,
+        })
+        .collect();
+
+    // Longest increasing run of old indexes, by patience sorting.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous = vec![None; pairs.len()];
+    for (index, &(old_index, _)) in pairs.iter().enumerate() {
+        let pile = tails.partition_point(|&tail| pairs[tail].0 < old_index);
+        if pile > 0 {
+            previous[index] = Some(tails[pile - 1]);
+        }
+        if pile == tails.len() {
+            tails.push(index);
+        } else {
+            tails[pile] = index;
+        }
+    }
+    let mut anchors = Vec::with_capacity(tails.len());
+    let mut next = tails.last().copied();
+    while let Some(index) = next {
+        anchors.push(pairs[index]);
+        next = previous[index];
+    }
+    anchors.reverse();
+    anchors
+}
+
+// A range of no lines names the line before it, as `diff -u` does.
+fn hunk_range(start: usize, count: usize) -> String {
+    let start = if count == 0 { start } else { start + 1 };
+    if count == 1 {
+        start.to_string()
+    } else {
+        format!("{},{}", start, count)
+    }
+}
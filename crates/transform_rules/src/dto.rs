@@ -1,9 +1,10 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use serde_json::Value as JsonValue;
 
-use crate::model::{Expr, RuleFile};
-use crate::path::{parse_path, PathToken};
+use crate::model::{Expr, MappingSource, RuleFile};
+use crate::path::{parse_array_target, parse_path, PathToken};
+use crate::transform::{decimal_scale, input_source_tokens};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DtoLanguage {
@@ -14,6 +15,8 @@ pub enum DtoLanguage {
     Java,
     Kotlin,
     Swift,
+    /// JSON Schema (draft 2020-12) of the output records.
+    JsonSchema,
 }
 
 #[derive(Debug, Clone)]
@@ -53,6 +56,7 @@ pub fn generate_dto(
         DtoLanguage::Java => render_java(&schema, name),
         DtoLanguage::Kotlin => render_kotlin(&schema, name),
         DtoLanguage::Swift => render_swift(&schema, name),
+        DtoLanguage::JsonSchema => render_json_schema(&schema, name),
     }
 }
 
@@ -72,35 +76,40 @@ struct Field {
 enum FieldType {
     Primitive(PrimitiveType),
     Object(Box<SchemaNode>),
+    Array(Box<FieldType>),
     JsonValue,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 enum PrimitiveType {
     String,
     Int,
     Float,
     Bool,
+    Date,
+    DateTime,
+    Decimal,
 }
 
 fn build_schema(rule: &RuleFile) -> Result<SchemaNode, DtoError> {
     let mut root = SchemaNode { fields: Vec::new() };
 
     for mapping in &rule.mappings {
-        let tokens = parse_path(&mapping.target)
-            .map_err(|_| DtoError::new("target path is invalid"))?;
-        if tokens.iter().any(|token| matches!(token, PathToken::Index(_))) {
+        let (tokens, item_tokens) = match parse_array_target(&mapping.target) {
+            Ok(Some((array_tokens, item_tokens))) => (array_tokens, Some(item_tokens)),
+            Ok(None) => (parse_path(&mapping.target)
+                .map_err(|_| DtoError::new("target path is invalid"))?, None),
+            Err(_) => return Err(DtoError::new("target path is invalid")),
+        };
+        if tokens
+            .iter()
+            .chain(item_tokens.iter().flatten())
+            .any(|token| matches!(token, PathToken::Index(_)))
+        {
             return Err(DtoError::new("target path must not include indexes"));
         }
 
-        let mut keys = Vec::new();
-        for token in tokens {
-            match token {
-                PathToken::Key(key) => keys.push(key),
-                PathToken::Index(_) => {}
-            }
-        }
-
+        let keys = token_keys(tokens);
         if keys.is_empty() {
             return Err(DtoError::new("target path is invalid"));
         }
@@ -110,23 +119,101 @@ fn build_schema(rule: &RuleFile) -> Result<SchemaNode, DtoError> {
             Some("int") => FieldType::Primitive(PrimitiveType::Int),
             Some("float") => FieldType::Primitive(PrimitiveType::Float),
             Some("bool") => FieldType::Primitive(PrimitiveType::Bool),
+            Some("date") => FieldType::Primitive(PrimitiveType::Date),
+            Some("datetime") => FieldType::Primitive(PrimitiveType::DateTime),
+            Some(name) if decimal_scale(name).is_some() => {
+                FieldType::Primitive(PrimitiveType::Decimal)
+            }
             Some(_) => return Err(DtoError::new("unsupported type in mapping")),
             None => FieldType::JsonValue,
         };
-        let conditional = match &mapping.when {
-            None => false,
-            Some(Expr::Literal(JsonValue::Bool(true))) => false,
-            _ => true,
-        };
+        let conditional = !matches!(
+            &mapping.when,
+            None | Some(Expr::Literal(JsonValue::Bool(true)))
+        );
+        // With `group`, the group key and its records are always present on each output.
+        let group_field = rule.group.is_some()
+            && mapping
+                .source
+                .as_ref()
+                .and_then(MappingSource::as_single)
+                .and_then(input_source_tokens)
+                .is_some_and(|tokens| match tokens.as_slice() {
+                    [PathToken::Key(key)] => key == "key" || key == "records",
+                    _ => false,
+                });
         let optional = conditional
-            || !(mapping.required || mapping.value.is_some() || mapping.default.is_some());
-
-        insert_field(&mut root, &keys, field_type, optional)?;
+            || !(mapping.required
+                || mapping.value.is_some()
+                || mapping.default.is_some()
+                || group_field);
+
+        match item_tokens {
+            None => insert_field(&mut root, &keys, field_type, optional)?,
+            Some(item_tokens) if item_tokens.is_empty() => insert_field(
+                &mut root,
+                &keys,
+                FieldType::Array(Box::new(field_type)),
+                optional,
+            )?,
+            Some(item_tokens) => {
+                let item = array_item_node(&mut root, &keys)?;
+                insert_field(item, &token_keys(item_tokens), field_type, optional)?;
+            }
+        }
     }
 
     Ok(root)
 }
 
+fn token_keys(tokens: Vec<PathToken>) -> Vec<String> {
+    tokens
+        .into_iter()
+        .filter_map(|token| match token {
+            PathToken::Key(key) => Some(key),
+            PathToken::Index(_) => None,
+        })
+        .collect()
+}
+
+// Object node for the items of the array at `keys`, created on first use.
+fn array_item_node<'a>(
+    node: &'a mut SchemaNode,
+    keys: &[String],
+) -> Result<&'a mut SchemaNode, DtoError> {
+    let Some((key, rest)) = keys.split_first() else {
+        return Err(DtoError::new("target path is invalid"));
+    };
+
+    let position = match node.fields.iter().position(|field| field.key == *key) {
+        Some(position) => position,
+        None => {
+            let field_type = if rest.is_empty() {
+                FieldType::Array(Box::new(FieldType::Object(Box::new(SchemaNode {
+                    fields: Vec::new(),
+                }))))
+            } else {
+                FieldType::Object(Box::new(SchemaNode { fields: Vec::new() }))
+            };
+            node.fields.push(Field {
+                key: key.clone(),
+                field_type,
+                optional: false,
+            });
+            node.fields.len() - 1
+        }
+    };
+
+    match (&mut node.fields[position].field_type, rest.is_empty()) {
+        (FieldType::Object(child), false) => array_item_node(child, rest),
+        (FieldType::Array(item), true) => match item.as_mut() {
+            FieldType::Object(child) => Ok(child),
+            _ => Err(DtoError::new("target conflicts with non-object")),
+        },
+        _ => Err(DtoError::new("target conflicts with non-object")),
+    }
+}
+
 fn insert_field(
     node: &mut SchemaNode,
     keys: &[String],
@@ -170,36 +257,61 @@ fn insert_field(
 }
 
 fn node_has_required(node: &SchemaNode) -> bool {
-    for field in &node.fields {
-        match &field.field_type {
-            FieldType::Object(child) => {
-                if node_has_required(child) {
-                    return true;
-                }
-            }
-            _ => {
-                if !field.optional {
-                    return true;
-                }
-            }
-        }
+    node.fields.iter().any(|field| !field_optional(field))
+}
+
+// Objects, and arrays of objects, are present whenever one of their fields is required.
+fn field_optional(field: &Field) -> bool {
+    match &field.field_type {
+        FieldType::Object(child) => !node_has_required(child),
+        FieldType::Array(item) => match item.as_ref() {
+            FieldType::Object(child) => !node_has_required(child),
+            _ => field.optional,
+        },
+        _ => field.optional,
+    }
+}
+
+fn item_node(field_type: &FieldType) -> Option<&SchemaNode> {
+    match field_type {
+        FieldType::Object(child) => Some(child),
+        FieldType::Array(item) => item_node(item),
+        _ => None,
     }
-    false
 }
 
 fn node_uses_json(node: &SchemaNode) -> bool {
-    for field in &node.fields {
-        match &field.field_type {
-            FieldType::JsonValue => return true,
-            FieldType::Object(child) => {
-                if node_uses_json(child) {
-                    return true;
-                }
-            }
-            _ => {}
-        }
+    node.fields.iter().any(|field| type_uses_json(&field.field_type))
+}
+
+fn type_uses_json(field_type: &FieldType) -> bool {
+    match field_type {
+        FieldType::JsonValue => true,
+        FieldType::Object(child) => node_uses_json(child),
+        FieldType::Array(item) => type_uses_json(item),
+        FieldType::Primitive(_) => false,
+    }
+}
+
+fn node_uses_primitive(node: &SchemaNode, primitive: PrimitiveType) -> bool {
+    node.fields.iter().any(|field| type_uses_primitive(&field.field_type, primitive))
+}
+
+fn type_uses_primitive(field_type: &FieldType, primitive: PrimitiveType) -> bool {
+    match field_type {
+        FieldType::Primitive(value) => *value == primitive,
+        FieldType::Object(child) => node_uses_primitive(child, primitive),
+        FieldType::Array(item) => type_uses_primitive(item, primitive),
+        FieldType::JsonValue => false,
     }
-    false
+}
+
+fn node_uses_array(node: &SchemaNode) -> bool {
+    node.fields.iter().any(|field| match &field.field_type {
+        FieldType::Array(_) => true,
+        FieldType::Object(child) => node_uses_array(child),
+        _ => false,
+    })
 }
 
 struct TypeDef<'a> {
@@ -208,44 +320,44 @@ struct TypeDef<'a> {
     path: Vec<String>,
 }
 
+// Type names join the base name with the path segments (`Record` + `Customer` + `Address`).
+// Paths whose names collide are taken in key order: the first keeps the name and the rest get
+// `2`, `3`, ..., so names do not depend on mapping order.
 struct NameRegistry {
-    base: String,
-    used: HashSet<String>,
     names: HashMap<Vec<String>, String>,
 }
 
 impl NameRegistry {
-    fn new(base: &str) -> Self {
-        Self {
-            base: base.to_string(),
-            used: HashSet::new(),
-            names: HashMap::new(),
-        }
-    }
-
-    fn type_name_for_path(&mut self, path: &[String]) -> String {
-        if let Some(name) = self.names.get(path) {
-            return name.clone();
-        }
-
-        let mut name = self.base.clone();
-        for segment in path {
-            name.push_str(&pascal_case(&words_from_key(segment)));
-        }
-
-        if name.is_empty() {
-            name = "Record".to_string();
+    fn new(base: &str, schema: &SchemaNode) -> Self {
+        let mut paths = vec![Vec::new()];
+        collect_object_paths(schema, &mut Vec::new(), &mut paths);
+        paths.sort();
+
+        let mut used = HashSet::new();
+        let mut names = HashMap::new();
+        let mut colliding = Vec::new();
+        for path in paths {
+            let mut name = base.to_string();
+            for segment in &path {
+                name.push_str(&pascal_case(&words_from_key(segment)));
+            }
+            if name.is_empty() {
+                name = "Record".to_string();
+            }
+            if used.insert(name.clone()) {
+                names.insert(path, name);
+            } else {
+                colliding.push((path, name));
+            }
         }
-
-        let mut unique = name.clone();
-        let mut suffix = 2;
-        while self.used.contains(&unique) {
-            unique = format!("{}_{}", name, suffix);
-            suffix += 1;
+        for (path, name) in colliding {
+            let mut suffix = 2;
+            while !used.insert(format!("{}{}", name, suffix)) {
+                suffix += 1;
+            }
+            names.insert(path, format!("{}{}", name, suffix));
         }
-        self.used.insert(unique.clone());
-        self.names.insert(path.to_vec(), unique.clone());
-        unique
+        Self { names }
     }
 
     fn get(&self, path: &[String]) -> Option<&String> {
@@ -253,22 +365,35 @@ impl NameRegistry {
     }
 }
 
+fn collect_object_paths(node: &SchemaNode, path: &mut Vec<String>, out: &mut Vec<Vec<String>>) {
+    for field in &node.fields {
+        if let Some(child) = item_node(&field.field_type) {
+            path.push(field.key.clone());
+            out.push(path.clone());
+            collect_object_paths(child, path, out);
+            path.pop();
+        }
+    }
+}
+
 fn collect_types<'a>(
     node: &'a SchemaNode,
     path: Vec<String>,
-    registry: &mut NameRegistry,
+    registry: &NameRegistry,
     out: &mut Vec<TypeDef<'a>>,
 ) {
     for field in &node.fields {
-        if let FieldType::Object(child) = &field.field_type {
+        if let Some(child) = item_node(&field.field_type) {
             let mut child_path = path.clone();
             child_path.push(field.key.clone());
-            registry.type_name_for_path(&child_path);
             collect_types(child, child_path, registry, out);
         }
     }
 
-    let name = registry.type_name_for_path(&path);
+    let name = registry
+        .get(&path)
+        .cloned()
+        .unwrap_or_else(|| "Record".to_string());
     out.push(TypeDef { name, node, path });
 }
 
@@ -283,6 +408,7 @@ fn field_identifier(
             lower_camel(&words_from_key(key))
         }
         DtoLanguage::Go => pascal_case(&words_from_key(key)),
+        DtoLanguage::JsonSchema => key.to_string(),
     };
 
     let mut ident = if base.is_empty() {
@@ -397,6 +523,7 @@ fn is_reserved(lang: DtoLanguage, ident: &str) -> bool {
         DtoLanguage::Java => is_reserved_java(ident),
         DtoLanguage::Kotlin => is_reserved_kotlin(ident),
         DtoLanguage::Swift => is_reserved_swift(ident),
+        DtoLanguage::JsonSchema => false,
     }
 }
 
@@ -714,11 +841,27 @@ fn is_reserved_swift(value: &str) -> bool {
 }
 
 fn render_rust(schema: &SchemaNode, name: &str) -> Result<String, DtoError> {
-    let mut registry = NameRegistry::new(name);
+    let registry = NameRegistry::new(name, schema);
     let mut defs = Vec::new();
-    collect_types(schema, Vec::new(), &mut registry, &mut defs);
+    collect_types(schema, Vec::new(), &registry, &mut defs);
+
+    let mut chrono_names = Vec::new();
+    if node_uses_primitive(schema, PrimitiveType::DateTime) {
+        chrono_names.push("DateTime");
+    }
+    if node_uses_primitive(schema, PrimitiveType::Date) {
+        chrono_names.push("NaiveDate");
+    }
+    if node_uses_primitive(schema, PrimitiveType::DateTime) {
+        chrono_names.push("Utc");
+    }
 
     let mut out = String::new();
+    match chrono_names.as_slice() {
+        [] => {}
+        [name] => out.push_str(&format!("use chrono::{};\n", name)),
+        names => out.push_str(&format!("use chrono::{{{}}};\n", names.join(", "))),
+    }
     out.push_str("use serde::{Deserialize, Serialize};\n");
     if node_uses_json(schema) {
         out.push_str("use serde_json::Value;\n");
@@ -733,10 +876,7 @@ fn render_rust(schema: &SchemaNode, name: &str) -> Result<String, DtoError> {
         for field in &def.node.fields {
             let ident = field_identifier(DtoLanguage::Rust, &field.key, &mut used);
             let rename = ident != field.key;
-            let optional = match &field.field_type {
-                FieldType::Object(child) => !node_has_required(child),
-                _ => field.optional,
-            };
+            let optional = field_optional(field);
             let field_type = rust_type_for_field(field, &def.path, &registry);
 
             let mut attrs = Vec::new();
@@ -767,27 +907,33 @@ fn render_rust(schema: &SchemaNode, name: &str) -> Result<String, DtoError> {
 }
 
 fn rust_type_for_field(field: &Field, parent_path: &[String], registry: &NameRegistry) -> String {
-    match &field.field_type {
+    let mut path = parent_path.to_vec();
+    path.push(field.key.clone());
+    rust_type(&field.field_type, &path, registry)
+}
+
+fn rust_type(field_type: &FieldType, path: &[String], registry: &NameRegistry) -> String {
+    match field_type {
         FieldType::Primitive(PrimitiveType::String) => "String".to_string(),
         FieldType::Primitive(PrimitiveType::Int) => "i64".to_string(),
         FieldType::Primitive(PrimitiveType::Float) => "f64".to_string(),
         FieldType::Primitive(PrimitiveType::Bool) => "bool".to_string(),
+        FieldType::Primitive(PrimitiveType::Date) => "NaiveDate".to_string(),
+        FieldType::Primitive(PrimitiveType::DateTime) => "DateTime<Utc>".to_string(),
+        FieldType::Primitive(PrimitiveType::Decimal) => "String".to_string(),
         FieldType::JsonValue => "Value".to_string(),
-        FieldType::Object(_) => {
-            let mut path = parent_path.to_vec();
-            path.push(field.key.clone());
-            registry
-                .get(&path)
-                .cloned()
-                .unwrap_or_else(|| "Record".to_string())
-        }
+        FieldType::Object(_) => registry
+            .get(path)
+            .cloned()
+            .unwrap_or_else(|| "Record".to_string()),
+        FieldType::Array(item) => format!("Vec<{}>", rust_type(item, path, registry)),
     }
 }
 
 fn render_typescript(schema: &SchemaNode, name: &str) -> Result<String, DtoError> {
-    let mut registry = NameRegistry::new(name);
+    let registry = NameRegistry::new(name, schema);
     let mut defs = Vec::new();
-    collect_types(schema, Vec::new(), &mut registry, &mut defs);
+    collect_types(schema, Vec::new(), &registry, &mut defs);
 
     let mut out = String::new();
     for def in defs {
@@ -796,10 +942,7 @@ fn render_typescript(schema: &SchemaNode, name: &str) -> Result<String, DtoError
         for field in &def.node.fields {
             let ident = field_identifier(DtoLanguage::TypeScript, &field.key, &mut used);
             let rename = ident != field.key;
-            let optional = match &field.field_type {
-                FieldType::Object(child) => !node_has_required(child),
-                _ => field.optional,
-            };
+            let optional = field_optional(field);
             let field_type = typescript_type_for_field(field, &def.path, &registry);
             if rename {
                 out.push_str(&format!("  /** json: \"{}\" */\n", field.key));
@@ -818,29 +961,36 @@ fn typescript_type_for_field(
     parent_path: &[String],
     registry: &NameRegistry,
 ) -> String {
-    match &field.field_type {
+    let mut path = parent_path.to_vec();
+    path.push(field.key.clone());
+    typescript_type(&field.field_type, &path, registry)
+}
+
+fn typescript_type(field_type: &FieldType, path: &[String], registry: &NameRegistry) -> String {
+    match field_type {
         FieldType::Primitive(PrimitiveType::String) => "string".to_string(),
         FieldType::Primitive(PrimitiveType::Int) => "number".to_string(),
         FieldType::Primitive(PrimitiveType::Float) => "number".to_string(),
         FieldType::Primitive(PrimitiveType::Bool) => "boolean".to_string(),
+        FieldType::Primitive(PrimitiveType::Date) => "string".to_string(),
+        FieldType::Primitive(PrimitiveType::DateTime) => "string".to_string(),
+        FieldType::Primitive(PrimitiveType::Decimal) => "string".to_string(),
         FieldType::JsonValue => "unknown".to_string(),
-        FieldType::Object(_) => {
-            let mut path = parent_path.to_vec();
-            path.push(field.key.clone());
-            registry
-                .get(&path)
-                .cloned()
-                .unwrap_or_else(|| "Record".to_string())
-        }
+        FieldType::Object(_) => registry
+            .get(path)
+            .cloned()
+            .unwrap_or_else(|| "Record".to_string()),
+        FieldType::Array(item) => format!("{}[]", typescript_type(item, path, registry)),
     }
 }
 
 fn render_python(schema: &SchemaNode, name: &str) -> Result<String, DtoError> {
-    let mut registry = NameRegistry::new(name);
+    let registry = NameRegistry::new(name, schema);
     let mut defs = Vec::new();
-    collect_types(schema, Vec::new(), &mut registry, &mut defs);
+    collect_types(schema, Vec::new(), &registry, &mut defs);
 
     let uses_json = node_uses_json(schema);
+    let uses_list = node_uses_array(schema);
     let uses_optional = schema_has_optional(schema);
     let uses_rename = schema_has_rename(schema, DtoLanguage::Python);
 
@@ -851,11 +1001,28 @@ fn render_python(schema: &SchemaNode, name: &str) -> Result<String, DtoError> {
     }
     out.push('\n');
 
-    if uses_json || uses_optional {
+    let mut datetime_names = Vec::new();
+    if node_uses_primitive(schema, PrimitiveType::Date) {
+        datetime_names.push("date");
+    }
+    if node_uses_primitive(schema, PrimitiveType::DateTime) {
+        datetime_names.push("datetime");
+    }
+    if !datetime_names.is_empty() {
+        out.push_str(&format!("from datetime import {}\n", datetime_names.join(", ")));
+    }
+    if node_uses_primitive(schema, PrimitiveType::Decimal) {
+        out.push_str("from decimal import Decimal\n");
+    }
+
+    if uses_json || uses_list || uses_optional {
         let mut parts = Vec::new();
         if uses_optional {
             parts.push("Optional");
         }
+        if uses_list {
+            parts.push("List");
+        }
         if uses_json {
             parts.push("Any");
         }
@@ -884,10 +1051,7 @@ fn render_python(schema: &SchemaNode, name: &str) -> Result<String, DtoError> {
         for field in &def.node.fields {
             let ident = field_identifier(DtoLanguage::Python, &field.key, &mut used);
             let rename = ident != field.key;
-            let optional = match &field.field_type {
-                FieldType::Object(child) => !node_has_required(child),
-                _ => field.optional,
-            };
+            let optional = field_optional(field);
             let field_type = python_type_for_field(field, &def.path, &registry, optional);
             fields.push(RenderField {
                 key: field.key.clone(),
@@ -937,21 +1101,9 @@ fn python_type_for_field(
     registry: &NameRegistry,
     optional: bool,
 ) -> String {
-    let base = match &field.field_type {
-        FieldType::Primitive(PrimitiveType::String) => "str".to_string(),
-        FieldType::Primitive(PrimitiveType::Int) => "int".to_string(),
-        FieldType::Primitive(PrimitiveType::Float) => "float".to_string(),
-        FieldType::Primitive(PrimitiveType::Bool) => "bool".to_string(),
-        FieldType::JsonValue => "Any".to_string(),
-        FieldType::Object(_) => {
-            let mut path = parent_path.to_vec();
-            path.push(field.key.clone());
-            registry
-                .get(&path)
-                .cloned()
-                .unwrap_or_else(|| "Record".to_string())
-        }
-    };
+    let mut path = parent_path.to_vec();
+    path.push(field.key.clone());
+    let base = python_type(&field.field_type, &path, registry);
 
     if optional {
         format!("Optional[{}]", base)
@@ -960,17 +1112,49 @@ fn python_type_for_field(
     }
 }
 
+fn python_type(field_type: &FieldType, path: &[String], registry: &NameRegistry) -> String {
+    match field_type {
+        FieldType::Primitive(PrimitiveType::String) => "str".to_string(),
+        FieldType::Primitive(PrimitiveType::Int) => "int".to_string(),
+        FieldType::Primitive(PrimitiveType::Float) => "float".to_string(),
+        FieldType::Primitive(PrimitiveType::Bool) => "bool".to_string(),
+        FieldType::Primitive(PrimitiveType::Date) => "date".to_string(),
+        FieldType::Primitive(PrimitiveType::DateTime) => "datetime".to_string(),
+        FieldType::Primitive(PrimitiveType::Decimal) => "Decimal".to_string(),
+        FieldType::JsonValue => "Any".to_string(),
+        FieldType::Object(_) => registry
+            .get(path)
+            .cloned()
+            .unwrap_or_else(|| "Record".to_string()),
+        FieldType::Array(item) => format!("List[{}]", python_type(item, path, registry)),
+    }
+}
+
 fn render_go(schema: &SchemaNode, name: &str) -> Result<String, DtoError> {
-    let mut registry = NameRegistry::new(name);
+    let registry = NameRegistry::new(name, schema);
     let mut defs = Vec::new();
-    collect_types(schema, Vec::new(), &mut registry, &mut defs);
+    collect_types(schema, Vec::new(), &registry, &mut defs);
 
-    let uses_json = node_uses_json(schema);
+    let mut imports = Vec::new();
+    if node_uses_json(schema) {
+        imports.push("encoding/json");
+    }
+    if node_uses_primitive(schema, PrimitiveType::DateTime) {
+        imports.push("time");
+    }
 
     let mut out = String::new();
     out.push_str("package dto\n\n");
-    if uses_json {
-        out.push_str("import \"encoding/json\"\n\n");
+    match imports.as_slice() {
+        [] => {}
+        [import] => out.push_str(&format!("import \"{}\"\n\n", import)),
+        imports => {
+            out.push_str("import (\n");
+            for import in imports {
+                out.push_str(&format!("    \"{}\"\n", import));
+            }
+            out.push_str(")\n\n");
+        }
     }
 
     for def in defs {
@@ -978,10 +1162,7 @@ fn render_go(schema: &SchemaNode, name: &str) -> Result<String, DtoError> {
         let mut used = HashMap::new();
         for field in &def.node.fields {
             let ident = field_identifier(DtoLanguage::Go, &field.key, &mut used);
-            let optional = match &field.field_type {
-                FieldType::Object(child) => !node_has_required(child),
-                _ => field.optional,
-            };
+            let optional = field_optional(field);
             let field_type = go_type_for_field(field, &def.path, &registry, optional);
             let tag = if optional {
                 format!("`json:\"{},omitempty\"`", field.key)
@@ -1002,49 +1183,66 @@ fn go_type_for_field(
     registry: &NameRegistry,
     optional: bool,
 ) -> String {
-    let base = match &field.field_type {
+    let mut path = parent_path.to_vec();
+    path.push(field.key.clone());
+    let base = go_type(&field.field_type, &path, registry);
+
+    // Slices are already nil-able, so optional arrays stay `[]T`.
+    if optional && !matches!(field.field_type, FieldType::Array(_)) {
+        format!("*{}", base)
+    } else {
+        base
+    }
+}
+
+fn go_type(field_type: &FieldType, path: &[String], registry: &NameRegistry) -> String {
+    match field_type {
         FieldType::Primitive(PrimitiveType::String) => "string".to_string(),
         FieldType::Primitive(PrimitiveType::Int) => "int64".to_string(),
         FieldType::Primitive(PrimitiveType::Float) => "float64".to_string(),
         FieldType::Primitive(PrimitiveType::Bool) => "bool".to_string(),
+        FieldType::Primitive(PrimitiveType::Date) => "string".to_string(),
+        FieldType::Primitive(PrimitiveType::DateTime) => "time.Time".to_string(),
+        FieldType::Primitive(PrimitiveType::Decimal) => "string".to_string(),
         FieldType::JsonValue => "json.RawMessage".to_string(),
-        FieldType::Object(_) => {
-            let mut path = parent_path.to_vec();
-            path.push(field.key.clone());
-            registry
-                .get(&path)
-                .cloned()
-                .unwrap_or_else(|| "Record".to_string())
-        }
-    };
-
-    if optional {
-        format!("*{}", base)
-    } else {
-        base
+        FieldType::Object(_) => registry
+            .get(path)
+            .cloned()
+            .unwrap_or_else(|| "Record".to_string()),
+        FieldType::Array(item) => format!("[]{}", go_type(item, path, registry)),
     }
 }
 
 fn render_java(schema: &SchemaNode, name: &str) -> Result<String, DtoError> {
-    let mut registry = NameRegistry::new(name);
+    let registry = NameRegistry::new(name, schema);
     let mut defs = Vec::new();
-    collect_types(schema, Vec::new(), &mut registry, &mut defs);
+    collect_types(schema, Vec::new(), &registry, &mut defs);
 
     let uses_json = node_uses_json(schema);
+    let uses_list = node_uses_array(schema);
     let uses_optional = schema_has_optional(schema);
     let uses_rename = schema_has_rename(schema, DtoLanguage::Java);
 
-    let mut out = String::new();
+    let mut imports = Vec::new();
     if uses_rename {
-        out.push_str("import com.fasterxml.jackson.annotation.JsonProperty;\n");
+        imports.push("com.fasterxml.jackson.annotation.JsonProperty");
     }
     if uses_json {
-        out.push_str("import com.fasterxml.jackson.databind.JsonNode;\n");
+        imports.push("com.fasterxml.jackson.databind.JsonNode");
+    }
+    imports.extend(java_time_imports(schema));
+    if uses_list {
+        imports.push("java.util.List");
     }
     if uses_optional {
-        out.push_str("import java.util.Optional;\n");
+        imports.push("java.util.Optional");
+    }
+
+    let mut out = String::new();
+    for import in &imports {
+        out.push_str(&format!("import {};\n", import));
     }
-    if uses_rename || uses_json || uses_optional {
+    if !imports.is_empty() {
         out.push('\n');
     }
 
@@ -1055,10 +1253,7 @@ fn render_java(schema: &SchemaNode, name: &str) -> Result<String, DtoError> {
         for field in &def.node.fields {
             let ident = field_identifier(DtoLanguage::Java, &field.key, &mut used);
             let rename = ident != field.key;
-            let optional = match &field.field_type {
-                FieldType::Object(child) => !node_has_required(child),
-                _ => field.optional,
-            };
+            let optional = field_optional(field);
             let field_type = java_type_for_field(field, &def.path, &registry, optional);
 
             if rename {
@@ -1078,21 +1273,9 @@ fn java_type_for_field(
     registry: &NameRegistry,
     optional: bool,
 ) -> String {
-    let base = match &field.field_type {
-        FieldType::Primitive(PrimitiveType::String) => "String".to_string(),
-        FieldType::Primitive(PrimitiveType::Int) => "Long".to_string(),
-        FieldType::Primitive(PrimitiveType::Float) => "Double".to_string(),
-        FieldType::Primitive(PrimitiveType::Bool) => "Boolean".to_string(),
-        FieldType::JsonValue => "JsonNode".to_string(),
-        FieldType::Object(_) => {
-            let mut path = parent_path.to_vec();
-            path.push(field.key.clone());
-            registry
-                .get(&path)
-                .cloned()
-                .unwrap_or_else(|| "Record".to_string())
-        }
-    };
+    let mut path = parent_path.to_vec();
+    path.push(field.key.clone());
+    let base = java_type(&field.field_type, &path, registry);
 
     if optional {
         format!("Optional<{}>", base)
@@ -1101,22 +1284,61 @@ fn java_type_for_field(
     }
 }
 
+// Shared by Java and Kotlin, in import order.
+fn java_time_imports(schema: &SchemaNode) -> Vec<&'static str> {
+    let mut imports = Vec::new();
+    if node_uses_primitive(schema, PrimitiveType::Decimal) {
+        imports.push("java.math.BigDecimal");
+    }
+    if node_uses_primitive(schema, PrimitiveType::Date) {
+        imports.push("java.time.LocalDate");
+    }
+    if node_uses_primitive(schema, PrimitiveType::DateTime) {
+        imports.push("java.time.OffsetDateTime");
+    }
+    imports
+}
+
+fn java_type(field_type: &FieldType, path: &[String], registry: &NameRegistry) -> String {
+    match field_type {
+        FieldType::Primitive(PrimitiveType::String) => "String".to_string(),
+        FieldType::Primitive(PrimitiveType::Int) => "Long".to_string(),
+        FieldType::Primitive(PrimitiveType::Float) => "Double".to_string(),
+        FieldType::Primitive(PrimitiveType::Bool) => "Boolean".to_string(),
+        FieldType::Primitive(PrimitiveType::Date) => "LocalDate".to_string(),
+        FieldType::Primitive(PrimitiveType::DateTime) => "OffsetDateTime".to_string(),
+        FieldType::Primitive(PrimitiveType::Decimal) => "BigDecimal".to_string(),
+        FieldType::JsonValue => "JsonNode".to_string(),
+        FieldType::Object(_) => registry
+            .get(path)
+            .cloned()
+            .unwrap_or_else(|| "Record".to_string()),
+        FieldType::Array(item) => format!("List<{}>", java_type(item, path, registry)),
+    }
+}
+
 fn render_kotlin(schema: &SchemaNode, name: &str) -> Result<String, DtoError> {
-    let mut registry = NameRegistry::new(name);
+    let registry = NameRegistry::new(name, schema);
     let mut defs = Vec::new();
-    collect_types(schema, Vec::new(), &mut registry, &mut defs);
+    collect_types(schema, Vec::new(), &registry, &mut defs);
 
     let uses_json = node_uses_json(schema);
     let uses_rename = schema_has_rename(schema, DtoLanguage::Kotlin);
 
-    let mut out = String::new();
+    let mut imports = Vec::new();
     if uses_rename {
-        out.push_str("import com.fasterxml.jackson.annotation.JsonProperty\n");
+        imports.push("com.fasterxml.jackson.annotation.JsonProperty");
     }
     if uses_json {
-        out.push_str("import com.fasterxml.jackson.databind.JsonNode\n");
+        imports.push("com.fasterxml.jackson.databind.JsonNode");
     }
-    if uses_rename || uses_json {
+    imports.extend(java_time_imports(schema));
+
+    let mut out = String::new();
+    for import in &imports {
+        out.push_str(&format!("import {}\n", import));
+    }
+    if !imports.is_empty() {
         out.push('\n');
     }
 
@@ -1126,10 +1348,7 @@ fn render_kotlin(schema: &SchemaNode, name: &str) -> Result<String, DtoError> {
         for (index, field) in def.node.fields.iter().enumerate() {
             let ident = field_identifier(DtoLanguage::Kotlin, &field.key, &mut used);
             let rename = ident != field.key;
-            let optional = match &field.field_type {
-                FieldType::Object(child) => !node_has_required(child),
-                _ => field.optional,
-            };
+            let optional = field_optional(field);
             let field_type = kotlin_type_for_field(field, &def.path, &registry, optional);
 
             if rename {
@@ -1154,21 +1373,9 @@ fn kotlin_type_for_field(
     registry: &NameRegistry,
     optional: bool,
 ) -> String {
-    let base = match &field.field_type {
-        FieldType::Primitive(PrimitiveType::String) => "String".to_string(),
-        FieldType::Primitive(PrimitiveType::Int) => "Long".to_string(),
-        FieldType::Primitive(PrimitiveType::Float) => "Double".to_string(),
-        FieldType::Primitive(PrimitiveType::Bool) => "Boolean".to_string(),
-        FieldType::JsonValue => "JsonNode".to_string(),
-        FieldType::Object(_) => {
-            let mut path = parent_path.to_vec();
-            path.push(field.key.clone());
-            registry
-                .get(&path)
-                .cloned()
-                .unwrap_or_else(|| "Record".to_string())
-        }
-    };
+    let mut path = parent_path.to_vec();
+    path.push(field.key.clone());
+    let base = kotlin_type(&field.field_type, &path, registry);
 
     if optional {
         format!("{}?", base)
@@ -1177,10 +1384,28 @@ fn kotlin_type_for_field(
     }
 }
 
+fn kotlin_type(field_type: &FieldType, path: &[String], registry: &NameRegistry) -> String {
+    match field_type {
+        FieldType::Primitive(PrimitiveType::String) => "String".to_string(),
+        FieldType::Primitive(PrimitiveType::Int) => "Long".to_string(),
+        FieldType::Primitive(PrimitiveType::Float) => "Double".to_string(),
+        FieldType::Primitive(PrimitiveType::Bool) => "Boolean".to_string(),
+        FieldType::Primitive(PrimitiveType::Date) => "LocalDate".to_string(),
+        FieldType::Primitive(PrimitiveType::DateTime) => "OffsetDateTime".to_string(),
+        FieldType::Primitive(PrimitiveType::Decimal) => "BigDecimal".to_string(),
+        FieldType::JsonValue => "JsonNode".to_string(),
+        FieldType::Object(_) => registry
+            .get(path)
+            .cloned()
+            .unwrap_or_else(|| "Record".to_string()),
+        FieldType::Array(item) => format!("List<{}>", kotlin_type(item, path, registry)),
+    }
+}
+
 fn render_swift(schema: &SchemaNode, name: &str) -> Result<String, DtoError> {
-    let mut registry = NameRegistry::new(name);
+    let registry = NameRegistry::new(name, schema);
     let mut defs = Vec::new();
-    collect_types(schema, Vec::new(), &mut registry, &mut defs);
+    collect_types(schema, Vec::new(), &registry, &mut defs);
 
     let uses_json = node_uses_json(schema);
 
@@ -1189,22 +1414,24 @@ fn render_swift(schema: &SchemaNode, name: &str) -> Result<String, DtoError> {
         out.push_str(&format!("struct {}: Codable {{\n", def.name));
         let mut used = HashMap::new();
         let mut coding_keys = Vec::new();
+        let mut renamed = false;
         for field in &def.node.fields {
             let ident = field_identifier(DtoLanguage::Swift, &field.key, &mut used);
             let rename = ident != field.key;
-            let optional = match &field.field_type {
-                FieldType::Object(child) => !node_has_required(child),
-                _ => field.optional,
-            };
+            let optional = field_optional(field);
             let field_type = swift_type_for_field(field, &def.path, &registry, optional);
 
             out.push_str(&format!("    let {}: {}\n", ident, field_type));
+            // CodingKeys must list every property once any key is renamed.
             if rename {
+                renamed = true;
                 coding_keys.push(format!("        case {} = \"{}\"", ident, field.key));
+            } else {
+                coding_keys.push(format!("        case {}", ident));
             }
         }
 
-        if !coding_keys.is_empty() {
+        if renamed {
             out.push('\n');
             out.push_str("    enum CodingKeys: String, CodingKey {\n");
             for line in coding_keys {
@@ -1229,45 +1456,116 @@ fn swift_type_for_field(
     registry: &NameRegistry,
     optional: bool,
 ) -> String {
-    let base = match &field.field_type {
+    let mut path = parent_path.to_vec();
+    path.push(field.key.clone());
+    let base = swift_type(&field.field_type, &path, registry);
+
+    if optional {
+        format!("{}?", base)
+    } else {
+        base
+    }
+}
+
+fn swift_type(field_type: &FieldType, path: &[String], registry: &NameRegistry) -> String {
+    match field_type {
         FieldType::Primitive(PrimitiveType::String) => "String".to_string(),
         FieldType::Primitive(PrimitiveType::Int) => "Int".to_string(),
         FieldType::Primitive(PrimitiveType::Float) => "Double".to_string(),
         FieldType::Primitive(PrimitiveType::Bool) => "Bool".to_string(),
+        FieldType::Primitive(PrimitiveType::Date) => "String".to_string(),
+        FieldType::Primitive(PrimitiveType::DateTime) => "String".to_string(),
+        FieldType::Primitive(PrimitiveType::Decimal) => "String".to_string(),
         FieldType::JsonValue => "JSONValue".to_string(),
-        FieldType::Object(_) => {
-            let mut path = parent_path.to_vec();
-            path.push(field.key.clone());
-            registry
-                .get(&path)
-                .cloned()
-                .unwrap_or_else(|| "Record".to_string())
+        FieldType::Object(_) => registry
+            .get(path)
+            .cloned()
+            .unwrap_or_else(|| "Record".to_string()),
+        FieldType::Array(item) => format!("[{}]", swift_type(item, path, registry)),
+    }
+}
+
+const JSON_SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+fn render_json_schema(schema: &SchemaNode, name: &str) -> Result<String, DtoError> {
+    let mut root = json_schema_object(schema);
+    root.insert("$schema", JsonValue::from(JSON_SCHEMA_DIALECT));
+    root.insert("title", JsonValue::from(name));
+    serde_json::to_string_pretty(&sorted_object(root))
+        .map_err(|err| DtoError::new(format!("failed to render json schema: {}", err)))
+}
+
+// Keys are inserted in sorted order so the output is the same with or without serde_json's
+// `preserve_order` feature.
+fn sorted_object(entries: BTreeMap<&str, JsonValue>) -> JsonValue {
+    JsonValue::Object(
+        entries
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect(),
+    )
+}
+
+fn json_schema_object(node: &SchemaNode) -> BTreeMap<&'static str, JsonValue> {
+    let mut properties = BTreeMap::new();
+    let mut required = Vec::new();
+    for field in &node.fields {
+        let optional = field_optional(field);
+        properties.insert(field.key.as_str(), json_schema_type(&field.field_type, optional));
+        if !optional {
+            required.push(field.key.clone());
         }
-    };
+    }
+    required.sort();
 
-    if optional {
-        format!("{}?", base)
-    } else {
-        base
+    let mut object = BTreeMap::new();
+    object.insert("type", JsonValue::from("object"));
+    object.insert("properties", sorted_object(properties));
+    if !required.is_empty() {
+        object.insert("required", JsonValue::from(required));
     }
+    object.insert("additionalProperties", JsonValue::Bool(false));
+    object
 }
 
-fn schema_has_optional(node: &SchemaNode) -> bool {
-    for field in &node.fields {
-        match &field.field_type {
-            FieldType::Object(child) => {
-                if !node_has_required(child) || schema_has_optional(child) {
-                    return true;
-                }
-            }
-            _ => {
-                if field.optional {
-                    return true;
-                }
-            }
+// Optional values may also be `null`; so may array items, which are `null` when missing.
+fn json_schema_type(field_type: &FieldType, nullable: bool) -> JsonValue {
+    let (type_name, format) = match field_type {
+        FieldType::Primitive(PrimitiveType::String) => ("string", None),
+        FieldType::Primitive(PrimitiveType::Int) => ("integer", None),
+        FieldType::Primitive(PrimitiveType::Float) => ("number", None),
+        FieldType::Primitive(PrimitiveType::Bool) => ("boolean", None),
+        FieldType::Primitive(PrimitiveType::Date) => ("string", Some("date")),
+        FieldType::Primitive(PrimitiveType::DateTime) => ("string", Some("date-time")),
+        FieldType::Primitive(PrimitiveType::Decimal) => ("string", None),
+        FieldType::JsonValue => return sorted_object(BTreeMap::new()),
+        FieldType::Object(child) => return sorted_object(json_schema_object(child)),
+        FieldType::Array(item) => {
+            let mut array = BTreeMap::new();
+            array.insert("type", JsonValue::from("array"));
+            array.insert("items", json_schema_type(item, nullable));
+            return sorted_object(array);
         }
+    };
+    let mut schema = BTreeMap::new();
+    schema.insert(
+        "type",
+        if nullable {
+            JsonValue::from(vec![type_name, "null"])
+        } else {
+            JsonValue::from(type_name)
+        },
+    );
+    if let Some(format) = format {
+        schema.insert("format", JsonValue::from(format));
     }
-    false
+    sorted_object(schema)
+}
+
+fn schema_has_optional(node: &SchemaNode) -> bool {
+    node.fields.iter().any(|field| {
+        field_optional(field) || item_node(&field.field_type).is_some_and(schema_has_optional)
+    })
 }
 
 fn schema_has_rename(node: &SchemaNode, lang: DtoLanguage) -> bool {
@@ -1277,10 +1575,10 @@ fn schema_has_rename(node: &SchemaNode, lang: DtoLanguage) -> bool {
         if ident != field.key {
             return true;
         }
-        if let FieldType::Object(child) = &field.field_type {
-            if schema_has_rename(child, lang) {
-                return true;
-            }
+        if let Some(child) = item_node(&field.field_type)
+            && schema_has_rename(child, lang)
+        {
+            return true;
         }
     }
     false
@@ -0,0 +1,45 @@
+use std::cell::Cell;
+
+use serde_json::Value as JsonValue;
+
+use crate::error::{TransformError, TransformErrorCode, TransformErrorKind};
+use crate::path::PathToken;
+
+thread_local! {
+    static ENV_ALLOWED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Lets `env.*` refs read the process environment while a stream that opted in with
+/// `TransformStream::with_env` evaluates, like `ConstScope` does for constants.
+pub(crate) struct EnvScope {
+    previous: bool,
+}
+
+impl EnvScope {
+    pub(crate) fn enter(allowed: bool) -> Self {
+        let previous = ENV_ALLOWED.with(|cell| cell.replace(allowed));
+        Self { previous }
+    }
+}
+
+impl Drop for EnvScope {
+    fn drop(&mut self) {
+        ENV_ALLOWED.with(|cell| cell.set(self.previous));
+    }
+}
+
+/// The variable named by `env.<tokens>` as a string; `None` when it is unset, not Unicode or
+/// the path goes below it.
+pub(crate) fn lookup(tokens: &[PathToken]) -> Result<Option<JsonValue>, TransformError> {
+    if !ENV_ALLOWED.with(Cell::get) {
+        return Err(TransformError::new(
+            TransformErrorKind::ExprError,
+            "env namespace is disabled; the transform must be run with allow_env",
+        )
+        .with_code(TransformErrorCode::EnvDisabled));
+    }
+    Ok(match tokens {
+        [PathToken::Key(name)] => std::env::var(name).ok().map(JsonValue::String),
+        _ => None,
+    })
+}
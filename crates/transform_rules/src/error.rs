@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ErrorCode {
     InvalidVersion,
@@ -7,14 +9,22 @@ pub enum ErrorCode {
     MissingJsonSection,
     InvalidDelimiterLength,
     MissingCsvColumns,
+    InvalidCsvColumns,
+    InvalidMaxDepth,
+    InvalidOutputColumns,
 
     MissingTarget,
     DuplicateTarget,
+    TargetConflict,
+    MappingsAndGroupsExclusive,
+    InvalidGroupOrder,
     SourceValueExprExclusive,
     MissingMappingValue,
     InvalidWhenType,
+    AmbiguousRecordWhen,
 
     InvalidRefNamespace,
+    UnknownConstant,
     ForwardOutReference,
     UnknownOp,
     InvalidArgs,
@@ -22,6 +32,12 @@ pub enum ErrorCode {
     InvalidPath,
 
     InvalidTypeName,
+
+    RulesReadFailed,
+    RulesParseFailed,
+    InvalidExtends,
+    ExtendsCycle,
+    LimitExceeded,
 }
 
 impl ErrorCode {
@@ -34,18 +50,31 @@ impl ErrorCode {
             ErrorCode::MissingJsonSection => "MissingJsonSection",
             ErrorCode::InvalidDelimiterLength => "InvalidDelimiterLength",
             ErrorCode::MissingCsvColumns => "MissingCsvColumns",
+            ErrorCode::InvalidCsvColumns => "InvalidCsvColumns",
+            ErrorCode::InvalidMaxDepth => "InvalidMaxDepth",
+            ErrorCode::InvalidOutputColumns => "InvalidOutputColumns",
             ErrorCode::MissingTarget => "MissingTarget",
             ErrorCode::DuplicateTarget => "DuplicateTarget",
+            ErrorCode::TargetConflict => "TargetConflict",
+            ErrorCode::MappingsAndGroupsExclusive => "MappingsAndGroupsExclusive",
+            ErrorCode::InvalidGroupOrder => "InvalidGroupOrder",
             ErrorCode::SourceValueExprExclusive => "SourceValueExprExclusive",
             ErrorCode::MissingMappingValue => "MissingMappingValue",
             ErrorCode::InvalidWhenType => "InvalidWhenType",
+            ErrorCode::AmbiguousRecordWhen => "AmbiguousRecordWhen",
             ErrorCode::InvalidRefNamespace => "InvalidRefNamespace",
+            ErrorCode::UnknownConstant => "UnknownConstant",
             ErrorCode::ForwardOutReference => "ForwardOutReference",
             ErrorCode::UnknownOp => "UnknownOp",
             ErrorCode::InvalidArgs => "InvalidArgs",
             ErrorCode::InvalidExprShape => "InvalidExprShape",
             ErrorCode::InvalidPath => "InvalidPath",
             ErrorCode::InvalidTypeName => "InvalidTypeName",
+            ErrorCode::RulesReadFailed => "RulesReadFailed",
+            ErrorCode::RulesParseFailed => "RulesParseFailed",
+            ErrorCode::InvalidExtends => "InvalidExtends",
+            ErrorCode::ExtendsCycle => "ExtendsCycle",
+            ErrorCode::LimitExceeded => "LimitExceeded",
         }
     }
 }
@@ -62,6 +91,10 @@ pub struct RuleError {
     pub message: String,
     pub location: Option<YamlLocation>,
     pub path: Option<String>,
+    /// `mappings[group][i]` form of `path` when the mapping comes from `mapping_groups`.
+    pub group_path: Option<String>,
+    /// File that declared the node, for rules composed with `extends`.
+    pub file: Option<String>,
 }
 
 impl RuleError {
@@ -71,6 +104,8 @@ impl RuleError {
             message: message.into(),
             location: None,
             path: None,
+            group_path: None,
+            file: None,
         }
     }
 
@@ -79,6 +114,16 @@ impl RuleError {
         self
     }
 
+    pub fn with_group_path(mut self, group_path: impl Into<String>) -> Self {
+        self.group_path = Some(group_path.into());
+        self
+    }
+
+    pub fn with_file(mut self, file: impl Into<String>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
     pub fn with_location(mut self, line: usize, column: usize) -> Self {
         self.location = Some(YamlLocation { line, column });
         self
@@ -87,7 +132,108 @@ impl RuleError {
 
 pub type ValidationResult = Result<(), Vec<RuleError>>;
 
+/// Rules that validate but probably do not do what was meant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WarningCode {
+    DateFormatMissingInputFormat,
+    ToUnixtimeAutoParse,
+    ConditionalDuplicateTarget,
+    OverwrittenMapping,
+    RequiredWithDefault,
+    ConstantWhen,
+    SingleArgCoalesce,
+    /// Lists the `env.*` variables a rule file reads.
+    EnvReference,
+    /// An op this build does not know, reported as a warning by callers that allow them.
+    UnknownOp,
+}
+
+impl WarningCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WarningCode::DateFormatMissingInputFormat => "date_format_missing_input_format",
+            WarningCode::ToUnixtimeAutoParse => "to_unixtime_auto_parse",
+            WarningCode::ConditionalDuplicateTarget => "conditional_duplicate_target",
+            WarningCode::OverwrittenMapping => "overwritten_mapping",
+            WarningCode::RequiredWithDefault => "required_with_default",
+            WarningCode::ConstantWhen => "constant_when",
+            WarningCode::SingleArgCoalesce => "single_arg_coalesce",
+            WarningCode::EnvReference => "env_reference",
+            WarningCode::UnknownOp => "unknown_op",
+        }
+    }
+}
+
+/// A [`WarningCode`] with the same location fields as [`RuleError`].
 #[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleWarning {
+    pub code: WarningCode,
+    pub message: String,
+    pub location: Option<YamlLocation>,
+    pub path: Option<String>,
+    pub group_path: Option<String>,
+    pub file: Option<String>,
+}
+
+impl RuleWarning {
+    pub fn new(code: WarningCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            location: None,
+            path: None,
+            group_path: None,
+            file: None,
+        }
+    }
+
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+}
+
+/// Everything [`validate_rule_file_full`](crate::validate_rule_file_full) found.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub errors: Vec<RuleError>,
+    pub warnings: Vec<RuleWarning>,
+}
+
+impl ValidationReport {
+    /// Whether the rule is usable; warnings do not count.
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn into_result(self) -> ValidationResult {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    /// Turns `UnknownOp` errors into [`WarningCode::UnknownOp`] warnings, listed before the
+    /// others, so rule files using ops from a newer release still validate.
+    pub fn allow_unknown_ops(&mut self) {
+        let (unknown, errors): (Vec<_>, Vec<_>) = std::mem::take(&mut self.errors)
+            .into_iter()
+            .partition(|err| err.code == ErrorCode::UnknownOp);
+        self.errors = errors;
+        let unknown = unknown.into_iter().map(|err| RuleWarning {
+            code: WarningCode::UnknownOp,
+            message: err.message,
+            location: err.location,
+            path: err.path,
+            group_path: err.group_path,
+            file: err.file,
+        });
+        self.warnings.splice(0..0, unknown);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum TransformErrorKind {
     InvalidInput,
     InvalidRecordsPath,
@@ -96,50 +242,288 @@ pub enum TransformErrorKind {
     MissingRequired,
     TypeCastFailed,
     ExprError,
+    /// Warnings only: the transform ran, but not the way it was asked to.
+    Degraded,
+}
+
+macro_rules! transform_error_codes {
+    ($($variant:ident = $code:literal $name:literal $kind:ident: $description:literal,)*) => {
+        /// Stable identifier of a transform-time failure; [`error_catalog`] lists them all.
+        ///
+        /// A code keeps its meaning across releases while the message may be reworded.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum TransformErrorCode {
+            $($variant,)*
+        }
+
+        impl TransformErrorCode {
+            /// The code, e.g. `T1201`.
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $(TransformErrorCode::$variant => $code,)*
+                }
+            }
+
+            /// The upper-case name, e.g. `ARG_COUNT`.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(TransformErrorCode::$variant => $name,)*
+                }
+            }
+        }
+
+        const ERROR_CATALOG: &[(TransformErrorCode, TransformErrorKind, &str)] = &[
+            $((TransformErrorCode::$variant, TransformErrorKind::$kind, $description),)*
+        ];
+    };
+}
+
+// Codes are grouped by hundreds: T10 input, T11 refs, targets and required values, T12 the
+// shape of an expr, T13 arg values, T14 casts, dates and numbers, T15 degraded runs (warnings
+// only). The `x00`/`x50`/`x10` codes are what `TransformError::new` assigns before a more
+// specific one is set.
+transform_error_codes! {
+    InvalidInput = "T1000" "INVALID_INPUT" InvalidInput:
+        "The input could not be read, for a reason without a more specific code.",
+    InputConfig = "T1001" "INPUT_CONFIG" InvalidInput:
+        "The input section cannot read this input, e.g. csv.columns is missing.",
+    InputRead = "T1002" "INPUT_READ" InvalidInput:
+        "Reading the input failed, e.g. an I/O error, a CSV error or invalid UTF-8.",
+    InputParse = "T1003" "INPUT_PARSE" InvalidInput:
+        "The JSON or NDJSON input is malformed.",
+    InputTooDeep = "T1004" "INPUT_TOO_DEEP" InvalidInput:
+        "The JSON input nests deeper than input.json.max_depth.",
+    LimitExceeded = "T1005" "LIMIT_EXCEEDED" InvalidInput:
+        "The input is over a parse limit; the message starts with `<limit> exceeded:`.",
+    RecordsNotList = "T1006" "RECORDS_NOT_LIST" InvalidInput:
+        "records_path points to a value that is neither an array nor an object.",
+    NoMappingValue = "T1007" "NO_MAPPING_VALUE" InvalidInput:
+        "A mapping has none of source, value and expr.",
+    InvalidRecordsPath = "T1050" "INVALID_RECORDS_PATH" InvalidRecordsPath:
+        "records_path is unusable, for a reason without a more specific code.",
+    RecordsPathNotFound = "T1051" "RECORDS_PATH_NOT_FOUND" InvalidRecordsPath:
+        "records_path does not exist in the input.",
+    BadRecordsPath = "T1052" "BAD_RECORDS_PATH" InvalidRecordsPath:
+        "records_path is not a valid path.",
+    InvalidRef = "T1100" "INVALID_REF" InvalidRef:
+        "A reference is invalid, for a reason without a more specific code.",
+    BadRef = "T1101" "BAD_REF" InvalidRef:
+        "A source or ref has no namespace, an unknown namespace or an invalid path.",
+    InvalidTarget = "T1110" "INVALID_TARGET" InvalidTarget:
+        "A target cannot be written, for a reason without a more specific code.",
+    BadTarget = "T1111" "BAD_TARGET" InvalidTarget:
+        "A target path is invalid or has indexes, or an array target got a non-array value.",
+    TargetConflict = "T1112" "TARGET_CONFLICT" InvalidTarget:
+        "A target path runs through an earlier value that is not an object or array.",
+    CsvOutput = "T1113" "CSV_OUTPUT" InvalidTarget:
+        "The records could not be written as CSV.",
+    MissingRequired = "T1120" "MISSING_REQUIRED" MissingRequired:
+        "A required mapping has no value.",
+    RequiredNull = "T1121" "REQUIRED_NULL" MissingRequired:
+        "A required mapping evaluated to null.",
+    ExprError = "T1200" "EXPR_ERROR" ExprError:
+        "An expression failed, for a reason without a more specific code.",
+    ArgCount = "T1201" "ARG_COUNT" ExprError:
+        "An op got too few or too many args.",
+    UnknownOp = "T1202" "UNKNOWN_OP" ExprError:
+        "expr.op names no supported op.",
+    BadChain = "T1203" "BAD_CHAIN" ExprError:
+        "expr.chain is empty or a step after the first is not an op.",
+    BadOption = "T1204" "BAD_OPTION" ExprError:
+        "An options arg is not an object, or has an unknown key or an invalid value.",
+    BadLookup = "T1205" "BAD_LOOKUP" ExprError:
+        "The args of a lookup op are malformed, e.g. key_path is not a string literal.",
+    EnvDisabled = "T1206" "ENV_DISABLED" ExprError:
+        "An env ref was evaluated without allow_env.",
+    OutOfScope = "T1207" "OUT_OF_SCOPE" ExprError:
+        "An item or acc ref is used outside the ops that provide it, or names no part of it.",
+    MissingArg = "T1301" "MISSING_ARG" ExprError:
+        "An arg that must be present evaluated to missing.",
+    NullArg = "T1302" "NULL_ARG" ExprError:
+        "An arg that must not be null evaluated to null.",
+    ArgType = "T1303" "ARG_TYPE" ExprError:
+        "An arg has the wrong type, e.g. a string where a number is needed.",
+    ArgRange = "T1304" "ARG_RANGE" ExprError:
+        "An arg is out of range, e.g. a negative length or an empty delimiter.",
+    BadPath = "T1305" "BAD_PATH" ExprError:
+        "A path arg is empty or malformed, or has indexes where none are allowed.",
+    PathConflict = "T1306" "PATH_CONFLICT" ExprError:
+        "Two paths, or a path and an existing value, would write to the same place.",
+    BadRegex = "T1307" "BAD_REGEX" ExprError:
+        "A regex is invalid or matches the empty string, or a capture group does not exist.",
+    NotBoolean = "T1308" "NOT_BOOLEAN" ExprError:
+        "A condition, when or record_when did not evaluate to a boolean.",
+    AmbiguousKey = "T1309" "AMBIGUOUS_KEY" ExprError:
+        "A key cannot be flattened or unflattened without ambiguity.",
+    BadTemplate = "T1310" "BAD_TEMPLATE" ExprError:
+        "A format template is malformed, or a placeholder has no usable value.",
+    TypeCastFailed = "T1400" "TYPE_CAST_FAILED" TypeCastFailed:
+        "A cast failed, for a reason without a more specific code.",
+    CastFailed = "T1401" "CAST_FAILED" TypeCastFailed:
+        "A value could not be cast to the mapping's type.",
+    BadCastType = "T1402" "BAD_CAST_TYPE" TypeCastFailed:
+        "A mapping's type names no supported type.",
+    CsvColumnType = "T1403" "CSV_COLUMN_TYPE" TypeCastFailed:
+        "A CSV cell is not a valid value of its column's type.",
+    BadDateFormat = "T1404" "BAD_DATE_FORMAT" ExprError:
+        "A date format or input_format is invalid or empty.",
+    BadTimezone = "T1405" "BAD_TIMEZONE" ExprError:
+        "A timezone is not UTC, an offset or, where accepted, an IANA name.",
+    BadUnit = "T1406" "BAD_UNIT" ExprError:
+        "An epoch unit is neither s nor ms.",
+    InvalidDate = "T1407" "INVALID_DATE" ExprError:
+        "A date does not exist, or an epoch value is out of range.",
+    DivisionByZero = "T1408" "DIVISION_BY_ZERO" ExprError:
+        "A division by zero.",
+    NumberOutOfRange = "T1409" "NUMBER_OUT_OF_RANGE" ExprError:
+        "A number result is not finite or does not fit its type.",
+    Degraded = "T1500" "DEGRADED" Degraded:
+        "Warning: the transform ran in a slower way than asked, without a more specific code.",
+    SingleThread = "T1501" "SINGLE_THREAD" Degraded:
+        "Warning: a stream given several threads runs on one.",
+    TableFallback = "T1502" "TABLE_FALLBACK" Degraded:
+        "Warning: a lookup table keeps full records or no index, as the rule reads beyond them.",
+}
+
+impl TransformErrorCode {
+    pub fn kind(&self) -> TransformErrorKind {
+        catalog_entry(*self).1.clone()
+    }
+
+    pub fn description(&self) -> &'static str {
+        catalog_entry(*self).2
+    }
+
+    fn for_kind(kind: &TransformErrorKind) -> Self {
+        match kind {
+            TransformErrorKind::InvalidInput => TransformErrorCode::InvalidInput,
+            TransformErrorKind::InvalidRecordsPath => TransformErrorCode::InvalidRecordsPath,
+            TransformErrorKind::InvalidRef => TransformErrorCode::InvalidRef,
+            TransformErrorKind::InvalidTarget => TransformErrorCode::InvalidTarget,
+            TransformErrorKind::MissingRequired => TransformErrorCode::MissingRequired,
+            TransformErrorKind::TypeCastFailed => TransformErrorCode::TypeCastFailed,
+            TransformErrorKind::ExprError => TransformErrorCode::ExprError,
+            TransformErrorKind::Degraded => TransformErrorCode::Degraded,
+        }
+    }
+}
+
+type CatalogEntry = (TransformErrorCode, TransformErrorKind, &'static str);
+
+fn catalog_entry(code: TransformErrorCode) -> &'static CatalogEntry {
+    ERROR_CATALOG
+        .iter()
+        .find(|entry| entry.0 == code)
+        .expect("every code is in the catalog")
+}
+
+/// Every [`TransformErrorCode`] with its kind and a description, in code order.
+pub fn error_catalog() -> &'static [(TransformErrorCode, TransformErrorKind, &'static str)] {
+    ERROR_CATALOG
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TransformWarning {
+    pub code: TransformErrorCode,
     pub kind: TransformErrorKind,
     pub message: String,
     pub path: Option<String>,
+    pub group_path: Option<String>,
+    /// Zero-based index of the input record being transformed.
+    pub record_index: Option<usize>,
+    /// 1-based source line of the record (CSV and NDJSON input).
+    pub line: Option<usize>,
 }
 
 impl TransformWarning {
     pub fn new(kind: TransformErrorKind, message: impl Into<String>) -> Self {
         Self {
+            code: TransformErrorCode::for_kind(&kind),
             kind,
             message: message.into(),
             path: None,
+            group_path: None,
+            record_index: None,
+            line: None,
         }
     }
 
+    pub fn with_code(mut self, code: TransformErrorCode) -> Self {
+        self.code = code;
+        self
+    }
+
     pub fn with_path(mut self, path: impl Into<String>) -> Self {
         self.path = Some(path.into());
         self
     }
+
+    pub fn with_group_path(mut self, group_path: impl Into<String>) -> Self {
+        self.group_path = Some(group_path.into());
+        self
+    }
+
+    pub fn with_record_index(mut self, record_index: usize) -> Self {
+        self.record_index = Some(record_index);
+        self
+    }
+
+    pub fn with_line(mut self, line: usize) -> Self {
+        self.line = Some(line);
+        self
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TransformError {
+    pub code: TransformErrorCode,
     pub kind: TransformErrorKind,
     pub message: String,
     pub path: Option<String>,
+    pub group_path: Option<String>,
+    /// Zero-based index of the input record being transformed.
+    pub record_index: Option<usize>,
+    /// 1-based source line of the record (CSV and NDJSON input).
+    pub line: Option<usize>,
 }
 
 impl TransformError {
     pub fn new(kind: TransformErrorKind, message: impl Into<String>) -> Self {
         Self {
+            code: TransformErrorCode::for_kind(&kind),
             kind,
             message: message.into(),
             path: None,
+            group_path: None,
+            record_index: None,
+            line: None,
         }
     }
 
+    pub fn with_code(mut self, code: TransformErrorCode) -> Self {
+        self.code = code;
+        self
+    }
+
     pub fn with_path(mut self, path: impl Into<String>) -> Self {
         self.path = Some(path.into());
         self
     }
+
+    pub fn with_group_path(mut self, group_path: impl Into<String>) -> Self {
+        self.group_path = Some(group_path.into());
+        self
+    }
+
+    pub fn with_record_index(mut self, record_index: usize) -> Self {
+        self.record_index = Some(record_index);
+        self
+    }
+
+    pub fn with_line(mut self, line: usize) -> Self {
+        self.line = Some(line);
+        self
+    }
 }
 
 impl std::fmt::Display for TransformError {
@@ -154,12 +538,85 @@ impl std::fmt::Display for TransformError {
 
 impl std::error::Error for TransformError {}
 
+/// Distinct warnings a `WarningSummary` keeps; further distinct warnings are only counted.
+pub const MAX_WARNING_GROUPS: usize = 1000;
+
+/// Warnings with the same kind, path and message folded into one entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WarningGroup {
+    /// The first occurrence.
+    pub warning: TransformWarning,
+    pub count: usize,
+    pub first_record_index: Option<usize>,
+    pub last_record_index: Option<usize>,
+}
+
+/// Aggregated warnings whose size does not grow with the number of records.
+#[derive(Debug, Clone, Default)]
+pub struct WarningSummary {
+    /// Groups in order of first occurrence.
+    pub groups: Vec<WarningGroup>,
+    /// Warnings that did not fit once `MAX_WARNING_GROUPS` groups were held.
+    pub dropped: usize,
+    index: HashMap<(TransformErrorKind, Option<String>, String), usize>,
+}
+
+impl WarningSummary {
+    pub fn push(&mut self, warning: TransformWarning) {
+        let key = (
+            warning.kind.clone(),
+            warning.path.clone(),
+            warning.message.clone(),
+        );
+        if let Some(&index) = self.index.get(&key) {
+            let group = &mut self.groups[index];
+            group.count += 1;
+            if warning.record_index.is_some() {
+                group.last_record_index = warning.record_index;
+                group.first_record_index = group.first_record_index.or(warning.record_index);
+            }
+            return;
+        }
+        if self.groups.len() >= MAX_WARNING_GROUPS {
+            self.dropped += 1;
+            return;
+        }
+        self.index.insert(key, self.groups.len());
+        self.groups.push(WarningGroup {
+            first_record_index: warning.record_index,
+            last_record_index: warning.record_index,
+            warning,
+            count: 1,
+        });
+    }
+
+    /// Number of warnings pushed, including dropped ones.
+    pub fn total(&self) -> usize {
+        self.groups.iter().map(|group| group.count).sum::<usize>() + self.dropped
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty() && self.dropped == 0
+    }
+}
+
+impl Extend<TransformWarning> for WarningSummary {
+    fn extend<I: IntoIterator<Item = TransformWarning>>(&mut self, warnings: I) {
+        for warning in warnings {
+            self.push(warning);
+        }
+    }
+}
+
 impl From<TransformError> for TransformWarning {
     fn from(err: TransformError) -> Self {
-        let mut warning = TransformWarning::new(err.kind, err.message);
+        let mut warning = TransformWarning::new(err.kind, err.message).with_code(err.code);
         if let Some(path) = err.path {
             warning = warning.with_path(path);
         }
+        warning.group_path = err.group_path;
+        warning.record_index = err.record_index;
+        warning.line = err.line;
         warning
     }
 }
@@ -170,6 +627,7 @@ impl From<csv::Error> for TransformError {
             TransformErrorKind::InvalidInput,
             format!("csv error: {}", err),
         )
+        .with_code(TransformErrorCode::InputRead)
     }
 }
 
@@ -179,5 +637,6 @@ impl From<serde_json::Error> for TransformError {
             TransformErrorKind::InvalidInput,
             format!("json error: {}", err),
         )
+        .with_code(TransformErrorCode::InputParse)
     }
 }
@@ -1,51 +1,75 @@
+mod builder;
 mod cache;
+mod compose;
+mod constants;
+mod csv_input;
+mod csv_output;
+mod diff;
+mod env;
 mod error;
+mod limits;
 mod locator;
+mod lookup_index;
 mod model;
-mod path;
+pub mod path;
 mod dto;
+mod self_check;
+mod table;
+mod trace;
 mod transform;
 mod validator;
+mod yaml_edit;
 
 /// Library version from Cargo.toml
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 pub use error::{
-    ErrorCode, RuleError, TransformError, TransformErrorKind, TransformWarning, ValidationResult,
-    YamlLocation,
+    error_catalog, ErrorCode, RuleError, RuleWarning, TransformError, TransformErrorCode,
+    TransformErrorKind, TransformWarning, ValidationReport, ValidationResult, WarningCode,
+    WarningGroup, WarningSummary, YamlLocation, MAX_WARNING_GROUPS,
 };
+pub use builder::{MappingBuilder, MappingTarget, RuleFileBuilder};
+pub use cache::{clear_rule_cache, rule_cache_stats, RuleCacheStats};
+pub use compose::{load_rule_file, RuleOrigins, RuleSource};
+pub use csv_input::parse_csv_records;
+pub use csv_output::records_to_csv;
+pub use diff::{diff_json, unified_diff, ArrayMatch, DiffEntry, DiffKind};
 pub use dto::{generate_dto, DtoError, DtoLanguage};
-pub use model::{Expr, ExprChain, ExprOp, ExprRef, InputFormat, InputSpec, Mapping, RuleFile};
+pub use limits::{parse_rule_file_with_limits, ParseLimits};
+pub use model::{
+    Column, CsvInput, Expr, ExprChain, ExprOp, ExprRef, GroupSpec, InputFormat, InputSpec,
+    JsonInput, Mapping, MappingDefaults, MappingGroup, MappingGroups, MappingSource, OnError,
+    OutputFormat, OutputSpec, RuleFile, TableFormat, TableSpec,
+};
+#[cfg(feature = "fault-injection")]
+pub use self_check::SELF_CHECK_FAIL_ENV;
+pub use self_check::{environment_info, run_check, run_self_checks, CheckResult, EnvironmentInfo};
+pub use table::{load_table, IndexedTable, LoadedTable};
+#[doc(hidden)]
+pub use trace::glob_match;
+pub use trace::{MappingTrace, RecordTrace, TraceEvent, TraceEventKind, TraceOptions};
 pub use transform::{
-    preflight_validate, preflight_validate_with_warnings, transform, transform_stream,
-    transform_with_warnings, TransformStream, TransformStreamItem,
+    input_record, op_spec, preflight_validate, preflight_validate_with_warning_summary,
+    preflight_validate_with_warnings, select_json_records, transform, transform_stream,
+    transform_record_traced, transform_stream_from_reader, transform_stream_with_limits,
+    transform_values,
+    transform_values_stream,
+    transform_with_warning_summary, transform_with_warnings, transform_with_warnings_parallel,
+    OpCategory, OpSpec, TransformStream, TransformStreamItem,
+    DEFAULT_MAX_JSON_DEPTH, OPS,
 };
-pub use validator::{validate_rule_file, validate_rule_file_with_source};
-
-use std::sync::{Mutex, OnceLock};
-
-use cache::LruCache;
+pub use validator::{validate_rule_file, validate_rule_file_full, validate_rule_file_with_source};
+pub use yaml_edit::patch_yaml;
 
-const RULE_CACHE_CAPACITY: usize = 128;
-
-fn rule_cache() -> &'static Mutex<LruCache<String, RuleFile>> {
-    static RULE_CACHE: OnceLock<Mutex<LruCache<String, RuleFile>>> = OnceLock::new();
-    RULE_CACHE.get_or_init(|| Mutex::new(LruCache::new(RULE_CACHE_CAPACITY)))
-}
+use cache::{rule_cache, RuleKey};
 
 pub fn parse_rule_file(yaml: &str) -> Result<RuleFile, serde_yaml::Error> {
-    let key = yaml.to_string();
-    if let Some(rule) = {
-        let mut cache = rule_cache().lock().unwrap_or_else(|err| err.into_inner());
-        cache.get_cloned(&key)
-    } {
+    let key = RuleKey::new(yaml);
+    if let Some(rule) = rule_cache().get(&key, yaml) {
         return Ok(rule);
     }
 
     let rule: RuleFile = serde_yaml::from_str(yaml)?;
-    {
-        let mut cache = rule_cache().lock().unwrap_or_else(|err| err.into_inner());
-        cache.insert(key, rule.clone());
-    }
+    rule_cache().insert(key, yaml, rule.clone());
     Ok(rule)
 }
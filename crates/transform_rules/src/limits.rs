@@ -0,0 +1,154 @@
+use std::io;
+
+use serde_yaml::Value as YamlValue;
+
+use crate::error::{
+    ErrorCode, RuleError, TransformError, TransformErrorCode, TransformErrorKind,
+};
+use crate::model::RuleFile;
+use crate::parse_rule_file;
+
+const LIMIT_NAMES: [&str; 5] = [
+    "max_bytes",
+    "max_depth",
+    "max_mappings",
+    "max_records",
+    "max_record_bytes",
+];
+
+// serde_yaml refuses documents nesting deeper than this while parsing them.
+const YAML_RECURSION_LIMIT: usize = 128;
+
+/// Caps for parsing untrusted rules and input, such as text a server receives from clients.
+///
+/// A `None` field is not checked, so the default limits nothing. Every limit error has a
+/// message starting with `<field> exceeded:`; [`ParseLimits::exceeded`] reads the field back.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseLimits {
+    /// Size of the rules YAML, or of the input text.
+    pub max_bytes: Option<usize>,
+    /// Nesting of the rules YAML, and of JSON input on top of `input.json.max_depth`.
+    ///
+    /// Rules are measured once parsed, and YAML nested over 128 levels is already a parse
+    /// error, so for rules a larger value acts as 128.
+    pub max_depth: Option<usize>,
+    /// Entries of `mappings` plus those of every `mapping_groups` list.
+    pub max_mappings: Option<usize>,
+    /// Input records a stream reads.
+    pub max_records: Option<usize>,
+    /// Size of a single input record written as compact JSON.
+    pub max_record_bytes: Option<usize>,
+}
+
+impl ParseLimits {
+    /// The field named by a limit error's message, e.g. `max_records`.
+    pub fn exceeded(message: &str) -> Option<&'static str> {
+        LIMIT_NAMES.into_iter().find(|name| {
+            message
+                .strip_prefix(name)
+                .is_some_and(|rest| rest.starts_with(" exceeded:"))
+        })
+    }
+}
+
+pub(crate) fn limit_message(name: &str, detail: impl std::fmt::Display) -> String {
+    format!("{} exceeded: {}", name, detail)
+}
+
+pub(crate) fn input_limit_error(name: &str, detail: impl std::fmt::Display) -> TransformError {
+    TransformError::new(TransformErrorKind::InvalidInput, limit_message(name, detail))
+        .with_code(TransformErrorCode::LimitExceeded)
+}
+
+/// Like [`parse_rule_file`], refusing YAML over `limits` before it is turned into a rule.
+///
+/// Limit errors have the code [`ErrorCode::LimitExceeded`] and YAML errors
+/// [`ErrorCode::RulesParseFailed`]. `max_records` and `max_record_bytes` do not apply here.
+#[allow(clippy::result_large_err)]
+pub fn parse_rule_file_with_limits(
+    yaml: &str,
+    limits: &ParseLimits,
+) -> Result<RuleFile, RuleError> {
+    let exceeded = |name: &str, detail: String| {
+        RuleError::new(ErrorCode::LimitExceeded, limit_message(name, detail))
+    };
+    if let Some(max) = limits.max_bytes
+        && yaml.len() > max
+    {
+        return Err(exceeded(
+            "max_bytes",
+            format!("rules are {} bytes, more than {}", yaml.len(), max),
+        ));
+    }
+    if limits.max_depth.is_some() || limits.max_mappings.is_some() {
+        // serde_yaml bounds its own recursion and alias expansion while building the value.
+        let value: YamlValue = serde_yaml::from_str(yaml).map_err(rules_parse_error)?;
+        if let Some(max) = limits.max_depth.map(|max| max.min(YAML_RECURSION_LIMIT)) {
+            let depth = yaml_depth(&value);
+            if depth > max {
+                return Err(exceeded(
+                    "max_depth",
+                    format!("rules nest {} levels deep, more than {}", depth, max),
+                ));
+            }
+        }
+        if let Some(max) = limits.max_mappings {
+            let count = mapping_count(&value);
+            if count > max {
+                return Err(exceeded(
+                    "max_mappings",
+                    format!("rules have {} mappings, more than {}", count, max),
+                )
+                .with_path("mappings"));
+            }
+        }
+    }
+    parse_rule_file(yaml).map_err(rules_parse_error)
+}
+
+fn rules_parse_error(err: serde_yaml::Error) -> RuleError {
+    let location = err.location();
+    let error = RuleError::new(
+        ErrorCode::RulesParseFailed,
+        format!("failed to parse rules: {}", err),
+    );
+    match location {
+        Some(location) => error.with_location(location.line(), location.column()),
+        None => error,
+    }
+}
+
+// A scalar is depth 0 and each enclosing sequence or mapping adds one.
+fn yaml_depth(value: &YamlValue) -> usize {
+    match value {
+        YamlValue::Sequence(items) => 1 + items.iter().map(yaml_depth).max().unwrap_or(0),
+        YamlValue::Mapping(map) => 1 + map.values().map(yaml_depth).max().unwrap_or(0),
+        YamlValue::Tagged(tagged) => yaml_depth(&tagged.value),
+        _ => 0,
+    }
+}
+
+fn mapping_count(value: &YamlValue) -> usize {
+    let list_len = |value: &YamlValue| value.as_sequence().map_or(0, Vec::len);
+    let mappings = value.get("mappings").map_or(0, list_len);
+    let grouped = value
+        .get("mapping_groups")
+        .and_then(YamlValue::as_mapping)
+        .map_or(0, |groups| groups.values().map(list_len).sum());
+    mappings + grouped
+}
+
+/// Counts what is written to it; used to size records without keeping their JSON text.
+#[derive(Default)]
+pub(crate) struct ByteCounter(pub(crate) usize);
+
+impl io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -2,6 +2,12 @@ use std::collections::HashMap;
 
 use crate::error::YamlLocation;
 
+/// Line and column of every node of a YAML document, keyed by its path
+/// (`mappings[3].expr.chain[2]`).
+///
+/// Block and flow collections are both followed, so nodes inside `{ op: ..., args: [...] }`
+/// have their own location. Mapping entries are located at their key and sequence items at
+/// their `-` or, in a flow sequence, at their first character.
 #[derive(Debug, Default)]
 pub struct YamlLocator {
     locations: HashMap<String, YamlLocation>,
@@ -9,146 +15,617 @@ pub struct YamlLocator {
 
 impl YamlLocator {
     pub fn from_str(source: &str) -> Self {
-        let mut locator = YamlLocator {
-            locations: HashMap::new(),
-        };
-        locator.build(source);
-        locator
+        YamlLocator {
+            locations: Scanner::scan(source).locations,
+        }
     }
 
     pub fn location_for(&self, path: &str) -> Option<YamlLocation> {
         self.locations.get(path).cloned()
     }
+}
 
-    fn build(&mut self, source: &str) {
-        let mut scopes: Vec<Scope> = vec![Scope {
-            indent: 0,
-            path: String::new(),
-        }];
-        let mut seq_indices: HashMap<String, usize> = HashMap::new();
+/// Byte offsets of a node in the source, for editing it in place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct NodeSpan {
+    /// The key of a mapping entry, the `-` of a block sequence item or the first character
+    /// of a flow sequence item.
+    pub(crate) entry: usize,
+    /// The value, after its anchor and tag; equal to `end` when the value is empty.
+    pub(crate) start: usize,
+    /// Just past the last character of the value, before any trailing comment.
+    pub(crate) end: usize,
+    /// Whether the node is an entry of a flow collection.
+    pub(crate) flow: bool,
+}
 
-        for (line_index, raw_line) in source.lines().enumerate() {
-            let line_number = line_index + 1;
-            let trimmed = raw_line.trim();
-            if trimmed.is_empty() || trimmed.starts_with('#') {
-                continue;
+/// The span of every node of a YAML document and the entries of each collection, keyed by
+/// path like [`YamlLocator`]; the document itself has the path `""`.
+#[derive(Debug, Default)]
+pub(crate) struct YamlNodes {
+    pub(crate) spans: HashMap<String, NodeSpan>,
+    pub(crate) children: HashMap<String, Vec<String>>,
+}
+
+impl YamlNodes {
+    pub(crate) fn from_str(source: &str) -> Self {
+        let scanner = Scanner::scan(source);
+        YamlNodes {
+            spans: scanner.spans,
+            children: scanner.children,
+        }
+    }
+}
+
+// Walks the source the way a YAML parser tokenizes it, without building values. Rules have
+// already been parsed by serde_yaml when they are located, so malformed input only has to
+// end the walk, not be reported.
+struct Scanner<'a> {
+    text: &'a [u8],
+    pos: usize,
+    line: usize,
+    line_start: usize,
+    // Just past the last value character read, so spans leave out comments and blank lines.
+    last_end: usize,
+    locations: HashMap<String, YamlLocation>,
+    spans: HashMap<String, NodeSpan>,
+    children: HashMap<String, Vec<String>>,
+}
+
+impl<'a> Scanner<'a> {
+    fn scan(source: &'a str) -> Self {
+        let mut scanner = Scanner {
+            text: source.as_bytes(),
+            pos: 0,
+            line: 1,
+            line_start: 0,
+            last_end: 0,
+            locations: HashMap::new(),
+            spans: HashMap::new(),
+            children: HashMap::new(),
+        };
+        scanner.document();
+        scanner
+    }
+
+    fn document(&mut self) {
+        while let Some(indent) = self.next_content() {
+            let start = self.pos;
+            self.enter("", "", start, false);
+            self.block_node("", indent);
+            self.set_value("", start);
+            if self.pos == start {
+                self.skip_line();
             }
+        }
+    }
 
-            let indent = raw_line.chars().take_while(|c| *c == ' ').count();
-            let content = &raw_line[indent..];
+    // A node that starts at `indent` on a line of its own.
+    fn block_node(&mut self, path: &str, indent: usize) {
+        if self.at_dash_item() {
+            self.block_sequence(path, indent);
+        } else if self.line_has_key() {
+            self.block_mapping(path, indent);
+        } else {
+            self.inline_value(path, indent, false);
+        }
+    }
 
-            if content.starts_with('-') {
-                while scopes.len() > 1 && scopes.last().unwrap().indent >= indent {
-                    scopes.pop();
+    fn block_mapping(&mut self, path: &str, indent: usize) {
+        loop {
+            let (line, column, entry) = (self.line, self.column(), self.pos);
+            match self.block_key() {
+                Some(key) => {
+                    let key_path = child_path(path, &key);
+                    self.insert(&key_path, line, column);
+                    self.enter(path, &key_path, entry, false);
+                    self.value(&key_path, indent, false);
                 }
-                let parent_path = scopes.last().map(|s| s.path.clone()).unwrap_or_default();
-                if parent_path.is_empty() {
-                    continue;
+                None => self.skip_line(),
+            }
+            loop {
+                match self.next_content() {
+                    Some(next) if next == indent && !self.at_dash_item() => break,
+                    Some(next) if next > indent => self.skip_line(),
+                    _ => return,
                 }
+            }
+        }
+    }
 
-                let index = seq_indices.entry(parent_path.clone()).or_insert(0);
-                let item_index = *index;
-                *index += 1;
+    fn block_sequence(&mut self, path: &str, indent: usize) {
+        let mut index = 0;
+        loop {
+            let item_path = format!("{}[{}]", path, index);
+            index += 1;
+            self.insert(&item_path, self.line, self.column());
+            self.enter(path, &item_path, self.pos, false);
+            self.advance();
+            self.last_end = self.pos;
+            self.value(&item_path, indent, true);
+            loop {
+                match self.next_content() {
+                    Some(next) if next == indent && self.at_dash_item() => break,
+                    Some(next) if next > indent => self.skip_line(),
+                    _ => return,
+                }
+            }
+        }
+    }
 
-                let item_path = format!("{}[{}]", parent_path, item_index);
-                self.insert_location(&item_path, line_number, indent + 1);
+    // The value after `key:` or `-`; `indent` is that of the key or the dash.
+    fn value(&mut self, path: &str, indent: usize, after_dash: bool) {
+        self.skip_inline();
+        self.skip_properties(false);
+        if !self.at_line_end() {
+            let start = self.pos;
+            self.inline_value(path, indent, after_dash);
+            self.set_value(path, start);
+            return;
+        }
+        let empty = self.last_end;
+        match self.next_content() {
+            Some(next) if next > indent => {
+                let start = self.pos;
+                self.block_node(path, next);
+                self.set_value(path, start);
+            }
+            // A sequence may sit at the indent of the key that holds it.
+            Some(next) if next == indent && !after_dash && self.at_dash_item() => {
+                let start = self.pos;
+                self.block_sequence(path, next);
+                self.set_value(path, start);
+            }
+            _ => self.set_value(path, empty),
+        }
+    }
 
-                scopes.push(Scope {
-                    indent,
-                    path: item_path.clone(),
-                });
+    fn inline_value(&mut self, path: &str, indent: usize, after_dash: bool) {
+        match self.peek() {
+            Some(b'{' | b'[') => {
+                self.flow_node(path);
+                self.skip_inline();
+            }
+            Some(b'|' | b'>') => self.block_scalar(indent),
+            Some(b'-') if after_dash && self.at_dash_item() => {
+                self.block_sequence(path, self.column_index())
+            }
+            _ if after_dash && self.line_has_key() => self.block_mapping(path, self.column_index()),
+            Some(b'"' | b'\'') => {
+                self.quoted();
+                self.scalar_continuation(indent);
+            }
+            _ => {
+                self.plain_line();
+                self.scalar_continuation(indent);
+            }
+        }
+    }
 
-                let after_dash = &content[1..];
-                let trimmed_after_dash = after_dash.trim_start();
-                let offset = 1 + (after_dash.len() - trimmed_after_dash.len());
-                if let Some((key, column, has_value, is_block)) =
-                    parse_key_at(trimmed_after_dash, indent, offset)
-                {
-                    let full_path = format!("{}.{}", item_path, key);
-                    self.insert_location(&full_path, line_number, column);
-                    if !has_value || is_block {
-                        scopes.push(Scope {
-                            indent: indent + offset,
-                            path: full_path,
-                        });
-                    }
+    // A plain or quoted scalar goes on over lines indented deeper than its key or dash.
+    fn scalar_continuation(&mut self, indent: usize) {
+        while let Some(next) = self.next_content() {
+            if next <= indent {
+                break;
+            }
+            self.plain_line();
+            self.advance();
+        }
+    }
+
+    // The rest of a line of a plain scalar, which a comment ends.
+    fn plain_line(&mut self) {
+        let mut after_space = false;
+        while let Some(byte) = self.peek() {
+            match byte {
+                b'\n' => return,
+                b'#' if after_space => {
+                    self.skip_line_content();
+                    return;
+                }
+                b' ' | b'\t' | b'\r' => {}
+                _ => self.last_end = self.pos + 1,
+            }
+            after_space = matches!(byte, b' ' | b'\t');
+            self.advance();
+        }
+    }
+
+    fn block_scalar(&mut self, indent: usize) {
+        self.skip_line_content();
+        self.last_end = self.pos;
+        while self.peek() == Some(b'\n') {
+            let line_start = self.pos + 1;
+            let mut end = line_start;
+            while self.text.get(end) == Some(&b' ') {
+                end += 1;
+            }
+            let blank = matches!(self.text.get(end), None | Some(b'\n' | b'\r'));
+            if !blank && end - line_start <= indent {
+                break;
+            }
+            self.advance();
+            self.skip_line_content();
+            if !blank {
+                self.last_end = self.pos;
+            }
+        }
+    }
+
+    fn flow_node(&mut self, path: &str) {
+        self.skip_properties(true);
+        let start = self.pos;
+        match self.peek() {
+            Some(b'{') => self.flow_mapping(path),
+            Some(b'[') => self.flow_sequence(path),
+            Some(b'"' | b'\'') => self.quoted(),
+            _ => self.flow_plain(),
+        }
+        self.set_value(path, start);
+    }
+
+    fn flow_mapping(&mut self, path: &str) {
+        self.advance();
+        loop {
+            self.skip_flow_space();
+            match self.peek() {
+                None => return,
+                Some(b'}') => {
+                    self.advance();
+                    self.last_end = self.pos;
+                    return;
+                }
+                Some(b',') => {
+                    self.advance();
+                    continue;
                 }
-                continue;
+                _ => {}
             }
+            let start = self.pos;
+            let (line, column) = (self.line, self.column());
+            let key = self.flow_scalar_text();
+            let key_path = child_path(path, &key);
+            self.insert(&key_path, line, column);
+            self.enter(path, &key_path, start, true);
+            self.skip_flow_space();
+            if self.peek() == Some(b':') {
+                self.advance();
+                self.last_end = self.pos;
+                self.skip_flow_space();
+                if !matches!(self.peek(), None | Some(b',' | b'}')) {
+                    self.flow_node(&key_path);
+                }
+            }
+            self.set_value(&key_path, self.last_end);
+            if self.pos == start {
+                self.advance();
+            }
+        }
+    }
 
-            while scopes.len() > 1 && scopes.last().unwrap().indent >= indent {
-                scopes.pop();
+    fn flow_sequence(&mut self, path: &str) {
+        self.advance();
+        let mut index = 0;
+        loop {
+            self.skip_flow_space();
+            match self.peek() {
+                None => return,
+                Some(b']') => {
+                    self.advance();
+                    self.last_end = self.pos;
+                    return;
+                }
+                Some(b',') => {
+                    self.advance();
+                    continue;
+                }
+                _ => {}
+            }
+            let start = self.pos;
+            let item_path = format!("{}[{}]", path, index);
+            index += 1;
+            self.insert(&item_path, self.line, self.column());
+            self.enter(path, &item_path, start, true);
+            self.flow_node(&item_path);
+            self.skip_flow_space();
+            // `[ key: value ]` is a single-pair mapping.
+            if self.peek() == Some(b':') {
+                self.advance();
+                self.skip_flow_space();
+                if !matches!(self.peek(), None | Some(b',' | b']')) {
+                    self.flow_node(&item_path);
+                }
             }
+            if self.pos == start {
+                self.advance();
+            }
+        }
+    }
 
-            if let Some((key, column, has_value, is_block)) = parse_key_at(content, indent, 0) {
-                let parent_path = scopes.last().map(|s| s.path.clone()).unwrap_or_default();
-                let full_path = if parent_path.is_empty() {
-                    key
-                } else {
-                    format!("{}.{}", parent_path, key)
+    fn flow_scalar_text(&mut self) -> String {
+        let start = self.pos;
+        if matches!(self.peek(), Some(b'"' | b'\'')) {
+            self.quoted();
+            return unquote(&self.text[start..self.pos]);
+        }
+        self.flow_plain();
+        String::from_utf8_lossy(&self.text[start..self.pos]).trim().to_string()
+    }
+
+    fn flow_plain(&mut self) {
+        let mut after_space = false;
+        while let Some(byte) = self.peek() {
+            match byte {
+                b',' | b'[' | b']' | b'{' | b'}' => return,
+                b':' if self.ends_key(1, true) => return,
+                b'#' if after_space => self.skip_line_content(),
+                b' ' | b'\t' | b'\r' | b'\n' => {}
+                _ => self.last_end = self.pos + 1,
+            }
+            after_space = matches!(byte, b' ' | b'\t' | b'\r' | b'\n');
+            self.advance();
+        }
+    }
+
+    fn block_key(&mut self) -> Option<String> {
+        let start = self.pos;
+        let key = if matches!(self.peek(), Some(b'"' | b'\'')) {
+            self.quoted();
+            let key = unquote(&self.text[start..self.pos]);
+            self.skip_inline();
+            key
+        } else {
+            while !self.at_line_end() && !self.at_key_colon() {
+                self.advance();
+            }
+            String::from_utf8_lossy(&self.text[start..self.pos]).trim().to_string()
+        };
+        if self.peek() != Some(b':') || key.is_empty() {
+            return None;
+        }
+        self.advance();
+        self.last_end = self.pos;
+        Some(key)
+    }
+
+    // Whether the rest of the line starts with `key:`.
+    fn line_has_key(&self) -> bool {
+        let mut pos = self.pos;
+        match self.text.get(pos) {
+            Some(b'{' | b'[') => return false,
+            Some(&quote @ (b'"' | b'\'')) => {
+                pos = quoted_end(self.text, pos, quote);
+                while self.text.get(pos) == Some(&b' ') {
+                    pos += 1;
+                }
+                return self.text.get(pos) == Some(&b':')
+                    && self.ends_key(pos + 1 - self.pos, false);
+            }
+            _ => {}
+        }
+        let mut after_space = false;
+        while let Some(&byte) = self.text.get(pos) {
+            match byte {
+                b'\n' => return false,
+                b'#' if after_space => return false,
+                b':' if self.ends_key(pos + 1 - self.pos, false) => return true,
+                _ => {}
+            }
+            after_space = matches!(byte, b' ' | b'\t');
+            pos += 1;
+        }
+        false
+    }
+
+    fn at_key_colon(&self) -> bool {
+        self.peek() == Some(b':') && self.ends_key(1, false)
+    }
+
+    // Whether the byte `offset` past the current one may follow the `:` of a key.
+    fn ends_key(&self, offset: usize, flow: bool) -> bool {
+        match self.text.get(self.pos + offset) {
+            None | Some(b' ' | b'\t' | b'\r' | b'\n') => true,
+            Some(b',' | b'[' | b']' | b'{' | b'}') => flow,
+            Some(_) => false,
+        }
+    }
+
+    fn at_dash_item(&self) -> bool {
+        self.peek() == Some(b'-')
+            && matches!(self.text.get(self.pos + 1), None | Some(b' ' | b'\t' | b'\r' | b'\n'))
+    }
+
+    // Anchors and tags before a value.
+    fn skip_properties(&mut self, flow: bool) {
+        while matches!(self.peek(), Some(b'&' | b'!')) {
+            while let Some(byte) = self.peek() {
+                let ends = match byte {
+                    b' ' | b'\t' | b'\r' | b'\n' => true,
+                    b',' | b'[' | b']' | b'{' | b'}' => flow,
+                    _ => false,
                 };
-                self.insert_location(&full_path, line_number, column);
-                if !has_value || is_block {
-                    scopes.push(Scope {
-                        indent,
-                        path: full_path,
-                    });
+                if ends {
+                    break;
+                }
+                self.advance();
+            }
+            if flow {
+                self.skip_flow_space();
+            } else {
+                self.skip_inline();
+            }
+        }
+    }
+
+    fn quoted(&mut self) {
+        let Some(quote) = self.peek() else {
+            return;
+        };
+        let end = quoted_end(self.text, self.pos, quote);
+        while self.pos < end {
+            self.advance();
+        }
+        self.last_end = self.pos;
+    }
+
+    // Moves to the first character of the next line with content and returns its indent.
+    fn next_content(&mut self) -> Option<usize> {
+        loop {
+            self.skip_inline();
+            match self.peek() {
+                None => return None,
+                Some(b'\n') => self.advance(),
+                Some(b'%') if self.pos == self.line_start => self.skip_line(),
+                Some(b'-' | b'.')
+                    if self.pos == self.line_start
+                        && (self.text[self.pos..].starts_with(b"---")
+                            || self.text[self.pos..].starts_with(b"..."))
+                        && matches!(
+                            self.text.get(self.pos + 3),
+                            None | Some(b' ' | b'\t' | b'\r' | b'\n')
+                        ) =>
+                {
+                    self.pos += 3;
                 }
+                Some(_) => return Some(self.column_index()),
+            }
+        }
+    }
+
+    // Skips spaces and a comment, up to the end of the line.
+    fn skip_inline(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\r')) {
+            self.advance();
+        }
+        if self.peek() == Some(b'#') {
+            self.skip_line_content();
+        }
+    }
+
+    fn skip_flow_space(&mut self) {
+        loop {
+            self.skip_inline();
+            if self.peek() != Some(b'\n') {
+                return;
+            }
+            self.advance();
+        }
+    }
+
+    fn skip_line_content(&mut self) {
+        while !self.at_line_end() {
+            self.advance();
+        }
+    }
+
+    fn skip_line(&mut self) {
+        self.skip_line_content();
+        self.advance();
+    }
+
+    fn at_line_end(&self) -> bool {
+        matches!(self.peek(), None | Some(b'\n'))
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.pos).copied()
+    }
+
+    fn advance(&mut self) {
+        if let Some(byte) = self.peek() {
+            self.pos += 1;
+            if byte == b'\n' {
+                self.line += 1;
+                self.line_start = self.pos;
             }
         }
     }
 
-    fn insert_location(&mut self, path: &str, line: usize, column: usize) {
+    fn column_index(&self) -> usize {
+        self.pos - self.line_start
+    }
+
+    fn column(&self) -> usize {
+        self.column_index() + 1
+    }
+
+    fn insert(&mut self, path: &str, line: usize, column: usize) {
         self.locations
             .entry(path.to_string())
             .or_insert(YamlLocation { line, column });
     }
-}
 
-#[derive(Debug, Clone)]
-struct Scope {
-    indent: usize,
-    path: String,
-}
+    fn enter(&mut self, parent: &str, path: &str, entry: usize, flow: bool) {
+        if self.spans.contains_key(path) {
+            return;
+        }
+        let span = NodeSpan {
+            entry,
+            start: entry,
+            end: entry,
+            flow,
+        };
+        self.spans.insert(path.to_string(), span);
+        if !path.is_empty() {
+            self.children
+                .entry(parent.to_string())
+                .or_default()
+                .push(path.to_string());
+        }
+    }
 
-fn parse_key_at(
-    content: &str,
-    indent: usize,
-    offset: usize,
-) -> Option<(String, usize, bool, bool)> {
-    let (key, key_start, has_value, is_block) = parse_key(content)?;
-    let column = indent + offset + key_start + 1;
-    Some((key, column, has_value, is_block))
+    // Only the first value read for a path counts, as with locations.
+    fn set_value(&mut self, path: &str, start: usize) {
+        if let Some(span) = self.spans.get_mut(path)
+            && span.start == span.entry
+            && span.end == span.entry
+        {
+            span.start = start;
+            span.end = self.last_end.max(start);
+        }
+    }
 }
 
-fn parse_key(content: &str) -> Option<(String, usize, bool, bool)> {
-    let mut in_single = false;
-    let mut in_double = false;
-    let mut colon_index = None;
+fn child_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
 
-    for (index, ch) in content.char_indices() {
-        match ch {
-            '\'' if !in_double => in_single = !in_single,
-            '"' if !in_single => in_double = !in_double,
-            ':' if !in_single && !in_double => {
-                colon_index = Some(index);
-                break;
+// The position just past the closing quote of the scalar opened at `start`.
+fn quoted_end(text: &[u8], start: usize, quote: u8) -> usize {
+    let mut pos = start + 1;
+    while let Some(&byte) = text.get(pos) {
+        pos += 1;
+        if quote == b'"' && byte == b'\\' {
+            pos += 1;
+        } else if byte == quote {
+            if quote == b'\'' && text.get(pos) == Some(&b'\'') {
+                pos += 1;
+            } else {
+                return pos;
             }
-            _ => {}
         }
     }
+    text.len()
+}
 
-    let colon_index = colon_index?;
-    let key_part = &content[..colon_index];
-    let key = key_part.trim();
-    if key.is_empty() {
-        return None;
+fn unquote(text: &[u8]) -> String {
+    let text = String::from_utf8_lossy(text);
+    if let Some(inner) = text.strip_prefix('\'') {
+        return inner.strip_suffix('\'').unwrap_or(inner).replace("''", "'");
     }
-
-    let key_start = key_part.find(|c: char| !c.is_whitespace())?;
-    let rest = content[colon_index + 1..].trim();
-    let has_value = !rest.is_empty();
-    let is_block = rest.starts_with('|') || rest.starts_with('>');
-
-    Some((key.to_string(), key_start, has_value, is_block))
+    let inner = text.strip_prefix('"').unwrap_or(&text);
+    let inner = inner.strip_suffix('"').unwrap_or(inner);
+    let mut key = String::new();
+    let mut chars = inner.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => key.extend(chars.next()),
+            _ => key.push(ch),
+        }
+    }
+    key
 }
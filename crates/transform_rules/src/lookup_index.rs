@@ -0,0 +1,79 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::table::IndexedTable;
+
+/// Hash indexes built by `lookup`/`lookup_first` over `context.*` collections.
+///
+/// Owned by a `TransformStream` and installed for the duration of each record, so an index
+/// never outlives the context it was built from.
+#[derive(Default)]
+pub(crate) struct LookupIndexes {
+    // (collection ref, key_path) -> key -> positions in array order
+    tables: HashMap<(String, String), HashMap<String, Vec<usize>>>,
+    // Tables indexed while they were loaded, by name.
+    loaded: HashMap<String, IndexedTable>,
+}
+
+impl LookupIndexes {
+    pub(crate) fn add_loaded(&mut self, table: IndexedTable) {
+        self.loaded.insert(table.name().to_string(), table);
+    }
+
+    /// An empty set sharing the loaded tables, for another thread.
+    pub(crate) fn with_loaded_of(other: &LookupIndexes) -> Self {
+        Self {
+            tables: HashMap::new(),
+            loaded: other.loaded.clone(),
+        }
+    }
+}
+
+thread_local! {
+    static ACTIVE_INDEXES: RefCell<Option<LookupIndexes>> = const { RefCell::new(None) };
+}
+
+pub(crate) struct IndexScope<'a> {
+    slot: &'a mut LookupIndexes,
+}
+
+impl<'a> IndexScope<'a> {
+    pub(crate) fn enter(slot: &'a mut LookupIndexes) -> Self {
+        let indexes = std::mem::take(slot);
+        ACTIVE_INDEXES.with(|cell| *cell.borrow_mut() = Some(indexes));
+        Self { slot }
+    }
+}
+
+impl Drop for IndexScope<'_> {
+    fn drop(&mut self) {
+        if let Some(indexes) = ACTIVE_INDEXES.with(|cell| cell.borrow_mut().take()) {
+            *self.slot = indexes;
+        }
+    }
+}
+
+/// Positions of the items whose key equals `key`, building the table on first use.
+///
+/// Returns `None` when no stream has installed indexes; callers fall back to a linear scan.
+pub(crate) fn positions(
+    collection_ref: &str,
+    key_path: &str,
+    key: &str,
+    build: impl FnOnce() -> HashMap<String, Vec<usize>>,
+) -> Option<Vec<usize>> {
+    ACTIVE_INDEXES.with(|cell| {
+        let mut cell = cell.borrow_mut();
+        let indexes = cell.as_mut()?;
+        let table = indexes
+            .tables
+            .entry((collection_ref.to_string(), key_path.to_string()))
+            .or_insert_with(build);
+        Some(table.get(key).cloned().unwrap_or_default())
+    })
+}
+
+/// The table loaded as `context.<name>`, if the active indexes have one.
+pub(crate) fn loaded_table(name: &str) -> Option<IndexedTable> {
+    ACTIVE_INDEXES.with(|cell| cell.borrow().as_ref()?.loaded.get(name).cloned())
+}
@@ -1,36 +1,422 @@
-use serde::Deserialize;
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
+use serde_yaml::{Mapping as YamlMap, Value as YamlValue};
 
-#[derive(Debug, Deserialize, Clone)]
-#[serde(deny_unknown_fields)]
+use crate::compose::RuleOrigins;
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(try_from = "RawRuleFile")]
 pub struct RuleFile {
     pub version: u8,
     pub input: InputSpec,
-    #[serde(default)]
     pub output: Option<OutputSpec>,
+    /// How the lookup tables given as `context.<name>` are loaded; see [`load_table`].
+    ///
+    /// [`load_table`]: crate::load_table
+    pub tables: Option<BTreeMap<String, TableSpec>>,
+    /// Named expressions evaluated once per transform from `context`; read as `const.<name>`.
+    pub constants: Option<BTreeMap<String, Expr>>,
     pub record_when: Option<Expr>,
+    /// When set, `mappings` run once per group of input records instead of once per record.
+    pub group: Option<GroupSpec>,
+    /// Flattened mappings; `mapping_groups` are concatenated here in group order.
     pub mappings: Vec<Mapping>,
+    pub mapping_groups: Option<MappingGroups>,
+    /// Already applied to `mappings`; kept for reference.
+    pub mapping_defaults: Option<MappingDefaults>,
+    pub on_error: OnError,
+    /// Set by `load_rule_file`: the files the rule was read from.
+    pub origins: Option<RuleOrigins>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+/// Fields applied to every mapping that does not set them itself.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct MappingDefaults {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required: Option<bool>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub value_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<Expr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub when: Option<Expr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub treat_empty_as_missing: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct GroupSpec {
+    /// Evaluated per input record; records with equal keys form one group.
+    pub key: Expr,
+    /// Filters input records before they are grouped.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record_when: Option<Expr>,
+}
+
+/// What the transform does when a single record fails to map.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OnError {
+    /// Stop at the first failing record.
+    #[default]
+    Abort,
+    /// Drop failing records and keep going.
+    Skip,
+    /// Drop failing records, keep going, and report each failure as a warning.
+    Collect,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MappingGroups {
+    /// Groups in evaluation order: `group_order` first, then the rest alphabetically.
+    pub groups: Vec<MappingGroup>,
+    pub group_order: Option<Vec<String>>,
+    /// `mappings` was declared alongside `mapping_groups` (a validation error).
+    pub with_mappings: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MappingGroup {
+    pub name: String,
+    pub start: usize,
+    pub len: usize,
+}
+
+impl RuleFile {
+    /// Returns the group name and intra-group index of a flattened mapping index.
+    pub fn mapping_group(&self, index: usize) -> Option<(&str, usize)> {
+        let groups = self.mapping_groups.as_ref()?;
+        groups
+            .groups
+            .iter()
+            .find(|group| index >= group.start && index < group.start + group.len)
+            .map(|group| (group.name.as_str(), index - group.start))
+    }
+
+    /// Rewrites `mappings[N]...` as `mappings[group][i]...` for grouped mappings.
+    pub fn group_path(&self, path: &str) -> Option<String> {
+        let (index, rest) = split_mapping_path(path)?;
+        let (name, local) = self.mapping_group(index)?;
+        Some(format!("mappings[{}][{}]{}", name, local, rest))
+    }
+
+    /// Serializes the rule back to YAML that `parse_rule_file` reads as an equal rule.
+    ///
+    /// Keys follow the spec's order, optional fields at their defaults are left out, and
+    /// `mapping_groups` and `mapping_defaults` are written as declared rather than flattened.
+    pub fn to_yaml(&self) -> String {
+        let mut map = YamlMap::new();
+        map.insert("version".into(), self.version.into());
+        map.insert("input".into(), to_yaml_value(&self.input));
+        if let Some(output) = &self.output {
+            map.insert("output".into(), to_yaml_value(output));
+        }
+        if let Some(tables) = &self.tables {
+            map.insert("tables".into(), to_yaml_value(tables));
+        }
+        if self.on_error != OnError::Abort {
+            map.insert("on_error".into(), to_yaml_value(&self.on_error));
+        }
+        if let Some(constants) = &self.constants {
+            map.insert("constants".into(), to_yaml_value(constants));
+        }
+        if let Some(record_when) = &self.record_when {
+            map.insert("record_when".into(), to_yaml_value(record_when));
+        }
+        if let Some(group) = &self.group {
+            map.insert("group".into(), to_yaml_value(group));
+        }
+        if let Some(defaults) = &self.mapping_defaults {
+            map.insert("mapping_defaults".into(), to_yaml_value(defaults));
+        }
+
+        let defaults = self.mapping_defaults.clone().unwrap_or_default();
+        let mappings_yaml = |mappings: &[Mapping]| -> YamlValue {
+            YamlValue::Sequence(
+                mappings
+                    .iter()
+                    .map(|mapping| mapping.to_yaml_value(&defaults))
+                    .collect(),
+            )
+        };
+        match &self.mapping_groups {
+            None => {
+                map.insert("mappings".into(), mappings_yaml(&self.mappings));
+            }
+            Some(groups) => {
+                if groups.with_mappings {
+                    let ungrouped = groups.groups.first().map_or(self.mappings.len(), |g| g.start);
+                    map.insert("mappings".into(), mappings_yaml(&self.mappings[..ungrouped]));
+                }
+                if let Some(order) = &groups.group_order {
+                    map.insert("group_order".into(), to_yaml_value(order));
+                }
+                let mut group_map = YamlMap::new();
+                for group in &groups.groups {
+                    let members = &self.mappings[group.start..group.start + group.len];
+                    group_map.insert(group.name.as_str().into(), mappings_yaml(members));
+                }
+                map.insert("mapping_groups".into(), YamlValue::Mapping(group_map));
+            }
+        }
+
+        serde_yaml::to_string(&YamlValue::Mapping(map)).expect("rule YAML is serializable")
+    }
+
+    pub(crate) fn yaml_mapping_path(&self, path: &str) -> Option<String> {
+        let (index, rest) = split_mapping_path(path)?;
+        let (name, local) = self.mapping_group(index)?;
+        Some(format!("mapping_groups.{}[{}]{}", name, local, rest))
+    }
+}
+
+fn to_yaml_value<T: Serialize>(value: &T) -> YamlValue {
+    serde_yaml::to_value(value).expect("rule values are serializable")
+}
+
+impl Mapping {
+    // Fields equal to what `mapping_defaults` would fill in are left out; a field the
+    // mapping cleared while a default exists is written as an explicit `null`.
+    fn to_yaml_value(&self, defaults: &MappingDefaults) -> YamlValue {
+        let mut map = YamlMap::new();
+        map.insert("target".into(), self.target.as_str().into());
+        if let Some(source) = &self.source {
+            map.insert("source".into(), to_yaml_value(source));
+        }
+        if let Some(value) = &self.value {
+            map.insert("value".into(), to_yaml_value(value));
+        }
+        if let Some(expr) = &self.expr {
+            map.insert("expr".into(), to_yaml_value(expr));
+        }
+        if self.when != defaults.when {
+            map.insert("when".into(), to_yaml_value(&self.when));
+        }
+        if self.value_type != defaults.value_type {
+            map.insert("type".into(), to_yaml_value(&self.value_type));
+        }
+        if self.required != defaults.required.unwrap_or(false) {
+            map.insert("required".into(), self.required.into());
+        }
+        if self.default != defaults.default {
+            map.insert("default".into(), to_yaml_value(&self.default));
+        }
+        if self.treat_empty_as_missing != defaults.treat_empty_as_missing.unwrap_or(false) {
+            map.insert(
+                "treat_empty_as_missing".into(),
+                self.treat_empty_as_missing.into(),
+            );
+        }
+        YamlValue::Mapping(map)
+    }
+}
+
+fn split_mapping_path(path: &str) -> Option<(usize, &str)> {
+    let rest = path.strip_prefix("mappings[")?;
+    let end = rest.find(']')?;
+    let index = rest[..end].parse().ok()?;
+    Some((index, &rest[end + 1..]))
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRuleFile {
+    version: u8,
+    input: InputSpec,
+    #[serde(default)]
+    output: Option<OutputSpec>,
+    tables: Option<BTreeMap<String, TableSpec>>,
+    constants: Option<BTreeMap<String, Expr>>,
+    record_when: Option<Expr>,
+    group: Option<GroupSpec>,
+    mappings: Option<Vec<RawMapping>>,
+    mapping_groups: Option<BTreeMap<String, Vec<RawMapping>>>,
+    group_order: Option<Vec<String>>,
+    mapping_defaults: Option<MappingDefaults>,
+    #[serde(default)]
+    on_error: OnError,
+    extends: Option<serde_yaml::Value>,
+}
+
+// `Mapping` as written, telling an explicit `when: null` (opt out of the default) from no `when`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawMapping {
+    target: String,
+    source: Option<MappingSource>,
+    value: Option<JsonValue>,
+    expr: Option<Expr>,
+    #[serde(default, deserialize_with = "explicit")]
+    when: Option<Option<Expr>>,
+    #[serde(rename = "type", default, deserialize_with = "explicit")]
+    value_type: Option<Option<String>>,
+    required: Option<bool>,
+    #[serde(default, deserialize_with = "explicit")]
+    default: Option<Option<Expr>>,
+    treat_empty_as_missing: Option<bool>,
+}
+
+fn explicit<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::deserialize(deserializer).map(Some)
+}
+
+impl RawMapping {
+    fn resolve(self, defaults: Option<&MappingDefaults>) -> Mapping {
+        let defaults = defaults.cloned().unwrap_or_default();
+        Mapping {
+            target: self.target,
+            source: self.source,
+            value: self.value,
+            expr: self.expr,
+            when: self.when.unwrap_or(defaults.when),
+            value_type: self.value_type.unwrap_or(defaults.value_type),
+            required: self.required.or(defaults.required).unwrap_or(false),
+            default: self.default.unwrap_or(defaults.default),
+            treat_empty_as_missing: self
+                .treat_empty_as_missing
+                .or(defaults.treat_empty_as_missing)
+                .unwrap_or(false),
+        }
+    }
+}
+
+impl TryFrom<RawRuleFile> for RuleFile {
+    type Error = String;
+
+    fn try_from(raw: RawRuleFile) -> Result<Self, Self::Error> {
+        if raw.extends.is_some() {
+            return Err("`extends` is only supported when rules are loaded from a file".to_string());
+        }
+        let with_mappings = raw.mappings.is_some();
+        let defaults = raw.mapping_defaults.as_ref();
+        let resolve = |group: Vec<RawMapping>| -> Vec<Mapping> {
+            group.into_iter().map(|mapping| mapping.resolve(defaults)).collect()
+        };
+        let mut mappings = match (raw.mappings, &raw.mapping_groups) {
+            (Some(mappings), _) => resolve(mappings),
+            (None, Some(_)) => Vec::new(),
+            (None, None) => return Err("missing field `mappings`".to_string()),
+        };
+        if raw.mapping_groups.is_none() && raw.group_order.is_some() {
+            return Err("`group_order` requires `mapping_groups`".to_string());
+        }
+
+        let mapping_groups = raw.mapping_groups.map(|mut declared| {
+            let mut ordered = Vec::new();
+            for name in raw.group_order.iter().flatten() {
+                if let Some(group) = declared.remove(name) {
+                    ordered.push((name.clone(), group));
+                }
+            }
+            ordered.extend(declared);
+
+            let mut groups = Vec::with_capacity(ordered.len());
+            for (name, group) in ordered {
+                groups.push(MappingGroup {
+                    name,
+                    start: mappings.len(),
+                    len: group.len(),
+                });
+                mappings.extend(resolve(group));
+            }
+            MappingGroups {
+                groups,
+                group_order: raw.group_order,
+                with_mappings,
+            }
+        });
+
+        Ok(RuleFile {
+            version: raw.version,
+            input: raw.input,
+            output: raw.output,
+            tables: raw.tables,
+            constants: raw.constants,
+            record_when: raw.record_when,
+            group: raw.group,
+            mappings,
+            mapping_groups,
+            mapping_defaults: raw.mapping_defaults,
+            on_error: raw.on_error,
+            origins: None,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct OutputSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub format: OutputFormat,
+    /// CSV header in order; defaults to the union of top-level keys in first-seen order.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub columns: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+/// A `tables` entry: how the file given as `context.<name>` is read.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct TableSpec {
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub format: TableFormat,
+    /// Paths kept from each record; everything else is dropped while loading.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fields: Option<Vec<String>>,
+    /// The key_path every lookup against the table uses, indexed while loading.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TableFormat {
+    /// An array of records, read whole.
+    #[default]
+    Json,
+    /// One record per line, read line by line.
+    Ndjson,
+    /// A header row and one record per row, read row by row.
+    Csv,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct InputSpec {
     pub format: InputFormat,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub csv: Option<CsvInput>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub json: Option<JsonInput>,
 }
 
-#[derive(Debug, Deserialize, Clone, Copy)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum InputFormat {
     Csv,
     Json,
+    Ndjson,
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
 }
 
 fn default_true() -> bool {
@@ -41,35 +427,39 @@ fn default_delimiter() -> String {
     ",".to_string()
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct CsvInput {
     #[serde(default = "default_true")]
     pub has_header: bool,
     #[serde(default = "default_delimiter")]
     pub delimiter: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub columns: Option<Vec<Column>>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct Column {
     pub name: String,
-    #[serde(rename = "type")]
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
     pub value_type: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct JsonInput {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub records_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<usize>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct Mapping {
     pub target: String,
-    pub source: Option<String>,
+    pub source: Option<MappingSource>,
     pub value: Option<JsonValue>,
     pub expr: Option<Expr>,
     pub when: Option<Expr>,
@@ -77,10 +467,50 @@ pub struct Mapping {
     pub value_type: Option<String>,
     #[serde(default)]
     pub required: bool,
-    pub default: Option<JsonValue>,
+    /// Used when the value is missing; a literal or any expression `expr` accepts.
+    pub default: Option<Expr>,
+    /// Counts `""`, `[]` and `{}` as missing, so `default` and `required` apply to them.
+    #[serde(default)]
+    pub treat_empty_as_missing: bool,
+}
+
+/// A mapping's `source`: one ref, or refs tried in order until one is neither missing nor null.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum MappingSource {
+    Single(String),
+    Fallback(Vec<String>),
+}
+
+impl MappingSource {
+    /// The ref of a single-ref source.
+    pub fn as_single(&self) -> Option<&str> {
+        match self {
+            MappingSource::Single(source) => Some(source),
+            MappingSource::Fallback(_) => None,
+        }
+    }
+}
+
+impl From<&str> for MappingSource {
+    fn from(source: &str) -> Self {
+        MappingSource::Single(source.to_string())
+    }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+impl From<String> for MappingSource {
+    fn from(source: String) -> Self {
+        MappingSource::Single(source)
+    }
+}
+
+impl From<Vec<String>> for MappingSource {
+    fn from(sources: Vec<String>) -> Self {
+        MappingSource::Fallback(sources)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 #[serde(untagged)]
 pub enum Expr {
     Ref(ExprRef),
@@ -89,22 +519,22 @@ pub enum Expr {
     Literal(JsonValue),
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct ExprRef {
     #[serde(rename = "ref")]
     pub ref_path: String,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct ExprOp {
     pub op: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub args: Vec<Expr>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct ExprChain {
     pub chain: Vec<Expr>,
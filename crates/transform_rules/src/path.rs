@@ -1,4 +1,11 @@
-use serde_json::Value as JsonValue;
+//! Path syntax shared by rule sources, targets and the JSON ops.
+//!
+//! A path is a list of dotted keys with optional bracketed indexes (`items[0].id`).
+//! Keys containing `.` are written as quoted brackets (`["a.b"]`), escaping `\` and the quote.
+
+use std::fmt;
+
+use serde_json::{Map, Value as JsonValue};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum PathToken {
@@ -7,11 +14,14 @@ pub enum PathToken {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum PathError {
     Empty,
     InvalidSyntax,
     InvalidEscape,
     EmptyKey,
+    /// `set_path` met a value that cannot hold the next token (e.g. a key on a string).
+    Conflict,
 }
 
 impl PathError {
@@ -21,10 +31,35 @@ impl PathError {
             PathError::InvalidSyntax => "path syntax is invalid",
             PathError::InvalidEscape => "path escape is invalid",
             PathError::EmptyKey => "path segment is empty",
+            PathError::Conflict => "path conflicts with non-object value",
         }
     }
 }
 
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for PathError {}
+
+/// Parses a path string into tokens.
+///
+/// ```
+/// use transform_rules::path::{parse_path, PathError, PathToken};
+///
+/// let tokens = parse_path(r#"items[0]["a.b"]"#).unwrap();
+/// assert_eq!(
+///     tokens,
+///     vec![
+///         PathToken::Key("items".to_string()),
+///         PathToken::Index(0),
+///         PathToken::Key("a.b".to_string()),
+///     ]
+/// );
+/// assert_eq!(parse_path("a..b"), Err(PathError::EmptyKey));
+/// ```
 pub fn parse_path(path: &str) -> Result<Vec<PathToken>, PathError> {
     if path.is_empty() {
         return Err(PathError::Empty);
@@ -79,6 +114,62 @@ pub fn parse_path(path: &str) -> Result<Vec<PathToken>, PathError> {
     Ok(tokens)
 }
 
+/// Splits a ref at the end of its first key, the first `.` or `[`: `out.a` into `out` and `a`,
+/// and `out["a.b"]` into `out` and `["a.b"]`. `None` when the ref has neither.
+pub(crate) fn split_namespace(value: &str) -> Option<(&str, &str)> {
+    let end = value.find(['.', '['])?;
+    let (namespace, rest) = value.split_at(end);
+    Some((namespace, rest.strip_prefix('.').unwrap_or(rest)))
+}
+
+/// Splits an array target (`items[].sku`) into the array path and the path inside each item.
+///
+/// Returns `Ok(None)` when the path has no `[]` marker. Only one marker is allowed; the item
+/// path is empty for targets such as `tags[]` that write each element directly.
+///
+/// ```
+/// use transform_rules::path::{parse_array_target, PathError, PathToken};
+///
+/// let (array, item) = parse_array_target("order.items[].sku").unwrap().unwrap();
+/// assert_eq!(
+///     array,
+///     vec![PathToken::Key("order".to_string()), PathToken::Key("items".to_string())]
+/// );
+/// assert_eq!(item, vec![PathToken::Key("sku".to_string())]);
+/// assert_eq!(parse_array_target("tags[]").unwrap().unwrap().1, vec![]);
+/// assert_eq!(
+///     parse_array_target(r#"tags[]["a.b"]"#).unwrap().unwrap().1,
+///     vec![PathToken::Key("a.b".to_string())]
+/// );
+/// assert_eq!(parse_array_target("order.id"), Ok(None));
+/// assert_eq!(parse_array_target("a[].b[].c"), Err(PathError::InvalidSyntax));
+/// ```
+#[allow(clippy::type_complexity)]
+pub fn parse_array_target(
+    path: &str,
+) -> Result<Option<(Vec<PathToken>, Vec<PathToken>)>, PathError> {
+    // Quoted keys cannot contain brackets, so the first `[]` is always the marker.
+    let Some(marker) = path.find("[]") else {
+        return Ok(None);
+    };
+    let array = parse_path(&path[..marker])?;
+    let rest = &path[marker + 2..];
+    if rest.contains("[]") {
+        return Err(PathError::InvalidSyntax);
+    }
+    let item = if rest.is_empty() {
+        Vec::new()
+    } else if rest.starts_with('[') {
+        parse_path(rest)?
+    } else {
+        match rest.strip_prefix('.') {
+            Some(rest) => parse_path(rest)?,
+            None => return Err(PathError::InvalidSyntax),
+        }
+    };
+    Ok(Some((array, item)))
+}
+
 fn parse_bracket(chars: &[char], start: usize) -> Result<(PathToken, usize), PathError> {
     if chars.get(start) != Some(&'[') {
         return Err(PathError::InvalidSyntax);
@@ -165,6 +256,17 @@ fn parse_quoted(chars: &[char], start: usize) -> Result<(PathToken, usize), Path
     Ok((PathToken::Key(value), index))
 }
 
+/// Returns the value at `tokens`, or `None` when any step is absent or of the wrong kind.
+///
+/// ```
+/// use serde_json::json;
+/// use transform_rules::path::{get_path, parse_path};
+///
+/// let value = json!({ "items": [{ "id": 7 }] });
+/// let tokens = parse_path("items[0].id").unwrap();
+/// assert_eq!(get_path(&value, &tokens), Some(&json!(7)));
+/// assert_eq!(get_path(&value, &parse_path("items[1]").unwrap()), None);
+/// ```
 pub fn get_path<'a>(value: &'a JsonValue, tokens: &[PathToken]) -> Option<&'a JsonValue> {
     let mut current = value;
     for token in tokens {
@@ -181,3 +283,177 @@ pub fn get_path<'a>(value: &'a JsonValue, tokens: &[PathToken]) -> Option<&'a Js
     }
     Some(current)
 }
+
+/// Mutable counterpart of [`get_path`].
+///
+/// ```
+/// use serde_json::json;
+/// use transform_rules::path::{get_path_mut, parse_path};
+///
+/// let mut value = json!({ "a": { "b": 1 } });
+/// *get_path_mut(&mut value, &parse_path("a.b").unwrap()).unwrap() = json!(2);
+/// assert_eq!(value, json!({ "a": { "b": 2 } }));
+/// ```
+pub fn get_path_mut<'a>(
+    value: &'a mut JsonValue,
+    tokens: &[PathToken],
+) -> Option<&'a mut JsonValue> {
+    let mut current = value;
+    for token in tokens {
+        match token {
+            PathToken::Key(key) => match current {
+                JsonValue::Object(map) => current = map.get_mut(key)?,
+                _ => return None,
+            },
+            PathToken::Index(index) => match current {
+                JsonValue::Array(items) => current = items.get_mut(*index)?,
+                _ => return None,
+            },
+        }
+    }
+    Some(current)
+}
+
+/// Writes `value` at `tokens`, creating objects for keys and arrays for indexes on the way.
+///
+/// Arrays are padded with `null` up to the requested index, and a `null` array slot is replaced
+/// by the container the next token needs. Any other existing value of the wrong kind, including
+/// a `null` object member, is a [`PathError::Conflict`].
+///
+/// ```
+/// use serde_json::json;
+/// use transform_rules::path::{parse_path, set_path, PathError};
+///
+/// let mut value = json!({});
+/// set_path(&mut value, &parse_path("a.list[1].id").unwrap(), json!(3)).unwrap();
+/// assert_eq!(value, json!({ "a": { "list": [null, { "id": 3 }] } }));
+///
+/// let err = set_path(&mut value, &parse_path("a.list.id").unwrap(), json!(1));
+/// assert_eq!(err, Err(PathError::Conflict));
+/// ```
+pub fn set_path(
+    root: &mut JsonValue,
+    tokens: &[PathToken],
+    value: JsonValue,
+) -> Result<(), PathError> {
+    let Some((last, parents)) = tokens.split_last() else {
+        return Err(PathError::Empty);
+    };
+
+    let mut current = root;
+    for (index, token) in parents.iter().enumerate() {
+        let next_is_index = matches!(tokens[index + 1], PathToken::Index(_));
+        let container = || {
+            if next_is_index {
+                JsonValue::Array(Vec::new())
+            } else {
+                JsonValue::Object(Map::new())
+            }
+        };
+        let entry = match (token, current) {
+            (PathToken::Key(key), JsonValue::Object(map)) => {
+                map.entry(key.clone()).or_insert_with(container)
+            }
+            (PathToken::Index(path_index), JsonValue::Array(items)) => {
+                if items.len() <= *path_index {
+                    items.resize_with(path_index + 1, || JsonValue::Null);
+                }
+                let entry = &mut items[*path_index];
+                if entry.is_null() {
+                    *entry = container();
+                }
+                entry
+            }
+            _ => return Err(PathError::Conflict),
+        };
+        let fits = if next_is_index {
+            entry.is_array()
+        } else {
+            entry.is_object()
+        };
+        if !fits {
+            return Err(PathError::Conflict);
+        }
+        current = entry;
+    }
+
+    match (last, current) {
+        (PathToken::Key(key), JsonValue::Object(map)) => {
+            map.insert(key.clone(), value);
+        }
+        (PathToken::Index(path_index), JsonValue::Array(items)) => {
+            if items.len() <= *path_index {
+                items.resize_with(path_index + 1, || JsonValue::Null);
+            }
+            items[*path_index] = value;
+        }
+        _ => return Err(PathError::Conflict),
+    }
+    Ok(())
+}
+
+/// Removes and returns the value at `tokens`; a trailing index removes the array element.
+///
+/// ```
+/// use serde_json::json;
+/// use transform_rules::path::{parse_path, remove_path};
+///
+/// let mut value = json!({ "a": { "b": 1, "c": [1, 2, 3] } });
+/// assert_eq!(remove_path(&mut value, &parse_path("a.b").unwrap()), Some(json!(1)));
+/// assert_eq!(remove_path(&mut value, &parse_path("a.c[0]").unwrap()), Some(json!(1)));
+/// assert_eq!(value, json!({ "a": { "c": [2, 3] } }));
+/// ```
+pub fn remove_path(root: &mut JsonValue, tokens: &[PathToken]) -> Option<JsonValue> {
+    let (last, parents) = tokens.split_last()?;
+    match (last, get_path_mut(root, parents)?) {
+        (PathToken::Key(key), JsonValue::Object(map)) => map.remove(key),
+        (PathToken::Index(index), JsonValue::Array(items)) if *index < items.len() => {
+            Some(items.remove(*index))
+        }
+        _ => None,
+    }
+}
+
+/// Formats tokens back into path syntax; the inverse of [`parse_path`].
+///
+/// Every token list returned by `parse_path` round-trips. Keys that have no path syntax
+/// (empty keys, or keys containing `[`) are still rendered, but do not parse back.
+///
+/// ```
+/// use transform_rules::path::{format_path, parse_path, PathToken};
+///
+/// let tokens = vec![
+///     PathToken::Key("a.b".to_string()),
+///     PathToken::Index(2),
+///     PathToken::Key("c".to_string()),
+/// ];
+/// let path = format_path(&tokens);
+/// assert_eq!(path, r#"["a.b"][2].c"#);
+/// assert_eq!(parse_path(&path).unwrap(), tokens);
+/// ```
+pub fn format_path(tokens: &[PathToken]) -> String {
+    let mut path = String::new();
+    for token in tokens {
+        match token {
+            PathToken::Key(key) => {
+                if key.is_empty() || key.contains('.') {
+                    let escaped = key.replace('\\', "\\\\").replace('"', "\\\"");
+                    path.push_str("[\"");
+                    path.push_str(&escaped);
+                    path.push_str("\"]");
+                } else {
+                    if !path.is_empty() {
+                        path.push('.');
+                    }
+                    path.push_str(key);
+                }
+            }
+            PathToken::Index(index) => {
+                path.push('[');
+                path.push_str(&index.to_string());
+                path.push(']');
+            }
+        }
+    }
+    path
+}
@@ -0,0 +1,228 @@
+use serde_json::{json, Value as JsonValue};
+use std::fs;
+use std::time::{Duration, Instant};
+
+use crate::cache::RULE_CACHE_CAPACITY;
+use crate::transform::{transform, REGEX_CACHE_CAPACITY};
+use crate::validator::validate_rule_file;
+use crate::{parse_rule_file, VERSION};
+
+/// Names a check that should be reported as failed; used by tests to exercise the failure path.
+/// Only built with the `fault-injection` feature.
+#[cfg(feature = "fault-injection")]
+#[doc(hidden)]
+pub const SELF_CHECK_FAIL_ENV: &str = "TRANSFORM_RULES_SELF_CHECK_FAIL";
+
+// Every cargo feature of this crate, with whether this build has it.
+const FEATURES: &[(&str, bool)] = &[("fault-injection", cfg!(feature = "fault-injection"))];
+
+const SAMPLE_RULES: &str = r#"
+version: 1
+input:
+  format: csv
+  csv:
+    has_header: true
+mappings:
+  - target: "id"
+    source: "id"
+    type: "int"
+  - target: "name"
+    expr:
+      chain:
+        - { ref: "input.name" }
+        - { op: "trim" }
+        - { op: "uppercase" }
+"#;
+
+const SAMPLE_INPUT: &str = "id,name\n1, alice \n2,bob\n";
+
+const REGEX_RULES: &str = r#"
+version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  - target: "digits"
+    expr:
+      op: "replace"
+      args: [ { ref: "input.code" }, "[^0-9]+", "", "regex_all" ]
+"#;
+
+// 2024-03-10 is the US spring-forward date: 01:59:59 EST and 03:00:00 EDT are one second apart.
+const DST_RULES: &str = r#"
+version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  - target: "utc"
+    expr:
+      op: "date_format"
+      args: [ { ref: "input.at" }, "%Y-%m-%dT%H:%M:%SZ", "%Y-%m-%dT%H:%M:%S%:z", "UTC" ]
+"#;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    pub name: String,
+    pub passed: bool,
+    pub message: Option<String>,
+    pub duration: Duration,
+}
+
+impl CheckResult {
+    pub fn to_json(&self) -> JsonValue {
+        let mut value = json!({
+            "name": self.name,
+            "status": if self.passed { "pass" } else { "fail" },
+            "duration_ms": self.duration.as_secs_f64() * 1000.0,
+        });
+        if let Some(message) = &self.message {
+            value["message"] = json!(message);
+        }
+        value
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvironmentInfo {
+    pub version: &'static str,
+    pub features: Vec<&'static str>,
+    pub locale: Option<String>,
+    pub timezone: String,
+    pub cache_capacities: Vec<(&'static str, usize)>,
+}
+
+impl EnvironmentInfo {
+    pub fn to_json(&self) -> JsonValue {
+        let caches: serde_json::Map<String, JsonValue> = self
+            .cache_capacities
+            .iter()
+            .map(|(name, capacity)| (name.to_string(), json!(capacity)))
+            .collect();
+        json!({
+            "version": self.version,
+            "features": self.features,
+            "locale": self.locale,
+            "timezone": self.timezone,
+            "cache_capacities": caches,
+        })
+    }
+}
+
+pub fn environment_info() -> EnvironmentInfo {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty());
+    let timezone = std::env::var("TZ")
+        .ok()
+        .filter(|value| !value.is_empty())
+        .unwrap_or_else(|| chrono::Local::now().offset().to_string());
+
+    EnvironmentInfo {
+        version: VERSION,
+        features: FEATURES
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| *name)
+            .collect(),
+        locale,
+        timezone,
+        cache_capacities: vec![("rules", RULE_CACHE_CAPACITY), ("regex", REGEX_CACHE_CAPACITY)],
+    }
+}
+
+pub fn run_self_checks() -> Vec<CheckResult> {
+    vec![
+        run_check("parse_rule", check_parse_rule),
+        run_check("transform_sample", check_transform_sample),
+        run_check("regex", check_regex),
+        run_check("date_dst", check_date_dst),
+        run_check("temp_file", check_temp_file),
+    ]
+}
+
+/// Runs a single named check, timing it (and honoring the failure-injection hook when built
+/// with `fault-injection`).
+pub fn run_check(name: &str, check: impl FnOnce() -> Result<(), String>) -> CheckResult {
+    let started = Instant::now();
+    let result = forced_failure(name).map_or_else(check, Err);
+    CheckResult {
+        name: name.to_string(),
+        passed: result.is_ok(),
+        message: result.err(),
+        duration: started.elapsed(),
+    }
+}
+
+#[cfg(feature = "fault-injection")]
+fn forced_failure(name: &str) -> Option<String> {
+    std::env::var(SELF_CHECK_FAIL_ENV)
+        .is_ok_and(|value| value == name)
+        .then(|| format!("forced failure via {}", SELF_CHECK_FAIL_ENV))
+}
+
+#[cfg(not(feature = "fault-injection"))]
+fn forced_failure(_name: &str) -> Option<String> {
+    None
+}
+
+fn check_parse_rule() -> Result<(), String> {
+    let rule = parse_rule_file(SAMPLE_RULES).map_err(|err| err.to_string())?;
+    validate_rule_file(&rule).map_err(|errors| {
+        let codes: Vec<_> = errors.iter().map(|err| err.code.as_str()).collect();
+        format!("built-in rule failed validation: {}", codes.join(", "))
+    })
+}
+
+fn check_transform_sample() -> Result<(), String> {
+    let rule = parse_rule_file(SAMPLE_RULES).map_err(|err| err.to_string())?;
+    let output = transform(&rule, SAMPLE_INPUT, None).map_err(|err| err.to_string())?;
+    expect_output(
+        output,
+        json!([{ "id": 1, "name": "ALICE" }, { "id": 2, "name": "BOB" }]),
+    )
+}
+
+fn check_regex() -> Result<(), String> {
+    let rule = parse_rule_file(REGEX_RULES).map_err(|err| err.to_string())?;
+    let output = transform(&rule, r#"[{ "code": "A-12/b3" }]"#, None)
+        .map_err(|err| err.to_string())?;
+    expect_output(output, json!([{ "digits": "123" }]))
+}
+
+fn check_date_dst() -> Result<(), String> {
+    let rule = parse_rule_file(DST_RULES).map_err(|err| err.to_string())?;
+    let input = r#"[
+        { "at": "2024-03-10T01:59:59-05:00" },
+        { "at": "2024-03-10T03:00:00-04:00" }
+    ]"#;
+    let output = transform(&rule, input, None).map_err(|err| err.to_string())?;
+    expect_output(
+        output,
+        json!([{ "utc": "2024-03-10T06:59:59Z" }, { "utc": "2024-03-10T07:00:00Z" }]),
+    )
+}
+
+fn check_temp_file() -> Result<(), String> {
+    let path = std::env::current_dir()
+        .map_err(|err| format!("failed to resolve working directory: {}", err))?
+        .join(format!(".transform-rules-self-check-{}.tmp", std::process::id()));
+    let payload = "transform-rules self check";
+    let result = fs::write(&path, payload)
+        .and_then(|_| fs::read_to_string(&path))
+        .map_err(|err| format!("failed to use {}: {}", path.display(), err));
+    let _ = fs::remove_file(&path);
+    if result? != payload {
+        return Err(format!("{} did not round-trip", path.display()));
+    }
+    Ok(())
+}
+
+fn expect_output(actual: JsonValue, expected: JsonValue) -> Result<(), String> {
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(format!("unexpected output: {}", actual))
+    }
+}
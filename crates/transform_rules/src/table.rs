@@ -0,0 +1,405 @@
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::sync::Arc;
+
+use serde_json::{Map, Value as JsonValue};
+
+use crate::csv_input::read_csv_records;
+use crate::error::{TransformError, TransformErrorCode, TransformErrorKind, TransformWarning};
+use crate::model::{Expr, MappingSource, RuleFile, TableFormat};
+use crate::path::{get_path, parse_path, set_path, split_namespace, PathToken};
+use crate::transform::lookup_item_key;
+
+/// A lookup table read by [`load_table`].
+#[derive(Debug, Clone)]
+pub enum LoadedTable {
+    /// The records, as the array to put in the context under the table's name.
+    Records(JsonValue),
+    /// The records indexed by the table's `key`; see
+    /// [`TransformStream::with_tables`](crate::TransformStream::with_tables).
+    Indexed(IndexedTable),
+}
+
+impl LoadedTable {
+    /// The records as an array, for callers that pass the table in the context.
+    pub fn into_records(self) -> JsonValue {
+        match self {
+            LoadedTable::Records(records) => records,
+            LoadedTable::Indexed(table) => table.to_records(),
+        }
+    }
+}
+
+/// The records of a table grouped by the value at its `key`, built while the table was read.
+///
+/// Records without a key are dropped, as no lookup can match them. Clones share the records.
+#[derive(Debug, Clone)]
+pub struct IndexedTable {
+    name: String,
+    key: String,
+    // key -> (position in the file, record), in file order
+    rows: Arc<HashMap<String, Vec<(usize, JsonValue)>>>,
+    len: usize,
+}
+
+impl IndexedTable {
+    /// The name the table is read as, i.e. `context.<name>`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Number of records kept.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The kept records in file order.
+    pub fn to_records(&self) -> JsonValue {
+        let mut rows: Vec<_> = self.rows.values().flatten().collect();
+        rows.sort_by_key(|(position, _)| *position);
+        JsonValue::Array(rows.into_iter().map(|(_, record)| record.clone()).collect())
+    }
+
+    pub(crate) fn matches(&self, key: &str) -> impl Iterator<Item = &JsonValue> {
+        self.rows.get(key).into_iter().flatten().map(|(_, record)| record)
+    }
+}
+
+/// Reads the lookup table `name` from `reader` as its `tables` entry in `rule` declares.
+///
+/// Each record is cut down to `fields` as it is read, and `ndjson` and `csv` tables are read
+/// one record at a time. With a `key` that every lookup against `context.<name>` uses, the
+/// records are indexed while they are read instead of kept as an array. When the rule reads
+/// the table in a way the declaration does not cover (a path outside `fields`, another
+/// key_path, or `context.<name>` outside a lookup), full records or an array are kept instead,
+/// with a [`TransformErrorCode::TableFallback`] warning. A table without an entry is read as
+/// a JSON array.
+pub fn load_table<R: BufRead>(
+    rule: &RuleFile,
+    name: &str,
+    reader: R,
+) -> Result<(LoadedTable, Vec<TransformWarning>), TransformError> {
+    let path = format!("tables.{}", name);
+    let spec = rule
+        .tables
+        .as_ref()
+        .and_then(|tables| tables.get(name))
+        .cloned()
+        .unwrap_or_default();
+    let usage = TableUsage::of(rule, name);
+    let mut warnings = Vec::new();
+    let mut fallback = |message: String| {
+        warnings.push(
+            TransformWarning::new(TransformErrorKind::Degraded, message)
+                .with_code(TransformErrorCode::TableFallback)
+                .with_path(path.as_str()),
+        );
+    };
+
+    let mut fields = match &spec.fields {
+        Some(fields) => Some(
+            fields
+                .iter()
+                .enumerate()
+                .map(|(index, field)| {
+                    table_path(field, &format!("{}.fields[{}]", path, index))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        None => None,
+    };
+    if let Some(tokens) = &fields
+        && let Some(read) = usage.uncovered(tokens)
+    {
+        fallback(format!("{} is not in fields; full records are kept", read));
+        fields = None;
+    }
+
+    let mut key = match &spec.key {
+        Some(key) => Some((key.clone(), table_path(key, &format!("{}.key", path))?)),
+        None => None,
+    };
+    if let Some((declared, _)) = &key
+        && let Some(read) = usage.other_key(declared)
+    {
+        fallback(format!("{}, not the key {}; the records are not indexed", read, declared));
+        key = None;
+    }
+
+    let mut loader = TableLoader {
+        fields,
+        key_tokens: key.as_ref().map(|(_, tokens)| tokens.clone()),
+        records: Vec::new(),
+        rows: HashMap::new(),
+        len: 0,
+    };
+    read_table(spec.format, reader, &path, |record| loader.push(record))?;
+
+    let table = match key {
+        Some((key, _)) => LoadedTable::Indexed(IndexedTable {
+            name: name.to_string(),
+            key,
+            rows: Arc::new(loader.rows),
+            len: loader.len,
+        }),
+        None => LoadedTable::Records(JsonValue::Array(loader.records)),
+    };
+    Ok((table, warnings))
+}
+
+fn table_path(value: &str, path: &str) -> Result<Vec<PathToken>, TransformError> {
+    match parse_path(value) {
+        Ok(tokens) if tokens.iter().all(|token| matches!(token, PathToken::Key(_))) => Ok(tokens),
+        _ => Err(TransformError::new(
+            TransformErrorKind::InvalidInput,
+            "table path must be a key path without indexes",
+        )
+        .with_code(TransformErrorCode::InputConfig)
+        .with_path(path)),
+    }
+}
+
+fn read_table<R: BufRead>(
+    format: TableFormat,
+    reader: R,
+    path: &str,
+    mut each: impl FnMut(JsonValue),
+) -> Result<(), TransformError> {
+    let error = |code: TransformErrorCode, message: String| {
+        TransformError::new(TransformErrorKind::InvalidInput, message)
+            .with_code(code)
+            .with_path(path)
+    };
+    match format {
+        TableFormat::Json => {
+            let value: JsonValue = serde_json::from_reader(reader).map_err(|err| {
+                error(TransformErrorCode::InputParse, format!("table is not valid JSON: {}", err))
+            })?;
+            let JsonValue::Array(records) = value else {
+                let message = "table must be an array of records".to_string();
+                return Err(error(TransformErrorCode::InputParse, message));
+            };
+            records.into_iter().for_each(each);
+        }
+        TableFormat::Ndjson => {
+            for (index, line) in reader.lines().enumerate() {
+                let line = line.map_err(|err| {
+                    error(TransformErrorCode::InputRead, format!("failed to read table: {}", err))
+                })?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let record = serde_json::from_str(&line).map_err(|err| {
+                    let message = format!("table line is not valid JSON: {}", err);
+                    error(TransformErrorCode::InputParse, message).with_line(index + 1)
+                })?;
+                each(record);
+            }
+        }
+        TableFormat::Csv => {
+            read_csv_records(reader, |record| {
+                each(record);
+                Ok(())
+            })
+            .map_err(|err| {
+                error(TransformErrorCode::InputRead, format!("failed to read table CSV: {}", err))
+            })?;
+        }
+    }
+    Ok(())
+}
+
+struct TableLoader {
+    fields: Option<Vec<Vec<PathToken>>>,
+    key_tokens: Option<Vec<PathToken>>,
+    records: Vec<JsonValue>,
+    rows: HashMap<String, Vec<(usize, JsonValue)>>,
+    len: usize,
+}
+
+impl TableLoader {
+    fn push(&mut self, record: JsonValue) {
+        let record = match &self.fields {
+            Some(fields) => project(&record, fields),
+            None => record,
+        };
+        let position = self.len;
+        match &self.key_tokens {
+            Some(tokens) => {
+                let Some(key) = lookup_item_key(&record, tokens) else {
+                    return;
+                };
+                self.rows.entry(key).or_default().push((position, record));
+            }
+            None => self.records.push(record),
+        }
+        self.len += 1;
+    }
+}
+
+fn project(record: &JsonValue, fields: &[Vec<PathToken>]) -> JsonValue {
+    let mut projected = JsonValue::Object(Map::new());
+    for field in fields {
+        if let Some(value) = get_path(record, field) {
+            // Fields are key paths into a fresh object, so only a field below another can
+            // conflict, and the outer one already holds it.
+            let _ = set_path(&mut projected, field, value.clone());
+        }
+    }
+    projected
+}
+
+// How the rule reads `context.<name>`.
+struct TableUsage<'a> {
+    name: &'a str,
+    lookups: Vec<LookupUse>,
+    // A description of the first read that is not the collection of a lookup.
+    other: Option<String>,
+}
+
+// The key_path and output_path of a lookup over the table; `None` when not a string literal.
+struct LookupUse {
+    key_path: Option<String>,
+    output_path: Option<Option<String>>,
+}
+
+impl<'a> TableUsage<'a> {
+    fn of(rule: &RuleFile, name: &'a str) -> Self {
+        let mut usage = TableUsage {
+            name,
+            lookups: Vec::new(),
+            other: None,
+        };
+        for expr in rule.constants.iter().flat_map(|constants| constants.values()) {
+            usage.expr(expr);
+        }
+        if let Some(expr) = &rule.record_when {
+            usage.expr(expr);
+        }
+        if let Some(group) = &rule.group {
+            usage.expr(&group.key);
+            if let Some(expr) = &group.record_when {
+                usage.expr(expr);
+            }
+        }
+        for mapping in &rule.mappings {
+            match &mapping.source {
+                Some(MappingSource::Single(source)) => usage.reference(source),
+                Some(MappingSource::Fallback(sources)) => {
+                    sources.iter().for_each(|source| usage.reference(source))
+                }
+                None => {}
+            }
+            for expr in [&mapping.expr, &mapping.when, &mapping.default].into_iter().flatten() {
+                usage.expr(expr);
+            }
+        }
+        usage
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Ref(expr_ref) => self.reference(&expr_ref.ref_path),
+            Expr::Op(expr_op) => {
+                let is_lookup = matches!(expr_op.op.as_str(), "lookup" | "lookup_first");
+                if is_lookup
+                    && let Some(Expr::Ref(collection)) = expr_op.args.first()
+                    && self.is_table(&collection.ref_path)
+                {
+                    self.lookups.push(LookupUse {
+                        key_path: expr_op.args.get(1).and_then(string_literal),
+                        output_path: match expr_op.args.get(3) {
+                            Some(arg) => string_literal(arg).map(Some),
+                            None => Some(None),
+                        },
+                    });
+                    expr_op.args[1..].iter().for_each(|arg| self.expr(arg));
+                    return;
+                }
+                expr_op.args.iter().for_each(|arg| self.expr(arg));
+            }
+            Expr::Chain(chain) => chain.chain.iter().for_each(|step| self.expr(step)),
+            Expr::Literal(_) => {}
+        }
+    }
+
+    fn reference(&mut self, ref_path: &str) {
+        if self.other.is_none() && self.reads_table(ref_path) {
+            self.other = Some(format!("{} is read outside a lookup", ref_path));
+        }
+    }
+
+    // `context.<name>` itself.
+    fn is_table(&self, ref_path: &str) -> bool {
+        context_tokens(ref_path).is_some_and(|tokens| {
+            matches!(tokens.as_slice(), [PathToken::Key(key)] if key == self.name)
+        })
+    }
+
+    // `context.<name>` or anything below it.
+    fn reads_table(&self, ref_path: &str) -> bool {
+        context_tokens(ref_path).is_some_and(|tokens| {
+            matches!(tokens.first(), Some(PathToken::Key(key)) if key == self.name)
+        })
+    }
+
+    // The first read of a path `fields` does not hold.
+    fn uncovered(&self, fields: &[Vec<PathToken>]) -> Option<String> {
+        if let Some(other) = &self.other {
+            return Some(other.clone());
+        }
+        let covered = |path: &str| {
+            parse_path(path)
+                .is_ok_and(|tokens| fields.iter().any(|field| tokens.starts_with(field)))
+        };
+        for lookup in &self.lookups {
+            match (&lookup.key_path, &lookup.output_path) {
+                (Some(key_path), _) if !covered(key_path) => {
+                    return Some(format!("lookup key_path {}", key_path));
+                }
+                (_, Some(Some(output_path))) if !covered(output_path) => {
+                    return Some(format!("lookup output_path {}", output_path));
+                }
+                (None, _) | (_, None) => {
+                    return Some("a lookup path that is not a string literal".to_string());
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    // The first read of the table that is not a lookup by `key`.
+    fn other_key(&self, key: &str) -> Option<String> {
+        if let Some(other) = &self.other {
+            return Some(other.clone());
+        }
+        self.lookups.iter().find_map(|lookup| match &lookup.key_path {
+            Some(key_path) if key_path == key => None,
+            Some(key_path) => Some(format!("a lookup uses key_path {}", key_path)),
+            None => Some("a lookup key_path is not a string literal".to_string()),
+        })
+    }
+}
+
+fn context_tokens(ref_path: &str) -> Option<Vec<PathToken>> {
+    match split_namespace(ref_path)? {
+        ("context", path) => parse_path(path).ok(),
+        _ => None,
+    }
+}
+
+fn string_literal(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Literal(JsonValue::String(value)) => Some(value.clone()),
+        _ => None,
+    }
+}
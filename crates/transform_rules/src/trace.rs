@@ -0,0 +1,468 @@
+use serde_json::{json, Value as JsonValue};
+use std::cell::RefCell;
+
+use crate::error::{TransformError, TransformWarning};
+
+const DEFAULT_MAX_VALUE_LEN: usize = 256;
+const TRUNCATED_SUFFIX: &str = "...";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceOptions {
+    pub targets: Vec<String>,
+    pub max_value_len: usize,
+}
+
+impl TraceOptions {
+    pub fn new<I, S>(targets: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            targets: targets.into_iter().map(Into::into).collect(),
+            max_value_len: DEFAULT_MAX_VALUE_LEN,
+        }
+    }
+
+    pub fn with_max_value_len(mut self, max_value_len: usize) -> Self {
+        self.max_value_len = max_value_len;
+        self
+    }
+
+    pub fn matches(&self, target: &str) -> bool {
+        self.targets
+            .iter()
+            .any(|pattern| glob_match(pattern, target))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceEventKind {
+    Step,
+    Arg(usize),
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEvent {
+    pub record_index: usize,
+    pub target: String,
+    pub step: usize,
+    pub op: Option<String>,
+    pub kind: TraceEventKind,
+    /// JSON rendering of the value (or the error message); `None` when the value is missing.
+    pub value: Option<String>,
+    pub truncated: bool,
+}
+
+impl TraceEvent {
+    pub fn to_json(&self) -> JsonValue {
+        let mut value = json!({
+            "record_index": self.record_index,
+            "target": self.target,
+            "step": self.step,
+            "op": self.op,
+        });
+        match self.kind {
+            TraceEventKind::Step => {
+                value["kind"] = json!("step");
+            }
+            TraceEventKind::Arg(index) => {
+                value["kind"] = json!("arg");
+                value["arg"] = json!(index);
+            }
+            TraceEventKind::Error => {
+                value["kind"] = json!("error");
+            }
+        }
+        value["value"] = json!(self.value);
+        if self.truncated {
+            value["truncated"] = json!(true);
+        }
+        value
+    }
+}
+
+/// One mapping of a [`RecordTrace`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MappingTrace {
+    /// `mappings[<index>]` in the rule as loaded.
+    pub path: String,
+    pub group_path: Option<String>,
+    pub target: String,
+    /// Whether `when` passed; `None` when the mapping has no `when`.
+    pub when: Option<bool>,
+    /// Op args and steps evaluated for `when`.
+    pub when_steps: Vec<TraceEvent>,
+    /// Source, chain steps and op args as they were evaluated, then any error.
+    pub steps: Vec<TraceEvent>,
+    /// JSON rendering of the value after `default` and `type`; `None` when nothing was written.
+    pub value: Option<String>,
+    pub truncated: bool,
+    /// Whether `value` was written to `target`.
+    pub written: bool,
+}
+
+impl MappingTrace {
+    pub fn to_json(&self) -> JsonValue {
+        let steps = |events: &[TraceEvent]| -> Vec<JsonValue> {
+            events
+                .iter()
+                .map(|event| {
+                    let mut value = event.to_json();
+                    if let Some(map) = value.as_object_mut() {
+                        map.remove("record_index");
+                        map.remove("target");
+                    }
+                    value
+                })
+                .collect()
+        };
+        let mut value = json!({
+            "path": self.path,
+            "target": self.target,
+            "when": self.when,
+            "when_steps": steps(&self.when_steps),
+            "steps": steps(&self.steps),
+            "value": self.value,
+            "written": self.written,
+        });
+        if let Some(group_path) = &self.group_path {
+            value["group_path"] = json!(group_path);
+        }
+        if self.truncated {
+            value["truncated"] = json!(true);
+        }
+        value
+    }
+}
+
+/// Everything one record went through, from [`crate::transform_record_traced`].
+///
+/// Values in `mappings` over 256 bytes of JSON are cut with `...` and their full size, e.g.
+/// `"xxxx... (1042 bytes)"`; `output` is the record as written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordTrace {
+    /// Whether `record_when` kept the record; `None` when the rule has none.
+    pub record_when: Option<bool>,
+    /// The mappings in the order they ran; empty when `record_when` dropped the record.
+    pub mappings: Vec<MappingTrace>,
+    /// The mapped record; `None` when it was dropped or failed.
+    pub output: Option<JsonValue>,
+    pub warnings: Vec<TransformWarning>,
+    /// The failure that stopped the record, whatever `on_error` says.
+    pub error: Option<TransformError>,
+}
+
+impl RecordTrace {
+    /// `record_when`, `mappings` and `output`; warnings and errors are left to the caller's
+    /// own format.
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "record_when": self.record_when,
+            "mappings": self.mappings.iter().map(MappingTrace::to_json).collect::<Vec<_>>(),
+            "output": self.output,
+        })
+    }
+}
+
+pub(crate) struct TraceState {
+    options: TraceOptions,
+    record_index: usize,
+    events: Vec<TraceEvent>,
+    // Set for `transform_record_traced`: every mapping is kept whole instead of as events.
+    mappings: Option<Vec<MappingTrace>>,
+    record_when: Option<bool>,
+}
+
+impl TraceState {
+    pub(crate) fn new(options: TraceOptions) -> Self {
+        Self {
+            options,
+            record_index: 0,
+            events: Vec::new(),
+            mappings: None,
+            record_when: None,
+        }
+    }
+
+    pub(crate) fn detailed() -> Self {
+        Self {
+            mappings: Some(Vec::new()),
+            ..Self::new(TraceOptions::new(["*"]))
+        }
+    }
+
+    pub(crate) fn set_record_when(&mut self, keep: bool) {
+        self.record_when = Some(keep);
+    }
+
+    pub(crate) fn take_record(&mut self) -> (Option<bool>, Vec<MappingTrace>) {
+        let mappings = self.mappings.as_mut().map(std::mem::take).unwrap_or_default();
+        (self.record_when.take(), mappings)
+    }
+
+    pub(crate) fn set_record_index(&mut self, record_index: usize) {
+        self.record_index = record_index;
+    }
+
+    pub(crate) fn matches(&self, target: &str) -> bool {
+        self.options.matches(target)
+    }
+
+    pub(crate) fn take_events(&mut self) -> Vec<TraceEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub(crate) fn begin(&self, path: &str, target: &str) {
+        ACTIVE_BUFFER.with(|cell| {
+            *cell.borrow_mut() = Some(TraceBuffer {
+                record_index: self.record_index,
+                path: path.to_string(),
+                target: target.to_string(),
+                max_value_len: self.options.max_value_len,
+                sized: self.mappings.is_some(),
+                steps: Vec::new(),
+                ops: Vec::new(),
+                initial: None,
+                when_events: Vec::new(),
+                events: Vec::new(),
+            });
+        });
+    }
+
+    // Keeps the buffered events only when the mapping changed its source value,
+    // failed, or emitted a warning; a detailed trace keeps every mapping.
+    pub(crate) fn finish(
+        &mut self,
+        when: Option<bool>,
+        result: Result<Option<&JsonValue>, &TransformError>,
+        warned: bool,
+    ) {
+        let Some(mut buffer) = ACTIVE_BUFFER.with(|cell| cell.borrow_mut().take()) else {
+            return;
+        };
+        let keep = match result {
+            Err(err) => {
+                let message = match &err.path {
+                    Some(path) => format!("{} (path: {})", err.message, path),
+                    None => err.message.clone(),
+                };
+                let (value, truncated) = buffer.truncate(message);
+                buffer.events.push(TraceEvent {
+                    record_index: buffer.record_index,
+                    target: buffer.target.clone(),
+                    step: 0,
+                    op: None,
+                    kind: TraceEventKind::Error,
+                    value: Some(value),
+                    truncated,
+                });
+                true
+            }
+            Ok(value) => {
+                warned
+                    || match &buffer.initial {
+                        Some(initial) => initial.as_ref() != value,
+                        None => false,
+                    }
+            }
+        };
+        if let Some(mappings) = self.mappings.as_mut() {
+            let (value, truncated) = match result {
+                Ok(Some(value)) => {
+                    let (text, truncated) = buffer.render(value);
+                    (Some(text), truncated)
+                }
+                _ => (None, false),
+            };
+            mappings.push(MappingTrace {
+                path: buffer.path,
+                group_path: None,
+                target: buffer.target,
+                when,
+                when_steps: buffer.when_events,
+                steps: buffer.events,
+                value,
+                truncated,
+                written: matches!(result, Ok(Some(_))),
+            });
+        } else if keep {
+            self.events.append(&mut buffer.when_events);
+            self.events.append(&mut buffer.events);
+        }
+    }
+}
+
+struct TraceBuffer {
+    record_index: usize,
+    path: String,
+    target: String,
+    max_value_len: usize,
+    // Truncated values also give their full size.
+    sized: bool,
+    steps: Vec<usize>,
+    ops: Vec<String>,
+    initial: Option<Option<JsonValue>>,
+    when_events: Vec<TraceEvent>,
+    events: Vec<TraceEvent>,
+}
+
+impl TraceBuffer {
+    fn push(&mut self, op: Option<String>, kind: TraceEventKind, value: Option<&JsonValue>) {
+        let top_level = match kind {
+            TraceEventKind::Step => self.ops.is_empty(),
+            TraceEventKind::Arg(_) => self.ops.len() == 1,
+            TraceEventKind::Error => false,
+        };
+        if top_level && self.initial.is_none() {
+            self.initial = Some(value.cloned());
+        }
+        let (rendered, truncated) = match value {
+            Some(value) => {
+                let (text, truncated) = self.render(value);
+                (Some(text), truncated)
+            }
+            None => (None, false),
+        };
+        self.events.push(TraceEvent {
+            record_index: self.record_index,
+            target: self.target.clone(),
+            step: self.steps.last().copied().unwrap_or(0),
+            op,
+            kind,
+            value: rendered,
+            truncated,
+        });
+    }
+
+    fn render(&self, value: &JsonValue) -> (String, bool) {
+        self.truncate(serde_json::to_string(value).unwrap_or_default())
+    }
+
+    fn truncate(&self, text: String) -> (String, bool) {
+        let len = text.len();
+        let (mut text, truncated) = truncate(text, self.max_value_len);
+        if truncated && self.sized {
+            text.push_str(&format!(" ({} bytes)", len));
+        }
+        (text, truncated)
+    }
+}
+
+thread_local! {
+    static ACTIVE_BUFFER: RefCell<Option<TraceBuffer>> = const { RefCell::new(None) };
+}
+
+pub(crate) struct OpScope;
+
+impl OpScope {
+    pub(crate) fn enter(op: &str) -> Option<Self> {
+        ACTIVE_BUFFER.with(|cell| {
+            let mut cell = cell.borrow_mut();
+            let buffer = cell.as_mut()?;
+            buffer.ops.push(op.to_string());
+            Some(OpScope)
+        })
+    }
+}
+
+impl Drop for OpScope {
+    fn drop(&mut self) {
+        ACTIVE_BUFFER.with(|cell| {
+            if let Some(buffer) = cell.borrow_mut().as_mut() {
+                buffer.ops.pop();
+            }
+        });
+    }
+}
+
+pub(crate) struct StepScope;
+
+impl StepScope {
+    pub(crate) fn enter(step: usize) -> Option<Self> {
+        ACTIVE_BUFFER.with(|cell| {
+            let mut cell = cell.borrow_mut();
+            let buffer = cell.as_mut()?;
+            buffer.steps.push(step);
+            Some(StepScope)
+        })
+    }
+}
+
+impl Drop for StepScope {
+    fn drop(&mut self) {
+        ACTIVE_BUFFER.with(|cell| {
+            if let Some(buffer) = cell.borrow_mut().as_mut() {
+                buffer.steps.pop();
+            }
+        });
+    }
+}
+
+// Sets aside what `when` evaluated so the value's own first step is the one compared.
+pub(crate) fn end_when() {
+    ACTIVE_BUFFER.with(|cell| {
+        if let Some(buffer) = cell.borrow_mut().as_mut() {
+            buffer.when_events = std::mem::take(&mut buffer.events);
+            buffer.initial = None;
+        }
+    });
+}
+
+pub(crate) fn record_step(op: Option<&str>, value: Option<&JsonValue>) {
+    ACTIVE_BUFFER.with(|cell| {
+        if let Some(buffer) = cell.borrow_mut().as_mut() {
+            buffer.push(op.map(str::to_string), TraceEventKind::Step, value);
+        }
+    });
+}
+
+pub(crate) fn record_arg(index: usize, value: Option<&JsonValue>) {
+    ACTIVE_BUFFER.with(|cell| {
+        if let Some(buffer) = cell.borrow_mut().as_mut() {
+            let op = buffer.ops.last().cloned();
+            buffer.push(op, TraceEventKind::Arg(index), value);
+        }
+    });
+}
+
+fn truncate(mut text: String, max_len: usize) -> (String, bool) {
+    if text.len() <= max_len {
+        return (text, false);
+    }
+    let mut end = max_len;
+    while end > 0 && !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+    text.push_str(TRUNCATED_SUFFIX);
+    (text, true)
+}
+
+/// Matches `*` (any run of characters) and `?` (a single character); shared with the CLI.
+#[doc(hidden)]
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|ch| *ch == '*')
+}
@@ -1,18 +1,40 @@
-use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, SecondsFormat};
 use chrono::offset::TimeZone;
+use chrono_tz::Tz;
 use csv::ReaderBuilder;
 use regex::Regex;
 use serde_json::{Map, Value as JsonValue};
 use std::cmp::Ordering;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::io::{self, BufRead};
 use std::sync::{Mutex, OnceLock};
 
 use crate::cache::LruCache;
-use crate::error::{TransformError, TransformErrorKind, TransformWarning};
-use crate::model::{Expr, ExprChain, ExprOp, ExprRef, InputFormat, RuleFile};
-use crate::path::{get_path, parse_path, PathToken};
-
-const REGEX_CACHE_CAPACITY: usize = 128;
+use crate::constants::{self, ConstScope};
+use crate::env::{self, EnvScope};
+use crate::csv_input::{CsvColumnType, CSV_COLUMN_TYPES_MESSAGE};
+use crate::error::{
+    TransformError, TransformErrorCode, TransformErrorKind, TransformWarning, WarningSummary,
+};
+use crate::limits::{input_limit_error, ByteCounter, ParseLimits};
+use crate::lookup_index::{self, IndexScope, LookupIndexes};
+use crate::model::{
+    Expr, ExprChain, ExprOp, ExprRef, GroupSpec, InputFormat, MappingSource, OnError, RuleFile,
+};
+use crate::path::{
+    format_path, get_path, get_path_mut, parse_array_target, parse_path, remove_path,
+    split_namespace, PathToken,
+};
+use crate::table::IndexedTable;
+use crate::trace::{self, OpScope, RecordTrace, StepScope, TraceEvent, TraceOptions, TraceState};
+
+pub(crate) const REGEX_CACHE_CAPACITY: usize = 128;
+
+// Matches serde_json's own recursion limit; rules may only lower it.
+pub const DEFAULT_MAX_JSON_DEPTH: usize = 128;
+
+// Records read per worker thread before a parallel stream maps them.
+const PARALLEL_BATCH_PER_THREAD: usize = 256;
 
 fn regex_cache() -> &'static Mutex<LruCache<String, Regex>> {
     static REGEX_CACHE: OnceLock<Mutex<LruCache<String, Regex>>> = OnceLock::new();
@@ -30,6 +52,7 @@ fn cached_regex(pattern: &str, path: &str) -> Result<Regex, TransformError> {
 
     let regex = Regex::new(pattern).map_err(|_| {
         TransformError::new(TransformErrorKind::ExprError, "regex pattern is invalid")
+            .with_code(TransformErrorCode::BadRegex)
             .with_path(path)
     })?;
     {
@@ -57,77 +80,557 @@ pub fn preflight_validate(
 
 #[derive(Debug)]
 pub struct TransformStreamItem {
+    /// Zero-based index of the input record this item came from. An item for a group holds the
+    /// group index here too; see `group_index`.
+    pub record_index: usize,
+    /// With `group`, the zero-based index of the group this item maps, in the order the keys
+    /// first appear. `None` for items of single records, including the warnings and key errors
+    /// of records while they are grouped.
+    pub group_index: Option<usize>,
     pub output: Option<JsonValue>,
     pub warnings: Vec<TransformWarning>,
+    /// Record-level failures kept when `on_error` is `skip` or `collect`; `output` is `None`.
+    pub errors: Vec<TransformError>,
 }
 
 pub struct TransformStream<'a> {
     rule: &'a RuleFile,
     context: Option<&'a JsonValue>,
     records: InputRecordsIter<'a>,
+    record_index: usize,
+    trace: Option<Box<TraceState>>,
+    lookup_indexes: LookupIndexes,
+    // `constants` evaluated on the first `next`, as an object keyed by name.
+    constants: Option<JsonValue>,
+    limits: ParseLimits,
+    groups: Option<BufferedGroups>,
+    threads: usize,
+    allow_env: bool,
+    // Items mapped by the worker threads, yielded before anything else is read.
+    ready: VecDeque<Result<TransformStreamItem, TransformError>>,
+    // One set per worker thread, kept across batches.
+    worker_indexes: Vec<LookupIndexes>,
     done: bool,
 }
 
+// (record index, line, record) as read for a parallel batch.
+type BatchRecord = (usize, Option<usize>, Result<JsonValue, TransformError>);
+// The warnings and the output or failure of one mapped record.
+type MappedRecord = (Vec<TransformWarning>, Result<Option<JsonValue>, TransformError>);
+
+// Every input record is held here until the input ends; groups are mapped one per `next`.
+struct BufferedGroups {
+    // Warnings and skipped failures from the grouping pass, yielded before any group.
+    pending: VecDeque<TransformStreamItem>,
+    groups: std::vec::IntoIter<(JsonValue, Vec<JsonValue>)>,
+    next_index: usize,
+}
+
 impl<'a> TransformStream<'a> {
     fn new(
         rule: &'a RuleFile,
-        input: &'a str,
+        records: InputRecordsIter<'a>,
         context: Option<&'a JsonValue>,
-    ) -> Result<Self, TransformError> {
-        let records = input_records_iter(rule, input)?;
-        Ok(Self {
+    ) -> Self {
+        Self {
             rule,
             context,
             records,
+            record_index: 0,
+            trace: None,
+            lookup_indexes: LookupIndexes::default(),
+            constants: None,
+            limits: ParseLimits::default(),
+            groups: None,
+            threads: 1,
+            allow_env: false,
+            ready: VecDeque::new(),
+            worker_indexes: Vec::new(),
             done: false,
+        }
+    }
+
+    pub fn with_trace(mut self, options: TraceOptions) -> Self {
+        self.trace = Some(Box::new(TraceState::new(options)));
+        self
+    }
+
+    /// Aborts the stream once it reads more than `max_records` records or one larger than
+    /// `max_record_bytes`; the other limits apply to input text, see
+    /// [`transform_stream_with_limits`].
+    pub fn with_limits(mut self, limits: ParseLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Maps records on `threads` worker threads (`0` for one per available core), yielding
+    /// them, their warnings and their errors in input order.
+    ///
+    /// Records are read in batches and each is mapped on its own, so `out.*` refs behave as
+    /// they do on one thread. Streams with `group` or a trace still run on one thread; see
+    /// [`TransformStream::parallel_fallback`].
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = match threads {
+            0 => std::thread::available_parallelism().map_or(1, usize::from),
+            threads => threads,
+        };
+        self
+    }
+
+    /// Lets `env.*` refs read the process environment. Without it they fail with an
+    /// `ExprError`, so output only depends on the rules, the input and the context.
+    pub fn with_env(mut self, allow: bool) -> Self {
+        self.allow_env = allow;
+        self
+    }
+
+    /// Lets `lookup` and `lookup_first` read tables indexed by [`load_table`] as
+    /// `context.<name>`, which then need no array in the context.
+    ///
+    /// [`load_table`]: crate::load_table
+    pub fn with_tables(mut self, tables: &[IndexedTable]) -> Self {
+        for table in tables {
+            self.lookup_indexes.add_loaded(table.clone());
+        }
+        self
+    }
+
+    /// Why a stream given several threads runs on one, as a warning to report.
+    pub fn parallel_fallback(&self) -> Option<TransformWarning> {
+        if self.threads <= 1 {
+            return None;
+        }
+        if self.rule.group.is_some() {
+            let message = "rules with group are transformed on one thread";
+            let warning = TransformWarning::new(TransformErrorKind::Degraded, message)
+                .with_code(TransformErrorCode::SingleThread);
+            return Some(warning.with_path("group"));
+        }
+        self.trace.is_some().then(|| {
+            TransformWarning::new(
+                TransformErrorKind::Degraded,
+                "traced transforms run on one thread",
+            )
+            .with_code(TransformErrorCode::SingleThread)
+        })
+    }
+
+    fn is_parallel(&self) -> bool {
+        self.threads > 1 && self.rule.group.is_none() && self.trace.is_none()
+    }
+
+    pub fn is_tracing(&self) -> bool {
+        self.trace.is_some()
+    }
+
+    pub fn take_trace(&mut self) -> Vec<TraceEvent> {
+        match self.trace.as_mut() {
+            Some(trace) => trace.take_events(),
+            None => Vec::new(),
+        }
+    }
+
+    // The inner error is a record that could not be built (a CSV cell failing its column
+    // type) and goes through `on_error`; the outer one is unreadable input and always aborts.
+    #[allow(clippy::type_complexity)]
+    fn next_record(
+        &mut self,
+    ) -> Option<Result<(Result<JsonValue, TransformError>, usize), TransformError>> {
+        let record = match self.records.next()? {
+            Ok(record) => Ok(record),
+            Err(err) if err.kind == TransformErrorKind::TypeCastFailed => Err(err),
+            Err(err) => return Some(Err(err)),
+        };
+        let record_index = self.record_index;
+        self.record_index += 1;
+        if let Err(err) = self.check_record_limits(record.as_ref().ok(), record_index) {
+            return Some(Err(err));
+        }
+        Some(Ok((record, record_index)))
+    }
+
+    fn check_record_limits(
+        &self,
+        record: Option<&JsonValue>,
+        record_index: usize,
+    ) -> Result<(), TransformError> {
+        let at_record = |err: TransformError| {
+            let err = err.with_record_index(record_index);
+            match self.records.line() {
+                Some(line) => err.with_line(line),
+                None => err,
+            }
+        };
+        if let Some(max) = self.limits.max_records
+            && record_index >= max
+        {
+            return Err(at_record(input_limit_error(
+                "max_records",
+                format!("input has more than {} records", max),
+            )));
+        }
+        if let (Some(max), Some(record)) = (self.limits.max_record_bytes, record) {
+            let mut counter = ByteCounter::default();
+            serde_json::to_writer(&mut counter, record)?;
+            if counter.0 > max {
+                return Err(at_record(input_limit_error(
+                    "max_record_bytes",
+                    format!("record is {} bytes as JSON, more than {}", counter.0, max),
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    // A constant that fails to evaluate aborts the stream whatever `on_error` says.
+    fn eval_constants(&mut self) -> Result<(), TransformError> {
+        if self.constants.is_some() {
+            return Ok(());
+        }
+        let mut values = Map::new();
+        let empty_out = JsonValue::Object(Map::new());
+        let _env = EnvScope::enter(self.allow_env);
+        for (name, expr) in self.rule.constants.iter().flatten() {
+            let path = format!("constants.{}", name);
+            let value = eval_expr(expr, &JsonValue::Null, self.context, &empty_out, &path, None)?;
+            if let EvalValue::Value(value) = value {
+                values.insert(name.clone(), value);
+            }
+        }
+        self.constants = Some(JsonValue::Object(values));
+        Ok(())
+    }
+
+    // Tags warnings and the error with the record, and applies `on_error`.
+    fn finish_item(
+        &mut self,
+        record_index: usize,
+        line: Option<usize>,
+        mut warnings: Vec<TransformWarning>,
+        result: Result<Option<JsonValue>, TransformError>,
+    ) -> Result<TransformStreamItem, TransformError> {
+        for warning in &mut warnings {
+            warning.record_index = Some(record_index);
+            warning.line = line;
+        }
+        let mut item = TransformStreamItem {
+            record_index,
+            group_index: None,
+            output: None,
+            warnings,
+            errors: Vec::new(),
+        };
+        match result {
+            Ok(output) => item.output = output,
+            Err(mut err) => {
+                err.record_index = Some(record_index);
+                err.line = line;
+                if self.rule.on_error == OnError::Abort {
+                    self.done = true;
+                    return Err(err);
+                }
+                item.errors.push(err);
+            }
+        }
+        Ok(item)
+    }
+
+    fn buffer_groups(&mut self, group: &GroupSpec) -> Result<BufferedGroups, TransformError> {
+        let mut pending = VecDeque::new();
+        let mut groups: Vec<(JsonValue, Vec<JsonValue>)> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        let empty_out = JsonValue::Object(Map::new());
+
+        while let Some(next) = self.next_record() {
+            let (record, record_index) = next?;
+            let line = self.records.line();
+            let record = match record {
+                Ok(record) => record,
+                Err(err) => {
+                    pending.push_back(self.finish_item(record_index, line, Vec::new(), Err(err))?);
+                    continue;
+                }
+            };
+            let constants = ConstScope::enter(self.constants.get_or_insert(JsonValue::Null));
+            let indexes = IndexScope::enter(&mut self.lookup_indexes);
+            let env = EnvScope::enter(self.allow_env);
+            let mut warnings = Vec::new();
+            let keep = eval_record_when(
+                self.rule.record_when.as_ref(),
+                "record_when",
+                &record,
+                self.context,
+                &mut warnings,
+            ) && eval_record_when(
+                group.record_when.as_ref(),
+                "group.record_when",
+                &record,
+                self.context,
+                &mut warnings,
+            );
+            let key = if keep {
+                eval_expr(&group.key, &record, self.context, &empty_out, "group.key", None).map(
+                    |key| match key {
+                        EvalValue::Missing => Some(JsonValue::Null),
+                        EvalValue::Value(value) => Some(value),
+                    },
+                )
+            } else {
+                Ok(None)
+            };
+            drop(env);
+            drop(indexes);
+            drop(constants);
+
+            match key {
+                Ok(Some(key)) => {
+                    let hash_key = serde_json::to_string(&key).unwrap_or_default();
+                    let position = *positions.entry(hash_key).or_insert_with(|| {
+                        groups.push((key, Vec::new()));
+                        groups.len() - 1
+                    });
+                    groups[position].1.push(record);
+                    if warnings.is_empty() {
+                        continue;
+                    }
+                    pending.push_back(self.finish_item(record_index, line, warnings, Ok(None))?);
+                }
+                Ok(None) if warnings.is_empty() => {}
+                Ok(None) => {
+                    pending.push_back(self.finish_item(record_index, line, warnings, Ok(None))?)
+                }
+                Err(err) => {
+                    pending.push_back(self.finish_item(record_index, line, warnings, Err(err))?)
+                }
+            }
+        }
+
+        Ok(BufferedGroups {
+            pending,
+            groups: groups.into_iter(),
+            next_index: 0,
+        })
+    }
+
+    fn next_group(&mut self, group: &GroupSpec) -> Option<Result<TransformStreamItem, TransformError>> {
+        if self.groups.is_none() {
+            match self.buffer_groups(group) {
+                Ok(groups) => self.groups = Some(groups),
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+        let groups = self.groups.as_mut()?;
+        if let Some(item) = groups.pending.pop_front() {
+            return Some(Ok(item));
+        }
+        let Some((key, records)) = groups.groups.next() else {
+            self.done = true;
+            return None;
+        };
+        let group_index = groups.next_index;
+        groups.next_index += 1;
+        if let Some(trace) = self.trace.as_mut() {
+            trace.set_record_index(group_index);
+        }
+
+        let record = serde_json::json!({ "key": key, "records": records });
+        let constants = ConstScope::enter(self.constants.get_or_insert(JsonValue::Null));
+        let indexes = IndexScope::enter(&mut self.lookup_indexes);
+        let env = EnvScope::enter(self.allow_env);
+        let mut warnings = Vec::new();
+        let result = apply_mappings(
+            self.rule,
+            &record,
+            self.context,
+            &mut warnings,
+            self.trace.as_deref_mut(),
+        );
+        drop(env);
+        drop(indexes);
+        drop(constants);
+        let item = self.finish_item(group_index, None, warnings, result.map(Some));
+        Some(item.map(|item| TransformStreamItem {
+            group_index: Some(group_index),
+            ..item
+        }))
+    }
+
+    fn next_parallel(&mut self) -> Option<Result<TransformStreamItem, TransformError>> {
+        while self.ready.is_empty() && !self.done {
+            self.map_batch();
+        }
+        self.ready.pop_front()
+    }
+
+    // Reads up to a batch of records, maps them on the workers and queues the items in order,
+    // stopping at the first failure that aborts the stream.
+    fn map_batch(&mut self) {
+        let batch_len = self.threads * PARALLEL_BATCH_PER_THREAD;
+        let mut batch: Vec<BatchRecord> = Vec::with_capacity(batch_len);
+        let mut read_error = None;
+        while batch.len() < batch_len {
+            match self.next_record() {
+                Some(Ok((record, record_index))) => {
+                    batch.push((record_index, self.records.line(), record))
+                }
+                Some(Err(err)) => {
+                    read_error = Some(err);
+                    break;
+                }
+                None => {
+                    self.done = true;
+                    break;
+                }
+            }
+        }
+
+        let mapped = self.map_on_workers(&batch);
+        for ((record_index, line, record), mapped) in batch.into_iter().zip(mapped) {
+            let (warnings, result) = match (record, mapped) {
+                (Err(err), _) => (Vec::new(), Err(err)),
+                (Ok(_), Some(mapped)) => mapped,
+                (Ok(_), None) => continue,
+            };
+            let item = self.finish_item(record_index, line, warnings, result);
+            let aborted = item.is_err();
+            self.ready.push_back(item);
+            if aborted {
+                return;
+            }
+        }
+        if let Some(err) = read_error {
+            self.done = true;
+            self.ready.push_back(Err(err));
+        }
+    }
+
+    // Splits `batch` into one contiguous chunk per worker; each worker installs its own copy
+    // of the constants and its own lookup indexes.
+    fn map_on_workers(&mut self, batch: &[BatchRecord]) -> Vec<Option<MappedRecord>> {
+        let (rule, context, allow_env) = (self.rule, self.context, self.allow_env);
+        let constants = self.constants.clone().unwrap_or(JsonValue::Null);
+        let chunk_len = batch.len().div_ceil(self.threads).max(1);
+        let loaded = &self.lookup_indexes;
+        self.worker_indexes
+            .resize_with(self.threads, || LookupIndexes::with_loaded_of(loaded));
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = batch
+                .chunks(chunk_len)
+                .zip(self.worker_indexes.iter_mut())
+                .map(|(chunk, indexes)| {
+                    let mut constants = constants.clone();
+                    scope.spawn(move || {
+                        let _constants = ConstScope::enter(&mut constants);
+                        let _indexes = IndexScope::enter(indexes);
+                        let _env = EnvScope::enter(allow_env);
+                        chunk
+                            .iter()
+                            .map(|(_, _, record)| {
+                                map_record(rule, record.as_ref().ok()?, context, None)
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| {
+                    worker
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                })
+                .collect()
         })
     }
 }
 
+// `record_when` and the mappings for one record; `None` when `record_when` drops it quietly.
+fn map_record(
+    rule: &RuleFile,
+    record: &JsonValue,
+    context: Option<&JsonValue>,
+    mut trace: Option<&mut TraceState>,
+) -> Option<MappedRecord> {
+    let mut warnings = Vec::new();
+    let keep = eval_record_when(
+        rule.record_when.as_ref(),
+        "record_when",
+        record,
+        context,
+        &mut warnings,
+    );
+    if let Some(trace) = trace.as_deref_mut()
+        && rule.record_when.is_some()
+    {
+        trace.set_record_when(keep);
+    }
+    if !keep && warnings.is_empty() {
+        return None;
+    }
+    let result = if keep {
+        apply_mappings(rule, record, context, &mut warnings, trace).map(Some)
+    } else {
+        Ok(None)
+    };
+    Some((warnings, result))
+}
+
 impl<'a> Iterator for TransformStream<'a> {
     type Item = Result<TransformStreamItem, TransformError>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(item) = self.ready.pop_front() {
+            return Some(item);
+        }
         if self.done {
             return None;
         }
+        if let Err(err) = self.eval_constants() {
+            self.done = true;
+            return Some(Err(err));
+        }
+        if let Some(group) = &self.rule.group {
+            return self.next_group(group);
+        }
+        if self.is_parallel() {
+            return self.next_parallel();
+        }
 
         loop {
-            let record = match self.records.next() {
+            let (record, record_index) = match self.next_record() {
                 None => {
                     self.done = true;
                     return None;
                 }
-                Some(Ok(record)) => record,
+                Some(Ok(next)) => next,
                 Some(Err(err)) => {
                     self.done = true;
                     return Some(Err(err));
                 }
             };
-
-            let mut warnings = Vec::new();
-            if !eval_record_when(self.rule, &record, self.context, &mut warnings) {
-                if warnings.is_empty() {
-                    continue;
-                }
-                return Some(Ok(TransformStreamItem {
-                    output: None,
-                    warnings,
-                }));
+            let line = self.records.line();
+            let record = match record {
+                Ok(record) => record,
+                Err(err) => return Some(self.finish_item(record_index, line, Vec::new(), Err(err))),
+            };
+            if let Some(trace) = self.trace.as_mut() {
+                trace.set_record_index(record_index);
             }
 
-            match apply_mappings(self.rule, &record, self.context, &mut warnings) {
-                Ok(output) => {
-                    return Some(Ok(TransformStreamItem {
-                        output: Some(output),
-                        warnings,
-                    }))
-                }
-                Err(err) => {
-                    self.done = true;
-                    return Some(Err(err));
-                }
-            }
+            let constants = ConstScope::enter(self.constants.get_or_insert(JsonValue::Null));
+            let indexes = IndexScope::enter(&mut self.lookup_indexes);
+            let env = EnvScope::enter(self.allow_env);
+            let mapped = map_record(self.rule, &record, self.context, self.trace.as_deref_mut());
+            drop(env);
+            drop(indexes);
+            drop(constants);
+            let Some((warnings, result)) = mapped else {
+                continue;
+            };
+            return Some(self.finish_item(record_index, line, warnings, result));
         }
     }
 }
@@ -137,7 +640,158 @@ pub fn transform_stream<'a>(
     input: &'a str,
     context: Option<&'a JsonValue>,
 ) -> Result<TransformStream<'a>, TransformError> {
-    TransformStream::new(rule, input, context)
+    let records = input_records_iter(rule, input)?;
+    Ok(TransformStream::new(rule, records, context))
+}
+
+/// Like [`transform_stream`], refusing input text over `limits` before parsing it.
+///
+/// `max_bytes` and `max_depth` are checked up front (the depth for JSON and NDJSON input only),
+/// and the stream enforces `max_records` and `max_record_bytes` as it reads; all of them fail
+/// with [`TransformErrorKind::InvalidInput`]. `max_mappings` applies to rules only.
+pub fn transform_stream_with_limits<'a>(
+    rule: &'a RuleFile,
+    input: &'a str,
+    context: Option<&'a JsonValue>,
+    limits: &ParseLimits,
+) -> Result<TransformStream<'a>, TransformError> {
+    if let Some(max) = limits.max_bytes
+        && input.len() > max
+    {
+        return Err(input_limit_error(
+            "max_bytes",
+            format!("input is {} bytes, more than {}", input.len(), max),
+        ));
+    }
+    if let Some(max) = limits.max_depth {
+        let depth_error =
+            |err: TransformError| input_limit_error("max_depth", err.message);
+        match rule.input.format {
+            InputFormat::Json => check_json_depth(input, max).map_err(depth_error)?,
+            InputFormat::Ndjson => {
+                for (index, line) in input.lines().enumerate() {
+                    check_json_depth(line, max)
+                        .map_err(|err| depth_error(err).with_line(index + 1))?;
+                }
+            }
+            InputFormat::Csv => {}
+        }
+    }
+    Ok(transform_stream(rule, input, context)?.with_limits(*limits))
+}
+
+/// Like [`transform_stream`], but pulls records from `reader` as the stream is consumed.
+///
+/// CSV rows and the elements of a top-level JSON array are parsed one at a time, so malformed
+/// or non-UTF-8 input is reported when the stream reaches it rather than up front. JSON input
+/// with `records_path` (or a single top-level object) is read fully before the first record.
+pub fn transform_stream_from_reader<'a, R>(
+    rule: &'a RuleFile,
+    reader: R,
+    context: Option<&'a JsonValue>,
+) -> Result<TransformStream<'a>, TransformError>
+where
+    R: BufRead + 'a,
+{
+    let reader: Box<dyn BufRead + 'a> = Box::new(reader);
+    let records = match rule.input.format {
+        InputFormat::Csv => InputRecordsIter::CsvReader(CsvRecordIter::new(rule, reader)?),
+        InputFormat::Json => json_reader_records(rule, reader)?,
+        InputFormat::Ndjson => {
+            InputRecordsIter::NdjsonReader(NdjsonRecordIter::new(reader, json_max_depth(rule)))
+        }
+    };
+    Ok(TransformStream::new(rule, records, context))
+}
+
+/// Like [`transform_stream`], over records that are already parsed.
+///
+/// The `input` section is not consulted: `format`, `records_path` and `max_depth` only apply
+/// to input text, so each element of `records` is one record (see [`select_json_records`]).
+pub fn transform_values_stream<'a>(
+    rule: &'a RuleFile,
+    records: &'a [JsonValue],
+    context: Option<&'a JsonValue>,
+) -> TransformStream<'a> {
+    TransformStream::new(rule, InputRecordsIter::Values(records.iter()), context)
+}
+
+/// Like [`transform_with_warnings`], over records that are already parsed.
+pub fn transform_values(
+    rule: &RuleFile,
+    records: &[JsonValue],
+    context: Option<&JsonValue>,
+) -> Result<(JsonValue, Vec<TransformWarning>), TransformError> {
+    collect_stream(rule, transform_values_stream(rule, records, context))
+}
+
+/// Maps one record as [`transform_values`] would, recording what each mapping did.
+///
+/// Constants and lookup indexes are built as for a stream. With `group`, the record is mapped
+/// as a group of its own.
+pub fn transform_record_traced(
+    rule: &RuleFile,
+    record: &JsonValue,
+    context: Option<&JsonValue>,
+) -> RecordTrace {
+    let mut stream = transform_values_stream(rule, std::slice::from_ref(record), context);
+    stream.trace = Some(Box::new(TraceState::detailed()));
+    let mut output = None;
+    let mut warnings = Vec::new();
+    let mut error = None;
+    for item in stream.by_ref() {
+        match item {
+            Ok(item) => {
+                output = item.output;
+                warnings.extend(item.warnings);
+                error = item.errors.into_iter().next().or(error);
+            }
+            Err(err) => error = Some(err),
+        }
+    }
+    let (record_when, mut mappings) = stream
+        .trace
+        .as_mut()
+        .map(|trace| trace.take_record())
+        .unwrap_or_default();
+    for mapping in &mut mappings {
+        mapping.group_path = rule.group_path(&mapping.path);
+    }
+    // There is only the one record, wherever the caller took it from.
+    for warning in &mut warnings {
+        warning.record_index = None;
+    }
+    if let Some(error) = error.as_mut() {
+        error.record_index = None;
+    }
+    RecordTrace {
+        record_when,
+        mappings,
+        output,
+        warnings,
+        error,
+    }
+}
+
+/// The record at `record_index` of `input`, read as [`transform_stream`] reads it; `None`
+/// past the last record.
+///
+/// Input that a stream could not read past, such as a malformed NDJSON line, fails here too.
+pub fn input_record(
+    rule: &RuleFile,
+    input: &str,
+    record_index: usize,
+) -> Result<Option<JsonValue>, TransformError> {
+    for (index, record) in input_records_iter(rule, input)?.enumerate() {
+        match record {
+            Ok(record) if index == record_index => return Ok(Some(record)),
+            Err(err) if index == record_index || err.kind != TransformErrorKind::TypeCastFailed => {
+                return Err(err.with_record_index(index));
+            }
+            _ => {}
+        }
+    }
+    Ok(None)
 }
 
 pub fn transform_with_warnings(
@@ -145,12 +799,48 @@ pub fn transform_with_warnings(
     input: &str,
     context: Option<&JsonValue>,
 ) -> Result<(JsonValue, Vec<TransformWarning>), TransformError> {
-    let mut warnings = Vec::new();
+    collect_stream(rule, transform_stream(rule, input, context)?)
+}
+
+/// Like [`transform_with_warnings`], mapping records on `threads` worker threads (`0` for one
+/// per available core); see [`TransformStream::with_threads`].
+///
+/// The output and warnings are the same as on one thread. When the rule cannot be mapped in
+/// parallel, the reason is the first warning.
+pub fn transform_with_warnings_parallel(
+    rule: &RuleFile,
+    input: &str,
+    context: Option<&JsonValue>,
+    threads: usize,
+) -> Result<(JsonValue, Vec<TransformWarning>), TransformError> {
+    let stream = transform_stream(rule, input, context)?.with_threads(threads);
+    let fallback = stream.parallel_fallback();
+    let (output, mut warnings): (JsonValue, Vec<_>) = collect_stream(rule, stream)?;
+    warnings.splice(0..0, fallback);
+    Ok((output, warnings))
+}
+
+/// Like `transform_with_warnings`, with identical warnings folded into counted groups.
+pub fn transform_with_warning_summary(
+    rule: &RuleFile,
+    input: &str,
+    context: Option<&JsonValue>,
+) -> Result<(JsonValue, WarningSummary), TransformError> {
+    collect_stream(rule, transform_stream(rule, input, context)?)
+}
+
+fn collect_stream<W: Default + Extend<TransformWarning>>(
+    rule: &RuleFile,
+    stream: TransformStream<'_>,
+) -> Result<(JsonValue, W), TransformError> {
+    let mut warnings = W::default();
     let mut output_records = Vec::new();
-    let stream = transform_stream(rule, input, context)?;
     for item in stream {
         let item = item?;
         warnings.extend(item.warnings);
+        if rule.on_error == OnError::Collect {
+            warnings.extend(item.errors.into_iter().map(TransformWarning::from));
+        }
         if let Some(output) = item.output {
             output_records.push(output);
         }
@@ -164,10 +854,27 @@ pub fn preflight_validate_with_warnings(
     input: &str,
     context: Option<&JsonValue>,
 ) -> Result<Vec<TransformWarning>, TransformError> {
-    let mut warnings = Vec::new();
-    let stream = transform_stream(rule, input, context)?;
+    preflight_stream(transform_stream(rule, input, context)?)
+}
+
+/// Like `preflight_validate_with_warnings`, with identical warnings folded into counted groups.
+pub fn preflight_validate_with_warning_summary(
+    rule: &RuleFile,
+    input: &str,
+    context: Option<&JsonValue>,
+) -> Result<WarningSummary, TransformError> {
+    preflight_stream(transform_stream(rule, input, context)?)
+}
+
+fn preflight_stream<W: Default + Extend<TransformWarning>>(
+    stream: TransformStream<'_>,
+) -> Result<W, TransformError> {
+    let mut warnings = W::default();
     for item in stream {
-        let item = item?;
+        let mut item = item?;
+        if !item.errors.is_empty() {
+            return Err(item.errors.remove(0));
+        }
         warnings.extend(item.warnings);
     }
     Ok(warnings)
@@ -178,36 +885,88 @@ fn apply_mappings(
     record: &JsonValue,
     context: Option<&JsonValue>,
     warnings: &mut Vec<TransformWarning>,
+    mut trace: Option<&mut TraceState>,
 ) -> Result<JsonValue, TransformError> {
     let mut out = JsonValue::Object(Map::new());
     for (index, mapping) in rule.mappings.iter().enumerate() {
         let mapping_path = format!("mappings[{}]", index);
-        if !eval_when(mapping, record, context, &out, &mapping_path, warnings) {
-            continue;
+        let trace = trace
+            .as_deref_mut()
+            .filter(|trace| trace.matches(&mapping.target));
+        if let Some(trace) = trace.as_deref() {
+            trace.begin(&mapping_path, &mapping.target);
         }
-        let value = eval_mapping(mapping, record, context, &out, &mapping_path)?;
-        if let Some(value) = value {
-            set_path(&mut out, &mapping.target, value, &mapping_path)?;
+        let warning_count = warnings.len();
+        let passed = eval_when(mapping, record, context, &out, &mapping_path, warnings);
+        if mapping.when.is_some() {
+            trace::end_when();
+        }
+        let value = if passed {
+            eval_mapping(mapping, record, context, &out, &mapping_path)
+        } else {
+            Ok(None)
+        };
+        // The written value is only kept for the trace.
+        let written = value.and_then(|value| match value {
+            Some(value) => {
+                let traced = trace.is_some().then(|| value.to_json());
+                set_target(&mut out, &mapping.target, value, &mapping_path).map(|()| traced)
+            }
+            None => Ok(None),
+        });
+        if let Some(trace) = trace {
+            trace.finish(
+                mapping.when.as_ref().map(|_| passed),
+                written.as_ref().map(Option::as_ref),
+                warnings.len() > warning_count,
+            );
+        }
+        if rule.mapping_groups.is_some() {
+            for warning in &mut warnings[warning_count..] {
+                if let Some(path) = &warning.path {
+                    warning.group_path = rule.group_path(path);
+                }
+            }
         }
+        written.map_err(|err| with_group_path(rule, err))?;
     }
     Ok(out)
 }
 
+fn with_group_path(rule: &RuleFile, mut err: TransformError) -> TransformError {
+    if let Some(path) = &err.path {
+        err.group_path = rule.group_path(path);
+    }
+    err
+}
+
 fn input_records_iter<'a>(
     rule: &RuleFile,
     input: &'a str,
 ) -> Result<InputRecordsIter<'a>, TransformError> {
     match rule.input.format {
-        InputFormat::Csv => Ok(InputRecordsIter::Csv(CsvRecordIter::new(rule, input)?)),
+        InputFormat::Csv => Ok(InputRecordsIter::Csv(CsvRecordIter::new(
+            rule,
+            input.as_bytes(),
+        )?)),
         InputFormat::Json => Ok(InputRecordsIter::Json(JsonRecordIter::new(parse_json(
             rule, input,
         )?))),
+        InputFormat::Ndjson => Ok(InputRecordsIter::Ndjson(NdjsonRecordIter::new(
+            input.as_bytes(),
+            json_max_depth(rule),
+        ))),
     }
 }
 
 enum InputRecordsIter<'a> {
-    Csv(CsvRecordIter<'a>),
+    Csv(CsvRecordIter<&'a [u8]>),
+    CsvReader(CsvRecordIter<Box<dyn BufRead + 'a>>),
     Json(JsonRecordIter),
+    JsonReader(JsonArrayIter<Box<dyn BufRead + 'a>>),
+    Ndjson(NdjsonRecordIter<&'a [u8]>),
+    NdjsonReader(NdjsonRecordIter<Box<dyn BufRead + 'a>>),
+    Values(std::slice::Iter<'a, JsonValue>),
 }
 
 impl<'a> Iterator for InputRecordsIter<'a> {
@@ -216,24 +975,48 @@ impl<'a> Iterator for InputRecordsIter<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         match self {
             InputRecordsIter::Csv(iter) => iter.next(),
+            InputRecordsIter::CsvReader(iter) => iter.next(),
             InputRecordsIter::Json(iter) => iter.next(),
+            InputRecordsIter::JsonReader(iter) => iter.next(),
+            InputRecordsIter::Ndjson(iter) => iter.next(),
+            InputRecordsIter::NdjsonReader(iter) => iter.next(),
+            InputRecordsIter::Values(iter) => iter.next().cloned().map(Ok),
+        }
+    }
+}
+
+impl InputRecordsIter<'_> {
+    // Source line of the record last returned, when the format has one.
+    fn line(&self) -> Option<usize> {
+        match self {
+            InputRecordsIter::Csv(iter) => iter.line,
+            InputRecordsIter::CsvReader(iter) => iter.line,
+            InputRecordsIter::Json(_)
+            | InputRecordsIter::JsonReader(_)
+            | InputRecordsIter::Values(_) => None,
+            InputRecordsIter::Ndjson(iter) => Some(iter.line_number),
+            InputRecordsIter::NdjsonReader(iter) => Some(iter.line_number),
         }
     }
 }
 
-struct CsvRecordIter<'a> {
-    reader: csv::Reader<&'a [u8]>,
+struct CsvRecordIter<R> {
+    reader: csv::Reader<R>,
     headers: Vec<String>,
+    // Column type for each header, from `csv.columns` (`string` when not listed).
+    types: Vec<CsvColumnType>,
+    line: Option<usize>,
     done: bool,
 }
 
-impl<'a> CsvRecordIter<'a> {
-    fn new(rule: &RuleFile, input: &'a str) -> Result<Self, TransformError> {
+impl<R: io::Read> CsvRecordIter<R> {
+    fn new(rule: &RuleFile, input: R) -> Result<Self, TransformError> {
         let csv_spec = rule.input.csv.as_ref().ok_or_else(|| {
             TransformError::new(
                 TransformErrorKind::InvalidInput,
                 "input.csv is required when format=csv",
             )
+            .with_code(TransformErrorCode::InputConfig)
         })?;
 
         let delimiter_chars: Vec<char> = csv_spec.delimiter.chars().collect();
@@ -241,14 +1024,15 @@ impl<'a> CsvRecordIter<'a> {
             return Err(TransformError::new(
                 TransformErrorKind::InvalidInput,
                 "csv.delimiter must be a single character",
-            ));
+            )
+            .with_code(TransformErrorCode::InputConfig));
         }
         let delimiter = delimiter_chars[0] as u8;
 
         let mut reader = ReaderBuilder::new()
             .delimiter(delimiter)
             .has_headers(csv_spec.has_header)
-            .from_reader(input.as_bytes());
+            .from_reader(input);
 
         let headers: Vec<String> = if csv_spec.has_header {
             let header_record = reader.headers().map_err(|err| {
@@ -256,6 +1040,7 @@ impl<'a> CsvRecordIter<'a> {
                     TransformErrorKind::InvalidInput,
                     format!("failed to read csv header: {}", err),
                 )
+                .with_code(TransformErrorCode::InputRead)
             })?;
             header_record.iter().map(|s| s.to_string()).collect()
         } else {
@@ -264,19 +1049,38 @@ impl<'a> CsvRecordIter<'a> {
                     TransformErrorKind::InvalidInput,
                     "csv.columns is required when has_header=false",
                 )
+                .with_code(TransformErrorCode::InputConfig)
             })?;
             columns.iter().map(|col| col.name.clone()).collect()
         };
 
+        let mut types = vec![CsvColumnType::String; headers.len()];
+        for column in csv_spec.columns.iter().flatten() {
+            let Some(type_name) = column.value_type.as_deref() else {
+                continue;
+            };
+            let column_type = CsvColumnType::parse(type_name).ok_or_else(|| {
+                TransformError::new(TransformErrorKind::InvalidInput, CSV_COLUMN_TYPES_MESSAGE)
+                    .with_code(TransformErrorCode::InputConfig)
+            })?;
+            for (header, slot) in headers.iter().zip(types.iter_mut()) {
+                if *header == column.name {
+                    *slot = column_type;
+                }
+            }
+        }
+
         Ok(Self {
             reader,
             headers,
+            types,
+            line: None,
             done: false,
         })
     }
 }
 
-impl<'a> Iterator for CsvRecordIter<'a> {
+impl<R: io::Read> Iterator for CsvRecordIter<R> {
     type Item = Result<JsonValue, TransformError>;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -291,15 +1095,22 @@ impl<'a> Iterator for CsvRecordIter<'a> {
                     self.done = true;
                     return None;
                 }
-                let obj = record_to_object(&self.headers, &record);
-                Some(Ok(JsonValue::Object(obj)))
+                self.line = record.position().map(|pos| pos.line() as usize);
+                Some(record_to_object(&self.headers, &self.types, &record).map(JsonValue::Object))
             }
             Err(err) => {
                 self.done = true;
+                let message = match err.kind() {
+                    csv::ErrorKind::Utf8 { pos: Some(pos), .. } => {
+                        format!("invalid UTF-8 in csv record at line {}", pos.line())
+                    }
+                    _ => format!("failed to read csv record: {}", err),
+                };
                 Some(Err(TransformError::new(
                     TransformErrorKind::InvalidInput,
-                    format!("failed to read csv record: {}", err),
-                )))
+                    message,
+                )
+                .with_code(TransformErrorCode::InputRead)))
             }
         }
     }
@@ -325,50 +1136,459 @@ impl Iterator for JsonRecordIter {
     }
 }
 
-fn parse_json(rule: &RuleFile, input: &str) -> Result<Vec<JsonValue>, TransformError> {
-    let value: JsonValue = serde_json::from_str(input).map_err(|err| {
+fn json_max_depth(rule: &RuleFile) -> usize {
+    rule.input
+        .json
+        .as_ref()
+        .and_then(|json| json.max_depth)
+        .unwrap_or(DEFAULT_MAX_JSON_DEPTH)
+}
+
+fn json_reader_records<'a>(
+    rule: &RuleFile,
+    mut reader: Box<dyn BufRead + 'a>,
+) -> Result<InputRecordsIter<'a>, TransformError> {
+    let has_records_path = rule
+        .input
+        .json
+        .as_ref()
+        .is_some_and(|json| json.records_path.is_some());
+    let mut leading = Vec::new();
+    if !has_records_path {
+        loop {
+            let byte = match reader.fill_buf() {
+                Ok(buf) => buf.first().copied(),
+                Err(err) => return Err(read_input_error(err)),
+            };
+            match byte {
+                Some(b'[') => {
+                    reader.consume(1);
+                    let offset = leading.len() + 1;
+                    return Ok(InputRecordsIter::JsonReader(JsonArrayIter::new(
+                        reader,
+                        offset,
+                        json_max_depth(rule),
+                    )));
+                }
+                Some(byte) if byte.is_ascii_whitespace() => {
+                    leading.push(byte);
+                    reader.consume(1);
+                }
+                _ => break,
+            }
+        }
+    }
+
+    // Anything but a top-level array needs the whole document.
+    reader.read_to_end(&mut leading).map_err(read_input_error)?;
+    let input = String::from_utf8(leading).map_err(|err| {
         TransformError::new(
             TransformErrorKind::InvalidInput,
-            format!("failed to parse JSON input: {}", err),
+            format!(
+                "invalid UTF-8 in JSON input at byte {}",
+                err.utf8_error().valid_up_to()
+            ),
         )
+        .with_code(TransformErrorCode::InputRead)
     })?;
+    Ok(InputRecordsIter::Json(JsonRecordIter::new(parse_json(
+        rule, &input,
+    )?)))
+}
+
+fn read_input_error(err: io::Error) -> TransformError {
+    TransformError::new(
+        TransformErrorKind::InvalidInput,
+        format!("failed to read input: {}", err),
+    )
+    .with_code(TransformErrorCode::InputRead)
+}
+
+// Splits the elements of a top-level JSON array off the reader one at a time. The depth
+// limit is enforced while scanning, with the array itself at depth 1 as in `check_json_depth`.
+struct JsonArrayIter<R> {
+    reader: R,
+    offset: usize,
+    max_depth: usize,
+    element: Vec<u8>,
+    record_index: usize,
+    closed: bool,
+    done: bool,
+}
+
+impl<R: BufRead> JsonArrayIter<R> {
+    fn new(reader: R, offset: usize, max_depth: usize) -> Self {
+        Self {
+            reader,
+            offset,
+            max_depth,
+            element: Vec::new(),
+            record_index: 0,
+            closed: false,
+            done: false,
+        }
+    }
+
+    fn next_byte(&mut self) -> Result<Option<u8>, TransformError> {
+        let byte = match self.reader.fill_buf() {
+            Ok(buf) => buf.first().copied(),
+            Err(err) => return Err(read_input_error(err)),
+        };
+        if byte.is_some() {
+            self.reader.consume(1);
+            self.offset += 1;
+        }
+        Ok(byte)
+    }
+
+    fn skip_whitespace(&mut self) -> Result<Option<u8>, TransformError> {
+        loop {
+            match self.next_byte()? {
+                Some(byte) if byte.is_ascii_whitespace() => continue,
+                other => return Ok(other),
+            }
+        }
+    }
+
+    fn parse_error(&self, message: impl std::fmt::Display) -> TransformError {
+        TransformError::new(
+            TransformErrorKind::InvalidInput,
+            format!("failed to parse JSON input: {}", message),
+        )
+        .with_code(TransformErrorCode::InputParse)
+    }
+
+    // Reads one element (without the trailing `,` or `]`) into `self.element`.
+    fn read_element(&mut self, first: u8) -> Result<(), TransformError> {
+        self.element.clear();
+        let mut depth = 1usize;
+        let mut in_string = false;
+        let mut escaped = false;
+        let mut byte = first;
+        loop {
+            if in_string {
+                if escaped {
+                    escaped = false;
+                } else if byte == b'\\' {
+                    escaped = true;
+                } else if byte == b'"' {
+                    in_string = false;
+                }
+            } else {
+                match byte {
+                    b'"' => in_string = true,
+                    b'[' | b'{' => {
+                        depth += 1;
+                        if depth > self.max_depth {
+                            return Err(TransformError::new(
+                                TransformErrorKind::InvalidInput,
+                                format!(
+                                    "JSON input exceeds maximum nesting depth of {} (depth {} at byte {})",
+                                    self.max_depth,
+                                    depth,
+                                    self.offset - 1
+                                ),
+                            )
+                            .with_code(TransformErrorCode::InputTooDeep));
+                        }
+                    }
+                    b',' if depth == 1 => return Ok(()),
+                    b']' if depth == 1 => {
+                        self.closed = true;
+                        return Ok(());
+                    }
+                    b']' | b'}' => depth -= 1,
+                    _ => {}
+                }
+            }
+            self.element.push(byte);
+            byte = match self.next_byte()? {
+                Some(byte) => byte,
+                None => return Err(self.parse_error("unexpected end of input")),
+            };
+        }
+    }
+
+    fn next_record(&mut self) -> Result<Option<JsonValue>, TransformError> {
+        if self.closed {
+            return match self.skip_whitespace()? {
+                None => Ok(None),
+                Some(_) => Err(self.parse_error(format!(
+                    "trailing characters at byte {}",
+                    self.offset - 1
+                ))),
+            };
+        }
+
+        let first = match self.skip_whitespace()? {
+            Some(b']') if self.record_index == 0 => {
+                self.closed = true;
+                return self.next_record();
+            }
+            Some(b',') | Some(b']') => {
+                return Err(self.parse_error(format!("expected value at byte {}", self.offset - 1)))
+            }
+            Some(byte) => byte,
+            None => return Err(self.parse_error("unexpected end of input")),
+        };
+        let start = self.offset - 1;
+        self.read_element(first)?;
+
+        let record_index = self.record_index;
+        self.record_index += 1;
+        if let Err(err) = std::str::from_utf8(&self.element) {
+            return Err(TransformError::new(
+                TransformErrorKind::InvalidInput,
+                format!(
+                    "invalid UTF-8 in JSON record {} at byte {}",
+                    record_index,
+                    start + err.valid_up_to()
+                ),
+            )
+            .with_code(TransformErrorCode::InputRead));
+        }
+        serde_json::from_slice(&self.element)
+            .map(Some)
+            .map_err(|err| self.parse_error(format!("{} in record {}", err, record_index)))
+    }
+}
+
+impl<R: BufRead> Iterator for JsonArrayIter<R> {
+    type Item = Result<JsonValue, TransformError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.next_record() {
+            Ok(Some(record)) => Some(Ok(record)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+// One JSON object per line; blank lines are skipped and line numbers are 1-based.
+struct NdjsonRecordIter<R> {
+    reader: R,
+    max_depth: usize,
+    line: Vec<u8>,
+    line_number: usize,
+    done: bool,
+}
+
+impl<R: BufRead> NdjsonRecordIter<R> {
+    fn new(reader: R, max_depth: usize) -> Self {
+        Self {
+            reader,
+            max_depth,
+            line: Vec::new(),
+            line_number: 0,
+            done: false,
+        }
+    }
+
+    fn line_error(&self, message: impl std::fmt::Display) -> TransformError {
+        TransformError::new(
+            TransformErrorKind::InvalidInput,
+            format!(
+                "failed to parse NDJSON input at line {}: {}",
+                self.line_number, message
+            ),
+        )
+        .with_code(TransformErrorCode::InputParse)
+    }
+
+    fn next_record(&mut self) -> Result<Option<JsonValue>, TransformError> {
+        loop {
+            self.line.clear();
+            let read = self
+                .reader
+                .read_until(b'\n', &mut self.line)
+                .map_err(read_input_error)?;
+            if read == 0 {
+                return Ok(None);
+            }
+            self.line_number += 1;
+            if self.line.iter().all(|byte| byte.is_ascii_whitespace()) {
+                continue;
+            }
+
+            let text = std::str::from_utf8(&self.line).map_err(|err| {
+                self.line_error(format!("invalid UTF-8 at byte {}", err.valid_up_to()))
+            })?;
+            check_json_depth(text, self.max_depth).map_err(|err| self.line_error(err.message))?;
+            let value: JsonValue =
+                serde_json::from_str(text).map_err(|err| self.line_error(err))?;
+            if !value.is_object() {
+                return Err(self.line_error("expected a JSON object"));
+            }
+            return Ok(Some(value));
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for NdjsonRecordIter<R> {
+    type Item = Result<JsonValue, TransformError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.next_record() {
+            Ok(Some(record)) => Some(Ok(record)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+fn parse_json(rule: &RuleFile, input: &str) -> Result<Vec<JsonValue>, TransformError> {
+    check_json_depth(input, json_max_depth(rule))?;
 
+    let value: JsonValue = serde_json::from_str(input).map_err(|err| {
+        TransformError::new(
+            TransformErrorKind::InvalidInput,
+            format!("failed to parse JSON input: {}", err),
+        )
+        .with_code(TransformErrorCode::InputParse)
+    })?;
+
+    select_json_records(rule, &value).map(<[JsonValue]>::to_vec)
+}
+
+/// Applies `input.json.records_path` to an already parsed JSON document.
+///
+/// The selected array yields its elements and an object yields itself, the same records
+/// `transform` reads from JSON text. Pair with [`transform_values`] to skip re-parsing.
+pub fn select_json_records<'a>(
+    rule: &RuleFile,
+    value: &'a JsonValue,
+) -> Result<&'a [JsonValue], TransformError> {
     let records_value = match rule.input.json.as_ref().and_then(|j| j.records_path.as_deref()) {
         Some(path) => {
             let tokens = parse_path(path).map_err(|err| {
                 TransformError::new(TransformErrorKind::InvalidRecordsPath, err.message())
+                    .with_code(TransformErrorCode::BadRecordsPath)
                     .with_path("input.json.records_path")
             })?;
-            let found = get_path(&value, &tokens).ok_or_else(|| {
+            get_path(value, &tokens).ok_or_else(|| {
                 TransformError::new(
                     TransformErrorKind::InvalidRecordsPath,
                     "records_path does not exist",
                 )
+                .with_code(TransformErrorCode::RecordsPathNotFound)
                 .with_path("input.json.records_path")
-            })?;
-            found
+            })?
         }
-        None => &value,
+        None => value,
     };
 
     match records_value {
-        JsonValue::Array(items) => Ok(items.clone()),
-        JsonValue::Object(_) => Ok(vec![records_value.clone()]),
+        JsonValue::Array(items) => Ok(items),
+        JsonValue::Object(_) => Ok(std::slice::from_ref(records_value)),
         _ => Err(TransformError::new(
             TransformErrorKind::InvalidInput,
             "records_path must point to an array or object",
-        )),
+        )
+        .with_code(TransformErrorCode::RecordsNotList)),
+    }
+}
+
+// Scans raw JSON text so overly nested input is rejected before serde_json recurses into it.
+fn check_json_depth(input: &str, max_depth: usize) -> Result<(), TransformError> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (offset, byte) in input.bytes().enumerate() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if byte == b'\\' {
+                escaped = true;
+            } else if byte == b'"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+                if depth > max_depth {
+                    return Err(TransformError::new(
+                        TransformErrorKind::InvalidInput,
+                        format!(
+                            "JSON input exceeds maximum nesting depth of {} (depth {} at byte {})",
+                            max_depth, depth, offset
+                        ),
+                    )
+                    .with_code(TransformErrorCode::InputTooDeep));
+                }
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
     }
+    Ok(())
 }
 
-fn record_to_object(headers: &[String], record: &csv::StringRecord) -> Map<String, JsonValue> {
+fn record_to_object(
+    headers: &[String],
+    types: &[CsvColumnType],
+    record: &csv::StringRecord,
+) -> Result<Map<String, JsonValue>, TransformError> {
     let mut obj = Map::new();
-    for (index, name) in headers.iter().enumerate() {
-        if let Some(value) = record.get(index) {
-            obj.insert(name.clone(), JsonValue::String(value.to_string()));
+    for ((name, column_type), cell) in headers.iter().zip(types).zip(record.iter()) {
+        let value = column_type.convert(cell).ok_or_else(|| {
+            TransformError::new(
+                TransformErrorKind::TypeCastFailed,
+                format!(
+                    "csv column `{}` is not a valid {}: {:?}",
+                    name,
+                    column_type.as_str(),
+                    cell
+                ),
+            )
+            .with_code(TransformErrorCode::CsvColumnType)
+        })?;
+        obj.insert(name.clone(), value);
+    }
+    Ok(obj)
+}
+
+// What a mapping writes: one value, or for an `items[].sku` target one entry per element,
+// `None` where the element is left missing.
+enum MappedValue {
+    Value(JsonValue),
+    Elements(Vec<Option<JsonValue>>),
+}
+
+impl MappedValue {
+    // Missing elements read as null, as in the trace.
+    fn to_json(&self) -> JsonValue {
+        match self {
+            MappedValue::Value(value) => value.clone(),
+            MappedValue::Elements(values) => JsonValue::Array(
+                values
+                    .iter()
+                    .map(|value| value.clone().unwrap_or(JsonValue::Null))
+                    .collect(),
+            ),
         }
     }
-    obj
 }
 
 fn eval_mapping(
@@ -377,9 +1597,11 @@ fn eval_mapping(
     context: Option<&JsonValue>,
     out: &JsonValue,
     mapping_path: &str,
-) -> Result<Option<JsonValue>, TransformError> {
+) -> Result<Option<MappedValue>, TransformError> {
     let value = if let Some(source) = &mapping.source {
-        resolve_source(source, record, context, out, mapping_path)?
+        let value = resolve_mapping_source(source, record, context, out, mapping_path)?;
+        trace::record_step(None, value.as_value());
+        value
     } else if let Some(literal) = &mapping.value {
         EvalValue::Value(literal.clone())
     } else if let Some(expr) = &mapping.expr {
@@ -396,18 +1618,101 @@ fn eval_mapping(
             TransformErrorKind::InvalidInput,
             "mapping must define source, value, or expr",
         )
+        .with_code(TransformErrorCode::NoMappingValue)
         .with_path(mapping_path));
     };
 
+    if mapping.target.contains("[]") {
+        return eval_array_mapping(mapping, value, record, context, out, mapping_path);
+    }
+    let value = resolve_mapping_value(mapping, value, record, context, out, mapping_path)?;
+    Ok(value.map(MappedValue::Value))
+}
+
+// `items[].sku` targets: `default`, `required` and `type` apply to each element, and a null
+// element counts as missing. A missing or null array writes nothing unless `required`, and an
+// element left missing writes nothing into its item, as a missing value does for a mapping.
+fn eval_array_mapping(
+    mapping: &crate::model::Mapping,
+    value: EvalValue,
+    record: &JsonValue,
+    context: Option<&JsonValue>,
+    out: &JsonValue,
+    mapping_path: &str,
+) -> Result<Option<MappedValue>, TransformError> {
+    let items = match value {
+        EvalValue::Value(JsonValue::Array(items)) => items,
+        EvalValue::Missing | EvalValue::Value(JsonValue::Null) => {
+            if mapping.required {
+                return Err(TransformError::new(
+                    TransformErrorKind::MissingRequired,
+                    "required value is missing",
+                )
+                .with_code(TransformErrorCode::MissingRequired)
+                .with_path(mapping_path));
+            }
+            return Ok(None);
+        }
+        EvalValue::Value(_) => {
+            return Err(target_error(
+                "array target requires an array value",
+                mapping_path,
+            )
+            .with_code(TransformErrorCode::BadTarget));
+        }
+    };
+
+    let mut values = Vec::with_capacity(items.len());
+    for (index, item) in items.into_iter().enumerate() {
+        let item = if item.is_null() {
+            EvalValue::Missing
+        } else {
+            EvalValue::Value(item)
+        };
+        let value = resolve_mapping_value(mapping, item, record, context, out, mapping_path)
+            .map_err(|mut err| {
+                err.message = format!("{} at item {}", err.message, index);
+                err
+            })?;
+        values.push(value);
+    }
+    Ok(Some(MappedValue::Elements(values)))
+}
+
+fn resolve_mapping_value(
+    mapping: &crate::model::Mapping,
+    value: EvalValue,
+    record: &JsonValue,
+    context: Option<&JsonValue>,
+    out: &JsonValue,
+    mapping_path: &str,
+) -> Result<Option<JsonValue>, TransformError> {
+    let value = match value {
+        EvalValue::Value(value) if mapping.treat_empty_as_missing && is_empty_value(&value) => {
+            EvalValue::Missing
+        }
+        value => value,
+    };
+    let value = match (value, &mapping.default) {
+        (EvalValue::Missing, Some(default)) => eval_expr(
+            default,
+            record,
+            context,
+            out,
+            &format!("{}.default", mapping_path),
+            None,
+        )?,
+        (value, _) => value,
+    };
+
     let mut value = match value {
         EvalValue::Missing => {
-            if let Some(default) = &mapping.default {
-                default.clone()
-            } else if mapping.required {
+            if mapping.required {
                 return Err(TransformError::new(
                     TransformErrorKind::MissingRequired,
                     "required value is missing",
                 )
+                .with_code(TransformErrorCode::MissingRequired)
                 .with_path(mapping_path));
             } else {
                 return Ok(None);
@@ -422,6 +1727,7 @@ fn eval_mapping(
                 TransformErrorKind::MissingRequired,
                 "required value is null",
             )
+            .with_code(TransformErrorCode::RequiredNull)
             .with_path(mapping_path));
         }
         return Ok(Some(value));
@@ -458,18 +1764,19 @@ fn eval_when(
 }
 
 fn eval_record_when(
-    rule: &RuleFile,
+    expr: Option<&Expr>,
+    path: &str,
     record: &JsonValue,
     context: Option<&JsonValue>,
     warnings: &mut Vec<TransformWarning>,
 ) -> bool {
-    let expr = match &rule.record_when {
+    let expr = match expr {
         Some(expr) => expr,
         None => return true,
     };
 
     let empty_out = JsonValue::Object(Map::new());
-    match eval_bool_expr(expr, record, context, &empty_out, "record_when") {
+    match eval_bool_expr(expr, record, context, &empty_out, path) {
         Ok(flag) => flag,
         Err(err) => {
             warnings.push(err.into());
@@ -501,33 +1808,63 @@ fn when_type_error(path: &str) -> TransformError {
         TransformErrorKind::ExprError,
         "when/record_when must evaluate to boolean",
     )
+    .with_code(TransformErrorCode::NotBoolean)
     .with_path(path)
 }
 
+// A fallback list skips missing and null values as `coalesce` does; a single source keeps null.
+fn resolve_mapping_source(
+    source: &MappingSource,
+    record: &JsonValue,
+    context: Option<&JsonValue>,
+    out: &JsonValue,
+    mapping_path: &str,
+) -> Result<EvalValue, TransformError> {
+    let sources = match source {
+        MappingSource::Single(source) => {
+            let source_path = format!("{}.source", mapping_path);
+            return resolve_source(source, record, context, out, &source_path);
+        }
+        MappingSource::Fallback(sources) => sources,
+    };
+    for (index, source) in sources.iter().enumerate() {
+        let source_path = format!("{}.source[{}]", mapping_path, index);
+        match resolve_source(source, record, context, out, &source_path)? {
+            EvalValue::Value(value) if !value.is_null() => return Ok(EvalValue::Value(value)),
+            _ => {}
+        }
+    }
+    Ok(EvalValue::Missing)
+}
+
 fn resolve_source(
     source: &str,
     record: &JsonValue,
     context: Option<&JsonValue>,
     out: &JsonValue,
-    mapping_path: &str,
+    source_path: &str,
 ) -> Result<EvalValue, TransformError> {
-    let (namespace, path) = parse_source(source)
-        .map_err(|err| err.with_path(format!("{}.source", mapping_path)))?;
-    let tokens = parse_path_tokens(
-        path,
-        TransformErrorKind::InvalidRef,
-        format!("{}.source", mapping_path),
-    )?;
+    let (namespace, path) = parse_source(source).map_err(|err| err.with_path(source_path))?;
+    let tokens = parse_path_tokens(path, TransformErrorKind::InvalidRef, source_path)?;
     let target = match namespace {
         Namespace::Input => Some(record),
         Namespace::Context => context,
         Namespace::Out => Some(out),
+        Namespace::Const => {
+            return Ok(constants::lookup(&tokens).map_or(EvalValue::Missing, EvalValue::Value))
+        }
+        Namespace::Env => {
+            return env::lookup(&tokens)
+                .map(|value| value.map_or(EvalValue::Missing, EvalValue::Value))
+                .map_err(|err| err.with_path(source_path))
+        }
         Namespace::Item | Namespace::Acc => {
             return Err(TransformError::new(
                 TransformErrorKind::InvalidRef,
-                "ref namespace must be input|context|out",
+                "ref namespace must be input|context|out|const|env",
             )
-            .with_path(format!("{}.source", mapping_path)))
+            .with_code(TransformErrorCode::BadRef)
+            .with_path(source_path))
         }
     };
 
@@ -566,12 +1903,17 @@ fn eval_chain(
             TransformErrorKind::ExprError,
             "expr.chain must be a non-empty array",
         )
+        .with_code(TransformErrorCode::BadChain)
         .with_path(format!("{}.chain", base_path)));
     }
 
     let first_path = format!("{}.chain[0]", base_path);
-    let mut current =
-        eval_expr(&expr_chain.chain[0], record, context, out, &first_path, locals)?;
+    let mut current = {
+        let _step = StepScope::enter(0);
+        let value = eval_expr(&expr_chain.chain[0], record, context, out, &first_path, locals)?;
+        trace::record_step(chain_step_op(&expr_chain.chain[0]), value.as_value());
+        value
+    };
 
     for (index, step) in expr_chain.chain.iter().enumerate().skip(1) {
         let step_path = format!("{}.chain[{}]", base_path, index);
@@ -582,10 +1924,12 @@ fn eval_chain(
                     TransformErrorKind::ExprError,
                     "expr.chain items after first must be op",
                 )
+                .with_code(TransformErrorCode::BadChain)
                 .with_path(step_path))
             }
         };
 
+        let _step = StepScope::enter(index);
         let injected = current.clone();
         current = eval_op(
             expr_op,
@@ -596,11 +1940,19 @@ fn eval_chain(
             Some(&injected),
             locals,
         )?;
+        trace::record_step(Some(&expr_op.op), current.as_value());
     }
 
     Ok(current)
 }
 
+fn chain_step_op(expr: &Expr) -> Option<&str> {
+    match expr {
+        Expr::Op(expr_op) => Some(&expr_op.op),
+        _ => None,
+    }
+}
+
 fn eval_ref(
     expr_ref: &ExprRef,
     record: &JsonValue,
@@ -616,12 +1968,21 @@ fn eval_ref(
         Namespace::Input => Some(record),
         Namespace::Context => context,
         Namespace::Out => Some(out),
+        Namespace::Const => {
+            return Ok(constants::lookup(&tokens).map_or(EvalValue::Missing, EvalValue::Value))
+        }
+        Namespace::Env => {
+            return env::lookup(&tokens)
+                .map(|value| value.map_or(EvalValue::Missing, EvalValue::Value))
+                .map_err(|err| err.with_path(base_path))
+        }
         Namespace::Item => {
             let item = locals.and_then(|locals| locals.item).ok_or_else(|| {
                 TransformError::new(
                     TransformErrorKind::ExprError,
                     "item is only available within array ops",
                 )
+                .with_code(TransformErrorCode::OutOfScope)
                 .with_path(base_path)
             })?;
             let (root, rest) = match tokens.split_first() {
@@ -638,6 +1999,7 @@ fn eval_ref(
                         TransformErrorKind::ExprError,
                         "item ref must start with value or index",
                     )
+                    .with_code(TransformErrorCode::OutOfScope)
                     .with_path(base_path))
                 }
             };
@@ -652,6 +2014,7 @@ fn eval_ref(
                     TransformErrorKind::ExprError,
                     "acc is only available within reduce/fold ops",
                 )
+                .with_code(TransformErrorCode::OutOfScope)
                 .with_path(base_path)
             })?;
             let (root, rest) = match tokens.split_first() {
@@ -661,6 +2024,7 @@ fn eval_ref(
                         TransformErrorKind::ExprError,
                         "acc ref must start with value",
                     )
+                    .with_code(TransformErrorCode::OutOfScope)
                     .with_path(base_path))
                 }
             };
@@ -677,6 +2041,151 @@ fn eval_ref(
     }
 }
 
+/// Grouping used by `list_ops`; an op may belong to several categories or none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpCategory {
+    String,
+    Json,
+    Array,
+    Numeric,
+    Date,
+    Logical,
+    Conditional,
+    Comparison,
+}
+
+impl OpCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OpCategory::String => "string_ops",
+            OpCategory::Json => "json_ops",
+            OpCategory::Array => "array_ops",
+            OpCategory::Numeric => "numeric_ops",
+            OpCategory::Date => "date_ops",
+            OpCategory::Logical => "logical_ops",
+            OpCategory::Conditional => "conditional_ops",
+            OpCategory::Comparison => "comparison_ops",
+        }
+    }
+}
+
+/// Static description of an expression op.
+///
+/// Arity counts the value a chain step receives as its first argument.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpSpec {
+    pub name: &'static str,
+    pub categories: &'static [OpCategory],
+    pub min_args: usize,
+    pub max_args: Option<usize>,
+    /// Argument names in order; `?` marks optional and `...` repeated arguments.
+    pub args: &'static [&'static str],
+}
+
+impl OpSpec {
+    pub fn accepts_arg_count(&self, count: usize) -> bool {
+        count >= self.min_args && self.max_args.is_none_or(|max| count <= max)
+    }
+}
+
+const fn spec(
+    name: &'static str,
+    categories: &'static [OpCategory],
+    min_args: usize,
+    max_args: Option<usize>,
+    args: &'static [&'static str],
+) -> OpSpec {
+    OpSpec { name, categories, min_args, max_args, args }
+}
+
+/// Every op `eval_op` understands, in documentation order.
+pub const OPS: &[OpSpec] = &[
+    spec("concat", &[OpCategory::String], 1, None, &["value...", "options?"]),
+    spec("concat_ws", &[OpCategory::String], 2, None, &["separator", "value..."]),
+    spec("format", &[OpCategory::String], 2, None, &["template", "values | value..."]),
+    spec("coalesce", &[], 1, None, &["value...", "options?"]),
+    spec("to_string", &[OpCategory::String], 1, Some(1), &["value"]),
+    spec("trim", &[OpCategory::String], 1, Some(1), &["value"]),
+    spec("lowercase", &[OpCategory::String], 1, Some(1), &["value"]),
+    spec("uppercase", &[OpCategory::String], 1, Some(1), &["value"]),
+    spec("replace", &[OpCategory::String], 3, Some(4), &["value", "pattern", "replacement", "mode?"]),
+    spec("regex_extract", &[OpCategory::String], 2, Some(3), &["value", "pattern", "group?"]),
+    spec("regex_match", &[OpCategory::String], 2, Some(2), &["value", "pattern"]),
+    spec("split", &[OpCategory::String], 2, Some(3), &["value", "delimiter", "limit|options?"]),
+    spec("pad_start", &[OpCategory::String], 2, Some(3), &["value", "length", "pad?"]),
+    spec("pad_end", &[OpCategory::String], 2, Some(3), &["value", "length", "pad?"]),
+    spec("substring", &[OpCategory::String], 2, Some(3), &["value", "start", "length?"]),
+    spec("lookup", &[], 3, Some(4), &["collection", "key_path", "match_value", "output_path?"]),
+    spec("lookup_first", &[], 3, Some(4), &["collection", "key_path", "match_value", "output_path?"]),
+    spec("merge", &[OpCategory::Json], 2, None, &["object..."]),
+    spec("deep_merge", &[OpCategory::Json], 2, None, &["object...", "options?"]),
+    spec("get", &[OpCategory::Json], 2, Some(2), &["value", "path"]),
+    spec("pick", &[OpCategory::Json], 2, Some(2), &["object", "paths"]),
+    spec("omit", &[OpCategory::Json], 2, Some(2), &["object", "paths"]),
+    spec("keys", &[OpCategory::Json], 1, Some(1), &["object"]),
+    spec("values", &[OpCategory::Json], 1, Some(1), &["object"]),
+    spec("entries", &[OpCategory::Json], 1, Some(1), &["object"]),
+    spec("object_flatten", &[OpCategory::Json], 1, Some(2), &["object", "options?"]),
+    spec("object_unflatten", &[OpCategory::Json], 1, Some(2), &["object", "options?"]),
+    spec("map", &[OpCategory::Array], 2, Some(2), &["array", "expr"]),
+    spec("filter", &[OpCategory::Array], 2, Some(2), &["array", "predicate"]),
+    spec("flat_map", &[OpCategory::Array], 2, Some(2), &["array", "expr"]),
+    spec("flatten", &[OpCategory::Array], 1, Some(2), &["array", "depth?"]),
+    spec("take", &[OpCategory::Array], 2, Some(2), &["array", "count"]),
+    spec("drop", &[OpCategory::Array], 2, Some(2), &["array", "count"]),
+    spec("slice", &[OpCategory::Array], 2, Some(3), &["array", "start", "end?"]),
+    spec("chunk", &[OpCategory::Array], 2, Some(2), &["array", "size"]),
+    spec("zip", &[OpCategory::Array], 2, None, &["array..."]),
+    spec("zip_with", &[OpCategory::Array], 3, None, &["array...", "expr"]),
+    spec("unzip", &[OpCategory::Array], 1, Some(1), &["array"]),
+    spec("group_by", &[OpCategory::Array], 2, Some(3), &["array", "key_expr", "options?"]),
+    spec("key_by", &[OpCategory::Array], 2, Some(3), &["array", "key_expr", "options?"]),
+    spec("partition", &[OpCategory::Array], 2, Some(2), &["array", "predicate"]),
+    spec("unique", &[OpCategory::Array], 1, Some(2), &["array", "options?"]),
+    spec("distinct_by", &[OpCategory::Array], 2, Some(3), &["array", "key_expr", "options?"]),
+    spec("sort_by", &[OpCategory::Array], 2, Some(3), &["array", "key_expr", "order?"]),
+    spec("find", &[OpCategory::Array], 2, Some(2), &["array", "predicate"]),
+    spec("find_index", &[OpCategory::Array], 2, Some(2), &["array", "predicate"]),
+    spec("index_of", &[OpCategory::Array], 2, Some(2), &["array", "value"]),
+    spec("contains", &[OpCategory::Array], 2, Some(2), &["array", "value"]),
+    spec("sum", &[OpCategory::Array, OpCategory::Numeric], 1, Some(1), &["array"]),
+    spec("avg", &[OpCategory::Array, OpCategory::Numeric], 1, Some(1), &["array"]),
+    spec("min", &[OpCategory::Array, OpCategory::Numeric], 1, Some(1), &["array"]),
+    spec("max", &[OpCategory::Array, OpCategory::Numeric], 1, Some(1), &["array"]),
+    spec("reduce", &[OpCategory::Array], 2, Some(2), &["array", "expr"]),
+    spec("fold", &[OpCategory::Array], 3, Some(3), &["array", "initial", "expr"]),
+    spec("+", &[OpCategory::Numeric], 2, None, &["number..."]),
+    spec("-", &[OpCategory::Numeric], 2, Some(2), &["left", "right"]),
+    spec("*", &[OpCategory::Numeric], 2, None, &["number..."]),
+    spec("/", &[OpCategory::Numeric], 2, Some(2), &["left", "right"]),
+    spec("int_div", &[OpCategory::Numeric], 2, Some(2), &["left", "right"]),
+    spec("mod", &[OpCategory::Numeric], 2, Some(2), &["left", "right"]),
+    spec("abs", &[OpCategory::Numeric], 1, Some(1), &["value"]),
+    spec("floor", &[OpCategory::Numeric], 1, Some(1), &["value"]),
+    spec("ceil", &[OpCategory::Numeric], 1, Some(1), &["value"]),
+    spec("trunc", &[OpCategory::Numeric], 1, Some(1), &["value"]),
+    spec("round", &[OpCategory::Numeric], 1, Some(2), &["value", "scale?"]),
+    spec("to_base", &[OpCategory::Numeric], 2, Some(2), &["value", "base"]),
+    spec("date_format", &[OpCategory::Date], 2, Some(4), &["value", "format", "input_format?", "timezone?"]),
+    spec("to_unixtime", &[OpCategory::Date], 1, Some(3), &["value", "unit?", "timezone?"]),
+    spec("and", &[OpCategory::Logical], 2, None, &["condition..."]),
+    spec("or", &[OpCategory::Logical], 2, None, &["condition..."]),
+    spec("not", &[OpCategory::Logical], 1, Some(1), &["condition"]),
+    spec("if", &[OpCategory::Conditional], 2, Some(3), &["condition", "then", "else?"]),
+    spec("case", &[OpCategory::Conditional], 2, None, &["(condition, value)...", "default?"]),
+    spec("==", &[OpCategory::Comparison], 2, Some(2), &["left", "right"]),
+    spec("!=", &[OpCategory::Comparison], 2, Some(2), &["left", "right"]),
+    spec("<", &[OpCategory::Comparison], 2, Some(2), &["left", "right"]),
+    spec("<=", &[OpCategory::Comparison], 2, Some(2), &["left", "right"]),
+    spec(">", &[OpCategory::Comparison], 2, Some(2), &["left", "right"]),
+    spec(">=", &[OpCategory::Comparison], 2, Some(2), &["left", "right"]),
+    spec("~=", &[OpCategory::Comparison], 2, Some(2), &["value", "pattern"]),
+];
+
+pub fn op_spec(name: &str) -> Option<&'static OpSpec> {
+    OPS.iter().find(|spec| spec.name == name)
+}
+
 fn eval_op(
     expr_op: &ExprOp,
     record: &JsonValue,
@@ -692,41 +2201,26 @@ fn eval_op(
             TransformErrorKind::ExprError,
             "expr.args must be a non-empty array",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
+    let _op = OpScope::enter(&expr_op.op);
     match expr_op.op.as_str() {
-        "concat" => {
-            let mut parts = Vec::new();
-            for index in 0..total_len {
-                let arg_path = format!("{}.args[{}]", base_path, index);
-                let value =
-                    eval_expr_at_index(index, &expr_op.args, injected, record, context, out, base_path, locals)?;
-                match value {
-                    EvalValue::Missing => return Ok(EvalValue::Missing),
-                    EvalValue::Value(value) => {
-                        if value.is_null() {
-                            return Err(TransformError::new(
-                                TransformErrorKind::ExprError,
-                                "concat does not accept null",
-                            )
-                            .with_path(arg_path));
-                        }
-                        let part = value_to_string(&value, &arg_path)?;
-                        parts.push(part);
-                    }
-                }
-            }
-            Ok(EvalValue::Value(JsonValue::String(parts.join(""))))
+        "concat" => eval_concat(&expr_op.args, injected, record, context, out, base_path, locals),
+        "concat_ws" => {
+            eval_concat_ws(&expr_op.args, injected, record, context, out, base_path, locals)
         }
+        "format" => eval_format(&expr_op.args, injected, record, context, out, base_path, locals),
         "coalesce" => {
-            for index in 0..total_len {
+            let (values_len, blank) = coalesce_options(&expr_op.args, total_len, base_path)?;
+            for index in 0..values_len {
                 let value =
                     eval_expr_at_index(index, &expr_op.args, injected, record, context, out, base_path, locals)?;
                 match value {
                     EvalValue::Missing => continue,
                     EvalValue::Value(value) => {
-                        if value.is_null() {
+                        if value.is_null() || (blank && is_empty_value(&value)) {
                             continue;
                         }
                         return Ok(EvalValue::Value(value));
@@ -785,9 +2279,14 @@ fn eval_op(
             },
         ),
         "replace" => eval_replace(&expr_op.args, injected, record, context, out, base_path, locals),
+        "regex_extract" => {
+            eval_regex_extract(&expr_op.args, injected, record, context, out, base_path, locals)
+        }
+        "regex_match" => eval_regex_match(&expr_op.args, injected, record, context, out, base_path, locals),
         "split" => eval_split(&expr_op.args, injected, record, context, out, base_path, locals),
         "pad_start" => eval_pad(&expr_op.args, injected, record, context, out, base_path, true, locals),
         "pad_end" => eval_pad(&expr_op.args, injected, record, context, out, base_path, false, locals),
+        "substring" => eval_substring(&expr_op.args, injected, record, context, out, base_path, locals),
         "lookup" => eval_lookup(&expr_op.args, injected, record, context, out, base_path, false, locals),
         "lookup_first" => {
             eval_lookup(&expr_op.args, injected, record, context, out, base_path, true, locals)
@@ -836,6 +2335,12 @@ fn eval_op(
         "reduce" => eval_array_reduce(&expr_op.args, injected, record, context, out, base_path, locals),
         "fold" => eval_array_fold(&expr_op.args, injected, record, context, out, base_path, locals),
         "+" | "-" | "*" | "/" => eval_numeric_op(expr_op, injected, record, context, out, base_path, locals),
+        "int_div" | "mod" => {
+            eval_division_op(expr_op, injected, record, context, out, base_path, locals)
+        }
+        "abs" | "floor" | "ceil" | "trunc" => {
+            eval_unary_numeric_op(expr_op, injected, record, context, out, base_path, locals)
+        }
         "round" => eval_round(&expr_op.args, injected, record, context, out, base_path, locals),
         "to_base" => eval_to_base(&expr_op.args, injected, record, context, out, base_path, locals),
         "date_format" => eval_date_format(&expr_op.args, injected, record, context, out, base_path, locals),
@@ -845,6 +2350,8 @@ fn eval_op(
         "and" => eval_bool_and_or(&expr_op.args, injected, record, context, out, base_path, true, locals),
         "or" => eval_bool_and_or(&expr_op.args, injected, record, context, out, base_path, false, locals),
         "not" => eval_bool_not(&expr_op.args, injected, record, context, out, base_path, locals),
+        "if" => eval_if(&expr_op.args, injected, record, context, out, base_path, locals),
+        "case" => eval_case(&expr_op.args, injected, record, context, out, base_path, locals),
         "==" | "!=" | "<" | "<=" | ">" | ">=" | "~=" => {
             eval_compare(expr_op, injected, record, context, out, base_path, locals)
         }
@@ -852,21 +2359,410 @@ fn eval_op(
             TransformErrorKind::ExprError,
             "expr.op is not supported",
         )
+        .with_code(TransformErrorCode::UnknownOp)
         .with_path(format!("{}.op", base_path))),
     }
 }
 
-fn eval_unary_string_op<F>(
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ConcatNulls {
+    Error,
+    Skip,
+    Empty,
+}
+
+/// `""`, `[]` and `{}`, which `coalesce` with `blank` and `treat_empty_as_missing` skip.
+fn is_empty_value(value: &JsonValue) -> bool {
+    match value {
+        JsonValue::String(text) => text.is_empty(),
+        JsonValue::Array(items) => items.is_empty(),
+        JsonValue::Object(map) => map.is_empty(),
+        _ => false,
+    }
+}
+
+// A trailing object literal with a `blank` key is the options bag rather than a fallback value.
+// Returns how many args are values and whether blank values are skipped.
+fn coalesce_options(
     args: &[Expr],
-    injected: Option<&EvalValue>,
-    record: &JsonValue,
-    context: Option<&JsonValue>,
-    out: &JsonValue,
+    total_len: usize,
     base_path: &str,
-    locals: Option<&EvalLocals<'_>>,
-    op: F,
-) -> Result<EvalValue, TransformError>
-where
+) -> Result<(usize, bool), TransformError> {
+    let Some(options) = coalesce_options_arg(args) else {
+        return Ok((total_len, false));
+    };
+    let values_len = total_len - 1;
+    let options_path = format!("{}.args[{}]", base_path, values_len);
+    let mut blank = false;
+    for (key, value) in options {
+        let option_path = format!("{}.{}", options_path, key);
+        match (key.as_str(), value) {
+            ("blank", JsonValue::Bool(flag)) => blank = *flag,
+            ("blank", _) => {
+                return Err(TransformError::new(
+                    TransformErrorKind::ExprError,
+                    "blank must be a boolean",
+                )
+                .with_code(TransformErrorCode::BadOption)
+                .with_path(option_path))
+            }
+            _ => {
+                return Err(TransformError::new(TransformErrorKind::ExprError, "unknown option")
+                    .with_code(TransformErrorCode::BadOption)
+                    .with_path(option_path))
+            }
+        }
+    }
+    if values_len == 0 {
+        return Err(TransformError::new(
+            TransformErrorKind::ExprError,
+            "expr.args must contain at least one item besides options",
+        )
+        .with_code(TransformErrorCode::ArgCount)
+        .with_path(format!("{}.args", base_path)));
+    }
+    Ok((values_len, blank))
+}
+
+pub(crate) fn coalesce_options_arg(args: &[Expr]) -> Option<&Map<String, JsonValue>> {
+    match args.last() {
+        Some(Expr::Literal(JsonValue::Object(options))) if options.contains_key("blank") => {
+            Some(options)
+        }
+        _ => None,
+    }
+}
+
+fn eval_concat(
+    args: &[Expr],
+    injected: Option<&EvalValue>,
+    record: &JsonValue,
+    context: Option<&JsonValue>,
+    out: &JsonValue,
+    base_path: &str,
+    locals: Option<&EvalLocals<'_>>,
+) -> Result<EvalValue, TransformError> {
+    let mut total_len = args_len(args, injected);
+    let mut nulls = ConcatNulls::Error;
+    // A trailing object literal is the options bag; objects were never valid parts.
+    if let Some(Expr::Literal(JsonValue::Object(options))) = args.last() {
+        total_len -= 1;
+        let options_path = format!("{}.args[{}]", base_path, total_len);
+        for (key, value) in options {
+            let option_path = format!("{}.{}", options_path, key);
+            match (key.as_str(), value.as_str()) {
+                ("nulls", Some("error")) => nulls = ConcatNulls::Error,
+                ("nulls", Some("skip")) => nulls = ConcatNulls::Skip,
+                ("nulls", Some("empty")) => nulls = ConcatNulls::Empty,
+                ("nulls", _) => {
+                    return Err(TransformError::new(
+                        TransformErrorKind::ExprError,
+                        "nulls must be error|skip|empty",
+                    )
+                    .with_code(TransformErrorCode::BadOption)
+                    .with_path(option_path))
+                }
+                _ => {
+                    return Err(TransformError::new(
+                        TransformErrorKind::ExprError,
+                        "unknown option",
+                    )
+                    .with_code(TransformErrorCode::BadOption)
+                    .with_path(option_path))
+                }
+            }
+        }
+        if total_len == 0 {
+            return Err(TransformError::new(
+                TransformErrorKind::ExprError,
+                "expr.args must contain at least one item besides options",
+            )
+            .with_code(TransformErrorCode::ArgCount)
+            .with_path(format!("{}.args", base_path)));
+        }
+    }
+
+    let mut parts = Vec::new();
+    for index in 0..total_len {
+        let arg_path = format!("{}.args[{}]", base_path, index);
+        let value =
+            eval_expr_at_index(index, args, injected, record, context, out, base_path, locals)?;
+        match value {
+            EvalValue::Missing => {
+                if nulls == ConcatNulls::Skip {
+                    continue;
+                }
+                return Ok(EvalValue::Missing);
+            }
+            EvalValue::Value(JsonValue::Null) => match nulls {
+                ConcatNulls::Error => {
+                    return Err(TransformError::new(
+                        TransformErrorKind::ExprError,
+                        "concat does not accept null",
+                    )
+                    .with_code(TransformErrorCode::NullArg)
+                    .with_path(arg_path))
+                }
+                ConcatNulls::Skip | ConcatNulls::Empty => continue,
+            },
+            EvalValue::Value(value) => parts.push(value_to_string(&value, &arg_path)?),
+        }
+    }
+    Ok(EvalValue::Value(JsonValue::String(parts.join(""))))
+}
+
+fn eval_concat_ws(
+    args: &[Expr],
+    injected: Option<&EvalValue>,
+    record: &JsonValue,
+    context: Option<&JsonValue>,
+    out: &JsonValue,
+    base_path: &str,
+    locals: Option<&EvalLocals<'_>>,
+) -> Result<EvalValue, TransformError> {
+    let total_len = args_len(args, injected);
+    if total_len < 2 {
+        return Err(TransformError::new(
+            TransformErrorKind::ExprError,
+            "expr.args must contain at least two items",
+        )
+        .with_code(TransformErrorCode::ArgCount)
+        .with_path(format!("{}.args", base_path)));
+    }
+
+    let separator_path = format!("{}.args[0]", base_path);
+    let separator =
+        match eval_expr_at_index(0, args, injected, record, context, out, base_path, locals)? {
+            EvalValue::Missing => return Ok(EvalValue::Missing),
+            EvalValue::Value(JsonValue::String(separator)) => separator,
+            EvalValue::Value(_) => {
+                return Err(TransformError::new(
+                    TransformErrorKind::ExprError,
+                    "separator must be a string",
+                )
+                .with_code(TransformErrorCode::ArgType)
+                .with_path(separator_path))
+            }
+        };
+
+    let mut parts = Vec::new();
+    for index in 1..total_len {
+        let arg_path = format!("{}.args[{}]", base_path, index);
+        let value =
+            eval_expr_at_index(index, args, injected, record, context, out, base_path, locals)?;
+        let EvalValue::Value(value) = value else {
+            continue;
+        };
+        if value.is_null() {
+            continue;
+        }
+        let part = value_to_string(&value, &arg_path)?;
+        if !part.is_empty() {
+            parts.push(part);
+        }
+    }
+    // Always a string, even when every part was skipped: the op exists to build display
+    // text from optional pieces, and an absent middle name should not drop the target.
+    Ok(EvalValue::Value(JsonValue::String(parts.join(&separator))))
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum FormatPart {
+    Text(String),
+    Placeholder(FormatPlaceholder),
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct FormatPlaceholder {
+    pub(crate) name: String,
+    pub(crate) json: bool,
+}
+
+impl FormatPlaceholder {
+    /// `{0}` refers to the first value argument (`args[1]`).
+    pub(crate) fn position(&self) -> Option<usize> {
+        if self.name.bytes().all(|byte| byte.is_ascii_digit()) {
+            self.name.parse().ok()
+        } else {
+            None
+        }
+    }
+}
+
+/// Splits a `format` template into literal text and `{name}` / `{name:json}` placeholders.
+pub(crate) fn parse_format_template(template: &str) -> Result<Vec<FormatPart>, String> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '}' => {
+                return Err("unmatched `}` in template; use `}}` for a literal brace".to_string())
+            }
+            '{' => {
+                let mut inner = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some('{') | None => {
+                            return Err(
+                                "unclosed `{` in template; use `{{` for a literal brace".to_string()
+                            )
+                        }
+                        Some(ch) => inner.push(ch),
+                    }
+                }
+                let (name, json) = match inner.split_once(':') {
+                    Some((name, "json")) => (name, true),
+                    Some((_, modifier)) => {
+                        return Err(format!("unknown placeholder modifier: {}", modifier))
+                    }
+                    None => (inner.as_str(), false),
+                };
+                let name = name.trim();
+                if name.is_empty() {
+                    return Err("placeholder name must not be empty".to_string());
+                }
+                if !text.is_empty() {
+                    parts.push(FormatPart::Text(std::mem::take(&mut text)));
+                }
+                parts.push(FormatPart::Placeholder(FormatPlaceholder {
+                    name: name.to_string(),
+                    json,
+                }));
+            }
+            ch => text.push(ch),
+        }
+    }
+    if !text.is_empty() {
+        parts.push(FormatPart::Text(text));
+    }
+    Ok(parts)
+}
+
+fn eval_format(
+    args: &[Expr],
+    injected: Option<&EvalValue>,
+    record: &JsonValue,
+    context: Option<&JsonValue>,
+    out: &JsonValue,
+    base_path: &str,
+    locals: Option<&EvalLocals<'_>>,
+) -> Result<EvalValue, TransformError> {
+    let total_len = args_len(args, injected);
+    if total_len < 2 {
+        return Err(TransformError::new(
+            TransformErrorKind::ExprError,
+            "expr.args must contain at least two items",
+        )
+        .with_code(TransformErrorCode::ArgCount)
+        .with_path(format!("{}.args", base_path)));
+    }
+
+    let template_path = format!("{}.args[0]", base_path);
+    let template =
+        match eval_expr_at_index(0, args, injected, record, context, out, base_path, locals)? {
+            EvalValue::Missing => return Ok(EvalValue::Missing),
+            EvalValue::Value(JsonValue::String(template)) => template,
+            EvalValue::Value(_) => {
+                return Err(expr_type_error("template must be a string", &template_path)
+                    .with_code(TransformErrorCode::BadTemplate))
+            }
+        };
+    let parts = parse_format_template(&template)
+        .map_err(|message| expr_type_error(&message, &template_path)
+            .with_code(TransformErrorCode::BadTemplate))?;
+
+    let mut values = Vec::with_capacity(total_len - 1);
+    for index in 1..total_len {
+        match eval_expr_at_index(index, args, injected, record, context, out, base_path, locals)? {
+            EvalValue::Missing => return Ok(EvalValue::Missing),
+            EvalValue::Value(value) => values.push(value),
+        }
+    }
+
+    let mut result = String::new();
+    for part in parts {
+        let placeholder = match part {
+            FormatPart::Text(text) => {
+                result.push_str(&text);
+                continue;
+            }
+            FormatPart::Placeholder(placeholder) => placeholder,
+        };
+        let (value, value_path) = match placeholder.position() {
+            Some(position) => (
+                values.get(position),
+                format!("{}.args[{}]", base_path, position + 1),
+            ),
+            None => {
+                let value_path = format!("{}.args[1]", base_path);
+                match &values[0] {
+                    JsonValue::Object(map) => (map.get(&placeholder.name), value_path),
+                    _ => {
+                        return Err(expr_type_error(
+                            &format!(
+                                "named placeholder {{{}}} needs an object of values",
+                                placeholder.name
+                            ),
+                            &value_path,
+                        )
+                        .with_code(TransformErrorCode::BadTemplate))
+                    }
+                }
+            }
+        };
+        let Some(value) = value else {
+            return Err(expr_type_error(
+                &format!("unknown placeholder: {{{}}}", placeholder.name),
+                &value_path,
+            )
+            .with_code(TransformErrorCode::BadTemplate));
+        };
+        match value {
+            _ if placeholder.json => result.push_str(&value.to_string()),
+            JsonValue::Object(_) | JsonValue::Array(_) => {
+                return Err(expr_type_error(
+                    &format!(
+                        "placeholder {{{}}} is an object/array; use {{{}:json}} to embed it",
+                        placeholder.name, placeholder.name
+                    ),
+                    &value_path,
+                )
+                .with_code(TransformErrorCode::BadTemplate))
+            }
+            _ => result.push_str(&value_to_string(value, &value_path).map_err(|err| {
+                expr_type_error(
+                    &format!("placeholder {{{}}}: {}", placeholder.name, err.message),
+                    &value_path,
+                )
+                .with_code(TransformErrorCode::BadTemplate)
+            })?),
+        }
+    }
+    Ok(EvalValue::Value(JsonValue::String(result)))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn eval_unary_string_op<F>(
+    args: &[Expr],
+    injected: Option<&EvalValue>,
+    record: &JsonValue,
+    context: Option<&JsonValue>,
+    out: &JsonValue,
+    base_path: &str,
+    locals: Option<&EvalLocals<'_>>,
+    op: F,
+) -> Result<EvalValue, TransformError>
+where
     F: FnOnce(&JsonValue, &str) -> Result<JsonValue, TransformError>,
 {
     let total_len = args_len(args, injected);
@@ -875,6 +2771,7 @@ where
             TransformErrorKind::ExprError,
             "expr.args must contain exactly one item",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -889,6 +2786,7 @@ where
                     TransformErrorKind::ExprError,
                     "expr arg must not be null",
                 )
+                .with_code(TransformErrorCode::NullArg)
                 .with_path(arg_path));
             }
             op(&value, &arg_path).map(EvalValue::Value)
@@ -916,6 +2814,7 @@ fn arg_expr_at<'a>(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn eval_expr_at_index(
     index: usize,
     args: &[Expr],
@@ -935,10 +2834,13 @@ fn eval_expr_at_index(
                 TransformErrorKind::ExprError,
                 "expr.args index is out of bounds",
             )
+            .with_code(TransformErrorCode::ArgCount)
             .with_path(format!("{}.args[{}]", base_path, index))
         })?;
         let arg_path = format!("{}.args[{}]", base_path, index);
-        return eval_expr(arg, record, context, out, &arg_path, locals);
+        let value = eval_expr(arg, record, context, out, &arg_path, locals)?;
+        trace::record_arg(index, value.as_value());
+        return Ok(value);
     }
 
     let arg = args.get(index).ok_or_else(|| {
@@ -946,12 +2848,16 @@ fn eval_expr_at_index(
             TransformErrorKind::ExprError,
             "expr.args index is out of bounds",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args[{}]", base_path, index))
     })?;
     let arg_path = format!("{}.args[{}]", base_path, index);
-    eval_expr(arg, record, context, out, &arg_path, locals)
+    let value = eval_expr(arg, record, context, out, &arg_path, locals)?;
+    trace::record_arg(index, value.as_value());
+    Ok(value)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn eval_arg_value_at(
     index: usize,
     args: &[Expr],
@@ -968,6 +2874,7 @@ fn eval_arg_value_at(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn eval_arg_string_at(
     index: usize,
     args: &[Expr],
@@ -988,11 +2895,13 @@ fn eval_arg_string_at(
             TransformErrorKind::ExprError,
             "expr arg must not be null",
         )
+        .with_code(TransformErrorCode::NullArg)
         .with_path(arg_path));
     }
     value_as_string(&value, &arg_path).map(Some)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn eval_expr_value_or_null_at(
     index: usize,
     args: &[Expr],
@@ -1026,6 +2935,7 @@ fn parse_replace_mode(value: &str, path: &str) -> Result<ReplaceMode, TransformE
             TransformErrorKind::ExprError,
             "replace mode must be all|regex|regex_all",
         )
+        .with_code(TransformErrorCode::ArgRange)
         .with_path(path)),
     }
 }
@@ -1045,6 +2955,7 @@ fn eval_replace(
             TransformErrorKind::ExprError,
             "expr.args must contain three or four items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -1091,7 +3002,7 @@ fn eval_replace(
     Ok(EvalValue::Value(JsonValue::String(replaced)))
 }
 
-fn eval_split(
+fn eval_regex_extract(
     args: &[Expr],
     injected: Option<&EvalValue>,
     record: &JsonValue,
@@ -1101,11 +3012,12 @@ fn eval_split(
     locals: Option<&EvalLocals<'_>>,
 ) -> Result<EvalValue, TransformError> {
     let total_len = args_len(args, injected);
-    if total_len != 2 {
+    if !(2..=3).contains(&total_len) {
         return Err(TransformError::new(
             TransformErrorKind::ExprError,
-            "expr.args must contain exactly two items",
+            "expr.args must contain two or three items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -1113,36 +3025,161 @@ fn eval_split(
         None => return Ok(EvalValue::Missing),
         Some(value) => value,
     };
-    let delimiter = match eval_arg_string_at(1, args, injected, record, context, out, base_path, locals)? {
+    let pattern = match eval_arg_string_at(1, args, injected, record, context, out, base_path, locals)? {
         None => return Ok(EvalValue::Missing),
         Some(value) => value,
     };
-    let delimiter_path = format!("{}.args[1]", base_path);
+    let regex = cached_regex(&pattern, &format!("{}.args[1]", base_path))?;
 
-    if delimiter.is_empty() {
+    let group = if total_len == 3 {
+        match eval_arg_value_at(2, args, injected, record, context, out, base_path, locals)? {
+            None => return Ok(EvalValue::Missing),
+            Some(value) => value,
+        }
+    } else {
+        JsonValue::from(1)
+    };
+    let group_path = format!("{}.args[2]", base_path);
+    let group_error = |message: &str| {
+        TransformError::new(TransformErrorKind::ExprError, message)
+            .with_code(TransformErrorCode::BadRegex).with_path(group_path.clone())
+    };
+
+    let captures = match group {
+        JsonValue::String(name) => {
+            if !regex.capture_names().any(|candidate| candidate == Some(name.as_str())) {
+                return Err(group_error("regex group does not exist in pattern"));
+            }
+            regex
+                .captures(&value)
+                .and_then(|captures| captures.name(&name).map(|m| m.as_str().to_string()))
+        }
+        JsonValue::Number(_) => {
+            let index = value_to_i64(
+                &group,
+                &group_path,
+                "regex group must be a non-negative integer or a group name",
+            )?;
+            let index = usize::try_from(index).map_err(|_| {
+                group_error("regex group must be a non-negative integer or a group name")
+            })?;
+            if index >= regex.captures_len() {
+                return Err(group_error("regex group does not exist in pattern"));
+            }
+            regex
+                .captures(&value)
+                .and_then(|captures| captures.get(index).map(|m| m.as_str().to_string()))
+        }
+        _ => {
+            return Err(group_error(
+                "regex group must be a non-negative integer or a group name",
+            ))
+        }
+    };
+
+    // No match (or a group that did not participate) is missing so coalesce/default can apply.
+    Ok(captures.map_or(EvalValue::Missing, |text| {
+        EvalValue::Value(JsonValue::String(text))
+    }))
+}
+
+fn eval_regex_match(
+    args: &[Expr],
+    injected: Option<&EvalValue>,
+    record: &JsonValue,
+    context: Option<&JsonValue>,
+    out: &JsonValue,
+    base_path: &str,
+    locals: Option<&EvalLocals<'_>>,
+) -> Result<EvalValue, TransformError> {
+    let total_len = args_len(args, injected);
+    if total_len != 2 {
         return Err(TransformError::new(
             TransformErrorKind::ExprError,
-            "split delimiter must not be empty",
+            "expr.args must contain exactly two items",
         )
-        .with_path(delimiter_path));
+        .with_code(TransformErrorCode::ArgCount)
+        .with_path(format!("{}.args", base_path)));
     }
 
-    let parts = value
-        .split(&delimiter)
-        .map(|part| JsonValue::String(part.to_string()))
-        .collect::<Vec<_>>();
+    let pattern = match eval_arg_string_at(1, args, injected, record, context, out, base_path, locals)? {
+        None => return Ok(EvalValue::Missing),
+        Some(value) => value,
+    };
+    let regex = cached_regex(&pattern, &format!("{}.args[1]", base_path))?;
+    let value = eval_expr_value_or_null_at(0, args, injected, record, context, out, base_path, locals)?;
+    if value.is_null() {
+        return Ok(EvalValue::Value(JsonValue::Bool(false)));
+    }
+    let value = value_as_string(&value, &format!("{}.args[0]", base_path))?;
+    Ok(EvalValue::Value(JsonValue::Bool(regex.is_match(&value))))
+}
 
-    Ok(EvalValue::Value(JsonValue::Array(parts)))
+#[derive(Default)]
+struct SplitOptions {
+    regex: bool,
+    limit: Option<usize>,
 }
 
-fn eval_pad(
+// The third `split` arg: a part limit, `"regex"`, or `{ regex?: bool, limit?: n }`.
+fn parse_split_options(value: &JsonValue, path: &str) -> Result<SplitOptions, TransformError> {
+    let invalid = |message: &str, path: String| {
+        Err(TransformError::new(TransformErrorKind::ExprError, message)
+            .with_code(TransformErrorCode::BadOption).with_path(path))
+    };
+    match value {
+        JsonValue::Number(_) => Ok(SplitOptions {
+            regex: false,
+            limit: Some(parse_split_limit(value, path)?),
+        }),
+        JsonValue::String(mode) if mode == "regex" => Ok(SplitOptions {
+            regex: true,
+            limit: None,
+        }),
+        JsonValue::Object(map) => {
+            let mut options = SplitOptions::default();
+            for (key, option) in map {
+                let option_path = format!("{}.{}", path, key);
+                match key.as_str() {
+                    "regex" => match option.as_bool() {
+                        Some(regex) => options.regex = regex,
+                        None => return invalid("regex must be a boolean", option_path),
+                    },
+                    "limit" => options.limit = Some(parse_split_limit(option, &option_path)?),
+                    _ => return invalid("unknown option", option_path),
+                }
+            }
+            Ok(options)
+        }
+        _ => invalid(
+            "split options must be a limit, \"regex\" or an options object",
+            path.to_string(),
+        ),
+    }
+}
+
+fn parse_split_limit(value: &JsonValue, path: &str) -> Result<usize, TransformError> {
+    value
+        .as_u64()
+        .filter(|limit| *limit > 0)
+        .and_then(|limit| usize::try_from(limit).ok())
+        .ok_or_else(|| {
+            TransformError::new(
+                TransformErrorKind::ExprError,
+                "split limit must be a positive integer",
+            )
+            .with_code(TransformErrorCode::ArgRange)
+            .with_path(path)
+        })
+}
+
+fn eval_split(
     args: &[Expr],
     injected: Option<&EvalValue>,
     record: &JsonValue,
     context: Option<&JsonValue>,
     out: &JsonValue,
     base_path: &str,
-    pad_start: bool,
     locals: Option<&EvalLocals<'_>>,
 ) -> Result<EvalValue, TransformError> {
     let total_len = args_len(args, injected);
@@ -1151,6 +3188,85 @@ fn eval_pad(
             TransformErrorKind::ExprError,
             "expr.args must contain two or three items",
         )
+        .with_code(TransformErrorCode::ArgCount)
+        .with_path(format!("{}.args", base_path)));
+    }
+
+    let value = match eval_arg_string_at(0, args, injected, record, context, out, base_path, locals)? {
+        None => return Ok(EvalValue::Missing),
+        Some(value) => value,
+    };
+    let delimiter = match eval_arg_string_at(1, args, injected, record, context, out, base_path, locals)? {
+        None => return Ok(EvalValue::Missing),
+        Some(value) => value,
+    };
+    let delimiter_path = format!("{}.args[1]", base_path);
+
+    if delimiter.is_empty() {
+        return Err(TransformError::new(
+            TransformErrorKind::ExprError,
+            "split delimiter must not be empty",
+        )
+        .with_code(TransformErrorCode::ArgRange)
+        .with_path(delimiter_path));
+    }
+
+    let options = if total_len == 3 {
+        let options_path = format!("{}.args[2]", base_path);
+        match eval_arg_value_at(2, args, injected, record, context, out, base_path, locals)? {
+            None => return Ok(EvalValue::Missing),
+            Some(value) => parse_split_options(&value, &options_path)?,
+        }
+    } else {
+        SplitOptions::default()
+    };
+
+    let parts: Vec<&str> = if options.regex {
+        let regex = cached_regex(&delimiter, &delimiter_path)?;
+        if regex.is_match("") {
+            return Err(TransformError::new(
+                TransformErrorKind::ExprError,
+                "split regex must not match an empty string",
+            )
+            .with_code(TransformErrorCode::BadRegex)
+            .with_path(delimiter_path));
+        }
+        match options.limit {
+            Some(limit) => regex.splitn(&value, limit).collect(),
+            None => regex.split(&value).collect(),
+        }
+    } else {
+        match options.limit {
+            Some(limit) => value.splitn(limit, delimiter.as_str()).collect(),
+            None => value.split(delimiter.as_str()).collect(),
+        }
+    };
+    let parts = parts
+        .into_iter()
+        .map(|part| JsonValue::String(part.to_string()))
+        .collect::<Vec<_>>();
+
+    Ok(EvalValue::Value(JsonValue::Array(parts)))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn eval_pad(
+    args: &[Expr],
+    injected: Option<&EvalValue>,
+    record: &JsonValue,
+    context: Option<&JsonValue>,
+    out: &JsonValue,
+    base_path: &str,
+    pad_start: bool,
+    locals: Option<&EvalLocals<'_>>,
+) -> Result<EvalValue, TransformError> {
+    let total_len = args_len(args, injected);
+    if !(2..=3).contains(&total_len) {
+        return Err(TransformError::new(
+            TransformErrorKind::ExprError,
+            "expr.args must contain two or three items",
+        )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -1170,6 +3286,7 @@ fn eval_pad(
             TransformErrorKind::ExprError,
             "expr arg must not be null",
         )
+        .with_code(TransformErrorCode::NullArg)
         .with_path(length_path));
     }
     let length = value_to_i64(
@@ -1182,6 +3299,7 @@ fn eval_pad(
             TransformErrorKind::ExprError,
             "pad length must be a non-negative integer",
         )
+        .with_code(TransformErrorCode::ArgRange)
         .with_path(length_path));
     }
 
@@ -1199,6 +3317,7 @@ fn eval_pad(
             TransformErrorKind::ExprError,
             "pad length must be a non-negative integer",
         )
+        .with_code(TransformErrorCode::ArgRange)
         .with_path(length_path)
     })?;
 
@@ -1214,7 +3333,7 @@ fn pad_string_value(value: &str, target_len: usize, pad: &str, pad_start: bool)
 
     let needed = target_len - value_len;
     let pad_len = pad.chars().count();
-    let repeats = (needed + pad_len - 1) / pad_len;
+    let repeats = needed.div_ceil(pad_len);
     let pad_buf = pad.repeat(repeats);
     let pad_slice = pad_buf.chars().take(needed).collect::<String>();
 
@@ -1225,6 +3344,100 @@ fn pad_string_value(value: &str, target_len: usize, pad: &str, pad_start: bool)
     }
 }
 
+fn eval_substring(
+    args: &[Expr],
+    injected: Option<&EvalValue>,
+    record: &JsonValue,
+    context: Option<&JsonValue>,
+    out: &JsonValue,
+    base_path: &str,
+    locals: Option<&EvalLocals<'_>>,
+) -> Result<EvalValue, TransformError> {
+    let total_len = args_len(args, injected);
+    if !(2..=3).contains(&total_len) {
+        return Err(TransformError::new(
+            TransformErrorKind::ExprError,
+            "expr.args must contain two or three items",
+        )
+        .with_code(TransformErrorCode::ArgCount)
+        .with_path(format!("{}.args", base_path)));
+    }
+
+    let value = match eval_arg_string_at(0, args, injected, record, context, out, base_path, locals)? {
+        None => return Ok(EvalValue::Missing),
+        Some(value) => value,
+    };
+
+    let start_value = match eval_arg_value_at(1, args, injected, record, context, out, base_path, locals)? {
+        None => return Ok(EvalValue::Missing),
+        Some(value) => value,
+    };
+    let start_path = format!("{}.args[1]", base_path);
+    if start_value.is_null() {
+        return Err(TransformError::new(
+            TransformErrorKind::ExprError,
+            "expr arg must not be null",
+        )
+        .with_code(TransformErrorCode::NullArg)
+        .with_path(start_path));
+    }
+    let start = value_to_i64(&start_value, &start_path, "substring start must be an integer")?;
+
+    let length = if total_len == 3 {
+        let length_value =
+            match eval_arg_value_at(2, args, injected, record, context, out, base_path, locals)? {
+                None => return Ok(EvalValue::Missing),
+                Some(value) => value,
+            };
+        let length_path = format!("{}.args[2]", base_path);
+        if length_value.is_null() {
+            return Err(TransformError::new(
+                TransformErrorKind::ExprError,
+                "expr arg must not be null",
+            )
+            .with_code(TransformErrorCode::NullArg)
+            .with_path(length_path));
+        }
+        let length = value_to_i64(
+            &length_value,
+            &length_path,
+            "substring length must be a non-negative integer",
+        )?;
+        if length < 0 {
+            return Err(TransformError::new(
+                TransformErrorKind::ExprError,
+                "substring length must be a non-negative integer",
+            )
+            .with_code(TransformErrorCode::ArgRange)
+            .with_path(length_path));
+        }
+        Some(length as u64)
+    } else {
+        None
+    };
+
+    Ok(EvalValue::Value(JsonValue::String(substring_chars(
+        &value, start, length,
+    ))))
+}
+
+// Counts Unicode scalar values; a negative start counts from the end and is clamped to 0.
+fn substring_chars(value: &str, start: i64, length: Option<u64>) -> String {
+    let char_len = value.chars().count() as i64;
+    let start = if start < 0 {
+        (char_len + start).max(0)
+    } else {
+        start
+    };
+    if start >= char_len {
+        return String::new();
+    }
+    let take = length.map_or(usize::MAX, |length| {
+        usize::try_from(length).unwrap_or(usize::MAX)
+    });
+    value.chars().skip(start as usize).take(take).collect()
+}
+
 fn eval_numeric_op(
     expr_op: &ExprOp,
     injected: Option<&EvalValue>,
@@ -1244,6 +3457,7 @@ fn eval_numeric_op(
             TransformErrorKind::ExprError,
             "expr.args must contain exactly two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
     if !requires_exact_two && total_len < 2 {
@@ -1251,38 +3465,197 @@ fn eval_numeric_op(
             TransformErrorKind::ExprError,
             "expr.args must contain at least two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
-    let mut result: f64 = 0.0;
+    let mut operands = Vec::with_capacity(total_len);
     for index in 0..total_len {
-        let arg_path = format!("{}.args[{}]", base_path, index);
-        let value = match eval_arg_value_at(index, args, injected, record, context, out, base_path, locals)?
-        {
+        match eval_operand(index, args, injected, record, context, out, base_path, locals)? {
             None => return Ok(EvalValue::Missing),
+            Some(operand) => operands.push(operand),
+        }
+    }
+
+    // Integer operands stay integers while the result fits in i64, so large ids keep
+    // their precision; anything else (or an overflow) is computed in f64.
+    if op != "/" {
+        let integers: Option<Vec<i64>> = operands.iter().map(Operand::as_int).collect();
+        if let Some(integers) = integers {
+            let result = integers[1..].iter().try_fold(integers[0], |acc, &number| match op {
+                "+" => acc.checked_add(number),
+                "-" => acc.checked_sub(number),
+                _ => acc.checked_mul(number),
+            });
+            if let Some(result) = result {
+                return Ok(EvalValue::Value(JsonValue::Number(result.into())));
+            }
+        }
+    }
+
+    let mut result = operands[0].as_f64();
+    for operand in &operands[1..] {
+        let number = operand.as_f64();
+        result = match op {
+            "+" => result + number,
+            "-" => result - number,
+            "*" => result * number,
+            "/" => result / number,
+            _ => result,
+        };
+    }
+
+    Ok(EvalValue::Value(json_number_from_f64(result, base_path)?))
+}
+
+// A numeric op argument: JSON integers are kept exact, everything else is an f64.
+#[derive(Clone, Copy)]
+enum Operand {
+    Int(i64),
+    Float(f64),
+}
+
+impl Operand {
+    fn as_int(&self) -> Option<i64> {
+        match self {
+            Operand::Int(value) => Some(*value),
+            Operand::Float(_) => None,
+        }
+    }
+
+    fn as_f64(&self) -> f64 {
+        match self {
+            Operand::Int(value) => *value as f64,
+            Operand::Float(value) => *value,
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn eval_operand(
+    index: usize,
+    args: &[Expr],
+    injected: Option<&EvalValue>,
+    record: &JsonValue,
+    context: Option<&JsonValue>,
+    out: &JsonValue,
+    base_path: &str,
+    locals: Option<&EvalLocals<'_>>,
+) -> Result<Option<Operand>, TransformError> {
+    let arg_path = format!("{}.args[{}]", base_path, index);
+    let value =
+        match eval_arg_value_at(index, args, injected, record, context, out, base_path, locals)? {
+            None => return Ok(None),
             Some(value) => value,
         };
-        if value.is_null() {
-            return Err(TransformError::new(
-                TransformErrorKind::ExprError,
-                "expr arg must not be null",
-            )
-            .with_path(arg_path));
+    if value.is_null() {
+        return Err(TransformError::new(
+            TransformErrorKind::ExprError,
+            "expr arg must not be null",
+        )
+        .with_code(TransformErrorCode::NullArg)
+        .with_path(arg_path));
+    }
+    if let JsonValue::Number(number) = &value
+        && let Some(int) = number.as_i64()
+    {
+        return Ok(Some(Operand::Int(int)));
+    }
+    value_to_number(&value, &arg_path, "operand must be a number")
+        .map(|number| Some(Operand::Float(number)))
+}
+
+fn eval_division_op(
+    expr_op: &ExprOp,
+    injected: Option<&EvalValue>,
+    record: &JsonValue,
+    context: Option<&JsonValue>,
+    out: &JsonValue,
+    base_path: &str,
+    locals: Option<&EvalLocals<'_>>,
+) -> Result<EvalValue, TransformError> {
+    let op = expr_op.op.as_str();
+    let args = &expr_op.args;
+    if args_len(args, injected) != 2 {
+        return Err(TransformError::new(
+            TransformErrorKind::ExprError,
+            "expr.args must contain exactly two items",
+        )
+        .with_code(TransformErrorCode::ArgCount)
+        .with_path(format!("{}.args", base_path)));
+    }
+
+    let mut operands = [Operand::Int(0); 2];
+    for (index, operand) in operands.iter_mut().enumerate() {
+        match eval_operand(index, args, injected, record, context, out, base_path, locals)? {
+            None => return Ok(EvalValue::Missing),
+            Some(value) => *operand = value,
         }
-        let number = value_to_number(&value, &arg_path, "operand must be a number")?;
-        if index == 0 {
-            result = number;
-        } else {
-            result = match op {
-                "+" => result + number,
-                "-" => result - number,
-                "*" => result * number,
-                "/" => result / number,
-                _ => result,
-            };
+    }
+    if operands[1].as_f64() == 0.0 {
+        return Err(expr_type_error("division by zero", &format!("{}.args[1]", base_path))
+            .with_code(TransformErrorCode::DivisionByZero));
+    }
+
+    // Both truncate toward zero, so `left == int_div * right + mod`.
+    match (op, operands[0], operands[1]) {
+        ("int_div", Operand::Int(left), Operand::Int(right)) => left
+            .checked_div(right)
+            .map(|result| EvalValue::Value(JsonValue::Number(result.into())))
+            .ok_or_else(|| expr_type_error("integer result is out of range", base_path)
+                .with_code(TransformErrorCode::NumberOutOfRange)),
+        ("int_div", left, right) => {
+            let result = (left.as_f64() / right.as_f64()).trunc();
+            Ok(EvalValue::Value(json_number_from_f64(result, base_path)?))
+        }
+        (_, Operand::Int(left), Operand::Int(right)) => {
+            // i64::MIN % -1 overflows, but the remainder is 0.
+            let result = left.checked_rem(right).unwrap_or(0);
+            Ok(EvalValue::Value(JsonValue::Number(result.into())))
         }
+        (_, left, right) => {
+            let result = left.as_f64() % right.as_f64();
+            Ok(EvalValue::Value(json_number_from_f64(result, base_path)?))
+        }
+    }
+}
+
+fn eval_unary_numeric_op(
+    expr_op: &ExprOp,
+    injected: Option<&EvalValue>,
+    record: &JsonValue,
+    context: Option<&JsonValue>,
+    out: &JsonValue,
+    base_path: &str,
+    locals: Option<&EvalLocals<'_>>,
+) -> Result<EvalValue, TransformError> {
+    let op = expr_op.op.as_str();
+    let args = &expr_op.args;
+    if args_len(args, injected) != 1 {
+        return Err(TransformError::new(
+            TransformErrorKind::ExprError,
+            "expr.args must contain exactly one item",
+        )
+        .with_code(TransformErrorCode::ArgCount)
+        .with_path(format!("{}.args", base_path)));
     }
 
+    let operand =
+        match eval_operand(0, args, injected, record, context, out, base_path, locals)? {
+            None => return Ok(EvalValue::Missing),
+            Some(operand) => operand,
+        };
+    let result = match (op, operand) {
+        ("abs", Operand::Int(value)) => match value.checked_abs() {
+            Some(value) => return Ok(EvalValue::Value(JsonValue::Number(value.into()))),
+            None => (value as f64).abs(),
+        },
+        (_, Operand::Int(value)) => return Ok(EvalValue::Value(JsonValue::Number(value.into()))),
+        ("abs", Operand::Float(value)) => value.abs(),
+        ("floor", Operand::Float(value)) => value.floor(),
+        ("ceil", Operand::Float(value)) => value.ceil(),
+        (_, Operand::Float(value)) => value.trunc(),
+    };
     Ok(EvalValue::Value(json_number_from_f64(result, base_path)?))
 }
 
@@ -1301,6 +3674,7 @@ fn eval_round(
             TransformErrorKind::ExprError,
             "expr.args must contain one or two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -1314,6 +3688,7 @@ fn eval_round(
             TransformErrorKind::ExprError,
             "expr arg must not be null",
         )
+        .with_code(TransformErrorCode::NullArg)
         .with_path(value_path));
     }
     let number = value_to_number(&value, &value_path, "operand must be a number")?;
@@ -1330,6 +3705,7 @@ fn eval_round(
                 TransformErrorKind::ExprError,
                 "expr arg must not be null",
             )
+            .with_code(TransformErrorCode::NullArg)
             .with_path(scale_path));
         }
         let scale = value_to_i64(
@@ -1342,6 +3718,7 @@ fn eval_round(
                 TransformErrorKind::ExprError,
                 "scale must be a non-negative integer",
             )
+            .with_code(TransformErrorCode::ArgRange)
             .with_path(scale_path));
         }
         if scale > 308 {
@@ -1349,6 +3726,7 @@ fn eval_round(
                 TransformErrorKind::ExprError,
                 "scale is too large",
             )
+            .with_code(TransformErrorCode::ArgRange)
             .with_path(scale_path));
         }
         scale as i32
@@ -1381,6 +3759,7 @@ fn eval_to_base(
             TransformErrorKind::ExprError,
             "expr.args must contain exactly two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -1400,6 +3779,7 @@ fn eval_to_base(
             TransformErrorKind::ExprError,
             "expr arg must not be null",
         )
+        .with_code(TransformErrorCode::NullArg)
         .with_path(value_path));
     }
     if base_value.is_null() {
@@ -1407,6 +3787,7 @@ fn eval_to_base(
             TransformErrorKind::ExprError,
             "expr arg must not be null",
         )
+        .with_code(TransformErrorCode::NullArg)
         .with_path(base_path_arg));
     }
 
@@ -1417,6 +3798,7 @@ fn eval_to_base(
             TransformErrorKind::ExprError,
             "base must be between 2 and 36",
         )
+        .with_code(TransformErrorCode::ArgRange)
         .with_path(base_path_arg));
     }
 
@@ -1439,10 +3821,11 @@ fn eval_date_format(
             TransformErrorKind::ExprError,
             "expr.args must contain two to four items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
-    let value = match eval_arg_string_at(0, args, injected, record, context, out, base_path, locals)? {
+    let value = match eval_date_input(args, injected, record, context, out, base_path, locals)? {
         None => return Ok(EvalValue::Missing),
         Some(value) => value,
     };
@@ -1454,7 +3837,8 @@ fn eval_date_format(
     };
     let value_path = format!("{}.args[0]", base_path);
     let mut input_formats: Option<Vec<String>> = None;
-    let mut timezone: Option<FixedOffset> = None;
+    let mut epoch_unit: Option<EpochUnit> = None;
+    let mut timezone: Option<Zone> = None;
 
     if total_len >= 3 {
         let input_path = format!("{}.args[2]", base_path);
@@ -1468,15 +3852,23 @@ fn eval_date_format(
                 TransformErrorKind::ExprError,
                 "expr arg must not be null",
             )
+            .with_code(TransformErrorCode::NullArg)
             .with_path(input_path));
         }
 
-        if let Some(value) = input_value.as_str() {
-            if looks_like_timezone(value) {
-                timezone = Some(parse_timezone(value, &input_path)?);
+        let is_epoch = matches!(value, DateInput::Epoch(_));
+        if let Some(text) = input_value.as_str() {
+            if looks_like_timezone(text) {
+                timezone = Some(parse_timezone(text, &input_path)?);
+            } else if is_epoch {
+                // Epoch input has no format to parse; the argument is the unit instead.
+                epoch_unit = Some(EpochUnit::parse(text, &input_path)?);
             } else {
                 input_formats = Some(parse_format_list(&input_value, &input_path)?);
             }
+        } else if is_epoch {
+            return Err(expr_type_error("unit must be s or ms", &input_path)
+                .with_code(TransformErrorCode::BadUnit));
         } else {
             input_formats = Some(parse_format_list(&input_value, &input_path)?);
         }
@@ -1492,9 +3884,14 @@ fn eval_date_format(
         timezone = Some(parse_timezone(&tz_value, &tz_path)?);
     }
 
-    let dt = parse_datetime(&value, input_formats.as_deref(), timezone, &value_path)?;
+    let dt = match &value {
+        DateInput::Text(value) => {
+            parse_datetime(value, input_formats.as_deref(), timezone, &value_path)?
+        }
+        DateInput::Epoch(number) => epoch_to_datetime(number, epoch_unit, &value_path)?,
+    };
     let dt = match timezone {
-        Some(offset) => dt.with_timezone(&offset),
+        Some(zone) => zone.convert(&dt),
         None => dt,
     };
     let formatted = dt.format(&output_format).to_string();
@@ -1516,17 +3913,18 @@ fn eval_to_unixtime(
             TransformErrorKind::ExprError,
             "expr.args must contain one to three items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
-    let value = match eval_arg_string_at(0, args, injected, record, context, out, base_path, locals)? {
+    let value = match eval_date_input(args, injected, record, context, out, base_path, locals)? {
         None => return Ok(EvalValue::Missing),
         Some(value) => value,
     };
     let value_path = format!("{}.args[0]", base_path);
 
     let mut unit = "s".to_string();
-    let mut timezone: Option<FixedOffset> = None;
+    let mut timezone: Option<Zone> = None;
 
     if total_len >= 2 {
         let arg_path = format!("{}.args[1]", base_path);
@@ -1541,6 +3939,7 @@ fn eval_to_unixtime(
                     TransformErrorKind::ExprError,
                     "unit must be s or ms",
                 )
+                .with_code(TransformErrorCode::BadUnit)
                 .with_path(arg_path));
             }
             unit = arg_value;
@@ -1553,6 +3952,7 @@ fn eval_to_unixtime(
                 TransformErrorKind::ExprError,
                 "unit must be s or ms",
             )
+            .with_code(TransformErrorCode::BadUnit)
             .with_path(arg_path));
         }
     }
@@ -1567,9 +3967,12 @@ fn eval_to_unixtime(
         timezone = Some(parse_timezone(&tz_value, &tz_path)?);
     }
 
-    let dt = parse_datetime(&value, None, timezone, &value_path)?;
+    let dt = match &value {
+        DateInput::Text(value) => parse_datetime(value, None, timezone, &value_path)?,
+        DateInput::Epoch(number) => epoch_to_datetime(number, None, &value_path)?,
+    };
     let dt = match timezone {
-        Some(offset) => dt.with_timezone(&offset),
+        Some(zone) => zone.convert(&dt),
         None => dt,
     };
     let timestamp = if unit == "ms" {
@@ -1581,6 +3984,7 @@ fn eval_to_unixtime(
     Ok(EvalValue::Value(JsonValue::Number(timestamp.into())))
 }
 
+#[allow(clippy::too_many_arguments)]
 fn eval_lookup(
     args: &[Expr],
     injected: Option<&EvalValue>,
@@ -1597,30 +4001,44 @@ fn eval_lookup(
             TransformErrorKind::ExprError,
             "lookup args must be [collection, key_path, match_value, output_path?]",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
     let collection_path = format!("{}.args[0]", base_path);
-    let collection =
-        match eval_expr_at_index(0, args, injected, record, context, out, base_path, locals)? {
-        EvalValue::Missing => return Ok(EvalValue::Missing),
-        EvalValue::Value(value) => value,
+    let loaded = match injected {
+        Some(_) => None,
+        None => loaded_lookup_table(args),
     };
-    let collection_array = match collection {
-        JsonValue::Array(items) => items,
-        JsonValue::Null => {
-            return Err(TransformError::new(
-                TransformErrorKind::ExprError,
-                "lookup collection must be an array",
-            )
-            .with_path(collection_path))
+    let indexed = match (injected, &loaded) {
+        (None, None) => indexed_lookup_collection(args, context),
+        _ => None,
+    };
+    let evaluated;
+    let collection_array = match indexed {
+        _ if loaded.is_some() => &[],
+        Some((_, collection)) => {
+            trace::record_arg(0, Some(collection));
+            collection.as_array().map(Vec::as_slice).unwrap_or_default()
         }
-        _ => {
-            return Err(TransformError::new(
-                TransformErrorKind::ExprError,
-                "lookup collection must be an array",
-            )
-            .with_path(collection_path))
+        None => {
+            let value =
+                eval_expr_at_index(0, args, injected, record, context, out, base_path, locals)?;
+            evaluated = match value {
+                EvalValue::Missing => return Ok(EvalValue::Missing),
+                EvalValue::Value(value) => value,
+            };
+            match &evaluated {
+                JsonValue::Array(items) => items.as_slice(),
+                _ => {
+                    return Err(TransformError::new(
+                        TransformErrorKind::ExprError,
+                        "lookup collection must be an array",
+                    )
+                    .with_code(TransformErrorCode::ArgType)
+                    .with_path(collection_path))
+                }
+            }
         }
     };
 
@@ -1629,6 +4047,7 @@ fn eval_lookup(
             TransformErrorKind::ExprError,
             "lookup key_path must be a non-empty string literal",
         )
+        .with_code(TransformErrorCode::BadLookup)
         .with_path(format!("{}.args[1]", base_path))
     })?;
     let key_path = literal_string(key_expr).ok_or_else(|| {
@@ -1636,6 +4055,7 @@ fn eval_lookup(
             TransformErrorKind::ExprError,
             "lookup key_path must be a non-empty string literal",
         )
+        .with_code(TransformErrorCode::BadLookup)
         .with_path(format!("{}.args[1]", base_path))
     })?;
     if key_path.is_empty() {
@@ -1643,10 +4063,12 @@ fn eval_lookup(
             TransformErrorKind::ExprError,
             "lookup key_path must be a non-empty string literal",
         )
+        .with_code(TransformErrorCode::BadLookup)
         .with_path(format!("{}.args[1]", base_path)));
     }
     let key_tokens = parse_path(key_path).map_err(|_| {
         TransformError::new(TransformErrorKind::ExprError, "lookup key_path is invalid")
+            .with_code(TransformErrorCode::BadLookup)
             .with_path(format!("{}.args[1]", base_path))
     })?;
 
@@ -1656,6 +4078,7 @@ fn eval_lookup(
                 TransformErrorKind::ExprError,
                 "lookup output_path must be a non-empty string literal",
             )
+            .with_code(TransformErrorCode::BadLookup)
             .with_path(format!("{}.args[3]", base_path))
         })?;
         let value = literal_string(output_expr).ok_or_else(|| {
@@ -1663,6 +4086,7 @@ fn eval_lookup(
                 TransformErrorKind::ExprError,
                 "lookup output_path must be a non-empty string literal",
             )
+            .with_code(TransformErrorCode::BadLookup)
             .with_path(format!("{}.args[3]", base_path))
         })?;
         if value.is_empty() {
@@ -1670,10 +4094,12 @@ fn eval_lookup(
                 TransformErrorKind::ExprError,
                 "lookup output_path must be a non-empty string literal",
             )
+            .with_code(TransformErrorCode::BadLookup)
             .with_path(format!("{}.args[3]", base_path)));
         }
         let tokens = parse_path(value).map_err(|_| {
             TransformError::new(TransformErrorKind::ExprError, "lookup output_path is invalid")
+                .with_code(TransformErrorCode::BadLookup)
                 .with_path(format!("{}.args[3]", base_path))
         })?;
         Some(tokens)
@@ -1692,27 +4118,51 @@ fn eval_lookup(
             TransformErrorKind::ExprError,
             "lookup match_value must not be null",
         )
+        .with_code(TransformErrorCode::NullArg)
         .with_path(match_path));
     }
     let match_key = value_to_string(&match_value, &match_path)?;
 
+    if let Some(table) = &loaded
+        && table.key() != key_path
+    {
+        let (name, key) = (table.name(), table.key());
+        let message = format!("lookup table {} is indexed by {}, not {}", name, key, key_path);
+        return Err(TransformError::new(TransformErrorKind::ExprError, message)
+            .with_code(TransformErrorCode::BadLookup)
+            .with_path(format!("{}.args[1]", base_path)));
+    }
+    let positions = indexed.and_then(|(collection_ref, _)| {
+        lookup_index::positions(collection_ref, key_path, &match_key, || {
+            let mut table: HashMap<String, Vec<usize>> = HashMap::new();
+            for (position, item) in collection_array.iter().enumerate() {
+                if let Some(key) = lookup_item_key(item, &key_tokens) {
+                    table.entry(key).or_default().push(position);
+                }
+            }
+            table
+        })
+    });
+    let matches: Vec<&JsonValue> = match positions {
+        _ if loaded.is_some() => loaded
+            .iter()
+            .flat_map(|table| table.matches(&match_key))
+            .collect(),
+        Some(positions) => positions
+            .into_iter()
+            .map(|position| &collection_array[position])
+            .collect(),
+        None => collection_array
+            .iter()
+            .filter(|item| lookup_item_key(item, &key_tokens).is_some_and(|key| key == match_key))
+            .collect(),
+    };
+
     let mut results = Vec::new();
-    for item in &collection_array {
-        let key_value = match get_path(item, &key_tokens) {
-            Some(value) => value,
-            None => continue,
-        };
-        let item_key = match value_to_string_optional(key_value) {
-            Some(value) => value,
-            None => continue,
-        };
-        if item_key != match_key {
-            continue;
-        }
-
-        let selected = match output_tokens.as_ref() {
-            Some(tokens) => get_path(item, tokens),
-            None => Some(item),
+    for item in matches {
+        let selected = match output_tokens.as_ref() {
+            Some(tokens) => get_path(item, tokens),
+            None => Some(item),
         };
 
         if let Some(value) = selected {
@@ -1730,6 +4180,42 @@ fn eval_lookup(
     }
 }
 
+// A `context.<name>` collection indexed by `load_table` has no array in the context.
+fn loaded_lookup_table(args: &[Expr]) -> Option<IndexedTable> {
+    let Some(Expr::Ref(expr_ref)) = args.first() else {
+        return None;
+    };
+    let (Namespace::Context, path) = parse_ref(&expr_ref.ref_path).ok()? else {
+        return None;
+    };
+    match parse_path(path).ok()?.as_slice() {
+        [PathToken::Key(name)] => lookup_index::loaded_table(name),
+        _ => None,
+    }
+}
+
+// A direct `context.*` collection ref can be borrowed (and indexed) instead of cloned per record.
+fn indexed_lookup_collection<'a, 'c>(
+    args: &'a [Expr],
+    context: Option<&'c JsonValue>,
+) -> Option<(&'a str, &'c JsonValue)> {
+    let Some(Expr::Ref(expr_ref)) = args.first() else {
+        return None;
+    };
+    let (Namespace::Context, path) = parse_ref(&expr_ref.ref_path).ok()? else {
+        return None;
+    };
+    let tokens = parse_path(path).ok()?;
+    let collection = get_path(context?, &tokens)?;
+    collection
+        .is_array()
+        .then_some((expr_ref.ref_path.as_str(), collection))
+}
+
+pub(crate) fn lookup_item_key(item: &JsonValue, key_tokens: &[PathToken]) -> Option<String> {
+    get_path(item, key_tokens).and_then(value_to_string_optional)
+}
+
 fn locals_with_item<'a>(
     locals: Option<&EvalLocals<'a>>,
     item: EvalItem<'a>,
@@ -1740,6 +4226,7 @@ fn locals_with_item<'a>(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn eval_array_arg(
     index: usize,
     args: &[Expr],
@@ -1763,6 +4250,7 @@ fn eval_array_arg(
                     TransformErrorKind::ExprError,
                     "expr arg must be an array",
                 )
+                .with_code(TransformErrorCode::ArgType)
                 .with_path(arg_path))
             }
         }
@@ -1817,6 +4305,7 @@ fn eval_key_expr_string(
                 TransformErrorKind::ExprError,
                 "expr arg must not be missing",
             )
+            .with_code(TransformErrorCode::MissingArg)
             .with_path(base_path))
         }
         EvalValue::Value(value) => value,
@@ -1826,11 +4315,137 @@ fn eval_key_expr_string(
             TransformErrorKind::ExprError,
             "expr arg must not be null",
         )
+        .with_code(TransformErrorCode::NullArg)
         .with_path(base_path));
     }
     value_to_string(&value, base_path)
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeyMode {
+    // Keys compared and written as text, so `1` and `"1"` are the same key.
+    String,
+    // Keys compared by their canonical JSON; any value but `missing` is a key.
+    Typed,
+}
+
+// The `options?` arg of group_by/key_by/distinct_by/unique: `{ keys: "string" | "typed" }`.
+#[allow(clippy::too_many_arguments)]
+fn eval_key_mode(
+    index: usize,
+    args: &[Expr],
+    injected: Option<&EvalValue>,
+    record: &JsonValue,
+    context: Option<&JsonValue>,
+    out: &JsonValue,
+    base_path: &str,
+    locals: Option<&EvalLocals<'_>>,
+) -> Result<KeyMode, TransformError> {
+    if args_len(args, injected) <= index {
+        return Ok(KeyMode::String);
+    }
+    let arg_index = if injected.is_some() { index - 1 } else { index };
+    let options_path = format!("{}.args[{}]", base_path, arg_index);
+    let map = match eval_expr_at_index(index, args, injected, record, context, out, base_path, locals)? {
+        EvalValue::Value(JsonValue::Object(map)) => map,
+        _ => {
+            return Err(TransformError::new(
+                TransformErrorKind::ExprError,
+                "options must be an object",
+            )
+            .with_code(TransformErrorCode::BadOption)
+            .with_path(options_path))
+        }
+    };
+    let mut mode = KeyMode::String;
+    for (key, value) in map {
+        let error = |message: &str| {
+            TransformError::new(TransformErrorKind::ExprError, message)
+                .with_code(TransformErrorCode::BadOption)
+                .with_path(format!("{}.{}", options_path, key))
+        };
+        match key.as_str() {
+            "keys" => match value.as_str() {
+                Some("string") => mode = KeyMode::String,
+                Some("typed") => mode = KeyMode::Typed,
+                _ => return Err(error("keys must be string|typed")),
+            },
+            _ => return Err(error("unknown option")),
+        }
+    }
+    Ok(mode)
+}
+
+fn eval_typed_key(
+    expr: &Expr,
+    record: &JsonValue,
+    context: Option<&JsonValue>,
+    out: &JsonValue,
+    base_path: &str,
+    locals: Option<&EvalLocals<'_>>,
+) -> Result<JsonValue, TransformError> {
+    match eval_expr(expr, record, context, out, base_path, locals)? {
+        EvalValue::Missing => Err(TransformError::new(
+            TransformErrorKind::ExprError,
+            "expr arg must not be missing",
+        )
+        .with_code(TransformErrorCode::MissingArg)
+        .with_path(base_path)),
+        EvalValue::Value(value) => Ok(value),
+    }
+}
+
+// JSON text that two values share only when they are the same JSON value: object members are
+// sorted and numbers are written by value, so `1` and `1.0` match but `1` and `"1"` do not.
+fn canonical_key(value: &JsonValue) -> String {
+    let mut text = String::new();
+    write_canonical_key(value, &mut text);
+    text
+}
+
+fn write_canonical_key(value: &JsonValue, text: &mut String) {
+    match value {
+        JsonValue::Number(number) => match number.as_f64() {
+            Some(float) if number.is_f64() && float.fract() == 0.0 && float.abs() < 9e15 => {
+                text.push_str(&(float as i64).to_string())
+            }
+            _ => text.push_str(&number.to_string()),
+        },
+        JsonValue::Array(items) => {
+            text.push('[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    text.push(',');
+                }
+                write_canonical_key(item, text);
+            }
+            text.push(']');
+        }
+        JsonValue::Object(map) => {
+            let mut members: Vec<_> = map.iter().collect();
+            members.sort_by_key(|(key, _)| *key);
+            text.push('{');
+            for (index, (key, item)) in members.into_iter().enumerate() {
+                if index > 0 {
+                    text.push(',');
+                }
+                text.push_str(&JsonValue::String(key.clone()).to_string());
+                text.push(':');
+                write_canonical_key(item, text);
+            }
+            text.push('}');
+        }
+        _ => text.push_str(&value.to_string()),
+    }
+}
+
+fn key_entry(key: JsonValue, value: JsonValue) -> JsonValue {
+    let mut entry = Map::new();
+    entry.insert("key".to_string(), key);
+    entry.insert("value".to_string(), value);
+    JsonValue::Object(entry)
+}
+
 fn ensure_eq_compatible(value: &JsonValue, path: &str) -> Result<(), TransformError> {
     if value.is_null() {
         return Ok(());
@@ -1838,7 +4453,8 @@ fn ensure_eq_compatible(value: &JsonValue, path: &str) -> Result<(), TransformEr
     if value_to_string_optional(value).is_some() {
         return Ok(());
     }
-    Err(expr_type_error("value must be string/number/bool or null", path))
+    Err(expr_type_error("value must be string/number/bool or null", path)
+        .with_code(TransformErrorCode::ArgType))
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -1888,6 +4504,7 @@ fn eval_sort_key(
                 TransformErrorKind::ExprError,
                 "expr arg must not be missing",
             )
+            .with_code(TransformErrorCode::MissingArg)
             .with_path(base_path))
         }
         EvalValue::Value(value) => value,
@@ -1897,6 +4514,7 @@ fn eval_sort_key(
             TransformErrorKind::ExprError,
             "expr arg must not be null",
         )
+        .with_code(TransformErrorCode::NullArg)
         .with_path(base_path));
     }
 
@@ -1905,7 +4523,8 @@ fn eval_sort_key(
             let value = number
                 .as_f64()
                 .filter(|value| value.is_finite())
-                .ok_or_else(|| expr_type_error("sort_by key must be a finite number", base_path))?;
+                .ok_or_else(|| expr_type_error("sort_by key must be a finite number", base_path)
+                    .with_code(TransformErrorCode::ArgType))?;
             Ok(SortKey::Number(value))
         }
         JsonValue::String(value) => Ok(SortKey::String(value)),
@@ -1913,7 +4532,8 @@ fn eval_sort_key(
         _ => Err(expr_type_error(
             "sort_by key must be string/number/bool",
             base_path,
-        )),
+        )
+        .with_code(TransformErrorCode::ArgType)),
     }
 }
 
@@ -1932,6 +4552,7 @@ fn eval_array_map(
             TransformErrorKind::ExprError,
             "expr.args must contain exactly two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -1941,6 +4562,7 @@ fn eval_array_map(
             TransformErrorKind::ExprError,
             "expr.args index is out of bounds",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args[1]", base_path))
     })?;
     let expr_index = if injected.is_some() { 0 } else { 1 };
@@ -1971,6 +4593,7 @@ fn eval_array_filter(
             TransformErrorKind::ExprError,
             "expr.args must contain exactly two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -1980,6 +4603,7 @@ fn eval_array_filter(
             TransformErrorKind::ExprError,
             "expr.args index is out of bounds",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args[1]", base_path))
     })?;
     let expr_index = if injected.is_some() { 0 } else { 1 };
@@ -2011,6 +4635,7 @@ fn eval_array_flat_map(
             TransformErrorKind::ExprError,
             "expr.args must contain exactly two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -2020,6 +4645,7 @@ fn eval_array_flat_map(
             TransformErrorKind::ExprError,
             "expr.args index is out of bounds",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args[1]", base_path))
     })?;
     let expr_index = if injected.is_some() { 0 } else { 1 };
@@ -2068,6 +4694,7 @@ fn eval_array_flatten(
             TransformErrorKind::ExprError,
             "expr.args must contain one or two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -2084,6 +4711,7 @@ fn eval_array_flatten(
                 TransformErrorKind::ExprError,
                 "expr arg must not be null",
             )
+            .with_code(TransformErrorCode::NullArg)
             .with_path(depth_path));
         }
         let depth =
@@ -2093,6 +4721,7 @@ fn eval_array_flatten(
                 TransformErrorKind::ExprError,
                 "depth must be a non-negative integer",
             )
+            .with_code(TransformErrorCode::ArgRange)
             .with_path(depth_path));
         }
         usize::try_from(depth).map_err(|_| {
@@ -2100,6 +4729,7 @@ fn eval_array_flatten(
                 TransformErrorKind::ExprError,
                 "depth is too large",
             )
+            .with_code(TransformErrorCode::ArgRange)
             .with_path(depth_path)
         })?
     } else {
@@ -2129,6 +4759,7 @@ fn eval_array_take(
             TransformErrorKind::ExprError,
             "expr.args must contain exactly two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -2144,6 +4775,7 @@ fn eval_array_take(
             TransformErrorKind::ExprError,
             "expr arg must not be null",
         )
+        .with_code(TransformErrorCode::NullArg)
         .with_path(count_path));
     }
     let count = value_to_i64(&count_value, &count_path, "count must be an integer")?;
@@ -2181,6 +4813,7 @@ fn eval_array_drop(
             TransformErrorKind::ExprError,
             "expr.args must contain exactly two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -2196,6 +4829,7 @@ fn eval_array_drop(
             TransformErrorKind::ExprError,
             "expr arg must not be null",
         )
+        .with_code(TransformErrorCode::NullArg)
         .with_path(count_path));
     }
     let count = value_to_i64(&count_value, &count_path, "count must be an integer")?;
@@ -2233,6 +4867,7 @@ fn eval_array_slice(
             TransformErrorKind::ExprError,
             "expr.args must contain two or three items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -2250,6 +4885,7 @@ fn eval_array_slice(
             TransformErrorKind::ExprError,
             "expr arg must not be null",
         )
+        .with_code(TransformErrorCode::NullArg)
         .with_path(start_path));
     }
     let start = value_to_i64(&start_value, &start_path, "start must be an integer")?;
@@ -2266,6 +4902,7 @@ fn eval_array_slice(
                 TransformErrorKind::ExprError,
                 "expr arg must not be null",
             )
+            .with_code(TransformErrorCode::NullArg)
             .with_path(end_path));
         }
         value_to_i64(&end_value, &end_path, "end must be an integer")?
@@ -2302,6 +4939,7 @@ fn eval_array_chunk(
             TransformErrorKind::ExprError,
             "expr.args must contain exactly two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -2317,6 +4955,7 @@ fn eval_array_chunk(
             TransformErrorKind::ExprError,
             "expr arg must not be null",
         )
+        .with_code(TransformErrorCode::NullArg)
         .with_path(size_path));
     }
     let size = value_to_i64(&size_value, &size_path, "size must be a positive integer")?;
@@ -2325,6 +4964,7 @@ fn eval_array_chunk(
             TransformErrorKind::ExprError,
             "size must be a positive integer",
         )
+        .with_code(TransformErrorCode::ArgRange)
         .with_path(size_path));
     }
     let size = usize::try_from(size).map_err(|_| {
@@ -2332,6 +4972,7 @@ fn eval_array_chunk(
             TransformErrorKind::ExprError,
             "size is too large",
         )
+        .with_code(TransformErrorCode::ArgRange)
         .with_path(size_path)
     })?;
 
@@ -2361,6 +5002,7 @@ fn eval_array_zip(
             TransformErrorKind::ExprError,
             "expr.args must contain at least two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -2397,6 +5039,7 @@ fn eval_array_zip_with(
             TransformErrorKind::ExprError,
             "expr.args must contain at least three items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -2406,6 +5049,7 @@ fn eval_array_zip_with(
             TransformErrorKind::ExprError,
             "expr.args index is out of bounds",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args[{}]", base_path, expr_index))
     })?;
     let expr_arg_index = if injected.is_some() {
@@ -2457,6 +5101,7 @@ fn eval_array_unzip(
             TransformErrorKind::ExprError,
             "expr.args must contain exactly one item",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -2475,6 +5120,7 @@ fn eval_array_unzip(
                     TransformErrorKind::ExprError,
                     "unzip items must be arrays",
                 )
+                .with_code(TransformErrorCode::ArgType)
                 .with_path(format!("{}.args[0]", base_path)))
             }
         };
@@ -2484,6 +5130,7 @@ fn eval_array_unzip(
                     TransformErrorKind::ExprError,
                     "unzip items must have the same length",
                 )
+                .with_code(TransformErrorCode::ArgRange)
                 .with_path(format!("{}.args[0]", base_path)));
             }
         } else {
@@ -2514,11 +5161,12 @@ fn eval_array_group_by(
     locals: Option<&EvalLocals<'_>>,
 ) -> Result<EvalValue, TransformError> {
     let total_len = args_len(args, injected);
-    if total_len != 2 {
+    if !(2..=3).contains(&total_len) {
         return Err(TransformError::new(
             TransformErrorKind::ExprError,
-            "expr.args must contain exactly two items",
+            "expr.args must contain two or three items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -2528,10 +5176,31 @@ fn eval_array_group_by(
             TransformErrorKind::ExprError,
             "expr.args index is out of bounds",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args[1]", base_path))
     })?;
     let expr_index = if injected.is_some() { 0 } else { 1 };
     let expr_path = format!("{}.args[{}]", base_path, expr_index);
+    let mode = eval_key_mode(2, args, injected, record, context, out, base_path, locals)?;
+
+    if mode == KeyMode::Typed {
+        let mut groups: Vec<(JsonValue, Vec<JsonValue>)> = Vec::new();
+        let mut positions = HashMap::new();
+        for (index, item) in array.iter().enumerate() {
+            let item_locals = locals_with_item(locals, EvalItem { value: item, index });
+            let key = eval_typed_key(expr, record, context, out, &expr_path, Some(&item_locals))?;
+            let position = *positions.entry(canonical_key(&key)).or_insert_with(|| {
+                groups.push((key, Vec::new()));
+                groups.len() - 1
+            });
+            groups[position].1.push(item.clone());
+        }
+        let entries = groups
+            .into_iter()
+            .map(|(key, items)| key_entry(key, JsonValue::Array(items)))
+            .collect();
+        return Ok(EvalValue::Value(JsonValue::Array(entries)));
+    }
 
     let mut results = Map::new();
     for (index, item) in array.iter().enumerate() {
@@ -2558,11 +5227,12 @@ fn eval_array_key_by(
     locals: Option<&EvalLocals<'_>>,
 ) -> Result<EvalValue, TransformError> {
     let total_len = args_len(args, injected);
-    if total_len != 2 {
+    if !(2..=3).contains(&total_len) {
         return Err(TransformError::new(
             TransformErrorKind::ExprError,
-            "expr.args must contain exactly two items",
+            "expr.args must contain two or three items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -2572,10 +5242,35 @@ fn eval_array_key_by(
             TransformErrorKind::ExprError,
             "expr.args index is out of bounds",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args[1]", base_path))
     })?;
     let expr_index = if injected.is_some() { 0 } else { 1 };
     let expr_path = format!("{}.args[{}]", base_path, expr_index);
+    let mode = eval_key_mode(2, args, injected, record, context, out, base_path, locals)?;
+
+    if mode == KeyMode::Typed {
+        // Like the object form, a repeated key keeps its first position and its last item.
+        let mut entries: Vec<(JsonValue, JsonValue)> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        for (index, item) in array.iter().enumerate() {
+            let item_locals = locals_with_item(locals, EvalItem { value: item, index });
+            let key = eval_typed_key(expr, record, context, out, &expr_path, Some(&item_locals))?;
+            let canonical = canonical_key(&key);
+            match positions.get(&canonical) {
+                Some(&position) => entries[position].1 = item.clone(),
+                None => {
+                    positions.insert(canonical, entries.len());
+                    entries.push((key, item.clone()));
+                }
+            }
+        }
+        let entries = entries
+            .into_iter()
+            .map(|(key, item)| key_entry(key, item))
+            .collect();
+        return Ok(EvalValue::Value(JsonValue::Array(entries)));
+    }
 
     let mut results = Map::new();
     for (index, item) in array.iter().enumerate() {
@@ -2602,6 +5297,7 @@ fn eval_array_partition(
             TransformErrorKind::ExprError,
             "expr.args must contain exactly two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -2611,6 +5307,7 @@ fn eval_array_partition(
             TransformErrorKind::ExprError,
             "expr.args index is out of bounds",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args[1]", base_path))
     })?;
     let expr_index = if injected.is_some() { 0 } else { 1 };
@@ -2643,16 +5340,27 @@ fn eval_array_unique(
     locals: Option<&EvalLocals<'_>>,
 ) -> Result<EvalValue, TransformError> {
     let total_len = args_len(args, injected);
-    if total_len != 1 {
+    if !(1..=2).contains(&total_len) {
         return Err(TransformError::new(
             TransformErrorKind::ExprError,
-            "expr.args must contain exactly one item",
+            "expr.args must contain one or two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
     let array = eval_array_arg(0, args, injected, record, context, out, base_path, locals)?;
     let item_path = format!("{}.args[0]", base_path);
+    let mode = eval_key_mode(1, args, injected, record, context, out, base_path, locals)?;
+
+    if mode == KeyMode::Typed {
+        let mut seen = HashSet::new();
+        let results = array
+            .into_iter()
+            .filter(|item| seen.insert(canonical_key(item)))
+            .collect();
+        return Ok(EvalValue::Value(JsonValue::Array(results)));
+    }
 
     let mut results: Vec<JsonValue> = Vec::new();
     for item in array {
@@ -2682,11 +5390,12 @@ fn eval_array_distinct_by(
     locals: Option<&EvalLocals<'_>>,
 ) -> Result<EvalValue, TransformError> {
     let total_len = args_len(args, injected);
-    if total_len != 2 {
+    if !(2..=3).contains(&total_len) {
         return Err(TransformError::new(
             TransformErrorKind::ExprError,
-            "expr.args must contain exactly two items",
+            "expr.args must contain two or three items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -2696,16 +5405,30 @@ fn eval_array_distinct_by(
             TransformErrorKind::ExprError,
             "expr.args index is out of bounds",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args[1]", base_path))
     })?;
     let expr_index = if injected.is_some() { 0 } else { 1 };
     let expr_path = format!("{}.args[{}]", base_path, expr_index);
+    let mode = eval_key_mode(2, args, injected, record, context, out, base_path, locals)?;
 
     let mut results = Vec::new();
     let mut seen = HashSet::new();
     for (index, item) in array.iter().enumerate() {
         let item_locals = locals_with_item(locals, EvalItem { value: item, index });
-        let key = eval_key_expr_string(expr, record, context, out, &expr_path, Some(&item_locals))?;
+        let key = match mode {
+            KeyMode::String => {
+                eval_key_expr_string(expr, record, context, out, &expr_path, Some(&item_locals))?
+            }
+            KeyMode::Typed => canonical_key(&eval_typed_key(
+                expr,
+                record,
+                context,
+                out,
+                &expr_path,
+                Some(&item_locals),
+            )?),
+        };
         if seen.insert(key) {
             results.push(item.clone());
         }
@@ -2729,6 +5452,7 @@ fn eval_array_sort_by(
             TransformErrorKind::ExprError,
             "expr.args must contain two or three items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -2742,6 +5466,7 @@ fn eval_array_sort_by(
             TransformErrorKind::ExprError,
             "expr.args index is out of bounds",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args[1]", base_path))
     })?;
     let expr_index = if injected.is_some() { 0 } else { 1 };
@@ -2759,6 +5484,7 @@ fn eval_array_sort_by(
                 TransformErrorKind::ExprError,
                 "order must be asc or desc",
             )
+            .with_code(TransformErrorCode::ArgRange)
             .with_path(order_path));
         }
         value
@@ -2783,7 +5509,8 @@ fn eval_array_sort_by(
                 return Err(expr_type_error(
                     "sort_by keys must be all the same type",
                     &expr_path,
-                ));
+                )
+                .with_code(TransformErrorCode::ArgType));
             }
         } else {
             key_kind = Some(kind);
@@ -2829,6 +5556,7 @@ fn eval_array_find(
             TransformErrorKind::ExprError,
             "expr.args must contain exactly two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -2838,6 +5566,7 @@ fn eval_array_find(
             TransformErrorKind::ExprError,
             "expr.args index is out of bounds",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args[1]", base_path))
     })?;
     let expr_index = if injected.is_some() { 0 } else { 1 };
@@ -2868,6 +5597,7 @@ fn eval_array_find_index(
             TransformErrorKind::ExprError,
             "expr.args must contain exactly two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -2877,6 +5607,7 @@ fn eval_array_find_index(
             TransformErrorKind::ExprError,
             "expr.args index is out of bounds",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args[1]", base_path))
     })?;
     let expr_index = if injected.is_some() { 0 } else { 1 };
@@ -2907,6 +5638,7 @@ fn eval_array_index_of(
             TransformErrorKind::ExprError,
             "expr.args must contain exactly two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -2942,6 +5674,7 @@ fn eval_array_contains(
             TransformErrorKind::ExprError,
             "expr.args must contain exactly two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -2977,6 +5710,7 @@ fn eval_array_sum(
             TransformErrorKind::ExprError,
             "expr.args must contain exactly one item",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -3010,6 +5744,7 @@ fn eval_array_avg(
             TransformErrorKind::ExprError,
             "expr.args must contain exactly one item",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -3044,6 +5779,7 @@ fn eval_array_min(
             TransformErrorKind::ExprError,
             "expr.args must contain exactly one item",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -3083,6 +5819,7 @@ fn eval_array_max(
             TransformErrorKind::ExprError,
             "expr.args must contain exactly one item",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -3122,6 +5859,7 @@ fn eval_array_reduce(
             TransformErrorKind::ExprError,
             "expr.args must contain exactly two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -3135,6 +5873,7 @@ fn eval_array_reduce(
             TransformErrorKind::ExprError,
             "expr.args index is out of bounds",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args[1]", base_path))
     })?;
     let expr_index = if injected.is_some() { 0 } else { 1 };
@@ -3168,6 +5907,7 @@ fn eval_array_fold(
             TransformErrorKind::ExprError,
             "expr.args must contain exactly three items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -3183,6 +5923,7 @@ fn eval_array_fold(
             TransformErrorKind::ExprError,
             "expr.args index is out of bounds",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args[2]", base_path))
     })?;
     let expr_index = if injected.is_some() { 1 } else { 2 };
@@ -3201,6 +5942,7 @@ fn eval_array_fold(
     Ok(EvalValue::Value(acc))
 }
 
+#[allow(clippy::too_many_arguments)]
 fn eval_json_merge(
     args: &[Expr],
     injected: Option<&EvalValue>,
@@ -3211,14 +5953,42 @@ fn eval_json_merge(
     deep: bool,
     locals: Option<&EvalLocals<'_>>,
 ) -> Result<EvalValue, TransformError> {
-    let total_len = args_len(args, injected);
+    let mut total_len = args_len(args, injected);
+    let mut arrays = MergeArrays::Replace;
+    if deep && let Some(options) = merge_options_arg(args) {
+        total_len -= 1;
+        let option_path = format!("{}.args[{}].arrays", base_path, total_len);
+        arrays = match options.as_str() {
+            Some("replace") => MergeArrays::Replace,
+            Some("concat") => MergeArrays::Concat,
+            Some("by_index") => MergeArrays::ByIndex,
+            _ => {
+                return Err(TransformError::new(
+                    TransformErrorKind::ExprError,
+                    "arrays must be replace|concat|by_index",
+                )
+                .with_code(TransformErrorCode::BadOption)
+                .with_path(option_path))
+            }
+        };
+        if total_len < 2 {
+            return Err(TransformError::new(
+                TransformErrorKind::ExprError,
+                "expr.args must contain at least two items besides options",
+            )
+            .with_code(TransformErrorCode::ArgCount)
+            .with_path(format!("{}.args", base_path)));
+        }
+    }
     if total_len < 2 {
         return Err(TransformError::new(
             TransformErrorKind::ExprError,
             "expr.args must contain at least two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
+    let deep = deep.then_some(arrays);
 
     let mut result: Option<Map<String, JsonValue>> = None;
     for index in 0..total_len {
@@ -3233,6 +6003,7 @@ fn eval_json_merge(
                 TransformErrorKind::ExprError,
                 "expr arg must not be null",
             )
+            .with_code(TransformErrorCode::NullArg)
             .with_path(arg_path));
         }
         let obj = match value {
@@ -3242,6 +6013,7 @@ fn eval_json_merge(
                     TransformErrorKind::ExprError,
                     "expr arg must be object",
                 )
+                .with_code(TransformErrorCode::ArgType)
                 .with_path(arg_path))
             }
         };
@@ -3258,6 +6030,25 @@ fn eval_json_merge(
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MergeArrays {
+    Replace,
+    Concat,
+    ByIndex,
+}
+
+// Objects are valid deep_merge parts, so only a trailing literal whose sole key is a string
+// `arrays` is read as options.
+fn merge_options_arg(args: &[Expr]) -> Option<&JsonValue> {
+    let Some(Expr::Literal(JsonValue::Object(options))) = args.last() else {
+        return None;
+    };
+    match options.get("arrays") {
+        Some(arrays) if options.len() == 1 && arrays.is_string() => Some(arrays),
+        _ => None,
+    }
+}
+
 fn eval_json_get(
     args: &[Expr],
     injected: Option<&EvalValue>,
@@ -3273,6 +6064,7 @@ fn eval_json_get(
             TransformErrorKind::ExprError,
             "expr.args must contain exactly two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -3298,6 +6090,7 @@ fn eval_json_get(
             TransformErrorKind::ExprError,
             "expr arg must not be null",
         )
+        .with_code(TransformErrorCode::NullArg)
         .with_path(path_path));
     }
     let path = value_as_string(&path_value, &path_path)?;
@@ -3306,6 +6099,7 @@ fn eval_json_get(
             TransformErrorKind::ExprError,
             "path must be a non-empty string",
         )
+        .with_code(TransformErrorCode::BadPath)
         .with_path(path_path));
     }
     let tokens = parse_path_tokens(&path, TransformErrorKind::ExprError, &path_path)?;
@@ -3330,6 +6124,7 @@ fn eval_json_pick(
             TransformErrorKind::ExprError,
             "expr.args must contain exactly two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -3345,6 +6140,7 @@ fn eval_json_pick(
             TransformErrorKind::ExprError,
             "expr arg must not be null",
         )
+        .with_code(TransformErrorCode::NullArg)
         .with_path(base_path_arg));
     }
     let base_obj = match base_value {
@@ -3354,6 +6150,7 @@ fn eval_json_pick(
                 TransformErrorKind::ExprError,
                 "expr arg must be object",
             )
+            .with_code(TransformErrorCode::ArgType)
             .with_path(base_path_arg))
         }
     };
@@ -3367,7 +6164,11 @@ fn eval_json_pick(
     let mut output = JsonValue::Object(Map::new());
     for tokens in paths {
         if let Some(value) = get_path(&base_value, &tokens) {
-            set_path_with_indexes(&mut output, &tokens, value.clone(), base_path)?;
+            crate::path::set_path(&mut output, &tokens, value.clone()).map_err(|err| {
+                TransformError::new(TransformErrorKind::ExprError, err.message())
+                    .with_code(TransformErrorCode::PathConflict)
+                    .with_path(format!("{}.args[1]", base_path))
+            })?;
         }
     }
 
@@ -3389,6 +6190,7 @@ fn eval_json_omit(
             TransformErrorKind::ExprError,
             "expr.args must contain exactly two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -3404,6 +6206,7 @@ fn eval_json_omit(
             TransformErrorKind::ExprError,
             "expr arg must not be null",
         )
+        .with_code(TransformErrorCode::NullArg)
         .with_path(base_path_arg));
     }
     let base_obj = match base_value {
@@ -3413,6 +6216,7 @@ fn eval_json_omit(
                 TransformErrorKind::ExprError,
                 "expr arg must be object",
             )
+            .with_code(TransformErrorCode::ArgType)
             .with_path(base_path_arg))
         }
     };
@@ -3490,12 +6294,16 @@ fn eval_json_object_flatten(
     base_path: &str,
     locals: Option<&EvalLocals<'_>>,
 ) -> Result<EvalValue, TransformError> {
-    eval_json_object_unary(args, injected, record, context, out, base_path, locals, |map| {
-        let mut output = Map::new();
-        let mut tokens = Vec::new();
-        flatten_object(map, &mut tokens, &mut output, base_path)?;
-        Ok(JsonValue::Object(output))
-    })
+    let options = eval_flatten_options(args, injected, record, context, out, base_path, locals)?;
+    let map = match eval_object_arg(args, injected, record, context, out, base_path, locals)? {
+        None => return Ok(EvalValue::Missing),
+        Some(map) => map,
+    };
+
+    let mut output = Map::new();
+    let mut tokens = Vec::new();
+    flatten_object(&map, &mut tokens, &mut output, &options, base_path)?;
+    Ok(EvalValue::Value(JsonValue::Object(output)))
 }
 
 fn eval_json_object_unflatten(
@@ -3507,43 +6315,77 @@ fn eval_json_object_unflatten(
     base_path: &str,
     locals: Option<&EvalLocals<'_>>,
 ) -> Result<EvalValue, TransformError> {
-    eval_json_object_unary(args, injected, record, context, out, base_path, locals, |map| {
-        let mut paths = Vec::with_capacity(map.len());
-        let mut values = Vec::with_capacity(map.len());
-        for (key, value) in map {
-            let tokens = parse_path_tokens(
-                key,
+    let options = eval_flatten_options(args, injected, record, context, out, base_path, locals)?;
+    let map = match eval_object_arg(args, injected, record, context, out, base_path, locals)? {
+        None => return Ok(EvalValue::Missing),
+        Some(map) => map,
+    };
+
+    let mut paths = Vec::with_capacity(map.len());
+    let mut values = Vec::with_capacity(map.len());
+    for (key, value) in map {
+        let tokens = unflatten_key_tokens(&key, &options, base_path)?;
+        if has_path_conflict(&paths, &tokens) {
+            return Err(TransformError::new(
                 TransformErrorKind::ExprError,
-                format!("{}.args[0]", base_path),
-            )?;
-            if tokens.iter().any(|token| matches!(token, PathToken::Index(_))) {
-                return Err(TransformError::new(
-                    TransformErrorKind::ExprError,
-                    "array indexes are not allowed in path",
-                )
-                .with_path(format!("{}.args[0]", base_path)));
-            }
-            if has_path_conflict(&paths, &tokens) {
+                "path conflicts with another path",
+            )
+            .with_code(TransformErrorCode::PathConflict)
+            .with_path(format!("{}.args[0]", base_path)));
+        }
+        paths.push(tokens);
+        values.push(value);
+    }
+
+    if options.arrays == FlattenArrays::Index {
+        let mut root = UnflattenNode::Empty;
+        for (tokens, value) in paths.into_iter().zip(values) {
+            if !root.insert(&tokens, value) {
                 return Err(TransformError::new(
                     TransformErrorKind::ExprError,
                     "path conflicts with another path",
                 )
+                .with_code(TransformErrorCode::PathConflict)
                 .with_path(format!("{}.args[0]", base_path)));
             }
-            paths.push(tokens);
-            values.push(value.clone());
         }
+        return match root {
+            UnflattenNode::Empty => Ok(EvalValue::Value(JsonValue::Object(Map::new()))),
+            root => root.into_json().map(EvalValue::Value).ok_or_else(|| {
+                TransformError::new(
+                    TransformErrorKind::ExprError,
+                    "array indexes must be contiguous from 0",
+                )
+                .with_code(TransformErrorCode::BadPath)
+                .with_path(format!("{}.args[0]", base_path))
+            }),
+        };
+    }
 
-        let mut root = JsonValue::Object(Map::new());
-        for (tokens, value) in paths.into_iter().zip(values) {
-            set_path_object_only(&mut root, &tokens, value, base_path)?;
-        }
+    let mut root = JsonValue::Object(Map::new());
+    for (tokens, value) in paths.into_iter().zip(values) {
+        set_path_object_only(&mut root, &tokens, value, base_path)?;
+    }
 
-        Ok(root)
-    })
+    Ok(EvalValue::Value(root))
 }
 
-fn eval_json_object_unary<F>(
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FlattenArrays {
+    Keep,
+    Index,
+}
+
+// Options shared by object_flatten/object_unflatten so a pair with the same options round-trips.
+// Without `separator`, keys use the regular path syntax (`a.b`, `["d.e"]`, `a[0]`).
+#[derive(Debug)]
+struct FlattenOptions {
+    separator: Option<String>,
+    max_depth: Option<usize>,
+    arrays: FlattenArrays,
+}
+
+fn eval_flatten_options(
     args: &[Expr],
     injected: Option<&EvalValue>,
     record: &JsonValue,
@@ -3551,48 +6393,132 @@ fn eval_json_object_unary<F>(
     out: &JsonValue,
     base_path: &str,
     locals: Option<&EvalLocals<'_>>,
-    op: F,
-) -> Result<EvalValue, TransformError>
-where
-    F: FnOnce(&Map<String, JsonValue>) -> Result<JsonValue, TransformError>,
-{
+) -> Result<FlattenOptions, TransformError> {
     let total_len = args_len(args, injected);
-    if total_len != 1 {
+    if !(1..=2).contains(&total_len) {
         return Err(TransformError::new(
             TransformErrorKind::ExprError,
-            "expr.args must contain exactly one item",
+            "expr.args must contain one or two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
+    let mut options = FlattenOptions {
+        separator: None,
+        max_depth: None,
+        arrays: FlattenArrays::Keep,
+    };
+    if total_len == 1 {
+        return Ok(options);
+    }
+
+    let options_path = format!("{}.args[1]", base_path);
+    let value =
+        eval_expr_at_index(1, args, injected, record, context, out, base_path, locals)?;
+    let map = match value {
+        EvalValue::Value(JsonValue::Object(map)) => map,
+        _ => {
+            return Err(TransformError::new(
+                TransformErrorKind::ExprError,
+                "options must be an object",
+            )
+            .with_code(TransformErrorCode::BadOption)
+            .with_path(options_path))
+        }
+    };
+    for (key, value) in map {
+        let error = |message: &str| {
+            TransformError::new(TransformErrorKind::ExprError, message)
+                .with_code(TransformErrorCode::BadOption)
+                .with_path(format!("{}.{}", options_path, key))
+        };
+        match key.as_str() {
+            "separator" => match value {
+                JsonValue::String(separator) if !separator.is_empty() => {
+                    options.separator = Some(separator);
+                }
+                _ => return Err(error("separator must be a non-empty string")),
+            },
+            "max_depth" => match value.as_u64() {
+                Some(max_depth) => options.max_depth = Some(max_depth as usize),
+                None => return Err(error("max_depth must be a non-negative integer")),
+            },
+            "arrays" => match value.as_str() {
+                Some("keep") => options.arrays = FlattenArrays::Keep,
+                Some("index") => options.arrays = FlattenArrays::Index,
+                _ => return Err(error("arrays must be keep|index")),
+            },
+            _ => return Err(error("unknown option")),
+        }
+    }
+    Ok(options)
+}
+
+fn eval_object_arg(
+    args: &[Expr],
+    injected: Option<&EvalValue>,
+    record: &JsonValue,
+    context: Option<&JsonValue>,
+    out: &JsonValue,
+    base_path: &str,
+    locals: Option<&EvalLocals<'_>>,
+) -> Result<Option<Map<String, JsonValue>>, TransformError> {
     let arg_path = format!("{}.args[0]", base_path);
     let value =
         eval_expr_at_index(0, args, injected, record, context, out, base_path, locals)?;
     let value = match value {
-        EvalValue::Missing => return Ok(EvalValue::Missing),
+        EvalValue::Missing => return Ok(None),
         EvalValue::Value(value) => value,
     };
-    if value.is_null() {
-        return Err(TransformError::new(
+    match value {
+        JsonValue::Object(map) => Ok(Some(map)),
+        JsonValue::Null => Err(TransformError::new(
             TransformErrorKind::ExprError,
             "expr arg must not be null",
         )
-        .with_path(arg_path));
+        .with_code(TransformErrorCode::NullArg)
+        .with_path(arg_path)),
+        _ => Err(TransformError::new(
+            TransformErrorKind::ExprError,
+            "expr arg must be object",
+        )
+        .with_code(TransformErrorCode::ArgType)
+        .with_path(arg_path)),
     }
-    let map = match value {
-        JsonValue::Object(map) => map,
-        _ => {
-            return Err(TransformError::new(
-                TransformErrorKind::ExprError,
-                "expr arg must be object",
-            )
-            .with_path(arg_path))
-        }
-    };
+}
 
-    op(&map).map(EvalValue::Value)
+#[allow(clippy::too_many_arguments)]
+fn eval_json_object_unary<F>(
+    args: &[Expr],
+    injected: Option<&EvalValue>,
+    record: &JsonValue,
+    context: Option<&JsonValue>,
+    out: &JsonValue,
+    base_path: &str,
+    locals: Option<&EvalLocals<'_>>,
+    op: F,
+) -> Result<EvalValue, TransformError>
+where
+    F: FnOnce(&Map<String, JsonValue>) -> Result<JsonValue, TransformError>,
+{
+    let total_len = args_len(args, injected);
+    if total_len != 1 {
+        return Err(TransformError::new(
+            TransformErrorKind::ExprError,
+            "expr.args must contain exactly one item",
+        )
+        .with_code(TransformErrorCode::ArgCount)
+        .with_path(format!("{}.args", base_path)));
+    }
+
+    match eval_object_arg(args, injected, record, context, out, base_path, locals)? {
+        None => Ok(EvalValue::Missing),
+        Some(map) => op(&map).map(EvalValue::Value),
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn eval_json_paths_arg(
     args: &[Expr],
     injected: Option<&EvalValue>,
@@ -3616,6 +6542,7 @@ fn eval_json_paths_arg(
             TransformErrorKind::ExprError,
             "expr arg must not be null",
         )
+        .with_code(TransformErrorCode::NullArg)
         .with_path(arg_path));
     }
     let items: Vec<(String, String)> = match value {
@@ -3630,6 +6557,7 @@ fn eval_json_paths_arg(
                         TransformErrorKind::ExprError,
                         "paths must be a string or array of strings",
                     )
+                    .with_code(TransformErrorCode::BadPath)
                     .with_path(&item_path)
                 })?;
                 Ok::<(String, String), TransformError>((item_path, path.to_string()))
@@ -3640,6 +6568,7 @@ fn eval_json_paths_arg(
                 TransformErrorKind::ExprError,
                 "paths must be a string or array of strings",
             )
+            .with_code(TransformErrorCode::BadPath)
             .with_path(arg_path))
         }
     };
@@ -3652,6 +6581,7 @@ fn eval_json_paths_arg(
                 TransformErrorKind::ExprError,
                 "path must not end with array index",
             )
+            .with_code(TransformErrorCode::BadPath)
             .with_path(item_path));
         }
         if has_duplicate_path(&paths, &tokens) {
@@ -3662,6 +6592,7 @@ fn eval_json_paths_arg(
                 TransformErrorKind::ExprError,
                 "path conflicts with another path",
             )
+            .with_code(TransformErrorCode::PathConflict)
             .with_path(item_path));
         }
         paths.push(tokens);
@@ -3687,17 +6618,39 @@ fn is_path_prefix(prefix: &[PathToken], tokens: &[PathToken]) -> bool {
     prefix.iter().zip(tokens).all(|(left, right)| left == right)
 }
 
-fn merge_object(target: &mut Map<String, JsonValue>, incoming: &Map<String, JsonValue>, deep: bool) {
+// `deep` is `None` for a shallow merge.
+fn merge_object(
+    target: &mut Map<String, JsonValue>,
+    incoming: &Map<String, JsonValue>,
+    deep: Option<MergeArrays>,
+) {
     for (key, value) in incoming {
-        if deep {
-            if let (Some(JsonValue::Object(target_obj)), JsonValue::Object(incoming_obj)) =
-                (target.get_mut(key), value)
-            {
-                merge_object(target_obj, incoming_obj, true);
-                continue;
+        match (deep, target.get_mut(key)) {
+            (Some(arrays), Some(existing)) => merge_value(existing, value, arrays),
+            _ => {
+                target.insert(key.clone(), value.clone());
             }
         }
-        target.insert(key.clone(), value.clone());
+    }
+}
+
+fn merge_value(target: &mut JsonValue, incoming: &JsonValue, arrays: MergeArrays) {
+    match (target, incoming) {
+        (JsonValue::Object(target), JsonValue::Object(incoming)) => {
+            merge_object(target, incoming, Some(arrays))
+        }
+        (JsonValue::Array(target), JsonValue::Array(incoming)) if arrays == MergeArrays::Concat => {
+            target.extend(incoming.iter().cloned())
+        }
+        (JsonValue::Array(target), JsonValue::Array(incoming)) if arrays == MergeArrays::ByIndex => {
+            for (index, value) in incoming.iter().enumerate() {
+                match target.get_mut(index) {
+                    Some(existing) => merge_value(existing, value, arrays),
+                    None => target.push(value.clone()),
+                }
+            }
+        }
+        (target, incoming) => *target = incoming.clone(),
     }
 }
 
@@ -3705,74 +6658,241 @@ fn flatten_object(
     map: &Map<String, JsonValue>,
     tokens: &mut Vec<PathToken>,
     output: &mut Map<String, JsonValue>,
+    options: &FlattenOptions,
     base_path: &str,
 ) -> Result<(), TransformError> {
     for (key, value) in map {
-        if key.is_empty() {
-            return Err(TransformError::new(
-                TransformErrorKind::ExprError,
-                "object_flatten does not support empty keys",
-            )
-            .with_path(format!("{}.args[0]", base_path)));
-        }
-        if key.contains('[') || key.contains(']') {
-            return Err(TransformError::new(
-                TransformErrorKind::ExprError,
-                "object_flatten does not support keys with '[' or ']'",
-            )
-            .with_path(format!("{}.args[0]", base_path)));
-        }
+        check_flatten_key(key, options, base_path)?;
         tokens.push(PathToken::Key(key.clone()));
-        match value {
-            JsonValue::Object(child) => {
-                if child.is_empty() {
-                    let path = format_path_tokens(tokens);
-                    output.insert(path, JsonValue::Object(Map::new()));
-                } else {
-                    flatten_object(child, tokens, output, base_path)?;
-                }
+        flatten_object_value(value, tokens, output, options, base_path)?;
+        tokens.pop();
+    }
+    Ok(())
+}
+
+fn flatten_object_value(
+    value: &JsonValue,
+    tokens: &mut Vec<PathToken>,
+    output: &mut Map<String, JsonValue>,
+    options: &FlattenOptions,
+    base_path: &str,
+) -> Result<(), TransformError> {
+    let depth_reached = options.max_depth.is_some_and(|max| tokens.len() > max);
+    match value {
+        JsonValue::Object(child) if !child.is_empty() && !depth_reached => {
+            flatten_object(child, tokens, output, options, base_path)
+        }
+        JsonValue::Array(items)
+            if options.arrays == FlattenArrays::Index && !items.is_empty() && !depth_reached =>
+        {
+            for (index, item) in items.iter().enumerate() {
+                tokens.push(PathToken::Index(index));
+                flatten_object_value(item, tokens, output, options, base_path)?;
+                tokens.pop();
             }
-            _ => {
-                let path = format_path_tokens(tokens);
-                output.insert(path, value.clone());
+            Ok(())
+        }
+        _ => {
+            output.insert(flatten_key(tokens, options), value.clone());
+            Ok(())
+        }
+    }
+}
+
+fn check_flatten_key(
+    key: &str,
+    options: &FlattenOptions,
+    base_path: &str,
+) -> Result<(), TransformError> {
+    let message = if key.is_empty() {
+        "object_flatten does not support empty keys".to_string()
+    } else if let Some(separator) = &options.separator {
+        if key.contains(separator.as_str()) {
+            ambiguous_key_message(key, separator)
+        } else if options.arrays == FlattenArrays::Index && is_index_segment(key) {
+            format!("key '{}' is ambiguous with an array index", key)
+        } else {
+            return Ok(());
+        }
+    } else if key.contains('[') || key.contains(']') {
+        "object_flatten does not support keys with '[' or ']'".to_string()
+    } else {
+        return Ok(());
+    };
+    Err(TransformError::new(TransformErrorKind::ExprError, message)
+        .with_code(TransformErrorCode::AmbiguousKey)
+        .with_path(format!("{}.args[0]", base_path)))
+}
+
+fn flatten_key(tokens: &[PathToken], options: &FlattenOptions) -> String {
+    let Some(separator) = &options.separator else {
+        return format_path(tokens);
+    };
+    let segments: Vec<String> = tokens
+        .iter()
+        .map(|token| match token {
+            PathToken::Key(key) => key.clone(),
+            PathToken::Index(index) => index.to_string(),
+        })
+        .collect();
+    segments.join(separator)
+}
+
+fn unflatten_key_tokens(
+    key: &str,
+    options: &FlattenOptions,
+    base_path: &str,
+) -> Result<Vec<PathToken>, TransformError> {
+    let arg_path = format!("{}.args[0]", base_path);
+    let mut tokens = match &options.separator {
+        None => {
+            let tokens = parse_path_tokens(key, TransformErrorKind::ExprError, &arg_path)?;
+            if options.arrays == FlattenArrays::Keep
+                && tokens.iter().any(|token| matches!(token, PathToken::Index(_)))
+            {
+                return Err(TransformError::new(
+                    TransformErrorKind::ExprError,
+                    "array indexes are not allowed in path",
+                )
+                .with_code(TransformErrorCode::BadPath)
+                .with_path(arg_path));
             }
+            tokens
         }
-        tokens.pop();
+        Some(separator) => {
+            let segments = split_flattened_key(key, separator).ok_or_else(|| {
+                TransformError::new(
+                    TransformErrorKind::ExprError,
+                    ambiguous_key_message(key, separator),
+                )
+                .with_code(TransformErrorCode::AmbiguousKey)
+                .with_path(&arg_path)
+            })?;
+            segments
+                .into_iter()
+                .map(|segment| match segment.parse::<usize>() {
+                    Ok(index)
+                        if options.arrays == FlattenArrays::Index
+                            && is_index_segment(segment) =>
+                    {
+                        PathToken::Index(index)
+                    }
+                    _ => PathToken::Key(segment.to_string()),
+                })
+                .collect()
+        }
+    };
+
+    if let Some(max_depth) = options.max_depth
+        && tokens.len() > max_depth + 1
+    {
+        let rest = tokens.split_off(max_depth);
+        let rest_key = match &options.separator {
+            Some(_) => flatten_key(&rest, options),
+            None => format_path(&rest),
+        };
+        tokens.push(PathToken::Key(rest_key));
+    }
+    Ok(tokens)
+}
+
+// Splits on `separator`, rejecting keys whose split is ambiguous: empty segments or
+// overlapping separator matches (e.g. "a___b" with "__").
+fn split_flattened_key<'a>(key: &'a str, separator: &str) -> Option<Vec<&'a str>> {
+    let starts: Vec<usize> = key
+        .char_indices()
+        .map(|(index, _)| index)
+        .filter(|index| key[*index..].starts_with(separator))
+        .collect();
+    if starts
+        .windows(2)
+        .any(|pair| pair[1] < pair[0] + separator.len())
+    {
+        return None;
     }
-    Ok(())
+    let segments: Vec<&str> = key.split(separator).collect();
+    if segments.iter().any(|segment| segment.is_empty()) {
+        return None;
+    }
+    Some(segments)
+}
+
+fn ambiguous_key_message(key: &str, separator: &str) -> String {
+    format!("key '{}' is ambiguous with separator '{}'", key, separator)
+}
+
+fn is_index_segment(segment: &str) -> bool {
+    !segment.is_empty() && segment.bytes().all(|byte| byte.is_ascii_digit())
 }
 
-fn format_path_tokens(tokens: &[PathToken]) -> String {
-    let mut path = String::new();
-    for token in tokens {
-        match token {
+enum UnflattenNode {
+    Empty,
+    Value(JsonValue),
+    Object(BTreeMap<String, UnflattenNode>),
+    Array(BTreeMap<usize, UnflattenNode>),
+}
+
+impl UnflattenNode {
+    fn insert(&mut self, tokens: &[PathToken], value: JsonValue) -> bool {
+        let Some((first, rest)) = tokens.split_first() else {
+            if matches!(self, UnflattenNode::Empty) {
+                *self = UnflattenNode::Value(value);
+                return true;
+            }
+            return false;
+        };
+        match first {
             PathToken::Key(key) => {
-                if needs_bracket_quote(key) {
-                    let escaped = key.replace('\\', "\\\\").replace('"', "\\\"");
-                    path.push('[');
-                    path.push('"');
-                    path.push_str(&escaped);
-                    path.push('"');
-                    path.push(']');
-                } else {
-                    if !path.is_empty() {
-                        path.push('.');
-                    }
-                    path.push_str(key);
+                if matches!(self, UnflattenNode::Empty) {
+                    *self = UnflattenNode::Object(BTreeMap::new());
+                }
+                match self {
+                    UnflattenNode::Object(map) => map
+                        .entry(key.clone())
+                        .or_insert(UnflattenNode::Empty)
+                        .insert(rest, value),
+                    _ => false,
                 }
             }
             PathToken::Index(index) => {
-                path.push('[');
-                path.push_str(&index.to_string());
-                path.push(']');
+                if matches!(self, UnflattenNode::Empty) {
+                    *self = UnflattenNode::Array(BTreeMap::new());
+                }
+                match self {
+                    UnflattenNode::Array(items) => items
+                        .entry(*index)
+                        .or_insert(UnflattenNode::Empty)
+                        .insert(rest, value),
+                    _ => false,
+                }
             }
         }
     }
-    path
-}
 
-fn needs_bracket_quote(key: &str) -> bool {
-    key.contains('.')
+    // Returns None when an array has gaps in its indexes.
+    fn into_json(self) -> Option<JsonValue> {
+        match self {
+            UnflattenNode::Empty => Some(JsonValue::Null),
+            UnflattenNode::Value(value) => Some(value),
+            UnflattenNode::Object(map) => {
+                let mut output = Map::new();
+                for (key, node) in map {
+                    output.insert(key, node.into_json()?);
+                }
+                Some(JsonValue::Object(output))
+            }
+            UnflattenNode::Array(items) => {
+                let mut output = Vec::with_capacity(items.len());
+                for (position, (index, node)) in items.into_iter().enumerate() {
+                    if position != index {
+                        return None;
+                    }
+                    output.push(node.into_json()?);
+                }
+                Some(JsonValue::Array(output))
+            }
+        }
+    }
 }
 
 fn set_path_object_only(
@@ -3786,6 +6906,7 @@ fn set_path_object_only(
             TransformErrorKind::ExprError,
             "path is empty",
         )
+        .with_code(TransformErrorCode::BadPath)
         .with_path(format!("{}.args[0]", base_path)));
     }
 
@@ -3798,6 +6919,7 @@ fn set_path_object_only(
                     TransformErrorKind::ExprError,
                     "array indexes are not allowed in path",
                 )
+                .with_code(TransformErrorCode::BadPath)
                 .with_path(format!("{}.args[0]", base_path)))
             }
         };
@@ -3811,6 +6933,7 @@ fn set_path_object_only(
                             TransformErrorKind::ExprError,
                             "path conflicts with existing value",
                         )
+                        .with_code(TransformErrorCode::PathConflict)
                         .with_path(format!("{}.args[0]", base_path)));
                     }
                     map.insert(key.clone(), value);
@@ -3821,157 +6944,30 @@ fn set_path_object_only(
                     JsonValue::Object(Map::new())
                 });
                 if !entry.is_object() {
-                    return Err(TransformError::new(
-                        TransformErrorKind::ExprError,
-                        "path conflicts with non-object value",
-                    )
-                    .with_path(format!("{}.args[0]", base_path)));
-                }
-                current = entry;
-            }
-            _ => {
-                return Err(TransformError::new(
-                    TransformErrorKind::ExprError,
-                    "path conflicts with non-object value",
-                )
-                .with_path(format!("{}.args[0]", base_path)))
-            }
-        }
-    }
-
-    Ok(())
-}
-
-fn set_path_with_indexes(
-    root: &mut JsonValue,
-    tokens: &[PathToken],
-    value: JsonValue,
-    base_path: &str,
-) -> Result<(), TransformError> {
-    if tokens.is_empty() {
-        return Err(TransformError::new(
-            TransformErrorKind::ExprError,
-            "path is empty",
-        )
-        .with_path(format!("{}.args[1]", base_path)));
-    }
-
-    let mut current = root;
-    for (index, token) in tokens.iter().enumerate() {
-        let is_last = index == tokens.len() - 1;
-        match token {
-            PathToken::Key(key) => {
-                let next_token = tokens.get(index + 1);
-                match current {
-                    JsonValue::Object(map) => {
-                        if is_last {
-                            map.insert(key.clone(), value);
-                            return Ok(());
-                        }
-                        let entry = map.entry(key.clone()).or_insert_with(|| {
-                            match next_token {
-                                Some(PathToken::Index(_)) => JsonValue::Array(Vec::new()),
-                                _ => JsonValue::Object(Map::new()),
-                            }
-                        });
-                        let expect_index = matches!(next_token, Some(PathToken::Index(_)));
-                        let entry_is_array = matches!(entry, JsonValue::Array(_));
-                        let entry_is_object = matches!(entry, JsonValue::Object(_));
-                        if !(expect_index && entry_is_array
-                            || !expect_index && entry_is_object)
-                        {
-                            return Err(TransformError::new(
-                                TransformErrorKind::ExprError,
-                                "path conflicts with non-object value",
-                            )
-                            .with_path(format!("{}.args[1]", base_path)));
-                        }
-                        current = entry;
-                    }
-                    _ => {
-                        return Err(TransformError::new(
-                            TransformErrorKind::ExprError,
-                            "path conflicts with non-object value",
-                        )
-                        .with_path(format!("{}.args[1]", base_path)))
-                    }
-                }
-            }
-            PathToken::Index(path_index) => {
-                let next_token = tokens.get(index + 1);
-                match current {
-                    JsonValue::Array(items) => {
-                        if items.len() <= *path_index {
-                            items.resize_with(path_index + 1, || JsonValue::Null);
-                        }
-                        if is_last {
-                            items[*path_index] = value;
-                            return Ok(());
-                        }
-                        let entry = &mut items[*path_index];
-                        if entry.is_null() {
-                            *entry = match next_token {
-                                Some(PathToken::Index(_)) => JsonValue::Array(Vec::new()),
-                                _ => JsonValue::Object(Map::new()),
-                            };
-                        }
-                        let expect_index = matches!(next_token, Some(PathToken::Index(_)));
-                        let entry_is_array = matches!(entry, JsonValue::Array(_));
-                        let entry_is_object = matches!(entry, JsonValue::Object(_));
-                        if !(expect_index && entry_is_array
-                            || !expect_index && entry_is_object)
-                        {
-                            return Err(TransformError::new(
-                                TransformErrorKind::ExprError,
-                                "path conflicts with non-object value",
-                            )
-                            .with_path(format!("{}.args[1]", base_path)));
-                        }
-                        current = entry;
-                    }
-                    _ => {
-                        return Err(TransformError::new(
-                            TransformErrorKind::ExprError,
-                            "path conflicts with non-object value",
-                        )
-                        .with_path(format!("{}.args[1]", base_path)))
-                    }
-                }
-            }
-        }
-    }
-
-    Ok(())
-}
-
-fn remove_path(root: &mut JsonValue, tokens: &[PathToken]) {
-    if tokens.is_empty() {
-        return;
-    }
-
-    let (first, rest) = tokens.split_first().unwrap();
-    match first {
-        PathToken::Key(key) => {
-            if let JsonValue::Object(map) = root {
-                if rest.is_empty() {
-                    map.remove(key);
-                    return;
-                }
-                if let Some(next) = map.get_mut(key) {
-                    remove_path(next, rest);
+                    return Err(TransformError::new(
+                        TransformErrorKind::ExprError,
+                        "path conflicts with non-object value",
+                    )
+                    .with_code(TransformErrorCode::PathConflict)
+                    .with_path(format!("{}.args[0]", base_path)));
                 }
+                current = entry;
             }
-        }
-        PathToken::Index(index) => {
-            if let JsonValue::Array(items) = root {
-                if let Some(next) = items.get_mut(*index) {
-                    remove_path(next, rest);
-                }
+            _ => {
+                return Err(TransformError::new(
+                    TransformErrorKind::ExprError,
+                    "path conflicts with non-object value",
+                )
+                .with_code(TransformErrorCode::PathConflict)
+                .with_path(format!("{}.args[0]", base_path)))
             }
         }
     }
+
+    Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 fn eval_bool_and_or(
     args: &[Expr],
     injected: Option<&EvalValue>,
@@ -3988,6 +6984,7 @@ fn eval_bool_and_or(
             TransformErrorKind::ExprError,
             "expr.args must contain at least two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -4035,6 +7032,7 @@ fn eval_bool_not(
             TransformErrorKind::ExprError,
             "expr.args must contain exactly one item",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -4049,6 +7047,91 @@ fn eval_bool_not(
     }
 }
 
+fn eval_if(
+    args: &[Expr],
+    injected: Option<&EvalValue>,
+    record: &JsonValue,
+    context: Option<&JsonValue>,
+    out: &JsonValue,
+    base_path: &str,
+    locals: Option<&EvalLocals<'_>>,
+) -> Result<EvalValue, TransformError> {
+    let total_len = args_len(args, injected);
+    if !(2..=3).contains(&total_len) {
+        return Err(TransformError::new(
+            TransformErrorKind::ExprError,
+            "expr.args must contain two or three items",
+        )
+        .with_code(TransformErrorCode::ArgCount)
+        .with_path(format!("{}.args", base_path)));
+    }
+
+    if eval_condition_at(0, args, injected, record, context, out, base_path, locals)? {
+        eval_expr_at_index(1, args, injected, record, context, out, base_path, locals)
+    } else if total_len == 3 {
+        eval_expr_at_index(2, args, injected, record, context, out, base_path, locals)
+    } else {
+        Ok(EvalValue::Missing)
+    }
+}
+
+fn eval_case(
+    args: &[Expr],
+    injected: Option<&EvalValue>,
+    record: &JsonValue,
+    context: Option<&JsonValue>,
+    out: &JsonValue,
+    base_path: &str,
+    locals: Option<&EvalLocals<'_>>,
+) -> Result<EvalValue, TransformError> {
+    let total_len = args_len(args, injected);
+    if total_len < 2 {
+        return Err(TransformError::new(
+            TransformErrorKind::ExprError,
+            "expr.args must contain at least two items",
+        )
+        .with_code(TransformErrorCode::ArgCount)
+        .with_path(format!("{}.args", base_path)));
+    }
+
+    // Pairs of [condition, value]; an odd trailing arg is the default.
+    let mut index = 0;
+    while index + 1 < total_len {
+        if eval_condition_at(index, args, injected, record, context, out, base_path, locals)? {
+            return eval_expr_at_index(index + 1, args, injected, record, context, out, base_path, locals);
+        }
+        index += 2;
+    }
+    if index < total_len {
+        eval_expr_at_index(index, args, injected, record, context, out, base_path, locals)
+    } else {
+        Ok(EvalValue::Missing)
+    }
+}
+
+// Conditions follow `when`: anything other than a boolean (including missing) is an error.
+#[allow(clippy::too_many_arguments)]
+fn eval_condition_at(
+    index: usize,
+    args: &[Expr],
+    injected: Option<&EvalValue>,
+    record: &JsonValue,
+    context: Option<&JsonValue>,
+    out: &JsonValue,
+    base_path: &str,
+    locals: Option<&EvalLocals<'_>>,
+) -> Result<bool, TransformError> {
+    let value = eval_expr_at_index(index, args, injected, record, context, out, base_path, locals)?;
+    match value {
+        EvalValue::Value(JsonValue::Bool(flag)) => Ok(flag),
+        _ => Err(expr_type_error(
+            "condition must evaluate to boolean",
+            &format!("{}.args[{}]", base_path, index),
+        )
+        .with_code(TransformErrorCode::NotBoolean)),
+    }
+}
+
 fn eval_compare(
     expr_op: &ExprOp,
     injected: Option<&EvalValue>,
@@ -4064,6 +7147,7 @@ fn eval_compare(
             TransformErrorKind::ExprError,
             "expr.args must contain exactly two items",
         )
+        .with_code(TransformErrorCode::ArgCount)
         .with_path(format!("{}.args", base_path)));
     }
 
@@ -4085,6 +7169,7 @@ fn eval_compare(
                 TransformErrorKind::ExprError,
                 "expr.op is not supported",
             )
+            .with_code(TransformErrorCode::UnknownOp)
             .with_path(format!("{}.op", base_path)))
         }
     };
@@ -4168,6 +7253,7 @@ fn parse_format_list(value: &JsonValue, path: &str) -> Result<Vec<String>, Trans
                     TransformErrorKind::ExprError,
                     "input_format must not be empty",
                 )
+                .with_code(TransformErrorCode::BadDateFormat)
                 .with_path(path))
             } else {
                 Ok(vec![s.clone()])
@@ -4179,6 +7265,7 @@ fn parse_format_list(value: &JsonValue, path: &str) -> Result<Vec<String>, Trans
                     TransformErrorKind::ExprError,
                     "input_format must not be empty",
                 )
+                .with_code(TransformErrorCode::BadDateFormat)
                 .with_path(path));
             }
             let mut formats = Vec::with_capacity(items.len());
@@ -4191,6 +7278,7 @@ fn parse_format_list(value: &JsonValue, path: &str) -> Result<Vec<String>, Trans
                             TransformErrorKind::ExprError,
                             "input_format must be a string or array of strings",
                         )
+                        .with_code(TransformErrorCode::BadDateFormat)
                         .with_path(item_path))
                     }
                 };
@@ -4199,6 +7287,7 @@ fn parse_format_list(value: &JsonValue, path: &str) -> Result<Vec<String>, Trans
                         TransformErrorKind::ExprError,
                         "input_format must not be empty",
                     )
+                    .with_code(TransformErrorCode::BadDateFormat)
                     .with_path(item_path));
                 }
                 formats.push(value.to_string());
@@ -4209,14 +7298,97 @@ fn parse_format_list(value: &JsonValue, path: &str) -> Result<Vec<String>, Trans
             TransformErrorKind::ExprError,
             "input_format must be a string or array of strings",
         )
+        .with_code(TransformErrorCode::BadDateFormat)
         .with_path(path)),
     }
 }
 
+// The first argument of `date_format` / `to_unixtime`: a date string or an epoch number.
+enum DateInput {
+    Text(String),
+    Epoch(serde_json::Number),
+}
+
+#[derive(Clone, Copy)]
+enum EpochUnit {
+    Seconds,
+    Millis,
+}
+
+impl EpochUnit {
+    fn parse(value: &str, path: &str) -> Result<Self, TransformError> {
+        match value {
+            "s" => Ok(EpochUnit::Seconds),
+            "ms" => Ok(EpochUnit::Millis),
+            _ => Err(expr_type_error("unit must be s or ms", path)
+                .with_code(TransformErrorCode::BadUnit)),
+        }
+    }
+}
+
+// Epochs at or above this magnitude are taken as milliseconds: 1e11 seconds is past the
+// year 5000, while 1e11 milliseconds is 1973.
+const EPOCH_MILLIS_THRESHOLD: f64 = 1e11;
+
+fn eval_date_input(
+    args: &[Expr],
+    injected: Option<&EvalValue>,
+    record: &JsonValue,
+    context: Option<&JsonValue>,
+    out: &JsonValue,
+    base_path: &str,
+    locals: Option<&EvalLocals<'_>>,
+) -> Result<Option<DateInput>, TransformError> {
+    let value = match eval_arg_value_at(0, args, injected, record, context, out, base_path, locals)? {
+        None => return Ok(None),
+        Some(value) => value,
+    };
+    let value_path = format!("{}.args[0]", base_path);
+    match value {
+        JsonValue::Null => Err(expr_type_error("expr arg must not be null", &value_path)
+            .with_code(TransformErrorCode::NullArg)),
+        JsonValue::Number(number) => Ok(Some(DateInput::Epoch(number))),
+        value => value_as_string(&value, &value_path).map(|value| Some(DateInput::Text(value))),
+    }
+}
+
+fn epoch_to_datetime(
+    number: &serde_json::Number,
+    unit: Option<EpochUnit>,
+    path: &str,
+) -> Result<DateTime<FixedOffset>, TransformError> {
+    let out_of_range = || expr_type_error("epoch value is out of range", path)
+        .with_code(TransformErrorCode::InvalidDate);
+    let value = number.as_f64().filter(|value| value.is_finite()).ok_or_else(out_of_range)?;
+    let unit = unit.unwrap_or(if value.abs() >= EPOCH_MILLIS_THRESHOLD {
+        EpochUnit::Millis
+    } else {
+        EpochUnit::Seconds
+    });
+    let millis = match (unit, number.as_i64()) {
+        (EpochUnit::Millis, Some(millis)) => millis,
+        (EpochUnit::Seconds, Some(seconds)) => seconds.checked_mul(1000).ok_or_else(out_of_range)?,
+        (unit, None) => {
+            let millis = match unit {
+                EpochUnit::Millis => value,
+                EpochUnit::Seconds => value * 1000.0,
+            }
+            .round();
+            if !(i64::MIN as f64..i64::MAX as f64).contains(&millis) {
+                return Err(out_of_range());
+            }
+            millis as i64
+        }
+    };
+    DateTime::from_timestamp_millis(millis)
+        .map(|dt| dt.fixed_offset())
+        .ok_or_else(out_of_range)
+}
+
 fn parse_datetime(
     value: &str,
     formats: Option<&[String]>,
-    timezone: Option<FixedOffset>,
+    timezone: Option<Zone>,
     path: &str,
 ) -> Result<DateTime<FixedOffset>, TransformError> {
     if let Some(formats) = formats {
@@ -4247,7 +7419,7 @@ fn parse_datetime(
 fn parse_datetime_with_formats(
     value: &str,
     formats: &[String],
-    timezone: Option<FixedOffset>,
+    timezone: Option<Zone>,
     path: &str,
 ) -> Result<DateTime<FixedOffset>, TransformError> {
     for format in formats {
@@ -4260,7 +7432,8 @@ fn parse_datetime_with_formats(
         if let Ok(date) = NaiveDate::parse_from_str(value, format) {
             let naive = date
                 .and_hms_opt(0, 0, 0)
-                .ok_or_else(|| expr_type_error("date is invalid", path))?;
+                .ok_or_else(|| expr_type_error("date is invalid", path)
+                    .with_code(TransformErrorCode::InvalidDate))?;
             return apply_timezone(naive, timezone, path);
         }
     }
@@ -4269,35 +7442,80 @@ fn parse_datetime_with_formats(
         TransformErrorKind::ExprError,
         "date format is invalid",
     )
+    .with_code(TransformErrorCode::BadDateFormat)
     .with_path(path))
 }
 
 fn apply_timezone(
     naive: NaiveDateTime,
-    timezone: Option<FixedOffset>,
+    timezone: Option<Zone>,
     path: &str,
 ) -> Result<DateTime<FixedOffset>, TransformError> {
-    let offset = timezone.unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
-    offset
-        .from_local_datetime(&naive)
-        .single()
-        .ok_or_else(|| expr_type_error("date is invalid", path))
+    let local = match timezone {
+        None => FixedOffset::east_opt(0).unwrap().from_local_datetime(&naive).single(),
+        Some(Zone::Fixed(offset)) => offset.from_local_datetime(&naive).single(),
+        // A wall time repeated by a DST change resolves to its first occurrence; one
+        // skipped by it does not exist.
+        Some(Zone::Named(tz)) => tz
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(|dt| dt.fixed_offset()),
+    };
+    local.ok_or_else(|| expr_type_error("date is invalid", path)
+        .with_code(TransformErrorCode::InvalidDate))
+}
+
+#[derive(Clone, Copy)]
+enum Zone {
+    Fixed(FixedOffset),
+    Named(Tz),
+}
+
+impl Zone {
+    fn convert(&self, dt: &DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+        match self {
+            Zone::Fixed(offset) => dt.with_timezone(offset),
+            Zone::Named(tz) => dt.with_timezone(tz).fixed_offset(),
+        }
+    }
 }
 
+// An argument that may be a timezone or a date format: formats always contain `%`, zones
+// are `UTC`/`Z`, an offset, or an IANA name such as `Asia/Tokyo`.
 fn looks_like_timezone(value: &str) -> bool {
     if value.eq_ignore_ascii_case("utc") || value == "Z" {
         return true;
     }
-    matches!(value.chars().next(), Some('+') | Some('-'))
+    if matches!(value.chars().next(), Some('+') | Some('-')) {
+        return true;
+    }
+    !value.contains('%') && (value.contains('/') || value.parse::<Tz>().is_ok())
+}
+
+fn parse_timezone(value: &str, path: &str) -> Result<Zone, TransformError> {
+    if value.eq_ignore_ascii_case("utc")
+        || value == "Z"
+        || matches!(value.chars().next(), Some('+') | Some('-'))
+    {
+        return parse_fixed_offset(value, path).map(Zone::Fixed);
+    }
+    value.parse::<Tz>().map(Zone::Named).map_err(|_| {
+        expr_type_error(
+            "timezone must be UTC, an offset like +09:00 or an IANA name like Asia/Tokyo",
+            path,
+        )
+        .with_code(TransformErrorCode::BadTimezone)
+    })
 }
 
-fn parse_timezone(value: &str, path: &str) -> Result<FixedOffset, TransformError> {
+fn parse_fixed_offset(value: &str, path: &str) -> Result<FixedOffset, TransformError> {
     if value.eq_ignore_ascii_case("utc") || value == "Z" {
         return FixedOffset::east_opt(0).ok_or_else(|| {
             TransformError::new(
                 TransformErrorKind::ExprError,
                 "timezone must be UTC or an offset like +09:00",
             )
+            .with_code(TransformErrorCode::BadTimezone)
             .with_path(path)
         });
     }
@@ -4310,6 +7528,7 @@ fn parse_timezone(value: &str, path: &str) -> Result<FixedOffset, TransformError
                 TransformErrorKind::ExprError,
                 "timezone must be UTC or an offset like +09:00",
             )
+            .with_code(TransformErrorCode::BadTimezone)
             .with_path(path))
         }
     };
@@ -4324,6 +7543,7 @@ fn parse_timezone(value: &str, path: &str) -> Result<FixedOffset, TransformError
                     TransformErrorKind::ExprError,
                     "timezone must be UTC or an offset like +09:00",
                 )
+                .with_code(TransformErrorCode::BadTimezone)
                 .with_path(path))
             }
         }
@@ -4338,6 +7558,7 @@ fn parse_timezone(value: &str, path: &str) -> Result<FixedOffset, TransformError
                             TransformErrorKind::ExprError,
                             "timezone must be UTC or an offset like +09:00",
                         )
+                        .with_code(TransformErrorCode::BadTimezone)
                         .with_path(path))
                     }
                 }
@@ -4352,6 +7573,7 @@ fn parse_timezone(value: &str, path: &str) -> Result<FixedOffset, TransformError
                             TransformErrorKind::ExprError,
                             "timezone must be UTC or an offset like +09:00",
                         )
+                        .with_code(TransformErrorCode::BadTimezone)
                         .with_path(path))
                     }
                 }
@@ -4361,6 +7583,7 @@ fn parse_timezone(value: &str, path: &str) -> Result<FixedOffset, TransformError
                     TransformErrorKind::ExprError,
                     "timezone must be UTC or an offset like +09:00",
                 )
+                .with_code(TransformErrorCode::BadTimezone)
                 .with_path(path))
             }
         }
@@ -4371,6 +7594,7 @@ fn parse_timezone(value: &str, path: &str) -> Result<FixedOffset, TransformError
             TransformErrorKind::ExprError,
             "timezone must be UTC or an offset like +09:00",
         )
+        .with_code(TransformErrorCode::BadTimezone)
         .with_path(path));
     }
 
@@ -4380,6 +7604,7 @@ fn parse_timezone(value: &str, path: &str) -> Result<FixedOffset, TransformError
             TransformErrorKind::ExprError,
             "timezone must be UTC or an offset like +09:00",
         )
+        .with_code(TransformErrorCode::BadTimezone)
         .with_path(path)
     })
 }
@@ -4393,6 +7618,7 @@ fn value_to_string(value: &JsonValue, path: &str) -> Result<String, TransformErr
             TransformErrorKind::ExprError,
             "value must be string/number/bool",
         )
+        .with_code(TransformErrorCode::ArgType)
         .with_path(path)),
     }
 }
@@ -4404,6 +7630,7 @@ fn value_as_string(value: &JsonValue, path: &str) -> Result<String, TransformErr
             TransformErrorKind::ExprError,
             "value must be a string",
         )
+        .with_code(TransformErrorCode::ArgType)
         .with_path(path)),
     }
 }
@@ -4411,7 +7638,8 @@ fn value_as_string(value: &JsonValue, path: &str) -> Result<String, TransformErr
 fn value_as_bool(value: &JsonValue, path: &str) -> Result<bool, TransformError> {
     match value {
         JsonValue::Bool(flag) => Ok(*flag),
-        _ => Err(expr_type_error("value must be a boolean", path)),
+        _ => Err(expr_type_error("value must be a boolean", path)
+            .with_code(TransformErrorCode::ArgType)),
     }
 }
 
@@ -4420,13 +7648,13 @@ fn value_to_number(value: &JsonValue, path: &str, message: &str) -> Result<f64,
         JsonValue::Number(n) => n
             .as_f64()
             .filter(|f| f.is_finite())
-            .ok_or_else(|| expr_type_error(message, path)),
+            .ok_or_else(|| expr_type_error(message, path).with_code(TransformErrorCode::ArgType)),
         JsonValue::String(s) => s
             .parse::<f64>()
             .ok()
             .filter(|f| f.is_finite())
-            .ok_or_else(|| expr_type_error(message, path)),
-        _ => Err(expr_type_error(message, path)),
+            .ok_or_else(|| expr_type_error(message, path).with_code(TransformErrorCode::ArgType)),
+        _ => Err(expr_type_error(message, path).with_code(TransformErrorCode::ArgType)),
     }
 }
 
@@ -4436,34 +7664,38 @@ fn value_to_i64(value: &JsonValue, path: &str, message: &str) -> Result<i64, Tra
             if let Some(i) = n.as_i64() {
                 Ok(i)
             } else if let Some(u) = n.as_u64() {
-                i64::try_from(u).map_err(|_| expr_type_error(message, path))
+                i64::try_from(u).map_err(|_| expr_type_error(message, path)
+                    .with_code(TransformErrorCode::ArgType))
             } else if let Some(f) = n.as_f64() {
                 if f.is_finite() && (f.fract()).abs() < f64::EPSILON {
                     let value = f as i64;
                     if (value as f64 - f).abs() < f64::EPSILON {
                         Ok(value)
                     } else {
-                        Err(expr_type_error(message, path))
+                        Err(expr_type_error(message, path).with_code(TransformErrorCode::ArgType))
                     }
                 } else {
-                    Err(expr_type_error(message, path))
+                    Err(expr_type_error(message, path).with_code(TransformErrorCode::ArgType))
                 }
             } else {
-                Err(expr_type_error(message, path))
+                Err(expr_type_error(message, path).with_code(TransformErrorCode::ArgType))
             }
         }
         JsonValue::String(s) => s
             .parse::<i64>()
-            .map_err(|_| expr_type_error(message, path)),
-        _ => Err(expr_type_error(message, path)),
+            .map_err(|_| expr_type_error(message, path).with_code(TransformErrorCode::ArgType)),
+        _ => Err(expr_type_error(message, path).with_code(TransformErrorCode::ArgType)),
     }
 }
 
 fn json_number_from_f64(value: f64, path: &str) -> Result<JsonValue, TransformError> {
     if !value.is_finite() {
-        return Err(expr_type_error("number result is not finite", path));
+        return Err(expr_type_error("number result is not finite", path)
+            .with_code(TransformErrorCode::NumberOutOfRange));
     }
-    if (value.fract()).abs() < f64::EPSILON {
+    // `as` saturates, so 2^63 would otherwise come back as i64::MAX.
+    let in_range = value >= i64::MIN as f64 && value < i64::MAX as f64;
+    if in_range && (value.fract()).abs() < f64::EPSILON {
         let as_i64 = value as i64;
         if (as_i64 as f64 - value).abs() < f64::EPSILON {
             return Ok(JsonValue::Number(as_i64.into()));
@@ -4471,13 +7703,15 @@ fn json_number_from_f64(value: f64, path: &str) -> Result<JsonValue, TransformEr
     }
     serde_json::Number::from_f64(value)
         .map(JsonValue::Number)
-        .ok_or_else(|| expr_type_error("number result is not finite", path))
+        .ok_or_else(|| expr_type_error("number result is not finite", path)
+            .with_code(TransformErrorCode::NumberOutOfRange))
 }
 
 fn to_radix_string(value: i64, base: u32, path: &str) -> Result<String, TransformError> {
     let digits = b"0123456789abcdefghijklmnopqrstuvwxyz";
-    if base < 2 || base > 36 {
-        return Err(expr_type_error("base must be between 2 and 36", path));
+    if !(2..=36).contains(&base) {
+        return Err(expr_type_error("base must be between 2 and 36", path)
+            .with_code(TransformErrorCode::ArgRange));
     }
 
     if value == 0 {
@@ -4487,6 +7721,7 @@ fn to_radix_string(value: i64, base: u32, path: &str) -> Result<String, Transfor
     let is_negative = value < 0;
     let mut n = value.checked_abs().ok_or_else(|| {
         expr_type_error("value is out of range for base conversion", path)
+            .with_code(TransformErrorCode::NumberOutOfRange)
     })? as u64;
 
     let mut buf = Vec::new();
@@ -4547,12 +7782,161 @@ fn cast_value(
         "int" => cast_to_int(value, path),
         "float" => cast_to_float(value, path),
         "bool" => cast_to_bool(value, path),
-        _ => Err(TransformError::new(
-            TransformErrorKind::TypeCastFailed,
-            "type must be string|int|float|bool",
-        )
-        .with_path(path)),
+        "date" => cast_to_datetime(value, path, "date")
+            .map(|dt| JsonValue::String(dt.format("%Y-%m-%d").to_string())),
+        "datetime" => cast_to_datetime(value, path, "datetime")
+            .map(|dt| JsonValue::String(dt.to_rfc3339_opts(SecondsFormat::AutoSi, true))),
+        _ => match decimal_scale(type_name) {
+            Some(scale) => cast_to_decimal(value, scale, path),
+            None => Err(TransformError::new(
+                TransformErrorKind::TypeCastFailed,
+                CAST_TYPES_MESSAGE,
+            )
+            .with_code(TransformErrorCode::BadCastType)
+            .with_path(path)),
+        },
+    }
+}
+
+pub(crate) const CAST_TYPES_MESSAGE: &str =
+    "type must be string|int|float|bool|date|datetime|decimal|decimal(N)";
+
+const MAX_DECIMAL_SCALE: u32 = 32;
+
+/// `Some(None)` for `decimal`, `Some(Some(n))` for `decimal(n)`, `None` for any other type name.
+pub(crate) fn decimal_scale(type_name: &str) -> Option<Option<u32>> {
+    if type_name == "decimal" {
+        return Some(None);
+    }
+    let digits = type_name.strip_prefix("decimal(")?.strip_suffix(')')?;
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits
+        .parse::<u32>()
+        .ok()
+        .filter(|scale| *scale <= MAX_DECIMAL_SCALE)
+        .map(Some)
+}
+
+pub(crate) fn is_cast_type(type_name: &str) -> bool {
+    matches!(
+        type_name,
+        "string" | "int" | "float" | "bool" | "date" | "datetime"
+    ) || decimal_scale(type_name).is_some()
+}
+
+// Strings only, read with the same heuristics as the date ops; naive values are taken as UTC.
+fn cast_to_datetime(
+    value: &JsonValue,
+    path: &str,
+    type_name: &str,
+) -> Result<DateTime<FixedOffset>, TransformError> {
+    match value {
+        JsonValue::String(s) => {
+            parse_datetime(s, None, None, path).map_err(|_| type_cast_error(type_name, path))
+        }
+        _ => Err(type_cast_error(type_name, path)),
+    }
+}
+
+// Works on the decimal digits of the input so that no f64 rounding is involved. With a scale,
+// extra fraction digits are rounded half away from zero and missing ones are zero-filled.
+fn cast_to_decimal(
+    value: &JsonValue,
+    scale: Option<u32>,
+    path: &str,
+) -> Result<JsonValue, TransformError> {
+    let text = match value {
+        JsonValue::Number(n) => n.to_string(),
+        JsonValue::String(s) => s.clone(),
+        _ => return Err(type_cast_error("decimal", path)),
+    };
+    let (negative, mut int_part, mut frac_part) =
+        parse_decimal_digits(&text).ok_or_else(|| type_cast_error("decimal", path))?;
+
+    if let Some(scale) = scale.map(|scale| scale as usize) {
+        if frac_part.len() > scale {
+            let round_up = frac_part.as_bytes()[scale] >= b'5';
+            frac_part.truncate(scale);
+            if round_up {
+                let mut digits = format!("{}{}", int_part, frac_part).into_bytes();
+                let mut carry = true;
+                for digit in digits.iter_mut().rev() {
+                    if *digit == b'9' {
+                        *digit = b'0';
+                    } else {
+                        *digit += 1;
+                        carry = false;
+                        break;
+                    }
+                }
+                if carry {
+                    digits.insert(0, b'1');
+                }
+                let digits = String::from_utf8(digits).unwrap_or_default();
+                let split = digits.len() - scale;
+                int_part = digits[..split].to_string();
+                frac_part = digits[split..].to_string();
+            }
+        }
+        while frac_part.len() < scale {
+            frac_part.push('0');
+        }
+    }
+
+    let int_part = int_part.trim_start_matches('0');
+    let int_part = if int_part.is_empty() { "0" } else { int_part };
+    let is_zero = int_part == "0" && frac_part.bytes().all(|b| b == b'0');
+    let mut out = String::new();
+    if negative && !is_zero {
+        out.push('-');
+    }
+    out.push_str(int_part);
+    if !frac_part.is_empty() {
+        out.push('.');
+        out.push_str(&frac_part);
+    }
+    Ok(JsonValue::String(out))
+}
+
+// Splits `-12.5e1` style text into sign, integer digits and fraction digits, applying the
+// exponent. Exponents are bounded so that the expansion stays small.
+fn parse_decimal_digits(text: &str) -> Option<(bool, String, String)> {
+    let (negative, rest) = match text.as_bytes().first()? {
+        b'-' => (true, &text[1..]),
+        b'+' => (false, &text[1..]),
+        _ => (false, text),
+    };
+    let (mantissa, exponent) = match rest.find(['e', 'E']) {
+        Some(index) => (&rest[..index], rest[index + 1..].parse::<i32>().ok()?),
+        None => (rest, 0),
+    };
+    if exponent.unsigned_abs() > 1024 {
+        return None;
+    }
+    let (int_part, frac_part) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if int_part.is_empty() && frac_part.is_empty()
+        || !int_part.bytes().all(|b| b.is_ascii_digit())
+        || !frac_part.bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
     }
+
+    let digits = format!("{}{}", int_part, frac_part);
+    let point = int_part.len() as i64 + exponent as i64;
+    let (int_digits, frac_digits) = if point <= 0 {
+        (String::new(), format!("{}{}", "0".repeat((-point) as usize), digits))
+    } else if point as usize >= digits.len() {
+        (
+            format!("{}{}", digits, "0".repeat(point as usize - digits.len())),
+            String::new(),
+        )
+    } else {
+        let (int_digits, frac_digits) = digits.split_at(point as usize);
+        (int_digits.to_string(), frac_digits.to_string())
+    };
+    Some((negative, int_digits, frac_digits))
 }
 
 fn cast_to_int(value: &JsonValue, path: &str) -> Result<JsonValue, TransformError> {
@@ -4617,26 +8001,46 @@ fn type_cast_error(type_name: &str, path: &str) -> TransformError {
         TransformErrorKind::TypeCastFailed,
         format!("failed to cast to {}", type_name),
     )
+    .with_code(TransformErrorCode::CastFailed)
     .with_path(path)
 }
 
+/// The path a mapping `source` reads from the input record, `None` for other namespaces or
+/// an invalid source.
+pub(crate) fn input_source_tokens(source: &str) -> Option<Vec<PathToken>> {
+    match parse_source(source).ok()? {
+        (Namespace::Input, path) => parse_path(path).ok(),
+        _ => None,
+    }
+}
+
 fn parse_source(source: &str) -> Result<(Namespace, &str), TransformError> {
-    if let Some((prefix, path)) = source.split_once('.') {
+    // A bracket after a key that is no namespace belongs to an input path (`items[0].id`).
+    let split = split_namespace(source).filter(|(prefix, _)| {
+        !prefix.is_empty()
+            && (source[prefix.len()..].starts_with('.')
+                || matches!(*prefix, "input" | "context" | "out" | "const" | "env"))
+    });
+    if let Some((prefix, path)) = split {
         if path.is_empty() {
             return Err(TransformError::new(
                 TransformErrorKind::InvalidRef,
                 "reference path is empty",
-            ));
+            )
+            .with_code(TransformErrorCode::BadRef));
         }
         let namespace = match prefix {
             "input" => Namespace::Input,
             "context" => Namespace::Context,
             "out" => Namespace::Out,
+            "const" => Namespace::Const,
+            "env" => Namespace::Env,
             _ => {
                 return Err(TransformError::new(
                     TransformErrorKind::InvalidRef,
-                    "ref namespace must be input|context|out",
-                ))
+                    "ref namespace must be input|context|out|const|env",
+                )
+                .with_code(TransformErrorCode::BadRef))
             }
         };
         Ok((namespace, path))
@@ -4645,25 +8049,29 @@ fn parse_source(source: &str) -> Result<(Namespace, &str), TransformError> {
             return Err(TransformError::new(
                 TransformErrorKind::InvalidRef,
                 "reference path is empty",
-            ));
+            )
+            .with_code(TransformErrorCode::BadRef));
         }
         Ok((Namespace::Input, source))
     }
 }
 
 fn parse_ref(value: &str) -> Result<(Namespace, &str), TransformError> {
-    let (prefix, path) = value.split_once('.').ok_or_else(|| {
+    let split = split_namespace(value).filter(|(prefix, _)| !prefix.is_empty());
+    let (prefix, path) = split.ok_or_else(|| {
         TransformError::new(
             TransformErrorKind::InvalidRef,
             "ref must include namespace",
         )
+        .with_code(TransformErrorCode::BadRef)
     })?;
 
     if path.is_empty() {
         return Err(TransformError::new(
             TransformErrorKind::InvalidRef,
             "ref path is empty",
-        ));
+        )
+        .with_code(TransformErrorCode::BadRef));
     }
 
     let namespace = match prefix {
@@ -4672,11 +8080,14 @@ fn parse_ref(value: &str) -> Result<(Namespace, &str), TransformError> {
         "out" => Namespace::Out,
         "item" => Namespace::Item,
         "acc" => Namespace::Acc,
+        "const" => Namespace::Const,
+        "env" => Namespace::Env,
         _ => {
             return Err(TransformError::new(
                 TransformErrorKind::InvalidRef,
-                "ref namespace must be input|context|out|item|acc",
-            ))
+                "ref namespace must be input|context|out|item|acc|const|env",
+            )
+            .with_code(TransformErrorCode::BadRef))
         }
     };
 
@@ -4688,73 +8099,101 @@ fn parse_path_tokens(
     kind: TransformErrorKind,
     error_path: impl Into<String>,
 ) -> Result<Vec<PathToken>, TransformError> {
+    let code = match kind {
+        TransformErrorKind::InvalidRef => TransformErrorCode::BadRef,
+        TransformErrorKind::InvalidTarget => TransformErrorCode::BadTarget,
+        _ => TransformErrorCode::BadPath,
+    };
     parse_path(path).map_err(|err| {
-        TransformError::new(kind, err.message()).with_path(error_path.into())
+        TransformError::new(kind, err.message())
+            .with_code(code)
+            .with_path(error_path.into())
     })
 }
 
-fn set_path(
+fn target_error(message: &str, mapping_path: &str) -> TransformError {
+    TransformError::new(TransformErrorKind::InvalidTarget, message)
+        .with_path(format!("{}.target", mapping_path))
+}
+
+fn target_conflict(mapping_path: &str) -> TransformError {
+    target_error("target path conflicts with non-object value", mapping_path)
+        .with_code(TransformErrorCode::TargetConflict)
+}
+
+fn set_target(
     root: &mut JsonValue,
     path: &str,
-    value: JsonValue,
+    value: MappedValue,
     mapping_path: &str,
 ) -> Result<(), TransformError> {
-    let tokens = parse_path_tokens(
-        path,
-        TransformErrorKind::InvalidTarget,
-        format!("{}.target", mapping_path),
-    )?;
-    if tokens.is_empty() {
-        return Err(TransformError::new(
-            TransformErrorKind::InvalidTarget,
-            "target path is invalid",
-        )
-        .with_path(format!("{}.target", mapping_path)));
+    let (tokens, item_tokens) = match parse_array_target(path) {
+        Ok(Some((array_tokens, item_tokens))) => (array_tokens, Some(item_tokens)),
+        Ok(None) => (
+            parse_path_tokens(
+                path,
+                TransformErrorKind::InvalidTarget,
+                format!("{}.target", mapping_path),
+            )?,
+            None,
+        ),
+        Err(err) => return Err(target_error(err.message(), mapping_path)
+            .with_code(TransformErrorCode::BadTarget)),
+    };
+    if tokens
+        .iter()
+        .chain(item_tokens.iter().flatten())
+        .any(|token| matches!(token, PathToken::Index(_)))
+    {
+        return Err(target_error("target path must not include indexes", mapping_path)
+            .with_code(TransformErrorCode::BadTarget));
+    }
+    if !root.is_object() {
+        return Err(target_error("target root must be an object", mapping_path)
+            .with_code(TransformErrorCode::BadTarget));
     }
 
-    let mut current = root;
-    for (index, token) in tokens.iter().enumerate() {
-        let is_last = index == tokens.len() - 1;
-        let key = match token {
-            PathToken::Key(key) => key,
-            PathToken::Index(_) => {
-                return Err(TransformError::new(
-                    TransformErrorKind::InvalidTarget,
-                    "target path must not include indexes",
-                )
-                .with_path(format!("{}.target", mapping_path)))
-            }
-        };
-
-        match current {
-            JsonValue::Object(map) => {
-                if is_last {
-                    map.insert(key.to_string(), value);
-                    return Ok(());
-                }
-
-                let entry = map.entry(key.to_string()).or_insert_with(|| {
-                    JsonValue::Object(Map::new())
-                });
-                if !entry.is_object() {
-                    return Err(TransformError::new(
-                        TransformErrorKind::InvalidTarget,
-                        "target path conflicts with non-object value",
-                    )
-                    .with_path(format!("{}.target", mapping_path)));
-                }
-                current = entry;
-            }
-            _ => {
-                return Err(TransformError::new(
-                    TransformErrorKind::InvalidTarget,
-                    "target root must be an object",
-                )
-                .with_path(format!("{}.target", mapping_path)))
-            }
+    let Some(item_tokens) = item_tokens else {
+        return crate::path::set_path(root, &tokens, value.to_json())
+            .map_err(|_| target_conflict(mapping_path));
+    };
+    let values = match value {
+        MappedValue::Elements(values) => values,
+        MappedValue::Value(JsonValue::Array(values)) => values.into_iter().map(Some).collect(),
+        MappedValue::Value(_) => {
+            return Err(target_error("array target requires an array value", mapping_path)
+                .with_code(TransformErrorCode::BadTarget));
+        }
+    };
+    if get_path(root, &tokens).is_none() {
+        crate::path::set_path(root, &tokens, JsonValue::Array(Vec::new()))
+            .map_err(|_| target_conflict(mapping_path))?;
+    }
+    let Some(JsonValue::Array(items)) = get_path_mut(root, &tokens) else {
+        return Err(target_error(
+            "array target conflicts with non-array value",
+            mapping_path,
+        )
+        .with_code(TransformErrorCode::TargetConflict));
+    };
+    if items.len() < values.len() {
+        items.resize_with(values.len(), || JsonValue::Null);
+    }
+    // A missing element still creates its item, so the items stay aligned with the elements;
+    // with `tags[]` its slot is null.
+    for (item, value) in items.iter_mut().zip(values) {
+        if item_tokens.is_empty() {
+            *item = value.unwrap_or(JsonValue::Null);
+            continue;
+        }
+        if item.is_null() {
+            *item = JsonValue::Object(Map::new());
+        }
+        if let Some(value) = value {
+            crate::path::set_path(item, &item_tokens, value)
+                .map_err(|_| target_conflict(mapping_path))?;
         }
     }
-
     Ok(())
 }
 
@@ -4772,6 +8211,8 @@ enum Namespace {
     Out,
     Item,
     Acc,
+    Const,
+    Env,
 }
 
 #[derive(Clone, Copy)]
@@ -4791,3 +8232,12 @@ enum EvalValue {
     Missing,
     Value(JsonValue),
 }
+
+impl EvalValue {
+    fn as_value(&self) -> Option<&JsonValue> {
+        match self {
+            EvalValue::Missing => None,
+            EvalValue::Value(value) => Some(value),
+        }
+    }
+}
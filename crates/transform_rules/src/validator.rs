@@ -1,26 +1,62 @@
 use std::collections::HashSet;
 
-use crate::error::{ErrorCode, RuleError, ValidationResult};
+use crate::csv_input::{CsvColumnType, CSV_COLUMN_TYPES_MESSAGE};
+use crate::error::{
+    ErrorCode, RuleError, RuleWarning, ValidationReport, ValidationResult, WarningCode,
+    YamlLocation,
+};
 use crate::locator::YamlLocator;
-use crate::model::{Expr, ExprChain, ExprOp, ExprRef, InputFormat, Mapping, RuleFile};
-use crate::path::{parse_path, PathToken};
+use crate::model::{
+    Expr, ExprChain, ExprOp, ExprRef, InputFormat, Mapping, MappingSource, OutputFormat, RuleFile,
+};
+use crate::path::{parse_array_target, parse_path, split_namespace, PathToken};
+use crate::transform::{
+    coalesce_options_arg, is_cast_type, op_spec, parse_format_template, FormatPart, OpSpec, CAST_TYPES_MESSAGE,
+    DEFAULT_MAX_JSON_DEPTH,
+};
 
 pub fn validate_rule_file(rule: &RuleFile) -> ValidationResult {
-    validate_rule_file_with_locator(rule, None)
+    validate_rule_file_with_locator(rule, None, false).into_result()
 }
 
 pub fn validate_rule_file_with_source(rule: &RuleFile, source: &str) -> ValidationResult {
     let locator = YamlLocator::from_str(source);
-    validate_rule_file_with_locator(rule, Some(&locator))
+    validate_rule_file_with_locator(rule, Some(&locator), false).into_result()
 }
 
-fn validate_rule_file_with_locator(rule: &RuleFile, locator: Option<&YamlLocator>) -> ValidationResult {
-    let mut ctx = ValidationCtx::new(locator);
+/// Like [`validate_rule_file_with_source`], also reporting rules that are legal but suspicious,
+/// such as a `when` that is a literal or a `required` mapping with a `default`.
+pub fn validate_rule_file_full(rule: &RuleFile, source: Option<&str>) -> ValidationReport {
+    let locator = source.map(YamlLocator::from_str);
+    validate_rule_file_with_locator(rule, locator.as_ref(), true)
+}
+
+// Rules from `load_rule_file` are located in the files that declared each node instead.
+fn validate_rule_file_with_locator(
+    rule: &RuleFile,
+    locator: Option<&YamlLocator>,
+    with_warnings: bool,
+) -> ValidationReport {
+    let origin_locators: Vec<_> = rule
+        .origins
+        .iter()
+        .flat_map(|origins| &origins.files)
+        .map(|file| YamlLocator::from_str(&file.yaml))
+        .collect();
+    let mut ctx = ValidationCtx::new(rule, locator, &origin_locators);
 
     validate_version(rule, &mut ctx);
     validate_input(rule, &mut ctx);
+    validate_tables(rule, &mut ctx);
+    validate_output(rule, &mut ctx);
+    validate_constants(rule, &mut ctx);
     validate_record_when(rule, &mut ctx);
+    validate_group(rule, &mut ctx);
+    validate_mapping_groups(rule, &mut ctx);
     validate_mappings(rule, &mut ctx);
+    if with_warnings {
+        collect_rule_warnings(rule, &mut ctx);
+    }
 
     ctx.finish()
 }
@@ -51,6 +87,20 @@ fn validate_input(rule: &RuleFile, ctx: &mut ValidationCtx<'_>) {
                 );
             }
         }
+        InputFormat::Ndjson => {
+            if rule
+                .input
+                .json
+                .as_ref()
+                .is_some_and(|json| json.records_path.is_some())
+            {
+                ctx.push(
+                    ErrorCode::InvalidInputFormat,
+                    "input.json.records_path is not supported when format=ndjson",
+                    "input.json.records_path",
+                );
+            }
+        }
     }
 
     if let Some(csv) = &rule.input.csv {
@@ -68,21 +118,136 @@ fn validate_input(rule: &RuleFile, ctx: &mut ValidationCtx<'_>) {
                 "input.csv.columns",
             );
         }
+        let columns = csv.columns.as_deref().unwrap_or_default();
+        for (index, column) in columns.iter().enumerate() {
+            let path = format!("input.csv.columns[{}]", index);
+            if column.name.is_empty() {
+                ctx.push(
+                    ErrorCode::InvalidCsvColumns,
+                    "column name must not be empty",
+                    format!("{}.name", path),
+                );
+            } else if columns[..index].iter().any(|other| other.name == column.name) {
+                ctx.push(
+                    ErrorCode::InvalidCsvColumns,
+                    "column name is duplicated",
+                    format!("{}.name", path),
+                );
+            }
+            if let Some(type_name) = &column.value_type
+                && CsvColumnType::parse(type_name).is_none()
+            {
+                ctx.push(
+                    ErrorCode::InvalidTypeName,
+                    CSV_COLUMN_TYPES_MESSAGE,
+                    format!("{}.type", path),
+                );
+            }
+        }
+    }
+
+    if let Some(json) = &rule.input.json
+        && let Some(path) = json.records_path.as_deref()
+        && parse_path(path).is_err()
+    {
+        ctx.push(
+            ErrorCode::InvalidPath,
+            "records_path is invalid",
+            "input.json.records_path",
+        );
     }
 
-    if let Some(json) = &rule.input.json {
-        if let Some(path) = json.records_path.as_deref() {
-            if parse_path(path).is_err() {
+    if let Some(json) = &rule.input.json
+        && let Some(max_depth) = json.max_depth
+        && !(1..=DEFAULT_MAX_JSON_DEPTH).contains(&max_depth)
+    {
+        ctx.push(
+            ErrorCode::InvalidMaxDepth,
+            &format!("max_depth must be between 1 and {}", DEFAULT_MAX_JSON_DEPTH),
+            "input.json.max_depth",
+        );
+    }
+}
+
+fn validate_tables(rule: &RuleFile, ctx: &mut ValidationCtx<'_>) {
+    let is_key_path = |path: &str| {
+        parse_path(path).is_ok_and(|tokens| {
+            tokens.iter().all(|token| matches!(token, PathToken::Key(_)))
+        })
+    };
+    for (name, table) in rule.tables.iter().flatten() {
+        let path = format!("tables.{}", name);
+        if let Some(fields) = &table.fields {
+            if fields.is_empty() {
                 ctx.push(
                     ErrorCode::InvalidPath,
-                    "records_path is invalid",
-                    "input.json.records_path",
+                    "fields must be a non-empty array",
+                    format!("{}.fields", path),
                 );
             }
+            for (index, field) in fields.iter().enumerate() {
+                if !is_key_path(field) {
+                    ctx.push(
+                        ErrorCode::InvalidPath,
+                        "field must be a key path without indexes",
+                        format!("{}.fields[{}]", path, index),
+                    );
+                }
+            }
+        }
+        if let Some(key) = &table.key
+            && !is_key_path(key)
+        {
+            ctx.push(
+                ErrorCode::InvalidPath,
+                "key must be a key path without indexes",
+                format!("{}.key", path),
+            );
+        }
+    }
+}
+
+fn validate_output(rule: &RuleFile, ctx: &mut ValidationCtx<'_>) {
+    let Some(output) = &rule.output else {
+        return;
+    };
+    let Some(columns) = &output.columns else {
+        return;
+    };
+    if output.format != OutputFormat::Csv {
+        ctx.push(
+            ErrorCode::InvalidOutputColumns,
+            "output.columns requires output.format: csv",
+            "output.columns",
+        );
+        return;
+    }
+    if columns.is_empty() {
+        ctx.push(
+            ErrorCode::InvalidOutputColumns,
+            "output.columns must be a non-empty array",
+            "output.columns",
+        );
+    }
+    for (index, column) in columns.iter().enumerate() {
+        let path = format!("output.columns[{}]", index);
+        if column.is_empty() {
+            ctx.push(ErrorCode::InvalidOutputColumns, "column name must not be empty", path);
+        } else if columns[..index].contains(column) {
+            ctx.push(ErrorCode::InvalidOutputColumns, "column name is duplicated", path);
         }
     }
 }
 
+// Constants are evaluated once per run, before any record, so they can only read `context`.
+fn validate_constants(rule: &RuleFile, ctx: &mut ValidationCtx<'_>) {
+    let produced_targets = HashSet::new();
+    for (name, expr) in rule.constants.iter().flatten() {
+        let base_path = format!("constants.{}", name);
+        validate_expr(expr, &base_path, &produced_targets, ctx, LocalScope::None);
+    }
+}
+
 fn validate_record_when(rule: &RuleFile, ctx: &mut ValidationCtx<'_>) {
     let expr = match rule.record_when.as_ref() {
         Some(expr) => expr,
@@ -95,8 +260,62 @@ fn validate_record_when(rule: &RuleFile, ctx: &mut ValidationCtx<'_>) {
     validate_when_expr(expr, base_path, ctx);
 }
 
+fn validate_group(rule: &RuleFile, ctx: &mut ValidationCtx<'_>) {
+    let Some(group) = &rule.group else {
+        return;
+    };
+
+    if rule.record_when.is_some() {
+        ctx.push(
+            ErrorCode::AmbiguousRecordWhen,
+            "record_when is ambiguous with group; use group.record_when to filter input records",
+            "record_when",
+        );
+    }
+
+    let produced_targets = HashSet::new();
+    validate_expr(&group.key, "group.key", &produced_targets, ctx, LocalScope::None);
+    if let Some(expr) = &group.record_when {
+        validate_expr(expr, "group.record_when", &produced_targets, ctx, LocalScope::None);
+        validate_when_expr(expr, "group.record_when", ctx);
+    }
+}
+
+fn validate_mapping_groups(rule: &RuleFile, ctx: &mut ValidationCtx<'_>) {
+    let Some(groups) = &rule.mapping_groups else {
+        return;
+    };
+
+    if groups.with_mappings {
+        ctx.push(
+            ErrorCode::MappingsAndGroupsExclusive,
+            "mappings and mapping_groups are mutually exclusive",
+            "mapping_groups",
+        );
+    }
+
+    let mut seen = HashSet::new();
+    for (index, name) in groups.group_order.iter().flatten().enumerate() {
+        let path = format!("group_order[{}]", index);
+        if !groups.groups.iter().any(|group| &group.name == name) {
+            ctx.push(
+                ErrorCode::InvalidGroupOrder,
+                &format!("group_order references unknown group '{}'", name),
+                path,
+            );
+        } else if !seen.insert(name) {
+            ctx.push(
+                ErrorCode::InvalidGroupOrder,
+                &format!("group_order lists '{}' more than once", name),
+                path,
+            );
+        }
+    }
+}
+
 fn validate_mappings(rule: &RuleFile, ctx: &mut ValidationCtx<'_>) {
     let mut produced_targets: HashSet<Vec<PathToken>> = HashSet::new();
+    let mut target_shapes: Vec<TargetShape<'_>> = Vec::new();
 
     for (index, mapping) in rule.mappings.iter().enumerate() {
         let base = format!("mappings[{}]", index);
@@ -109,8 +328,13 @@ fn validate_mappings(rule: &RuleFile, ctx: &mut ValidationCtx<'_>) {
             );
         }
 
-        let target_tokens = match parse_path(&mapping.target) {
-            Ok(tokens) => tokens,
+        let parsed = match parse_array_target(&mapping.target) {
+            Ok(Some((array_tokens, item_tokens))) => Ok((array_tokens, Some(item_tokens))),
+            Ok(None) => parse_path(&mapping.target).map(|tokens| (tokens, None)),
+            Err(err) => Err(err),
+        };
+        let (array_tokens, item_tokens) = match parsed {
+            Ok(parsed) => parsed,
             Err(_) => {
                 ctx.push(
                     ErrorCode::InvalidPath,
@@ -120,8 +344,9 @@ fn validate_mappings(rule: &RuleFile, ctx: &mut ValidationCtx<'_>) {
                 continue;
             }
         };
-        if target_tokens
+        if array_tokens
             .iter()
+            .chain(item_tokens.iter().flatten())
             .any(|token| matches!(token, PathToken::Index(_)))
         {
             ctx.push(
@@ -132,13 +357,17 @@ fn validate_mappings(rule: &RuleFile, ctx: &mut ValidationCtx<'_>) {
             continue;
         }
 
-        if produced_targets.contains(&target_tokens) {
-            ctx.push(
-                ErrorCode::DuplicateTarget,
-                "mapping.target is duplicated",
-                format!("{}.target", base),
-            );
-        }
+        let shape = TargetShape {
+            index,
+            target: &mapping.target,
+            tokens: array_tokens,
+            item_tokens,
+            conditional: mapping.when.is_some(),
+            object_valued: is_object_valued(mapping),
+        };
+        validate_target_shape(&shape, &target_shapes, ctx);
+        let target_tokens = shape.full_tokens();
+        target_shapes.push(shape);
 
         let value_count = count_value_fields(mapping);
         if value_count == 0 {
@@ -155,18 +384,32 @@ fn validate_mappings(rule: &RuleFile, ctx: &mut ValidationCtx<'_>) {
             );
         }
 
-        if let Some(type_name) = &mapping.value_type {
-            if !is_valid_type_name(type_name) {
-                ctx.push(
-                    ErrorCode::InvalidTypeName,
-                    "type must be string|int|float|bool",
-                    format!("{}.type", base),
-                );
-            }
+        if let Some(type_name) = &mapping.value_type
+            && !is_cast_type(type_name)
+        {
+            ctx.push(
+                ErrorCode::InvalidTypeName,
+                CAST_TYPES_MESSAGE,
+                format!("{}.type", base),
+            );
         }
 
-        if let Some(source) = &mapping.source {
-            validate_source(source, &base, &produced_targets, ctx);
+        match &mapping.source {
+            Some(MappingSource::Single(source)) => {
+                validate_source(source, &format!("{}.source", base), &produced_targets, ctx);
+            }
+            Some(MappingSource::Fallback(sources)) if sources.is_empty() => ctx.push(
+                ErrorCode::MissingMappingValue,
+                "source list must not be empty",
+                format!("{}.source", base),
+            ),
+            Some(MappingSource::Fallback(sources)) => {
+                for (index, source) in sources.iter().enumerate() {
+                    let source_path = format!("{}.source[{}]", base, index);
+                    validate_source(source, &source_path, &produced_targets, ctx);
+                }
+            }
+            None => {}
         }
 
         if let Some(expr) = &mapping.expr {
@@ -174,6 +417,11 @@ fn validate_mappings(rule: &RuleFile, ctx: &mut ValidationCtx<'_>) {
             validate_expr(expr, &expr_path, &produced_targets, ctx, LocalScope::None);
         }
 
+        if let Some(default) = &mapping.default {
+            let default_path = format!("{}.default", base);
+            validate_expr(default, &default_path, &produced_targets, ctx, LocalScope::None);
+        }
+
         if let Some(when) = &mapping.when {
             let when_path = format!("{}.when", base);
             validate_expr(when, &when_path, &produced_targets, ctx, LocalScope::None);
@@ -184,6 +432,112 @@ fn validate_mappings(rule: &RuleFile, ctx: &mut ValidationCtx<'_>) {
     }
 }
 
+fn validate_target_shape(
+    shape: &TargetShape<'_>,
+    earlier: &[TargetShape<'_>],
+    ctx: &mut ValidationCtx<'_>,
+) {
+    let path = format!("mappings[{}].target", shape.index);
+    if let Some(other) = earlier.iter().find(|other| other.conflicts_with(shape)) {
+        ctx.push(
+            ErrorCode::TargetConflict,
+            &format!("mapping.target conflicts with the array shape of '{}'", other.target),
+            path.clone(),
+        );
+    }
+
+    let tokens = shape.full_tokens();
+    // Two `when`-guarded writes may be mutually exclusive, which cannot be proven statically.
+    if let Some(other) = earlier.iter().find(|other| {
+        other.full_tokens() == tokens && !(other.conditional && shape.conditional)
+    }) {
+        ctx.push(
+            ErrorCode::DuplicateTarget,
+            &format!(
+                "mapping.target duplicates {}",
+                ctx.describe(&format!("mappings[{}].target", other.index))
+            ),
+            path.clone(),
+        );
+    }
+
+    if shape.item_tokens.is_some() {
+        return;
+    }
+    for other in earlier.iter().filter(|other| other.item_tokens.is_none()) {
+        let other_path = ctx.describe(&format!("mappings[{}].target", other.index));
+        let message = if other.tokens.len() < tokens.len()
+            && tokens.starts_with(&other.tokens)
+            && !other.object_valued
+        {
+            format!(
+                "mapping.target is nested under '{}', which {} writes as a non-object value",
+                other.target, other_path
+            )
+        } else if tokens.len() < other.tokens.len() && other.tokens.starts_with(&tokens) {
+            format!(
+                "mapping.target replaces '{}', which {} writes earlier",
+                other.target, other_path
+            )
+        } else {
+            continue;
+        };
+        ctx.push(ErrorCode::TargetConflict, &message, path);
+        return;
+    }
+}
+
+// Only values that are objects whatever the input holds can have later targets nested under them.
+fn is_object_valued(mapping: &Mapping) -> bool {
+    if mapping.value_type.is_some() {
+        return false;
+    }
+    match (&mapping.value, &mapping.expr) {
+        (Some(value), None) => value.is_object(),
+        (None, Some(Expr::Literal(value))) => value.is_object(),
+        (None, Some(Expr::Op(expr_op))) => matches!(
+            expr_op.op.as_str(),
+            "merge" | "deep_merge" | "pick" | "omit" | "object_flatten" | "object_unflatten"
+        ),
+        _ => false,
+    }
+}
+
+// A mapping target; `item_tokens` is set for `items[].sku` targets, with `tokens` as the array.
+struct TargetShape<'a> {
+    index: usize,
+    target: &'a str,
+    tokens: Vec<PathToken>,
+    item_tokens: Option<Vec<PathToken>>,
+    conditional: bool,
+    object_valued: bool,
+}
+
+impl TargetShape<'_> {
+    fn full_tokens(&self) -> Vec<PathToken> {
+        let mut tokens = self.tokens.clone();
+        tokens.extend(self.item_tokens.iter().flatten().cloned());
+        tokens
+    }
+
+    // Sibling `items[].x` / `items[].y` targets agree; anything else writing at or under an
+    // array, or a bare `items[]` next to `items[].x`, disagrees about what the array holds.
+    fn conflicts_with(&self, other: &TargetShape<'_>) -> bool {
+        match (&self.item_tokens, &other.item_tokens) {
+            (None, None) => false,
+            (Some(_), None) => other.tokens.starts_with(&self.tokens),
+            (None, Some(_)) => self.tokens.starts_with(&other.tokens),
+            (Some(items), Some(other_items)) => {
+                if self.tokens == other.tokens {
+                    items.is_empty() != other_items.is_empty()
+                } else {
+                    self.tokens.starts_with(&other.tokens) || other.tokens.starts_with(&self.tokens)
+                }
+            }
+        }
+    }
+}
+
 fn count_value_fields(mapping: &Mapping) -> usize {
     let mut count = 0;
     if mapping.source.is_some() {
@@ -200,17 +554,16 @@ fn count_value_fields(mapping: &Mapping) -> usize {
 
 fn validate_source(
     source: &str,
-    base_path: &str,
+    full_path: &str,
     produced_targets: &HashSet<Vec<PathToken>>,
     ctx: &mut ValidationCtx<'_>,
 ) {
-    let full_path = format!("{}.source", base_path);
     let (namespace, path) = match parse_source(source) {
         Some(parsed) => parsed,
         None => {
             ctx.push(
                 ErrorCode::InvalidRefNamespace,
-                "ref namespace must be input|context|out",
+                "ref namespace must be input|context|out|const|env",
                 full_path,
             );
             return;
@@ -225,12 +578,15 @@ fn validate_source(
         }
     };
 
-    if namespace == Namespace::Out && !out_ref_resolves(&tokens, produced_targets) {
-        ctx.push(
+    match namespace {
+        Namespace::Out if !out_ref_resolves(&tokens, produced_targets) => ctx.push(
             ErrorCode::ForwardOutReference,
             "out reference must point to previous mappings",
             full_path,
-        );
+        ),
+        Namespace::Const => validate_const_name(&tokens, full_path, ctx),
+        Namespace::Env => validate_env_name(&tokens, full_path, ctx),
+        _ => {}
     }
 }
 
@@ -285,6 +641,16 @@ fn validate_when_expr(expr: &Expr, base_path: &str, ctx: &mut ValidationCtx<'_>)
     }
 }
 
+fn validate_condition_arg(expr: &Expr, base_path: &str, ctx: &mut ValidationCtx<'_>) {
+    if matches!(bool_expr_kind(expr), BoolExprKind::NotBool) {
+        ctx.push(
+            ErrorCode::InvalidWhenType,
+            "condition must evaluate to boolean",
+            base_path,
+        );
+    }
+}
+
 fn bool_expr_kind(expr: &Expr) -> BoolExprKind {
     match expr {
         Expr::Literal(value) => {
@@ -297,14 +663,18 @@ fn bool_expr_kind(expr: &Expr) -> BoolExprKind {
         Expr::Ref(_) => BoolExprKind::Maybe,
         Expr::Op(expr_op) => match expr_op.op.as_str() {
             "concat"
+            | "concat_ws"
+            | "format"
             | "to_string"
             | "trim"
             | "lowercase"
             | "uppercase"
             | "replace"
+            | "regex_extract"
             | "split"
             | "pad_start"
             | "pad_end"
+            | "substring"
             | "lookup"
             | "lookup_first"
             | "merge"
@@ -344,15 +714,21 @@ fn bool_expr_kind(expr: &Expr) -> BoolExprKind {
             | "-"
             | "*"
             | "/"
+            | "int_div"
+            | "mod"
+            | "abs"
+            | "floor"
+            | "ceil"
+            | "trunc"
             | "round"
             | "to_base"
             | "date_format"
             | "to_unixtime" => BoolExprKind::NotBool,
             "and" | "or" | "not" | "contains" => BoolExprKind::Bool,
-            "==" | "!=" | "<" | "<=" | ">" | ">=" | "~=" => BoolExprKind::Bool,
+            "==" | "!=" | "<" | "<=" | ">" | ">=" | "~=" | "regex_match" => BoolExprKind::Bool,
             "coalesce" => {
                 let mut saw_maybe = false;
-                for arg in &expr_op.args {
+                for arg in coalesce_value_args(expr_op) {
                     match bool_expr_kind(arg) {
                         BoolExprKind::Bool => {}
                         BoolExprKind::Maybe => saw_maybe = true,
@@ -390,14 +766,18 @@ fn bool_expr_kind_chain(expr_chain: &ExprChain) -> BoolExprKind {
 fn bool_expr_kind_for_op_with_input(expr_op: &ExprOp, injected: BoolExprKind) -> BoolExprKind {
     match expr_op.op.as_str() {
             "concat"
+            | "concat_ws"
+            | "format"
             | "to_string"
             | "trim"
             | "lowercase"
             | "uppercase"
             | "replace"
+            | "regex_extract"
             | "split"
             | "pad_start"
             | "pad_end"
+            | "substring"
             | "lookup"
             | "lookup_first"
             | "merge"
@@ -437,18 +817,24 @@ fn bool_expr_kind_for_op_with_input(expr_op: &ExprOp, injected: BoolExprKind) ->
             | "-"
             | "*"
             | "/"
+            | "int_div"
+            | "mod"
+            | "abs"
+            | "floor"
+            | "ceil"
+            | "trunc"
             | "round"
             | "to_base"
             | "date_format"
             | "to_unixtime" => BoolExprKind::NotBool,
             "and" | "or" | "not" | "contains" => BoolExprKind::Bool,
-            "==" | "!=" | "<" | "<=" | ">" | ">=" | "~=" => BoolExprKind::Bool,
+            "==" | "!=" | "<" | "<=" | ">" | ">=" | "~=" | "regex_match" => BoolExprKind::Bool,
             "coalesce" => {
             let mut saw_maybe = matches!(injected, BoolExprKind::Maybe);
             if matches!(injected, BoolExprKind::NotBool) {
                 return BoolExprKind::NotBool;
             }
-            for arg in &expr_op.args {
+            for arg in coalesce_value_args(expr_op) {
                 match bool_expr_kind(arg) {
                     BoolExprKind::Bool => {}
                     BoolExprKind::Maybe => saw_maybe = true,
@@ -503,6 +889,7 @@ fn validate_chain(
     }
 }
 
+#[allow(clippy::collapsible_match)]
 fn validate_chain_op(
     expr_op: &ExprOp,
     base_path: &str,
@@ -510,101 +897,73 @@ fn validate_chain_op(
     ctx: &mut ValidationCtx<'_>,
     scope: LocalScope,
 ) {
-    if !is_valid_op(&expr_op.op) {
-        ctx.push(
-            ErrorCode::UnknownOp,
-            "expr.op is not supported",
-            format!("{}.op", base_path),
-        );
-    }
-
     let args_len = expr_op.args.len() + 1;
+    let arity_ok = validate_op_spec(expr_op, args_len, base_path, ctx);
     match expr_op.op.as_str() {
-        "trim" | "lowercase" | "uppercase" | "to_string" | "not" => {
-            if args_len != 1 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain exactly one item",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "replace" => {
-            if !(3..=4).contains(&args_len) {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain three or four items",
-                    format!("{}.args", base_path),
-                );
+        _ if !arity_ok => {}
+        "concat" => validate_concat_options(expr_op, base_path, true, ctx),
+        "coalesce" => validate_coalesce_options(expr_op, base_path, true, ctx),
+        "deep_merge" => validate_merge_options(expr_op, base_path, true, ctx),
+        "regex_extract" => {
+            if args_len == 3 {
+                validate_regex_group(expr_op, 1, base_path, ctx);
             }
         }
         "split" => {
-            if args_len != 2 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain exactly two items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "pad_start" | "pad_end" => {
-            if !(2..=3).contains(&args_len) {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain two or three items",
-                    format!("{}.args", base_path),
-                );
+            if args_len == 3 {
+                validate_split_options(expr_op, 1, base_path, ctx);
             }
         }
         "lookup" | "lookup_first" => {
             validate_lookup_args_chain(expr_op, base_path, ctx);
         }
-        "merge" | "deep_merge" => {
-            if args_len < 2 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain at least two items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "get" => {
-            if args_len != 2 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain exactly two items",
-                    format!("{}.args", base_path),
-                );
-            } else {
-                validate_path_arg(&expr_op.args[0], &format!("{}.args[0]", base_path), ctx);
-            }
-        }
+        "get" => validate_path_arg(&expr_op.args[0], &format!("{}.args[0]", base_path), ctx),
         "pick" | "omit" => {
-            if args_len != 2 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain exactly two items",
-                    format!("{}.args", base_path),
-                );
-            } else {
-                let allow_terminal_index = expr_op.op == "pick";
-                validate_path_array_arg(
-                    &expr_op.args[0],
-                    &format!("{}.args[0]", base_path),
-                    allow_terminal_index,
-                    ctx,
-                );
+            let allow_terminal_index = expr_op.op == "pick";
+            validate_path_array_arg(
+                &expr_op.args[0],
+                &format!("{}.args[0]", base_path),
+                allow_terminal_index,
+                ctx,
+            );
+        }
+        "object_flatten" | "object_unflatten" => {
+            if let Some(options) = expr_op.args.first() {
+                validate_flatten_options(options, &format!("{}.args[0]", base_path), ctx);
             }
         }
-        "keys" | "values" | "entries" | "object_flatten" | "object_unflatten" => {
-            if args_len != 1 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain exactly one item",
-                    format!("{}.args", base_path),
-                );
+        "group_by" | "key_by" | "distinct_by" | "unique" => {
+            let index = if expr_op.op == "unique" { 0 } else { 1 };
+            if let Some(options) = expr_op.args.get(index) {
+                validate_key_options(options, &format!("{}.args[{}]", base_path, index), ctx);
             }
         }
+        _ => {}
+    }
+
+    let expr_scope = element_expr_scope(&expr_op.op, true, expr_op.args.len(), scope);
+    for (index, arg) in expr_op.args.iter().enumerate() {
+        let arg_path = format!("{}.args[{}]", base_path, index);
+        let arg_scope = match expr_scope {
+            Some((expr_index, expr_scope)) if expr_index == index => expr_scope,
+            _ => scope,
+        };
+        validate_expr(arg, &arg_path, produced_targets, ctx, arg_scope);
+    }
+}
+
+fn element_expr_scope(
+    op: &str,
+    injected: bool,
+    args_len: usize,
+    parent_scope: LocalScope,
+) -> Option<(usize, LocalScope)> {
+    let item_scope = if parent_scope.allows_acc() {
+        LocalScope::ItemAcc
+    } else {
+        LocalScope::Item
+    };
+    match op {
         "map"
         | "filter"
         | "flat_map"
@@ -612,180 +971,20 @@ fn validate_chain_op(
         | "key_by"
         | "partition"
         | "distinct_by"
+        | "sort_by"
         | "find"
         | "find_index" => {
-            if args_len != 2 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain exactly two items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "flatten" => {
-            if !(1..=2).contains(&args_len) {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain one or two items",
-                    format!("{}.args", base_path),
-                );
-            }
+            let index = if injected { 0 } else { 1 };
+            Some((index, item_scope))
         }
-        "take" | "drop" | "chunk" | "index_of" | "contains" | "reduce" => {
-            if args_len != 2 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain exactly two items",
-                    format!("{}.args", base_path),
-                );
-            }
+        "zip_with" => args_len.checked_sub(1).map(|index| (index, item_scope)),
+        "reduce" => {
+            let index = if injected { 0 } else { 1 };
+            Some((index, LocalScope::ItemAcc))
         }
-        "slice" => {
-            if !(2..=3).contains(&args_len) {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain two or three items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "zip" => {
-            if args_len < 2 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain at least two items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "zip_with" => {
-            if args_len < 3 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain at least three items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "unzip" | "unique" | "sum" | "avg" | "min" | "max" => {
-            if args_len != 1 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain exactly one item",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "sort_by" => {
-            if !(2..=3).contains(&args_len) {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain two or three items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "fold" => {
-            if args_len != 3 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain exactly three items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "+" | "*" | "and" | "or" => {
-            if args_len < 2 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain at least two items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "-" | "/" | "to_base" | "==" | "!=" | "<" | "<=" | ">" | ">=" | "~=" => {
-            if args_len != 2 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain exactly two items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "round" => {
-            if !(1..=2).contains(&args_len) {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain one or two items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "date_format" => {
-            if !(2..=4).contains(&args_len) {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain two to four items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "to_unixtime" => {
-            if !(1..=3).contains(&args_len) {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain one to three items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        _ => {}
-    }
-
-    let expr_scope = element_expr_scope(&expr_op.op, true, expr_op.args.len(), scope);
-    for (index, arg) in expr_op.args.iter().enumerate() {
-        let arg_path = format!("{}.args[{}]", base_path, index);
-        let arg_scope = match expr_scope {
-            Some((expr_index, expr_scope)) if expr_index == index => expr_scope,
-            _ => scope,
-        };
-        validate_expr(arg, &arg_path, produced_targets, ctx, arg_scope);
-    }
-}
-
-fn element_expr_scope(
-    op: &str,
-    injected: bool,
-    args_len: usize,
-    parent_scope: LocalScope,
-) -> Option<(usize, LocalScope)> {
-    let item_scope = if parent_scope.allows_acc() {
-        LocalScope::ItemAcc
-    } else {
-        LocalScope::Item
-    };
-    match op {
-        "map"
-        | "filter"
-        | "flat_map"
-        | "group_by"
-        | "key_by"
-        | "partition"
-        | "distinct_by"
-        | "sort_by"
-        | "find"
-        | "find_index" => {
-            let index = if injected { 0 } else { 1 };
-            Some((index, item_scope))
-        }
-        "zip_with" => args_len.checked_sub(1).map(|index| (index, item_scope)),
-        "reduce" => {
-            let index = if injected { 0 } else { 1 };
-            Some((index, LocalScope::ItemAcc))
-        }
-        "fold" => {
-            let index = if injected { 1 } else { 2 };
-            Some((index, LocalScope::ItemAcc))
+        "fold" => {
+            let index = if injected { 1 } else { 2 };
+            Some((index, LocalScope::ItemAcc))
         }
         _ => None,
     }
@@ -803,13 +1002,15 @@ fn validate_lookup_args_chain(expr_op: &ExprOp, base_path: &str, ctx: &mut Valid
     }
 
     let key_path = literal_string(&expr_op.args[0]);
-    if key_path.is_none() || key_path == Some("") {
+    if key_path.is_none_or(str::is_empty) {
         ctx.push(
             ErrorCode::InvalidArgs,
             "lookup key_path must be a non-empty string literal",
             format!("{}.args[0]", base_path),
         );
-    } else if parse_path(key_path.unwrap()).is_err() {
+    } else if let Some(key_path) = key_path
+        && parse_path(key_path).is_err()
+    {
         ctx.push(
             ErrorCode::InvalidArgs,
             "lookup key_path is invalid",
@@ -819,13 +1020,15 @@ fn validate_lookup_args_chain(expr_op: &ExprOp, base_path: &str, ctx: &mut Valid
 
     if len == 3 {
         let output_path = literal_string(&expr_op.args[2]);
-        if output_path.is_none() || output_path == Some("") {
+        if output_path.is_none_or(str::is_empty) {
             ctx.push(
                 ErrorCode::InvalidArgs,
                 "lookup output_path must be a non-empty string literal",
                 format!("{}.args[2]", base_path),
             );
-        } else if parse_path(output_path.unwrap()).is_err() {
+        } else if let Some(output_path) = output_path
+            && parse_path(output_path).is_err()
+        {
             ctx.push(
                 ErrorCode::InvalidArgs,
                 "lookup output_path is invalid",
@@ -847,33 +1050,40 @@ fn validate_ref(
         None => {
             ctx.push(
                 ErrorCode::InvalidRefNamespace,
-                "ref namespace must be input|context|out|item|acc",
+                "ref namespace must be input|context|out|item|acc|const|env",
                 base_path,
             );
             return;
         }
     };
 
+    if base_path.starts_with("constants.")
+        && matches!(namespace, Namespace::Input | Namespace::Out | Namespace::Const)
+    {
+        ctx.push(
+            ErrorCode::InvalidRefNamespace,
+            "constants may only reference context",
+            base_path,
+        );
+        return;
+    }
+
     match namespace {
-        Namespace::Item => {
-            if !scope.allows_item() {
-                ctx.push(
-                    ErrorCode::InvalidRefNamespace,
-                    "item refs are only allowed inside array ops",
-                    base_path,
-                );
-                return;
-            }
+        Namespace::Item if !scope.allows_item() => {
+            ctx.push(
+                ErrorCode::InvalidRefNamespace,
+                "item refs are only allowed inside array ops",
+                base_path,
+            );
+            return;
         }
-        Namespace::Acc => {
-            if !scope.allows_acc() {
-                ctx.push(
-                    ErrorCode::InvalidRefNamespace,
-                    "acc refs are only allowed inside reduce/fold ops",
-                    base_path,
-                );
-                return;
-            }
+        Namespace::Acc if !scope.allows_acc() => {
+            ctx.push(
+                ErrorCode::InvalidRefNamespace,
+                "acc refs are only allowed inside reduce/fold ops",
+                base_path,
+            );
+            return;
         }
         _ => {}
     }
@@ -887,14 +1097,12 @@ fn validate_ref(
     };
 
     match namespace {
-        Namespace::Out => {
-            if !out_ref_resolves(&tokens, produced_targets) {
-                ctx.push(
-                    ErrorCode::ForwardOutReference,
-                    "out reference must point to previous mappings",
-                    base_path,
-                );
-            }
+        Namespace::Out if !out_ref_resolves(&tokens, produced_targets) => {
+            ctx.push(
+                ErrorCode::ForwardOutReference,
+                "out reference must point to previous mappings",
+                base_path,
+            );
         }
         Namespace::Item => {
             let ok = matches!(tokens.first(), Some(PathToken::Key(key)) if key == "value" || key == "index");
@@ -916,10 +1124,42 @@ fn validate_ref(
                 );
             }
         }
+        Namespace::Const => validate_const_name(&tokens, base_path, ctx),
+        Namespace::Env => validate_env_name(&tokens, base_path, ctx),
         _ => {}
     }
 }
 
+// Remembers the variable for the `env_reference` warning.
+fn validate_env_name(tokens: &[PathToken], path: &str, ctx: &mut ValidationCtx<'_>) {
+    match tokens {
+        [PathToken::Key(name)] => ctx.env_refs.push((name.clone(), path.to_string())),
+        _ => ctx.push(
+            ErrorCode::InvalidPath,
+            "env ref must name a single variable",
+            path,
+        ),
+    }
+}
+
+fn validate_const_name(tokens: &[PathToken], path: &str, ctx: &mut ValidationCtx<'_>) {
+    let declared = match tokens.first() {
+        Some(PathToken::Key(name)) => ctx
+            .rule
+            .constants
+            .as_ref()
+            .is_some_and(|constants| constants.contains_key(name)),
+        _ => false,
+    };
+    if !declared {
+        ctx.push(
+            ErrorCode::UnknownConstant,
+            "const ref must name a declared constant",
+            path,
+        );
+    }
+}
+
 fn out_ref_resolves(tokens: &[PathToken], produced_targets: &HashSet<Vec<PathToken>>) -> bool {
     let key_tokens: Vec<PathToken> = tokens
         .iter()
@@ -940,6 +1180,7 @@ fn out_ref_resolves(tokens: &[PathToken], produced_targets: &HashSet<Vec<PathTok
     false
 }
 
+#[allow(clippy::collapsible_match)]
 fn validate_op(
     expr_op: &ExprOp,
     base_path: &str,
@@ -947,14 +1188,6 @@ fn validate_op(
     ctx: &mut ValidationCtx<'_>,
     scope: LocalScope,
 ) {
-    if !is_valid_op(&expr_op.op) {
-        ctx.push(
-            ErrorCode::UnknownOp,
-            "expr.op is not supported",
-            format!("{}.op", base_path),
-        );
-    }
-
     if expr_op.args.is_empty() {
         ctx.push(
             ErrorCode::InvalidArgs,
@@ -963,251 +1196,64 @@ fn validate_op(
         );
     }
 
+    let args_len = expr_op.args.len();
+    let arity_ok = validate_op_spec(expr_op, args_len, base_path, ctx);
     match expr_op.op.as_str() {
-        "trim" | "lowercase" | "uppercase" | "to_string" => {
-            if expr_op.args.len() != 1 {
+        _ if !arity_ok => {}
+        "concat" => validate_concat_options(expr_op, base_path, false, ctx),
+        "coalesce" => validate_coalesce_options(expr_op, base_path, false, ctx),
+        "format" => validate_format_template(expr_op, base_path, ctx),
+        "deep_merge" => validate_merge_options(expr_op, base_path, false, ctx),
+        "concat_ws" => {
+            if let Expr::Literal(separator) = &expr_op.args[0]
+                && !separator.is_string()
+            {
                 ctx.push(
                     ErrorCode::InvalidArgs,
-                    "expr.args must contain exactly one item",
-                    format!("{}.args", base_path),
+                    "separator must be a string",
+                    format!("{}.args[0]", base_path),
                 );
             }
         }
-        "replace" => {
-            if !(3..=4).contains(&expr_op.args.len()) {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain three or four items",
-                    format!("{}.args", base_path),
-                );
+        "regex_extract" => {
+            if args_len == 3 {
+                validate_regex_group(expr_op, 2, base_path, ctx);
             }
         }
         "split" => {
-            if expr_op.args.len() != 2 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain exactly two items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "pad_start" | "pad_end" => {
-            if !(2..=3).contains(&expr_op.args.len()) {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain two or three items",
-                    format!("{}.args", base_path),
-                );
+            if args_len == 3 {
+                validate_split_options(expr_op, 2, base_path, ctx);
             }
         }
         "lookup" | "lookup_first" => {
             validate_lookup_args(expr_op, base_path, ctx);
         }
-        "merge" | "deep_merge" => {
-            if expr_op.args.len() < 2 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain at least two items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "get" => {
-            if expr_op.args.len() != 2 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain exactly two items",
-                    format!("{}.args", base_path),
-                );
-            } else {
-                validate_path_arg(&expr_op.args[1], &format!("{}.args[1]", base_path), ctx);
-            }
-        }
+        "get" => validate_path_arg(&expr_op.args[1], &format!("{}.args[1]", base_path), ctx),
         "pick" | "omit" => {
-            if expr_op.args.len() != 2 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain exactly two items",
-                    format!("{}.args", base_path),
-                );
-            } else {
-                let allow_terminal_index = expr_op.op == "pick";
-                validate_path_array_arg(
-                    &expr_op.args[1],
-                    &format!("{}.args[1]", base_path),
-                    allow_terminal_index,
-                    ctx,
-                );
-            }
-        }
-        "keys" | "values" | "entries" | "object_flatten" | "object_unflatten" => {
-            if expr_op.args.len() != 1 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain exactly one item",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "map"
-        | "filter"
-        | "flat_map"
-        | "group_by"
-        | "key_by"
-        | "partition"
-        | "distinct_by"
-        | "find"
-        | "find_index" => {
-            if expr_op.args.len() != 2 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain exactly two items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "flatten" => {
-            if !(1..=2).contains(&expr_op.args.len()) {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain one or two items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "take" | "drop" | "chunk" | "index_of" | "contains" | "reduce" => {
-            if expr_op.args.len() != 2 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain exactly two items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "slice" => {
-            if !(2..=3).contains(&expr_op.args.len()) {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain two or three items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "zip" => {
-            if expr_op.args.len() < 2 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain at least two items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "zip_with" => {
-            if expr_op.args.len() < 3 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain at least three items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "unzip" | "unique" | "sum" | "avg" | "min" | "max" => {
-            if expr_op.args.len() != 1 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain exactly one item",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "sort_by" => {
-            if !(2..=3).contains(&expr_op.args.len()) {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain two or three items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "fold" => {
-            if expr_op.args.len() != 3 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain exactly three items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "+" | "*" => {
-            if expr_op.args.len() < 2 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain at least two items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "-" | "/" | "to_base" => {
-            if expr_op.args.len() != 2 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain exactly two items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "round" => {
-            if !(1..=2).contains(&expr_op.args.len()) {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain one or two items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "date_format" => {
-            if !(2..=4).contains(&expr_op.args.len()) {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain two to four items",
-                    format!("{}.args", base_path),
-                );
-            }
+            let allow_terminal_index = expr_op.op == "pick";
+            validate_path_array_arg(
+                &expr_op.args[1],
+                &format!("{}.args[1]", base_path),
+                allow_terminal_index,
+                ctx,
+            );
         }
-        "to_unixtime" => {
-            if !(1..=3).contains(&expr_op.args.len()) {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain one to three items",
-                    format!("{}.args", base_path),
-                );
+        "object_flatten" | "object_unflatten" => {
+            if let Some(options) = expr_op.args.get(1) {
+                validate_flatten_options(options, &format!("{}.args[1]", base_path), ctx);
             }
         }
-        "and" | "or" => {
-            if expr_op.args.len() < 2 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain at least two items",
-                    format!("{}.args", base_path),
-                );
-            }
-        }
-        "not" => {
-            if expr_op.args.len() != 1 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain exactly one item",
-                    format!("{}.args", base_path),
-                );
+        "group_by" | "key_by" | "distinct_by" | "unique" => {
+            let index = if expr_op.op == "unique" { 1 } else { 2 };
+            if let Some(options) = expr_op.args.get(index) {
+                validate_key_options(options, &format!("{}.args[{}]", base_path, index), ctx);
             }
         }
-        "==" | "!=" | "<" | "<=" | ">" | ">=" | "~=" => {
-            if expr_op.args.len() != 2 {
-                ctx.push(
-                    ErrorCode::InvalidArgs,
-                    "expr.args must contain exactly two items",
-                    format!("{}.args", base_path),
-                );
+        "if" => validate_condition_arg(&expr_op.args[0], &format!("{}.args[0]", base_path), ctx),
+        "case" => {
+            for index in (0..args_len - 1).step_by(2) {
+                let arg_path = format!("{}.args[{}]", base_path, index);
+                validate_condition_arg(&expr_op.args[index], &arg_path, ctx);
             }
         }
         _ => {}
@@ -1225,9 +1271,7 @@ fn validate_op(
 }
 
 fn parse_ref(value: &str) -> Option<(Namespace, &str)> {
-    let mut parts = value.splitn(2, '.');
-    let namespace = parts.next()?;
-    let path = parts.next()?;
+    let (namespace, path) = split_namespace(value)?;
     if path.is_empty() {
         return None;
     }
@@ -1238,107 +1282,176 @@ fn parse_ref(value: &str) -> Option<(Namespace, &str)> {
         "out" => Namespace::Out,
         "item" => Namespace::Item,
         "acc" => Namespace::Acc,
+        "const" => Namespace::Const,
+        "env" => Namespace::Env,
         _ => return None,
     };
-
-    Some((namespace, path))
+
+    Some((namespace, path))
+}
+
+fn parse_source(value: &str) -> Option<(Namespace, &str)> {
+    // Same split as the transform: a bracket after a non-namespace key starts an input path.
+    let split = split_namespace(value).filter(|(prefix, _)| {
+        !prefix.is_empty()
+            && (value[prefix.len()..].starts_with('.')
+                || matches!(*prefix, "input" | "context" | "out" | "const" | "env"))
+    });
+    if let Some((prefix, path)) = split {
+        if path.is_empty() {
+            return None;
+        }
+        let namespace = match prefix {
+            "input" => Namespace::Input,
+            "context" => Namespace::Context,
+            "out" => Namespace::Out,
+            "const" => Namespace::Const,
+            "env" => Namespace::Env,
+            _ => return None,
+        };
+        Some((namespace, path))
+    } else {
+        if value.is_empty() {
+            return None;
+        }
+        Some((Namespace::Input, value))
+    }
+}
+
+// Only literal groups can be checked statically; a bad index against the pattern is a runtime error.
+fn validate_regex_group(
+    expr_op: &ExprOp,
+    index: usize,
+    base_path: &str,
+    ctx: &mut ValidationCtx<'_>,
+) {
+    let Some(Expr::Literal(group)) = expr_op.args.get(index) else {
+        return;
+    };
+    let valid = match group {
+        serde_json::Value::String(name) => !name.is_empty(),
+        serde_json::Value::Number(number) => number.as_u64().is_some(),
+        _ => false,
+    };
+    if !valid {
+        ctx.push(
+            ErrorCode::InvalidArgs,
+            "regex group must be a non-negative integer or a group name",
+            format!("{}.args[{}]", base_path, index),
+        );
+    }
+}
+
+// `index` is the options arg; the delimiter comes right before it.
+fn validate_split_options(
+    expr_op: &ExprOp,
+    index: usize,
+    base_path: &str,
+    ctx: &mut ValidationCtx<'_>,
+) {
+    let Some(Expr::Literal(options)) = expr_op.args.get(index) else {
+        return;
+    };
+    let path = format!("{}.args[{}]", base_path, index);
+    let valid_limit = |value: &serde_json::Value| value.as_u64().is_some_and(|limit| limit > 0);
+    let mut regex = false;
+    match options {
+        serde_json::Value::Number(_) => {
+            if !valid_limit(options) {
+                ctx.push(ErrorCode::InvalidArgs, "split limit must be a positive integer", path);
+            }
+        }
+        serde_json::Value::String(mode) if mode == "regex" => regex = true,
+        serde_json::Value::Object(map) => {
+            for (key, value) in map {
+                let option_path = format!("{}.{}", path, key);
+                match key.as_str() {
+                    "regex" => match value.as_bool() {
+                        Some(flag) => regex = flag,
+                        None => ctx.push(ErrorCode::InvalidArgs, "regex must be a boolean", option_path),
+                    },
+                    "limit" if !valid_limit(value) => ctx.push(
+                        ErrorCode::InvalidArgs,
+                        "split limit must be a positive integer",
+                        option_path,
+                    ),
+                    "limit" => {}
+                    _ => ctx.push(ErrorCode::InvalidArgs, "unknown option", option_path),
+                }
+            }
+        }
+        _ => ctx.push(
+            ErrorCode::InvalidArgs,
+            "split options must be a limit, \"regex\" or an options object",
+            path,
+        ),
+    }
+    if let (true, Some(Expr::Literal(serde_json::Value::String(pattern)))) =
+        (regex, expr_op.args.get(index - 1))
+    {
+        let delimiter_path = format!("{}.args[{}]", base_path, index - 1);
+        match regex::Regex::new(pattern) {
+            Err(_) => ctx.push(ErrorCode::InvalidArgs, "regex pattern is invalid", delimiter_path),
+            Ok(compiled) if compiled.is_match("") => ctx.push(
+                ErrorCode::InvalidArgs,
+                "split regex must not match an empty string",
+                delimiter_path,
+            ),
+            Ok(_) => {}
+        }
+    }
+}
+
+/// Checks the op name and argument count against the op registry. Returns whether the
+/// op-specific checks, which index into `args`, can run.
+fn validate_op_spec(
+    expr_op: &ExprOp,
+    args_len: usize,
+    base_path: &str,
+    ctx: &mut ValidationCtx<'_>,
+) -> bool {
+    let Some(spec) = op_spec(&expr_op.op) else {
+        ctx.push(
+            ErrorCode::UnknownOp,
+            "expr.op is not supported",
+            format!("{}.op", base_path),
+        );
+        return false;
+    };
+    // lookup describes its expected argument shape itself.
+    if matches!(spec.name, "lookup" | "lookup_first") || spec.accepts_arg_count(args_len) {
+        return true;
+    }
+    ctx.push(
+        ErrorCode::InvalidArgs,
+        &format!("expr.args must contain {}", arity_text(spec)),
+        format!("{}.args", base_path),
+    );
+    false
 }
 
-fn parse_source(value: &str) -> Option<(Namespace, &str)> {
-    if let Some((prefix, path)) = value.split_once('.') {
-        if path.is_empty() {
-            return None;
+fn arity_text(spec: &OpSpec) -> String {
+    let items = |count: usize| if count == 1 { "item" } else { "items" };
+    match spec.max_args {
+        Some(max) if max == spec.min_args => {
+            format!("exactly {} {}", count_word(max), items(max))
         }
-        let namespace = match prefix {
-            "input" => Namespace::Input,
-            "context" => Namespace::Context,
-            "out" => Namespace::Out,
-            _ => return None,
-        };
-        Some((namespace, path))
-    } else {
-        if value.is_empty() {
-            return None;
+        Some(max) if max == spec.min_args + 1 => {
+            format!("{} or {} items", count_word(spec.min_args), count_word(max))
         }
-        Some((Namespace::Input, value))
+        Some(max) => format!("{} to {} items", count_word(spec.min_args), count_word(max)),
+        None => format!("at least {} {}", count_word(spec.min_args), items(spec.min_args)),
     }
 }
 
-fn is_valid_type_name(value: &str) -> bool {
-    matches!(value, "string" | "int" | "float" | "bool")
-}
-
-fn is_valid_op(value: &str) -> bool {
-    matches!(
-        value,
-        "concat"
-            | "coalesce"
-            | "to_string"
-            | "trim"
-            | "lowercase"
-            | "uppercase"
-            | "replace"
-            | "split"
-            | "pad_start"
-            | "pad_end"
-            | "lookup"
-            | "lookup_first"
-            | "merge"
-            | "deep_merge"
-            | "get"
-            | "pick"
-            | "omit"
-            | "keys"
-            | "values"
-            | "entries"
-            | "object_flatten"
-            | "object_unflatten"
-            | "map"
-            | "filter"
-            | "flat_map"
-            | "flatten"
-            | "take"
-            | "drop"
-            | "slice"
-            | "chunk"
-            | "zip"
-            | "zip_with"
-            | "unzip"
-            | "group_by"
-            | "key_by"
-            | "partition"
-            | "unique"
-            | "distinct_by"
-            | "sort_by"
-            | "find"
-            | "find_index"
-            | "index_of"
-            | "contains"
-            | "sum"
-            | "avg"
-            | "min"
-            | "max"
-            | "reduce"
-            | "fold"
-            | "+"
-            | "-"
-            | "*"
-            | "/"
-            | "round"
-            | "to_base"
-            | "date_format"
-            | "to_unixtime"
-            | "and"
-            | "or"
-            | "not"
-            | "=="
-            | "!="
-            | "<"
-            | "<="
-            | ">"
-            | ">="
-            | "~="
-    )
+fn count_word(count: usize) -> String {
+    match count {
+        1 => "one".to_string(),
+        2 => "two".to_string(),
+        3 => "three".to_string(),
+        4 => "four".to_string(),
+        _ => count.to_string(),
+    }
 }
 
 fn validate_lookup_args(expr_op: &ExprOp, base_path: &str, ctx: &mut ValidationCtx<'_>) {
@@ -1353,13 +1466,15 @@ fn validate_lookup_args(expr_op: &ExprOp, base_path: &str, ctx: &mut ValidationC
     }
 
     let key_path = literal_string(&expr_op.args[1]);
-    if key_path.is_none() || key_path == Some("") {
+    if key_path.is_none_or(str::is_empty) {
         ctx.push(
             ErrorCode::InvalidArgs,
             "lookup key_path must be a non-empty string literal",
             format!("{}.args[1]", base_path),
         );
-    } else if parse_path(key_path.unwrap()).is_err() {
+    } else if let Some(key_path) = key_path
+        && parse_path(key_path).is_err()
+    {
         ctx.push(
             ErrorCode::InvalidArgs,
             "lookup key_path is invalid",
@@ -1369,13 +1484,15 @@ fn validate_lookup_args(expr_op: &ExprOp, base_path: &str, ctx: &mut ValidationC
 
     if len == 4 {
         let output_path = literal_string(&expr_op.args[3]);
-        if output_path.is_none() || output_path == Some("") {
+        if output_path.is_none_or(str::is_empty) {
             ctx.push(
                 ErrorCode::InvalidArgs,
                 "lookup output_path must be a non-empty string literal",
                 format!("{}.args[3]", base_path),
             );
-        } else if parse_path(output_path.unwrap()).is_err() {
+        } else if let Some(output_path) = output_path
+            && parse_path(output_path).is_err()
+        {
             ctx.push(
                 ErrorCode::InvalidArgs,
                 "lookup output_path is invalid",
@@ -1463,6 +1580,209 @@ fn validate_path_array_arg(
     }
 }
 
+// In chain steps the injected value is always a part, so options may be the only literal arg.
+fn validate_concat_options(
+    expr_op: &ExprOp,
+    base_path: &str,
+    chained: bool,
+    ctx: &mut ValidationCtx<'_>,
+) {
+    let Some(Expr::Literal(serde_json::Value::Object(options))) = expr_op.args.last() else {
+        return;
+    };
+    let index = expr_op.args.len() - 1;
+    if index == 0 && !chained {
+        ctx.push(
+            ErrorCode::InvalidArgs,
+            "expr.args must contain at least one item besides options",
+            format!("{}.args", base_path),
+        );
+    }
+    for (key, value) in options {
+        let path = format!("{}.args[{}].{}", base_path, index, key);
+        if key != "nulls" {
+            ctx.push(ErrorCode::InvalidArgs, "unknown option", path);
+        } else if !matches!(value.as_str(), Some("error" | "skip" | "empty")) {
+            ctx.push(ErrorCode::InvalidArgs, "nulls must be error|skip|empty", path);
+        }
+    }
+}
+
+fn validate_coalesce_options(
+    expr_op: &ExprOp,
+    base_path: &str,
+    chained: bool,
+    ctx: &mut ValidationCtx<'_>,
+) {
+    let Some(options) = coalesce_options_arg(&expr_op.args) else {
+        return;
+    };
+    let index = expr_op.args.len() - 1;
+    if index == 0 && !chained {
+        ctx.push(
+            ErrorCode::InvalidArgs,
+            "expr.args must contain at least one item besides options",
+            format!("{}.args", base_path),
+        );
+    }
+    for (key, value) in options {
+        let path = format!("{}.args[{}].{}", base_path, index, key);
+        if key != "blank" {
+            ctx.push(ErrorCode::InvalidArgs, "unknown option", path);
+        } else if !value.is_boolean() {
+            ctx.push(ErrorCode::InvalidArgs, "blank must be a boolean", path);
+        }
+    }
+}
+
+// Checks a literal template against literal values; refs are only known at runtime.
+fn validate_format_template(expr_op: &ExprOp, base_path: &str, ctx: &mut ValidationCtx<'_>) {
+    let template_path = format!("{}.args[0]", base_path);
+    let template = match &expr_op.args[0] {
+        Expr::Literal(serde_json::Value::String(template)) => template,
+        Expr::Literal(_) => {
+            ctx.push(ErrorCode::InvalidArgs, "template must be a string", template_path);
+            return;
+        }
+        _ => return,
+    };
+    let parts = match parse_format_template(template) {
+        Ok(parts) => parts,
+        Err(message) => {
+            ctx.push(ErrorCode::InvalidArgs, &message, template_path);
+            return;
+        }
+    };
+    for part in parts {
+        let FormatPart::Placeholder(placeholder) = part else {
+            continue;
+        };
+        let unknown = format!("unknown placeholder: {{{}}}", placeholder.name);
+        match placeholder.position() {
+            Some(position) => {
+                if position + 1 >= expr_op.args.len() {
+                    ctx.push(ErrorCode::InvalidArgs, &unknown, format!("{}.args", base_path));
+                }
+            }
+            None => {
+                let values_path = format!("{}.args[1]", base_path);
+                match &expr_op.args[1] {
+                    Expr::Literal(serde_json::Value::Object(values))
+                        if !values.contains_key(&placeholder.name) =>
+                    {
+                        ctx.push(ErrorCode::InvalidArgs, &unknown, values_path)
+                    }
+                    Expr::Literal(value) if !value.is_object() => ctx.push(
+                        ErrorCode::InvalidArgs,
+                        &format!(
+                            "named placeholder {{{}}} needs an object of values",
+                            placeholder.name
+                        ),
+                        values_path,
+                    ),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+// Mirrors the runtime: only a trailing literal whose sole key is a string `arrays` is options.
+fn validate_merge_options(
+    expr_op: &ExprOp,
+    base_path: &str,
+    chained: bool,
+    ctx: &mut ValidationCtx<'_>,
+) {
+    let Some(Expr::Literal(serde_json::Value::Object(options))) = expr_op.args.last() else {
+        return;
+    };
+    let Some(arrays) = options.get("arrays").and_then(|value| value.as_str()) else {
+        return;
+    };
+    if options.len() != 1 {
+        return;
+    }
+    let index = expr_op.args.len() - 1;
+    if index + usize::from(chained) < 2 {
+        ctx.push(
+            ErrorCode::InvalidArgs,
+            "expr.args must contain at least two items besides options",
+            format!("{}.args", base_path),
+        );
+    }
+    if !matches!(arrays, "replace" | "concat" | "by_index") {
+        ctx.push(
+            ErrorCode::InvalidArgs,
+            "arrays must be replace|concat|by_index",
+            format!("{}.args[{}].arrays", base_path, index),
+        );
+    }
+}
+
+fn validate_flatten_options(expr: &Expr, base_path: &str, ctx: &mut ValidationCtx<'_>) {
+    let value = match expr {
+        Expr::Literal(value) => value,
+        _ => return,
+    };
+    let Some(options) = value.as_object() else {
+        ctx.push(ErrorCode::InvalidArgs, "options must be an object", base_path);
+        return;
+    };
+
+    for (key, value) in options {
+        let path = format!("{}.{}", base_path, key);
+        match key.as_str() {
+            "separator" => {
+                if value.as_str().is_none_or(str::is_empty) {
+                    ctx.push(
+                        ErrorCode::InvalidArgs,
+                        "separator must be a non-empty string",
+                        path,
+                    );
+                }
+            }
+            "max_depth" => {
+                if value.as_u64().is_none() {
+                    ctx.push(
+                        ErrorCode::InvalidArgs,
+                        "max_depth must be a non-negative integer",
+                        path,
+                    );
+                }
+            }
+            "arrays" => {
+                if !matches!(value.as_str(), Some("keep" | "index")) {
+                    ctx.push(ErrorCode::InvalidArgs, "arrays must be keep|index", path);
+                }
+            }
+            _ => ctx.push(ErrorCode::InvalidArgs, "unknown option", path),
+        }
+    }
+}
+
+fn validate_key_options(expr: &Expr, base_path: &str, ctx: &mut ValidationCtx<'_>) {
+    let Expr::Literal(value) = expr else {
+        return;
+    };
+    let Some(options) = value.as_object() else {
+        ctx.push(ErrorCode::InvalidArgs, "options must be an object", base_path);
+        return;
+    };
+
+    for (key, value) in options {
+        let path = format!("{}.{}", base_path, key);
+        match key.as_str() {
+            "keys" => {
+                if !matches!(value.as_str(), Some("string" | "typed")) {
+                    ctx.push(ErrorCode::InvalidArgs, "keys must be string|typed", path);
+                }
+            }
+            _ => ctx.push(ErrorCode::InvalidArgs, "unknown option", path),
+        }
+    }
+}
+
 fn validate_path_arg(expr: &Expr, base_path: &str, ctx: &mut ValidationCtx<'_>) {
     let value = match expr {
         Expr::Literal(value) => value,
@@ -1515,39 +1835,286 @@ fn literal_string(expr: &Expr) -> Option<&str> {
     }
 }
 
+// `mappings[3].when.args` for `mappings[3].when.args[0]`, `mappings[3].when` for that.
+fn parent_path(path: &str) -> Option<&str> {
+    let end = if path.ends_with(']') {
+        path.rfind('[')?
+    } else {
+        path.rfind('.')?
+    };
+    Some(&path[..end])
+}
+
 struct ValidationCtx<'a> {
+    rule: &'a RuleFile,
     locator: Option<&'a YamlLocator>,
+    origin_locators: &'a [YamlLocator],
     errors: Vec<RuleError>,
+    warnings: Vec<RuleWarning>,
+    // (variable, path) of every `env.*` ref, in the order they were validated.
+    env_refs: Vec<(String, String)>,
 }
 
 impl<'a> ValidationCtx<'a> {
-    fn new(locator: Option<&'a YamlLocator>) -> Self {
+    fn new(
+        rule: &'a RuleFile,
+        locator: Option<&'a YamlLocator>,
+        origin_locators: &'a [YamlLocator],
+    ) -> Self {
         Self {
+            rule,
             locator,
+            origin_locators,
             errors: Vec::new(),
+            warnings: Vec::new(),
+            env_refs: Vec::new(),
         }
     }
 
     fn push(&mut self, code: ErrorCode, message: &str, path: impl Into<String>) {
         let path = path.into();
         let mut err = RuleError::new(code, message).with_path(path.clone());
-        if let Some(locator) = self.locator {
-            if let Some(location) = locator.location_for(&path) {
-                err = err.with_location(location.line, location.column);
-            }
+        if let Some(group_path) = self.rule.group_path(&path) {
+            err = err.with_group_path(group_path);
+        }
+        let (file, location) = self.locate(&path);
+        if let Some(file) = file {
+            err = err.with_file(file);
+        }
+        if let Some(location) = location {
+            err = err.with_location(location.line, location.column);
         }
         self.errors.push(err);
     }
 
-    fn finish(self) -> ValidationResult {
-        if self.errors.is_empty() {
-            Ok(())
-        } else {
-            Err(self.errors)
+    fn warn(&mut self, code: WarningCode, message: impl Into<String>, path: impl Into<String>) {
+        let path = path.into();
+        let mut warning = RuleWarning::new(code, message).with_path(path.clone());
+        warning.group_path = self.rule.group_path(&path);
+        let (file, location) = self.locate(&path);
+        warning.file = file;
+        warning.location = location;
+        self.warnings.push(warning);
+    }
+
+    // Returns the declaring file (only for rules composed from several files) and the location.
+    // Fields inherited from `mapping_defaults` have no YAML of their own in the mapping, so
+    // they are reported at the mapping that inherits them.
+    fn locate(&self, path: &str) -> (Option<String>, Option<YamlLocation>) {
+        let yaml_path = self.rule.yaml_mapping_path(path);
+        let yaml_path = yaml_path.as_deref().unwrap_or(path);
+        let (file, locator, yaml_path) = match &self.rule.origins {
+            Some(origins) => match origins.resolve(yaml_path) {
+                Some((index, own_path)) => {
+                    let file = origins
+                        .is_composed()
+                        .then(|| origins.files[index].path.display().to_string());
+                    (file, self.origin_locators.get(index), own_path)
+                }
+                None => return (None, None),
+            },
+            None => (None, self.locator, yaml_path.to_string()),
+        };
+        // A path the YAML does not spell out, such as an option read from an absent key, is
+        // reported at the closest node that exists.
+        let location = locator.and_then(|locator| {
+            let mut path = yaml_path.as_str();
+            loop {
+                if let Some(location) = locator.location_for(path) {
+                    return Some(location);
+                }
+                path = parent_path(path)?;
+            }
+        });
+        (file, location)
+    }
+
+    // Names a second mapping in a message, with its file and line when the source is known.
+    fn describe(&self, path: &str) -> String {
+        let shown = self.rule.group_path(path).unwrap_or_else(|| path.to_string());
+        match self.locate(path) {
+            (Some(file), Some(location)) => format!("{} ({} line {})", shown, file, location.line),
+            (None, Some(location)) => format!("{} (line {})", shown, location.line),
+            (_, None) => shown,
+        }
+    }
+
+    fn finish(self) -> ValidationReport {
+        ValidationReport {
+            errors: self.errors,
+            warnings: self.warnings,
+        }
+    }
+}
+
+fn collect_rule_warnings(rule: &RuleFile, ctx: &mut ValidationCtx<'_>) {
+    for (name, expr) in rule.constants.iter().flatten() {
+        collect_expr_warnings(expr, &format!("constants.{}", name), ctx);
+    }
+    if let Some(expr) = &rule.record_when {
+        collect_when_warnings(expr, "record_when", ctx);
+    }
+    if let Some(group) = &rule.group {
+        collect_expr_warnings(&group.key, "group.key", ctx);
+        if let Some(expr) = &group.record_when {
+            collect_when_warnings(expr, "group.record_when", ctx);
+        }
+    }
+    // Validation accepts duplicate targets only when every write is guarded by `when`.
+    let mut conditional_targets: Vec<(Vec<PathToken>, usize)> = Vec::new();
+    for (index, mapping) in rule.mappings.iter().enumerate() {
+        let base_path = format!("mappings[{}]", index);
+        if let Some(expr) = &mapping.expr {
+            collect_expr_warnings(expr, &format!("{}.expr", base_path), ctx);
+        }
+        if let Some(expr) = &mapping.default {
+            collect_expr_warnings(expr, &format!("{}.default", base_path), ctx);
+            if mapping.required {
+                ctx.warn(
+                    WarningCode::RequiredWithDefault,
+                    "required only fails when default is missing too; drop one of them",
+                    format!("{}.required", base_path),
+                );
+            }
+        }
+        let Some(when) = &mapping.when else {
+            continue;
+        };
+        collect_when_warnings(when, &format!("{}.when", base_path), ctx);
+        let Ok(tokens) = parse_path(&mapping.target) else {
+            continue;
+        };
+        let earlier: Vec<usize> = conditional_targets
+            .iter()
+            .filter(|(other, _)| *other == tokens)
+            .map(|(_, other)| *other)
+            .collect();
+        if matches!(when, Expr::Literal(serde_json::Value::Bool(true))) {
+            for other in &earlier {
+                ctx.warn(
+                    WarningCode::OverwrittenMapping,
+                    format!(
+                        "mapping is always overwritten by {}, whose when is always true",
+                        base_path
+                    ),
+                    format!("mappings[{}].target", other),
+                );
+            }
+        } else if let Some(first) = earlier.first() {
+            ctx.warn(
+                WarningCode::ConditionalDuplicateTarget,
+                format!(
+                    "mapping.target duplicates mappings[{}].target; their when conditions must be mutually exclusive.",
+                    first
+                ),
+                format!("{}.target", base_path),
+            );
+        }
+        conditional_targets.push((tokens, index));
+    }
+    // One warning for the whole file, so operators see every variable the rules read.
+    if let Some((_, first_path)) = ctx.env_refs.first() {
+        let first_path = first_path.clone();
+        let mut names: Vec<&str> = Vec::new();
+        for (name, _) in &ctx.env_refs {
+            if !names.contains(&name.as_str()) {
+                names.push(name);
+            }
+        }
+        let message = format!("rules read environment variables: {}", names.join(", "));
+        ctx.warn(WarningCode::EnvReference, message, first_path);
+    }
+}
+
+fn collect_when_warnings(expr: &Expr, path: &str, ctx: &mut ValidationCtx<'_>) {
+    if let Expr::Literal(serde_json::Value::Bool(flag)) = expr {
+        ctx.warn(
+            WarningCode::ConstantWhen,
+            format!("condition is always {}", flag),
+            path,
+        );
+    }
+    collect_expr_warnings(expr, path, ctx);
+}
+
+fn collect_expr_warnings(expr: &Expr, path: &str, ctx: &mut ValidationCtx<'_>) {
+    match expr {
+        Expr::Ref(_) | Expr::Literal(_) => {}
+        Expr::Op(expr_op) => collect_op_warnings(expr_op, path, false, ctx),
+        Expr::Chain(chain) => {
+            for (index, step) in chain.chain.iter().enumerate() {
+                let step_path = format!("{}.chain[{}]", path, index);
+                match step {
+                    Expr::Op(expr_op) if index > 0 => {
+                        collect_op_warnings(expr_op, &step_path, true, ctx)
+                    }
+                    _ => collect_expr_warnings(step, &step_path, ctx),
+                }
+            }
+        }
+    }
+}
+
+fn collect_op_warnings(
+    expr_op: &ExprOp,
+    path: &str,
+    chain_step: bool,
+    ctx: &mut ValidationCtx<'_>,
+) {
+    let injected = usize::from(chain_step);
+    match expr_op.op.as_str() {
+        "date_format" => {
+            // `input_format` is the third arg; a timezone there means it was left out.
+            let input_index = 2 - injected;
+            let missing = match expr_op.args.get(input_index) {
+                None => Some(format!("{}.args", path)),
+                Some(Expr::Literal(serde_json::Value::String(value)))
+                    if looks_like_timezone(value) =>
+                {
+                    Some(format!("{}.args[{}]", path, input_index))
+                }
+                Some(_) => None,
+            };
+            if let Some(arg_path) = missing {
+                ctx.warn(
+                    WarningCode::DateFormatMissingInputFormat,
+                    "date_format without input_format relies on heuristic parsing; consider providing input_format.",
+                    arg_path,
+                );
+            }
         }
+        "to_unixtime" => ctx.warn(
+            WarningCode::ToUnixtimeAutoParse,
+            "to_unixtime relies on heuristic date parsing; consider normalizing with date_format + input_format.",
+            path,
+        ),
+        "coalesce" if coalesce_values_len(expr_op, injected) == 1 => ctx.warn(
+            WarningCode::SingleArgCoalesce,
+            "coalesce with a single argument returns it unchanged",
+            path,
+        ),
+        _ => {}
+    }
+
+    for (index, arg) in expr_op.args.iter().enumerate() {
+        collect_expr_warnings(arg, &format!("{}.args[{}]", path, index), ctx);
     }
 }
 
+fn coalesce_value_args(expr_op: &ExprOp) -> &[Expr] {
+    let options = usize::from(coalesce_options_arg(&expr_op.args).is_some());
+    &expr_op.args[..expr_op.args.len() - options]
+}
+
+// Arguments besides the options bag, counting a chain's piped value.
+fn coalesce_values_len(expr_op: &ExprOp, injected: usize) -> usize {
+    coalesce_value_args(expr_op).len() + injected
+}
+
+fn looks_like_timezone(value: &str) -> bool {
+    value.eq_ignore_ascii_case("utc") || value == "Z" || value.starts_with(['+', '-'])
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Namespace {
     Input,
@@ -1555,4 +2122,6 @@ enum Namespace {
     Out,
     Item,
     Acc,
+    Const,
+    Env,
 }
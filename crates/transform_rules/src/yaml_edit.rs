@@ -0,0 +1,270 @@
+use serde_yaml::{Mapping as YamlMapping, Value as YamlValue};
+
+use crate::locator::{NodeSpan, YamlNodes};
+
+/// Rewrites the YAML `source` so that it parses to `value`, changing only the nodes that differ.
+///
+/// Comments, blank lines, anchors and the layout of every other node are kept byte for byte.
+/// A changed scalar keeps its quote style, and new or replaced collections are written in flow
+/// style. Returns `None` when `source` cannot be edited into `value` in place, e.g. when an
+/// anchor the edit replaces is still aliased elsewhere; callers then serialize `value` instead.
+pub fn patch_yaml(source: &str, value: &YamlValue) -> Option<String> {
+    let old: YamlValue = serde_yaml::from_str(source).ok()?;
+    let mut patch = Patch {
+        source,
+        nodes: YamlNodes::from_str(source),
+        edits: Vec::new(),
+    };
+    patch.node("", &old, value)?;
+    let text = patch.apply()?;
+    let parsed: YamlValue = serde_yaml::from_str(&text).ok()?;
+    (parsed == *value).then_some(text)
+}
+
+struct Edit {
+    start: usize,
+    end: usize,
+    text: String,
+}
+
+struct Patch<'a> {
+    source: &'a str,
+    nodes: YamlNodes,
+    edits: Vec<Edit>,
+}
+
+impl Patch<'_> {
+    fn node(&mut self, path: &str, old: &YamlValue, new: &YamlValue) -> Option<()> {
+        if old == new {
+            return Some(());
+        }
+        let span = *self.nodes.spans.get(path)?;
+        // An alias is written out in place, as its anchor may be used as it is elsewhere.
+        let alias = self.source.get(span.start..span.end)?.starts_with('*');
+        match (old, new) {
+            (YamlValue::Mapping(old), YamlValue::Mapping(new)) if !alias => {
+                self.mapping(path, span, old, new)
+            }
+            (YamlValue::Sequence(old), YamlValue::Sequence(new))
+                if !alias && old.len() == new.len() =>
+            {
+                for (index, (old, new)) in old.iter().zip(new).enumerate() {
+                    self.node(&format!("{}[{}]", path, index), old, new)?;
+                }
+                Some(())
+            }
+            _ => self.replace_value(span, new),
+        }
+    }
+
+    fn mapping(
+        &mut self,
+        path: &str,
+        span: NodeSpan,
+        old: &YamlMapping,
+        new: &YamlMapping,
+    ) -> Option<()> {
+        let mut removed = Vec::new();
+        for (key, old_value) in old {
+            let child = child_path(path, key.as_str()?);
+            match new.get(key) {
+                Some(new_value) => self.node(&child, old_value, new_value)?,
+                None => removed.push(child),
+            }
+        }
+        let added: Vec<_> = new.iter().filter(|(key, _)| !old.contains_key(*key)).collect();
+
+        // A key swapped for another is rewritten where it was.
+        let renamed = removed.len().min(added.len());
+        for (child, (key, value)) in removed.iter().zip(&added) {
+            self.replace_entry(child, key, value)?;
+        }
+        for child in &removed[renamed..] {
+            self.remove_entry(path, child)?;
+        }
+        if added.len() > renamed {
+            let last_kept = self
+                .nodes
+                .children
+                .get(path)
+                .and_then(|children| {
+                    children.iter().rev().find(|child| !removed[renamed..].contains(child))
+                })
+                .cloned();
+            self.insert_entries(span, last_kept.as_deref(), &added[renamed..])?;
+        }
+        Some(())
+    }
+
+    fn replace_value(&mut self, span: NodeSpan, value: &YamlValue) -> Option<()> {
+        let old = self.source.get(span.start..span.end)?;
+        let mut text = render(value, old.chars().next(), span.flow)?;
+        if span.start == span.end {
+            text.insert(0, ' ');
+        }
+        self.edit(span.start, span.end, text);
+        Some(())
+    }
+
+    fn replace_entry(&mut self, path: &str, key: &YamlValue, value: &YamlValue) -> Option<()> {
+        let span = *self.nodes.spans.get(path)?;
+        let text = format!("{}: {}", render_key(key)?, render(value, None, true)?);
+        self.edit(span.entry, span.end, text);
+        Some(())
+    }
+
+    fn remove_entry(&mut self, parent: &str, path: &str) -> Option<()> {
+        let span = *self.nodes.spans.get(path)?;
+        let siblings = self.nodes.children.get(parent)?;
+        let index = siblings.iter().position(|sibling| sibling == path)?;
+        let sibling = |index: usize| {
+            siblings
+                .get(index)
+                .and_then(|sibling| self.nodes.spans.get(sibling))
+                .copied()
+        };
+        let previous = index.checked_sub(1).and_then(sibling);
+        let next = sibling(index + 1);
+        let (start, end) = if span.flow {
+            match (previous, next) {
+                (Some(previous), _) => (previous.end, span.end),
+                (None, Some(next)) => (span.entry, next.entry),
+                (None, None) => (span.entry, span.end),
+            }
+        } else {
+            let line_start = self.line_start(span.entry);
+            if self.source[line_start..span.entry].trim().is_empty() {
+                let line_end = self.line_end(span.end);
+                (line_start, (line_end + 1).min(self.source.len()))
+            } else {
+                // The entry follows a `-`, so the next one moves up to take its place.
+                (span.entry, next?.entry)
+            }
+        };
+        self.edit(start, end, String::new());
+        Some(())
+    }
+
+    fn insert_entries(
+        &mut self,
+        span: NodeSpan,
+        after: Option<&str>,
+        entries: &[(&YamlValue, &YamlValue)],
+    ) -> Option<()> {
+        let mut rendered = Vec::new();
+        for (key, value) in entries {
+            rendered.push(format!("{}: {}", render_key(key)?, render(value, None, true)?));
+        }
+        let after = after.and_then(|after| self.nodes.spans.get(after)).copied();
+        if self.source.get(span.start..span.end)?.starts_with('{') {
+            let entries = rendered.join(", ");
+            match after {
+                Some(after) => self.edit(after.end, after.end, format!(", {}", entries)),
+                None => self.edit(span.start + 1, span.start + 1, format!(" {} ", entries)),
+            }
+            return Some(());
+        }
+        let after = after?;
+        let indent = " ".repeat(after.entry - self.line_start(after.entry));
+        let text: String = rendered
+            .iter()
+            .map(|entry| format!("\n{}{}", indent, entry))
+            .collect();
+        let line_end = self.line_end(after.end);
+        self.edit(line_end, line_end, text);
+        Some(())
+    }
+
+    fn edit(&mut self, start: usize, end: usize, text: String) {
+        self.edits.push(Edit { start, end, text });
+    }
+
+    fn apply(mut self) -> Option<String> {
+        self.edits.sort_by_key(|edit| (edit.start, edit.end));
+        let mut text = String::with_capacity(self.source.len());
+        let mut pos = 0;
+        for edit in &self.edits {
+            if edit.start < pos {
+                return None;
+            }
+            text.push_str(self.source.get(pos..edit.start)?);
+            text.push_str(&edit.text);
+            pos = edit.end;
+        }
+        text.push_str(self.source.get(pos..)?);
+        Some(text)
+    }
+
+    fn line_start(&self, pos: usize) -> usize {
+        self.source[..pos].rfind('\n').map_or(0, |index| index + 1)
+    }
+
+    fn line_end(&self, pos: usize) -> usize {
+        self.source[pos..]
+            .find('\n')
+            .map_or(self.source.len(), |index| pos + index)
+    }
+}
+
+fn child_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn render_key(key: &YamlValue) -> Option<String> {
+    let key = key.as_str()?;
+    let plain = key.bytes().enumerate().all(|(index, byte)| {
+        byte.is_ascii_alphabetic() || byte == b'_' || (index > 0 && byte.is_ascii_digit())
+    });
+    if plain && !key.is_empty() {
+        Some(key.to_string())
+    } else {
+        serde_json::to_string(key).ok()
+    }
+}
+
+// `quote` is the first character of the node being replaced: a string keeps the style of a
+// quoted or plain scalar, and is otherwise double quoted, as in a flow collection.
+fn render(value: &YamlValue, quote: Option<char>, flow: bool) -> Option<String> {
+    match value {
+        YamlValue::String(text) => match quote {
+            Some('\'') => Some(format!("'{}'", text.replace('\'', "''"))),
+            Some(first) if !flow && !"\"*{[|>".contains(first) => {
+                let plain = serde_yaml::to_string(text).ok()?;
+                let plain = plain.trim_end_matches('\n');
+                if plain.contains('\n') {
+                    serde_json::to_string(text).ok()
+                } else {
+                    Some(plain.to_string())
+                }
+            }
+            _ => serde_json::to_string(text).ok(),
+        },
+        YamlValue::Sequence(items) if items.is_empty() => Some("[]".to_string()),
+        YamlValue::Sequence(items) => {
+            let items = items
+                .iter()
+                .map(|item| render(item, None, true))
+                .collect::<Option<Vec<_>>>()?;
+            Some(format!("[ {} ]", items.join(", ")))
+        }
+        YamlValue::Mapping(map) if map.is_empty() => Some("{}".to_string()),
+        YamlValue::Mapping(map) => {
+            let entries = map
+                .iter()
+                .map(|(key, value)| {
+                    Some(format!("{}: {}", render_key(key)?, render(value, None, true)?))
+                })
+                .collect::<Option<Vec<_>>>()?;
+            Some(format!("{{ {} }}", entries.join(", ")))
+        }
+        _ => {
+            let text = serde_yaml::to_string(value).ok()?;
+            let text = text.trim_end_matches('\n');
+            (!text.contains('\n')).then(|| text.to_string())
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use serde_json::json;
+use transform_rules::{
+    parse_rule_file, records_to_csv, transform, OutputFormat, TransformErrorKind,
+};
+
+#[test]
+fn derived_columns_follow_first_seen_key_order() {
+    let records = vec![
+        json!({ "id": 1, "name": "Ada" }),
+        json!({ "id": 2, "email": "bob@example.com", "name": "Bob" }),
+    ];
+    let csv = records_to_csv(&records, None).unwrap();
+    assert_eq!(csv, "id,name,email\n1,Ada,\n2,Bob,bob@example.com\n");
+}
+
+#[test]
+fn cells_are_quoted_and_nested_values_are_json_encoded() {
+    let records = vec![json!({
+        "text": "a,b",
+        "quote": "say \"hi\"",
+        "multiline": "line1\nline2",
+        "tags": ["x", "y"],
+        "meta": { "k": 1 },
+        "none": null,
+        "flag": true,
+        "ratio": 1.5
+    })];
+    let columns: Vec<String> = ["flag", "ratio", "text", "quote", "multiline", "tags", "meta", "none", "absent"]
+        .iter()
+        .map(|column| column.to_string())
+        .collect();
+    let csv = records_to_csv(&records, Some(&columns)).unwrap();
+
+    let mut reader = csv::Reader::from_reader(csv.as_bytes());
+    let headers: Vec<_> = reader.headers().unwrap().iter().map(str::to_string).collect();
+    assert_eq!(headers, columns);
+    let row = reader.records().next().unwrap().unwrap();
+    let cells: Vec<_> = row.iter().collect();
+    assert_eq!(
+        cells,
+        vec!["true", "1.5", "a,b", "say \"hi\"", "line1\nline2", "[\"x\",\"y\"]", "{\"k\":1}", "", ""]
+    );
+}
+
+#[test]
+fn non_object_records_are_rejected() {
+    let err = records_to_csv(&[json!({ "id": 1 }), json!([1, 2])], None).unwrap_err();
+    assert_eq!(err.kind, TransformErrorKind::InvalidTarget);
+    assert_eq!(err.record_index, Some(1));
+}
+
+#[test]
+fn rule_output_section_declares_format_and_columns() {
+    let rule = parse_rule_file(
+        r#"
+version: 1
+input:
+  format: json
+  json: {}
+output:
+  format: csv
+  columns: [ "name", "id" ]
+mappings:
+  - target: "id"
+    source: "id"
+  - target: "name"
+    source: "name"
+  - target: "ignored"
+    value: "x"
+"#,
+    )
+    .expect("failed to parse rules");
+    let output = rule.output.as_ref().unwrap();
+    assert_eq!(output.format, OutputFormat::Csv);
+
+    let records = transform(&rule, r#"[{ "id": 1, "name": "Ada" }]"#, None).unwrap();
+    let csv = records_to_csv(records.as_array().unwrap(), output.columns.as_deref()).unwrap();
+    assert_eq!(csv, "name,id\nAda,1\n");
+}
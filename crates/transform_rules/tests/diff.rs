@@ -0,0 +1,119 @@
+use serde_json::json;
+use transform_rules::{diff_json, unified_diff, ArrayMatch, DiffKind};
+
+#[test]
+fn identical_values_have_no_diff() {
+    let value = json!([{ "id": 1, "tags": ["a", "b"], "price": 10 }]);
+    assert!(diff_json(&value, &value, &ArrayMatch::Ordered).is_empty());
+    // Numbers compare by value.
+    let float = json!([{ "id": 1, "tags": ["a", "b"], "price": 10.0 }]);
+    assert!(diff_json(&value, &float, &ArrayMatch::Ordered).is_empty());
+}
+
+#[test]
+fn ordered_diff_reports_changed_missing_and_extra() {
+    let expected = json!([
+        { "id": 1, "name": "alice", "address": { "city": "Tokyo" } },
+        { "id": 2, "name": "bob" }
+    ]);
+    let actual = json!([
+        { "id": 1, "name": "alice", "address": { "city": "Osaka" }, "extra": true },
+        { "id": 2, "name": "bob" },
+        { "id": 3, "name": "carol" }
+    ]);
+    let diff = diff_json(&expected, &actual, &ArrayMatch::Ordered);
+    let summary: Vec<_> = diff
+        .iter()
+        .map(|entry| (entry.kind, entry.path.as_str()))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (DiffKind::Changed, "[0].address.city"),
+            (DiffKind::Extra, "[0].extra"),
+            (DiffKind::Extra, "[2]"),
+        ]
+    );
+    assert_eq!(diff[0].expected, Some(json!("Tokyo")));
+    assert_eq!(diff[0].actual, Some(json!("Osaka")));
+    assert_eq!(diff[2].expected, None);
+    assert_eq!(
+        diff[0].to_json(),
+        json!({
+            "type": "diff",
+            "kind": "changed",
+            "path": "[0].address.city",
+            "expected": "Tokyo",
+            "actual": "Osaka"
+        })
+    );
+}
+
+#[test]
+fn keyed_diff_ignores_order() {
+    let expected = json!([
+        { "sku": "A", "qty": 1 },
+        { "sku": "B", "qty": 2 },
+        { "sku": "C", "qty": 3 }
+    ]);
+    let actual = json!([
+        { "sku": "B", "qty": 5 },
+        { "sku": "A", "qty": 1 },
+        { "sku": "D", "qty": 4 }
+    ]);
+    let diff = diff_json(&expected, &actual, &ArrayMatch::Key("sku".to_string()));
+    let summary: Vec<_> = diff
+        .iter()
+        .map(|entry| (entry.kind, entry.path.as_str()))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (DiffKind::Changed, "[sku=\"B\"].qty"),
+            (DiffKind::Missing, "[sku=\"C\"]"),
+            (DiffKind::Extra, "[sku=\"D\"]"),
+        ]
+    );
+}
+
+#[test]
+fn arrays_without_distinct_keys_fall_back_to_order() {
+    let expected = json!([{ "id": 1 }, { "id": 1 }]);
+    let actual = json!([{ "id": 1 }, { "id": 2 }]);
+    let diff = diff_json(&expected, &actual, &ArrayMatch::Key("id".to_string()));
+    assert_eq!(diff.len(), 1);
+    assert_eq!(diff[0].path, "[1].id");
+}
+
+#[test]
+fn unified_diff_groups_changes_into_hunks_with_context() {
+    let old: String = (1..=20).map(|line| format!("line {}\n", line)).collect();
+    let new = old
+        .replace("line 2\n", "line two\n")
+        .replace("line 4\n", "")
+        .replace("line 18\n", "line 18\nline 18b\n");
+    assert_eq!(
+        unified_diff(&old, &new, "a/rules.yaml", "b/rules.yaml"),
+        "--- a/rules.yaml
++++ b/rules.yaml
+@@ -1,7 +1,6 @@
+ line 1
+-line 2
++line two
+ line 3
+-line 4
+ line 5
+ line 6
+ line 7
+@@ -16,5 +15,6 @@
+ line 16
+ line 17
+ line 18
++line 18b
+ line 19
+ line 20
+"
+    );
+    assert_eq!(unified_diff(&old, &old, "a", "b"), "");
+    assert_eq!(unified_diff("", "x\n", "a", "b"), "--- a\n+++ b\n@@ -0,0 +1 @@\n+x\n");
+}
@@ -24,8 +24,8 @@ fn load_text(path: &Path) -> String {
         .to_string()
 }
 
-fn assert_golden(lang: DtoLanguage, expected: &str) {
-    let base = fixtures_dir().join("dto01_basic");
+fn assert_golden(case: &str, lang: DtoLanguage, expected: &str) {
+    let base = fixtures_dir().join(case);
     let rule = load_rule(&base.join("rules.yaml"));
     let output = generate_dto(&rule, lang, None).expect("dto failed");
     let expected = load_text(&base.join(expected));
@@ -34,35 +34,261 @@ fn assert_golden(lang: DtoLanguage, expected: &str) {
 
 #[test]
 fn dto01_rust() {
-    assert_golden(DtoLanguage::Rust, "expected_rust.rs");
+    assert_golden("dto01_basic", DtoLanguage::Rust, "expected_rust.rs");
 }
 
 #[test]
 fn dto01_typescript() {
-    assert_golden(DtoLanguage::TypeScript, "expected_typescript.ts");
+    assert_golden("dto01_basic", DtoLanguage::TypeScript, "expected_typescript.ts");
 }
 
 #[test]
 fn dto01_python() {
-    assert_golden(DtoLanguage::Python, "expected_python.py");
+    assert_golden("dto01_basic", DtoLanguage::Python, "expected_python.py");
 }
 
 #[test]
 fn dto01_go() {
-    assert_golden(DtoLanguage::Go, "expected_go.go");
+    assert_golden("dto01_basic", DtoLanguage::Go, "expected_go.go");
 }
 
 #[test]
 fn dto01_java() {
-    assert_golden(DtoLanguage::Java, "expected_java.java");
+    assert_golden("dto01_basic", DtoLanguage::Java, "expected_java.java");
 }
 
 #[test]
 fn dto01_kotlin() {
-    assert_golden(DtoLanguage::Kotlin, "expected_kotlin.kt");
+    assert_golden("dto01_basic", DtoLanguage::Kotlin, "expected_kotlin.kt");
 }
 
 #[test]
 fn dto01_swift() {
-    assert_golden(DtoLanguage::Swift, "expected_swift.swift");
+    assert_golden("dto01_basic", DtoLanguage::Swift, "expected_swift.swift");
+}
+
+#[test]
+fn dto02_rust() {
+    assert_golden("dto02_nested", DtoLanguage::Rust, "expected_rust.rs");
+}
+
+#[test]
+fn dto02_typescript() {
+    assert_golden("dto02_nested", DtoLanguage::TypeScript, "expected_typescript.ts");
+}
+
+#[test]
+fn dto02_python() {
+    assert_golden("dto02_nested", DtoLanguage::Python, "expected_python.py");
+}
+
+#[test]
+fn dto02_go() {
+    assert_golden("dto02_nested", DtoLanguage::Go, "expected_go.go");
+}
+
+#[test]
+fn dto02_java() {
+    assert_golden("dto02_nested", DtoLanguage::Java, "expected_java.java");
+}
+
+#[test]
+fn dto02_kotlin() {
+    assert_golden("dto02_nested", DtoLanguage::Kotlin, "expected_kotlin.kt");
+}
+
+#[test]
+fn dto02_swift() {
+    assert_golden("dto02_nested", DtoLanguage::Swift, "expected_swift.swift");
+}
+
+#[test]
+fn dto01_json_schema() {
+    assert_golden("dto01_basic", DtoLanguage::JsonSchema, "expected_json_schema.json");
+}
+
+#[test]
+fn dto02_json_schema() {
+    assert_golden("dto02_nested", DtoLanguage::JsonSchema, "expected_json_schema.json");
+}
+
+#[test]
+fn json_schema_title_is_the_dto_name() {
+    let base = fixtures_dir().join("dto01_basic");
+    let mut rule = load_rule(&base.join("rules.yaml"));
+    let output = generate_dto(&rule, DtoLanguage::JsonSchema, Some("Order")).expect("dto failed");
+    let schema: serde_json::Value = serde_json::from_str(&output).expect("schema is json");
+    assert_eq!(schema["title"], "Order");
+
+    // Key order follows the keys, not the mapping order.
+    rule.mappings.reverse();
+    let reversed = generate_dto(&rule, DtoLanguage::JsonSchema, Some("Order")).expect("dto failed");
+    assert_eq!(reversed, output);
+}
+
+#[test]
+fn dto_nested_type_names_do_not_depend_on_mapping_order() {
+    let base = fixtures_dir().join("dto02_nested");
+    let mut rule = load_rule(&base.join("rules.yaml"));
+    rule.mappings.reverse();
+    let output = generate_dto(&rule, DtoLanguage::Rust, None).expect("dto failed");
+    assert!(output.contains("pub struct RecordBillingAddress {\n    pub city: String,"));
+    assert!(output.contains("pub billing_address: Option<RecordBillingAddress2>,"));
+}
+
+#[test]
+fn dto_expression_default_is_not_optional() {
+    let rule = parse_rule_file(
+        r#"
+version: 1
+input:
+  format: json
+mappings:
+  - target: "status"
+    source: "status"
+    type: "string"
+    default: "active"
+  - target: "display_name"
+    source: "display_name"
+    type: "string"
+    default: { op: "concat", args: [ { ref: "input.first" }, " ", { ref: "input.last" } ] }
+  - target: "nickname"
+    source: "nickname"
+    type: "string"
+"#,
+    )
+    .expect("failed to parse rules");
+    let output = generate_dto(&rule, DtoLanguage::TypeScript, None).expect("dto failed");
+    assert!(output.contains("  status: string;"), "{}", output);
+    assert!(output.contains("  displayName: string;"), "{}", output);
+    assert!(output.contains("  nickname?: string;"), "{}", output);
+}
+
+
+#[test]
+fn dto_group_key_and_records_are_not_optional() {
+    let rule = parse_rule_file(
+        r#"
+version: 1
+input:
+  format: json
+group:
+  key: { ref: "input.store_id" }
+mappings:
+  - target: "store_id"
+    source: "key"
+    type: "string"
+  - target: "items"
+    source: "input.records"
+  - target: "note"
+    source: "note"
+  - target: "quoted_key"
+    source: 'input["key"]'
+  - target: "key_part"
+    source: "input.key.region"
+  - target: "first"
+    source: "records[0]"
+"#,
+    )
+    .expect("failed to parse rules");
+    let output = generate_dto(&rule, DtoLanguage::TypeScript, None).expect("dto failed");
+    assert!(output.contains("  storeId: string;"), "{}", output);
+    assert!(output.contains("  items: unknown;"), "{}", output);
+    assert!(output.contains("  note?: unknown;"), "{}", output);
+    assert!(output.contains("  quotedKey: unknown;"), "{}", output);
+    // Only the key and the records themselves are always present.
+    assert!(output.contains("  keyPart?: unknown;"), "{}", output);
+    assert!(output.contains("  first?: unknown;"), "{}", output);
+}
+
+#[test]
+fn dto_array_targets_become_item_types() {
+    let rule = parse_rule_file(
+        r#"
+version: 1
+input:
+  format: json
+mappings:
+  - target: "items[].sku"
+    source: "skus"
+    type: "string"
+    required: true
+  - target: "items[].qty"
+    source: "qtys"
+    type: "int"
+  - target: "tags[]"
+    source: "tags"
+    type: "string"
+"#,
+    )
+    .expect("failed to parse rules");
+    let output = generate_dto(&rule, DtoLanguage::Rust, None).expect("dto failed");
+    assert!(output.contains("pub struct RecordItems {\n    pub sku: String,"), "{}", output);
+    assert!(output.contains("    pub items: Vec<RecordItems>,"), "{}", output);
+    assert!(output.contains("    pub tags: Option<Vec<String>>,"), "{}", output);
+
+    let output = generate_dto(&rule, DtoLanguage::TypeScript, None).expect("dto failed");
+    assert!(output.contains("  items: RecordItems[];"), "{}", output);
+    assert!(output.contains("  tags?: string[];"), "{}", output);
+
+    let output = generate_dto(&rule, DtoLanguage::Python, None).expect("dto failed");
+    assert!(output.contains("from typing import Optional, List"), "{}", output);
+    assert!(output.contains("    items: List[RecordItems]"), "{}", output);
+
+    let output = generate_dto(&rule, DtoLanguage::Go, None).expect("dto failed");
+    assert!(output.contains("    Tags []string `json:\"tags,omitempty\"`"), "{}", output);
+
+    let output = generate_dto(&rule, DtoLanguage::Java, None).expect("dto failed");
+    assert!(output.contains("import java.util.List;"), "{}", output);
+    assert!(output.contains("    public List<RecordItems> items;"), "{}", output);
+
+    let output = generate_dto(&rule, DtoLanguage::Swift, None).expect("dto failed");
+    assert!(output.contains("    let items: [RecordItems]"), "{}", output);
+}
+
+#[test]
+fn dto_date_and_decimal_types() {
+    let rule = parse_rule_file(
+        r#"
+version: 1
+input:
+  format: json
+mappings:
+  - target: "ordered_at"
+    source: "ordered_at"
+    type: "datetime"
+    required: true
+  - target: "ordered_on"
+    source: "ordered_on"
+    type: "date"
+    required: true
+  - target: "amount"
+    source: "amount"
+    type: "decimal(2)"
+    required: true
+"#,
+    )
+    .expect("failed to parse rules");
+    let output = generate_dto(&rule, DtoLanguage::Rust, None).expect("dto failed");
+    assert!(output.starts_with("use chrono::{DateTime, NaiveDate, Utc};\n"), "{}", output);
+    assert!(output.contains("    pub ordered_at: DateTime<Utc>,"), "{}", output);
+    assert!(output.contains("    pub amount: String,"), "{}", output);
+
+    let output = generate_dto(&rule, DtoLanguage::TypeScript, None).expect("dto failed");
+    assert!(output.contains("  orderedAt: string;"), "{}", output);
+
+    let output = generate_dto(&rule, DtoLanguage::Python, None).expect("dto failed");
+    assert!(output.contains("from datetime import date, datetime\nfrom decimal import Decimal\n"), "{}", output);
+    assert!(output.contains("    amount: Decimal"), "{}", output);
+
+    let output = generate_dto(&rule, DtoLanguage::Go, None).expect("dto failed");
+    assert!(output.contains("import \"time\""), "{}", output);
+    assert!(output.contains("    OrderedAt time.Time"), "{}", output);
+
+    let output = generate_dto(&rule, DtoLanguage::Java, None).expect("dto failed");
+    assert!(output.contains("import java.math.BigDecimal;\nimport java.time.LocalDate;\nimport java.time.OffsetDateTime;\n"), "{}", output);
+    assert!(output.contains("    public BigDecimal amount;"), "{}", output);
+
+    let output = generate_dto(&rule, DtoLanguage::Kotlin, None).expect("dto failed");
+    assert!(output.contains("    val orderedOn: LocalDate,"), "{}", output);
 }
@@ -0,0 +1,108 @@
+use serde_json::json;
+use transform_rules::{
+    parse_rule_file, transform, transform_stream, validate_rule_file_full, ErrorCode,
+    TransformErrorKind, WarningCode,
+};
+
+// Cargo sets `CARGO_PKG_NAME` for the test binary, so no test has to change the environment.
+const RULES: &str = r#"version: 1
+input:
+  format: json
+  json: {}
+constants:
+  package: { ref: "env.CARGO_PKG_NAME" }
+mappings:
+  - target: "id"
+    source: "id"
+  - target: "package"
+    source: "env.CARGO_PKG_NAME"
+  - target: "region"
+    expr:
+      op: "coalesce"
+      args: [ { ref: "env.TRANSFORM_RULES_TEST_UNSET_REGION" }, "eu" ]
+  - target: "tenant"
+    source: "env[\"TRANSFORM_RULES_TEST_UNSET_TENANT\"]"
+    default: "none"
+  - target: "constant"
+    source: "const.package"
+"#;
+
+fn collect(rule: &transform_rules::RuleFile, input: &str) -> serde_json::Value {
+    let outputs: Vec<_> = transform_stream(rule, input, None)
+        .unwrap()
+        .with_env(true)
+        .map(|item| item.unwrap().output.unwrap())
+        .collect();
+    json!(outputs)
+}
+
+#[test]
+fn env_refs_read_the_environment_when_allowed() {
+    let rule = parse_rule_file(RULES).unwrap();
+    assert_eq!(
+        collect(&rule, r#"[{ "id": 1 }]"#),
+        json!([{
+            "id": 1,
+            "package": "transform_rules",
+            "region": "eu",
+            "tenant": "none",
+            "constant": "transform_rules"
+        }])
+    );
+}
+
+#[test]
+fn env_refs_fail_without_the_opt_in() {
+    let rule = parse_rule_file(RULES).unwrap();
+    let err = transform(&rule, r#"[{ "id": 1 }]"#, None).unwrap_err();
+    assert_eq!(err.kind, TransformErrorKind::ExprError);
+    assert_eq!(
+        err.message,
+        "env namespace is disabled; the transform must be run with allow_env"
+    );
+    assert_eq!(err.path.as_deref(), Some("constants.package"));
+
+    let yaml = RULES.replace("constants:\n  package: { ref: \"env.CARGO_PKG_NAME\" }\n", "");
+    let yaml = yaml.replace("  - target: \"constant\"\n    source: \"const.package\"\n", "");
+    let rule = parse_rule_file(&yaml).unwrap();
+    let err = transform(&rule, r#"[{ "id": 1 }]"#, None).unwrap_err();
+    assert_eq!(err.kind, TransformErrorKind::ExprError);
+    assert_eq!(err.path.as_deref(), Some("mappings[1].source"));
+}
+
+#[test]
+fn validation_warns_with_every_env_variable_the_rules_read() {
+    let rule = parse_rule_file(RULES).unwrap();
+    let report = validate_rule_file_full(&rule, Some(RULES));
+    assert!(report.is_valid(), "{:?}", report.errors);
+    assert_eq!(report.warnings.len(), 1);
+    let warning = &report.warnings[0];
+    assert_eq!(warning.code, WarningCode::EnvReference);
+    assert_eq!(
+        warning.message,
+        "rules read environment variables: CARGO_PKG_NAME, TRANSFORM_RULES_TEST_UNSET_REGION, \
+         TRANSFORM_RULES_TEST_UNSET_TENANT"
+    );
+    assert_eq!(warning.path.as_deref(), Some("constants.package"));
+    assert_eq!(warning.location.as_ref().map(|location| location.line), Some(6));
+}
+
+#[test]
+fn env_refs_name_a_single_variable() {
+    let yaml = r#"version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  - target: "home"
+    expr: { ref: "env.HOME.path" }
+"#;
+    let rule = parse_rule_file(yaml).unwrap();
+    let report = validate_rule_file_full(&rule, Some(yaml));
+    let errors: Vec<_> = report
+        .errors
+        .iter()
+        .map(|err| (err.code.clone(), err.path.as_deref()))
+        .collect();
+    assert_eq!(errors, vec![(ErrorCode::InvalidPath, Some("mappings[0].expr"))]);
+}
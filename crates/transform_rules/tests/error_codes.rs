@@ -0,0 +1,94 @@
+use std::collections::HashSet;
+
+use transform_rules::{
+    error_catalog, parse_rule_file, transform, TransformError, TransformErrorCode,
+    TransformErrorKind, TransformWarning,
+};
+
+fn transform_error(expr: &str, input: &str) -> TransformError {
+    let yaml = format!(
+        "version: 1\ninput:\n  format: json\n  json: {{}}\nmappings:\n  - target: \"out\"\n    expr: {}\n",
+        expr
+    );
+    let rule = parse_rule_file(&yaml).unwrap();
+    transform(&rule, input, None).expect_err("expected transform error")
+}
+
+#[test]
+fn catalog_codes_are_unique_and_in_order() {
+    let catalog = error_catalog();
+    let mut seen = HashSet::new();
+    for (code, kind, description) in catalog {
+        let text = code.as_str();
+        assert!(text.len() == 5 && text.starts_with('T'), "{}", text);
+        assert!(text[1..].bytes().all(|byte| byte.is_ascii_digit()), "{}", text);
+        assert!(
+            code.name().bytes().all(|byte| byte.is_ascii_uppercase() || byte == b'_'),
+            "{}",
+            code.name()
+        );
+        assert!(seen.insert(text), "duplicate code {}", text);
+        assert_eq!(code.kind(), *kind);
+        assert_eq!(code.description(), *description);
+        assert!(!description.is_empty());
+    }
+    let codes: Vec<_> = catalog.iter().map(|(code, _, _)| code.as_str()).collect();
+    let mut sorted = codes.clone();
+    sorted.sort_unstable();
+    assert_eq!(codes, sorted);
+}
+
+#[test]
+fn transform_errors_carry_the_code_of_their_failure() {
+    let cases = [
+        (r#"{ op: "trim", args: [] }"#, "{}", "T1201", "ARG_COUNT"),
+        (
+            r#"{ op: "substring", args: [ { ref: "input.name" }, 0, { ref: "input.length" } ] }"#,
+            r#"{ "name": "abc", "length": null }"#,
+            "T1302",
+            "NULL_ARG",
+        ),
+        (
+            r#"{ op: "date_format", args: [ "2024-01-02", "%Y", "Mars/Olympus" ] }"#,
+            "{}",
+            "T1405",
+            "BAD_TIMEZONE",
+        ),
+        (
+            r#"{ op: "int_div", args: [ 1, 0 ] }"#,
+            "{}",
+            "T1408",
+            "DIVISION_BY_ZERO",
+        ),
+    ];
+    for (expr, input, code, name) in cases {
+        let err = transform_error(expr, input);
+        assert_eq!(err.code.as_str(), code, "{}: {}", expr, err.message);
+        assert_eq!(err.code.name(), name);
+        assert_eq!(err.code.kind(), err.kind);
+    }
+}
+
+#[test]
+fn errors_without_a_specific_code_get_the_one_of_their_kind() {
+    let err = TransformError::new(TransformErrorKind::ExprError, "custom failure");
+    assert_eq!(err.code, TransformErrorCode::ExprError);
+    assert_eq!(err.code.as_str(), "T1200");
+
+    let err = err.with_code(TransformErrorCode::ArgRange);
+    let warning = TransformWarning::from(err);
+    assert_eq!(warning.code, TransformErrorCode::ArgRange);
+    assert_eq!(warning.kind, TransformErrorKind::ExprError);
+}
+
+#[test]
+fn rules_spec_lists_every_code() {
+    let docs = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../docs");
+    let en = std::fs::read_to_string(docs.join("rules_spec_en.md")).unwrap();
+    let ja = std::fs::read_to_string(docs.join("rules_spec_ja.md")).unwrap();
+    for (code, kind, description) in error_catalog() {
+        let row = format!("| `{}` | `{}` | {:?} | ", code.as_str(), code.name(), kind);
+        assert!(en.contains(&format!("{}{} |", row, description)), "{}", row);
+        assert!(ja.contains(&row), "{}", row);
+    }
+}
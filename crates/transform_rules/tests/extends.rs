@@ -0,0 +1,118 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::json;
+use transform_rules::{
+    load_rule_file, parse_rule_file, transform, validate_rule_file, ErrorCode,
+};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join("extends")
+        .join(name)
+}
+
+#[test]
+fn child_overrides_and_extends_parents() {
+    let rule = load_rule_file(&fixture("tenant.yaml")).expect("failed to load rules");
+    validate_rule_file(&rule).expect("composed rules should be valid");
+
+    let targets: Vec<_> = rule.mappings.iter().map(|mapping| mapping.target.as_str()).collect();
+    assert_eq!(targets, vec!["id", "name", "status", "audit.source", "tenant"]);
+    let csv = rule.input.csv.as_ref().unwrap();
+    assert_eq!(csv.delimiter, ";");
+
+    let origins = rule.origins.as_ref().unwrap();
+    let files: Vec<_> = origins
+        .files
+        .iter()
+        .map(|file| file.path.file_name().unwrap().to_str().unwrap())
+        .collect();
+    assert_eq!(files, vec!["base.yaml", "audit.yaml", "tenant.yaml"]);
+
+    let input = "id;name;tenant\n0;skip;t0\n1;alice;t1\n";
+    let output = transform(&rule, input, None).expect("transform failed");
+    assert_eq!(
+        output,
+        json!([{
+            "id": 1,
+            "name": "alice",
+            "status": "tenant",
+            "audit": { "source": "audit" },
+            "tenant": "t1"
+        }])
+    );
+}
+
+#[test]
+fn validation_errors_name_the_declaring_file() {
+    let rule = load_rule_file(&fixture("invalid_tenant.yaml")).expect("failed to load rules");
+    let errors = validate_rule_file(&rule).unwrap_err();
+    assert_eq!(errors.len(), 2);
+
+    let unknown_op = errors.iter().find(|err| err.code == ErrorCode::UnknownOp).unwrap();
+    assert_eq!(unknown_op.path.as_deref(), Some("mappings[1].expr.op"));
+    assert!(unknown_op.file.as_deref().unwrap().ends_with("invalid_base.yaml"));
+    assert_eq!(unknown_op.location.as_ref().map(|location| location.line), Some(10));
+
+    let namespace = errors
+        .iter()
+        .find(|err| err.code == ErrorCode::InvalidRefNamespace)
+        .unwrap();
+    assert_eq!(namespace.path.as_deref(), Some("mappings[2].expr"));
+    assert!(namespace.file.as_deref().unwrap().ends_with("invalid_tenant.yaml"));
+    assert_eq!(namespace.location.as_ref().map(|location| location.line), Some(4));
+}
+
+#[test]
+fn single_files_keep_unqualified_errors() {
+    let rule = load_rule_file(&fixture("invalid_base.yaml")).expect("failed to load rules");
+    let errors = validate_rule_file(&rule).unwrap_err();
+    assert_eq!(errors[0].code, ErrorCode::UnknownOp);
+    assert_eq!(errors[0].file, None);
+    assert_eq!(errors[0].location.as_ref().map(|location| location.line), Some(10));
+}
+
+#[test]
+fn extends_cycles_report_the_chain() {
+    let err = load_rule_file(&fixture("cycle_a.yaml")).unwrap_err();
+    assert_eq!(err.code, ErrorCode::ExtendsCycle);
+    let chain: Vec<_> = err
+        .message
+        .trim_start_matches("extends cycle: ")
+        .split(" -> ")
+        .map(|path| Path::new(path).file_name().unwrap().to_str().unwrap().to_string())
+        .collect();
+    assert_eq!(chain, vec!["cycle_a.yaml", "cycle_b.yaml", "cycle_a.yaml"]);
+}
+
+#[test]
+fn missing_parent_is_a_read_error() {
+    let dir = std::env::temp_dir().join(format!("extends-missing-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let child = dir.join("child.yaml");
+    std::fs::write(&child, "extends: \"missing.yaml\"\nmappings: []\n").unwrap();
+
+    let err = load_rule_file(&child).unwrap_err();
+    assert_eq!(err.code, ErrorCode::RulesReadFailed);
+    assert!(err.file.as_deref().unwrap().ends_with("missing.yaml"));
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn inline_rules_reject_extends() {
+    let err = parse_rule_file(
+        r#"
+extends: "base.yaml"
+version: 1
+input:
+  format: json
+mappings: []
+"#,
+    )
+    .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("`extends` is only supported when rules are loaded from a file"));
+}
@@ -15,8 +15,15 @@ struct Record: Codable {
     let source: String
 
     enum CodingKeys: String, CodingKey {
+        case id
+        case user
+        case price
+        case active
+        case meta
         case userName = "user-name"
         case class_ = "class"
+        case status
+        case source
     }
 }
 
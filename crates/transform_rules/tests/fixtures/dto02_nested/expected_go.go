@@ -0,0 +1,42 @@
+package dto
+
+import "encoding/json"
+
+type RecordBillingAddress2 struct {
+    Line *json.RawMessage `json:"line,omitempty"`
+}
+
+type RecordCustomerAddress struct {
+    City *string `json:"city,omitempty"`
+    ZipCode *string `json:"zip-code,omitempty"`
+}
+
+type RecordCustomer struct {
+    Address *RecordCustomerAddress `json:"address,omitempty"`
+    Name string `json:"name"`
+}
+
+type RecordBillingAddress struct {
+    City string `json:"city"`
+}
+
+type RecordBilling struct {
+    Address RecordBillingAddress `json:"address"`
+}
+
+type RecordOrdersShip struct {
+    ToName *string `json:"to-name,omitempty"`
+}
+
+type RecordOrders struct {
+    Sku string `json:"sku"`
+    Ship *RecordOrdersShip `json:"ship,omitempty"`
+}
+
+type Record struct {
+    Id int64 `json:"id"`
+    BillingAddress *RecordBillingAddress2 `json:"billing_address,omitempty"`
+    Customer RecordCustomer `json:"customer"`
+    Billing RecordBilling `json:"billing"`
+    Orders []RecordOrders `json:"orders"`
+}
@@ -0,0 +1,46 @@
+import com.fasterxml.jackson.annotation.JsonProperty;
+import com.fasterxml.jackson.databind.JsonNode;
+import java.util.List;
+import java.util.Optional;
+
+class RecordBillingAddress2 {
+    public Optional<JsonNode> line;
+}
+
+class RecordCustomerAddress {
+    public Optional<String> city;
+    @JsonProperty("zip-code")
+    public Optional<String> zipCode;
+}
+
+class RecordCustomer {
+    public Optional<RecordCustomerAddress> address;
+    public String name;
+}
+
+class RecordBillingAddress {
+    public String city;
+}
+
+class RecordBilling {
+    public RecordBillingAddress address;
+}
+
+class RecordOrdersShip {
+    @JsonProperty("to-name")
+    public Optional<String> toName;
+}
+
+class RecordOrders {
+    public String sku;
+    public Optional<RecordOrdersShip> ship;
+}
+
+public class Record {
+    public Long id;
+    @JsonProperty("billing_address")
+    public Optional<RecordBillingAddress2> billingAddress;
+    public RecordCustomer customer;
+    public RecordBilling billing;
+    public List<RecordOrders> orders;
+}
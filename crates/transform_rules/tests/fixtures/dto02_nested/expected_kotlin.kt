@@ -0,0 +1,44 @@
+import com.fasterxml.jackson.annotation.JsonProperty
+import com.fasterxml.jackson.databind.JsonNode
+
+data class RecordBillingAddress2(
+    val line: JsonNode?
+)
+
+data class RecordCustomerAddress(
+    val city: String?,
+    @JsonProperty("zip-code")
+    val zipCode: String?
+)
+
+data class RecordCustomer(
+    val address: RecordCustomerAddress?,
+    val name: String
+)
+
+data class RecordBillingAddress(
+    val city: String
+)
+
+data class RecordBilling(
+    val address: RecordBillingAddress
+)
+
+data class RecordOrdersShip(
+    @JsonProperty("to-name")
+    val toName: String?
+)
+
+data class RecordOrders(
+    val sku: String,
+    val ship: RecordOrdersShip?
+)
+
+data class Record(
+    val id: Long,
+    @JsonProperty("billing_address")
+    val billingAddress: RecordBillingAddress2?,
+    val customer: RecordCustomer,
+    val billing: RecordBilling,
+    val orders: List<RecordOrders>
+)
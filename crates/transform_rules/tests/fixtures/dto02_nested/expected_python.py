@@ -0,0 +1,43 @@
+from dataclasses import dataclass, field
+from typing import Optional, List, Any
+
+@dataclass
+class RecordBillingAddress2:
+    line: Optional[Any] = None
+
+@dataclass
+class RecordCustomerAddress:
+    city: Optional[str] = None
+    # json: "zip-code"
+    zip_code: Optional[str] = field(default=None, metadata={"json_key": "zip-code"})
+
+@dataclass
+class RecordCustomer:
+    name: str
+    address: Optional[RecordCustomerAddress] = None
+
+@dataclass
+class RecordBillingAddress:
+    city: str
+
+@dataclass
+class RecordBilling:
+    address: RecordBillingAddress
+
+@dataclass
+class RecordOrdersShip:
+    # json: "to-name"
+    to_name: Optional[str] = field(default=None, metadata={"json_key": "to-name"})
+
+@dataclass
+class RecordOrders:
+    sku: str
+    ship: Optional[RecordOrdersShip] = None
+
+@dataclass
+class Record:
+    id: int
+    customer: RecordCustomer
+    billing: RecordBilling
+    orders: List[RecordOrders]
+    billing_address: Optional[RecordBillingAddress2] = None
@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordBillingAddress2 {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordCustomerAddress {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "zip-code")]
+    pub zip_code: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordCustomer {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<RecordCustomerAddress>,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordBillingAddress {
+    pub city: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordBilling {
+    pub address: RecordBillingAddress,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordOrdersShip {
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "to-name")]
+    pub to_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordOrders {
+    pub sku: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ship: Option<RecordOrdersShip>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    pub id: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub billing_address: Option<RecordBillingAddress2>,
+    pub customer: RecordCustomer,
+    pub billing: RecordBilling,
+    pub orders: Vec<RecordOrders>,
+}
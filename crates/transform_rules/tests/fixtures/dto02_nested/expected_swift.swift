@@ -0,0 +1,101 @@
+struct RecordBillingAddress2: Codable {
+    let line: JSONValue?
+}
+
+struct RecordCustomerAddress: Codable {
+    let city: String?
+    let zipCode: String?
+
+    enum CodingKeys: String, CodingKey {
+        case city
+        case zipCode = "zip-code"
+    }
+}
+
+struct RecordCustomer: Codable {
+    let address: RecordCustomerAddress?
+    let name: String
+}
+
+struct RecordBillingAddress: Codable {
+    let city: String
+}
+
+struct RecordBilling: Codable {
+    let address: RecordBillingAddress
+}
+
+struct RecordOrdersShip: Codable {
+    let toName: String?
+
+    enum CodingKeys: String, CodingKey {
+        case toName = "to-name"
+    }
+}
+
+struct RecordOrders: Codable {
+    let sku: String
+    let ship: RecordOrdersShip?
+}
+
+struct Record: Codable {
+    let id: Int
+    let billingAddress: RecordBillingAddress2?
+    let customer: RecordCustomer
+    let billing: RecordBilling
+    let orders: [RecordOrders]
+
+    enum CodingKeys: String, CodingKey {
+        case id
+        case billingAddress = "billing_address"
+        case customer
+        case billing
+        case orders
+    }
+}
+
+enum JSONValue: Codable {
+    case string(String)
+    case number(Double)
+    case bool(Bool)
+    case object([String: JSONValue])
+    case array([JSONValue])
+    case null
+
+    init(from decoder: Decoder) throws {
+        let container = try decoder.singleValueContainer()
+        if container.decodeNil() {
+            self = .null
+        } else if let value = try? container.decode(Bool.self) {
+            self = .bool(value)
+        } else if let value = try? container.decode(Double.self) {
+            self = .number(value)
+        } else if let value = try? container.decode(String.self) {
+            self = .string(value)
+        } else if let value = try? container.decode([String: JSONValue].self) {
+            self = .object(value)
+        } else if let value = try? container.decode([JSONValue].self) {
+            self = .array(value)
+        } else {
+            throw DecodingError.typeMismatch(JSONValue.self, DecodingError.Context(codingPath: decoder.codingPath, debugDescription: "Unsupported JSON value"))
+        }
+    }
+
+    func encode(to encoder: Encoder) throws {
+        var container = encoder.singleValueContainer()
+        switch self {
+        case .string(let value):
+            try container.encode(value)
+        case .number(let value):
+            try container.encode(value)
+        case .bool(let value):
+            try container.encode(value)
+        case .object(let value):
+            try container.encode(value)
+        case .array(let value):
+            try container.encode(value)
+        case .null:
+            try container.encodeNil()
+        }
+    }
+}
@@ -0,0 +1,41 @@
+export interface RecordBillingAddress2 {
+  line?: unknown;
+}
+
+export interface RecordCustomerAddress {
+  city?: string;
+  /** json: "zip-code" */
+  zipCode?: string;
+}
+
+export interface RecordCustomer {
+  address?: RecordCustomerAddress;
+  name: string;
+}
+
+export interface RecordBillingAddress {
+  city: string;
+}
+
+export interface RecordBilling {
+  address: RecordBillingAddress;
+}
+
+export interface RecordOrdersShip {
+  /** json: "to-name" */
+  toName?: string;
+}
+
+export interface RecordOrders {
+  sku: string;
+  ship?: RecordOrdersShip;
+}
+
+export interface Record {
+  id: number;
+  /** json: "billing_address" */
+  billingAddress?: RecordBillingAddress2;
+  customer: RecordCustomer;
+  billing: RecordBilling;
+  orders: RecordOrders[];
+}
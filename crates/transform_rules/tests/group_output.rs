@@ -0,0 +1,120 @@
+use serde_json::json;
+use transform_rules::{
+    parse_rule_file, preflight_validate_with_warnings, transform_stream,
+    transform_stream_from_reader, transform_with_warnings, RuleFile, TransformErrorKind,
+};
+
+fn rule(extra: &str) -> RuleFile {
+    let yaml = format!(
+        r#"
+version: 1
+input:
+  format: json
+  json: {{}}
+{}
+group:
+  key: {{ ref: "input.team" }}
+  record_when:
+    op: "!="
+    args: [ {{ op: "lowercase", args: [ {{ ref: "input.skip" }} ] }}, "yes" ]
+mappings:
+  - target: "team"
+    source: "key"
+  - target: "points"
+    expr:
+      chain:
+        - {{ ref: "input.records" }}
+        - {{ op: "map", args: [ {{ ref: "item.value.points" }} ] }}
+        - {{ op: "sum" }}
+"#,
+        extra
+    );
+    parse_rule_file(&yaml).expect("failed to parse rules")
+}
+
+const INPUT: &str = r#"[
+  { "team": "red", "points": 1 },
+  { "team": "blue", "points": 2 },
+  { "team": "red", "points": 3 },
+  { "points": 4 },
+  { "team": "blue", "points": 5, "skip": "YES" },
+  { "team": 1, "points": 6 },
+  { "team": "1", "points": 7 }
+]"#;
+
+#[test]
+fn groups_are_emitted_in_first_seen_order() {
+    let rule = rule("");
+    let items: Vec<_> = transform_stream(&rule, INPUT, None)
+        .unwrap()
+        .map(|item| item.unwrap())
+        .collect();
+    let outputs: Vec<_> = items.iter().map(|item| item.output.clone().unwrap()).collect();
+    assert_eq!(
+        outputs,
+        vec![
+            json!({ "team": "red", "points": 4 }),
+            json!({ "team": "blue", "points": 2 }),
+            json!({ "team": null, "points": 4 }),
+            json!({ "team": 1, "points": 6 }),
+            json!({ "team": "1", "points": 7 }),
+        ]
+    );
+    let indexes: Vec<_> = items.iter().map(|item| item.record_index).collect();
+    assert_eq!(indexes, vec![0, 1, 2, 3, 4]);
+    let groups: Vec<_> = items.iter().map(|item| item.group_index).collect();
+    assert_eq!(groups, vec![Some(0), Some(1), Some(2), Some(3), Some(4)]);
+
+    let from_reader: Vec<_> = transform_stream_from_reader(&rule, INPUT.as_bytes(), None)
+        .unwrap()
+        .map(|item| item.unwrap().output.unwrap())
+        .collect();
+    assert_eq!(from_reader, outputs);
+}
+
+#[test]
+fn group_errors_follow_on_error() {
+    let input = r#"[
+        { "team": "red", "points": 1 },
+        { "team": "red", "points": "x" },
+        { "team": "blue", "points": 2 }
+    ]"#;
+
+    let err = transform_with_warnings(&rule(""), input, None).unwrap_err();
+    assert_eq!(err.kind, TransformErrorKind::ExprError);
+    assert_eq!(err.record_index, Some(0));
+
+    let rule = rule("on_error: collect");
+    let items: Vec<_> = transform_stream(&rule, input, None)
+        .unwrap()
+        .map(|item| item.unwrap())
+        .collect();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0].output, None);
+    assert_eq!(items[0].errors[0].record_index, Some(0));
+    assert_eq!(items[1].output, Some(json!({ "team": "blue", "points": 2 })));
+}
+
+#[test]
+fn record_when_warnings_come_before_groups_and_preflight_works() {
+    let rule = rule("");
+    let input = r#"[{ "team": "red", "points": 1, "skip": 1 }, { "team": "red", "points": 2 }]"#;
+    let mut stream = transform_stream(&rule, input, None).unwrap();
+
+    let first = stream.next().unwrap().unwrap();
+    assert_eq!(first.output, None);
+    assert_eq!(first.record_index, 0);
+    assert_eq!(first.group_index, None);
+    assert_eq!(first.warnings.len(), 1);
+    assert_eq!(first.warnings[0].path.as_deref(), Some("group.record_when.args[0].args[0]"));
+    let second = stream.next().unwrap().unwrap();
+    assert_eq!(second.output, Some(json!({ "team": "red", "points": 2 })));
+    assert_eq!(second.group_index, Some(0));
+    assert!(stream.next().is_none());
+
+    let warnings = preflight_validate_with_warnings(&rule, input, None).unwrap();
+    assert_eq!(warnings.len(), 1);
+
+    let err = preflight_validate_with_warnings(&rule, "[{", None).unwrap_err();
+    assert_eq!(err.kind, TransformErrorKind::InvalidInput);
+}
@@ -0,0 +1,105 @@
+use serde_json::json;
+use transform_rules::{parse_rule_file, transform, transform_stream};
+
+// `context.*` collections are indexed; the same collection under `input.*` is scanned linearly.
+const RULES: &str = r#"
+version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  - target: "first"
+    expr:
+      op: "lookup_first"
+      args: [ { ref: "context.users" }, "id", { ref: "input.user_id" }, "name" ]
+  - target: "first_scan"
+    expr:
+      op: "lookup_first"
+      args: [ { ref: "input.users" }, "id", { ref: "input.user_id" }, "name" ]
+  - target: "all"
+    expr:
+      op: "lookup"
+      args: [ { ref: "context.users" }, "id", { ref: "input.user_id" } ]
+  - target: "all_scan"
+    expr:
+      op: "lookup"
+      args: [ { ref: "input.users" }, "id", { ref: "input.user_id" } ]
+"#;
+
+#[test]
+fn indexed_lookup_matches_linear_scan() {
+    let users = json!([
+        { "id": 1, "role": "no name" },
+        { "id": "1", "name": "first" },
+        { "name": "no id" },
+        { "id": 2, "name": "two" },
+        { "id": 1, "name": "second" },
+        { "id": true, "name": "bool" },
+        { "id": { "nested": 1 }, "name": "object" }
+    ]);
+    let rule = parse_rule_file(RULES).expect("failed to parse rules");
+    let records: Vec<_> = [json!(1), json!("1"), json!(2), json!(3), json!(true), json!(1.0)]
+        .into_iter()
+        .map(|user_id| json!({ "user_id": user_id, "users": users }))
+        .collect();
+    let input = serde_json::to_string(&records).unwrap();
+    let context = json!({ "users": users });
+
+    let output = transform(&rule, &input, Some(&context)).expect("transform failed");
+    for record in output.as_array().unwrap() {
+        assert_eq!(record.get("first"), record.get("first_scan"), "{}", record);
+        assert_eq!(record.get("all"), record.get("all_scan"), "{}", record);
+    }
+    assert_eq!(output[0]["first"], "first");
+    assert_eq!(output[0]["all"].as_array().unwrap().len(), 3);
+    assert_eq!(output[2]["first"], "two");
+    assert!(output[3].get("all").is_none());
+    assert_eq!(output[4]["first"], "bool");
+}
+
+#[test]
+fn index_is_scoped_to_one_stream() {
+    let rule = parse_rule_file(RULES).expect("failed to parse rules");
+    let input = r#"[{ "user_id": 1, "users": [] }]"#;
+
+    let context = json!({ "users": [{ "id": 1, "name": "before" }] });
+    let output = transform(&rule, input, Some(&context)).expect("transform failed");
+    assert_eq!(output[0]["first"], "before");
+
+    let context = json!({ "users": [{ "id": 1, "name": "after" }] });
+    let output = transform(&rule, input, Some(&context)).expect("transform failed");
+    assert_eq!(output[0]["first"], "after");
+
+    // Interleaved streams on one thread keep their own indexes.
+    let left = json!({ "users": [{ "id": 1, "name": "left" }] });
+    let right = json!({ "users": [{ "id": 1, "name": "right" }] });
+    let input = r#"[{ "user_id": 1 }, { "user_id": 1 }]"#;
+    let mut left_stream = transform_stream(&rule, input, Some(&left)).unwrap();
+    let mut right_stream = transform_stream(&rule, input, Some(&right)).unwrap();
+    for _ in 0..2 {
+        let item = left_stream.next().unwrap().unwrap();
+        assert_eq!(item.output.unwrap()["first"], "left");
+        let item = right_stream.next().unwrap().unwrap();
+        assert_eq!(item.output.unwrap()["first"], "right");
+    }
+}
+
+#[test]
+fn indexed_lookup_handles_large_reference_tables() {
+    let rule = parse_rule_file(RULES).expect("failed to parse rules");
+    let users: Vec<_> = (0..50_000)
+        .map(|id| json!({ "id": id, "name": format!("user-{}", id) }))
+        .collect();
+    let context = json!({ "users": users });
+    let records: Vec<_> = (0..20_000)
+        .map(|index| json!({ "user_id": (index * 7) % 60_000 }))
+        .collect();
+    let input = serde_json::to_string(&records).unwrap();
+
+    let output = transform(&rule, &input, Some(&context)).expect("transform failed");
+    let output = output.as_array().unwrap();
+    assert_eq!(output.len(), 20_000);
+    assert_eq!(output[1]["first"], "user-7");
+    assert_eq!(output[1]["all"], json!([{ "id": 7, "name": "user-7" }]));
+    assert!(output[7_500].get("first").is_none());
+}
@@ -0,0 +1,214 @@
+use serde_json::json;
+use transform_rules::{
+    parse_rule_file, transform, validate_rule_file, validate_rule_file_with_source, ErrorCode,
+    TransformErrorKind,
+};
+
+const WITH_DEFAULTS: &str = r#"
+version: 1
+input:
+  format: json
+  json: {}
+mapping_defaults:
+  required: true
+  type: "string"
+  when: { op: "==", args: [ { ref: "input.active" }, true ] }
+mappings:
+  - target: "id"
+    source: "id"
+  - target: "count"
+    source: "count"
+    type: "int"
+    required: false
+    default: 0
+  - target: "note"
+    source: "note"
+    when: null
+    type: null
+"#;
+
+const EXPANDED: &str = r#"
+version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  - target: "id"
+    source: "id"
+    required: true
+    type: "string"
+    when: { op: "==", args: [ { ref: "input.active" }, true ] }
+  - target: "count"
+    source: "count"
+    type: "int"
+    required: false
+    default: 0
+    when: { op: "==", args: [ { ref: "input.active" }, true ] }
+  - target: "note"
+    source: "note"
+    required: true
+"#;
+
+#[test]
+fn defaults_expand_like_written_out_mappings() {
+    let with_defaults = parse_rule_file(WITH_DEFAULTS).expect("failed to parse rules");
+    let expanded = parse_rule_file(EXPANDED).expect("failed to parse rules");
+    assert_eq!(
+        format!("{:?}", with_defaults.mappings),
+        format!("{:?}", expanded.mappings)
+    );
+    assert_eq!(
+        with_defaults.mapping_defaults.as_ref().and_then(|defaults| defaults.required),
+        Some(true)
+    );
+    assert!(expanded.mapping_defaults.is_none());
+    validate_rule_file(&with_defaults).expect("expected valid rules");
+
+    let input = r#"[
+        { "active": true, "id": 1, "note": "a" },
+        { "active": false, "id": 2, "note": "b" }
+    ]"#;
+    let output = transform(&with_defaults, input, None).expect("transform failed");
+    assert_eq!(
+        output,
+        json!([
+            { "id": "1", "count": 0, "note": "a" },
+            { "note": "b" }
+        ])
+    );
+
+    let err = transform(&with_defaults, r#"[{ "active": true, "note": "a" }]"#, None)
+        .expect_err("expected missing id");
+    assert_eq!(err.kind, TransformErrorKind::MissingRequired);
+    assert_eq!(err.path.as_deref(), Some("mappings[0]"));
+}
+
+#[test]
+fn defaults_apply_to_mapping_groups_and_anchors() {
+    let rule = parse_rule_file(
+        r#"
+version: 1
+input:
+  format: json
+  json: {}
+mapping_defaults:
+  type: "string"
+mapping_groups:
+  ids:
+    - target: "id"
+      source: "id"
+      when: &has_id { op: "exists", args: [ { ref: "input.id" } ] }
+  names:
+    - target: "name"
+      source: "name"
+      when: *has_id
+"#,
+    )
+    .expect("failed to parse rules");
+    let expanded = parse_rule_file(
+        r#"
+version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  - target: "id"
+    source: "id"
+    type: "string"
+    when: { op: "exists", args: [ { ref: "input.id" } ] }
+  - target: "name"
+    source: "name"
+    type: "string"
+    when: { op: "exists", args: [ { ref: "input.id" } ] }
+"#,
+    )
+    .expect("failed to parse rules");
+    assert_eq!(
+        format!("{:?}", rule.mappings),
+        format!("{:?}", expanded.mappings)
+    );
+}
+
+#[test]
+fn inherited_field_errors_point_at_the_mapping() {
+    let yaml = r#"version: 1
+input:
+  format: json
+  json: {}
+mapping_defaults:
+  type: "money"
+mappings:
+  - target: "id"
+    source: "id"
+  - target: "name"
+    source: "name"
+    type: "text"
+  - target: "note"
+    source: "note"
+    type: "string"
+"#;
+    let rule = parse_rule_file(yaml).expect("failed to parse rules");
+    let errors = validate_rule_file_with_source(&rule, yaml).unwrap_err();
+    let reported: Vec<_> = errors
+        .iter()
+        .map(|err| {
+            assert_eq!(err.code, ErrorCode::InvalidTypeName);
+            let location = err.location.as_ref().expect("expected location");
+            (err.path.as_deref().unwrap(), location.line, location.column)
+        })
+        .collect();
+    assert_eq!(
+        reported,
+        vec![("mappings[0].type", 8, 3), ("mappings[1].type", 12, 5)]
+    );
+}
+
+#[test]
+fn unknown_default_fields_are_rejected() {
+    let err = parse_rule_file(
+        r#"
+version: 1
+input:
+  format: json
+  json: {}
+mapping_defaults:
+  source: "id"
+mappings:
+  - target: "id"
+"#,
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("unknown field `source`"), "{}", err);
+}
+
+#[test]
+fn treat_empty_as_missing_is_inherited_and_written_back() {
+    let yaml = r#"
+version: 1
+input:
+  format: csv
+  csv: {}
+mapping_defaults:
+  treat_empty_as_missing: true
+mappings:
+  - target: "city"
+    source: "city"
+    default: "n/a"
+  - target: "code"
+    source: "code"
+    treat_empty_as_missing: false
+"#;
+    let rule = parse_rule_file(yaml).expect("failed to parse rules");
+    assert!(rule.mappings[0].treat_empty_as_missing);
+    assert!(!rule.mappings[1].treat_empty_as_missing);
+    assert_eq!(parse_rule_file(&rule.to_yaml()).unwrap(), rule);
+
+    let output = transform(&rule, "city,code\n,\n", None).expect("transform failed");
+    assert_eq!(output, json!([{ "city": "n/a", "code": "" }]));
+
+    let required = yaml.replace("    default: \"n/a\"", "    required: true");
+    let rule = parse_rule_file(&required).expect("failed to parse rules");
+    let err = transform(&rule, "city,code\n,\n", None).unwrap_err();
+    assert_eq!(err.kind, TransformErrorKind::MissingRequired);
+    assert_eq!(err.path.as_deref(), Some("mappings[0]"));
+}
@@ -0,0 +1,124 @@
+use transform_rules::{
+    parse_rule_file, transform, validate_rule_file, validate_rule_file_with_source, ErrorCode,
+    RuleFile,
+};
+
+const GROUPED: &str = r#"
+version: 1
+input:
+  format: json
+  json: {}
+mapping_groups:
+  shipping:
+    - target: "city"
+      source: "city"
+  billing:
+    - target: "id"
+      source: "id"
+    - target: "total"
+      source: "total"
+      type: "int"
+  audit:
+    - target: "note"
+      value: "ok"
+"#;
+
+fn targets(rule: &RuleFile) -> Vec<&str> {
+    rule.mappings
+        .iter()
+        .map(|mapping| mapping.target.as_str())
+        .collect()
+}
+
+#[test]
+fn groups_concatenate_alphabetically_by_default() {
+    let rule = parse_rule_file(GROUPED).expect("failed to parse rules");
+
+    assert_eq!(targets(&rule), vec!["note", "id", "total", "city"]);
+    assert_eq!(rule.mapping_group(0), Some(("audit", 0)));
+    assert_eq!(rule.mapping_group(2), Some(("billing", 1)));
+    assert_eq!(rule.mapping_group(3), Some(("shipping", 0)));
+    assert!(validate_rule_file(&rule).is_ok());
+}
+
+#[test]
+fn group_order_controls_concatenation() {
+    let yaml = GROUPED.replace(
+        "mapping_groups:",
+        "group_order: [billing, shipping]\nmapping_groups:",
+    );
+    let rule = parse_rule_file(&yaml).expect("failed to parse rules");
+
+    assert_eq!(targets(&rule), vec!["id", "total", "city", "note"]);
+    assert_eq!(rule.mapping_group(3), Some(("audit", 0)));
+}
+
+#[test]
+fn plain_mappings_have_no_group_path() {
+    let yaml = r#"
+version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  - target: "id"
+    source: "id"
+"#;
+    let rule = parse_rule_file(yaml).expect("failed to parse rules");
+
+    assert!(rule.mapping_groups.is_none());
+    assert_eq!(rule.mapping_group(0), None);
+    assert_eq!(rule.group_path("mappings[0].target"), None);
+}
+
+#[test]
+fn validation_errors_carry_group_path_and_location() {
+    let yaml = GROUPED.replace("source: \"total\"", "source: \"total[\"");
+    let rule = parse_rule_file(&yaml).expect("failed to parse rules");
+    let errors = validate_rule_file_with_source(&rule, &yaml).unwrap_err();
+
+    assert_eq!(errors.len(), 1);
+    let error = &errors[0];
+    assert_eq!(error.code, ErrorCode::InvalidPath);
+    assert_eq!(error.path.as_deref(), Some("mappings[2].source"));
+    assert_eq!(error.group_path.as_deref(), Some("mappings[billing][1].source"));
+    let location = error.location.clone().expect("expected location");
+    assert_eq!(location.line, 14);
+}
+
+#[test]
+fn transform_errors_carry_group_path() {
+    let rule = parse_rule_file(GROUPED).expect("failed to parse rules");
+    let input = r#"[{ "id": 1, "total": "x", "city": "Hue" }]"#;
+    let err = transform(&rule, input, None).expect_err("expected transform error");
+
+    assert_eq!(err.path.as_deref(), Some("mappings[2].type"));
+    assert_eq!(err.group_path.as_deref(), Some("mappings[billing][1].type"));
+}
+
+#[test]
+fn parse_cache_keeps_group_layout() {
+    let first = parse_rule_file(GROUPED).expect("failed to parse rules");
+    let second = parse_rule_file(GROUPED).expect("failed to parse cached rules");
+
+    assert_eq!(targets(&first), targets(&second));
+    let groups = second.mapping_groups.as_ref().expect("mapping groups");
+    let names: Vec<_> = groups.groups.iter().map(|group| group.name.as_str()).collect();
+    assert_eq!(names, vec!["audit", "billing", "shipping"]);
+}
+
+#[test]
+fn group_order_requires_mapping_groups() {
+    let yaml = r#"
+version: 1
+input:
+  format: json
+  json: {}
+group_order: [billing]
+mappings:
+  - target: "id"
+    source: "id"
+"#;
+    let err = parse_rule_file(yaml).expect_err("expected parse error");
+    assert!(err.to_string().contains("group_order"));
+}
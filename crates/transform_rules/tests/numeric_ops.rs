@@ -0,0 +1,124 @@
+use serde_json::{json, Value};
+use transform_rules::{parse_rule_file, transform, TransformErrorKind};
+
+fn eval(op: &str, args: Value, input: Value) -> Result<Value, transform_rules::TransformError> {
+    let yaml = format!(
+        "version: 1\ninput:\n  format: json\n  json: {{}}\nmappings:\n  - target: \"out\"\n    expr:\n      op: {}\n      args: {}\n",
+        serde_json::to_string(op).unwrap(),
+        args
+    );
+    let rule = parse_rule_file(&yaml).expect("failed to parse rules");
+    let output = transform(&rule, &json!([input]).to_string(), None)?;
+    Ok(output[0].get("out").cloned().unwrap_or(Value::Null))
+}
+
+fn refs(count: usize) -> Value {
+    Value::Array(
+        (0..count)
+            .map(|index| json!({ "ref": format!("input.a{}", index) }))
+            .collect(),
+    )
+}
+
+#[test]
+fn integer_arithmetic_keeps_precision() {
+    let max = i64::MAX;
+    let cases = [
+        ("+", json!({ "a0": max - 1, "a1": 1 }), json!(max)),
+        ("-", json!({ "a0": i64::MIN + 1, "a1": 1 }), json!(i64::MIN)),
+        (
+            "*",
+            json!({ "a0": 3_037_000_499_i64, "a1": 3_037_000_499_i64 }),
+            json!(9_223_372_030_926_249_001_i64),
+        ),
+        // Above 2^53, where f64 can no longer represent every integer.
+        (
+            "+",
+            json!({ "a0": 9_007_199_254_740_993_i64, "a1": 2 }),
+            json!(9_007_199_254_740_995_i64),
+        ),
+        ("-", json!({ "a0": -7, "a1": 5 }), json!(-12)),
+    ];
+    for (op, input, expected) in cases {
+        assert_eq!(eval(op, refs(2), input.clone()).unwrap(), expected, "{} {}", op, input);
+    }
+}
+
+#[test]
+fn overflow_and_floats_fall_back_to_f64() {
+    let output = eval("+", refs(2), json!({ "a0": i64::MAX, "a1": 1 })).unwrap();
+    assert_eq!(output.as_f64(), Some(i64::MAX as f64 + 1.0));
+    assert!(!output.is_i64());
+    assert_eq!(eval("+", refs(2), json!({ "a0": 1.5, "a1": 1 })).unwrap(), json!(2.5));
+    assert_eq!(eval("/", refs(2), json!({ "a0": 7, "a1": 2 })).unwrap(), json!(3.5));
+}
+
+#[test]
+fn int_div_and_mod_truncate_toward_zero() {
+    let cases = [
+        (7, 2, 3, 1),
+        (-7, 2, -3, -1),
+        (7, -2, -3, 1),
+        (-7, -2, 3, -1),
+        (i64::MAX, 2, i64::MAX / 2, 1),
+        (i64::MIN, -1, i64::MIN, 0),
+    ];
+    for (left, right, quotient, remainder) in cases {
+        let input = json!({ "a0": left, "a1": right });
+        assert_eq!(eval("mod", refs(2), input.clone()).unwrap(), json!(remainder), "{}", input);
+        if left == i64::MIN && right == -1 {
+            let err = eval("int_div", refs(2), input).unwrap_err();
+            assert_eq!(err.kind, TransformErrorKind::ExprError);
+            continue;
+        }
+        assert_eq!(eval("int_div", refs(2), input.clone()).unwrap(), json!(quotient), "{}", input);
+        assert_eq!(left, quotient * right + remainder);
+    }
+    assert_eq!(eval("mod", refs(2), json!({ "a0": 5.5, "a1": 2 })).unwrap(), json!(1.5));
+    assert_eq!(eval("int_div", refs(2), json!({ "a0": -5.5, "a1": 2 })).unwrap(), json!(-2));
+}
+
+#[test]
+fn division_by_zero_is_an_error() {
+    for op in ["int_div", "mod"] {
+        let err = eval(op, refs(2), json!({ "a0": 1, "a1": 0 })).unwrap_err();
+        assert_eq!(err.kind, TransformErrorKind::ExprError);
+        assert_eq!(err.message, "division by zero");
+        assert_eq!(err.path.as_deref(), Some("mappings[0].expr.args[1]"));
+    }
+}
+
+#[test]
+fn unary_ops_round_toward_their_direction() {
+    let cases = [
+        ("abs", json!(-5), json!(5)),
+        ("abs", json!(-2.5), json!(2.5)),
+        ("floor", json!(-2.5), json!(-3)),
+        ("ceil", json!(-2.5), json!(-2)),
+        ("trunc", json!(-2.5), json!(-2)),
+        ("floor", json!(i64::MAX), json!(i64::MAX)),
+        ("ceil", json!("1.2"), json!(2)),
+    ];
+    for (op, value, expected) in cases {
+        let output = eval(op, refs(1), json!({ "a0": value })).unwrap();
+        assert_eq!(output, expected, "{} {}", op, value);
+    }
+    let output = eval("abs", refs(1), json!({ "a0": i64::MIN })).unwrap();
+    assert_eq!(output.as_f64(), Some(-(i64::MIN as f64)));
+}
+
+#[test]
+fn missing_propagates_and_null_or_text_is_an_error() {
+    let ops = [("abs", 1), ("floor", 1), ("ceil", 1), ("trunc", 1), ("mod", 2), ("int_div", 2)];
+    for (op, count) in ops {
+        assert_eq!(eval(op, refs(count), json!({ "a1": 1 })).unwrap(), Value::Null, "{}", op);
+
+        let err = eval(op, refs(count), json!({ "a0": null, "a1": 1 })).unwrap_err();
+        assert_eq!(err.kind, TransformErrorKind::ExprError, "{}", op);
+        assert_eq!(err.message, "expr arg must not be null");
+
+        let err = eval(op, refs(count), json!({ "a0": "abc", "a1": 1 })).unwrap_err();
+        assert_eq!(err.kind, TransformErrorKind::ExprError, "{}", op);
+        assert_eq!(err.path.as_deref(), Some("mappings[0].expr.args[0]"));
+    }
+}
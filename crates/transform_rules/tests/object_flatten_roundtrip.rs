@@ -0,0 +1,117 @@
+use serde_json::{json, Map, Value};
+use transform_rules::{parse_rule_file, transform};
+
+const KEYS: [&str; 5] = ["a", "b", "c.d", "e_f", "x y"];
+
+// Small xorshift generator so the property test stays deterministic without extra deps.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+}
+
+fn random_value(rng: &mut Rng, depth: usize) -> Value {
+    let choice = if depth >= 4 { rng.below(3) } else { rng.below(6) };
+    match choice {
+        0 => json!(rng.below(100)),
+        1 => json!(format!("s{}", rng.below(10))),
+        2 => Value::Null,
+        3 | 4 => random_object(rng, depth + 1),
+        _ => {
+            let len = rng.below(4) as usize;
+            Value::Array((0..len).map(|_| random_value(rng, depth + 1)).collect())
+        }
+    }
+}
+
+fn random_object(rng: &mut Rng, depth: usize) -> Value {
+    let mut map = Map::new();
+    let len = rng.below(4) as usize;
+    for _ in 0..len {
+        let key = KEYS[rng.below(KEYS.len() as u64) as usize];
+        map.insert(key.to_string(), random_value(rng, depth));
+    }
+    Value::Object(map)
+}
+
+fn round_trip(obj: &Value, options: Option<&Value>) -> (Value, Value) {
+    let args = match options {
+        Some(options) => format!(", {}", options),
+        None => String::new(),
+    };
+    let rules = format!(
+        r#"
+version: 1
+input:
+  format: json
+  json: {{}}
+mappings:
+  - target: "flat"
+    expr:
+      op: "object_flatten"
+      args: [ {{ ref: "input.obj" }}{args} ]
+  - target: "restored"
+    expr:
+      op: "object_unflatten"
+      args: [ {{ ref: "out.flat" }}{args} ]
+"#
+    );
+    let rule = parse_rule_file(&rules).expect("failed to parse rules");
+    let input = json!({ "obj": obj }).to_string();
+    let output = transform(&rule, &input, None).expect("transform failed");
+    (output[0]["flat"].clone(), output[0]["restored"].clone())
+}
+
+#[test]
+fn flatten_unflatten_round_trips_under_each_option_combination() {
+    let mut option_sets = vec![None];
+    for separator in [None, Some("__"), Some("/")] {
+        for max_depth in [None, Some(0), Some(1), Some(2)] {
+            for arrays in ["keep", "index"] {
+                let mut options = Map::new();
+                if let Some(separator) = separator {
+                    options.insert("separator".to_string(), json!(separator));
+                }
+                if let Some(max_depth) = max_depth {
+                    options.insert("max_depth".to_string(), json!(max_depth));
+                }
+                options.insert("arrays".to_string(), json!(arrays));
+                option_sets.push(Some(Value::Object(options)));
+            }
+        }
+    }
+
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+    for _ in 0..40 {
+        let obj = random_object(&mut rng, 0);
+        for options in &option_sets {
+            let (flat, restored) = round_trip(&obj, options.as_ref());
+            assert_eq!(
+                restored, obj,
+                "round trip failed for {} with options {:?} (flat: {})",
+                obj, options, flat
+            );
+        }
+    }
+}
+
+#[test]
+fn max_depth_keeps_deeper_values_intact() {
+    let obj = json!({ "a": { "b": { "c": { "d": 1 } } } });
+    let options = json!({ "separator": "__", "max_depth": 1 });
+    let (flat, _) = round_trip(&obj, Some(&options));
+    assert_eq!(flat, json!({ "a__b": { "c": { "d": 1 } } }));
+
+    let options = json!({ "max_depth": 0 });
+    let (flat, _) = round_trip(&obj, Some(&options));
+    assert_eq!(flat, obj);
+}
@@ -0,0 +1,115 @@
+use serde_json::json;
+use transform_rules::{
+    parse_rule_file, preflight_validate, transform_stream, transform_with_warnings, OnError,
+    RuleFile, TransformErrorKind,
+};
+
+fn rule(on_error: &str) -> RuleFile {
+    let yaml = format!(
+        r#"
+version: 1
+input:
+  format: json
+  json: {{}}
+on_error: {}
+mappings:
+  - target: "id"
+    source: "id"
+    type: "int"
+"#,
+        on_error
+    );
+    parse_rule_file(&yaml).expect("failed to parse rules")
+}
+
+const INPUT: &str = r#"[{ "id": "1" }, { "id": "x" }, { "id": "3" }, { "id": "y" }]"#;
+
+#[test]
+fn on_error_defaults_to_abort() {
+    let rule = parse_rule_file(
+        r#"
+version: 1
+input:
+  format: json
+mappings:
+  - target: "id"
+    source: "id"
+    type: "int"
+"#,
+    )
+    .expect("failed to parse rules");
+    assert_eq!(rule.on_error, OnError::Abort);
+
+    let err = transform_with_warnings(&rule, INPUT, None).unwrap_err();
+    assert_eq!(err.kind, TransformErrorKind::TypeCastFailed);
+
+    let mut stream = transform_stream(&rule, INPUT, None).unwrap();
+    assert!(stream.next().unwrap().is_ok());
+    assert!(stream.next().unwrap().is_err());
+    assert!(stream.next().is_none());
+}
+
+#[test]
+fn stream_attaches_row_errors_and_keeps_going() {
+    let rule = rule("skip");
+    let items: Vec<_> = transform_stream(&rule, INPUT, None)
+        .unwrap()
+        .map(|item| item.expect("row errors should not end the stream"))
+        .collect();
+
+    assert_eq!(items.len(), 4);
+    let failed: Vec<_> = items
+        .iter()
+        .filter(|item| !item.errors.is_empty())
+        .map(|item| item.record_index)
+        .collect();
+    assert_eq!(failed, vec![1, 3]);
+    assert_eq!(items[1].output, None);
+    assert_eq!(items[1].errors[0].kind, TransformErrorKind::TypeCastFailed);
+    assert_eq!(items[1].errors[0].path.as_deref(), Some("mappings[0].type"));
+    assert_eq!(items[2].output, Some(json!({ "id": 3 })));
+}
+
+#[test]
+fn skip_drops_failures_and_collect_reports_them() {
+    let (output, warnings) = transform_with_warnings(&rule("skip"), INPUT, None).unwrap();
+    assert_eq!(output, json!([{ "id": 1 }, { "id": 3 }]));
+    assert!(warnings.is_empty());
+
+    let (output, warnings) = transform_with_warnings(&rule("collect"), INPUT, None).unwrap();
+    assert_eq!(output, json!([{ "id": 1 }, { "id": 3 }]));
+    assert_eq!(warnings.len(), 2);
+    assert!(warnings
+        .iter()
+        .all(|warning| warning.kind == TransformErrorKind::TypeCastFailed));
+}
+
+#[test]
+fn input_errors_still_abort() {
+    let rule = rule("collect");
+    let err = transform_with_warnings(&rule, "[{ \"id\": 1 },", None).unwrap_err();
+    assert_eq!(err.kind, TransformErrorKind::InvalidInput);
+
+    let rule = parse_rule_file(
+        r#"
+version: 1
+input:
+  format: json
+  json:
+    records_path: "items"
+on_error: skip
+mappings:
+  - target: "id"
+    source: "id"
+"#,
+    )
+    .expect("failed to parse rules");
+    let err = transform_with_warnings(&rule, r#"{ "rows": [] }"#, None).unwrap_err();
+    assert_eq!(err.kind, TransformErrorKind::InvalidRecordsPath);
+}
+
+#[test]
+fn preflight_reports_row_errors_regardless_of_policy() {
+    let err = preflight_validate(&rule("skip"), INPUT, None).unwrap_err();
+    assert_eq!(err.kind, TransformErrorKind::TypeCastFailed);
+}
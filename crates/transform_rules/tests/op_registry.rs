@@ -0,0 +1,48 @@
+use serde_json::{json, Value};
+use transform_rules::{
+    op_spec, parse_rule_file, transform, validate_rule_file, ErrorCode, OpCategory, OPS,
+};
+
+fn rule_for(op: &str, args: &[Value]) -> String {
+    let args = serde_json::to_string(args).unwrap();
+    format!(
+        "version: 1\ninput:\n  format: json\n  json: {{}}\nmappings:\n  - target: \"out\"\n    expr:\n      op: {}\n      args: {}\n",
+        serde_json::to_string(op).unwrap(),
+        args
+    )
+}
+
+#[test]
+fn every_registered_op_is_evaluated_and_validated() {
+    for spec in OPS {
+        let args = vec![json!(1); spec.min_args];
+        let rule = parse_rule_file(&rule_for(spec.name, &args)).expect("failed to parse rules");
+        if let Err(err) = transform(&rule, "[{}]", None) {
+            assert_ne!(err.message, "expr.op is not supported", "{}", spec.name);
+        }
+        if let Err(errors) = validate_rule_file(&rule) {
+            assert!(
+                errors.iter().all(|err| err.code != ErrorCode::UnknownOp),
+                "{}",
+                spec.name
+            );
+        }
+    }
+}
+
+#[test]
+fn registry_names_are_unique_and_ranges_are_sane() {
+    for (index, spec) in OPS.iter().enumerate() {
+        assert!(
+            OPS[..index].iter().all(|other| other.name != spec.name),
+            "{} is registered twice",
+            spec.name
+        );
+        assert!(spec.min_args >= 1, "{}", spec.name);
+        assert!(spec.max_args.is_none_or(|max| max >= spec.min_args), "{}", spec.name);
+    }
+    assert!(op_spec("lowcase").is_none());
+    let sum = op_spec("sum").expect("sum is registered");
+    assert!(sum.categories.contains(&OpCategory::Array));
+    assert!(sum.categories.contains(&OpCategory::Numeric));
+}
@@ -0,0 +1,150 @@
+use serde_json::json;
+use transform_rules::{
+    parse_rule_file, transform_stream, transform_with_warnings, transform_with_warnings_parallel,
+    RuleFile, TransformErrorKind,
+};
+
+fn rule(on_error: &str) -> RuleFile {
+    let yaml = format!(
+        r#"
+version: 1
+input:
+  format: csv
+  csv:
+    has_header: true
+on_error: {}
+constants:
+  prefix: "user-"
+record_when: {{ op: "!=", args: [ {{ ref: "input.kind" }}, "skip" ] }}
+mappings:
+  - target: "id"
+    source: "id"
+    type: "int"
+  - target: "key"
+    expr: {{ op: "concat", args: [ {{ ref: "const.prefix" }}, {{ ref: "out.id" }} ] }}
+  - target: "name"
+    expr: {{ op: "replace", args: [ {{ ref: "input.name" }}, "[aeiou]", "*", "regex_all" ] }}
+  - target: "team"
+    expr: {{ op: "lookup_first", args: [ {{ ref: "context.teams" }}, "id", {{ ref: "input.team" }}, "name" ] }}
+  - target: "flag"
+    value: true
+    when: {{ ref: "input.flag" }}
+"#,
+        on_error
+    );
+    parse_rule_file(&yaml).expect("failed to parse rules")
+}
+
+// Every 7th record is filtered out, and every 5th has a non-boolean `when` (a warning).
+fn input(records: usize, bad_id_at: Option<usize>) -> String {
+    let mut text = String::from("id,kind,name,team,flag\n");
+    for index in 0..records {
+        let id = if Some(index) == bad_id_at {
+            "x".to_string()
+        } else {
+            index.to_string()
+        };
+        let kind = if index % 7 == 3 { "skip" } else { "keep" };
+        let flag = if index % 5 == 0 { "yes" } else { "" };
+        text.push_str(&format!("{},{},name {},{},{}\n", id, kind, index, index % 3, flag));
+    }
+    text
+}
+
+fn context() -> serde_json::Value {
+    json!({ "teams": [
+        { "id": "0", "name": "red" },
+        { "id": "1", "name": "green" },
+        { "id": "2", "name": "blue" }
+    ] })
+}
+
+#[test]
+fn parallel_output_and_warnings_match_one_thread() {
+    let rule = rule("abort");
+    let input = input(3000, None);
+    let context = context();
+    let sequential = transform_with_warnings(&rule, &input, Some(&context)).unwrap();
+    assert!(!sequential.1.is_empty());
+    for threads in [0, 2, 3, 8] {
+        let parallel =
+            transform_with_warnings_parallel(&rule, &input, Some(&context), threads).unwrap();
+        assert_eq!(parallel, sequential, "threads={}", threads);
+    }
+    assert_eq!(sequential.0[1]["key"], "user-1");
+    assert_eq!(sequential.0[1]["name"], "n*m* 1");
+    assert_eq!(sequential.0[1]["team"], "green");
+}
+
+#[test]
+fn parallel_streams_keep_record_order_and_on_error() {
+    let context = context();
+    let input = input(2000, Some(1234));
+
+    let abort = rule("abort");
+    let sequential = transform_with_warnings(&abort, &input, Some(&context)).unwrap_err();
+    let parallel =
+        transform_with_warnings_parallel(&abort, &input, Some(&context), 4).unwrap_err();
+    assert_eq!(parallel, sequential);
+    assert_eq!(parallel.kind, TransformErrorKind::TypeCastFailed);
+    assert_eq!(parallel.record_index, Some(1234));
+    assert_eq!(parallel.line, Some(1236));
+
+    let skip = rule("skip");
+    let items: Vec<_> = transform_stream(&skip, &input, Some(&context))
+        .unwrap()
+        .with_threads(4)
+        .map(|item| item.expect("skip keeps going"))
+        .collect();
+    let indexes: Vec<_> = items.iter().map(|item| item.record_index).collect();
+    let expected: Vec<_> = (0..2000).filter(|index| index % 7 != 3).collect();
+    assert_eq!(indexes, expected);
+    let failed: Vec<_> = items
+        .iter()
+        .filter(|item| !item.errors.is_empty())
+        .map(|item| item.record_index)
+        .collect();
+    assert_eq!(failed, vec![1234]);
+}
+
+#[test]
+fn group_rules_fall_back_to_one_thread_with_a_warning() {
+    let rule = parse_rule_file(
+        r#"
+version: 1
+input:
+  format: json
+group:
+  key: { ref: "input.team" }
+mappings:
+  - target: "team"
+    source: "key"
+  - target: "points"
+    expr:
+      chain:
+        - { ref: "input.records" }
+        - { op: "map", args: [ { ref: "item.value.points" } ] }
+        - { op: "sum" }
+"#,
+    )
+    .expect("failed to parse rules");
+    let input = r#"[
+  { "team": "a", "points": 1 },
+  { "team": "b", "points": 2 },
+  { "team": "a", "points": 3 }
+]"#;
+    let (output, warnings) = transform_with_warnings_parallel(&rule, input, None, 4).unwrap();
+    assert_eq!(
+        output,
+        json!([{ "team": "a", "points": 4 }, { "team": "b", "points": 2 }])
+    );
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].path.as_deref(), Some("group"));
+    assert_eq!(
+        warnings[0].message,
+        "rules with group are transformed on one thread"
+    );
+
+    let (_, warnings) = transform_with_warnings_parallel(&rule, input, None, 1).unwrap();
+    assert!(warnings.is_empty());
+}
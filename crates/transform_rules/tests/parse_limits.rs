@@ -0,0 +1,208 @@
+use transform_rules::{
+    parse_rule_file, parse_rule_file_with_limits, transform_stream_with_limits, ErrorCode,
+    ParseLimits, TransformErrorKind,
+};
+
+const RULES: &str = r#"
+version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  - target: "id"
+    source: "id"
+  - target: "name"
+    expr: { op: "trim", args: [ { ref: "input.name" } ] }
+"#;
+
+fn rules_with_format(format: &str) -> String {
+    RULES.replace("format: json\n  json: {}", &format!("format: {}", format))
+}
+
+#[test]
+fn rules_within_limits_parse_as_usual() {
+    let limits = ParseLimits {
+        max_bytes: Some(RULES.len()),
+        max_depth: Some(6),
+        max_mappings: Some(2),
+        ..ParseLimits::default()
+    };
+    let rule = parse_rule_file_with_limits(RULES, &limits).expect("within limits");
+    assert_eq!(rule, parse_rule_file(RULES).unwrap());
+    assert!(parse_rule_file_with_limits(RULES, &ParseLimits::default()).is_ok());
+}
+
+#[test]
+fn rules_over_a_limit_name_it() {
+    let cases = [
+        (
+            ParseLimits {
+                max_bytes: Some(RULES.len() - 1),
+                ..ParseLimits::default()
+            },
+            "max_bytes",
+        ),
+        (
+            ParseLimits {
+                max_depth: Some(5),
+                ..ParseLimits::default()
+            },
+            "max_depth",
+        ),
+        (
+            ParseLimits {
+                max_mappings: Some(1),
+                ..ParseLimits::default()
+            },
+            "max_mappings",
+        ),
+    ];
+    for (limits, name) in cases {
+        let err = parse_rule_file_with_limits(RULES, &limits).unwrap_err();
+        assert_eq!(err.code, ErrorCode::LimitExceeded, "{}", name);
+        assert_eq!(ParseLimits::exceeded(&err.message), Some(name), "{}", err.message);
+    }
+}
+
+#[test]
+fn mapping_groups_count_toward_max_mappings() {
+    let yaml = r#"
+version: 1
+input:
+  format: json
+  json: {}
+mapping_groups:
+  ids:
+    - { target: "id", source: "id" }
+  names:
+    - { target: "name", source: "name" }
+    - { target: "nick", source: "nick" }
+"#;
+    let limits = ParseLimits {
+        max_mappings: Some(2),
+        ..ParseLimits::default()
+    };
+    let err = parse_rule_file_with_limits(yaml, &limits).unwrap_err();
+    assert_eq!(err.message, "max_mappings exceeded: rules have 3 mappings, more than 2");
+}
+
+#[test]
+fn deeply_nested_rules_are_refused_without_overflowing() {
+    let yaml = format!("version: 1\nmeta: {}{}\n", "[".repeat(10_000), "]".repeat(10_000));
+    let limits = ParseLimits {
+        max_depth: Some(32),
+        ..ParseLimits::default()
+    };
+    let err = parse_rule_file_with_limits(&yaml, &limits).unwrap_err();
+    assert_eq!(err.code, ErrorCode::RulesParseFailed, "{}", err.message);
+}
+
+#[test]
+fn max_depth_over_the_yaml_recursion_limit_acts_as_the_limit() {
+    let nested = |depth: usize| {
+        format!(
+            "{}mappings:\n  - target: \"a\"\n    value: {}{}\n",
+            "version: 1\ninput:\n  format: json\n  json: {}\n",
+            "[".repeat(depth),
+            "]".repeat(depth)
+        )
+    };
+    let limits = ParseLimits {
+        max_depth: Some(1_000),
+        ..ParseLimits::default()
+    };
+    assert!(parse_rule_file_with_limits(&nested(100), &limits).is_ok());
+    let err = parse_rule_file_with_limits(&nested(200), &limits).unwrap_err();
+    assert_eq!(err.code, ErrorCode::RulesParseFailed, "{}", err.message);
+}
+
+#[test]
+fn malformed_rules_are_parse_errors() {
+    let limits = ParseLimits {
+        max_depth: Some(8),
+        ..ParseLimits::default()
+    };
+    let err = parse_rule_file_with_limits("version: [1\n", &limits).unwrap_err();
+    assert_eq!(err.code, ErrorCode::RulesParseFailed);
+    assert!(err.message.starts_with("failed to parse rules: "), "{}", err.message);
+    assert!(ParseLimits::exceeded(&err.message).is_none());
+}
+
+#[test]
+fn input_text_over_max_bytes_or_max_depth_is_invalid_input() {
+    let rule = parse_rule_file(RULES).unwrap();
+    let input = r#"[{ "id": 1, "name": { "a": { "b": 1 } } }]"#;
+
+    let limits = ParseLimits {
+        max_bytes: Some(input.len() - 1),
+        ..ParseLimits::default()
+    };
+    let err = transform_stream_with_limits(&rule, input, None, &limits).err().unwrap();
+    assert_eq!(err.kind, TransformErrorKind::InvalidInput);
+    assert_eq!(ParseLimits::exceeded(&err.message), Some("max_bytes"));
+
+    let limits = ParseLimits {
+        max_depth: Some(3),
+        ..ParseLimits::default()
+    };
+    let err = transform_stream_with_limits(&rule, input, None, &limits).err().unwrap();
+    assert_eq!(err.kind, TransformErrorKind::InvalidInput);
+    assert_eq!(ParseLimits::exceeded(&err.message), Some("max_depth"));
+
+    let ndjson_rule = parse_rule_file(&rules_with_format("ndjson")).unwrap();
+    let input = "{\"id\":1}\n{\"id\":{\"a\":[1]}}\n";
+    let limits = ParseLimits {
+        max_depth: Some(2),
+        ..ParseLimits::default()
+    };
+    let err = transform_stream_with_limits(&ndjson_rule, input, None, &limits)
+        .err()
+        .unwrap();
+    assert_eq!(ParseLimits::exceeded(&err.message), Some("max_depth"));
+    assert_eq!(err.line, Some(2));
+}
+
+#[test]
+fn streams_stop_after_max_records() {
+    let rule = parse_rule_file(&rules_with_format("csv\n  csv: {}")).unwrap();
+    let input = "id,name\n1,a\n2,b\n3,c\n";
+    let limits = ParseLimits {
+        max_records: Some(3),
+        ..ParseLimits::default()
+    };
+    let items: Vec<_> = transform_stream_with_limits(&rule, input, None, &limits)
+        .unwrap()
+        .collect();
+    assert!(items.iter().all(Result::is_ok));
+    assert_eq!(items.len(), 3);
+
+    let limits = ParseLimits {
+        max_records: Some(2),
+        ..ParseLimits::default()
+    };
+    let mut stream = transform_stream_with_limits(&rule, input, None, &limits).unwrap();
+    assert!(stream.next().unwrap().is_ok());
+    assert!(stream.next().unwrap().is_ok());
+    let err = stream.next().unwrap().unwrap_err();
+    assert_eq!(err.kind, TransformErrorKind::InvalidInput);
+    assert_eq!(err.message, "max_records exceeded: input has more than 2 records");
+    assert_eq!(err.record_index, Some(2));
+    assert_eq!(err.line, Some(4));
+    assert!(stream.next().is_none());
+}
+
+#[test]
+fn large_records_abort_the_stream_whatever_on_error_says() {
+    let yaml = format!("{}on_error: skip\n", RULES);
+    let rule = parse_rule_file(&yaml).unwrap();
+    let input = r#"[{ "id": 1 }, { "id": 2, "name": "a long enough name" }]"#;
+    let limits = ParseLimits {
+        max_record_bytes: Some(20),
+        ..ParseLimits::default()
+    };
+    let mut stream = transform_stream_with_limits(&rule, input, None, &limits).unwrap();
+    assert!(stream.next().unwrap().is_ok());
+    let err = stream.next().unwrap().unwrap_err();
+    assert_eq!(ParseLimits::exceeded(&err.message), Some("max_record_bytes"));
+    assert_eq!(err.record_index, Some(1));
+}
@@ -0,0 +1,141 @@
+use serde_json::json;
+use transform_rules::path::{
+    format_path, get_path, get_path_mut, parse_path, remove_path, set_path, PathError, PathToken,
+};
+
+const KEY_CHARS: [char; 10] = ['a', 'b', '1', '_', ' ', '.', '"', '\'', '\\', ']'];
+
+// Small xorshift generator so the property test stays deterministic without extra deps.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+}
+
+// Only keys the path syntax can express: non-empty, no '[' and no ']' inside a quoted key.
+fn random_key(rng: &mut Rng) -> String {
+    let len = 1 + rng.below(4) as usize;
+    let key: String = (0..len)
+        .map(|_| KEY_CHARS[rng.below(KEY_CHARS.len() as u64) as usize])
+        .collect();
+    if key.contains('.') {
+        key.replace(']', "")
+    } else {
+        key
+    }
+}
+
+fn random_tokens(rng: &mut Rng) -> Vec<PathToken> {
+    let len = 1 + rng.below(5) as usize;
+    (0..len)
+        .map(|_| {
+            if rng.below(3) == 0 {
+                PathToken::Index(rng.below(20) as usize)
+            } else {
+                PathToken::Key(random_key(rng))
+            }
+        })
+        .collect()
+}
+
+fn key(value: &str) -> PathToken {
+    PathToken::Key(value.to_string())
+}
+
+#[test]
+fn format_path_round_trips_random_tokens() {
+    let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+    for _ in 0..2000 {
+        let tokens = random_tokens(&mut rng);
+        let path = format_path(&tokens);
+        let parsed = parse_path(&path)
+            .unwrap_or_else(|err| panic!("failed to parse {:?} from {:?}: {}", path, tokens, err));
+        assert_eq!(parsed, tokens, "path {:?}", path);
+        assert_eq!(format_path(&parsed), path);
+    }
+}
+
+#[test]
+fn parse_path_pins_empty_segment_handling() {
+    assert_eq!(parse_path(""), Err(PathError::Empty));
+    assert_eq!(parse_path(".a"), Err(PathError::EmptyKey));
+    assert_eq!(parse_path("a..b"), Err(PathError::EmptyKey));
+    assert_eq!(parse_path("a."), Err(PathError::InvalidSyntax));
+    assert_eq!(parse_path(r#"a[""]"#), Err(PathError::EmptyKey));
+    assert_eq!(parse_path("a['']"), Err(PathError::EmptyKey));
+    assert_eq!(parse_path("a[]"), Err(PathError::InvalidSyntax));
+    assert_eq!(format_path(&[key("a"), key("")]), r#"a[""]"#);
+}
+
+#[test]
+fn parse_path_accepts_quoted_and_indexed_segments() {
+    assert_eq!(
+        parse_path(r#"[0]['a.b'].c[2]["d\"e"]"#).unwrap(),
+        vec![
+            PathToken::Index(0),
+            key("a.b"),
+            key("c"),
+            PathToken::Index(2),
+            key("d\"e"),
+        ]
+    );
+    assert_eq!(parse_path("a[x]"), Err(PathError::InvalidSyntax));
+    assert_eq!(parse_path(r#"a["b\n"]"#), Err(PathError::InvalidEscape));
+    assert_eq!(PathError::EmptyKey.to_string(), "path segment is empty");
+}
+
+#[test]
+fn set_path_creates_containers_and_reports_conflicts() {
+    let mut value = json!({});
+    set_path(&mut value, &parse_path("a.b").unwrap(), json!(1)).unwrap();
+    set_path(&mut value, &parse_path("a.list[2][0]").unwrap(), json!("x")).unwrap();
+    set_path(&mut value, &parse_path("a.list[0].id").unwrap(), json!(5)).unwrap();
+    assert_eq!(
+        value,
+        json!({ "a": { "b": 1, "list": [{ "id": 5 }, null, ["x"]] } })
+    );
+
+    assert_eq!(
+        set_path(&mut value, &parse_path("a.b.c").unwrap(), json!(1)),
+        Err(PathError::Conflict)
+    );
+    assert_eq!(
+        set_path(&mut value, &parse_path("a[0]").unwrap(), json!(1)),
+        Err(PathError::Conflict)
+    );
+    assert_eq!(set_path(&mut value, &[], json!(1)), Err(PathError::Empty));
+
+    let mut value = json!({ "a": null });
+    assert_eq!(
+        set_path(&mut value, &parse_path("a.b").unwrap(), json!(1)),
+        Err(PathError::Conflict)
+    );
+}
+
+#[test]
+fn get_and_remove_path() {
+    let mut value = json!({ "a": { "b": [1, { "c": true }] } });
+    let tokens = parse_path("a.b[1].c").unwrap();
+    assert_eq!(get_path(&value, &tokens), Some(&json!(true)));
+    assert_eq!(get_path(&value, &parse_path("a.b.c").unwrap()), None);
+
+    *get_path_mut(&mut value, &tokens).unwrap() = json!(false);
+    assert_eq!(remove_path(&mut value, &tokens), Some(json!(false)));
+    assert_eq!(remove_path(&mut value, &tokens), None);
+    assert_eq!(
+        remove_path(&mut value, &parse_path("a.b[0]").unwrap()),
+        Some(json!(1))
+    );
+    assert_eq!(remove_path(&mut value, &parse_path("a.b[5]").unwrap()), None);
+    assert_eq!(remove_path(&mut value, &[]), None);
+    assert_eq!(value, json!({ "a": { "b": [{}] } }));
+}
@@ -35,6 +35,7 @@ fn transform_kind_to_str(kind: &TransformErrorKind) -> &'static str {
         TransformErrorKind::MissingRequired => "MissingRequired",
         TransformErrorKind::TypeCastFailed => "TypeCastFailed",
         TransformErrorKind::ExprError => "ExprError",
+        TransformErrorKind::Degraded => "Degraded",
     }
 }
 
@@ -0,0 +1,107 @@
+use transform_rules::{parse_rule_file, transform_stream, transform_with_warnings, TransformErrorKind};
+
+#[test]
+fn csv_errors_carry_record_index_and_source_line() {
+    let rule = parse_rule_file(
+        r#"
+version: 1
+input:
+  format: csv
+  csv:
+    has_header: true
+mappings:
+  - target: "id"
+    source: "id"
+    type: "int"
+  - target: "note"
+    source: "note"
+"#,
+    )
+    .expect("failed to parse rules");
+    // The quoted note spans two lines, so the failing record starts on line 4.
+    let input = "id,note\n1,\"multi\nline\"\nx,bad\n";
+    let err = transform_with_warnings(&rule, input, None).unwrap_err();
+    assert_eq!(err.kind, TransformErrorKind::TypeCastFailed);
+    assert_eq!(err.record_index, Some(1));
+    assert_eq!(err.line, Some(4));
+}
+
+#[test]
+fn csv_column_type_failures_name_the_column_and_line() {
+    let rule = parse_rule_file(
+        r#"
+version: 1
+input:
+  format: csv
+  csv:
+    has_header: false
+    columns:
+      - { name: "id", type: "int" }
+      - { name: "active", type: "bool" }
+on_error: skip
+mappings:
+  - target: "id"
+    source: "id"
+"#,
+    )
+    .expect("failed to parse rules");
+    let input = "1,true\n2,maybe\n3,false\n";
+    let items: Vec<_> = transform_stream(&rule, input, None)
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let outputs: Vec<_> = items.iter().filter_map(|item| item.output.clone()).collect();
+    assert_eq!(outputs, vec![serde_json::json!({ "id": 1 }), serde_json::json!({ "id": 3 })]);
+
+    let err = &items[1].errors[0];
+    assert_eq!(err.kind, TransformErrorKind::TypeCastFailed);
+    assert_eq!(err.message, "csv column `active` is not a valid bool: \"maybe\"");
+    assert_eq!(err.record_index, Some(1));
+    assert_eq!(err.line, Some(2));
+}
+
+#[test]
+fn flattened_warnings_keep_their_record_index() {
+    let rule = parse_rule_file(
+        r#"
+version: 1
+input:
+  format: json
+mappings:
+  - target: "name"
+    source: "name"
+    when:
+      op: "=="
+      args: [ { op: "lowercase", args: [ { ref: "input.flag" } ] }, "yes" ]
+"#,
+    )
+    .expect("failed to parse rules");
+    let input = r#"[{ "name": "a", "flag": "yes" }, { "name": "b", "flag": 1 }, { "name": "c", "flag": 2 }]"#;
+    let (_, warnings) = transform_with_warnings(&rule, input, None).unwrap();
+    let indexes: Vec<_> = warnings.iter().map(|warning| warning.record_index).collect();
+    assert_eq!(indexes, vec![Some(1), Some(2)]);
+    assert!(warnings.iter().all(|warning| warning.line.is_none()));
+}
+
+#[test]
+fn ndjson_errors_report_the_record_line() {
+    let rule = parse_rule_file(
+        r#"
+version: 1
+input:
+  format: ndjson
+mappings:
+  - target: "id"
+    source: "id"
+    required: true
+"#,
+    )
+    .expect("failed to parse rules");
+    let input = "{\"id\":1}\n\n{\"name\":\"x\"}\n";
+    let mut stream = transform_stream(&rule, input, None).unwrap();
+    assert!(stream.next().unwrap().is_ok());
+    let err = stream.next().unwrap().unwrap_err();
+    assert_eq!(err.kind, TransformErrorKind::MissingRequired);
+    assert_eq!(err.record_index, Some(1));
+    assert_eq!(err.line, Some(3));
+}
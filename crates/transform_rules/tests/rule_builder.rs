@@ -0,0 +1,95 @@
+use std::fs;
+use std::path::Path;
+
+use serde_json::json;
+use transform_rules::{
+    parse_rule_file, transform, ErrorCode, Expr, InputFormat, MappingBuilder, OnError,
+    RuleFileBuilder,
+};
+
+#[test]
+fn built_rules_round_trip_through_yaml() {
+    let rule = RuleFileBuilder::new(InputFormat::Json)
+        .records_path("items")
+        .on_error(OnError::Skip)
+        .mapping(MappingBuilder::target("id").source("input.id").required(true))
+        .mapping(
+            MappingBuilder::target("name").expr(Expr::op(
+                "concat",
+                vec![Expr::reference("input.first"), Expr::literal(" "), Expr::reference("input.last")],
+            )),
+        )
+        .mapping(MappingBuilder::target("price").source("input.price").value_type("float"))
+        .mapping(MappingBuilder::target("code").value(json!("007")))
+        .build()
+        .expect("rule should be valid");
+
+    let yaml = rule.to_yaml();
+    let parsed = parse_rule_file(&yaml).expect("generated yaml should parse");
+    assert_eq!(parsed, rule);
+
+    let input = r#"{ "items": [{ "id": 1, "first": "Ada", "last": "Lovelace", "price": "2.5" }] }"#;
+    let output = transform(&parsed, input, None).expect("transform failed");
+    assert_eq!(
+        output,
+        json!([{ "id": 1, "name": "Ada Lovelace", "price": 2.5, "code": "007" }])
+    );
+}
+
+#[test]
+fn build_reports_validation_errors() {
+    let errors = RuleFileBuilder::new(InputFormat::Csv)
+        .records_path("items")
+        .mapping(MappingBuilder::target("id").expr(Expr::op("no_such_op", Vec::new())))
+        .build()
+        .unwrap_err();
+    assert!(errors.iter().any(|err| err.code == ErrorCode::UnknownOp));
+}
+
+#[test]
+fn mapping_defaults_and_groups_survive_to_yaml() {
+    let yaml = r#"
+version: 1
+input:
+  format: json
+mapping_defaults:
+  required: true
+  when: { op: "exists", args: [ { ref: "input.id" } ] }
+group_order: ["b"]
+mapping_groups:
+  a:
+    - target: "a"
+      source: "input.a"
+      when: null
+      required: false
+  b:
+    - target: "b"
+      source: "input.b"
+"#;
+    let rule = parse_rule_file(yaml).expect("failed to parse rules");
+    let round_trip = parse_rule_file(&rule.to_yaml()).expect("generated yaml should parse");
+    assert_eq!(round_trip, rule);
+    let targets: Vec<_> = round_trip.mappings.iter().map(|m| m.target.as_str()).collect();
+    assert_eq!(targets, vec!["b", "a"]);
+    assert!(round_trip.mappings[1].when.is_none());
+}
+
+#[test]
+fn fixture_rules_round_trip_through_yaml() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures");
+    let mut checked = 0;
+    for entry in fs::read_dir(&fixtures).unwrap() {
+        let path = entry.unwrap().path().join("rules.yaml");
+        let Ok(yaml) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let Ok(rule) = parse_rule_file(&yaml) else {
+            continue;
+        };
+        let round_trip = parse_rule_file(&rule.to_yaml())
+            .unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+        assert_eq!(round_trip, rule, "{}", path.display());
+        checked += 1;
+    }
+    assert!(checked > 50);
+}
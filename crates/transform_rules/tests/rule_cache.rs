@@ -0,0 +1,60 @@
+use transform_rules::{clear_rule_cache, parse_rule_file, rule_cache_stats, RuleCacheStats};
+
+// One test on purpose: the cache and its counters are process-wide.
+#[test]
+fn parse_rule_file_is_cached_by_content() {
+    clear_rule_cache();
+    assert_eq!(rule_cache_stats(), RuleCacheStats::default());
+
+    let yaml = "version: 1\ninput:\n  format: json\n  json: {}\nmappings:\n  - target: \"id\"\n    source: \"id\"\n";
+    let first = parse_rule_file(yaml).expect("failed to parse rules");
+    // Same content in a different allocation.
+    let copy = String::from(yaml);
+    let second = parse_rule_file(&copy).expect("failed to parse rules");
+    assert_eq!(first.mappings[0].target, second.mappings[0].target);
+    assert_eq!(
+        rule_cache_stats(),
+        RuleCacheStats { hits: 1, misses: 1, entries: 1 }
+    );
+
+    let other = yaml.replace("\"id\"", "\"name\"");
+    let rule = parse_rule_file(&other).expect("failed to parse rules");
+    assert_eq!(rule.mappings[0].target, "name");
+    assert_eq!(
+        rule_cache_stats(),
+        RuleCacheStats { hits: 1, misses: 2, entries: 2 }
+    );
+
+    // Parse failures are not cached.
+    assert!(parse_rule_file("mappings: [").is_err());
+    assert!(parse_rule_file("mappings: [").is_err());
+    assert_eq!(rule_cache_stats().entries, 2);
+
+    clear_rule_cache();
+    assert_eq!(rule_cache_stats(), RuleCacheStats::default());
+    parse_rule_file(yaml).expect("failed to parse rules");
+    assert_eq!(
+        rule_cache_stats(),
+        RuleCacheStats { hits: 0, misses: 1, entries: 1 }
+    );
+
+    // Same length and same 64-bit FNV-1a hash, so both land on one key.
+    clear_rule_cache();
+    let colliding = |target: &str| {
+        format!(
+            concat!(
+                "version: 1\ninput:\n  format: json\n  json: {{}}\n",
+                "mappings:\n  - target: \"{}\"\n    source: \"id\"\n"
+            ),
+            target
+        )
+    };
+    for target in ["ncmdbjlbpbkcohli", "nblljekdlomnldag", "ncmdbjlbpbkcohli"] {
+        let rule = parse_rule_file(&colliding(target)).expect("failed to parse rules");
+        assert_eq!(rule.mappings[0].target, target);
+    }
+    assert_eq!(
+        rule_cache_stats(),
+        RuleCacheStats { hits: 0, misses: 3, entries: 1 }
+    );
+}
@@ -0,0 +1,140 @@
+use transform_rules::{
+    parse_rule_file, validate_rule_file, validate_rule_file_full, ErrorCode, WarningCode,
+};
+
+fn warnings_of(yaml: &str) -> Vec<(WarningCode, String, Option<usize>)> {
+    let rule = parse_rule_file(yaml).expect("parse rules");
+    let report = validate_rule_file_full(&rule, Some(yaml));
+    assert!(report.is_valid(), "{:?}", report.errors);
+    report
+        .warnings
+        .into_iter()
+        .map(|warning| {
+            let line = warning.location.map(|location| location.line);
+            (warning.code, warning.path.unwrap_or_default(), line)
+        })
+        .collect()
+}
+
+#[test]
+fn clean_rules_have_no_warnings() {
+    let yaml = r#"version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  - target: "id"
+    source: "id"
+  - target: "name"
+    expr: { op: "coalesce", args: [ { ref: "input.name" }, "unknown" ] }
+"#;
+    assert!(warnings_of(yaml).is_empty());
+}
+
+#[test]
+fn suspicious_rules_are_warned_at_their_yaml_location() {
+    let yaml = r#"version: 1
+input:
+  format: json
+  json: {}
+record_when: true
+mappings:
+  - target: "id"
+    source: "id"
+    required: true
+    default: 0
+  - target: "name"
+    expr: { op: "coalesce", args: [ { ref: "input.name" } ] }
+  - target: "nick"
+    expr:
+      chain:
+        - { ref: "input.nick" }
+        - { op: "coalesce" }
+  - target: "seen"
+    expr: { op: "to_unixtime", args: [ { ref: "input.seen" } ] }
+  - target: "day"
+    expr: { op: "date_format", args: [ { ref: "input.day" }, "%Y-%m-%d", "UTC" ] }
+"#;
+    assert_eq!(
+        warnings_of(yaml),
+        vec![
+            (WarningCode::ConstantWhen, "record_when".to_string(), Some(5)),
+            (WarningCode::RequiredWithDefault, "mappings[0].required".to_string(), Some(9)),
+            (WarningCode::SingleArgCoalesce, "mappings[1].expr".to_string(), Some(12)),
+            (WarningCode::SingleArgCoalesce, "mappings[2].expr.chain[1]".to_string(), Some(17)),
+            (WarningCode::ToUnixtimeAutoParse, "mappings[3].expr".to_string(), Some(19)),
+            (
+                WarningCode::DateFormatMissingInputFormat,
+                "mappings[4].expr.args[2]".to_string(),
+                Some(21)
+            ),
+        ]
+    );
+}
+
+#[test]
+fn mappings_before_an_unconditional_duplicate_are_overwritten() {
+    let yaml = r#"version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  - target: "status"
+    source: "status"
+    when: { op: "==", args: [ { ref: "input.kind" }, "a" ] }
+  - target: "status"
+    value: "other"
+    when: { op: "==", args: [ { ref: "input.kind" }, "b" ] }
+  - target: "status"
+    value: "fallback"
+    when: true
+"#;
+    assert_eq!(
+        warnings_of(yaml),
+        vec![
+            (
+                WarningCode::ConditionalDuplicateTarget,
+                "mappings[1].target".to_string(),
+                Some(9)
+            ),
+            (WarningCode::ConstantWhen, "mappings[2].when".to_string(), Some(14)),
+            (WarningCode::OverwrittenMapping, "mappings[0].target".to_string(), Some(6)),
+            (WarningCode::OverwrittenMapping, "mappings[1].target".to_string(), Some(9)),
+        ]
+    );
+}
+
+#[test]
+fn warnings_do_not_fail_validation() {
+    let yaml = r#"version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  - target: "id"
+    source: "id"
+    when: false
+  - target: "name"
+    source: "name"
+"#;
+    let rule = parse_rule_file(yaml).expect("parse rules");
+    assert!(validate_rule_file(&rule).is_ok());
+    let report = validate_rule_file_full(&rule, Some(yaml));
+    assert!(report.is_valid());
+    assert_eq!(report.warnings[0].message, "condition is always false");
+    assert_eq!(report.warnings[0].code.as_str(), "constant_when");
+
+    let yaml = yaml.replace("source: \"name\"", "expr: { op: \"nope\", args: [ 1 ] }");
+    let rule = parse_rule_file(&yaml).expect("parse rules");
+    let mut report = validate_rule_file_full(&rule, Some(&yaml));
+    assert_eq!(report.errors[0].code, ErrorCode::UnknownOp);
+    assert_eq!(report.warnings.len(), 1);
+    assert!(report.clone().into_result().is_err());
+
+    report.allow_unknown_ops();
+    assert!(report.is_valid());
+    let codes: Vec<_> = report.warnings.iter().map(|warning| warning.code).collect();
+    assert_eq!(codes, [WarningCode::UnknownOp, WarningCode::ConstantWhen]);
+    assert_eq!(report.warnings[0].path.as_deref(), Some("mappings[1].expr.op"));
+    assert!(report.warnings[0].location.is_some());
+}
@@ -0,0 +1,42 @@
+use transform_rules::{environment_info, run_check, run_self_checks, VERSION};
+
+#[test]
+fn self_checks_pass() {
+    let checks = run_self_checks();
+
+    assert!(!checks.is_empty());
+    for check in &checks {
+        assert!(check.passed, "{} failed: {:?}", check.name, check.message);
+    }
+}
+
+#[test]
+fn failing_check_reports_message() {
+    let result = run_check("custom", || Err("boom".to_string()));
+
+    assert!(!result.passed);
+    assert_eq!(result.message.as_deref(), Some("boom"));
+    let value = result.to_json();
+    assert_eq!(value["name"], "custom");
+    assert_eq!(value["status"], "fail");
+    assert_eq!(value["message"], "boom");
+}
+
+#[test]
+fn environment_reports_version_and_caches() {
+    let environment = environment_info();
+
+    assert_eq!(environment.version, VERSION);
+    let names: Vec<_> = environment
+        .cache_capacities
+        .iter()
+        .map(|(name, _)| *name)
+        .collect();
+    assert_eq!(names, vec!["rules", "regex"]);
+    let expected: &[&str] = if cfg!(feature = "fault-injection") {
+        &["fault-injection"]
+    } else {
+        &[]
+    };
+    assert_eq!(environment.features, expected);
+}
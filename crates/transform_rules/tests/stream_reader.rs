@@ -0,0 +1,268 @@
+use std::fs;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use serde_json::Value;
+use transform_rules::{
+    parse_rule_file, transform_stream, transform_stream_from_reader, transform_with_warnings,
+    RuleFile, TransformError, TransformErrorKind,
+};
+
+fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+}
+
+fn rule(yaml: &str) -> RuleFile {
+    parse_rule_file(yaml).expect("failed to parse rules")
+}
+
+fn collect_from_reader(
+    rule: &RuleFile,
+    input: &[u8],
+    context: Option<&Value>,
+) -> Result<(Value, Vec<String>), TransformError> {
+    let mut outputs = Vec::new();
+    let mut warnings = Vec::new();
+    for item in transform_stream_from_reader(rule, input, context)? {
+        let item = item?;
+        warnings.extend(item.warnings.into_iter().map(|warning| warning.message));
+        outputs.extend(item.output);
+    }
+    Ok((Value::Array(outputs), warnings))
+}
+
+// Endless CSV rows; counts how many bytes the stream actually pulled.
+struct EndlessCsv {
+    header_sent: bool,
+    row: usize,
+    pending: Vec<u8>,
+    bytes_read: Arc<AtomicUsize>,
+}
+
+impl Read for EndlessCsv {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pending.is_empty() {
+            if self.header_sent {
+                self.pending = format!("{},name{}\n", self.row, self.row).into_bytes();
+                self.row += 1;
+            } else {
+                self.pending = b"id,name\n".to_vec();
+                self.header_sent = true;
+            }
+        }
+        let len = buf.len().min(self.pending.len());
+        buf[..len].copy_from_slice(&self.pending[..len]);
+        self.pending.drain(..len);
+        self.bytes_read.fetch_add(len, Ordering::SeqCst);
+        Ok(len)
+    }
+}
+
+const CSV_RULES: &str = r#"
+version: 1
+input:
+  format: csv
+  csv:
+    has_header: true
+mappings:
+  - target: "id"
+    source: "id"
+    type: "int"
+  - target: "name"
+    source: "name"
+"#;
+
+const JSON_RULES: &str = r#"
+version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  - target: "id"
+    source: "id"
+"#;
+
+#[test]
+fn reader_matches_string_stream_for_golden_fixtures() {
+    let mut entries: Vec<_> = fs::read_dir(fixtures_dir())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            let name = path.file_name().unwrap().to_string_lossy();
+            name.starts_with('t') || name.starts_with('r')
+        })
+        .collect();
+    entries.sort();
+    assert!(!entries.is_empty());
+
+    for dir in entries {
+        let rule = rule(&fs::read_to_string(dir.join("rules.yaml")).unwrap());
+        let input = ["input.json", "input.csv", "input.ndjson"]
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.exists())
+            .map(|path| fs::read_to_string(path).unwrap())
+            .unwrap();
+        let context = fs::read_to_string(dir.join("context.json"))
+            .ok()
+            .map(|text| serde_json::from_str::<Value>(&text).unwrap());
+
+        let expected = transform_with_warnings(&rule, &input, context.as_ref());
+        let actual = collect_from_reader(&rule, input.as_bytes(), context.as_ref());
+        match (expected, actual) {
+            (Ok((output, warnings)), Ok((reader_output, reader_warnings))) => {
+                let warnings: Vec<_> = warnings.into_iter().map(|w| w.message).collect();
+                assert_eq!(reader_output, output, "output mismatch for {}", dir.display());
+                assert_eq!(reader_warnings, warnings, "warning mismatch for {}", dir.display());
+            }
+            (Err(err), Err(reader_err)) => {
+                assert_eq!(reader_err.kind, err.kind, "{}", dir.display());
+                assert_eq!(reader_err.path, err.path, "{}", dir.display());
+                assert_eq!(reader_err.message, err.message, "{}", dir.display());
+            }
+            (expected, actual) => panic!(
+                "result mismatch for {}: {:?} vs {:?}",
+                dir.display(),
+                expected.map(|_| ()),
+                actual.map(|_| ())
+            ),
+        }
+    }
+}
+
+#[test]
+fn reader_streams_json_array_elements() {
+    let rule = rule(JSON_RULES);
+    let input = b" \n[ {\"id\": \"a,]\"}, {\"id\": [1, {\"x\": \"}\"}]} ,{\"id\":3}]\n";
+    let (output, _) = collect_from_reader(&rule, input, None).unwrap();
+    assert_eq!(
+        output,
+        serde_json::json!([{ "id": "a,]" }, { "id": [1, { "x": "}" }] }, { "id": 3 }])
+    );
+
+    let (output, _) = collect_from_reader(&rule, b"[]", None).unwrap();
+    assert_eq!(output, serde_json::json!([]));
+
+    for input in [&b"[{\"id\":1},]"[..], b"[{\"id\":1}] x", b"[{\"id\":1}", b"[,]"] {
+        let err = collect_from_reader(&rule, input, None).unwrap_err();
+        assert_eq!(err.kind, TransformErrorKind::InvalidInput);
+        assert!(
+            err.message.starts_with("failed to parse JSON input"),
+            "{}",
+            err.message
+        );
+    }
+}
+
+#[test]
+fn reader_reports_invalid_utf8_with_row() {
+    let rule_csv = rule(CSV_RULES);
+    let input = b"id,name\n1,a\n2,caf\xe9\n3,c\n";
+    let mut stream = transform_stream_from_reader(&rule_csv, &input[..], None).unwrap();
+    assert!(stream.next().unwrap().is_ok());
+    let err = stream.next().unwrap().unwrap_err();
+    assert_eq!(err.kind, TransformErrorKind::InvalidInput);
+    assert_eq!(err.message, "invalid UTF-8 in csv record at line 3");
+    assert!(stream.next().is_none());
+
+    let rule_json = rule(JSON_RULES);
+    let input = b"[{\"id\":1},{\"id\":\"caf\xe9\"}]";
+    let mut stream = transform_stream_from_reader(&rule_json, &input[..], None).unwrap();
+    assert!(stream.next().unwrap().is_ok());
+    let err = stream.next().unwrap().unwrap_err();
+    assert_eq!(err.kind, TransformErrorKind::InvalidInput);
+    assert_eq!(err.message, "invalid UTF-8 in JSON record 1 at byte 20");
+}
+
+#[test]
+fn reader_handles_inputs_larger_than_memory_lazily() {
+    let rule = rule(CSV_RULES);
+    let bytes_read = Arc::new(AtomicUsize::new(0));
+    let reader = BufReader::new(EndlessCsv {
+        header_sent: false,
+        row: 0,
+        pending: Vec::new(),
+        bytes_read: bytes_read.clone(),
+    });
+
+    let stream = transform_stream_from_reader(&rule, reader, None).unwrap();
+    let outputs: Vec<Value> = stream
+        .take(50_000)
+        .map(|item| item.unwrap().output.unwrap())
+        .collect();
+    assert_eq!(outputs.len(), 50_000);
+    assert_eq!(outputs[49_999], serde_json::json!({ "id": 49_999, "name": "name49999" }));
+
+    // Dropping the stream stops reading; the endless source was only pulled as far as needed.
+    let read = bytes_read.load(Ordering::SeqCst);
+    assert!(read < 1_000_000, "read {} bytes", read);
+}
+
+#[test]
+fn reader_stream_supports_early_termination() {
+    let rule = rule(JSON_RULES);
+    let input = format!(
+        "[{}]",
+        (0..10_000)
+            .map(|id| format!("{{\"id\":{}}}", id))
+            .collect::<Vec<_>>()
+            .join(",")
+    );
+    let bytes_read = Arc::new(AtomicUsize::new(0));
+    struct Counting<'a>(&'a [u8], Arc<AtomicUsize>);
+    impl Read for Counting<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = self.0.read(buf)?;
+            self.1.fetch_add(len, Ordering::SeqCst);
+            Ok(len)
+        }
+    }
+
+    let reader = BufReader::with_capacity(64, Counting(input.as_bytes(), bytes_read.clone()));
+    let mut stream = transform_stream_from_reader(&rule, reader, None).unwrap();
+    let first = stream.next().unwrap().unwrap();
+    assert_eq!(first.output, Some(serde_json::json!({ "id": 0 })));
+    drop(stream);
+    assert!(bytes_read.load(Ordering::SeqCst) <= 128);
+
+    let from_str: Vec<_> = transform_stream(&rule, &input, None)
+        .unwrap()
+        .map(|item| item.unwrap().output)
+        .collect();
+    assert_eq!(from_str.len(), 10_000);
+}
+
+#[test]
+fn reader_streams_ndjson_lines() {
+    let rule = rule(
+        r#"
+version: 1
+input:
+  format: ndjson
+mappings:
+  - target: "id"
+    source: "id"
+"#,
+    );
+    let (output, _) = collect_from_reader(&rule, b"{\"id\":1}\r\n\n{\"id\":2}", None).unwrap();
+    assert_eq!(output, serde_json::json!([{ "id": 1 }, { "id": 2 }]));
+
+    let (output, _) = collect_from_reader(&rule, b"{\"id\":1}\n", None).unwrap();
+    assert_eq!(output, serde_json::json!([{ "id": 1 }]));
+
+    let mut stream =
+        transform_stream_from_reader(&rule, &b"{\"id\":1}\n[1]\n{\"id\":3}\n"[..], None).unwrap();
+    assert!(stream.next().unwrap().is_ok());
+    let err = stream.next().unwrap().unwrap_err();
+    assert_eq!(err.kind, TransformErrorKind::InvalidInput);
+    assert_eq!(
+        err.message,
+        "failed to parse NDJSON input at line 2: expected a JSON object"
+    );
+    assert!(stream.next().is_none());
+}
+
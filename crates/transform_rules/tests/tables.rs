@@ -0,0 +1,239 @@
+use std::io::{BufReader, Cursor, Read};
+
+use serde_json::{json, Value as JsonValue};
+use transform_rules::{
+    load_table, parse_rule_file, transform, transform_stream, validate_rule_file, LoadedTable,
+    RuleFile, TransformErrorCode, TransformErrorKind,
+};
+
+fn products_rule(tables: &str) -> RuleFile {
+    let yaml = format!(
+        r#"
+version: 1
+input:
+  format: json
+  json: {{}}
+tables:
+{}
+mappings:
+  - target: "sku"
+    source: "input.sku"
+  - target: "price"
+    expr:
+      op: "lookup_first"
+      args: [ {{ ref: "context.products" }}, "sku", {{ ref: "input.sku" }}, "price.amount" ]
+  - target: "matches"
+    expr:
+      op: "lookup"
+      args: [ {{ ref: "context.products" }}, "sku", {{ ref: "input.sku" }} ]
+"#,
+        tables
+    );
+    parse_rule_file(&yaml).expect("failed to parse rules")
+}
+
+fn products() -> Vec<JsonValue> {
+    (0..2_000)
+        .map(|index| {
+            json!({
+                "sku": format!("S{}", index % 1_500),
+                "price": { "amount": index, "currency": "EUR" },
+                "description": "x".repeat(64),
+            })
+        })
+        .chain([json!({ "price": { "amount": -1 } })])
+        .collect()
+}
+
+fn ndjson(records: &[JsonValue]) -> String {
+    records
+        .iter()
+        .map(|record| format!("{}\n", record))
+        .collect()
+}
+
+fn input() -> String {
+    let records: Vec<_> = [0, 7, 499, 1_499, 1_500, 3_000]
+        .iter()
+        .map(|index| json!({ "sku": format!("S{}", index) }))
+        .collect();
+    serde_json::to_string(&records).unwrap()
+}
+
+// Hands out a few bytes per read, as a file bigger than any buffer would.
+struct Trickle<'a>(&'a [u8]);
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = buf.len().min(self.0.len()).min(7);
+        buf[..len].copy_from_slice(&self.0[..len]);
+        self.0 = &self.0[len..];
+        Ok(len)
+    }
+}
+
+#[test]
+fn fields_project_each_record() {
+    let rule = products_rule("  products:\n    fields: [ \"sku\", \"price.amount\" ]");
+    let text = serde_json::to_string(&products()).unwrap();
+    let (table, warnings) = load_table(&rule, "products", Cursor::new(text)).unwrap();
+    assert!(warnings.is_empty(), "{:?}", warnings);
+
+    let LoadedTable::Records(records) = table else {
+        panic!("expected records");
+    };
+    let records = records.as_array().unwrap();
+    assert_eq!(records.len(), 2_001);
+    assert_eq!(records[3], json!({ "sku": "S3", "price": { "amount": 3 } }));
+    assert_eq!(records[2_000], json!({ "price": { "amount": -1 } }));
+}
+
+#[test]
+fn ndjson_tables_are_read_line_by_line() {
+    let rule = products_rule("  products:\n    format: ndjson");
+    let text = format!("\n{}\n", ndjson(&products()));
+    let reader = BufReader::with_capacity(16, Trickle(text.as_bytes()));
+    let (table, _) = load_table(&rule, "products", reader).unwrap();
+    assert_eq!(table.into_records(), JsonValue::Array(products()));
+
+    let text = "{\"sku\": \"S1\"}\n\n{\"sku\": \n";
+    let err = load_table(&rule, "products", Cursor::new(text)).unwrap_err();
+    assert_eq!(err.code, TransformErrorCode::InputParse);
+    assert_eq!(err.path.as_deref(), Some("tables.products"));
+    assert_eq!(err.line, Some(3));
+}
+
+#[test]
+fn csv_tables_are_read_row_by_row() {
+    let rule = products_rule("  products:\n    format: csv\n    key: \"sku\"");
+    let text = "sku,name\nS1,one\nS2,two\n,none\nS1,again\n";
+    let reader = BufReader::with_capacity(8, Trickle(text.as_bytes()));
+    let (table, _) = load_table(&rule, "products", reader).unwrap();
+    let LoadedTable::Indexed(table) = table else {
+        panic!("expected an index");
+    };
+    assert_eq!(table.len(), 3);
+    assert_eq!(
+        table.to_records(),
+        json!([
+            { "sku": "S1", "name": "one" },
+            { "sku": "S2", "name": "two" },
+            { "sku": "S1", "name": "again" }
+        ])
+    );
+}
+
+#[test]
+fn indexed_tables_match_the_records_in_context() {
+    let rule = products_rule(concat!(
+        "  products:\n    format: ndjson\n",
+        "    fields: [ \"sku\", \"price.amount\" ]\n    key: \"sku\"",
+    ));
+    let (table, warnings) =
+        load_table(&rule, "products", Cursor::new(ndjson(&products()))).unwrap();
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    let LoadedTable::Indexed(table) = table else {
+        panic!("expected an index");
+    };
+    assert_eq!(table.name(), "products");
+    assert_eq!(table.len(), 2_000);
+
+    let input = input();
+    let naive = transform(&rule, &input, Some(&json!({ "products": products() }))).unwrap();
+    for threads in [1, 3] {
+        let outputs: Vec<_> = transform_stream(&rule, &input, None)
+            .unwrap()
+            .with_tables(std::slice::from_ref(&table))
+            .with_threads(threads)
+            .map(|item| item.unwrap().output.unwrap())
+            .collect();
+        let naive = naive.as_array().unwrap();
+        assert_eq!(outputs.len(), naive.len());
+        for (indexed, naive) in outputs.iter().zip(naive) {
+            assert_eq!(indexed["sku"], naive["sku"]);
+            assert_eq!(indexed.get("price"), naive.get("price"), "{}", naive);
+        }
+    }
+    let outputs: Vec<_> = transform_stream(&rule, &input, None)
+        .unwrap()
+        .with_tables(&[table])
+        .map(|item| item.unwrap().output.unwrap())
+        .collect();
+    assert_eq!(outputs[0]["price"], 0);
+    assert_eq!(
+        outputs[1]["matches"],
+        json!([
+            { "sku": "S7", "price": { "amount": 7 } },
+            { "sku": "S7", "price": { "amount": 1_507 } }
+        ])
+    );
+    assert_eq!(outputs[3]["price"], 1_499);
+    assert!(outputs[4].get("price").is_none());
+    assert!(outputs[5].get("matches").is_none());
+}
+
+#[test]
+fn undeclared_reads_keep_full_records_with_a_warning() {
+    let yaml = r#"
+version: 1
+input:
+  format: json
+  json: {}
+tables:
+  products:
+    format: ndjson
+    fields: [ "sku", "price.amount" ]
+    key: "sku"
+mappings:
+  - target: "currency"
+    expr:
+      op: "lookup_first"
+      args: [ { ref: "context.products" }, "description", { ref: "input.text" }, "price.currency" ]
+"#;
+    let rule = parse_rule_file(yaml).unwrap();
+    let (table, warnings) =
+        load_table(&rule, "products", Cursor::new(ndjson(&products()))).unwrap();
+    let LoadedTable::Records(records) = table else {
+        panic!("expected records");
+    };
+    assert_eq!(records, JsonValue::Array(products()));
+    let codes: Vec<_> = warnings.iter().map(|warning| warning.code).collect();
+    assert_eq!(codes, [TransformErrorCode::TableFallback; 2]);
+    assert!(warnings
+        .iter()
+        .all(|warning| warning.kind == TransformErrorKind::Degraded));
+    assert!(warnings[0].message.contains("description"), "{}", warnings[0].message);
+    assert!(warnings[1].message.contains("key_path description"), "{}", warnings[1].message);
+    assert_eq!(warnings[1].path.as_deref(), Some("tables.products"));
+
+    // A read outside a lookup keeps the array too.
+    let mut rule = products_rule("  products:\n    format: ndjson\n    key: \"sku\"");
+    rule.mappings[0].source = Some("context.products[0].sku".into());
+    let (table, warnings) =
+        load_table(&rule, "products", Cursor::new(ndjson(&products()))).unwrap();
+    assert!(matches!(table, LoadedTable::Records(_)));
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].message.starts_with("context.products[0].sku is read outside a lookup"));
+}
+
+#[test]
+fn table_fields_and_key_must_be_key_paths() {
+    let rule = products_rule(concat!(
+        "  products:\n    fields: [ \"sku\", \"tags[0]\", \"price..amount\" ]\n",
+        "  prices:\n    fields: []\n    key: \"rows[0].id\"",
+    ));
+    let errors = validate_rule_file(&rule).unwrap_err();
+    let paths: Vec<_> = errors
+        .iter()
+        .map(|err| (err.code.as_str(), err.path.as_deref().unwrap_or_default()))
+        .collect();
+    assert_eq!(
+        paths,
+        [
+            ("InvalidPath", "tables.prices.fields"),
+            ("InvalidPath", "tables.prices.key"),
+            ("InvalidPath", "tables.products.fields[1]"),
+            ("InvalidPath", "tables.products.fields[2]"),
+        ]
+    );
+}
@@ -0,0 +1,259 @@
+use serde_json::json;
+use transform_rules::{
+    input_record, parse_rule_file, transform_record_traced, transform_stream, TraceEvent,
+    TraceEventKind, TraceOptions, TransformErrorKind,
+};
+
+const RULES: &str = r#"
+version: 1
+input:
+  format: json
+mappings:
+  - target: "price_total"
+    expr:
+      chain:
+        - { ref: "input.price" }
+        - { op: "*", args: [{ ref: "input.qty" }] }
+  - target: "name"
+    expr:
+      chain:
+        - { ref: "input.name" }
+        - { op: "trim" }
+"#;
+
+fn run_traced(rules: &str, input: &str, options: TraceOptions) -> Vec<TraceEvent> {
+    let rule = parse_rule_file(rules).expect("failed to parse rules");
+    let mut stream = transform_stream(&rule, input, None)
+        .expect("failed to create stream")
+        .with_trace(options);
+    let mut events = Vec::new();
+    while let Some(item) = stream.next() {
+        events.extend(stream.take_trace());
+        if item.is_err() {
+            break;
+        }
+    }
+    events
+}
+
+#[test]
+fn trace_glob_selects_matching_targets() {
+    let input = r#"[{ "price": 2, "qty": 3, "name": " a " }]"#;
+    let events = run_traced(RULES, input, TraceOptions::new(["price*"]));
+
+    assert!(!events.is_empty());
+    assert!(events.iter().all(|event| event.target == "price_total"));
+
+    let steps: Vec<_> = events
+        .iter()
+        .filter(|event| event.kind == TraceEventKind::Step)
+        .map(|event| (event.step, event.op.as_deref(), event.value.as_deref()))
+        .collect();
+    assert_eq!(steps, vec![(0, None, Some("2")), (1, Some("*"), Some("6"))]);
+
+    let args: Vec<_> = events
+        .iter()
+        .filter(|event| matches!(event.kind, TraceEventKind::Arg(_)))
+        .collect();
+    assert_eq!(args.len(), 1);
+    assert_eq!(args[0].kind, TraceEventKind::Arg(1));
+    assert_eq!(args[0].op.as_deref(), Some("*"));
+    assert_eq!(args[0].value.as_deref(), Some("3"));
+}
+
+#[test]
+fn trace_only_records_changed_values() {
+    let input = r#"[
+        { "price": 1, "qty": 1, "name": "a" },
+        { "price": 1, "qty": 1, "name": " b " },
+        { "price": 1, "qty": 1, "name": "c" }
+    ]"#;
+    let events = run_traced(RULES, input, TraceOptions::new(["name"]));
+
+    assert!(!events.is_empty());
+    assert!(events.iter().all(|event| event.record_index == 1));
+    let last = events.last().expect("trace event");
+    assert_eq!(last.value.as_deref(), Some("\"b\""));
+    assert_eq!(
+        last.to_json(),
+        json!({
+            "record_index": 1,
+            "target": "name",
+            "step": 1,
+            "op": "trim",
+            "kind": "step",
+            "value": "\"b\""
+        })
+    );
+}
+
+#[test]
+fn trace_records_errors() {
+    let input = r#"[
+        { "price": 2, "qty": 2, "name": "a" },
+        { "price": 2, "qty": "x", "name": "a" }
+    ]"#;
+    let events = run_traced(RULES, input, TraceOptions::new(["price_total"]));
+
+    let error = events
+        .iter()
+        .find(|event| event.kind == TraceEventKind::Error)
+        .expect("error event");
+    assert_eq!(error.record_index, 1);
+    assert!(error
+        .value
+        .as_deref()
+        .is_some_and(|value| value.contains("mappings[0].expr.chain[1]")));
+}
+
+#[test]
+fn trace_truncates_large_values() {
+    let long_name = format!(" {} ", "x".repeat(1000));
+    let input = serde_json::to_string(&json!([{ "price": 1, "qty": 1, "name": long_name }]))
+        .expect("serialize input");
+    let events = run_traced(
+        RULES,
+        &input,
+        TraceOptions::new(["name"]).with_max_value_len(16),
+    );
+
+    assert!(!events.is_empty());
+    for event in &events {
+        assert!(event.truncated);
+        let value = event.value.as_deref().expect("value");
+        assert_eq!(value.len(), 16 + "...".len());
+        assert!(value.ends_with("..."));
+    }
+}
+
+#[test]
+fn stream_without_trace_has_no_sink() {
+    let rule = parse_rule_file(RULES).expect("failed to parse rules");
+    let input = r#"[{ "price": 1, "qty": 1, "name": " a " }]"#;
+    let mut stream = transform_stream(&rule, input, None).expect("failed to create stream");
+
+    assert!(!stream.is_tracing());
+    while let Some(item) = stream.next() {
+        item.expect("transform failed");
+        assert!(stream.take_trace().is_empty());
+    }
+}
+
+const RECORD_RULES: &str = r#"
+version: 1
+input:
+  format: json
+record_when: { op: "!=", args: [{ ref: "input.skip" }, true] }
+mappings:
+  - target: "id"
+    source: "id"
+    type: "int"
+  - target: "total"
+    expr:
+      chain:
+        - { ref: "input.price" }
+        - { op: "*", args: [{ ref: "input.qty" }] }
+  - target: "note"
+    source: "note"
+    when: { op: "==", args: [{ ref: "out.total" }, 0] }
+"#;
+
+#[test]
+fn record_trace_shows_every_mapping() {
+    let rule = parse_rule_file(RECORD_RULES).unwrap();
+    let record = json!({ "id": "7", "price": 2, "qty": 3, "note": "n" });
+    let trace = transform_record_traced(&rule, &record, None);
+
+    assert_eq!(trace.record_when, Some(true));
+    assert_eq!(trace.output, Some(json!({ "id": 7, "total": 6 })));
+    assert!(trace.error.is_none());
+
+    let id = &trace.mappings[0];
+    assert_eq!((id.path.as_str(), id.target.as_str()), ("mappings[0]", "id"));
+    assert_eq!(id.when, None);
+    assert_eq!(id.steps[0].value.as_deref(), Some("\"7\""));
+    assert_eq!(id.value.as_deref(), Some("7"));
+    assert!(id.written);
+
+    let total = &trace.mappings[1];
+    let steps: Vec<_> = total
+        .steps
+        .iter()
+        .map(|event| (event.kind.clone(), event.op.as_deref(), event.value.as_deref()))
+        .collect();
+    assert_eq!(
+        steps,
+        vec![
+            (TraceEventKind::Step, None, Some("2")),
+            (TraceEventKind::Arg(1), Some("*"), Some("3")),
+            (TraceEventKind::Step, Some("*"), Some("6")),
+        ]
+    );
+
+    let note = &trace.mappings[2];
+    assert_eq!(note.when, Some(false));
+    assert_eq!(note.value, None);
+    assert!(!note.written);
+    assert_eq!(
+        note.to_json(),
+        json!({
+            "path": "mappings[2]",
+            "target": "note",
+            "when": false,
+            "when_steps": [
+                { "step": 0, "op": "==", "kind": "arg", "arg": 0, "value": "6" },
+                { "step": 0, "op": "==", "kind": "arg", "arg": 1, "value": "0" }
+            ],
+            "steps": [],
+            "value": null,
+            "written": false
+        })
+    );
+}
+
+#[test]
+fn record_trace_keeps_the_failing_step_and_dropped_records() {
+    let rule = parse_rule_file(RECORD_RULES).unwrap();
+    let trace = transform_record_traced(&rule, &json!({ "id": "x", "price": 1, "qty": 1 }), None);
+    assert_eq!(trace.output, None);
+    let error = trace.error.expect("cast error");
+    assert_eq!(error.kind, TransformErrorKind::TypeCastFailed);
+    assert_eq!(error.record_index, None);
+    assert_eq!(trace.mappings.len(), 1);
+    let last = trace.mappings[0].steps.last().unwrap();
+    assert_eq!(last.kind, TraceEventKind::Error);
+    assert!(!trace.mappings[0].written);
+
+    let trace = transform_record_traced(&rule, &json!({ "skip": true }), None);
+    assert_eq!(trace.record_when, Some(false));
+    assert!(trace.mappings.is_empty());
+    assert_eq!(trace.output, None);
+}
+
+#[test]
+fn record_trace_gives_the_size_of_truncated_values() {
+    let rule = parse_rule_file(RECORD_RULES).unwrap();
+    let note = "x".repeat(1000);
+    let record = json!({ "id": 1, "price": 0, "qty": 1, "note": note });
+    let trace = transform_record_traced(&rule, &record, None);
+
+    let note = &trace.mappings[2];
+    assert!(note.truncated);
+    let value = note.value.as_deref().unwrap();
+    assert!(value.ends_with("... (1002 bytes)"), "{}", value);
+    assert_eq!(value.len(), 256 + "... (1002 bytes)".len());
+    assert_eq!(trace.output.unwrap()["note"].as_str().unwrap().len(), 1000);
+}
+
+#[test]
+fn input_record_reads_one_record_of_any_format() {
+    let rule = parse_rule_file(RULES).unwrap();
+    let input = r#"[{ "name": "a" }, { "name": "b" }]"#;
+    assert_eq!(input_record(&rule, input, 1).unwrap(), Some(json!({ "name": "b" })));
+    assert_eq!(input_record(&rule, input, 2).unwrap(), None);
+
+    let ndjson = parse_rule_file(&RULES.replace("format: json", "format: ndjson")).unwrap();
+    let input = "{\"name\":\"a\"}\nnot json\n{\"name\":\"c\"}\n";
+    assert_eq!(input_record(&ndjson, input, 0).unwrap(), Some(json!({ "name": "a" })));
+    assert_eq!(input_record(&ndjson, input, 2).unwrap_err().record_index, Some(1));
+}
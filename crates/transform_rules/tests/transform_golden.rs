@@ -47,6 +47,7 @@ fn transform_kind_to_str(kind: &TransformErrorKind) -> &'static str {
         TransformErrorKind::MissingRequired => "MissingRequired",
         TransformErrorKind::TypeCastFailed => "TypeCastFailed",
         TransformErrorKind::ExprError => "ExprError",
+        TransformErrorKind::Degraded => "Degraded",
     }
 }
 
@@ -329,9 +330,268 @@ fn t26_chain_all_ops() {
     assert_eq!(output, expected);
 }
 
+#[test]
+fn t27_mapping_groups() {
+    let base = fixtures_dir().join("t27_mapping_groups");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_json(&base.join("expected.json"));
+    let output = transform(&rule, &input, None).expect("transform failed");
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn t28_json_ops_object_flatten_options() {
+    let base = fixtures_dir().join("t28_json_ops_object_flatten_options");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_json(&base.join("expected.json"));
+    let output = transform(&rule, &input, None).expect("transform failed");
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn t29_string_ops_concat_nulls() {
+    let base = fixtures_dir().join("t29_string_ops_concat_nulls");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_json(&base.join("expected.json"));
+    let output = transform(&rule, &input, None).expect("transform failed");
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn t30_ndjson_input() {
+    let base = fixtures_dir().join("t30_ndjson_input");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.ndjson"))
+        .unwrap_or_else(|_| panic!("failed to read input.ndjson"));
+    let expected = load_json(&base.join("expected.json"));
+    let output = transform(&rule, &input, None).expect("transform failed");
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn t31_string_ops_substring() {
+    let base = fixtures_dir().join("t31_string_ops_substring");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_json(&base.join("expected.json"));
+    let output = transform(&rule, &input, None).expect("transform failed");
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn t32_string_ops_regex() {
+    let base = fixtures_dir().join("t32_string_ops_regex");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_json(&base.join("expected.json"));
+    let output = transform(&rule, &input, None).expect("transform failed");
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn t33_default_expr() {
+    let base = fixtures_dir().join("t33_default_expr");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let context = load_optional_json(&base.join("context.json"));
+    let expected = load_json(&base.join("expected.json"));
+    let output = transform(&rule, &input, context.as_ref()).expect("transform failed");
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn t34_on_error_skip() {
+    let base = fixtures_dir().join("t34_on_error_skip");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.csv"))
+        .unwrap_or_else(|_| panic!("failed to read input.csv"));
+    let expected = load_json(&base.join("expected.json"));
+    let output = transform(&rule, &input, None).expect("transform failed");
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn t35_group_output() {
+    let base = fixtures_dir().join("t35_group_output");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.csv"))
+        .unwrap_or_else(|_| panic!("failed to read input.csv"));
+    let context = load_optional_json(&base.join("context.json"));
+    let expected = load_json(&base.join("expected.json"));
+    let output = transform(&rule, &input, context.as_ref()).expect("transform failed");
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn t36_conditional_ops() {
+    let base = fixtures_dir().join("t36_conditional_ops");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_json(&base.join("expected.json"));
+    let output = transform(&rule, &input, None).expect("transform failed");
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn t37_array_targets() {
+    let base = fixtures_dir().join("t37_array_targets");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_json(&base.join("expected.json"));
+    let output = transform(&rule, &input, None).expect("transform failed");
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn t38_date_decimal_casts() {
+    let base = fixtures_dir().join("t38_date_decimal_casts");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_json(&base.join("expected.json"));
+    let output = transform(&rule, &input, None).expect("transform failed");
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn t39_deep_merge_arrays() {
+    let base = fixtures_dir().join("t39_deep_merge_arrays");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_json(&base.join("expected.json"));
+    let output = transform(&rule, &input, None).expect("transform failed");
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn t40_format() {
+    let base = fixtures_dir().join("t40_format");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_json(&base.join("expected.json"));
+    let output = transform(&rule, &input, None).expect("transform failed");
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn t41_csv_column_types() {
+    let base = fixtures_dir().join("t41_csv_column_types");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.csv"))
+        .unwrap_or_else(|_| panic!("failed to read input.csv"));
+    let expected = load_json(&base.join("expected.json"));
+    let output = transform(&rule, &input, None).expect("transform failed");
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn t42_constants() {
+    let base = fixtures_dir().join("t42_constants");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let context = load_optional_json(&base.join("context.json"));
+    let expected = load_json(&base.join("expected.json"));
+    let output = transform(&rule, &input, context.as_ref()).expect("transform failed");
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn t43_split_options() {
+    let base = fixtures_dir().join("t43_split_options");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_json(&base.join("expected.json"));
+    let output = transform(&rule, &input, None).expect("transform failed");
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn t44_quoted_key_targets() {
+    let base = fixtures_dir().join("t44_quoted_key_targets");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_json(&base.join("expected.json"));
+    let output = transform(&rule, &input, None).expect("transform failed");
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn t45_typed_keys() {
+    let base = fixtures_dir().join("t45_typed_keys");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_json(&base.join("expected.json"));
+    let output = transform(&rule, &input, None).expect("transform failed");
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn t46_blank_values() {
+    let base = fixtures_dir().join("t46_blank_values");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.csv"))
+        .unwrap_or_else(|_| panic!("failed to read input.csv"));
+    let expected = load_json(&base.join("expected.json"));
+    let output = transform(&rule, &input, None).expect("transform failed");
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn t47_pick_omit_nested_paths() {
+    let base = fixtures_dir().join("t47_pick_omit_nested_paths");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_json(&base.join("expected.json"));
+    let output = transform(&rule, &input, None).expect("transform failed");
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn t48_source_fallback() {
+    let base = fixtures_dir().join("t48_source_fallback");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let context = load_optional_json(&base.join("context.json"));
+    let expected = load_json(&base.join("expected.json"));
+    let output = transform(&rule, &input, context.as_ref()).expect("transform failed");
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn t49_array_target_missing_elements() {
+    let base = fixtures_dir().join("t49_array_target_missing_elements");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let context = load_optional_json(&base.join("context.json"));
+    let expected = load_json(&base.join("expected.json"));
+    let output = transform(&rule, &input, context.as_ref()).expect("transform failed");
+    assert_eq!(output, expected);
+}
+
 #[derive(Debug, serde::Deserialize)]
 struct ExpectedTransformError {
     kind: String,
+    code: String,
     path: Option<String>,
 }
 
@@ -345,6 +605,7 @@ fn r01_float_non_finite() {
 
     let err = transform(&rule, &input, None).expect_err("expected transform error");
     assert_eq!(transform_kind_to_str(&err.kind), expected.kind);
+    assert_eq!(err.code.as_str(), expected.code);
     assert_eq!(err.path, expected.path);
 }
 
@@ -358,6 +619,7 @@ fn r02_json_ops_invalid_path_pick() {
 
     let err = transform(&rule, &input, None).expect_err("expected transform error");
     assert_eq!(transform_kind_to_str(&err.kind), expected.kind);
+    assert_eq!(err.code.as_str(), expected.code);
     assert_eq!(err.path, expected.path);
 }
 
@@ -371,6 +633,7 @@ fn r03_json_ops_non_object() {
 
     let err = transform(&rule, &input, None).expect_err("expected transform error");
     assert_eq!(transform_kind_to_str(&err.kind), expected.kind);
+    assert_eq!(err.code.as_str(), expected.code);
     assert_eq!(err.path, expected.path);
 }
 
@@ -384,6 +647,7 @@ fn r04_json_ops_null_arg() {
 
     let err = transform(&rule, &input, None).expect_err("expected transform error");
     assert_eq!(transform_kind_to_str(&err.kind), expected.kind);
+    assert_eq!(err.code.as_str(), expected.code);
     assert_eq!(err.path, expected.path);
 }
 
@@ -397,6 +661,7 @@ fn r05_json_ops_unflatten_array_index() {
 
     let err = transform(&rule, &input, None).expect_err("expected transform error");
     assert_eq!(transform_kind_to_str(&err.kind), expected.kind);
+    assert_eq!(err.code.as_str(), expected.code);
     assert_eq!(err.path, expected.path);
 }
 
@@ -410,6 +675,7 @@ fn r06_json_ops_flatten_brackets() {
 
     let err = transform(&rule, &input, None).expect_err("expected transform error");
     assert_eq!(transform_kind_to_str(&err.kind), expected.kind);
+    assert_eq!(err.code.as_str(), expected.code);
     assert_eq!(err.path, expected.path);
 }
 
@@ -423,5 +689,327 @@ fn r07_json_ops_flatten_empty_key() {
 
     let err = transform(&rule, &input, None).expect_err("expected transform error");
     assert_eq!(transform_kind_to_str(&err.kind), expected.kind);
+    assert_eq!(err.code.as_str(), expected.code);
+    assert_eq!(err.path, expected.path);
+}
+
+#[test]
+fn r08_json_max_depth() {
+    let base = fixtures_dir().join("r08_json_max_depth");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_expected_error(&base.join("expected_error.json"));
+
+    let err = transform(&rule, &input, None).expect_err("expected transform error");
+    assert_eq!(transform_kind_to_str(&err.kind), expected.kind);
+    assert_eq!(err.code.as_str(), expected.code);
+    assert_eq!(err.path, expected.path);
+    assert!(err.message.contains("maximum nesting depth of 4"));
+    assert!(err.message.contains("depth 5"));
+}
+
+#[test]
+fn json_default_max_depth_rejects_deep_input() {
+    let base = fixtures_dir().join("r08_json_max_depth");
+    let mut rule = load_rule(&base.join("rules.yaml"));
+    rule.input.json.as_mut().expect("json input").max_depth = None;
+    let input = format!(
+        "[{{\"id\": 1, \"meta\": {}{}}}]",
+        "[".repeat(200),
+        "]".repeat(200)
+    );
+
+    let err = transform(&rule, &input, None).expect_err("expected transform error");
+    assert_eq!(transform_kind_to_str(&err.kind), "InvalidInput");
+    assert!(err.message.contains("maximum nesting depth of 128"));
+}
+
+#[test]
+fn r09_json_ops_flatten_separator_collision() {
+    let base = fixtures_dir().join("r09_json_ops_flatten_separator_collision");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_expected_error(&base.join("expected_error.json"));
+
+    let err = transform(&rule, &input, None).expect_err("expected transform error");
+    assert_eq!(transform_kind_to_str(&err.kind), expected.kind);
+    assert_eq!(err.code.as_str(), expected.code);
+    assert_eq!(err.path, expected.path);
+    assert!(err.message.contains("is ambiguous with separator '__'"));
+}
+
+#[test]
+fn r10_json_ops_unflatten_ambiguous_key() {
+    let base = fixtures_dir().join("r10_json_ops_unflatten_ambiguous_key");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_expected_error(&base.join("expected_error.json"));
+
+    let err = transform(&rule, &input, None).expect_err("expected transform error");
+    assert_eq!(transform_kind_to_str(&err.kind), expected.kind);
+    assert_eq!(err.code.as_str(), expected.code);
+    assert_eq!(err.path, expected.path);
+    assert!(err.message.contains("is ambiguous with separator '__'"));
+}
+
+#[test]
+fn r11_string_ops_concat_null() {
+    let base = fixtures_dir().join("r11_string_ops_concat_null");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_expected_error(&base.join("expected_error.json"));
+
+    let err = transform(&rule, &input, None).expect_err("expected transform error");
+    assert_eq!(transform_kind_to_str(&err.kind), expected.kind);
+    assert_eq!(err.code.as_str(), expected.code);
+    assert_eq!(err.path, expected.path);
+    assert!(err.message.contains("concat does not accept null"));
+}
+
+#[test]
+fn r12_ndjson_invalid_line() {
+    let base = fixtures_dir().join("r12_ndjson_invalid_line");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.ndjson"))
+        .unwrap_or_else(|_| panic!("failed to read input.ndjson"));
+    let expected = load_expected_error(&base.join("expected_error.json"));
+
+    let err = transform(&rule, &input, None).expect_err("expected transform error");
+    assert_eq!(transform_kind_to_str(&err.kind), expected.kind);
+    assert_eq!(err.code.as_str(), expected.code);
+    assert_eq!(err.path, expected.path);
+    assert!(err.message.starts_with("failed to parse NDJSON input at line 3:"));
+}
+
+#[test]
+fn r13_string_ops_substring_length() {
+    let base = fixtures_dir().join("r13_string_ops_substring_length");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_expected_error(&base.join("expected_error.json"));
+
+    let err = transform(&rule, &input, None).expect_err("expected transform error");
+    assert_eq!(transform_kind_to_str(&err.kind), expected.kind);
+    assert_eq!(err.code.as_str(), expected.code);
+    assert_eq!(err.path, expected.path);
+    assert!(err.message.contains("substring length must be a non-negative integer"));
+}
+
+#[test]
+fn r14_string_ops_regex_group() {
+    let base = fixtures_dir().join("r14_string_ops_regex_group");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_expected_error(&base.join("expected_error.json"));
+
+    let err = transform(&rule, &input, None).expect_err("expected transform error");
+    assert_eq!(transform_kind_to_str(&err.kind), expected.kind);
+    assert_eq!(err.code.as_str(), expected.code);
+    assert_eq!(err.path, expected.path);
+    assert!(err.message.contains("regex group does not exist in pattern"));
+}
+
+#[test]
+fn r15_default_expr_missing() {
+    let base = fixtures_dir().join("r15_default_expr_missing");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_expected_error(&base.join("expected_error.json"));
+    let context = serde_json::json!({});
+
+    let err = transform(&rule, &input, Some(&context)).expect_err("expected transform error");
+    assert_eq!(transform_kind_to_str(&err.kind), expected.kind);
+    assert_eq!(err.code.as_str(), expected.code);
+    assert_eq!(err.path, expected.path);
+    assert_eq!(err.message, "required value is missing");
+}
+
+#[test]
+fn r16_group_key_error() {
+    let base = fixtures_dir().join("r16_group_key_error");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_expected_error(&base.join("expected_error.json"));
+
+    let err = transform(&rule, &input, None).expect_err("expected transform error");
+    assert_eq!(transform_kind_to_str(&err.kind), expected.kind);
+    assert_eq!(err.code.as_str(), expected.code);
+    assert_eq!(err.path, expected.path);
+    assert_eq!(err.record_index, Some(1));
+}
+
+#[test]
+fn r17_if_condition_not_bool() {
+    let base = fixtures_dir().join("r17_if_condition_not_bool");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_expected_error(&base.join("expected_error.json"));
+
+    let err = transform(&rule, &input, None).expect_err("expected transform error");
+    assert_eq!(transform_kind_to_str(&err.kind), expected.kind);
+    assert_eq!(err.code.as_str(), expected.code);
+    assert_eq!(err.path, expected.path);
+    assert_eq!(err.record_index, Some(1));
+}
+
+#[test]
+fn r18_array_target_required() {
+    let base = fixtures_dir().join("r18_array_target_required");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_expected_error(&base.join("expected_error.json"));
+
+    let err = transform(&rule, &input, None).expect_err("expected transform error");
+    assert_eq!(transform_kind_to_str(&err.kind), expected.kind);
+    assert_eq!(err.code.as_str(), expected.code);
+    assert_eq!(err.path, expected.path);
+    assert_eq!(err.message, "required value is missing at item 1");
+    assert_eq!(err.record_index, Some(1));
+}
+
+#[test]
+fn r19_decimal_cast_failed() {
+    let base = fixtures_dir().join("r19_decimal_cast_failed");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_expected_error(&base.join("expected_error.json"));
+
+    let err = transform(&rule, &input, None).expect_err("expected transform error");
+    assert_eq!(transform_kind_to_str(&err.kind), expected.kind);
+    assert_eq!(err.code.as_str(), expected.code);
+    assert_eq!(err.path, expected.path);
+    assert_eq!(err.message, "failed to cast to decimal");
+    assert_eq!(err.record_index, Some(1));
+}
+
+#[test]
+fn r20_deep_merge_invalid_arrays() {
+    let base = fixtures_dir().join("r20_deep_merge_invalid_arrays");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_expected_error(&base.join("expected_error.json"));
+
+    let err = transform(&rule, &input, None).expect_err("expected transform error");
+    assert_eq!(transform_kind_to_str(&err.kind), expected.kind);
+    assert_eq!(err.code.as_str(), expected.code);
+    assert_eq!(err.path, expected.path);
+    assert_eq!(err.message, "arrays must be replace|concat|by_index");
+    assert_eq!(err.record_index, Some(0));
+}
+
+#[test]
+fn r21_format_unknown_placeholder() {
+    let base = fixtures_dir().join("r21_format_unknown_placeholder");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_expected_error(&base.join("expected_error.json"));
+
+    let err = transform(&rule, &input, None).expect_err("expected transform error");
+    assert_eq!(transform_kind_to_str(&err.kind), expected.kind);
+    assert_eq!(err.code.as_str(), expected.code);
+    assert_eq!(err.path, expected.path);
+    assert_eq!(err.message, "unknown placeholder: {region}");
+}
+
+#[test]
+fn format_rejects_objects_without_json_modifier() {
+    let rule = parse_rule_file(
+        r#"
+version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  - target: "label"
+    expr:
+      op: "format"
+      args: [ "tags={tags}", { ref: "input.order" } ]
+"#,
+    )
+    .expect("failed to parse rules");
+    let input = r#"[{ "order": { "tags": ["a"] } }]"#;
+    let err = transform(&rule, input, None).expect_err("expected transform error");
+    assert_eq!(err.path.as_deref(), Some("mappings[0].expr.args[1]"));
+    assert_eq!(
+        err.message,
+        "placeholder {tags} is an object/array; use {tags:json} to embed it"
+    );
+}
+
+#[test]
+fn r22_epoch_out_of_range() {
+    let base = fixtures_dir().join("r22_epoch_out_of_range");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_expected_error(&base.join("expected_error.json"));
+
+    let err = transform(&rule, &input, None).expect_err("expected transform error");
+    assert_eq!(transform_kind_to_str(&err.kind), expected.kind);
+    assert_eq!(err.code.as_str(), expected.code);
+    assert_eq!(err.path, expected.path);
+    assert_eq!(err.message, "epoch value is out of range");
+}
+
+#[test]
+fn r23_invalid_timezone() {
+    let base = fixtures_dir().join("r23_invalid_timezone");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_expected_error(&base.join("expected_error.json"));
+
+    let err = transform(&rule, &input, None).expect_err("expected transform error");
+    assert_eq!(transform_kind_to_str(&err.kind), expected.kind);
+    assert_eq!(err.code.as_str(), expected.code);
+    assert_eq!(err.path, expected.path);
+    assert_eq!(
+        err.message,
+        "timezone must be UTC, an offset like +09:00 or an IANA name like Asia/Tokyo"
+    );
+}
+
+#[test]
+fn r24_constant_failure() {
+    let base = fixtures_dir().join("r24_constant_failure");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_expected_error(&base.join("expected_error.json"));
+    let context = serde_json::json!({ "total": 10 });
+
+    // Constants are not per record, so `on_error: skip` does not apply.
+    let err = transform(&rule, &input, Some(&context)).expect_err("expected transform error");
+    assert_eq!(transform_kind_to_str(&err.kind), expected.kind);
+    assert_eq!(err.code.as_str(), expected.code);
+    assert_eq!(err.path, expected.path);
+    assert_eq!(err.record_index, None);
+}
+
+#[test]
+fn r25_split_empty_regex() {
+    let base = fixtures_dir().join("r25_split_empty_regex");
+    let rule = load_rule(&base.join("rules.yaml"));
+    let input = fs::read_to_string(base.join("input.json"))
+        .unwrap_or_else(|_| panic!("failed to read input.json"));
+    let expected = load_expected_error(&base.join("expected_error.json"));
+
+    let err = transform(&rule, &input, None).expect_err("expected transform error");
+    assert_eq!(transform_kind_to_str(&err.kind), expected.kind);
+    assert_eq!(err.code.as_str(), expected.code);
     assert_eq!(err.path, expected.path);
+    assert_eq!(err.message, "split regex must not match an empty string");
 }
@@ -0,0 +1,132 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+use transform_rules::{
+    parse_rule_file, select_json_records, transform_stream, transform_values,
+    transform_values_stream, transform_with_warnings, InputFormat, TransformErrorKind,
+};
+
+fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+}
+
+#[test]
+fn json_fixtures_match_the_string_path() {
+    let mut checked = 0;
+    for entry in fs::read_dir(fixtures_dir()).expect("fixtures dir") {
+        let base = entry.expect("fixture entry").path();
+        let name = base.file_name().unwrap().to_string_lossy().to_string();
+        let Ok(input) = fs::read_to_string(base.join("input.json")) else {
+            continue;
+        };
+        let yaml = fs::read_to_string(base.join("rules.yaml")).expect("rules.yaml");
+        let rule = parse_rule_file(&yaml).expect("failed to parse rules");
+        // `max_depth` guards the text parser, which parsed values never reach.
+        let max_depth = rule.input.json.as_ref().and_then(|json| json.max_depth);
+        if !matches!(rule.input.format, InputFormat::Json) || max_depth.is_some() {
+            continue;
+        }
+        let context: Option<Value> = fs::read_to_string(base.join("context.json"))
+            .ok()
+            .map(|text| serde_json::from_str(&text).expect("context json"));
+        let Ok(document) = serde_json::from_str::<Value>(&input) else {
+            continue;
+        };
+
+        let expected = transform_with_warnings(&rule, &input, context.as_ref());
+        let actual = select_json_records(&rule, &document)
+            .and_then(|records| transform_values(&rule, records, context.as_ref()));
+        match (expected, actual) {
+            (Ok(expected), Ok(actual)) => assert_eq!(actual, expected, "{}", name),
+            (Err(expected), Err(actual)) => {
+                assert_eq!(actual.kind, expected.kind, "{}", name);
+                assert_eq!(actual.path, expected.path, "{}", name);
+                assert_eq!(actual.record_index, expected.record_index, "{}", name);
+            }
+            (expected, actual) => panic!("{}: {:?} vs {:?}", name, expected, actual),
+        }
+        checked += 1;
+    }
+    assert!(checked >= 20, "only {} fixtures checked", checked);
+}
+
+#[test]
+fn stream_items_match_the_string_path() {
+    let rule = parse_rule_file(
+        r#"
+version: 1
+input:
+  format: json
+  json:
+    records_path: "data.items"
+on_error: skip
+mappings:
+  - target: "id"
+    source: "id"
+    type: "int"
+"#,
+    )
+    .expect("failed to parse rules");
+    let document = json!({ "data": { "items": [{ "id": "1" }, { "id": "x" }, { "id": 3 }] } });
+    let input = document.to_string();
+
+    let records = select_json_records(&rule, &document).unwrap();
+    assert_eq!(records.len(), 3);
+    let expected: Vec<_> = transform_stream(&rule, &input, None)
+        .unwrap()
+        .map(|item| item.unwrap())
+        .collect();
+    let actual: Vec<_> = transform_values_stream(&rule, records, None)
+        .map(|item| item.unwrap())
+        .collect();
+    assert_eq!(actual.len(), expected.len());
+    for (actual, expected) in actual.iter().zip(&expected) {
+        assert_eq!(actual.record_index, expected.record_index);
+        assert_eq!(actual.output, expected.output);
+        assert_eq!(actual.errors.len(), expected.errors.len());
+    }
+}
+
+#[test]
+fn records_are_used_as_given() {
+    // `records_path` only applies to input text; the caller passes the records.
+    let rule = parse_rule_file(
+        r#"
+version: 1
+input:
+  format: csv
+  csv:
+    has_header: true
+mappings:
+  - target: "id"
+    source: "id"
+"#,
+    )
+    .expect("failed to parse rules");
+    let records = [json!({ "id": 1 }), json!({ "id": 2 })];
+    let (output, warnings) = transform_values(&rule, &records, None).unwrap();
+    assert_eq!(output, json!([{ "id": 1 }, { "id": 2 }]));
+    assert!(warnings.is_empty());
+
+    let rule = parse_rule_file(
+        r#"
+version: 1
+input:
+  format: json
+  json:
+    records_path: "items"
+mappings:
+  - target: "id"
+    source: "id"
+"#,
+    )
+    .expect("failed to parse rules");
+    let err = select_json_records(&rule, &json!({ "rows": [] })).unwrap_err();
+    assert_eq!(err.kind, TransformErrorKind::InvalidRecordsPath);
+    let record = json!({ "items": [{ "id": 7 }] });
+    let output = transform_values(&rule, std::slice::from_ref(&record), None).unwrap().0;
+    assert_eq!(output, json!([{}]));
+}
@@ -86,6 +86,19 @@ fn valid_rules_should_pass_validation() {
         "t24_json_ops_missing",
         "t25_json_ops_get_chain",
         "t26_chain_all_ops",
+        "t27_mapping_groups",
+        "t28_json_ops_object_flatten_options",
+        "t29_string_ops_concat_nulls",
+        "t30_ndjson_input",
+        "t31_string_ops_substring",
+        "t32_string_ops_regex",
+        "t33_default_expr",
+        "t34_on_error_skip",
+        "t35_group_output",
+        "t36_conditional_ops",
+        "t37_array_targets",
+        "t38_date_decimal_casts",
+        "t39_deep_merge_arrays",
     ];
 
     for case in cases {
@@ -111,6 +124,30 @@ fn invalid_rules_should_match_expected_errors() {
         "v09_invalid_when_type",
         "v10_invalid_record_when_type",
         "v11_invalid_item_ref",
+        "v12_invalid_max_depth",
+        "v13_mappings_and_groups",
+        "v14_invalid_flatten_options",
+        "v15_invalid_concat_options",
+        "v16_ndjson_records_path",
+        "v17_invalid_regex_group",
+        "v18_invalid_default_expr",
+        "v19_group_record_when",
+        "v20_conditional_args",
+        "v21_op_arity",
+        "v22_invalid_output_columns",
+        "v23_array_target_conflicts",
+        "v24_invalid_type_name",
+        "v25_deep_merge_options",
+        "v26_target_path_conflicts",
+        "v27_invalid_format",
+        "v28_invalid_csv_columns",
+        "v29_invalid_constants",
+        "v30_invalid_split_options",
+        "v31_quoted_key_targets",
+        "v32_invalid_key_options",
+        "v33_invalid_coalesce_options",
+        "v34_expr_locations",
+        "v35_invalid_source_fallback",
     ];
 
     for case in cases {
@@ -153,3 +190,110 @@ fn validation_errors_include_location_with_source() {
         .expect("expected location");
     assert_eq!(location.line, 7);
 }
+
+#[test]
+fn target_conflicts_name_both_mappings() {
+    let rules_path = fixtures_dir()
+        .join("v26_target_path_conflicts")
+        .join("rules.yaml");
+    let yaml = fs::read_to_string(&rules_path)
+        .unwrap_or_else(|_| panic!("failed to read {}", rules_path.display()));
+    let rule = parse_rule_file(&yaml).unwrap();
+    let errors = validate_rule_file_with_source(&rule, &yaml).unwrap_err();
+    let messages: Vec<_> = errors
+        .iter()
+        .map(|err| {
+            let line = err.location.as_ref().map(|location| location.line);
+            (err.message.as_str(), line)
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        vec![
+            ("mapping.target duplicates mappings[0].target (line 6)", Some(8)),
+            ("mapping.target duplicates mappings[2].target (line 10)", Some(16)),
+            (
+                "mapping.target is nested under 'name', which mappings[5].target (line 18) writes as a non-object value",
+                Some(21)
+            ),
+            (
+                "mapping.target replaces 'customer.id', which mappings[0].target (line 6) writes earlier",
+                Some(27)
+            ),
+        ]
+    );
+}
+
+#[test]
+fn op_arity_errors_describe_expected_count_with_location() {
+    let rules_path = fixtures_dir().join("v21_op_arity").join("rules.yaml");
+    let yaml = fs::read_to_string(&rules_path)
+        .unwrap_or_else(|_| panic!("failed to read {}", rules_path.display()));
+    let rule = parse_rule_file(&yaml).unwrap();
+    let errors = validate_rule_file_with_source(&rule, &yaml).unwrap_err();
+
+    let trim = errors
+        .iter()
+        .find(|err| err.path.as_deref() == Some("mappings[0].expr.args"))
+        .expect("expected trim arity error");
+    assert_eq!(trim.message, "expr.args must contain exactly one item");
+    assert!(trim.location.is_some());
+
+    let unknown = errors
+        .iter()
+        .find(|err| err.code == ErrorCode::UnknownOp)
+        .expect("expected UnknownOp");
+    assert_eq!(unknown.location.clone().expect("expected location").line, 21);
+}
+
+#[test]
+fn expression_errors_are_located_in_block_and_flow_yaml() {
+    let rules_path = fixtures_dir().join("v34_expr_locations").join("rules.yaml");
+    let yaml = fs::read_to_string(&rules_path)
+        .unwrap_or_else(|_| panic!("failed to read {}", rules_path.display()));
+    let rule = parse_rule_file(&yaml).unwrap();
+    let errors = validate_rule_file_with_source(&rule, &yaml).unwrap_err();
+    let located: Vec<_> = errors
+        .iter()
+        .map(|err| {
+            let location = err.location.as_ref().expect("expected location");
+            (err.path.as_deref().unwrap(), location.line, location.column)
+        })
+        .collect();
+    assert_eq!(
+        located,
+        vec![
+            ("record_when.args[1].args", 10, 60),
+            ("record_when.args[1].op", 10, 62),
+            ("mappings[0].expr.chain[2]", 17, 9),
+            ("mappings[1].expr.chain[1]", 21, 9),
+            ("mappings[2].expr.args[1].args[0].args", 28, 19),
+            ("mappings[2].expr.args[1].args[0].op", 28, 21),
+            ("mappings[3].when.args[1].args", 35, 9),
+            ("mappings[3].when.args[1].op", 35, 13),
+            ("mappings[4].expr.args[1].op", 41, 13),
+        ]
+    );
+}
+
+#[test]
+fn output_columns_require_csv_format() {
+    let rule = parse_rule_file(
+        r#"
+version: 1
+input:
+  format: json
+  json: {}
+output:
+  columns: [ "id" ]
+mappings:
+  - target: "id"
+    source: "id"
+"#,
+    )
+    .unwrap();
+    let errors = validate_rule_file(&rule).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].code, ErrorCode::InvalidOutputColumns);
+    assert_eq!(errors[0].path.as_deref(), Some("output.columns"));
+}
@@ -0,0 +1,60 @@
+use serde_json::json;
+use transform_rules::{
+    parse_rule_file, preflight_validate_with_warning_summary, transform_with_warning_summary,
+    TransformErrorKind, TransformWarning, WarningSummary, MAX_WARNING_GROUPS,
+};
+
+const RULES: &str = r#"
+version: 1
+input:
+  format: json
+mappings:
+  - target: "name"
+    source: "name"
+    when:
+      op: "=="
+      args: [ { op: "lowercase", args: [ { ref: "input.flag" } ] }, "yes" ]
+"#;
+
+#[test]
+fn identical_warnings_are_counted_once() {
+    let rule = parse_rule_file(RULES).expect("failed to parse rules");
+    // Every numeric `flag` makes `lowercase` fail; record 2 has a string flag.
+    let records: Vec<_> = (0..5_000)
+        .map(|index| match index {
+            2 => json!({ "name": index, "flag": "yes" }),
+            _ => json!({ "name": index, "flag": index }),
+        })
+        .collect();
+    let input = serde_json::to_string(&records).unwrap();
+
+    let (output, summary) = transform_with_warning_summary(&rule, &input, None).unwrap();
+    assert_eq!(output.as_array().unwrap().len(), 5_000);
+    assert_eq!(summary.groups.len(), 1);
+    assert_eq!(summary.total(), 4_999);
+    let group = &summary.groups[0];
+    assert_eq!(group.count, 4_999);
+    assert_eq!(group.first_record_index, Some(0));
+    assert_eq!(group.last_record_index, Some(4_999));
+    assert_eq!(group.warning.record_index, Some(0));
+    assert_eq!(group.warning.kind, TransformErrorKind::ExprError);
+
+    let preflight = preflight_validate_with_warning_summary(&rule, &input, None).unwrap();
+    assert_eq!(preflight.groups, summary.groups);
+}
+
+#[test]
+fn distinct_warnings_beyond_the_limit_are_only_counted() {
+    let mut summary = WarningSummary::default();
+    for index in 0..MAX_WARNING_GROUPS + 10 {
+        let warning = TransformWarning::new(TransformErrorKind::ExprError, "bad value")
+            .with_path(format!("mappings[{}]", index))
+            .with_record_index(index);
+        summary.push(warning.clone());
+        summary.push(warning);
+    }
+    assert_eq!(summary.groups.len(), MAX_WARNING_GROUPS);
+    assert_eq!(summary.dropped, 20);
+    assert_eq!(summary.total(), (MAX_WARNING_GROUPS + 10) * 2);
+    assert!(summary.groups.iter().all(|group| group.count == 2));
+}
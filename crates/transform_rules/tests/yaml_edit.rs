@@ -0,0 +1,196 @@
+use serde_yaml::Value as YamlValue;
+use transform_rules::patch_yaml;
+
+fn patch(source: &str, change: impl FnOnce(&mut YamlValue)) -> Option<String> {
+    let mut value: YamlValue = serde_yaml::from_str(source).unwrap();
+    change(&mut value);
+    patch_yaml(source, &value)
+}
+
+fn mapping(value: &mut YamlValue, index: usize) -> &mut serde_yaml::Mapping {
+    value["mappings"][index].as_mapping_mut().unwrap()
+}
+
+fn yaml(text: &str) -> YamlValue {
+    serde_yaml::from_str(text).unwrap()
+}
+
+#[test]
+fn only_changed_nodes_are_rewritten() {
+    let source = r#"# Base rules
+version: 1
+input:
+  format: csv # from the export
+  csv:
+    has_header: true
+
+mappings:
+  # identifiers
+  - target: "id"
+    source: "old_id"   # legacy column
+  - target: "city"
+    source: old_city
+    type: "string"
+  - target: "total"
+    expr: { op: "+", args: [ { ref: "input.a" }, 1 ] }
+"#;
+    let output = patch(source, |value| {
+        let input = value["input"].as_mapping_mut().unwrap();
+        input.insert("format".into(), "json".into());
+        input.insert("json".into(), yaml("{ records_path: items }"));
+        mapping(value, 0).insert("source".into(), "id".into());
+        mapping(value, 1).insert("source".into(), "address.city".into());
+    })
+    .expect("patched");
+    assert_eq!(
+        output,
+        r#"# Base rules
+version: 1
+input:
+  format: json # from the export
+  csv:
+    has_header: true
+  json: { records_path: "items" }
+
+mappings:
+  # identifiers
+  - target: "id"
+    source: "id"   # legacy column
+  - target: "city"
+    source: address.city
+    type: "string"
+  - target: "total"
+    expr: { op: "+", args: [ { ref: "input.a" }, 1 ] }
+"#
+    );
+}
+
+#[test]
+fn swapped_keys_are_rewritten_in_place_in_block_and_flow_mappings() {
+    let source = r#"mappings:
+  - { target: "name", source: 'old_name' } # flow
+  - source: "old_sku"
+    target: "sku"
+  - target: "tags"
+    source: "old_tags"
+  - { source: "a", target: "b", expr: { ref: "input.b" } }
+"#;
+    let output = patch(source, |value| {
+        let name = mapping(value, 0);
+        name.remove("source");
+        name.insert("value".into(), YamlValue::Null);
+        name.insert("required".into(), false.into());
+        let sku = mapping(value, 1);
+        sku.remove("source");
+        sku.insert("expr".into(), yaml("{ op: map, args: [ { ref: input.lines } ] }"));
+        mapping(value, 2).insert("source".into(), yaml("[ tags, labels.all ]"));
+        mapping(value, 3).remove("source");
+    })
+    .expect("patched");
+    assert_eq!(
+        output,
+        r#"mappings:
+  - { target: "name", value: null, required: false } # flow
+  - expr: { op: "map", args: [ { ref: "input.lines" } ] }
+    target: "sku"
+  - target: "tags"
+    source: [ "tags", "labels.all" ]
+  - { target: "b", expr: { ref: "input.b" } }
+"#
+    );
+}
+
+#[test]
+fn block_scalars_stay_intact_around_edits() {
+    let source = r#"mappings:
+  - target: "note"
+    value: |
+      keep: this
+        - and this
+
+  - target: "code"
+    source: "old_code"
+    default: >
+      folded
+      text
+  - target: "after"
+    source: "old_after"
+"#;
+    let output = patch(source, |value| {
+        let code = mapping(value, 1);
+        code.insert("source".into(), "code".into());
+        code.insert("required".into(), false.into());
+        mapping(value, 2).insert("source".into(), "after".into());
+    })
+    .expect("patched");
+    assert_eq!(
+        output,
+        r#"mappings:
+  - target: "note"
+    value: |
+      keep: this
+        - and this
+
+  - target: "code"
+    source: "code"
+    default: >
+      folded
+      text
+    required: false
+  - target: "after"
+    source: "after"
+"#
+    );
+}
+
+#[test]
+fn anchors_survive_and_changed_aliases_are_written_out() {
+    let source = r#"mappings:
+  - target: "id"
+    source: &id_source "old_id"
+    type: &text "string"
+  - target: "city"
+    source: *id_source
+    type: *text
+  - &zip
+    target: "zip"
+    source: "old_zip"
+  - *zip
+"#;
+    let output = patch(source, |value| {
+        mapping(value, 0).insert("source".into(), "id".into());
+        mapping(value, 1).insert("source".into(), "city".into());
+        mapping(value, 2).insert("source".into(), "zip".into());
+        mapping(value, 3).insert("source".into(), "postal_code".into());
+    })
+    .expect("patched");
+    assert_eq!(
+        output,
+        r#"mappings:
+  - target: "id"
+    source: &id_source "id"
+    type: &text "string"
+  - target: "city"
+    source: "city"
+    type: *text
+  - &zip
+    target: "zip"
+    source: "zip"
+  - { target: "zip", source: "postal_code" }
+"#
+    );
+}
+
+#[test]
+fn edits_that_would_change_an_alias_are_refused() {
+    let source = r#"mappings:
+  - &zip
+    target: "zip"
+    source: "old_zip"
+  - *zip
+"#;
+    let output = patch(source, |value| {
+        mapping(value, 0).insert("source".into(), "zip".into());
+    });
+    assert_eq!(output, None);
+}
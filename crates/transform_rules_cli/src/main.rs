@@ -1,13 +1,20 @@
+use std::borrow::Cow;
 use std::fs;
-use std::io::{self, Write};
-use std::path::PathBuf;
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Path, PathBuf};
 
 use clap::{Args, Parser, Subcommand, ValueEnum};
 use serde_json::json;
 use transform_rules::{
-    generate_dto, parse_rule_file, preflight_validate_with_warnings, transform_stream,
-    transform_with_warnings, validate_rule_file_with_source, DtoLanguage, InputFormat, RuleError,
-    RuleFile, TransformError, TransformErrorKind, TransformWarning,
+    diff_json, environment_info, generate_dto, glob_match, input_record, load_rule_file,
+    load_table, parse_csv_records, parse_rule_file, preflight_validate_with_warning_summary,
+    preflight_validate_with_warnings, records_to_csv, run_self_checks, transform,
+    transform_record_traced, transform_stream, transform_stream_from_reader,
+    validate_rule_file_full, validate_rule_file_with_source, ArrayMatch, DiffEntry, DtoLanguage,
+    IndexedTable, InputFormat, LoadedTable, OnError, OutputFormat, OutputSpec, RecordTrace,
+    RuleError, RuleFile, RuleWarning, TraceEvent, TraceEventKind, TraceOptions, TransformError,
+    TransformErrorKind, TransformWarning, ValidationReport, WarningGroup, WarningSummary,
+    YamlLocation,
 };
 
 #[derive(Parser)]
@@ -24,6 +31,9 @@ enum Commands {
     Preflight(PreflightArgs),
     Transform(TransformArgs),
     Generate(GenerateArgs),
+    Doctor(DoctorArgs),
+    Check(CheckArgs),
+    Trace(TraceArgs),
 }
 
 #[derive(Args)]
@@ -32,6 +42,10 @@ struct ValidateArgs {
     rules: PathBuf,
     #[arg(short = 'e', long, default_value = "text")]
     error_format: ErrorFormat,
+    #[arg(long)]
+    allow_unknown_ops: bool,
+    #[arg(long)]
+    deny_warnings: bool,
 }
 
 #[derive(Args)]
@@ -42,30 +56,68 @@ struct PreflightArgs {
     input: PathBuf,
     #[arg(short = 'f', long)]
     format: Option<FormatOverride>,
-    #[arg(short = 'c', long)]
-    context: Option<PathBuf>,
+    #[arg(short = 'c', long, value_name = "[NAME=]PATH")]
+    context: Vec<String>,
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    strict_utf8: Option<bool>,
     #[arg(short = 'e', long, default_value = "text")]
     error_format: ErrorFormat,
+    #[arg(long)]
+    verbose_warnings: bool,
 }
 
 #[derive(Args)]
 struct TransformArgs {
     #[arg(short = 'r', long)]
     rules: PathBuf,
-    #[arg(short = 'i', long)]
-    input: PathBuf,
+    #[arg(short = 'i', long, required_unless_present = "input_dir")]
+    input: Option<PathBuf>,
     #[arg(short = 'f', long)]
     format: Option<FormatOverride>,
-    #[arg(short = 'c', long)]
-    context: Option<PathBuf>,
+    #[arg(short = 'c', long, value_name = "[NAME=]PATH")]
+    context: Vec<String>,
     #[arg(short = 'o', long)]
     output: Option<PathBuf>,
     #[arg(long)]
     ndjson: bool,
+    #[arg(long, conflicts_with = "ndjson")]
+    output_format: Option<OutputFormatOverride>,
+    #[arg(
+        long,
+        requires = "output_dir",
+        conflicts_with_all = ["input", "output", "ndjson", "trace_expr"]
+    )]
+    input_dir: Option<PathBuf>,
+    #[arg(long, requires = "input_dir")]
+    output_dir: Option<PathBuf>,
+    #[arg(long, requires = "input_dir")]
+    include: Option<String>,
+    #[arg(long, requires = "input_dir")]
+    output_ext: Option<OutputExt>,
+    #[arg(long, requires = "input_dir")]
+    fail_fast: bool,
+    #[arg(long, requires = "input_dir")]
+    per_file_context: bool,
+    #[arg(long, value_name = "N")]
+    max_errors: Option<usize>,
     #[arg(short = 'v', long)]
     validate: bool,
+    #[arg(long, requires = "validate")]
+    allow_unknown_ops: bool,
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    strict_utf8: Option<bool>,
     #[arg(short = 'e', long, default_value = "text")]
     error_format: ErrorFormat,
+    #[arg(long)]
+    verbose_warnings: bool,
+    #[arg(long, value_name = "TARGET_GLOB", requires = "trace_out")]
+    trace_expr: Vec<String>,
+    #[arg(long, requires = "trace_expr")]
+    trace_out: Option<PathBuf>,
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    threads: usize,
+    #[arg(long)]
+    allow_env: bool,
 }
 
 #[derive(Args)]
@@ -80,6 +132,52 @@ struct GenerateArgs {
     output: Option<PathBuf>,
 }
 
+#[derive(Args)]
+struct CheckArgs {
+    #[arg(short = 'r', long)]
+    rules: PathBuf,
+    #[arg(short = 'i', long)]
+    input: PathBuf,
+    #[arg(short = 'x', long)]
+    expected: PathBuf,
+    #[arg(short = 'f', long)]
+    format: Option<FormatOverride>,
+    #[arg(short = 'c', long, value_name = "[NAME=]PATH")]
+    context: Vec<String>,
+    /// Pair array elements by this field instead of by position.
+    #[arg(long, value_name = "FIELD")]
+    array_key: Option<String>,
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    strict_utf8: Option<bool>,
+    #[arg(short = 'e', long, default_value = "text")]
+    error_format: ErrorFormat,
+}
+
+#[derive(Args)]
+struct TraceArgs {
+    #[arg(short = 'r', long)]
+    rules: PathBuf,
+    #[arg(short = 'i', long)]
+    input: PathBuf,
+    /// Zero-based index of the input record to map.
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    record: usize,
+    #[arg(short = 'f', long)]
+    format: Option<FormatOverride>,
+    #[arg(short = 'c', long, value_name = "[NAME=]PATH")]
+    context: Vec<String>,
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    strict_utf8: Option<bool>,
+    #[arg(short = 'e', long, default_value = "text")]
+    error_format: ErrorFormat,
+}
+
+#[derive(Args)]
+struct DoctorArgs {
+    #[arg(long)]
+    json: bool,
+}
+
 #[derive(Clone, Copy, Debug, ValueEnum)]
 enum ErrorFormat {
     Text,
@@ -90,6 +188,30 @@ enum ErrorFormat {
 enum FormatOverride {
     Csv,
     Json,
+    Ndjson,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum OutputFormatOverride {
+    Json,
+    Csv,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum OutputExt {
+    Json,
+    Ndjson,
+    Csv,
+}
+
+impl OutputExt {
+    fn as_str(self) -> &'static str {
+        match self {
+            OutputExt::Json => "json",
+            OutputExt::Ndjson => "ndjson",
+            OutputExt::Csv => "csv",
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, ValueEnum)]
@@ -102,6 +224,8 @@ enum DtoLanguageArg {
     Java,
     Kotlin,
     Swift,
+    #[value(alias = "jsonschema")]
+    JsonSchema,
 }
 
 fn main() {
@@ -111,6 +235,9 @@ fn main() {
         Commands::Preflight(args) => run_preflight(args),
         Commands::Transform(args) => run_transform(args),
         Commands::Generate(args) => run_generate(args),
+        Commands::Doctor(args) => run_doctor(args),
+        Commands::Check(args) => run_check(args),
+        Commands::Trace(args) => run_trace(args),
     };
     std::process::exit(exit_code);
 }
@@ -121,16 +248,43 @@ fn run_validate(args: ValidateArgs) -> i32 {
         Err(code) => return code,
     };
 
-    match validate_rule_file_with_source(&rule, &yaml) {
-        Ok(()) => 0,
-        Err(errors) => {
-            emit_validation_errors(&errors, args.error_format);
-            2
-        }
+    let mut report = validate_rule_file_full(&rule, Some(&yaml));
+    if args.allow_unknown_ops {
+        report.allow_unknown_ops();
     }
+    emit_validation_warnings(&report.warnings, args.error_format);
+    if !report.is_valid() {
+        emit_validation_errors(&report.errors, args.error_format);
+        return 2;
+    }
+    if args.deny_warnings && !report.warnings.is_empty() {
+        return 2;
+    }
+    0
+}
+
+// With `allow_unknown_ops`, ops this build does not know are reported as warnings so rule
+// files written for a newer release still validate.
+fn validate_rules(rule: &RuleFile, yaml: &str, allow_unknown_ops: bool, format: ErrorFormat) -> bool {
+    let Err(errors) = validate_rule_file_with_source(rule, yaml) else {
+        return true;
+    };
+    let mut report = ValidationReport { errors, warnings: Vec::new() };
+    if allow_unknown_ops {
+        report.allow_unknown_ops();
+    }
+    emit_validation_warnings(&report.warnings, format);
+    if report.is_valid() {
+        return true;
+    }
+    emit_validation_errors(&report.errors, format);
+    false
 }
 
 fn run_preflight(args: PreflightArgs) -> i32 {
+    if let Err(code) = check_stdin_sources(&args.rules, Some(&args.input)) {
+        return code;
+    }
     let (mut rule, _) = match load_rule(&args.rules) {
         Ok(value) => value,
         Err(code) => return code,
@@ -138,17 +292,23 @@ fn run_preflight(args: PreflightArgs) -> i32 {
 
     apply_format_override(&mut rule, args.format);
 
-    let input = match load_input(&args.input) {
+    let input = match load_input(&args.input, strict_utf8(&rule, args.strict_utf8)) {
         Ok(value) => value,
         Err(code) => return code,
     };
 
-    let context_value = match load_context(&args.context) {
-        Ok(value) => value,
+    let context_value = match load_context(&args.context, &rule) {
+        Ok(context) => context.into_value(),
         Err(code) => return code,
     };
 
-    let warnings = match preflight_validate_with_warnings(&rule, &input, context_value.as_ref()) {
+    let context_value = context_value.as_ref();
+    let warnings = if args.verbose_warnings {
+        preflight_validate_with_warnings(&rule, &input, context_value).map(Warnings::Each)
+    } else {
+        preflight_validate_with_warning_summary(&rule, &input, context_value).map(Warnings::Grouped)
+    };
+    let warnings = match warnings {
         Ok(warnings) => warnings,
         Err(err) => {
             emit_transform_error(&err, args.error_format);
@@ -162,103 +322,401 @@ fn run_preflight(args: PreflightArgs) -> i32 {
 }
 
 fn run_transform(args: TransformArgs) -> i32 {
+    if let Err(code) = check_stdin_sources(&args.rules, args.input.as_deref()) {
+        return code;
+    }
     let (mut rule, yaml) = match load_rule(&args.rules) {
         Ok(value) => value,
         Err(code) => return code,
     };
 
     apply_format_override(&mut rule, args.format);
+    apply_output_format_override(&mut rule, args.output_format);
 
-    if args.validate {
-        if let Err(errors) = validate_rule_file_with_source(&rule, &yaml) {
-            emit_validation_errors(&errors, args.error_format);
-            return 2;
-        }
+    if args.validate && !validate_rules(&rule, &yaml, args.allow_unknown_ops, args.error_format) {
+        return 2;
     }
 
-    let input = match load_input(&args.input) {
-        Ok(value) => value,
-        Err(code) => return code,
-    };
+    if let (Some(input_dir), Some(output_dir)) = (&args.input_dir, &args.output_dir) {
+        return run_transform_dir(&rule, &args, input_dir, output_dir);
+    }
 
-    let context_value = match load_context(&args.context) {
-        Ok(value) => value,
-        Err(code) => return code,
+    let Some(input_path) = args.input.as_ref() else {
+        eprintln!("either --input or --input-dir is required");
+        return 1;
     };
+    let strict = strict_utf8(&rule, args.strict_utf8);
 
     if args.ndjson {
-        return run_transform_ndjson(
+        let input = match open_input(input_path, strict) {
+            Ok(value) => value,
+            Err(code) => return code,
+        };
+        let context = match load_context(&args.context, &rule) {
+            Ok(context) => context,
+            Err(code) => return code,
+        };
+        let mut trace = match create_trace(&args) {
+            Ok(trace) => trace,
+            Err(code) => return code,
+        };
+        let code = run_transform_ndjson(
             &rule,
-            &input,
-            context_value.as_ref(),
-            args.output,
+            input,
+            &context,
+            output_file(args.output),
             args.error_format,
+            args.verbose_warnings,
+            args.max_errors,
+            args.threads,
+            args.allow_env,
+            trace.as_mut(),
         );
+        return finish_trace(trace, code);
     }
 
-    let (output, warnings) = match transform_with_warnings(&rule, &input, context_value.as_ref())
-    {
-        Ok(result) => result,
+    let input = match load_input(input_path, strict) {
+        Ok(value) => value,
+        Err(code) => return code,
+    };
+
+    let context = match load_context(&args.context, &rule) {
+        Ok(context) => context,
+        Err(code) => return code,
+    };
+
+    let mut trace = match create_trace(&args) {
+        Ok(trace) => trace,
+        Err(code) => return code,
+    };
+
+    let run = match transform_collect(
+        &rule,
+        &input,
+        &context,
+        trace.as_mut(),
+        args.verbose_warnings,
+        args.threads,
+        args.allow_env,
+    ) {
+        Ok(run) => run,
         Err(err) => {
             emit_transform_error(&err, args.error_format);
-            return 3;
+            return finish_trace(trace, 3);
         }
     };
+    if let Err(code) = finish_trace_output(trace) {
+        return code;
+    }
 
-    let output_text = match serde_json::to_string(&output) {
-        Ok(text) => text,
-        Err(err) => {
-            eprintln!("failed to serialize output JSON: {}", err);
-            return 1;
-        }
+    let output_text = match output_format(&rule) {
+        OutputFormat::Json => match serde_json::to_string(&run.output) {
+            Ok(text) => text,
+            Err(err) => {
+                eprintln!("failed to serialize output JSON: {}", err);
+                return 1;
+            }
+        },
+        OutputFormat::Csv => match records_to_csv(output_records(&run.output), csv_columns(&rule)) {
+            Ok(text) => text,
+            Err(err) => {
+                emit_transform_error(&err, args.error_format);
+                return 3;
+            }
+        },
     };
 
-    emit_transform_warnings(&warnings, args.error_format);
+    emit_transform_warnings(&run.warnings, args.error_format);
+    emit_row_failures(None, &run.errors, run.failed, args.error_format);
 
-    if let Some(path) = args.output {
-        if let Some(parent) = path.parent() {
-            if !parent.as_os_str().is_empty() {
-                if let Err(err) = fs::create_dir_all(parent) {
-                    eprintln!("failed to create output directory: {}", err);
-                    return 1;
-                }
-            }
+    if let Some(path) = output_file(args.output) {
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+            && let Err(err) = fs::create_dir_all(parent)
+        {
+            eprintln!("failed to create output directory: {}", err);
+            return 1;
         }
         if let Err(err) = fs::write(&path, output_text.as_bytes()) {
             eprintln!("failed to write output: {}", err);
             return 1;
         }
+    } else if output_format(&rule) == OutputFormat::Csv {
+        // The CSV writer already terminates every row.
+        print!("{}", output_text);
     } else {
         println!("{}", output_text);
     }
 
-    0
+    row_failures_exit_code(run.failed, args.max_errors)
+}
+
+fn run_transform_dir(
+    rule: &RuleFile,
+    args: &TransformArgs,
+    input_dir: &Path,
+    output_dir: &Path,
+) -> i32 {
+    let include = args.include.clone().unwrap_or_else(|| match rule.input.format {
+        InputFormat::Csv => "**/*.csv".to_string(),
+        InputFormat::Json => "**/*.json".to_string(),
+        InputFormat::Ndjson => "**/*.ndjson".to_string(),
+    });
+    let output_ext = args.output_ext.unwrap_or(match output_format(rule) {
+        OutputFormat::Json => OutputExt::Json,
+        OutputFormat::Csv => OutputExt::Csv,
+    });
+    let strict = strict_utf8(rule, args.strict_utf8);
+
+    let shared_context = match load_context(&args.context, rule) {
+        Ok(context) => context,
+        Err(code) => return code,
+    };
+
+    let mut files = Vec::new();
+    // Never pick up our own output when --output-dir sits inside --input-dir.
+    let skip_dir = fs::canonicalize(output_dir).ok();
+    if let Err(err) = collect_input_files(input_dir, input_dir, skip_dir.as_deref(), &mut files) {
+        eprintln!("failed to read input directory: {}", err);
+        return 1;
+    }
+    let pattern: Vec<&str> = include.split('/').collect();
+    files.retain(|relative| {
+        let name = relative_display(relative);
+        let segments: Vec<&str> = name.split('/').collect();
+        !(args.per_file_context && name.ends_with(".context.json"))
+            && path_glob_match(&pattern, &segments)
+    });
+    files.sort();
+
+    let mut ok_files = 0usize;
+    let mut failed_files = 0usize;
+    let mut total_records = 0usize;
+    let mut exit_code = 0;
+
+    for relative in &files {
+        let name = relative_display(relative);
+        let output_relative = relative.with_extension(output_ext.as_str());
+        let result = transform_dir_file(
+            rule,
+            &input_dir.join(relative),
+            &output_dir.join(&output_relative),
+            &shared_context,
+            args.per_file_context,
+            strict,
+            output_ext,
+            args.verbose_warnings,
+            args.threads,
+            args.allow_env,
+        );
+        match result {
+            Ok((_, run)) if row_failures_exit_code(run.failed, args.max_errors) != 0 => {
+                emit_file_transform_warnings(Some(&name), &run.warnings, args.error_format);
+                emit_row_failures(Some(&name), &run.errors, run.failed, args.error_format);
+                println!("FAIL {}", name);
+                failed_files += 1;
+                if exit_code == 0 {
+                    exit_code = 3;
+                }
+                if args.fail_fast {
+                    break;
+                }
+            }
+            Ok((records, run)) => {
+                emit_file_transform_warnings(Some(&name), &run.warnings, args.error_format);
+                emit_row_failures(Some(&name), &run.errors, run.failed, args.error_format);
+                println!(
+                    "ok   {} -> {} ({} records)",
+                    name,
+                    relative_display(&output_relative),
+                    records
+                );
+                ok_files += 1;
+                total_records += records;
+            }
+            Err(failure) => {
+                let code = match failure {
+                    DirFileFailure::Io(message) => {
+                        eprintln!("{}: {}", name, message);
+                        1
+                    }
+                    DirFileFailure::Transform(err) => {
+                        emit_file_transform_error(Some(&name), &err, args.error_format);
+                        3
+                    }
+                };
+                println!("FAIL {}", name);
+                failed_files += 1;
+                if exit_code == 0 {
+                    exit_code = code;
+                }
+                if args.fail_fast {
+                    break;
+                }
+            }
+        }
+    }
+
+    println!(
+        "files: {} ok, {} failed; records: {}",
+        ok_files, failed_files, total_records
+    );
+
+    exit_code
+}
+
+enum DirFileFailure {
+    Io(String),
+    Transform(TransformError),
+}
+
+#[allow(clippy::too_many_arguments)]
+fn transform_dir_file(
+    rule: &RuleFile,
+    input_path: &Path,
+    output_path: &Path,
+    shared_context: &Context,
+    per_file_context: bool,
+    strict_utf8: bool,
+    output_ext: OutputExt,
+    verbose_warnings: bool,
+    threads: usize,
+    allow_env: bool,
+) -> Result<(usize, TransformRun), DirFileFailure> {
+    let input = read_input(input_path, strict_utf8).map_err(DirFileFailure::Io)?;
+
+    let file_context = if per_file_context {
+        let context_path = sibling_context_path(input_path);
+        if context_path.is_file() {
+            let value = read_context(&context_path).map_err(DirFileFailure::Io)?;
+            Some(Context::from(value))
+        } else {
+            None
+        }
+    } else {
+        None
+    };
+    let context = file_context.as_ref().unwrap_or(shared_context);
+
+    let mut run =
+        transform_collect(rule, &input, context, None, verbose_warnings, threads, allow_env)
+            .map_err(DirFileFailure::Transform)?;
+    let output = std::mem::take(&mut run.output);
+    let records = output_records(&output);
+
+    let json_error =
+        |err: serde_json::Error| DirFileFailure::Io(format!("failed to serialize output JSON: {}", err));
+    let output_text = match output_ext {
+        OutputExt::Json => serde_json::to_string(&output).map_err(json_error)?,
+        OutputExt::Ndjson => records
+            .iter()
+            .map(|record| serde_json::to_string(record).map(|line| line + "\n"))
+            .collect::<Result<String, _>>()
+            .map_err(json_error)?,
+        OutputExt::Csv => {
+            records_to_csv(records, csv_columns(rule)).map_err(DirFileFailure::Transform)?
+        }
+    };
+
+    if let Some(parent) = output_path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        fs::create_dir_all(parent).map_err(|err| {
+            DirFileFailure::Io(format!("failed to create output directory: {}", err))
+        })?;
+    }
+    fs::write(output_path, output_text.as_bytes())
+        .map_err(|err| DirFileFailure::Io(format!("failed to write output: {}", err)))?;
+
+    Ok((records.len(), run))
+}
+
+// `orders.csv` picks up `orders.context.json` from the same directory.
+fn sibling_context_path(input_path: &Path) -> PathBuf {
+    let stem = input_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    input_path.with_file_name(format!("{}.context.json", stem))
+}
+
+fn collect_input_files(
+    root: &Path,
+    dir: &Path,
+    skip_dir: Option<&Path>,
+    files: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            let is_output = skip_dir
+                .is_some_and(|skip| fs::canonicalize(&path).is_ok_and(|path| path == skip));
+            if is_output {
+                continue;
+            }
+            collect_input_files(root, &path, skip_dir, files)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            files.push(relative.to_path_buf());
+        }
+    }
+    Ok(())
 }
 
+fn relative_display(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// `**` spans any number of directories; `*` and `?` stay within one path segment.
+fn path_glob_match(pattern: &[&str], segments: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => segments.is_empty(),
+        Some((&"**", rest)) => {
+            (0..=segments.len()).any(|skip| path_glob_match(rest, &segments[skip..]))
+        }
+        Some((head, rest)) => segments.split_first().is_some_and(|(segment, remaining)| {
+            glob_match(head, segment) && path_glob_match(rest, remaining)
+        }),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn run_transform_ndjson(
     rule: &RuleFile,
-    input: &str,
-    context: Option<&serde_json::Value>,
+    input: Box<dyn BufRead>,
+    context: &Context,
     output: Option<PathBuf>,
     error_format: ErrorFormat,
+    verbose_warnings: bool,
+    max_errors: Option<usize>,
+    threads: usize,
+    allow_env: bool,
+    mut trace: Option<&mut TraceOutput>,
 ) -> i32 {
-    let stream = match transform_stream(rule, input, context) {
-        Ok(stream) => stream,
+    let mut stream = match transform_stream_from_reader(rule, input, context.value.as_ref()) {
+        Ok(stream) => stream
+            .with_tables(&context.tables)
+            .with_threads(threads)
+            .with_env(allow_env),
         Err(err) => {
             emit_transform_error(&err, error_format);
             return 3;
         }
     };
+    if let Some(trace) = trace.as_deref() {
+        stream = stream.with_trace(trace.options.clone());
+    }
 
     let writer: Box<dyn Write> = match output {
         Some(path) => {
-            if let Some(parent) = path.parent() {
-                if !parent.as_os_str().is_empty() {
-                    if let Err(err) = fs::create_dir_all(parent) {
-                        eprintln!("failed to create output directory: {}", err);
-                        return 1;
-                    }
-                }
+            if let Some(parent) = path.parent()
+                && !parent.as_os_str().is_empty()
+                && let Err(err) = fs::create_dir_all(parent)
+            {
+                eprintln!("failed to create output directory: {}", err);
+                return 1;
             }
             match fs::File::create(&path) {
                 Ok(file) => Box::new(file),
@@ -272,17 +730,38 @@ fn run_transform_ndjson(
     };
 
     let mut writer = io::BufWriter::new(writer);
+    let mut failed = 0usize;
+    // Without --verbose-warnings, warnings are grouped and printed once the stream ends.
+    let mut warnings = Warnings::new(false);
+    warnings.extend(context.warnings.iter().cloned());
+    warnings.extend(stream.parallel_fallback());
 
-    for item in stream {
+    while let Some(item) = stream.next() {
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.write_events(stream.take_trace());
+        }
         let item = match item {
             Ok(item) => item,
             Err(err) => {
+                emit_transform_warnings(&warnings, error_format);
                 emit_transform_error(&err, error_format);
                 return 3;
             }
         };
 
-        emit_transform_warnings(&item.warnings, error_format);
+        if verbose_warnings {
+            emit_transform_warnings(&Warnings::Each(item.warnings), error_format);
+        } else {
+            warnings.extend(item.warnings);
+        }
+        if !item.errors.is_empty() {
+            failed += 1;
+            if rule.on_error == OnError::Collect {
+                for err in &item.errors {
+                    emit_transform_error(err, error_format);
+                }
+            }
+        }
 
         let output = match item.output {
             Some(output) => output,
@@ -307,7 +786,198 @@ fn run_transform_ndjson(
         return 1;
     }
 
-    0
+    emit_transform_warnings(&warnings, error_format);
+    emit_row_failures(None, &[], failed, error_format);
+    row_failures_exit_code(failed, max_errors)
+}
+
+fn create_trace(args: &TransformArgs) -> Result<Option<TraceOutput>, i32> {
+    match args.trace_out.as_ref() {
+        Some(path) => {
+            TraceOutput::create(path, TraceOptions::new(args.trace_expr.clone())).map(Some)
+        }
+        None => Ok(None),
+    }
+}
+
+struct TraceOutput {
+    options: TraceOptions,
+    writer: io::BufWriter<fs::File>,
+    error: Option<io::Error>,
+}
+
+impl TraceOutput {
+    fn create(path: &PathBuf, options: TraceOptions) -> Result<Self, i32> {
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+            && let Err(err) = fs::create_dir_all(parent)
+        {
+            eprintln!("failed to create trace directory: {}", err);
+            return Err(1);
+        }
+        match fs::File::create(path) {
+            Ok(file) => Ok(Self {
+                options,
+                writer: io::BufWriter::new(file),
+                error: None,
+            }),
+            Err(err) => {
+                eprintln!("failed to write trace: {}", err);
+                Err(1)
+            }
+        }
+    }
+
+    fn write_events(&mut self, events: Vec<TraceEvent>) {
+        if self.error.is_some() {
+            return;
+        }
+        for event in events {
+            let line = serde_json::to_string(&event.to_json()).unwrap_or_default();
+            if let Err(err) = writeln!(self.writer, "{}", line) {
+                self.error = Some(err);
+                return;
+            }
+        }
+    }
+}
+
+// Every warning with --verbose-warnings, otherwise identical warnings grouped with a count.
+enum Warnings {
+    Each(Vec<TransformWarning>),
+    Grouped(WarningSummary),
+}
+
+impl Warnings {
+    fn new(verbose: bool) -> Self {
+        if verbose {
+            Warnings::Each(Vec::new())
+        } else {
+            Warnings::Grouped(WarningSummary::default())
+        }
+    }
+}
+
+impl Extend<TransformWarning> for Warnings {
+    fn extend<I: IntoIterator<Item = TransformWarning>>(&mut self, warnings: I) {
+        match self {
+            Warnings::Each(each) => each.extend(warnings),
+            Warnings::Grouped(summary) => summary.extend(warnings),
+        }
+    }
+}
+
+struct TransformRun {
+    output: serde_json::Value,
+    warnings: Warnings,
+    /// Row errors kept by `on_error: collect`; `skip` only counts them.
+    errors: Vec<TransformError>,
+    failed: usize,
+}
+
+fn transform_collect(
+    rule: &RuleFile,
+    input: &str,
+    context: &Context,
+    mut trace: Option<&mut TraceOutput>,
+    verbose_warnings: bool,
+    threads: usize,
+    allow_env: bool,
+) -> Result<TransformRun, TransformError> {
+    let mut stream = transform_stream(rule, input, context.value.as_ref())?
+        .with_tables(&context.tables)
+        .with_threads(threads)
+        .with_env(allow_env);
+    if let Some(trace) = trace.as_deref() {
+        stream = stream.with_trace(trace.options.clone());
+    }
+    let mut run = TransformRun {
+        output: serde_json::Value::Null,
+        warnings: Warnings::new(verbose_warnings),
+        errors: Vec::new(),
+        failed: 0,
+    };
+    run.warnings.extend(context.warnings.iter().cloned());
+    run.warnings.extend(stream.parallel_fallback());
+    let mut output_records = Vec::new();
+    while let Some(item) = stream.next() {
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.write_events(stream.take_trace());
+        }
+        let item = item?;
+        run.warnings.extend(item.warnings);
+        if !item.errors.is_empty() {
+            run.failed += 1;
+            if rule.on_error == OnError::Collect {
+                run.errors.extend(item.errors);
+            }
+        }
+        if let Some(output) = item.output {
+            output_records.push(output);
+        }
+    }
+    run.output = serde_json::Value::Array(output_records);
+    Ok(run)
+}
+
+// Row errors are reported but only fail the run once `--max-errors` is exceeded.
+fn row_failures_exit_code(failed: usize, max_errors: Option<usize>) -> i32 {
+    match max_errors {
+        Some(max) if failed > max => 3,
+        _ => 0,
+    }
+}
+
+fn emit_row_failures(
+    file: Option<&str>,
+    errors: &[TransformError],
+    failed: usize,
+    format: ErrorFormat,
+) {
+    for err in errors {
+        emit_file_transform_error(file, err, format);
+    }
+    if failed == 0 {
+        return;
+    }
+    match format {
+        ErrorFormat::Text => {
+            let noun = if failed == 1 { "row" } else { "rows" };
+            match file {
+                Some(file) => eprintln!("{}: {} {} failed", file, failed, noun),
+                None => eprintln!("{} {} failed", failed, noun),
+            }
+        }
+        ErrorFormat::Json => {
+            let mut value = json!({ "type": "summary", "failed_rows": failed });
+            if let Some(file) = file {
+                value["file"] = json!(file);
+            }
+            eprintln!("{}", serde_json::to_string(&vec![value]).unwrap_or_default());
+        }
+    }
+}
+
+fn finish_trace_output(trace: Option<TraceOutput>) -> Result<(), i32> {
+    let Some(mut trace) = trace else {
+        return Ok(());
+    };
+    let result = match trace.error.take() {
+        Some(err) => Err(err),
+        None => trace.writer.flush(),
+    };
+    result.map_err(|err| {
+        eprintln!("failed to write trace: {}", err);
+        1
+    })
+}
+
+fn finish_trace(trace: Option<TraceOutput>, code: i32) -> i32 {
+    match finish_trace_output(trace) {
+        Ok(()) => code,
+        Err(trace_code) if code == 0 => trace_code,
+        Err(_) => code,
+    }
 }
 
 fn run_generate(args: GenerateArgs) -> i32 {
@@ -324,6 +994,7 @@ fn run_generate(args: GenerateArgs) -> i32 {
         DtoLanguageArg::Java => DtoLanguage::Java,
         DtoLanguageArg::Kotlin => DtoLanguage::Kotlin,
         DtoLanguageArg::Swift => DtoLanguage::Swift,
+        DtoLanguageArg::JsonSchema => DtoLanguage::JsonSchema,
     };
 
     let output = match generate_dto(&rule, lang, args.name.as_deref()) {
@@ -334,14 +1005,13 @@ fn run_generate(args: GenerateArgs) -> i32 {
         }
     };
 
-    if let Some(path) = args.output {
-        if let Some(parent) = path.parent() {
-            if !parent.as_os_str().is_empty() {
-                if let Err(err) = fs::create_dir_all(parent) {
-                    eprintln!("failed to create output directory: {}", err);
-                    return 1;
-                }
-            }
+    if let Some(path) = output_file(args.output) {
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+            && let Err(err) = fs::create_dir_all(parent)
+        {
+            eprintln!("failed to create output directory: {}", err);
+            return 1;
         }
         if let Err(err) = fs::write(&path, output.as_bytes()) {
             eprintln!("failed to write output: {}", err);
@@ -354,8 +1024,248 @@ fn run_generate(args: GenerateArgs) -> i32 {
     0
 }
 
-fn load_rule(path: &PathBuf) -> Result<(RuleFile, String), i32> {
-    let yaml = match fs::read_to_string(path) {
+fn run_doctor(args: DoctorArgs) -> i32 {
+    let environment = environment_info();
+    let checks = run_self_checks();
+    let passed = checks.iter().all(|check| check.passed);
+
+    if args.json {
+        let value = json!({
+            "ok": passed,
+            "environment": environment.to_json(),
+            "checks": checks.iter().map(|check| check.to_json()).collect::<Vec<_>>(),
+        });
+        println!("{}", serde_json::to_string(&value).unwrap_or_default());
+    } else {
+        println!("transform-rules {}", environment.version);
+        if environment.features.is_empty() {
+            println!("features: (none)");
+        } else {
+            println!("features: {}", environment.features.join(","));
+        }
+        println!(
+            "locale: {}",
+            environment.locale.as_deref().unwrap_or("(unset)")
+        );
+        println!("timezone: {}", environment.timezone);
+        let caches: Vec<_> = environment
+            .cache_capacities
+            .iter()
+            .map(|(name, capacity)| format!("{}={}", name, capacity))
+            .collect();
+        println!("cache capacities: {}", caches.join(" "));
+        for check in &checks {
+            let status = if check.passed { "ok  " } else { "FAIL" };
+            let millis = check.duration.as_secs_f64() * 1000.0;
+            match &check.message {
+                Some(message) => {
+                    println!("{} {} ({:.2}ms): {}", status, check.name, millis, message)
+                }
+                None => println!("{} {} ({:.2}ms)", status, check.name, millis),
+            }
+        }
+    }
+
+    if passed { 0 } else { 5 }
+}
+
+// Exits with 4 when the output differs from `--expected`; each difference is reported on stderr.
+fn run_check(args: CheckArgs) -> i32 {
+    if let Err(code) = check_stdin_sources(&args.rules, Some(&args.input)) {
+        return code;
+    }
+    let (mut rule, _) = match load_rule(&args.rules) {
+        Ok(value) => value,
+        Err(code) => return code,
+    };
+
+    apply_format_override(&mut rule, args.format);
+
+    let input = match load_input(&args.input, strict_utf8(&rule, args.strict_utf8)) {
+        Ok(value) => value,
+        Err(code) => return code,
+    };
+
+    let context_value = match load_context(&args.context, &rule) {
+        Ok(context) => context.into_value(),
+        Err(code) => return code,
+    };
+
+    let expected = match fs::read_to_string(&args.expected)
+        .map_err(|err| err.to_string())
+        .and_then(|text| serde_json::from_str(&text).map_err(|err| err.to_string()))
+    {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("failed to read expected output: {}: {}", args.expected.display(), err);
+            return 1;
+        }
+    };
+
+    let actual = match transform(&rule, &input, context_value.as_ref()) {
+        Ok(value) => value,
+        Err(err) => {
+            emit_transform_error(&err, args.error_format);
+            return 3;
+        }
+    };
+
+    let arrays = args.array_key.map_or(ArrayMatch::Ordered, ArrayMatch::Key);
+    let diff = diff_json(&expected, &actual, &arrays);
+    if diff.is_empty() {
+        return 0;
+    }
+    emit_diff(&diff, args.error_format);
+    4
+}
+
+// Prints what each mapping did to one record on stdout; its warnings and error go to stderr.
+fn run_trace(args: TraceArgs) -> i32 {
+    if let Err(code) = check_stdin_sources(&args.rules, Some(&args.input)) {
+        return code;
+    }
+    let (mut rule, _) = match load_rule(&args.rules) {
+        Ok(value) => value,
+        Err(code) => return code,
+    };
+
+    apply_format_override(&mut rule, args.format);
+
+    let input = match load_input(&args.input, strict_utf8(&rule, args.strict_utf8)) {
+        Ok(value) => value,
+        Err(code) => return code,
+    };
+
+    let context_value = match load_context(&args.context, &rule) {
+        Ok(context) => context.into_value(),
+        Err(code) => return code,
+    };
+
+    let record = match input_record(&rule, &input, args.record) {
+        Ok(Some(record)) => record,
+        Ok(None) => {
+            eprintln!("input has no record {}", args.record);
+            return 1;
+        }
+        Err(err) => {
+            emit_transform_error(&err, args.error_format);
+            return 3;
+        }
+    };
+
+    let trace = transform_record_traced(&rule, &record, context_value.as_ref());
+    match args.error_format {
+        ErrorFormat::Text => print_record_trace(args.record, &trace),
+        ErrorFormat::Json => {
+            let mut value = trace.to_json();
+            value["record_index"] = json!(args.record);
+            println!("{}", serde_json::to_string(&value).unwrap_or_default());
+        }
+    }
+
+    emit_transform_warnings(&Warnings::Each(trace.warnings), args.error_format);
+    match &trace.error {
+        Some(err) => {
+            emit_transform_error(err, args.error_format);
+            3
+        }
+        None => 0,
+    }
+}
+
+fn print_record_trace(record_index: usize, trace: &RecordTrace) {
+    println!("record {}", record_index);
+    if let Some(keep) = trace.record_when {
+        println!("  record_when: {}", if keep { "passed" } else { "failed" });
+    }
+    for mapping in &trace.mappings {
+        match &mapping.group_path {
+            Some(group_path) => {
+                println!("  {} ({}) -> {}", mapping.path, group_path, mapping.target)
+            }
+            None => println!("  {} -> {}", mapping.path, mapping.target),
+        }
+        if let Some(passed) = mapping.when {
+            print_trace_steps("  ", &mapping.when_steps);
+            println!("    when: {}", if passed { "passed" } else { "failed" });
+        }
+        print_trace_steps("", &mapping.steps);
+        match (&mapping.value, mapping.written) {
+            (Some(value), true) => println!("    wrote {}: {}", mapping.target, value),
+            _ => println!("    wrote nothing"),
+        }
+    }
+    if let Some(output) = &trace.output {
+        println!("  output: {}", serde_json::to_string(output).unwrap_or_default());
+    }
+}
+
+fn print_trace_steps(indent: &str, events: &[TraceEvent]) {
+    for event in events {
+        let value = event.value.as_deref().unwrap_or("(missing)");
+        match (&event.kind, event.op.as_deref()) {
+            (TraceEventKind::Step, Some(op)) => {
+                println!("    {}step {} {}: {}", indent, event.step, op, value)
+            }
+            (TraceEventKind::Step, None) => {
+                println!("    {}step {}: {}", indent, event.step, value)
+            }
+            (TraceEventKind::Arg(index), op) => println!(
+                "      {}arg {} of {}: {}",
+                indent,
+                index,
+                op.unwrap_or("?"),
+                value
+            ),
+            (TraceEventKind::Error, _) => println!("    {}error: {}", indent, value),
+        }
+    }
+}
+
+fn emit_diff(entries: &[DiffEntry], format: ErrorFormat) {
+    match format {
+        ErrorFormat::Text => {
+            for entry in entries {
+                let mut parts = vec![format!("D {}", entry.kind.as_str())];
+                parts.push(format!("path={}", entry.path));
+                if let Some(expected) = &entry.expected {
+                    parts.push(format!("expected={}", expected));
+                }
+                if let Some(actual) = &entry.actual {
+                    parts.push(format!("actual={}", actual));
+                }
+                eprintln!("{}", parts.join(" "));
+            }
+            eprintln!("{} differences", entries.len());
+        }
+        ErrorFormat::Json => {
+            let values: Vec<_> = entries.iter().map(DiffEntry::to_json).collect();
+            eprintln!("{}", serde_json::to_string(&values).unwrap_or_default());
+        }
+    }
+}
+
+// Rules read from stdin cannot use `extends`; there is no file to resolve parents against.
+fn load_rule(path: &Path) -> Result<(RuleFile, String), i32> {
+    if !is_stdio(path) {
+        return match load_rule_file(path) {
+            Ok(rule) => {
+                let yaml = rule
+                    .origins
+                    .as_ref()
+                    .and_then(|origins| origins.files.last())
+                    .map(|file| file.yaml.clone())
+                    .unwrap_or_default();
+                Ok((rule, yaml))
+            }
+            Err(err) => {
+                eprintln!("{}", err.message);
+                Err(1)
+            }
+        };
+    }
+
+    let yaml = match io::read_to_string(io::stdin()) {
         Ok(data) => data,
         Err(err) => {
             eprintln!("failed to read rules: {}", err);
@@ -374,86 +1284,441 @@ fn load_rule(path: &PathBuf) -> Result<(RuleFile, String), i32> {
     Ok((rule, yaml))
 }
 
+fn apply_output_format_override(rule: &mut RuleFile, format: Option<OutputFormatOverride>) {
+    if let Some(format) = format {
+        let format = match format {
+            OutputFormatOverride::Json => OutputFormat::Json,
+            OutputFormatOverride::Csv => OutputFormat::Csv,
+        };
+        match &mut rule.output {
+            Some(output) => output.format = format,
+            None => {
+                rule.output = Some(OutputSpec {
+                    name: None,
+                    format,
+                    columns: None,
+                })
+            }
+        }
+    }
+}
+
+fn output_format(rule: &RuleFile) -> OutputFormat {
+    rule.output
+        .as_ref()
+        .map_or(OutputFormat::Json, |output| output.format)
+}
+
+fn csv_columns(rule: &RuleFile) -> Option<&[String]> {
+    rule.output.as_ref()?.columns.as_deref()
+}
+
+fn output_records(output: &serde_json::Value) -> &[serde_json::Value] {
+    match output {
+        serde_json::Value::Array(items) => items.as_slice(),
+        other => std::slice::from_ref(other),
+    }
+}
+
 fn apply_format_override(rule: &mut RuleFile, format: Option<FormatOverride>) {
     if let Some(format) = format {
         rule.input.format = match format {
             FormatOverride::Csv => InputFormat::Csv,
             FormatOverride::Json => InputFormat::Json,
+            FormatOverride::Ndjson => InputFormat::Ndjson,
         };
     }
 }
 
-fn load_input(path: &PathBuf) -> Result<String, i32> {
-    match fs::read_to_string(path) {
-        Ok(value) => Ok(value),
-        Err(err) => {
+// JSON input is strict by default; CSV tolerates stray bytes in columns the rules never read.
+fn strict_utf8(rule: &RuleFile, flag: Option<bool>) -> bool {
+    flag.unwrap_or(matches!(
+        rule.input.format,
+        InputFormat::Json | InputFormat::Ndjson
+    ))
+}
+
+// `-` reads --rules/--input from stdin and writes --output to stdout.
+fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+fn check_stdin_sources(rules: &Path, input: Option<&Path>) -> Result<(), i32> {
+    if is_stdio(rules) && input.is_some_and(is_stdio) {
+        eprintln!("--rules and --input cannot both read from stdin (-)");
+        return Err(1);
+    }
+    Ok(())
+}
+
+fn output_file(path: Option<PathBuf>) -> Option<PathBuf> {
+    path.filter(|path| !is_stdio(path))
+}
+
+fn load_input(path: &Path, strict_utf8: bool) -> Result<String, i32> {
+    read_input(path, strict_utf8).map_err(|message| {
+        eprintln!("{}", message);
+        1
+    })
+}
+
+// The streaming path leaves UTF-8 checks to the library, which reports the offending record;
+// lenient mode decodes lossily on the way in, matching `read_input`.
+fn open_input(path: &Path, strict_utf8: bool) -> Result<Box<dyn BufRead>, i32> {
+    let file: Box<dyn Read> = if is_stdio(path) {
+        Box::new(io::stdin())
+    } else {
+        Box::new(fs::File::open(path).map_err(|err| {
             eprintln!("failed to read input: {}", err);
-            Err(1)
+            1
+        })?)
+    };
+    if strict_utf8 {
+        Ok(Box::new(io::BufReader::new(file)))
+    } else {
+        Ok(Box::new(io::BufReader::new(LossyUtf8Reader::new(file))))
+    }
+}
+
+struct LossyUtf8Reader<R> {
+    inner: R,
+    pending: Vec<u8>,
+    position: usize,
+    carry: Vec<u8>,
+}
+
+impl<R> LossyUtf8Reader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            pending: Vec::new(),
+            position: 0,
+            carry: Vec::new(),
         }
     }
 }
 
-fn load_context(path: &Option<PathBuf>) -> Result<Option<serde_json::Value>, i32> {
-    match path {
-        Some(path) => match fs::read_to_string(path) {
-            Ok(data) => match serde_json::from_str(&data) {
-                Ok(json) => Ok(Some(json)),
-                Err(err) => {
-                    eprintln!("failed to parse context JSON: {}", err);
-                    Err(1)
+impl<R: Read> Read for LossyUtf8Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position >= self.pending.len() {
+            let mut raw = [0u8; 8192];
+            let read = self.inner.read(&mut raw)?;
+            let mut chunk = std::mem::take(&mut self.carry);
+            chunk.extend_from_slice(&raw[..read]);
+            if chunk.is_empty() {
+                return Ok(0);
+            }
+            // Hold back a sequence split across reads unless the input has ended.
+            let keep = if read == 0 {
+                0
+            } else {
+                incomplete_utf8_suffix(&chunk)
+            };
+            let (body, tail) = chunk.split_at(chunk.len() - keep);
+            self.pending = String::from_utf8_lossy(body).into_owned().into_bytes();
+            self.position = 0;
+            self.carry = tail.to_vec();
+        }
+        let len = buf.len().min(self.pending.len() - self.position);
+        buf[..len].copy_from_slice(&self.pending[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+fn incomplete_utf8_suffix(bytes: &[u8]) -> usize {
+    for back in 1..=bytes.len().min(3) {
+        let byte = bytes[bytes.len() - back];
+        if byte & 0xC0 == 0x80 {
+            continue;
+        }
+        let width = match byte {
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => 1,
+        };
+        return if width > back { back } else { 0 };
+    }
+    0
+}
+
+fn read_input(path: &Path, strict_utf8: bool) -> Result<String, String> {
+    let bytes = if is_stdio(path) {
+        let mut bytes = Vec::new();
+        io::stdin().read_to_end(&mut bytes).map(|_| bytes)
+    } else {
+        fs::read(path)
+    }
+    .map_err(|err| format!("failed to read input: {}", err))?;
+    if !strict_utf8 {
+        return Ok(String::from_utf8_lossy(&bytes).into_owned());
+    }
+    String::from_utf8(bytes).map_err(|err| {
+        format!(
+            "failed to read input: invalid UTF-8 at byte offset {}",
+            err.utf8_error().valid_up_to()
+        )
+    })
+}
+
+// The context, and the `tables` the rule has indexed while they were read.
+struct Context {
+    value: Option<serde_json::Value>,
+    tables: Vec<IndexedTable>,
+    warnings: Vec<TransformWarning>,
+}
+
+impl Context {
+    // For commands without a stream: indexed tables go back into the context as arrays.
+    fn into_value(self) -> Option<serde_json::Value> {
+        if self.tables.is_empty() {
+            return self.value;
+        }
+        let mut value = self.value.unwrap_or_else(|| json!({}));
+        for table in self.tables {
+            value[table.name()] = table.to_records();
+        }
+        Some(value)
+    }
+}
+
+impl From<serde_json::Value> for Context {
+    fn from(value: serde_json::Value) -> Self {
+        Context {
+            value: Some(value),
+            tables: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+fn load_context(values: &[String], rule: &RuleFile) -> Result<Context, i32> {
+    let sources = parse_context_args(values).map_err(|message| {
+        eprintln!("{}", message);
+        1
+    })?;
+    let fail = |message: String| {
+        eprintln!("{}", message);
+        1
+    };
+    match sources {
+        ContextSources::None => Ok(Context {
+            value: None,
+            tables: Vec::new(),
+            warnings: Vec::new(),
+        }),
+        ContextSources::Single(path) => read_context(&path).map(Context::from).map_err(fail),
+        ContextSources::Named(files) => {
+            let mut context = serde_json::Map::new();
+            let mut tables = Vec::new();
+            let mut warnings = Vec::new();
+            for (name, path) in files {
+                if !rule.tables.as_ref().is_some_and(|tables| tables.contains_key(&name)) {
+                    context.insert(name, read_context(&path).map_err(fail)?);
+                    continue;
+                }
+                let (table, table_warnings) = read_table(rule, &name, &path).map_err(fail)?;
+                warnings.extend(table_warnings);
+                match table {
+                    LoadedTable::Records(records) => {
+                        context.insert(name, records);
+                    }
+                    LoadedTable::Indexed(table) => tables.push(table),
                 }
-            },
-            Err(err) => {
-                eprintln!("failed to read context: {}", err);
-                Err(1)
             }
-        },
-        None => Ok(None),
+            Ok(Context {
+                value: Some(serde_json::Value::Object(context)),
+                tables,
+                warnings,
+            })
+        }
+    }
+}
+
+// A context file the rule declares in `tables`, read as the entry says.
+fn read_table(
+    rule: &RuleFile,
+    name: &str,
+    path: &Path,
+) -> Result<(LoadedTable, Vec<TransformWarning>), String> {
+    let file = fs::File::open(path)
+        .map_err(|err| format!("failed to read context: {}: {}", path.display(), err))?;
+    load_table(rule, name, io::BufReader::new(file)).map_err(|err| {
+        let line = err.line.map(|line| format!(" (line {})", line)).unwrap_or_default();
+        format!("failed to load table: {}{}: {}", path.display(), line, err.message)
+    })
+}
+
+enum ContextSources {
+    None,
+    Single(PathBuf),
+    Named(Vec<(String, PathBuf)>),
+}
+
+// `--context path` is the whole context; repeated `--context name=path` builds an object with
+// one key per name.
+fn parse_context_args(values: &[String]) -> Result<ContextSources, String> {
+    let mut named: Vec<(String, PathBuf)> = Vec::new();
+    let mut unnamed = Vec::new();
+    for value in values {
+        match value.split_once('=') {
+            Some((name, path)) if is_context_name(name) => {
+                if named.iter().any(|(existing, _)| existing == name) {
+                    return Err(format!("duplicate context name: {}", name));
+                }
+                named.push((name.to_string(), PathBuf::from(path)));
+            }
+            _ => unnamed.push(PathBuf::from(value)),
+        }
+    }
+    match (unnamed.len(), named.is_empty()) {
+        (0, true) => Ok(ContextSources::None),
+        (0, false) => Ok(ContextSources::Named(named)),
+        (1, true) => Ok(ContextSources::Single(unnamed.remove(0))),
+        _ => Err("--context without a name cannot be combined with other --context values".to_string()),
+    }
+}
+
+fn is_context_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-')
+}
+
+// JSON context file, or a `.csv` file read as an array of row objects.
+fn read_context(path: &Path) -> Result<serde_json::Value, String> {
+    let data = fs::read_to_string(path)
+        .map_err(|err| format!("failed to read context: {}: {}", path.display(), err))?;
+    let is_csv = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+    if is_csv {
+        return parse_csv_records(&data)
+            .map(serde_json::Value::Array)
+            .map_err(|err| format!("failed to parse context CSV: {}: {}", path.display(), err));
     }
+    serde_json::from_str(&data)
+        .map_err(|err| format!("failed to parse context JSON: {}: {}", path.display(), err))
 }
 
 fn emit_validation_errors(errors: &[RuleError], format: ErrorFormat) {
     match format {
         ErrorFormat::Text => {
             for err in errors {
-                emit_validation_text(err);
+                emit_validation_text("E", &ValidationItem::from(err));
             }
         }
         ErrorFormat::Json => {
             let values: Vec<_> = errors
                 .iter()
-                .map(|err| validation_error_json(err))
+                .map(validation_error_json)
+                .collect();
+            eprintln!("{}", serde_json::to_string(&values).unwrap_or_default());
+        }
+    }
+}
+
+fn emit_validation_warnings(warnings: &[RuleWarning], format: ErrorFormat) {
+    if warnings.is_empty() {
+        return;
+    }
+    let items: Vec<_> = warnings
+        .iter()
+        .map(|warning| ValidationItem {
+            code: warning.code.as_str(),
+            message: &warning.message,
+            path: warning.path.as_deref(),
+            group_path: warning.group_path.as_deref(),
+            file: warning.file.as_deref(),
+            location: warning.location.as_ref(),
+        })
+        .collect();
+    match format {
+        ErrorFormat::Text => {
+            for item in &items {
+                emit_validation_text("W", item);
+            }
+        }
+        ErrorFormat::Json => {
+            let values: Vec<_> = items
+                .iter()
+                .map(|item| {
+                    let mut value = validation_item_json(item);
+                    value["type"] = json!("warning");
+                    value
+                })
                 .collect();
             eprintln!("{}", serde_json::to_string(&values).unwrap_or_default());
         }
     }
 }
 
-fn emit_validation_text(err: &RuleError) {
+// The fields errors and warnings share when printed.
+struct ValidationItem<'a> {
+    code: &'a str,
+    message: &'a str,
+    path: Option<&'a str>,
+    group_path: Option<&'a str>,
+    file: Option<&'a str>,
+    location: Option<&'a YamlLocation>,
+}
+
+impl<'a> From<&'a RuleError> for ValidationItem<'a> {
+    fn from(err: &'a RuleError) -> Self {
+        Self {
+            code: err.code.as_str(),
+            message: &err.message,
+            path: err.path.as_deref(),
+            group_path: err.group_path.as_deref(),
+            file: err.file.as_deref(),
+            location: err.location.as_ref(),
+        }
+    }
+}
+
+fn emit_validation_text(severity: &str, item: &ValidationItem<'_>) {
     let mut parts = Vec::new();
-    parts.push(format!("E {}", err.code.as_str()));
-    if let Some(path) = &err.path {
+    parts.push(format!("{} {}", severity, item.code));
+    if let Some(path) = item.path {
         parts.push(format!("path={}", path));
     }
-    if let Some(location) = &err.location {
+    if let Some(group_path) = item.group_path {
+        parts.push(format!("group_path={}", group_path));
+    }
+    if let Some(file) = item.file {
+        parts.push(format!("file={}", file));
+    }
+    if let Some(location) = item.location {
         parts.push(format!("line={}", location.line));
         parts.push(format!("col={}", location.column));
     }
-    parts.push(format!("msg=\"{}\"", err.message));
+    parts.push(format!("msg=\"{}\"", item.message));
     eprintln!("{}", parts.join(" "));
 }
 
 fn validation_error_json(err: &RuleError) -> serde_json::Value {
+    validation_item_json(&ValidationItem::from(err))
+}
+
+fn validation_item_json(item: &ValidationItem<'_>) -> serde_json::Value {
     let mut value = json!({
         "type": "validation",
-        "code": err.code.as_str(),
-        "message": err.message,
+        "code": item.code,
+        "message": item.message,
     });
 
-    if let Some(path) = &err.path {
+    if let Some(path) = item.path {
         value["path"] = json!(path);
     }
-    if let Some(location) = &err.location {
+    if let Some(group_path) = item.group_path {
+        value["group_path"] = json!(group_path);
+    }
+    if let Some(file) = item.file {
+        value["file"] = json!(file);
+    }
+    if let Some(location) = item.location {
         value["line"] = json!(location.line);
         value["column"] = json!(location.column);
     }
@@ -462,69 +1727,193 @@ fn validation_error_json(err: &RuleError) -> serde_json::Value {
 }
 
 fn emit_transform_error(err: &TransformError, format: ErrorFormat) {
+    emit_file_transform_error(None, err, format);
+}
+
+// `file` labels diagnostics from --input-dir runs with the input path relative to the directory.
+fn emit_file_transform_error(file: Option<&str>, err: &TransformError, format: ErrorFormat) {
     match format {
         ErrorFormat::Text => {
             let mut parts = Vec::new();
-            parts.push(format!("E {}", transform_kind_to_str(&err.kind)));
+            if let Some(file) = file {
+                parts.push(format!("{}:", file));
+            }
+            parts.push(format!(
+                "E {} {}",
+                err.code.as_str(),
+                transform_kind_to_str(&err.kind)
+            ));
             if let Some(path) = &err.path {
                 parts.push(format!("path={}", path));
             }
+            if let Some(group_path) = &err.group_path {
+                parts.push(format!("group_path={}", group_path));
+            }
+            push_record_parts(&mut parts, err.record_index, err.line);
             parts.push(format!("msg=\"{}\"", err.message));
             eprintln!("{}", parts.join(" "));
         }
         ErrorFormat::Json => {
             let mut value = json!({
                 "type": "transform",
+                "code": err.code.as_str(),
                 "kind": transform_kind_to_str(&err.kind),
                 "message": err.message,
             });
             if let Some(path) = &err.path {
                 value["path"] = json!(path);
             }
+            if let Some(group_path) = &err.group_path {
+                value["group_path"] = json!(group_path);
+            }
+            insert_record_fields(&mut value, err.record_index, err.line);
+            if let Some(file) = file {
+                value["file"] = json!(file);
+            }
             eprintln!("{}", serde_json::to_string(&vec![value]).unwrap_or_default());
         }
     }
 }
 
-fn emit_transform_warnings(warnings: &[TransformWarning], format: ErrorFormat) {
-    if warnings.is_empty() {
+fn emit_transform_warnings(warnings: &Warnings, format: ErrorFormat) {
+    emit_file_transform_warnings(None, warnings, format);
+}
+
+fn emit_file_transform_warnings(file: Option<&str>, warnings: &Warnings, format: ErrorFormat) {
+    let (groups, dropped): (Cow<[WarningGroup]>, usize) = match warnings {
+        Warnings::Each(each) => {
+            let groups = each
+                .iter()
+                .map(|warning| WarningGroup {
+                    warning: warning.clone(),
+                    count: 1,
+                    first_record_index: warning.record_index,
+                    last_record_index: warning.record_index,
+                })
+                .collect();
+            (Cow::Owned(groups), 0)
+        }
+        Warnings::Grouped(summary) => (Cow::Borrowed(&summary.groups), summary.dropped),
+    };
+    if groups.is_empty() && dropped == 0 {
         return;
     }
 
     match format {
         ErrorFormat::Text => {
-            for warning in warnings {
+            for group in groups.iter() {
+                let warning = &group.warning;
                 let mut parts = Vec::new();
-                parts.push(format!("W {}", transform_kind_to_str(&warning.kind)));
+                if let Some(file) = file {
+                    parts.push(format!("{}:", file));
+                }
+                parts.push(format!(
+                    "W {} {}",
+                    warning.code.as_str(),
+                    transform_kind_to_str(&warning.kind)
+                ));
                 if let Some(path) = &warning.path {
                     parts.push(format!("path={}", path));
                 }
+                if let Some(group_path) = &warning.group_path {
+                    parts.push(format!("group_path={}", group_path));
+                }
+                if group.count == 1 {
+                    push_record_parts(&mut parts, warning.record_index, warning.line);
+                } else {
+                    parts.push(format!("count={}", group.count));
+                    if let (Some(first), Some(last)) =
+                        (group.first_record_index, group.last_record_index)
+                    {
+                        parts.push(format!("rows={}..{}", first, last));
+                    }
+                }
                 parts.push(format!("msg=\"{}\"", warning.message));
                 eprintln!("{}", parts.join(" "));
             }
+            if dropped > 0 {
+                let prefix = file.map(|file| format!("{}: ", file)).unwrap_or_default();
+                eprintln!(
+                    "{}W count={} msg=\"further distinct warnings were not itemized\"",
+                    prefix, dropped
+                );
+            }
         }
         ErrorFormat::Json => {
-            let values: Vec<_> = warnings
+            let mut values: Vec<_> = groups
                 .iter()
-                .map(|warning| transform_warning_json(warning))
+                .map(|group| {
+                    let mut value = transform_warning_json(&group.warning);
+                    if matches!(warnings, Warnings::Grouped(_)) {
+                        insert_group_fields(&mut value, group);
+                    }
+                    if let Some(file) = file {
+                        value["file"] = json!(file);
+                    }
+                    value
+                })
                 .collect();
+            if dropped > 0 {
+                let mut value = json!({ "type": "warning", "dropped": dropped });
+                if let Some(file) = file {
+                    value["file"] = json!(file);
+                }
+                values.push(value);
+            }
             eprintln!("{}", serde_json::to_string(&values).unwrap_or_default());
         }
     }
 }
 
+fn insert_group_fields(value: &mut serde_json::Value, group: &WarningGroup) {
+    value["count"] = json!(group.count);
+    if let Some(first) = group.first_record_index {
+        value["first_record_index"] = json!(first);
+    }
+    if let Some(last) = group.last_record_index {
+        value["last_record_index"] = json!(last);
+    }
+}
+
 fn transform_warning_json(warning: &TransformWarning) -> serde_json::Value {
     let mut value = json!({
         "type": "warning",
+        "code": warning.code.as_str(),
         "kind": transform_kind_to_str(&warning.kind),
         "message": warning.message,
     });
     if let Some(path) = &warning.path {
         value["path"] = json!(path);
     }
+    if let Some(group_path) = &warning.group_path {
+        value["group_path"] = json!(group_path);
+    }
+    insert_record_fields(&mut value, warning.record_index, warning.line);
     value
 }
 
+fn push_record_parts(parts: &mut Vec<String>, record_index: Option<usize>, line: Option<usize>) {
+    if let Some(record_index) = record_index {
+        parts.push(format!("row={}", record_index));
+    }
+    if let Some(line) = line {
+        parts.push(format!("line={}", line));
+    }
+}
+
+fn insert_record_fields(
+    value: &mut serde_json::Value,
+    record_index: Option<usize>,
+    line: Option<usize>,
+) {
+    if let Some(record_index) = record_index {
+        value["record_index"] = json!(record_index);
+    }
+    if let Some(line) = line {
+        value["line"] = json!(line);
+    }
+}
+
 fn transform_kind_to_str(kind: &TransformErrorKind) -> &'static str {
     match kind {
         TransformErrorKind::InvalidInput => "InvalidInput",
@@ -534,5 +1923,6 @@ fn transform_kind_to_str(kind: &TransformErrorKind) -> &'static str {
         TransformErrorKind::MissingRequired => "MissingRequired",
         TransformErrorKind::TypeCastFailed => "TypeCastFailed",
         TransformErrorKind::ExprError => "ExprError",
+        TransformErrorKind::Degraded => "Degraded",
     }
 }
@@ -54,6 +54,221 @@ fn validate_json_errors() {
     assert_eq!(value[0]["code"], "MissingMappingValue");
 }
 
+#[test]
+fn transform_resolves_extends() {
+    let base = fixtures_dir().join("extends");
+    let mut cmd = cargo_bin_cmd!("transform-rules");
+    let output = cmd
+        .arg("transform")
+        .arg("-r")
+        .arg(base.join("tenant.yaml"))
+        .arg("-i")
+        .arg("-")
+        .write_stdin("id;name;tenant\n1;alice;t1\n")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let stdout: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(stdout[0]["status"], "tenant");
+    assert_eq!(stdout[0]["audit"]["source"], "audit");
+
+    let mut cmd = cargo_bin_cmd!("transform-rules");
+    let output = cmd
+        .arg("validate")
+        .arg("-r")
+        .arg(base.join("invalid_tenant.yaml"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("E UnknownOp path=mappings[1].expr.op file="), "{}", stderr);
+    assert!(stderr.contains("invalid_base.yaml line=10"), "{}", stderr);
+
+    let mut cmd = cargo_bin_cmd!("transform-rules");
+    let output = cmd
+        .arg("validate")
+        .arg("-r")
+        .arg(base.join("cycle_a.yaml"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("extends cycle: "), "{}", stderr);
+}
+
+#[test]
+fn validate_allow_unknown_ops_reports_warnings() {
+    let rules = fixtures_dir().join("v05_unknown_op").join("rules.yaml");
+    let mut cmd = cargo_bin_cmd!("transform-rules");
+    let output = cmd.arg("validate").arg("-r").arg(&rules).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+
+    let mut cmd = cargo_bin_cmd!("transform-rules");
+    let output = cmd
+        .arg("validate")
+        .arg("-r")
+        .arg(&rules)
+        .arg("--allow-unknown-ops")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("W unknown_op path=mappings[0].expr.op"), "{}", stderr);
+}
+
+#[test]
+fn validate_prints_rule_warnings_and_denies_them_on_request() {
+    let dir = tempfile::tempdir().unwrap();
+    let rules = dir.path().join("rules.yaml");
+    fs::write(
+        &rules,
+        "version: 1\ninput:\n  format: json\n  json: {}\nmappings:\n  - target: \"id\"\n    source: \"id\"\n    when: true\n",
+    )
+    .unwrap();
+
+    let mut cmd = cargo_bin_cmd!("transform-rules");
+    let output = cmd.arg("validate").arg("-r").arg(&rules).output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(
+        stderr.trim_end(),
+        "W constant_when path=mappings[0].when line=8 col=5 msg=\"condition is always true\""
+    );
+
+    let mut cmd = cargo_bin_cmd!("transform-rules");
+    let output = cmd
+        .arg("validate")
+        .arg("-r")
+        .arg(&rules)
+        .arg("--deny-warnings")
+        .arg("-e")
+        .arg("json")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    let value: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(value[0]["type"], "warning");
+    assert_eq!(value[0]["code"], "constant_when");
+    assert_eq!(value[0]["line"], 8);
+}
+
+#[test]
+fn validate_locates_errors_inside_expressions() {
+    let rules = fixtures_dir().join("v34_expr_locations").join("rules.yaml");
+    let mut cmd = cargo_bin_cmd!("transform-rules");
+    let output = cmd.arg("validate").arg("-r").arg(&rules).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("E InvalidExprShape path=mappings[1].expr.chain[1] line=21 col=9 msg="),
+        "{}",
+        stderr
+    );
+
+    let mut cmd = cargo_bin_cmd!("transform-rules");
+    let output = cmd
+        .arg("validate")
+        .arg("-r")
+        .arg(&rules)
+        .arg("-e")
+        .arg("json")
+        .output()
+        .unwrap();
+    let value: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    let error = value
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|error| error["path"] == "mappings[2].expr.args[1].args[0].op")
+        .expect("nested unknown op");
+    assert_eq!(error["line"], 28);
+    assert_eq!(error["column"], 21);
+}
+
+#[test]
+fn transform_threads_keeps_output_and_warns_on_group() {
+    let rules = fixtures_dir().join("t01_csv_basic").join("rules.yaml");
+    let input = fixtures_dir().join("t01_csv_basic").join("input.csv");
+    let run = |threads: &str| {
+        let mut cmd = cargo_bin_cmd!("transform-rules");
+        cmd.arg("transform")
+            .arg("-r")
+            .arg(&rules)
+            .arg("-i")
+            .arg(&input)
+            .arg("--threads")
+            .arg(threads)
+            .output()
+            .unwrap()
+    };
+    let sequential = run("1");
+    let parallel = run("4");
+    assert_eq!(parallel.status.code(), Some(0));
+    assert_eq!(parallel.stdout, sequential.stdout);
+    assert!(parallel.stderr.is_empty());
+
+    let dir = tempfile::tempdir().unwrap();
+    let rules = dir.path().join("rules.yaml");
+    fs::write(
+        &rules,
+        "version: 1\ninput:\n  format: json\ngroup:\n  key: { ref: \"input.team\" }\nmappings:\n  - target: \"team\"\n    source: \"key\"\n",
+    )
+    .unwrap();
+    let mut cmd = cargo_bin_cmd!("transform-rules");
+    let output = cmd
+        .arg("transform")
+        .arg("-r")
+        .arg(&rules)
+        .arg("-i")
+        .arg("-")
+        .arg("--threads")
+        .arg("2")
+        .write_stdin(r#"[{ "team": "a" }, { "team": "a" }]"#)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let stdout: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(stdout, serde_json::json!([{ "team": "a" }]));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("W T1501 Degraded path=group"), "{}", stderr);
+    assert!(stderr.contains("rules with group are transformed on one thread"), "{}", stderr);
+}
+
+#[test]
+fn transform_reads_env_refs_only_with_allow_env() {
+    let dir = tempfile::tempdir().unwrap();
+    let rules = dir.path().join("rules.yaml");
+    fs::write(
+        &rules,
+        "version: 1\ninput:\n  format: json\n  json: {}\nmappings:\n  - target: \"tenant\"\n    source: \"env.TR_TEST_TENANT\"\n",
+    )
+    .unwrap();
+    let run = |allow_env: bool| {
+        let mut cmd = cargo_bin_cmd!("transform-rules");
+        cmd.arg("transform")
+            .arg("-r")
+            .arg(&rules)
+            .arg("-i")
+            .arg("-")
+            .env("TR_TEST_TENANT", "t-001")
+            .write_stdin("[{}]");
+        if allow_env {
+            cmd.arg("--allow-env");
+        }
+        cmd.output().unwrap()
+    };
+
+    let output = run(true);
+    assert_eq!(output.status.code(), Some(0));
+    let stdout: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(stdout, serde_json::json!([{ "tenant": "t-001" }]));
+
+    let output = run(false);
+    assert_eq!(output.status.code(), Some(3));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("env namespace is disabled"), "{}", stderr);
+}
+
 #[test]
 fn preflight_success_returns_zero() {
     let base = fixtures_dir().join("p01_preflight_ok");
@@ -94,6 +309,40 @@ fn preflight_json_errors() {
         .unwrap_or_else(|_| panic!("invalid json stderr: {}", stderr));
     assert_eq!(value[0]["type"], "transform");
     assert_eq!(value[0]["kind"], "TypeCastFailed");
+    assert_eq!(value[0]["code"], "T1401");
+}
+
+#[test]
+fn transform_errors_report_stable_codes() {
+    let base = fixtures_dir().join("r23_invalid_timezone");
+    let run = |format: &str| {
+        let mut cmd = cargo_bin_cmd!("transform-rules");
+        cmd.arg("transform")
+            .arg("-r")
+            .arg(base.join("rules.yaml"))
+            .arg("-i")
+            .arg(base.join("input.json"))
+            .arg("-e")
+            .arg(format)
+            .output()
+            .unwrap()
+    };
+
+    let output = run("text");
+    assert_eq!(output.status.code(), Some(3));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.starts_with("E T1405 ExprError path=mappings[0].expr.args[2]"),
+        "{}",
+        stderr
+    );
+
+    let output = run("json");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let value: serde_json::Value = serde_json::from_str(&stderr)
+        .unwrap_or_else(|_| panic!("invalid json stderr: {}", stderr));
+    assert_eq!(value[0]["code"], "T1405");
+    assert_eq!(value[0]["kind"], "ExprError");
 }
 
 #[test]
@@ -147,6 +396,56 @@ fn transform_outputs_ndjson() {
     assert_eq!(stdout, expected);
 }
 
+#[test]
+fn transform_format_override_reads_ndjson_input() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let rules = temp_dir.path().join("rules.yaml");
+    let input = temp_dir.path().join("input.ndjson");
+    fs::write(
+        &rules,
+        r#"version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  - target: "id"
+    source: "id"
+"#,
+    )
+    .unwrap();
+    fs::write(&input, "{\"id\":1}\n\n{\"id\":2}\n").unwrap();
+
+    let output = cargo_bin_cmd!("transform-rules")
+        .arg("transform")
+        .arg("-r")
+        .arg(&rules)
+        .arg("-i")
+        .arg(&input)
+        .arg("--format")
+        .arg("ndjson")
+        .arg("--ndjson")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, "{\"id\":1}\n{\"id\":2}\n");
+
+    fs::write(&input, "{\"id\":1}\n{\"id\":\n").unwrap();
+    let output = cargo_bin_cmd!("transform-rules")
+        .arg("transform")
+        .arg("-r")
+        .arg(&rules)
+        .arg("-i")
+        .arg(&input)
+        .arg("--format")
+        .arg("ndjson")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("failed to parse NDJSON input at line 2"));
+}
+
 #[test]
 fn transform_writes_output_file() {
     let base = fixtures_dir().join("t01_csv_basic");
@@ -242,3 +541,1038 @@ fn generate_outputs_rust_dto() {
     let stdout = String::from_utf8(output.stdout).unwrap();
     assert!(stdout.contains("struct Record"));
 }
+
+#[test]
+fn generate_outputs_json_schema() {
+    let rules = fixtures_dir().join("t01_csv_basic").join("rules.yaml");
+
+    let mut cmd = cargo_bin_cmd!("transform-rules");
+    let output = cmd
+        .arg("generate")
+        .arg("-r")
+        .arg(rules)
+        .arg("-l")
+        .arg("jsonschema")
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    let schema: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(schema["$schema"], "https://json-schema.org/draft/2020-12/schema");
+    assert_eq!(schema["type"], "object");
+}
+
+#[test]
+fn check_exits_zero_when_output_matches() {
+    let base = fixtures_dir().join("t01_csv_basic");
+
+    let mut cmd = cargo_bin_cmd!("transform-rules");
+    let output = cmd
+        .arg("check")
+        .arg("-r")
+        .arg(base.join("rules.yaml"))
+        .arg("-i")
+        .arg(base.join("input.csv"))
+        .arg("--expected")
+        .arg(base.join("expected.json"))
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn trace_prints_one_record_as_a_tree_or_json() {
+    let base = fixtures_dir().join("t01_csv_basic");
+    let run = |args: &[&str]| {
+        let mut cmd = cargo_bin_cmd!("transform-rules");
+        cmd.arg("trace")
+            .arg("-r")
+            .arg(base.join("rules.yaml"))
+            .arg("-i")
+            .arg(base.join("input.csv"))
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let output = run(&[]);
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.starts_with("record 0\n  mappings[0] -> id\n    step 0: \"001\"\n"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("  mappings[2] -> price\n    step 0: \"100\"\n    wrote price: 100.0\n"),
+        "{}",
+        stdout
+    );
+
+    let output = run(&["-e", "json"]);
+    assert_eq!(output.status.code(), Some(0));
+    let trace: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(trace["record_index"], 0);
+    assert_eq!(trace["mappings"][2]["value"], "100.0");
+    assert_eq!(trace["output"]["price"], 100.0);
+
+    let output = run(&["--record", "1"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stderr).trim(), "input has no record 1");
+}
+
+#[test]
+fn check_reports_differences_as_json() {
+    let base = fixtures_dir().join("t01_csv_basic");
+    let temp_dir = tempfile::tempdir().unwrap();
+    let expected = temp_dir.path().join("expected.json");
+    fs::write(
+        &expected,
+        r#"[{ "id": "001", "name": "Banana", "price": 100, "stock": 3 }]"#,
+    )
+    .unwrap();
+
+    let mut cmd = cargo_bin_cmd!("transform-rules");
+    let output = cmd
+        .arg("check")
+        .arg("-r")
+        .arg(base.join("rules.yaml"))
+        .arg("-i")
+        .arg(base.join("input.csv"))
+        .arg("-x")
+        .arg(&expected)
+        .arg("--array-key")
+        .arg("id")
+        .arg("-e")
+        .arg("json")
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(4));
+    let diff: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(
+        diff,
+        serde_json::json!([
+            {
+                "type": "diff",
+                "kind": "changed",
+                "path": "[id=\"001\"].name",
+                "expected": "Banana",
+                "actual": "Apple"
+            },
+            {
+                "type": "diff",
+                "kind": "missing",
+                "path": "[id=\"001\"].stock",
+                "expected": 3
+            }
+        ])
+    );
+}
+
+#[test]
+fn transform_writes_trace_ndjson() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let rules = temp_dir.path().join("rules.yaml");
+    let input = temp_dir.path().join("input.json");
+    let trace_path = temp_dir.path().join("trace").join("trace.ndjson");
+    fs::write(
+        &rules,
+        r#"version: 1
+input:
+  format: json
+mappings:
+  - target: "id"
+    source: "id"
+  - target: "name"
+    expr:
+      chain:
+        - { ref: "input.name" }
+        - { op: "trim" }
+"#,
+    )
+    .unwrap();
+    fs::write(
+        &input,
+        r#"[{ "id": 1, "name": "a" }, { "id": 2, "name": " b " }]"#,
+    )
+    .unwrap();
+
+    let mut cmd = cargo_bin_cmd!("transform-rules");
+    let output = cmd
+        .arg("transform")
+        .arg("-r")
+        .arg(&rules)
+        .arg("-i")
+        .arg(&input)
+        .arg("--trace-expr")
+        .arg("na*")
+        .arg("--trace-out")
+        .arg(&trace_path)
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    let contents = fs::read_to_string(&trace_path)
+        .unwrap_or_else(|_| panic!("failed to read {}", trace_path.display()));
+    let lines: Vec<serde_json::Value> = contents
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("invalid trace line"))
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines
+        .iter()
+        .all(|line| line["record_index"] == 1 && line["target"] == "name"));
+    assert_eq!(lines[1]["op"], "trim");
+    assert_eq!(lines[1]["value"], "\"b\"");
+}
+
+#[test]
+fn transform_strict_utf8_defaults_by_format() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let csv_rules = temp_dir.path().join("csv_rules.yaml");
+    let json_rules = temp_dir.path().join("json_rules.yaml");
+    let csv_input = temp_dir.path().join("input.csv");
+    let json_input = temp_dir.path().join("input.json");
+    fs::write(
+        &csv_rules,
+        r#"version: 1
+input:
+  format: csv
+  csv:
+    has_header: true
+mappings:
+  - target: "id"
+    source: "id"
+"#,
+    )
+    .unwrap();
+    fs::write(
+        &json_rules,
+        r#"version: 1
+input:
+  format: json
+mappings:
+  - target: "id"
+    source: "id"
+"#,
+    )
+    .unwrap();
+    fs::write(&csv_input, b"id,note\n1,caf\xe9\n").unwrap();
+    fs::write(&json_input, b"[{ \"id\": 1, \"note\": \"caf\xe9\" }]").unwrap();
+
+    let output = cargo_bin_cmd!("transform-rules")
+        .arg("transform")
+        .arg("-r")
+        .arg(&csv_rules)
+        .arg("-i")
+        .arg(&csv_input)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = cargo_bin_cmd!("transform-rules")
+        .arg("transform")
+        .arg("-r")
+        .arg(&csv_rules)
+        .arg("-i")
+        .arg(&csv_input)
+        .arg("--strict-utf8")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("invalid UTF-8 at byte offset 13"));
+
+    let output = cargo_bin_cmd!("transform-rules")
+        .arg("transform")
+        .arg("-r")
+        .arg(&json_rules)
+        .arg("-i")
+        .arg(&json_input)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("invalid UTF-8 at byte offset 24"));
+
+    let output = cargo_bin_cmd!("transform-rules")
+        .arg("transform")
+        .arg("-r")
+        .arg(&json_rules)
+        .arg("-i")
+        .arg(&json_input)
+        .arg("--strict-utf8=false")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn doctor_reports_passing_checks() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let mut cmd = cargo_bin_cmd!("transform-rules");
+    let output = cmd
+        .current_dir(temp_dir.path())
+        .arg("doctor")
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("cache capacities: rules="));
+    assert!(stdout.contains("transform_sample"));
+    assert!(!stdout.contains("FAIL"));
+    assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn doctor_json_reports_injected_failure() {
+    let mut cmd = cargo_bin_cmd!("transform-rules");
+    let output = cmd
+        .env("TRANSFORM_RULES_SELF_CHECK_FAIL", "regex")
+        .arg("doctor")
+        .arg("--json")
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(5));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let value: serde_json::Value = serde_json::from_str(&stdout)
+        .unwrap_or_else(|_| panic!("invalid json stdout: {}", stdout));
+    assert_eq!(value["ok"], false);
+    assert_eq!(value["environment"]["version"], env!("CARGO_PKG_VERSION"));
+    let checks = value["checks"].as_array().expect("checks array");
+    let failed: Vec<_> = checks
+        .iter()
+        .filter(|check| check["status"] == "fail")
+        .collect();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0]["name"], "regex");
+    assert!(failed[0]["message"].is_string());
+    assert!(failed[0]["duration_ms"].is_number());
+}
+
+fn write_dir_rules(dir: &std::path::Path) -> PathBuf {
+    let rules = dir.join("rules.yaml");
+    fs::write(
+        &rules,
+        r#"version: 1
+input:
+  format: csv
+  csv:
+    has_header: true
+mappings:
+  - target: "id"
+    source: "id"
+    type: "int"
+  - target: "tenant"
+    source: "context.tenant"
+"#,
+    )
+    .unwrap();
+    rules
+}
+
+fn write_named_context_rules(dir: &std::path::Path) -> PathBuf {
+    let rules = dir.join("rules.yaml");
+    fs::write(
+        &rules,
+        r#"version: 1
+input:
+  format: json
+mappings:
+  - target: "country"
+    expr:
+      op: "lookup_first"
+      args: [ { ref: "context.countries" }, "code", { ref: "input.country" }, "name" ]
+  - target: "store"
+    expr:
+      op: "lookup_first"
+      args: [ { ref: "context.stores" }, "id", { ref: "input.store" }, "city" ]
+"#,
+    )
+    .unwrap();
+    fs::write(
+        dir.join("countries.json"),
+        r#"[{ "code": "JP", "name": "Japan" }]"#,
+    )
+    .unwrap();
+    fs::write(dir.join("stores.csv"), "id,city\n7,Osaka\n8,Kyoto\n").unwrap();
+    rules
+}
+
+#[test]
+fn transform_builds_context_from_named_files() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let rules = write_named_context_rules(temp_dir.path());
+
+    let output = cargo_bin_cmd!("transform-rules")
+        .arg("transform")
+        .arg("-r")
+        .arg(&rules)
+        .arg("-i")
+        .arg("-")
+        .arg("-c")
+        .arg(format!("countries={}", temp_dir.path().join("countries.json").display()))
+        .arg("-c")
+        .arg(format!("stores={}", temp_dir.path().join("stores.csv").display()))
+        .write_stdin(r#"[{ "country": "JP", "store": 8 }]"#)
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    let value: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(value, serde_json::json!([{ "country": "Japan", "store": "Kyoto" }]));
+}
+
+#[test]
+fn transform_rejects_conflicting_or_missing_context_files() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let rules = write_named_context_rules(temp_dir.path());
+    let countries = temp_dir.path().join("countries.json");
+    let missing = temp_dir.path().join("missing.csv");
+
+    let output = cargo_bin_cmd!("transform-rules")
+        .arg("transform")
+        .arg("-r")
+        .arg(&rules)
+        .arg("-i")
+        .arg("-")
+        .arg("-c")
+        .arg(format!("countries={}", countries.display()))
+        .arg("-c")
+        .arg(format!("countries={}", missing.display()))
+        .write_stdin("[]")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("duplicate context name: countries"), "{}", stderr);
+
+    let output = cargo_bin_cmd!("transform-rules")
+        .arg("transform")
+        .arg("-r")
+        .arg(&rules)
+        .arg("-i")
+        .arg("-")
+        .arg("-c")
+        .arg(format!("countries={}", countries.display()))
+        .arg("-c")
+        .arg(format!("stores={}", missing.display()))
+        .write_stdin("[]")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("failed to read context: "), "{}", stderr);
+    assert!(stderr.contains(&missing.display().to_string()), "{}", stderr);
+}
+
+#[test]
+fn transform_reads_declared_tables_from_named_files() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let rules = temp_dir.path().join("rules.yaml");
+    fs::write(
+        &rules,
+        r#"version: 1
+input:
+  format: json
+tables:
+  products:
+    format: ndjson
+    fields: [ "sku", "price" ]
+    key: "sku"
+  stores:
+    format: csv
+    fields: [ "id" ]
+mappings:
+  - target: "price"
+    expr:
+      op: "lookup_first"
+      args: [ { ref: "context.products" }, "sku", { ref: "input.sku" }, "price" ]
+  - target: "city"
+    expr:
+      op: "lookup_first"
+      args: [ { ref: "context.stores" }, "id", { ref: "input.store" }, "city" ]
+"#,
+    )
+    .unwrap();
+    let products = temp_dir.path().join("products.ndjson");
+    fs::write(
+        &products,
+        "{\"sku\": \"A\", \"price\": 3, \"notes\": \"x\"}\n{\"sku\": \"B\", \"price\": 5}\n",
+    )
+    .unwrap();
+    let stores = temp_dir.path().join("stores.csv");
+    fs::write(&stores, "id,city\n7,Osaka\n8,Kyoto\n").unwrap();
+
+    for extra in [None, Some("--ndjson")] {
+        let mut cmd = cargo_bin_cmd!("transform-rules");
+        cmd.arg("transform")
+            .arg("-r")
+            .arg(&rules)
+            .arg("-i")
+            .arg("-")
+            .arg("-c")
+            .arg(format!("products={}", products.display()))
+            .arg("-c")
+            .arg(format!("stores={}", stores.display()));
+        let input = match extra {
+            Some(flag) => {
+                cmd.arg(flag);
+                "{\"sku\": \"B\", \"store\": 8}\n"
+            }
+            None => r#"[{ "sku": "B", "store": 8 }]"#,
+        };
+        let output = cmd.write_stdin(input).output().unwrap();
+
+        assert_eq!(output.status.code(), Some(0));
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let value: serde_json::Value = serde_json::from_str(stdout.trim()).unwrap();
+        let record = value.as_array().map_or(&value, |records| &records[0]);
+        assert_eq!(record, &serde_json::json!({ "price": 5, "city": "Kyoto" }));
+        // `city` is read from stores but not declared, so full rows are kept.
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.starts_with("W T1502 Degraded path=tables.stores"),
+            "{}",
+            stderr
+        );
+        assert!(stderr.contains("lookup output_path city is not in fields"), "{}", stderr);
+    }
+
+    fs::write(&products, "{\"sku\": \"A\"}\n{\"sku\"\n").unwrap();
+    let output = cargo_bin_cmd!("transform-rules")
+        .arg("transform")
+        .arg("-r")
+        .arg(&rules)
+        .arg("-i")
+        .arg("-")
+        .arg("-c")
+        .arg(format!("products={}", products.display()))
+        .write_stdin("[]")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("failed to load table: "), "{}", stderr);
+    assert!(stderr.contains("(line 2)"), "{}", stderr);
+}
+
+#[test]
+fn transform_input_dir_mirrors_tree() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let rules = write_dir_rules(temp_dir.path());
+    let input_dir = temp_dir.path().join("in");
+    let output_dir = temp_dir.path().join("out");
+    let context = temp_dir.path().join("context.json");
+    fs::create_dir_all(input_dir.join("stores").join("002")).unwrap();
+    fs::write(input_dir.join("top.csv"), "id\n1\n").unwrap();
+    fs::write(input_dir.join("stores").join("002").join("orders.csv"), "id\n2\n3\n").unwrap();
+    fs::write(input_dir.join("stores").join("notes.txt"), "ignored").unwrap();
+    fs::write(&context, r#"{ "tenant": "shared" }"#).unwrap();
+
+    let output = cargo_bin_cmd!("transform-rules")
+        .arg("transform")
+        .arg("-r")
+        .arg(&rules)
+        .arg("--input-dir")
+        .arg(&input_dir)
+        .arg("--output-dir")
+        .arg(&output_dir)
+        .arg("-c")
+        .arg(&context)
+        .arg("--output-ext")
+        .arg("ndjson")
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("ok   stores/002/orders.csv -> stores/002/orders.ndjson (2 records)"));
+    assert!(stdout.contains("ok   top.csv -> top.ndjson (1 records)"));
+    assert!(stdout.contains("files: 2 ok, 0 failed; records: 3"));
+
+    let nested = fs::read_to_string(output_dir.join("stores").join("002").join("orders.ndjson"))
+        .unwrap();
+    let lines: Vec<serde_json::Value> = nested
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(
+        lines,
+        vec![
+            serde_json::json!({ "id": 2, "tenant": "shared" }),
+            serde_json::json!({ "id": 3, "tenant": "shared" }),
+        ]
+    );
+    assert!(output_dir.join("top.ndjson").is_file());
+    assert!(!output_dir.join("stores").join("notes.json").exists());
+    assert!(!output_dir.join("stores").join("notes.ndjson").exists());
+}
+
+#[test]
+fn transform_input_dir_uses_per_file_context() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let rules = write_dir_rules(temp_dir.path());
+    let input_dir = temp_dir.path().join("in");
+    let output_dir = temp_dir.path().join("out");
+    let context = temp_dir.path().join("context.json");
+    fs::create_dir_all(input_dir.join("b")).unwrap();
+    fs::write(input_dir.join("a.csv"), "id\n1\n").unwrap();
+    fs::write(input_dir.join("b").join("orders.csv"), "id\n2\n").unwrap();
+    fs::write(
+        input_dir.join("b").join("orders.context.json"),
+        r#"{ "tenant": "beta" }"#,
+    )
+    .unwrap();
+    fs::write(&context, r#"{ "tenant": "shared" }"#).unwrap();
+
+    let output = cargo_bin_cmd!("transform-rules")
+        .arg("transform")
+        .arg("-r")
+        .arg(&rules)
+        .arg("--input-dir")
+        .arg(&input_dir)
+        .arg("--output-dir")
+        .arg(&output_dir)
+        .arg("-c")
+        .arg(&context)
+        .arg("--include")
+        .arg("**/*")
+        .arg("--per-file-context")
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        read_json(&output_dir.join("a.json")),
+        serde_json::json!([{ "id": 1, "tenant": "shared" }])
+    );
+    assert_eq!(
+        read_json(&output_dir.join("b").join("orders.json")),
+        serde_json::json!([{ "id": 2, "tenant": "beta" }])
+    );
+    assert!(!output_dir.join("b").join("orders.context.json").exists());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("files: 2 ok, 0 failed; records: 2"));
+}
+
+#[test]
+fn transform_input_dir_continues_or_fails_fast() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let rules = write_dir_rules(temp_dir.path());
+    let input_dir = temp_dir.path().join("in");
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("a.csv"), "id\n1\n2\n").unwrap();
+    fs::write(input_dir.join("b.csv"), "id\nx\n").unwrap();
+    fs::write(input_dir.join("c.csv"), "id\n3\n").unwrap();
+
+    let output_dir = temp_dir.path().join("continue");
+    let output = cargo_bin_cmd!("transform-rules")
+        .arg("transform")
+        .arg("-r")
+        .arg(&rules)
+        .arg("--input-dir")
+        .arg(&input_dir)
+        .arg("--output-dir")
+        .arg(&output_dir)
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(3));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stdout.contains("FAIL b.csv"));
+    assert!(stdout.contains("files: 2 ok, 1 failed; records: 3"));
+    assert!(stderr.contains("b.csv: E T1401 TypeCastFailed"));
+    assert!(output_dir.join("c.json").is_file());
+    assert!(!output_dir.join("b.json").exists());
+
+    let output_dir = temp_dir.path().join("fail_fast");
+    let output = cargo_bin_cmd!("transform-rules")
+        .arg("transform")
+        .arg("-r")
+        .arg(&rules)
+        .arg("--input-dir")
+        .arg(&input_dir)
+        .arg("--output-dir")
+        .arg(&output_dir)
+        .arg("--fail-fast")
+        .arg("-e")
+        .arg("json")
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(3));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stdout.contains("files: 1 ok, 1 failed; records: 2"));
+    let errors: serde_json::Value = serde_json::from_str(stderr.trim()).unwrap();
+    assert_eq!(errors[0]["file"], "b.csv");
+    assert_eq!(errors[0]["record_index"], 0);
+    assert_eq!(errors[0]["line"], 2);
+    assert!(output_dir.join("a.json").is_file());
+    assert!(!output_dir.join("c.json").exists());
+}
+
+#[test]
+fn transform_on_error_reports_failed_rows() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let rules = temp_dir.path().join("rules.yaml");
+    let input = temp_dir.path().join("input.csv");
+    let write_rules = |on_error: &str| {
+        fs::write(
+            &rules,
+            format!(
+                r#"version: 1
+input:
+  format: csv
+  csv:
+    has_header: true
+on_error: {}
+mappings:
+  - target: "id"
+    source: "id"
+    type: "int"
+"#,
+                on_error
+            ),
+        )
+        .unwrap();
+    };
+    fs::write(&input, "id\n1\nx\n3\ny\n").unwrap();
+
+    write_rules("collect");
+    let output = cargo_bin_cmd!("transform-rules")
+        .arg("transform")
+        .arg("-r")
+        .arg(&rules)
+        .arg("-i")
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let actual: serde_json::Value = serde_json::from_str(stdout.trim()).unwrap();
+    assert_eq!(actual, serde_json::json!([{ "id": 1 }, { "id": 3 }]));
+    assert_eq!(stderr.matches("E T1401 TypeCastFailed").count(), 2);
+    assert!(stderr.contains("path=mappings[0].type row=1 line=3 msg="));
+    assert!(stderr.contains("row=3 line=5"));
+    assert!(stderr.ends_with("2 rows failed\n"));
+
+    write_rules("skip");
+    let output = cargo_bin_cmd!("transform-rules")
+        .arg("transform")
+        .arg("-r")
+        .arg(&rules)
+        .arg("-i")
+        .arg(&input)
+        .arg("--ndjson")
+        .arg("--max-errors")
+        .arg("1")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stdout, "{\"id\":1}\n{\"id\":3}\n");
+    assert_eq!(stderr, "2 rows failed\n");
+
+    let output = cargo_bin_cmd!("transform-rules")
+        .arg("transform")
+        .arg("-r")
+        .arg(&rules)
+        .arg("-i")
+        .arg(&input)
+        .arg("--max-errors")
+        .arg("2")
+        .arg("-e")
+        .arg("json")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let summary: serde_json::Value = serde_json::from_str(stderr.trim()).unwrap();
+    assert_eq!(summary[0]["type"], "summary");
+    assert_eq!(summary[0]["failed_rows"], 2);
+}
+
+#[test]
+fn transform_writes_csv_output() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let rules = temp_dir.path().join("rules.yaml");
+    let input = temp_dir.path().join("input.json");
+    fs::write(
+        &rules,
+        r#"version: 1
+input:
+  format: json
+  json: {}
+output:
+  format: csv
+  columns: [ "name", "id" ]
+mappings:
+  - target: "id"
+    source: "id"
+  - target: "name"
+    source: "name"
+"#,
+    )
+    .unwrap();
+    fs::write(&input, r#"[{ "id": 1, "name": "Ada, Countess" }, { "id": 2, "name": "Bob" }]"#).unwrap();
+
+    let output = cargo_bin_cmd!("transform-rules")
+        .arg("transform")
+        .arg("-r")
+        .arg(&rules)
+        .arg("-i")
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, "name,id\n\"Ada, Countess\",1\nBob,2\n");
+
+    // The flag overrides the rule file.
+    let output = cargo_bin_cmd!("transform-rules")
+        .arg("transform")
+        .arg("-r")
+        .arg(&rules)
+        .arg("-i")
+        .arg(&input)
+        .arg("--output-format")
+        .arg("json")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let actual: serde_json::Value = serde_json::from_str(stdout.trim()).unwrap();
+    assert_eq!(actual[1], serde_json::json!({ "id": 2, "name": "Bob" }));
+}
+
+#[test]
+fn transform_output_format_flag_derives_csv_columns() {
+    let rules = fixtures_dir().join("t01_csv_basic").join("rules.yaml");
+    let input = fixtures_dir().join("t01_csv_basic").join("input.csv");
+    let temp_dir = tempfile::tempdir().unwrap();
+    let out_path = temp_dir.path().join("out.csv");
+
+    let output = cargo_bin_cmd!("transform-rules")
+        .arg("transform")
+        .arg("-r")
+        .arg(&rules)
+        .arg("-i")
+        .arg(&input)
+        .arg("--output-format")
+        .arg("csv")
+        .arg("-o")
+        .arg(&out_path)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+
+    let written = fs::read_to_string(&out_path).unwrap();
+    assert_eq!(written, "id,name,price\n001,Apple,100.0\n");
+}
+
+#[test]
+fn transform_groups_repeated_warnings() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let rules = temp_dir.path().join("rules.yaml");
+    fs::write(
+        &rules,
+        r#"version: 1
+input:
+  format: json
+mappings:
+  - target: "name"
+    source: "name"
+    when:
+      op: "=="
+      args: [ { op: "lowercase", args: [ { ref: "input.flag" } ] }, "yes" ]
+"#,
+    )
+    .unwrap();
+    let input = r#"[{ "name": "a", "flag": 1 }, { "name": "b", "flag": "yes" }, { "name": "c", "flag": 2 }, { "name": "d", "flag": 3 }]"#;
+
+    let run = |extra: &[&str]| {
+        let mut cmd = cargo_bin_cmd!("transform-rules");
+        let output = cmd
+            .arg("transform")
+            .arg("-r")
+            .arg(&rules)
+            .arg("-i")
+            .arg("-")
+            .args(extra)
+            .write_stdin(input)
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(0));
+        String::from_utf8(output.stderr).unwrap()
+    };
+
+    let stderr = run(&[]);
+    assert_eq!(stderr.lines().count(), 1, "{}", stderr);
+    assert!(stderr.contains(" count=3 rows=0..3 msg="), "{}", stderr);
+
+    let stderr = run(&["--verbose-warnings"]);
+    assert_eq!(stderr.lines().count(), 3, "{}", stderr);
+    assert!(stderr.lines().all(|line| line.contains(" row=")), "{}", stderr);
+
+    let stderr = run(&["--ndjson"]);
+    assert_eq!(stderr.lines().count(), 1, "{}", stderr);
+
+    let stderr = run(&["-e", "json"]);
+    let value: serde_json::Value = serde_json::from_str(&stderr).unwrap();
+    assert_eq!(value[0]["count"], 3);
+    assert_eq!(value[0]["first_record_index"], 0);
+    assert_eq!(value[0]["last_record_index"], 3);
+}
+
+#[test]
+fn transform_reads_stdin_and_writes_stdout() {
+    let base = fixtures_dir().join("t03_json_out_context");
+    let input = fs::read_to_string(base.join("input.json")).unwrap();
+    let expected = read_json(&base.join("expected.json"));
+
+    let mut cmd = cargo_bin_cmd!("transform-rules");
+    let output = cmd
+        .arg("transform")
+        .arg("-r")
+        .arg(base.join("rules.yaml"))
+        .arg("-i")
+        .arg("-")
+        .arg("-c")
+        .arg(base.join("context.json"))
+        .arg("-o")
+        .arg("-")
+        .write_stdin(input)
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let actual: serde_json::Value = serde_json::from_str(&stdout)
+        .unwrap_or_else(|_| panic!("invalid json stdout: {}", stdout));
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn transform_ndjson_streams_stdin() {
+    let base = fixtures_dir().join("t12_ndjson_csv");
+    let input = fs::read_to_string(base.join("input.csv")).unwrap();
+    let expected = fs::read_to_string(base.join("expected.ndjson")).unwrap();
+
+    let mut cmd = cargo_bin_cmd!("transform-rules");
+    let output = cmd
+        .arg("transform")
+        .arg("-r")
+        .arg(base.join("rules.yaml"))
+        .arg("-i")
+        .arg("-")
+        .arg("--ndjson")
+        .arg("-o")
+        .arg("-")
+        .write_stdin(input)
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+
+    // Records before malformed stdin input are still written.
+    let temp_dir = tempfile::tempdir().unwrap();
+    let rules = temp_dir.path().join("rules.yaml");
+    fs::write(
+        &rules,
+        "version: 1\ninput:\n  format: ndjson\nmappings:\n  - target: \"id\"\n    source: \"id\"\n",
+    )
+    .unwrap();
+    let mut cmd = cargo_bin_cmd!("transform-rules");
+    let output = cmd
+        .arg("transform")
+        .arg("-r")
+        .arg(&rules)
+        .arg("-i")
+        .arg("-")
+        .arg("--ndjson")
+        .write_stdin("{\"id\":1}\n{\"id\":2}\n{\"id\":\n")
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "{\"id\":1}\n{\"id\":2}\n"
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("InvalidInput"), "{}", stderr);
+}
+
+#[test]
+fn rules_can_be_read_from_stdin() {
+    let mut cmd = cargo_bin_cmd!("transform-rules");
+    let output = cmd
+        .arg("validate")
+        .arg("-r")
+        .arg("-")
+        .write_stdin(
+            fs::read_to_string(fixtures_dir().join("t01_csv_basic").join("rules.yaml")).unwrap(),
+        )
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+
+    let base = fixtures_dir().join("p01_preflight_ok");
+    let rules = fs::read_to_string(base.join("rules.yaml")).unwrap();
+
+    let mut cmd = cargo_bin_cmd!("transform-rules");
+    let output = cmd
+        .arg("preflight")
+        .arg("-r")
+        .arg("-")
+        .arg("-i")
+        .arg(base.join("input.json"))
+        .write_stdin(rules)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+
+    let mut cmd = cargo_bin_cmd!("transform-rules");
+    let output = cmd
+        .arg("preflight")
+        .arg("-r")
+        .arg(base.join("rules.yaml"))
+        .arg("-i")
+        .arg("-")
+        .write_stdin(fs::read_to_string(base.join("input.json")).unwrap())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn rules_and_input_cannot_both_read_stdin() {
+    for subcommand in ["transform", "preflight"] {
+        let mut cmd = cargo_bin_cmd!("transform-rules");
+        let output = cmd
+            .arg(subcommand)
+            .arg("-r")
+            .arg("-")
+            .arg("-i")
+            .arg("-")
+            .write_stdin("")
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert_eq!(
+            stderr.trim_end(),
+            "--rules and --input cannot both read from stdin (-)"
+        );
+    }
+}
@@ -1,14 +1,25 @@
 use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard, OnceLock, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use csv::ReaderBuilder;
 use serde_json::{json, Map, Value};
 use serde_yaml::{Mapping as YamlMapping, Value as YamlValue};
+use transform_rules::path::{format_path, get_path, parse_path, PathToken};
 use transform_rules::{
-    generate_dto, parse_rule_file, transform_stream, transform_with_warnings,
-    validate_rule_file_with_source, DtoLanguage, Expr, ExprChain, ExprOp, InputFormat, RuleError,
-    RuleFile, TransformError, TransformErrorKind, TransformWarning,
+    clear_rule_cache, diff_json, environment_info, generate_dto, input_record, load_rule_file,
+    load_table, parse_csv_records, parse_rule_file_with_limits, records_to_csv, rule_cache_stats,
+    run_check, run_self_checks, error_catalog, select_json_records, transform_record_traced,
+    transform_stream_with_limits, transform_values_stream, patch_yaml, unified_diff,
+    validate_rule_file_full, validate_rule_file_with_source, ArrayMatch, DiffEntry, DtoLanguage,
+    ErrorCode, Expr, InputFormat, LoadedTable, OnError, OpCategory, OutputFormat, OutputSpec,
+    ParseLimits, RuleError, RuleFile, RuleWarning, MappingSource, TraceOptions, TransformError,
+    TransformErrorCode, TransformErrorKind, TransformStream, TransformWarning, WarningSummary,
+    OPS,
 };
 
 const PROTOCOL_VERSION: &str = "2024-11-05";
@@ -18,6 +29,96 @@ const RESOURCE_URI_README: &str = "transform-rules://docs/readme";
 const RESOURCE_RULES_SPEC_EN: &str = include_str!("../../../docs/rules_spec_en.md");
 const RESOURCE_RULES_SPEC_JA: &str = include_str!("../../../docs/rules_spec_ja.md");
 const RESOURCE_README: &str = include_str!("../../../README.md");
+const RESOURCE_URI_EXAMPLES: &str = "transform-rules://examples";
+
+struct Example {
+    name: &'static str,
+    description: &'static str,
+    rules: &'static str,
+    input_name: &'static str,
+    input: &'static str,
+    context: Option<&'static str>,
+    expected: &'static str,
+}
+
+macro_rules! example {
+    ($name:literal, $description:literal, $input_name:literal) => {
+        example!($name, $description, $input_name, None)
+    };
+    ($name:literal, $description:literal, $input_name:literal, context) => {
+        example!(
+            $name,
+            $description,
+            $input_name,
+            Some(include_str!(concat!("../../../examples/", $name, "/context.json")))
+        )
+    };
+    ($name:literal, $description:literal, $input_name:literal, $context:expr) => {
+        Example {
+            name: $name,
+            description: $description,
+            rules: include_str!(concat!("../../../examples/", $name, "/rules.yaml")),
+            input_name: $input_name,
+            input: include_str!(concat!("../../../examples/", $name, "/", $input_name)),
+            context: $context,
+            expected: include_str!(concat!("../../../examples/", $name, "/expected.json")),
+        }
+    };
+}
+
+// Rule files under `examples/`, served as resources and runnable with `run_example`.
+const EXAMPLES: &[Example] = &[
+    example!(
+        "csv_to_json",
+        "CSV rows to JSON objects, casting columns with `type`.",
+        "input.csv"
+    ),
+    example!(
+        "json_records_path",
+        "Records nested in a JSON document, selected with `records_path`.",
+        "input.json"
+    ),
+    example!(
+        "context_lookup",
+        "Resolving ids against a table passed as context with `lookup_first`.",
+        "input.json",
+        context
+    ),
+    example!(
+        "string_cleanup",
+        "Normalizing messy strings step by step with `chain`.",
+        "input.json"
+    ),
+    example!(
+        "array_map_filter",
+        "Reshaping nested arrays with `filter`, `map` and `sum`.",
+        "input.json"
+    ),
+];
+
+const DEFAULT_MAX_ARRAY_SAMPLES: usize = 100;
+const DEFAULT_MAX_ARRAY_DEPTH: usize = 3;
+// Rules and input come from the client, so a server keeps them bounded unless told otherwise.
+const DEFAULT_LIMITS: ParseLimits = ParseLimits {
+    max_bytes: Some(32 * 1024 * 1024),
+    max_depth: Some(64),
+    max_mappings: Some(10_000),
+    max_records: Some(1_000_000),
+    max_record_bytes: Some(1024 * 1024),
+};
+// Paginated results are kept for `fetch_result_page` within this many bytes of row text and
+// row offsets.
+const DEFAULT_RESULT_CACHE_BYTES: usize = 64 * 1024 * 1024;
+const DEFAULT_RESULT_TTL_SECS: u64 = 600;
+// `tools/call` requests past this many running at once are answered busy, not queued.
+const DEFAULT_MAX_CALLS: u64 = 8;
+const LIMIT_NAMES: [&str; 5] = [
+    "max_bytes",
+    "max_depth",
+    "max_mappings",
+    "max_records",
+    "max_record_bytes",
+];
 
 fn main() {
     if let Err(err) = run() {
@@ -32,18 +133,43 @@ enum OutputMode {
     ContentLength,
 }
 
+// Cancellation flags of the `tools/call` requests still running, keyed by serialized id.
+type InFlight = Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>;
+
+// `tools/call` runs on a worker thread so the loop keeps reading and can see
+// `notifications/cancelled`; every response goes through the writer thread.
 fn run() -> Result<(), String> {
+    set_limits(limits_from_env()?);
+    configure_result_cache(result_cache_from_env()?);
+    let max_calls = positive_env("TRANSFORM_RULES_MCP_MAX_CALLS", DEFAULT_MAX_CALLS)?;
+    let max_calls = usize::try_from(max_calls).unwrap_or(usize::MAX);
     let stdin = io::stdin();
-    let stdout = io::stdout();
     let mut reader = BufReader::new(stdin.lock());
-    let mut writer = io::BufWriter::new(stdout.lock());
     let mut output_mode = OutputMode::Line;
+    let (sender, receiver) = mpsc::channel::<(OutputMode, Value)>();
+    let writer = thread::spawn(move || -> io::Result<()> {
+        let stdout = io::stdout();
+        let mut writer = io::BufWriter::new(stdout.lock());
+        for (output_mode, response) in receiver {
+            write_message(&mut writer, output_mode, &response)?;
+        }
+        Ok(())
+    });
+    let in_flight = InFlight::default();
+    let mut workers = Vec::new();
 
-    loop {
+    let result = loop {
         let message = match read_message(&mut reader, &mut output_mode) {
-            Ok(Some(message)) => message,
-            Ok(None) => break,
-            Err(err) => return Err(err.to_string()),
+            Ok(Some(IncomingMessage::Text(message))) => message,
+            Ok(Some(IncomingMessage::Invalid(message))) => {
+                let response = error_response(Value::Null, -32700, &message);
+                if sender.send((output_mode, response)).is_err() {
+                    break Ok(());
+                }
+                continue;
+            }
+            Ok(None) => break Ok(()),
+            Err(err) => break Err(err.to_string()),
         };
 
         let value: Value = match serde_json::from_str(&message) {
@@ -54,19 +180,96 @@ fn run() -> Result<(), String> {
             }
         };
 
-        if let Some(response) = handle_message(value) {
-            write_message(&mut writer, output_mode, &response)
-                .map_err(|err| err.to_string())?;
+        let method = value.get("method").and_then(Value::as_str);
+        if method == Some("notifications/cancelled") {
+            cancel_request(&in_flight, &value);
+            continue;
+        }
+        if method == Some("tools/call")
+            && let Some(id) = value.get("id").cloned()
+        {
+            workers.retain(|worker: &thread::JoinHandle<()>| !worker.is_finished());
+            let running = lock_in_flight(&in_flight).len();
+            if running >= max_calls {
+                let message = format!(
+                    "Server busy: {} tool calls are running; retry when one finishes",
+                    running
+                );
+                if sender.send((output_mode, error_response(id, -32000, &message))).is_err() {
+                    break Ok(());
+                }
+                continue;
+            }
+            workers.push(spawn_tools_call(id, value, output_mode, &in_flight, &sender));
+            continue;
+        }
+
+        if let Some(response) = handle_message(value)
+            && sender.send((output_mode, response)).is_err()
+        {
+            break Ok(());
         }
+    };
+
+    // Calls still running at end of input get to answer before the server exits.
+    for worker in workers {
+        let _ = worker.join();
+    }
+    drop(sender);
+    match writer.join() {
+        Ok(Ok(())) => result,
+        Ok(Err(err)) => Err(err.to_string()),
+        Err(_) => Err("writer thread panicked".to_string()),
+    }
+}
+
+fn spawn_tools_call(
+    id: Value,
+    message: Value,
+    output_mode: OutputMode,
+    in_flight: &InFlight,
+    sender: &mpsc::Sender<(OutputMode, Value)>,
+) -> thread::JoinHandle<()> {
+    let key = id.to_string();
+    let cancelled = Arc::new(AtomicBool::new(false));
+    lock_in_flight(in_flight).insert(key.clone(), Arc::clone(&cancelled));
+    let in_flight = Arc::clone(in_flight);
+    let sender = sender.clone();
+    thread::spawn(move || {
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let response = tools_call_response(id, &params, &cancelled);
+        lock_in_flight(&in_flight).remove(&key);
+        let _ = sender.send((output_mode, response));
+    })
+}
+
+// A worker that panicked still leaves the map usable.
+fn lock_in_flight(in_flight: &InFlight) -> MutexGuard<'_, HashMap<String, Arc<AtomicBool>>> {
+    in_flight.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+// Unknown or already answered request ids are ignored.
+fn cancel_request(in_flight: &InFlight, message: &Value) {
+    let Some(request_id) = message
+        .get("params")
+        .and_then(|params| params.get("requestId"))
+    else {
+        return;
+    };
+    if let Some(cancelled) = lock_in_flight(in_flight).get(&request_id.to_string()) {
+        cancelled.store(true, Ordering::Relaxed);
     }
+}
 
-    Ok(())
+enum IncomingMessage {
+    Text(String),
+    Invalid(String),
 }
 
 fn read_message(
     reader: &mut impl BufRead,
     output_mode: &mut OutputMode,
-) -> io::Result<Option<String>> {
+) -> io::Result<Option<IncomingMessage>> {
     let mut line = String::new();
     loop {
         line.clear();
@@ -94,7 +297,13 @@ fn read_message(
             let mut buffer = vec![0u8; length];
             reader.read_exact(&mut buffer)?;
             *output_mode = OutputMode::ContentLength;
-            return Ok(Some(String::from_utf8_lossy(&buffer).to_string()));
+            return Ok(Some(match String::from_utf8(buffer) {
+                Ok(text) => IncomingMessage::Text(text),
+                Err(err) => IncomingMessage::Invalid(format!(
+                    "Parse error: message body is not valid UTF-8 at byte offset {}",
+                    err.utf8_error().valid_up_to()
+                )),
+            }));
         }
 
         let trimmed = line.trim_end_matches(['\r', '\n']);
@@ -102,7 +311,7 @@ fn read_message(
             continue;
         }
         *output_mode = OutputMode::Line;
-        return Ok(Some(trimmed.to_string()));
+        return Ok(Some(IncomingMessage::Text(trimmed.to_string())));
     }
 }
 
@@ -132,20 +341,23 @@ fn handle_message(message: Value) -> Option<Value> {
     };
 
     match method {
-        "initialize" => id.map(|id| ok_response(id, initialize_result())),
+        "initialize" => {
+            let id = id?;
+            let limits = obj.get("params").and_then(|params| params.get("limits"));
+            match limits.map(|limits| apply_limits_param(current_limits(), limits)) {
+                Some(Err(message)) => Some(error_response(id, -32602, &message)),
+                Some(Ok(limits)) => {
+                    set_limits(limits);
+                    Some(ok_response(id, initialize_result()))
+                }
+                None => Some(ok_response(id, initialize_result())),
+            }
+        }
         "tools/list" => id.map(|id| ok_response(id, tools_list_result())),
         "tools/call" => {
             let id = id?;
             let params = obj.get("params").cloned().unwrap_or(Value::Null);
-            match handle_tools_call(&params) {
-                Ok(result) => Some(ok_response(id, result)),
-                Err(CallError::InvalidParams(message)) => {
-                    Some(error_response(id, -32602, &message))
-                }
-                Err(CallError::Tool { message, errors }) => {
-                    Some(ok_response(id, tool_error_result(&message, errors)))
-                }
-            }
+            Some(tools_call_response(id, &params, &AtomicBool::new(false)))
         }
         "resources/list" => id.map(|id| ok_response(id, resources_list_result())),
         "resources/read" => {
@@ -172,6 +384,17 @@ fn handle_message(message: Value) -> Option<Value> {
     }
 }
 
+fn tools_call_response(id: Value, params: &Value, cancelled: &AtomicBool) -> Value {
+    match handle_tools_call(params, cancelled) {
+        Ok(result) => ok_response(id, result),
+        Err(CallError::InvalidParams(message)) => error_response(id, -32602, &message),
+        Err(CallError::Tool { message, errors }) => {
+            ok_response(id, tool_error_result(&message, errors))
+        }
+        Err(CallError::Cancelled) => error_response(id, -32800, "Request cancelled"),
+    }
+}
+
 fn ok_response(id: Value, result: Value) -> Value {
     json!({
         "jsonrpc": "2.0",
@@ -212,6 +435,93 @@ fn initialize_result() -> Value {
     })
 }
 
+fn limits_cell() -> &'static RwLock<ParseLimits> {
+    static LIMITS: OnceLock<RwLock<ParseLimits>> = OnceLock::new();
+    LIMITS.get_or_init(|| RwLock::new(DEFAULT_LIMITS))
+}
+
+fn current_limits() -> ParseLimits {
+    *limits_cell().read().unwrap_or_else(|err| err.into_inner())
+}
+
+fn set_limits(limits: ParseLimits) {
+    *limits_cell().write().unwrap_or_else(|err| err.into_inner()) = limits;
+}
+
+fn limit_slot<'a>(limits: &'a mut ParseLimits, name: &str) -> Option<&'a mut Option<usize>> {
+    match name {
+        "max_bytes" => Some(&mut limits.max_bytes),
+        "max_depth" => Some(&mut limits.max_depth),
+        "max_mappings" => Some(&mut limits.max_mappings),
+        "max_records" => Some(&mut limits.max_records),
+        "max_record_bytes" => Some(&mut limits.max_record_bytes),
+        _ => None,
+    }
+}
+
+// `TRANSFORM_RULES_MCP_MAX_BYTES` and so on; `0` turns a limit off.
+fn limits_from_env() -> Result<ParseLimits, String> {
+    let mut limits = DEFAULT_LIMITS;
+    for name in LIMIT_NAMES {
+        let var = format!("TRANSFORM_RULES_MCP_{}", name.to_ascii_uppercase());
+        let Ok(value) = std::env::var(&var) else {
+            continue;
+        };
+        let value = value
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| format!("{} must be a non-negative integer", var))?;
+        if let Some(slot) = limit_slot(&mut limits, name) {
+            *slot = (value > 0).then_some(value);
+        }
+    }
+    Ok(limits)
+}
+
+// `TRANSFORM_RULES_MCP_RESULT_CACHE_BYTES` and `TRANSFORM_RULES_MCP_RESULT_TTL_SECS`.
+fn positive_env(var: &str, default: u64) -> Result<u64, String> {
+    match std::env::var(var) {
+        Ok(value) => value
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .filter(|value| *value > 0)
+            .ok_or_else(|| format!("{} must be a positive integer", var)),
+        Err(_) => Ok(default),
+    }
+}
+
+fn result_cache_from_env() -> Result<(usize, Duration), String> {
+    let max_bytes = positive_env(
+        "TRANSFORM_RULES_MCP_RESULT_CACHE_BYTES",
+        DEFAULT_RESULT_CACHE_BYTES as u64,
+    )?;
+    let ttl = positive_env("TRANSFORM_RULES_MCP_RESULT_TTL_SECS", DEFAULT_RESULT_TTL_SECS)?;
+    Ok((
+        usize::try_from(max_bytes).unwrap_or(usize::MAX),
+        Duration::from_secs(ttl),
+    ))
+}
+
+// `initialize` params may carry `limits: { max_bytes: n, ... }`; `0` or `null` turns one off.
+fn apply_limits_param(mut limits: ParseLimits, value: &Value) -> Result<ParseLimits, String> {
+    let Value::Object(map) = value else {
+        return Err("limits must be an object".to_string());
+    };
+    for (name, value) in map {
+        let slot = limit_slot(&mut limits, name).ok_or_else(|| format!("unknown limit: {}", name))?;
+        *slot = match value {
+            Value::Null => None,
+            _ => match value.as_u64() {
+                Some(0) => None,
+                Some(max) => Some(usize::try_from(max).unwrap_or(usize::MAX)),
+                None => return Err(format!("limits.{} must be a non-negative integer", name)),
+            },
+        };
+    }
+    Ok(limits)
+}
+
 fn tools_list_result() -> Value {
     json!({
         "tools": [
@@ -220,6 +530,31 @@ fn tools_list_result() -> Value {
                 "description": "Transform CSV/JSON input with a YAML rule file.",
                 "inputSchema": transform_input_schema()
             },
+            {
+                "name": "fetch_result_page",
+                "description": "Fetch more rows of a transform run with page_rows, by the meta.result_id it returned. Each page is well-formed: a JSON array, or NDJSON lines when the transform used ndjson=true.",
+                "inputSchema": fetch_result_page_input_schema()
+            },
+            {
+                "name": "preflight",
+                "description": "Run the rules against the input without returning output; reports the record count, warnings grouped by kind and path, and the first failing record.",
+                "inputSchema": preflight_input_schema()
+            },
+            {
+                "name": "transform_check",
+                "description": "Transform the input and compare the output with an expected JSON value; returns each difference (path, expected, actual, changed/missing/extra) in meta.diff.",
+                "inputSchema": transform_check_input_schema()
+            },
+            {
+                "name": "trace_record",
+                "description": "Map one input record and return what each mapping did: whether `when` passed, the value at each chain step with its op args, the value after default/type, and whether it was written to the target. Long values are cut with an ellipsis and their byte size.",
+                "inputSchema": trace_record_input_schema()
+            },
+            {
+                "name": "run_example",
+                "description": "Transform with one of the example rule files (see the transform-rules://examples resource), on its own input or on input given here.",
+                "inputSchema": run_example_input_schema()
+            },
             {
                 "name": "validate_rules",
                 "description": "Validate a YAML rule file.",
@@ -235,6 +570,11 @@ fn tools_list_result() -> Value {
                 "description": "List supported expression ops, comparisons, and type casts.",
                 "inputSchema": list_ops_input_schema()
             },
+            {
+                "name": "list_error_codes",
+                "description": "List the stable codes of transform errors with their kind and description.",
+                "inputSchema": list_error_codes_input_schema()
+            },
             {
                 "name": "analyze_input",
                 "description": "Analyze input data and summarize field paths and types.",
@@ -249,13 +589,23 @@ fn tools_list_result() -> Value {
                 "name": "generate_rules_from_dto",
                 "description": "Generate rules by mapping input data to a DTO schema.",
                 "inputSchema": generate_rules_from_dto_input_schema()
+            },
+            {
+                "name": "self_check",
+                "description": "Run built-in self-checks against the installed library and report environment diagnostics.",
+                "inputSchema": self_check_input_schema()
+            },
+            {
+                "name": "cache_stats",
+                "description": "Report hits, misses and entries of the parsed-rule cache, optionally clearing it.",
+                "inputSchema": cache_stats_input_schema()
             }
         ]
     })
 }
 
 fn resources_list_result() -> Value {
-    json!({
+    let mut result = json!({
         "resources": [
             {
                 "uri": RESOURCE_URI_RULES_SPEC_EN,
@@ -274,9 +624,80 @@ fn resources_list_result() -> Value {
                 "name": "readme",
                 "description": "Project README.",
                 "mimeType": "text/markdown"
+            },
+            {
+                "uri": RESOURCE_URI_EXAMPLES,
+                "name": "examples",
+                "description": "Index of the example rule files.",
+                "mimeType": "text/markdown"
             }
         ]
-    })
+    });
+    let resources = result["resources"].as_array_mut().expect("resources list");
+    for example in EXAMPLES {
+        resources.push(json!({
+            "uri": example_uri(example.name),
+            "name": format!("example_{}", example.name),
+            "description": example.description,
+            "mimeType": "application/yaml"
+        }));
+    }
+    result
+}
+
+fn example_uri(name: &str) -> String {
+    format!("{}/{}", RESOURCE_URI_EXAMPLES, name)
+}
+
+fn find_example(name: &str) -> Option<&'static Example> {
+    EXAMPLES.iter().find(|example| example.name == name)
+}
+
+fn examples_index() -> String {
+    let mut text = String::from(
+        "# Examples\n\nEach example is a resource holding its rules, input, context (if any) \
+         and expected output. Run one with the `run_example` tool.\n\n",
+    );
+    for example in EXAMPLES {
+        text.push_str(&format!(
+            "- `{}` ({}): {}\n",
+            example.name,
+            example_uri(example.name),
+            example.description
+        ));
+    }
+    text
+}
+
+// The rules first, then the files they run against, each under its own uri.
+fn example_contents(example: &Example) -> Value {
+    let uri = example_uri(example.name);
+    let input_mime = if example.input_name.ends_with(".csv") {
+        "text/csv"
+    } else {
+        "application/json"
+    };
+    let mut contents = vec![
+        json!({ "uri": uri, "mimeType": "application/yaml", "text": example.rules }),
+        json!({
+            "uri": format!("{}/{}", uri, example.input_name),
+            "mimeType": input_mime,
+            "text": example.input
+        }),
+    ];
+    if let Some(context) = example.context {
+        contents.push(json!({
+            "uri": format!("{}/context.json", uri),
+            "mimeType": "application/json",
+            "text": context
+        }));
+    }
+    contents.push(json!({
+        "uri": format!("{}/expected.json", uri),
+        "mimeType": "application/json",
+        "text": example.expected
+    }));
+    Value::Array(contents)
 }
 
 fn resources_read_result(params: &Value) -> Result<Value, String> {
@@ -291,7 +712,21 @@ fn resources_read_result(params: &Value) -> Result<Value, String> {
         RESOURCE_URI_RULES_SPEC_EN => RESOURCE_RULES_SPEC_EN,
         RESOURCE_URI_RULES_SPEC_JA => RESOURCE_RULES_SPEC_JA,
         RESOURCE_URI_README => RESOURCE_README,
-        _ => return Err("unknown resource uri".to_string()),
+        RESOURCE_URI_EXAMPLES => {
+            return Ok(json!({
+                "contents": [
+                    { "uri": uri, "mimeType": "text/markdown", "text": examples_index() }
+                ]
+            }))
+        }
+        _ => {
+            let example = uri
+                .strip_prefix(RESOURCE_URI_EXAMPLES)
+                .and_then(|rest| rest.strip_prefix('/'))
+                .and_then(find_example)
+                .ok_or_else(|| "unknown resource uri".to_string())?;
+            return Ok(json!({ "contents": example_contents(example) }));
+        }
     };
 
     Ok(json!({
@@ -458,17 +893,23 @@ fn transform_input_schema() -> Value {
             },
             "context_path": {
                 "type": "string",
-                "description": "Optional path to a JSON context file. Mutually exclusive with context_json.",
+                "description": "Optional path to a JSON context file (`.csv` is read as an array of row objects). Mutually exclusive with context_json and context_files.",
                 "examples": ["context.json"]
             },
             "context_json": {
                 "type": "object",
-                "description": "Optional inline JSON context value. Mutually exclusive with context_path.",
+                "description": "Optional inline JSON context value. Mutually exclusive with context_path and context_files.",
                 "examples": [{"tenant_id": "t-001"}]
             },
+            "context_files": {
+                "type": "object",
+                "additionalProperties": { "type": "string" },
+                "description": "Optional named context files; each name becomes a key of the context. `.csv` files are read as an array of row objects, and names declared in the rule's `tables` are read as the entry says. Mutually exclusive with context_path and context_json.",
+                "examples": [{"countries": "countries.json", "stores": "stores.csv"}]
+            },
             "format": {
                 "type": "string",
-                "enum": ["csv", "json"],
+                "enum": ["csv", "json", "ndjson"],
                 "description": "Override input format from the rule file.",
                 "examples": ["json"]
             },
@@ -477,6 +918,12 @@ fn transform_input_schema() -> Value {
                 "description": "Emit NDJSON output (one JSON object per line).",
                 "examples": [false]
             },
+            "output_format": {
+                "type": "string",
+                "enum": ["json", "csv"],
+                "description": "Output format; overrides output.format in the rules. csv writes a header row and cannot be combined with ndjson.",
+                "examples": ["csv"]
+            },
             "validate": {
                 "type": "boolean",
                 "description": "Validate the rule file before transforming.",
@@ -499,15 +946,164 @@ fn transform_input_schema() -> Value {
                 "description": "Maximum rows to return when ndjson=true.",
                 "examples": [100]
             },
+            "page_rows": {
+                "type": "integer",
+                "minimum": 1,
+                "description": "Return the output in pages of this many rows. The first page comes back with meta.total_rows, meta.page, meta.has_more and, when there is more, meta.result_id for fetch_result_page. Cannot be combined with csv output, preview_rows, max_output_bytes or return_output_json.",
+                "examples": [500]
+            },
             "return_output_json": {
                 "type": "boolean",
                 "description": "Include parsed output JSON in meta.output when ndjson=false and within size limits.",
                 "examples": [false]
+            },
+            "trace_targets": {
+                "type": "array",
+                "items": { "type": "string" },
+                "description": "Target globs to trace. Chain steps and op args of matching mappings are returned in meta.trace for records whose value changed or that produced an error/warning.",
+                "examples": [["price*"]]
+            },
+            "timeout_ms": {
+                "type": "integer",
+                "minimum": 1,
+                "description": "Abort with a tool error (type `timeout`) once the transform has run this long. A `notifications/cancelled` for the request aborts it with JSON-RPC error -32800.",
+                "examples": [30000]
+            },
+            "allow_env": {
+                "type": "boolean",
+                "description": "Let `env.<NAME>` refs read the server's environment variables. Without it they fail with an ExprError.",
+                "default": false
             }
         }
     })
 }
 
+fn fetch_result_page_input_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "result_id": {
+                "type": "string",
+                "description": "meta.result_id of a transform run with page_rows."
+            },
+            "page": {
+                "type": "integer",
+                "minimum": 0,
+                "description": "Page to return, counted from 0 in pages of `rows`. Mutually exclusive with offset.",
+                "examples": [1]
+            },
+            "offset": {
+                "type": "integer",
+                "minimum": 0,
+                "description": "Index of the first row to return. Mutually exclusive with page.",
+                "examples": [500]
+            },
+            "rows": {
+                "type": "integer",
+                "minimum": 1,
+                "description": "Rows per page; defaults to the page_rows of the transform.",
+                "examples": [500]
+            }
+        },
+        "required": ["result_id"]
+    })
+}
+
+fn preflight_input_schema() -> Value {
+    let mut schema = transform_input_schema();
+    let properties = schema["properties"].as_object_mut().expect("transform schema properties");
+    properties.retain(|key, _| {
+        [
+            "rules_path",
+            "rules_text",
+            "input_path",
+            "input_text",
+            "input_json",
+            "context_path",
+            "context_json",
+            "context_files",
+            "format",
+            "allow_env",
+        ]
+        .contains(&key.as_str())
+    });
+    properties.insert(
+        "max_records".to_string(),
+        json!({
+            "type": "integer",
+            "minimum": 1,
+            "description": "Only preflight the first N records (groups with `group`).",
+            "examples": [1000]
+        }),
+    );
+    schema
+}
+
+fn transform_check_input_schema() -> Value {
+    let mut schema = preflight_input_schema();
+    let properties = schema["properties"].as_object_mut().expect("transform schema properties");
+    properties.remove("max_records");
+    properties.insert(
+        "expected_path".to_string(),
+        json!({
+            "type": "string",
+            "description": "Path to the expected output JSON. Mutually exclusive with expected_json.",
+            "examples": ["expected.json"]
+        }),
+    );
+    properties.insert(
+        "expected_json".to_string(),
+        json!({
+            "type": ["object", "array"],
+            "description": "Expected output JSON. Mutually exclusive with expected_path.",
+            "examples": [[{"id": 1}]]
+        }),
+    );
+    properties.insert(
+        "array_key".to_string(),
+        json!({
+            "type": "string",
+            "description": "Pair array elements by this field instead of by position; arrays whose elements lack a distinct value for it are compared in order.",
+            "examples": ["id"]
+        }),
+    );
+    schema
+}
+
+fn trace_record_input_schema() -> Value {
+    let mut schema = preflight_input_schema();
+    let properties = schema["properties"].as_object_mut().expect("transform schema properties");
+    properties.remove("max_records");
+    properties.remove("allow_env");
+    properties.insert(
+        "record_index".to_string(),
+        json!({
+            "type": "integer",
+            "minimum": 0,
+            "description": "Zero-based index of the input record to trace. Defaults to 0.",
+            "examples": [3]
+        }),
+    );
+    schema
+}
+
+fn run_example_input_schema() -> Value {
+    let mut schema = transform_input_schema();
+    let properties = schema["properties"].as_object_mut().expect("transform schema properties");
+    properties.retain(|key, _| RUN_EXAMPLE_ARGS.contains(&key.as_str()));
+    properties.insert(
+        "name".to_string(),
+        json!({
+            "type": "string",
+            "enum": EXAMPLES.iter().map(|example| example.name).collect::<Vec<_>>(),
+            "description": "Example to run.",
+            "examples": ["csv_to_json"]
+        }),
+    );
+    schema["required"] = json!(["name"]);
+    schema
+}
+
 fn validate_rules_input_schema() -> Value {
     json!({
         "type": "object",
@@ -521,6 +1117,11 @@ fn validate_rules_input_schema() -> Value {
                 "type": "string",
                 "description": "Inline YAML rules content. Mutually exclusive with rules_path.",
                 "examples": ["version: 1\ninput:\n  format: json\n  json: {}\nmappings:\n  - target: \"id\"\n    source: \"id\""]
+            },
+            "allow_unknown_ops": {
+                "type": "boolean",
+                "description": "Report ops this build does not know as warnings instead of errors.",
+                "examples": [true]
             }
         }
     })
@@ -542,13 +1143,13 @@ fn generate_dto_input_schema() -> Value {
             },
             "language": {
                 "type": "string",
-                "enum": ["rust", "typescript", "python", "go", "java", "kotlin", "swift"],
-                "description": "DTO output language.",
+                "enum": ["rust", "typescript", "python", "go", "java", "kotlin", "swift", "json-schema"],
+                "description": "DTO output language; json-schema writes a JSON Schema (draft 2020-12) document.",
                 "examples": ["typescript"]
             },
             "name": {
                 "type": "string",
-                "description": "Optional DTO root type name.",
+                "description": "Optional DTO root type name (the schema title for json-schema).",
                 "examples": ["Record"]
             }
         },
@@ -563,6 +1164,33 @@ fn list_ops_input_schema() -> Value {
     })
 }
 
+fn list_error_codes_input_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {}
+    })
+}
+
+fn self_check_input_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {}
+    })
+}
+
+fn cache_stats_input_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "clear": {
+                "type": "boolean",
+                "description": "Clear the cache and reset the counters after reporting them.",
+                "default": false
+            }
+        }
+    })
+}
+
 fn analyze_input_input_schema() -> Value {
     json!({
         "type": "object",
@@ -598,6 +1226,18 @@ fn analyze_input_input_schema() -> Value {
                 "minimum": 1,
                 "description": "Maximum number of unique paths to include in the response.",
                 "examples": [200]
+            },
+            "max_array_samples": {
+                "type": "integer",
+                "minimum": 1,
+                "description": "Elements read from each array when describing its items (`items[]`, `items[].sku`). Defaults to 100.",
+                "examples": [100]
+            },
+            "max_array_depth": {
+                "type": "integer",
+                "minimum": 1,
+                "description": "How many levels of nested arrays to descend into; 1 describes the items of top-level arrays only. Defaults to 3.",
+                "examples": [3]
             }
         }
     })
@@ -711,9 +1351,38 @@ enum CallError {
         message: String,
         errors: Option<Vec<Value>>,
     },
+    Cancelled,
+}
+
+// Stops a transform between records once the request is cancelled or `timeout_ms` has passed.
+struct CallControl<'a> {
+    cancelled: &'a AtomicBool,
+    deadline: Option<(Instant, u64)>,
+}
+
+impl CallControl<'_> {
+    fn check(&self) -> Result<(), CallError> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Err(CallError::Cancelled);
+        }
+        match self.deadline {
+            Some((deadline, timeout_ms)) if Instant::now() >= deadline => {
+                let message = format!("transform timed out after {} ms", timeout_ms);
+                Err(CallError::Tool {
+                    errors: Some(vec![json!({
+                        "type": "timeout",
+                        "message": message,
+                        "timeout_ms": timeout_ms,
+                    })]),
+                    message,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
-fn handle_tools_call(params: &Value) -> Result<Value, CallError> {
+fn handle_tools_call(params: &Value, cancelled: &AtomicBool) -> Result<Value, CallError> {
     let obj = params.as_object().ok_or_else(|| {
         CallError::InvalidParams("params must be an object".to_string())
     })?;
@@ -729,75 +1398,845 @@ fn handle_tools_call(params: &Value) -> Result<Value, CallError> {
         })?;
 
     match name {
-        "transform" => run_transform_tool(args),
+        "transform" => run_transform_tool(args, cancelled),
+        "fetch_result_page" => run_fetch_result_page_tool(args),
+        "preflight" => run_preflight_tool(args),
+        "transform_check" => run_transform_check_tool(args, cancelled),
+        "trace_record" => run_trace_record_tool(args),
+        "run_example" => run_example_tool(args, cancelled),
         "validate_rules" => run_validate_rules_tool(args),
         "generate_dto" => run_generate_dto_tool(args),
         "list_ops" => run_list_ops_tool(),
+        "list_error_codes" => run_list_error_codes_tool(),
         "analyze_input" => run_analyze_input_tool(args),
         "generate_rules_from_base" => run_generate_rules_from_base_tool(args),
         "generate_rules_from_dto" => run_generate_rules_from_dto_tool(args),
+        "self_check" => run_self_check_tool(),
+        "cache_stats" => run_cache_stats_tool(args),
         _ => Ok(tool_error_result(&format!("unknown tool: {}", name), None)),
     }
 }
 
-fn run_transform_tool(args: &Map<String, Value>) -> Result<Value, CallError> {
-    let rules_path = get_optional_string(args, "rules_path").map_err(CallError::InvalidParams)?;
-    let rules_text = get_optional_string(args, "rules_text").map_err(CallError::InvalidParams)?;
-    let input_path = get_optional_string(args, "input_path").map_err(CallError::InvalidParams)?;
-    let input_text = get_optional_string(args, "input_text").map_err(CallError::InvalidParams)?;
-    let input_json = get_optional_json_value(args, "input_json").map_err(CallError::InvalidParams)?;
-    let context_path = get_optional_string(args, "context_path").map_err(CallError::InvalidParams)?;
-    let context_json = get_optional_object(args, "context_json").map_err(CallError::InvalidParams)?;
-    let format = get_optional_string(args, "format").map_err(CallError::InvalidParams)?;
+fn run_transform_tool(args: &Map<String, Value>, cancelled: &AtomicBool) -> Result<Value, CallError> {
+    let timeout_ms = get_optional_usize(args, "timeout_ms").map_err(CallError::InvalidParams)?;
+    let control = CallControl {
+        cancelled,
+        deadline: timeout_ms.map(|ms| {
+            (Instant::now() + Duration::from_millis(ms as u64), ms as u64)
+        }),
+    };
     let ndjson = get_optional_bool(args, "ndjson")
         .map_err(CallError::InvalidParams)?
         .unwrap_or(false);
     let validate = get_optional_bool(args, "validate")
         .map_err(CallError::InvalidParams)?
         .unwrap_or(false);
+    let output_format =
+        get_optional_string(args, "output_format").map_err(CallError::InvalidParams)?;
     let output_path = get_optional_string(args, "output_path").map_err(CallError::InvalidParams)?;
     let max_output_bytes =
         get_optional_usize(args, "max_output_bytes").map_err(CallError::InvalidParams)?;
     let preview_rows = get_optional_usize(args, "preview_rows").map_err(CallError::InvalidParams)?;
+    let page_rows = get_optional_usize(args, "page_rows").map_err(CallError::InvalidParams)?;
     let return_output_json = get_optional_bool(args, "return_output_json")
         .map_err(CallError::InvalidParams)?
         .unwrap_or(false);
-
-    let rule_source_count = rules_path.is_some() as u8 + rules_text.is_some() as u8;
-    if rule_source_count == 0 {
-        return Err(CallError::InvalidParams(
-            "rules_path or rules_text is required".to_string(),
-        ));
+    let trace_targets =
+        get_optional_string_array(args, "trace_targets").map_err(CallError::InvalidParams)?;
+
+    let output_format = match output_format.as_deref() {
+        None => None,
+        Some(value) if value.eq_ignore_ascii_case("json") => Some(OutputFormat::Json),
+        Some(value) if value.eq_ignore_ascii_case("csv") => Some(OutputFormat::Csv),
+        Some(_) => {
+            return Err(CallError::InvalidParams(
+                "output_format must be json or csv".to_string(),
+            ))
+        }
+    };
+    if page_rows.is_some() {
+        let conflict = [
+            ("preview_rows", preview_rows.is_some()),
+            ("max_output_bytes", max_output_bytes.is_some()),
+            ("return_output_json", return_output_json),
+        ]
+        .into_iter()
+        .find(|(_, set)| *set);
+        if let Some((name, _)) = conflict {
+            return Err(CallError::InvalidParams(format!(
+                "page_rows cannot be combined with {}",
+                name
+            )));
+        }
+    }
+
+    let TransformSources {
+        mut rule,
+        yaml,
+        input,
+        context: context_value,
+        table_warnings,
+        allow_env,
+    } = load_transform_sources(args)?;
+    if let Some(format) = output_format {
+        match &mut rule.output {
+            Some(output) => output.format = format,
+            None => {
+                rule.output = Some(OutputSpec {
+                    name: None,
+                    format,
+                    columns: None,
+                })
+            }
+        }
     }
-    if rule_source_count > 1 {
+    let csv_output = rule
+        .output
+        .as_ref()
+        .is_some_and(|output| output.format == OutputFormat::Csv);
+    if csv_output && ndjson {
         return Err(CallError::InvalidParams(
-            "rules_path and rules_text are mutually exclusive".to_string(),
+            "csv output cannot be combined with ndjson".to_string(),
         ));
     }
-
-    let input_source_count =
-        input_path.is_some() as u8 + input_text.is_some() as u8 + input_json.is_some() as u8;
-    if input_source_count == 0 {
+    if csv_output && page_rows.is_some() {
         return Err(CallError::InvalidParams(
-            "input_path, input_text, or input_json is required".to_string(),
+            "csv output cannot be combined with page_rows".to_string(),
         ));
     }
-    if input_source_count > 1 {
-        return Err(CallError::InvalidParams(
-            "input_path, input_text, and input_json are mutually exclusive".to_string(),
-        ));
+
+    if validate
+        && let Err(errors) = validate_rule_file_with_source(&rule, &yaml)
+    {
+        let error_text = validation_errors_to_text(&errors);
+        let error_values = validation_errors_to_values(&errors);
+        return Err(CallError::Tool {
+            message: error_text,
+            errors: Some(error_values),
+        });
     }
 
-    if context_path.is_some() && context_json.is_some() {
+    let mut trace = trace_targets.map(|targets| TraceCollector {
+        options: TraceOptions::new(targets),
+        events: Vec::new(),
+    });
+    let transformed = if ndjson {
+        transform_to_ndjson(
+            &rule,
+            &input,
+            context_value.as_ref(),
+            trace.as_mut(),
+            &control,
+            allow_env,
+        )
+            .map(|(output_text, warnings)| (None, output_text, warnings))
+    } else if csv_output {
+        transform_to_csv(
+            &rule,
+            &input,
+            context_value.as_ref(),
+            trace.as_mut(),
+            &control,
+            allow_env,
+        )
+    } else {
+        transform_to_json(
+            &rule,
+            &input,
+            context_value.as_ref(),
+            trace.as_mut(),
+            &control,
+            allow_env,
+        )
+    };
+    let (output_value, output_text, mut warnings) = match (transformed, trace.as_mut()) {
+        (Ok(result), _) => result,
+        (Err(CallError::Tool { message, errors }), Some(trace)) => {
+            let mut result = tool_error_result(&message, errors);
+            result["meta"]["trace"] = Value::Array(std::mem::take(&mut trace.events));
+            return Ok(result);
+        }
+        (Err(err), _) => return Err(err),
+    };
+    warnings.extend(table_warnings);
+
+    if let Some(path) = output_path.as_deref() {
+        write_output(path, &output_text).map_err(|err| {
+            let message = err;
+            CallError::Tool {
+                message: message.clone(),
+                errors: Some(vec![io_error_json(&message, Some(path))]),
+            }
+        })?;
+    }
+
+    let mut meta = serde_json::Map::new();
+    if !warnings.is_empty() {
+        meta.insert("warnings".to_string(), warnings_to_json(&warnings));
+    }
+    if warnings.dropped > 0 {
+        meta.insert("warnings_dropped".to_string(), json!(warnings.dropped));
+    }
+    if let Some(path) = output_path {
+        meta.insert("output_path".to_string(), json!(path));
+    }
+
+    if let Some(page_rows) = page_rows {
+        // The rows take over the output, so it is not held twice while they are cached.
+        let rows = match output_value {
+            Some(value) => {
+                drop(output_text);
+                let records = match value {
+                    Value::Array(records) => records,
+                    value => vec![value],
+                };
+                ResultRows::from_records(records)
+            }
+            None => ResultRows::from_ndjson(output_text),
+        };
+        let page = ResultPage::new(&rows, 0, page_rows);
+        let text = page.text;
+        let has_more = page.has_more;
+        if has_more {
+            let result_id = cache_result(CachedResult::new(rows, page_rows))?;
+            meta.insert("result_id".to_string(), json!(result_id));
+        }
+        meta.insert("total_rows".to_string(), json!(page.total_rows));
+        meta.insert("page".to_string(), json!(0));
+        meta.insert("page_rows".to_string(), json!(page_rows));
+        meta.insert("has_more".to_string(), json!(has_more));
+        if let Some(trace) = trace {
+            meta.insert("trace".to_string(), Value::Array(trace.events));
+        }
+        return Ok(json!({
+            "content": [{ "type": "text", "text": text }],
+            "meta": meta
+        }));
+    }
+
+    let output_bytes = output_text.len();
+    let mut response_text = output_text.clone();
+    let mut truncated = false;
+
+    if ndjson
+        && let Some(limit) = preview_rows
+    {
+        let preview = preview_ndjson(&output_text, limit);
+        if preview.len() != output_text.len() {
+            truncated = true;
+        }
+        response_text = preview;
+    }
+
+    if let Some(max_bytes) = max_output_bytes {
+        if output_bytes > max_bytes {
+            truncated = true;
+        }
+        if response_text.len() > max_bytes {
+            response_text = truncate_to_bytes(&response_text, max_bytes).to_string();
+            truncated = true;
+        }
+    }
+
+    let mut result = json!({
+        "content": [
+            {
+                "type": "text",
+                "text": response_text
+            }
+        ]
+    });
+
+    let exceeds_max = max_output_bytes.is_some_and(|max| output_bytes > max);
+    if truncated {
+        meta.insert("output_bytes".to_string(), json!(output_bytes));
+        meta.insert("truncated".to_string(), json!(true));
+    }
+    if return_output_json && !ndjson && !exceeds_max
+        && let Some(output) = output_value
+    {
+        meta.insert("output".to_string(), output);
+    }
+    if let Some(trace) = trace {
+        meta.insert("trace".to_string(), Value::Array(trace.events));
+    }
+    if !meta.is_empty() {
+        result["meta"] = Value::Object(meta);
+    }
+
+    Ok(result)
+}
+
+// Runs every record like `transform` but keeps only counts; a record error fails the call
+// whatever `on_error` says, as in `preflight_validate`.
+fn run_preflight_tool(args: &Map<String, Value>) -> Result<Value, CallError> {
+    let max_records = get_optional_usize(args, "max_records").map_err(CallError::InvalidParams)?;
+    let TransformSources {
+        rule,
+        input,
+        context,
+        table_warnings,
+        allow_env,
+        ..
+    } = load_transform_sources(args)?;
+
+    let mut stream = open_transform_stream(&rule, &input, context.as_ref(), None, allow_env)?;
+    let mut records = 0;
+    let mut complete = true;
+    let mut warnings = WarningSummary::default();
+    loop {
+        if max_records.is_some_and(|max| records >= max) {
+            complete = stream.next().is_none();
+            break;
+        }
+        let Some(item) = stream.next() else {
+            break;
+        };
+        let first_error = match item {
+            Ok(mut item) if !item.errors.is_empty() => Some(item.errors.remove(0)),
+            Ok(item) => {
+                warnings.extend(item.warnings);
+                None
+            }
+            Err(err) => Some(err),
+        };
+        if let Some(err) = first_error {
+            let mut result = tool_error_result(
+                &transform_error_to_text(&err),
+                Some(vec![transform_error_json(&err)]),
+            );
+            result["meta"]["records"] = json!(records);
+            return Ok(result);
+        }
+        records += 1;
+    }
+
+    warnings.extend(table_warnings);
+    let mut result = json!({
+        "content": [
+            {
+                "type": "text",
+                "text": format!("ok: {} records, {} warnings", records, warnings.total())
+            }
+        ],
+        "meta": {
+            "records": records,
+            "complete": complete,
+            "warnings": warnings_to_json(&warnings)
+        }
+    });
+    if warnings.dropped > 0 {
+        result["meta"]["warnings_dropped"] = json!(warnings.dropped);
+    }
+    Ok(result)
+}
+
+// Transform args that make sense with an example's rules; paths and overrides are left out.
+const RUN_EXAMPLE_ARGS: [&str; 8] = [
+    "input_text",
+    "input_json",
+    "context_json",
+    "ndjson",
+    "output_format",
+    "return_output_json",
+    "page_rows",
+    "timeout_ms",
+];
+
+fn run_example_tool(args: &Map<String, Value>, cancelled: &AtomicBool) -> Result<Value, CallError> {
+    let name = get_optional_string(args, "name")
+        .map_err(CallError::InvalidParams)?
+        .ok_or_else(|| CallError::InvalidParams("name is required".to_string()))?;
+    let Some(example) = find_example(&name) else {
+        return Ok(tool_error_result(&format!("unknown example: {}", name), None));
+    };
+    if let Some(key) = args
+        .keys()
+        .find(|key| *key != "name" && !RUN_EXAMPLE_ARGS.contains(&key.as_str()))
+    {
+        return Err(CallError::InvalidParams(format!(
+            "{} is not accepted by run_example",
+            key
+        )));
+    }
+
+    let mut transform_args = args.clone();
+    transform_args.remove("name");
+    transform_args.insert("rules_text".to_string(), json!(example.rules));
+    if !args.contains_key("input_text") && !args.contains_key("input_json") {
+        transform_args.insert("input_text".to_string(), json!(example.input));
+    }
+    if !args.contains_key("context_json")
+        && let Some(context) = example.context
+    {
+        let context: Value = serde_json::from_str(context).expect("example context is JSON");
+        transform_args.insert("context_json".to_string(), context);
+    }
+    run_transform_tool(&transform_args, cancelled)
+}
+
+// Rows of a paginated transform in one buffer: row `i` is `text[ends[i - 1]..ends[i]]`. An
+// NDJSON row keeps its newline, so an NDJSON page is a single slice.
+struct ResultRows {
+    text: String,
+    ends: Vec<usize>,
+    ndjson: bool,
+}
+
+impl ResultRows {
+    fn from_records(records: Vec<Value>) -> Self {
+        use std::fmt::Write as _;
+        let mut text = String::new();
+        let mut ends = Vec::with_capacity(records.len());
+        for record in records {
+            let _ = write!(text, "{}", record);
+            ends.push(text.len());
+        }
+        Self::new(text, ends, false)
+    }
+
+    fn from_ndjson(mut text: String) -> Self {
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        let ends = text.match_indices('\n').map(|(index, _)| index + 1).collect();
+        Self::new(text, ends, true)
+    }
+
+    fn new(mut text: String, mut ends: Vec<usize>, ndjson: bool) -> Self {
+        text.shrink_to_fit();
+        ends.shrink_to_fit();
+        Self { text, ends, ndjson }
+    }
+
+    fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    // Where row `index` starts, or the end of the text for `len()`.
+    fn start(&self, index: usize) -> usize {
+        index.checked_sub(1).map_or(0, |previous| self.ends[previous])
+    }
+
+    fn bytes(&self) -> usize {
+        self.text.capacity() + self.ends.capacity() * std::mem::size_of::<usize>()
+    }
+}
+
+// Rows of a paginated transform, kept by `cache_result` for `fetch_result_page`.
+struct CachedResult {
+    rows: ResultRows,
+    page_rows: usize,
+    bytes: usize,
+    last_used: Instant,
+}
+
+impl CachedResult {
+    fn new(rows: ResultRows, page_rows: usize) -> Self {
+        let bytes = rows.bytes();
+        Self {
+            rows,
+            page_rows,
+            bytes,
+            last_used: Instant::now(),
+        }
+    }
+}
+
+// Entries in least recently used order. Each fetch renews an entry's TTL; an entry is dropped
+// once it expires or when a new result needs its bytes.
+struct ResultCache {
+    entries: Vec<(String, CachedResult)>,
+    bytes: usize,
+    max_bytes: usize,
+    ttl: Duration,
+}
+
+impl ResultCache {
+    fn remove_expired(&mut self, now: Instant) {
+        let ttl = self.ttl;
+        self.entries
+            .retain(|(_, entry)| now.duration_since(entry.last_used) < ttl);
+        self.bytes = self.entries.iter().map(|(_, entry)| entry.bytes).sum();
+    }
+
+    fn insert(&mut self, entry: CachedResult) -> Option<String> {
+        if entry.bytes > self.max_bytes {
+            return None;
+        }
+        self.remove_expired(Instant::now());
+        while self.bytes + entry.bytes > self.max_bytes && !self.entries.is_empty() {
+            let (_, evicted) = self.entries.remove(0);
+            self.bytes -= evicted.bytes;
+        }
+        let id = result_id();
+        self.bytes += entry.bytes;
+        self.entries.push((id.clone(), entry));
+        Some(id)
+    }
+
+    fn get(&mut self, id: &str) -> Option<&CachedResult> {
+        let now = Instant::now();
+        self.remove_expired(now);
+        let index = self.entries.iter().position(|(key, _)| key == id)?;
+        let mut entry = self.entries.remove(index);
+        entry.1.last_used = now;
+        self.entries.push(entry);
+        self.entries.last().map(|(_, entry)| entry)
+    }
+}
+
+// 128 random bits, so an id cannot be guessed from another one. Each `RandomState` is a
+// SipHash key drawn from the OS, which is all the randomness std offers.
+fn result_id() -> String {
+    use std::collections::hash_map::RandomState;
+    use std::hash::BuildHasher;
+    let random = || RandomState::new().hash_one(0u8);
+    format!("r{:016x}{:016x}", random(), random())
+}
+
+fn result_cache() -> &'static Mutex<ResultCache> {
+    static CACHE: OnceLock<Mutex<ResultCache>> = OnceLock::new();
+    CACHE.get_or_init(|| {
+        Mutex::new(ResultCache {
+            entries: Vec::new(),
+            bytes: 0,
+            max_bytes: DEFAULT_RESULT_CACHE_BYTES,
+            ttl: Duration::from_secs(DEFAULT_RESULT_TTL_SECS),
+        })
+    })
+}
+
+fn configure_result_cache((max_bytes, ttl): (usize, Duration)) {
+    let mut cache = result_cache().lock().unwrap_or_else(|err| err.into_inner());
+    cache.max_bytes = max_bytes;
+    cache.ttl = ttl;
+}
+
+fn cache_result(entry: CachedResult) -> Result<String, CallError> {
+    let bytes = entry.bytes;
+    let mut cache = result_cache().lock().unwrap_or_else(|err| err.into_inner());
+    cache.insert(entry).ok_or_else(|| {
+        let message = format!(
+            "result_cache_bytes exceeded: output rows are {} bytes, more than {}; write it with output_path instead",
+            bytes, cache.max_bytes
+        );
+        CallError::Tool {
+            errors: Some(vec![json!({
+                "type": "limit",
+                "limit": "result_cache_bytes",
+                "max": cache.max_bytes,
+                "message": message,
+            })]),
+            message,
+        }
+    })
+}
+
+// A slice of rows written as a JSON array, or as NDJSON lines.
+struct ResultPage {
+    text: String,
+    total_rows: usize,
+    rows: usize,
+    has_more: bool,
+}
+
+impl ResultPage {
+    fn new(rows: &ResultRows, offset: usize, limit: usize) -> Self {
+        let start = offset.min(rows.len());
+        let end = offset.saturating_add(limit).min(rows.len());
+        let slice = &rows.text[rows.start(start)..rows.start(end)];
+        let text = if rows.ndjson {
+            slice.to_string()
+        } else {
+            let mut text = String::with_capacity(slice.len() + end - start + 2);
+            text.push('[');
+            for index in start..end {
+                if index > start {
+                    text.push(',');
+                }
+                text.push_str(&rows.text[rows.start(index)..rows.ends[index]]);
+            }
+            text.push(']');
+            text
+        };
+        Self {
+            text,
+            total_rows: rows.len(),
+            rows: end - start,
+            has_more: end < rows.len(),
+        }
+    }
+}
+
+fn run_fetch_result_page_tool(args: &Map<String, Value>) -> Result<Value, CallError> {
+    let result_id = get_optional_string(args, "result_id")
+        .map_err(CallError::InvalidParams)?
+        .ok_or_else(|| CallError::InvalidParams("result_id is required".to_string()))?;
+    let page = get_optional_index(args, "page").map_err(CallError::InvalidParams)?;
+    let offset = get_optional_index(args, "offset").map_err(CallError::InvalidParams)?;
+    let rows = get_optional_usize(args, "rows").map_err(CallError::InvalidParams)?;
+    if page.is_some() && offset.is_some() {
+        return Err(CallError::InvalidParams(
+            "page and offset are mutually exclusive".to_string(),
+        ));
+    }
+
+    let mut cache = result_cache().lock().unwrap_or_else(|err| err.into_inner());
+    let Some(entry) = cache.get(&result_id) else {
+        let message = format!(
+            "result {} is unknown or has expired; run the transform again with page_rows",
+            result_id
+        );
+        return Ok(tool_error_result(
+            &message,
+            Some(vec![json!({
+                "type": "result_expired",
+                "result_id": result_id,
+                "message": message,
+            })]),
+        ));
+    };
+    let rows = rows.unwrap_or(entry.page_rows);
+    let offset = offset.unwrap_or_else(|| page.unwrap_or(0).saturating_mul(rows));
+    let result_page = ResultPage::new(&entry.rows, offset, rows);
+    Ok(json!({
+        "content": [{ "type": "text", "text": result_page.text }],
+        "meta": {
+            "result_id": result_id,
+            "total_rows": result_page.total_rows,
+            "offset": offset,
+            "rows": result_page.rows,
+            "page": offset / rows,
+            "has_more": result_page.has_more,
+        }
+    }))
+}
+
+// A record that fails to map is an error result that still carries its trace in meta.trace.
+fn run_trace_record_tool(args: &Map<String, Value>) -> Result<Value, CallError> {
+    let record_index = match args.get("record_index") {
+        None | Some(Value::Null) => 0,
+        Some(value) => value
+            .as_u64()
+            .map(|value| usize::try_from(value).unwrap_or(usize::MAX))
+            .ok_or_else(|| {
+                CallError::InvalidParams("record_index must be a non-negative integer".to_string())
+            })?,
+    };
+    let TransformSources {
+        rule,
+        input,
+        context,
+        ..
+    } = load_transform_sources(args)?;
+
+    let limits = current_limits();
+    let record = match &input {
+        ToolInput::Text(text) => match limits.max_bytes {
+            Some(max) if text.len() > max => Err(TransformError::new(
+                TransformErrorKind::InvalidInput,
+                format!("max_bytes exceeded: input is {} bytes, more than {}", text.len(), max),
+            )
+            .with_code(TransformErrorCode::LimitExceeded)),
+            _ => input_record(&rule, text, record_index),
+        },
+        ToolInput::Json(value) => select_json_records(&rule, value)
+            .map(|records| records.get(record_index).cloned()),
+    }
+    .map_err(|err| CallError::Tool {
+        message: transform_error_to_text(&err),
+        errors: Some(vec![transform_error_json(&err)]),
+    })?;
+    let Some(record) = record else {
+        return Ok(tool_error_result(
+            &format!("input has no record {}", record_index),
+            None,
+        ));
+    };
+
+    let trace = transform_record_traced(&rule, &record, context.as_ref());
+    let mut meta = trace.to_json();
+    meta["record_index"] = json!(record_index);
+    let warnings: Vec<_> = trace.warnings.iter().map(transform_warning_json).collect();
+    meta["warnings"] = json!(warnings);
+    if let Some(err) = &trace.error {
+        let mut result = tool_error_result(
+            &transform_error_to_text(err),
+            Some(vec![transform_error_json(err)]),
+        );
+        result["meta"]["trace"] = meta;
+        return Ok(result);
+    }
+    let written = trace.mappings.iter().filter(|mapping| mapping.written).count();
+    let text = match &trace.output {
+        Some(output) => format!(
+            "record {}: {} of {} mappings written: {}",
+            record_index,
+            written,
+            trace.mappings.len(),
+            serde_json::to_string(output).unwrap_or_default()
+        ),
+        None => format!("record {}: dropped by record_when", record_index),
+    };
+    Ok(json!({
+        "content": [{ "type": "text", "text": text }],
+        "meta": { "trace": meta }
+    }))
+}
+
+// Differences are a successful result; `isError` is only set when the transform itself fails.
+fn run_transform_check_tool(
+    args: &Map<String, Value>,
+    cancelled: &AtomicBool,
+) -> Result<Value, CallError> {
+    let expected_path = get_optional_string(args, "expected_path").map_err(CallError::InvalidParams)?;
+    let expected_json =
+        get_optional_json_value(args, "expected_json").map_err(CallError::InvalidParams)?;
+    let array_key = get_optional_string(args, "array_key").map_err(CallError::InvalidParams)?;
+    let expected = match (expected_path, expected_json) {
+        (Some(_), Some(_)) => {
+            return Err(CallError::InvalidParams(
+                "expected_path and expected_json are mutually exclusive".to_string(),
+            ))
+        }
+        (None, None) => {
+            return Err(CallError::InvalidParams(
+                "expected_path or expected_json is required".to_string(),
+            ))
+        }
+        (None, Some(value)) => value,
+        (Some(path), None) => {
+            let data = fs::read_to_string(&path).map_err(|err| {
+                let message = format!("failed to read expected output: {}", err);
+                CallError::Tool {
+                    message: message.clone(),
+                    errors: Some(vec![io_error_json(&message, Some(&path))]),
+                }
+            })?;
+            serde_json::from_str(&data).map_err(|err| {
+                let message = format!("failed to parse expected output JSON: {}", err);
+                CallError::Tool {
+                    message: message.clone(),
+                    errors: Some(vec![parse_error_json(&message, Some(&path))]),
+                }
+            })?
+        }
+    };
+
+    let TransformSources {
+        rule,
+        input,
+        context,
+        allow_env,
+        ..
+    } = load_transform_sources(args)?;
+    let control = CallControl {
+        cancelled,
+        deadline: None,
+    };
+    let (actual, _, _) =
+        transform_to_json(&rule, &input, context.as_ref(), None, &control, allow_env)?;
+    let actual = actual.unwrap_or(Value::Null);
+
+    let arrays = array_key.map_or(ArrayMatch::Ordered, ArrayMatch::Key);
+    let diff = diff_json(&expected, &actual, &arrays);
+    let text = if diff.is_empty() {
+        "ok: output matches".to_string()
+    } else {
+        format!("{} differences", diff.len())
+    };
+    Ok(json!({
+        "content": [
+            {
+                "type": "text",
+                "text": text
+            }
+        ],
+        "meta": {
+            "identical": diff.is_empty(),
+            "diff": diff.iter().map(DiffEntry::to_json).collect::<Vec<_>>()
+        }
+    }))
+}
+
+// Rules, input and context shared by the `transform`, `transform_check` and `preflight` tools.
+struct TransformSources {
+    rule: RuleFile,
+    yaml: String,
+    input: ToolInput,
+    context: Option<Value>,
+    // `TABLE_FALLBACK` warnings from reading the `tables` in `context_files`.
+    table_warnings: Vec<TransformWarning>,
+    allow_env: bool,
+}
+
+fn load_transform_sources(args: &Map<String, Value>) -> Result<TransformSources, CallError> {
+    let rules_path = get_optional_string(args, "rules_path").map_err(CallError::InvalidParams)?;
+    let rules_text = get_optional_string(args, "rules_text").map_err(CallError::InvalidParams)?;
+    let input_path = get_optional_string(args, "input_path").map_err(CallError::InvalidParams)?;
+    let input_text = get_optional_string(args, "input_text").map_err(CallError::InvalidParams)?;
+    let input_json = get_optional_json_value(args, "input_json").map_err(CallError::InvalidParams)?;
+    let context_path = get_optional_string(args, "context_path").map_err(CallError::InvalidParams)?;
+    let context_json = get_optional_object(args, "context_json").map_err(CallError::InvalidParams)?;
+    let context_files = get_optional_object(args, "context_files").map_err(CallError::InvalidParams)?;
+    let format = get_optional_string(args, "format").map_err(CallError::InvalidParams)?;
+    let allow_env = get_optional_bool(args, "allow_env")
+        .map_err(CallError::InvalidParams)?
+        .unwrap_or(false);
+
+    let rule_source_count = rules_path.is_some() as u8 + rules_text.is_some() as u8;
+    if rule_source_count == 0 {
         return Err(CallError::InvalidParams(
-            "context_path and context_json are mutually exclusive".to_string(),
+            "rules_path or rules_text is required".to_string(),
         ));
     }
+    if rule_source_count > 1 {
+        return Err(CallError::InvalidParams(
+            "rules_path and rules_text are mutually exclusive".to_string(),
+        ));
+    }
+
+    let input_source_count =
+        input_path.is_some() as u8 + input_text.is_some() as u8 + input_json.is_some() as u8;
+    if input_source_count == 0 {
+        return Err(CallError::InvalidParams(
+            "input_path, input_text, or input_json is required".to_string(),
+        ));
+    }
+    if input_source_count > 1 {
+        return Err(CallError::InvalidParams(
+            "input_path, input_text, and input_json are mutually exclusive".to_string(),
+        ));
+    }
+
+    let context_source_count = context_path.is_some() as u8
+        + context_json.is_some() as u8
+        + context_files.is_some() as u8;
+    if context_source_count > 1 {
+        return Err(CallError::InvalidParams(
+            "context_path, context_json, and context_files are mutually exclusive".to_string(),
+        ));
+    }
+    let context_files = match context_files {
+        Some(Value::Object(files)) => Some(
+            files
+                .into_iter()
+                .map(|(name, path)| match path {
+                    Value::String(path) if !name.is_empty() => Ok((name, path)),
+                    _ => Err(CallError::InvalidParams(
+                        "context_files must map names to file paths".to_string(),
+                    )),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        _ => None,
+    };
 
     if input_json.is_some()
         && format
             .as_deref()
-            .is_some_and(|value| value.eq_ignore_ascii_case("csv"))
+            .is_some_and(|value| !value.eq_ignore_ascii_case("json"))
     {
         return Err(CallError::InvalidParams(
             "format must be json when input_json is provided".to_string(),
@@ -805,31 +2244,29 @@ fn run_transform_tool(args: &Map<String, Value>) -> Result<Value, CallError> {
     }
     if format
         .as_deref()
-        .is_some_and(|value| !value.eq_ignore_ascii_case("csv") && !value.eq_ignore_ascii_case("json"))
+        .is_some_and(|value| {
+            !["csv", "json", "ndjson"]
+                .iter()
+                .any(|format| value.eq_ignore_ascii_case(format))
+        })
     {
         return Err(CallError::InvalidParams(
-            "format must be csv or json".to_string(),
+            "format must be csv, json, or ndjson".to_string(),
         ));
     }
 
     let (mut rule, yaml) = load_rule_from_source(rules_path.as_deref(), rules_text.as_deref())?;
-
-    let input = match (input_path.as_deref(), input_text.as_deref(), input_json.as_ref()) {
-        (Some(path), None, None) => fs::read_to_string(path).map_err(|err| {
+    let has_input_json = input_json.is_some();
+    let input = match (input_path.as_deref(), input_text.as_deref(), input_json) {
+        (Some(path), None, None) => ToolInput::Text(fs::read_to_string(path).map_err(|err| {
             let message = format!("failed to read input: {}", err);
             CallError::Tool {
                 message: message.clone(),
                 errors: Some(vec![io_error_json(&message, Some(path))]),
             }
-        })?,
-        (None, Some(text), None) => text.to_string(),
-        (None, None, Some(value)) => serde_json::to_string(value).map_err(|err| {
-            let message = format!("failed to serialize input JSON: {}", err);
-            CallError::Tool {
-                message: message.clone(),
-                errors: Some(vec![parse_error_json(&message, None)]),
-            }
-        })?,
+        })?),
+        (None, Some(text), None) => ToolInput::Text(text.to_string()),
+        (None, None, Some(value)) => ToolInput::Json(value),
         _ => {
             return Err(CallError::InvalidParams(
                 "input_path, input_text, or input_json is required".to_string(),
@@ -837,29 +2274,28 @@ fn run_transform_tool(args: &Map<String, Value>) -> Result<Value, CallError> {
         }
     };
 
-    let context_value = match (context_path.as_deref(), context_json.as_ref()) {
-        (Some(path), None) => {
-            let data = fs::read_to_string(path).map_err(|err| {
-                let message = format!("failed to read context: {}", err);
-                CallError::Tool {
-                    message: message.clone(),
-                    errors: Some(vec![io_error_json(&message, Some(path))]),
-                }
-            })?;
-            Some(serde_json::from_str(&data).map_err(|err| {
-                let message = format!("failed to parse context JSON: {}", err);
-                CallError::Tool {
-                    message: message.clone(),
-                    errors: Some(vec![parse_error_json(&message, Some(path))]),
-                }
-            })?)
+    let mut table_warnings = Vec::new();
+    let context_value = if let Some(path) = context_path.as_deref() {
+        Some(read_context_file(path)?)
+    } else if let Some(files) = context_files {
+        let mut context = Map::new();
+        for (name, path) in files {
+            let declared = rule.tables.as_ref().is_some_and(|tables| tables.contains_key(&name));
+            let value = if declared {
+                let (table, warnings) = read_table_file(&rule, &name, &path)?;
+                table_warnings.extend(warnings);
+                table.into_records()
+            } else {
+                read_context_file(&path)?
+            };
+            context.insert(name, value);
         }
-        (None, Some(value)) => Some(value.clone()),
-        (None, None) => None,
-        _ => None,
+        Some(Value::Object(context))
+    } else {
+        context_json
     };
 
-    let format_override = if input_json.is_some() {
+    let format_override = if has_input_json {
         Some("json".to_string())
     } else {
         format
@@ -867,103 +2303,58 @@ fn run_transform_tool(args: &Map<String, Value>) -> Result<Value, CallError> {
     apply_format_override(&mut rule, format_override.as_deref())
         .map_err(CallError::InvalidParams)?;
 
-    if validate {
-        if let Err(errors) = validate_rule_file_with_source(&rule, &yaml) {
-            let error_text = validation_errors_to_text(&errors);
-            let error_values = validation_errors_to_values(&errors);
-            return Err(CallError::Tool {
-                message: error_text,
-                errors: Some(error_values),
-            });
-        }
-    }
+    Ok(TransformSources {
+        rule,
+        yaml,
+        input,
+        context: context_value,
+        table_warnings,
+        allow_env,
+    })
+}
 
-    let (output_value, output_text, warnings) = if ndjson {
-        let (output_text, warnings) = transform_to_ndjson(&rule, &input, context_value.as_ref())?;
-        (None, output_text, warnings)
+// JSON context file, or a `.csv` file read as an array of row objects.
+fn read_context_file(path: &str) -> Result<Value, CallError> {
+    let data = fs::read_to_string(path).map_err(|err| {
+        let message = format!("failed to read context: {}", err);
+        CallError::Tool {
+            message: message.clone(),
+            errors: Some(vec![io_error_json(&message, Some(path))]),
+        }
+    })?;
+    let is_csv = Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+    let parsed = if is_csv {
+        parse_csv_records(&data)
+            .map(Value::Array)
+            .map_err(|err| format!("failed to parse context CSV: {}", err))
     } else {
-        let (output, warnings) =
-            transform_with_warnings(&rule, &input, context_value.as_ref()).map_err(|err| {
-                CallError::Tool {
-                    message: transform_error_to_text(&err),
-                    errors: Some(vec![transform_error_json(&err)]),
-                }
-            })?;
-        let output_text = serde_json::to_string(&output).map_err(|err| {
-            let message = format!("failed to serialize output JSON: {}", err);
-            CallError::Tool {
-                message: message.clone(),
-                errors: Some(vec![parse_error_json(&message, None)]),
-            }
-        })?;
-        (Some(output), output_text, warnings)
+        serde_json::from_str(&data).map_err(|err| format!("failed to parse context JSON: {}", err))
     };
+    parsed.map_err(|message| CallError::Tool {
+        message: message.clone(),
+        errors: Some(vec![parse_error_json(&message, Some(path))]),
+    })
+}
 
-    if let Some(path) = output_path.as_deref() {
-        write_output(path, &output_text).map_err(|err| {
-            let message = err;
-            CallError::Tool {
-                message: message.clone(),
-                errors: Some(vec![io_error_json(&message, Some(path))]),
-            }
-        })?;
-    }
-
-    let output_bytes = output_text.as_bytes().len();
-    let mut response_text = output_text.clone();
-    let mut truncated = false;
-
-    if ndjson {
-        if let Some(limit) = preview_rows {
-            let preview = preview_ndjson(&output_text, limit);
-            if preview.len() != output_text.len() {
-                truncated = true;
-            }
-            response_text = preview;
-        }
-    }
-
-    if let Some(max_bytes) = max_output_bytes {
-        if output_bytes > max_bytes {
-            truncated = true;
-        }
-        if response_text.as_bytes().len() > max_bytes {
-            response_text = truncate_to_bytes(&response_text, max_bytes).to_string();
-            truncated = true;
-        }
-    }
-
-    let mut result = json!({
-        "content": [
-            {
-                "type": "text",
-                "text": response_text
-            }
-        ]
-    });
-
-    let exceeds_max = max_output_bytes.map_or(false, |max| output_bytes > max);
-    let mut meta = serde_json::Map::new();
-    if !warnings.is_empty() {
-        meta.insert("warnings".to_string(), warnings_to_json(&warnings));
-    }
-    if let Some(path) = output_path {
-        meta.insert("output_path".to_string(), json!(path));
-    }
-    if truncated {
-        meta.insert("output_bytes".to_string(), json!(output_bytes));
-        meta.insert("truncated".to_string(), json!(true));
-    }
-    if return_output_json && !ndjson && !exceeds_max {
-        if let Some(output) = output_value {
-            meta.insert("output".to_string(), output);
+// A context file the rule declares in `tables`, read as the entry says.
+fn read_table_file(
+    rule: &RuleFile,
+    name: &str,
+    path: &str,
+) -> Result<(LoadedTable, Vec<TransformWarning>), CallError> {
+    let file = fs::File::open(path).map_err(|err| {
+        let message = format!("failed to read context: {}", err);
+        CallError::Tool {
+            message: message.clone(),
+            errors: Some(vec![io_error_json(&message, Some(path))]),
         }
-    }
-    if !meta.is_empty() {
-        result["meta"] = Value::Object(meta);
-    }
-
-    Ok(result)
+    })?;
+    load_table(rule, name, io::BufReader::new(file)).map_err(|err| CallError::Tool {
+        message: transform_error_to_text(&err),
+        errors: Some(vec![transform_error_json(&err)]),
+    })
 }
 
 fn run_validate_rules_tool(args: &Map<String, Value>) -> Result<Value, CallError> {
@@ -982,41 +2373,44 @@ fn run_validate_rules_tool(args: &Map<String, Value>) -> Result<Value, CallError
         ));
     }
 
+    let allow_unknown_ops = get_optional_bool(args, "allow_unknown_ops")
+        .map_err(CallError::InvalidParams)?
+        .unwrap_or(false);
+
     let (rule, yaml) = load_rule_from_source(rules_path.as_deref(), rules_text.as_deref())?;
-    match validate_rule_file_with_source(&rule, &yaml) {
-        Ok(_) => {
-            let warnings = collect_rule_warnings(&rule);
-            let mut result = json!({
-                "content": [
-                    {
-                        "type": "text",
-                        "text": "ok"
-                    }
-                ]
-            });
-            if !warnings.is_empty() {
-                result["meta"] = json!({
-                    "warnings": rule_warnings_to_json(&warnings)
-                });
-            }
-            Ok(result)
-        }
-        Err(errors) => {
-            let error_values = validation_errors_to_values(&errors);
-            Ok(json!({
-                "content": [
-                    {
-                        "type": "text",
-                        "text": "validation failed"
-                    }
-                ],
-                "isError": true,
-                "meta": {
-                    "errors": error_values
+    let mut report = validate_rule_file_full(&rule, Some(&yaml));
+    if allow_unknown_ops {
+        report.allow_unknown_ops();
+    }
+    if !report.is_valid() {
+        let error_values = validation_errors_to_values(&report.errors);
+        return Ok(json!({
+            "content": [
+                {
+                    "type": "text",
+                    "text": "validation failed"
                 }
-            }))
-        }
+            ],
+            "isError": true,
+            "meta": {
+                "errors": error_values
+            }
+        }));
+    }
+    let mut result = json!({
+        "content": [
+            {
+                "type": "text",
+                "text": "ok"
+            }
+        ]
+    });
+    if !report.warnings.is_empty() {
+        result["meta"] = json!({
+            "warnings": rule_warnings_to_json(&report.warnings)
+        });
     }
+    Ok(result)
 }
 
 fn run_generate_dto_tool(args: &Map<String, Value>) -> Result<Value, CallError> {
@@ -1073,122 +2467,51 @@ fn run_generate_dto_tool(args: &Map<String, Value>) -> Result<Value, CallError>
 }
 
 fn run_list_ops_tool() -> Result<Value, CallError> {
+    let ops_in = |category: OpCategory| -> Vec<&'static str> {
+        OPS.iter()
+            .filter(|spec| spec.categories.contains(&category))
+            .map(|spec| spec.name)
+            .collect()
+    };
+    // Logical, conditional and comparison ops are listed separately, not under expr_ops.
+    let expr_ops: Vec<&'static str> = OPS
+        .iter()
+        .filter(|spec| {
+            !spec.categories.iter().any(|category| {
+                matches!(
+                    category,
+                    OpCategory::Logical | OpCategory::Conditional | OpCategory::Comparison
+                )
+            })
+        })
+        .map(|spec| spec.name)
+        .collect();
+    let signatures: Map<String, Value> = OPS
+        .iter()
+        .map(|spec| {
+            let signature = json!({
+                "min_args": spec.min_args,
+                "max_args": spec.max_args,
+                "args": spec.args
+            });
+            (spec.name.to_string(), signature)
+        })
+        .collect();
+    let categories: Map<String, Value> = [
+        OpCategory::String,
+        OpCategory::Json,
+        OpCategory::Array,
+        OpCategory::Numeric,
+        OpCategory::Date,
+    ]
+    .into_iter()
+    .map(|category| (category.as_str().to_string(), json!(ops_in(category))))
+    .collect();
+
     let ops = json!({
-        "expr_ops": [
-            "concat",
-            "coalesce",
-            "to_string",
-            "trim",
-            "lowercase",
-            "uppercase",
-            "replace",
-            "split",
-            "pad_start",
-            "pad_end",
-            "lookup",
-            "lookup_first",
-            "merge",
-            "deep_merge",
-            "get",
-            "pick",
-            "omit",
-            "keys",
-            "values",
-            "entries",
-            "object_flatten",
-            "object_unflatten",
-            "map",
-            "filter",
-            "flat_map",
-            "flatten",
-            "take",
-            "drop",
-            "slice",
-            "chunk",
-            "zip",
-            "zip_with",
-            "unzip",
-            "group_by",
-            "key_by",
-            "partition",
-            "unique",
-            "distinct_by",
-            "sort_by",
-            "find",
-            "find_index",
-            "index_of",
-            "contains",
-            "sum",
-            "avg",
-            "min",
-            "max",
-            "reduce",
-            "fold",
-            "+",
-            "-",
-            "*",
-            "/",
-            "round",
-            "to_base",
-            "date_format",
-            "to_unixtime"
-        ],
-        "categories": {
-            "string_ops": [
-                "concat",
-                "to_string",
-                "trim",
-                "lowercase",
-                "uppercase",
-                "replace",
-                "split",
-                "pad_start",
-                "pad_end"
-            ],
-            "json_ops": [
-                "merge",
-                "deep_merge",
-                "get",
-                "pick",
-                "omit",
-                "keys",
-                "values",
-                "entries",
-                "object_flatten",
-                "object_unflatten"
-            ],
-            "array_ops": [
-                "map",
-                "filter",
-                "flat_map",
-                "flatten",
-                "take",
-                "drop",
-                "slice",
-                "chunk",
-                "zip",
-                "zip_with",
-                "unzip",
-                "group_by",
-                "key_by",
-                "partition",
-                "unique",
-                "distinct_by",
-                "sort_by",
-                "find",
-                "find_index",
-                "index_of",
-                "contains",
-                "sum",
-                "avg",
-                "min",
-                "max",
-                "reduce",
-                "fold"
-            ],
-            "numeric_ops": ["+", "-", "*", "/", "round", "to_base", "sum", "avg", "min", "max"],
-            "date_ops": ["date_format", "to_unixtime"]
-        },
+        "expr_ops": expr_ops,
+        "categories": categories,
+        "signatures": signatures,
         "category_docs": {
             "string_ops": {
                 "summary": "String transformations and formatting.",
@@ -1203,6 +2526,33 @@ fn run_list_ops_tool() -> Result<Value, CallError> {
                             "op": "concat",
                             "args": [ { "ref": "input.first" }, " ", { "ref": "input.last" } ]
                         }
+                    },
+                    {
+                        "op": "concat_ws",
+                        "expr": {
+                            "op": "concat_ws",
+                            "args": [
+                                " ",
+                                { "ref": "input.first" },
+                                { "ref": "input.middle" },
+                                { "ref": "input.last" }
+                            ]
+                        }
+                    },
+                    {
+                        "op": "regex_extract",
+                        "expr": {
+                            "op": "regex_extract",
+                            "args": [ { "ref": "input.order" }, "^ORD-(?<id>\\d+)", "id" ]
+                        }
+                    },
+                    {
+                        "op": "regex_match",
+                        "expr": { "op": "regex_match", "args": [ { "ref": "input.code" }, "^[A-Z]{4}$" ] }
+                    },
+                    {
+                        "op": "substring",
+                        "expr": { "op": "substring", "args": [ { "ref": "input.code" }, 0, 4 ] }
                     }
                 ]
             },
@@ -1223,6 +2573,16 @@ fn run_list_ops_tool() -> Result<Value, CallError> {
                     {
                         "op": "pick",
                         "expr": { "op": "pick", "args": [ { "ref": "input.obj" }, ["id"] ] }
+                    },
+                    {
+                        "op": "object_flatten",
+                        "expr": {
+                            "op": "object_flatten",
+                            "args": [
+                                { "ref": "input.obj" },
+                                { "separator": "__", "max_depth": 2, "arrays": "index" }
+                            ]
+                        }
                     }
                 ]
             },
@@ -1265,9 +2625,10 @@ fn run_list_ops_tool() -> Result<Value, CallError> {
                 ]
             }
         },
-        "logical_ops": ["and", "or", "not"],
-        "comparison_ops": ["==", "!=", "<", "<=", ">", ">=", "~="],
-        "type_casts": ["string", "int", "float", "bool"]
+        "logical_ops": ops_in(OpCategory::Logical),
+        "conditional_ops": ops_in(OpCategory::Conditional),
+        "comparison_ops": ops_in(OpCategory::Comparison),
+        "type_casts": ["string", "int", "float", "bool", "date", "datetime", "decimal", "decimal(N)"]
     });
 
     let text = serde_json::to_string_pretty(&ops)
@@ -1286,6 +2647,127 @@ fn run_list_ops_tool() -> Result<Value, CallError> {
     }))
 }
 
+fn run_list_error_codes_tool() -> Result<Value, CallError> {
+    let codes: Vec<Value> = error_catalog()
+        .iter()
+        .map(|(code, kind, description)| {
+            json!({
+                "code": code.as_str(),
+                "name": code.name(),
+                "kind": transform_kind_to_str(kind),
+                "description": description
+            })
+        })
+        .collect();
+    let lines: Vec<String> = error_catalog()
+        .iter()
+        .map(|(code, kind, description)| {
+            format!(
+                "{} {} ({}): {}",
+                code.as_str(),
+                code.name(),
+                transform_kind_to_str(kind),
+                description
+            )
+        })
+        .collect();
+
+    Ok(json!({
+        "content": [
+            {
+                "type": "text",
+                "text": lines.join("\n")
+            }
+        ],
+        "meta": {
+            "codes": codes
+        }
+    }))
+}
+
+fn run_cache_stats_tool(args: &Map<String, Value>) -> Result<Value, CallError> {
+    let clear = get_optional_bool(args, "clear")
+        .map_err(CallError::InvalidParams)?
+        .unwrap_or(false);
+    let stats = rule_cache_stats();
+    if clear {
+        clear_rule_cache();
+    }
+    Ok(json!({
+        "content": [
+            {
+                "type": "text",
+                "text": format!(
+                    "rules cache: {} hits, {} misses, {} entries",
+                    stats.hits, stats.misses, stats.entries
+                )
+            }
+        ],
+        "meta": {
+            "hits": stats.hits,
+            "misses": stats.misses,
+            "entries": stats.entries,
+            "cleared": clear
+        }
+    }))
+}
+
+fn run_self_check_tool() -> Result<Value, CallError> {
+    let environment = environment_info();
+    let mut checks = run_self_checks();
+    checks.push(run_check("stdio_framing", check_stdio_framing));
+    let passed = checks.iter().all(|check| check.passed);
+
+    let mut lines = vec![format!("transform-rules-mcp {}", environment.version)];
+    for check in &checks {
+        let status = if check.passed { "ok" } else { "FAIL" };
+        match &check.message {
+            Some(message) => lines.push(format!("{} {}: {}", status, check.name, message)),
+            None => lines.push(format!("{} {}", status, check.name)),
+        }
+    }
+
+    let mut result = json!({
+        "content": [
+            {
+                "type": "text",
+                "text": lines.join("\n")
+            }
+        ],
+        "meta": {
+            "ok": passed,
+            "environment": environment.to_json(),
+            "checks": checks.iter().map(|check| check.to_json()).collect::<Vec<_>>()
+        }
+    });
+    if !passed {
+        result["isError"] = json!(true);
+    }
+    Ok(result)
+}
+
+// Writes a message in both framing modes and reads it back through the real reader.
+fn check_stdio_framing() -> Result<(), String> {
+    let message = json!({ "jsonrpc": "2.0", "id": 1, "result": { "text": "héllo\nworld" } });
+    for mode in [OutputMode::Line, OutputMode::ContentLength] {
+        let mut buffer = Vec::new();
+        write_message(&mut buffer, mode, &message).map_err(|err| err.to_string())?;
+        let mut reader = BufReader::new(buffer.as_slice());
+        let mut read_mode = OutputMode::Line;
+        let text = match read_message(&mut reader, &mut read_mode) {
+            Ok(Some(IncomingMessage::Text(text))) => text,
+            Ok(Some(IncomingMessage::Invalid(message))) => return Err(message),
+            Ok(None) => return Err(format!("{:?} message was not read back", mode)),
+            Err(err) => return Err(err.to_string()),
+        };
+        let value: Value = serde_json::from_str(&text).map_err(|err| err.to_string())?;
+        if value != message || read_mode != mode {
+            return Err(format!("{:?} framing did not round-trip", mode));
+        }
+    }
+    Ok(())
+}
+
 fn run_analyze_input_tool(args: &Map<String, Value>) -> Result<Value, CallError> {
     let input_path = get_optional_string(args, "input_path").map_err(CallError::InvalidParams)?;
     let input_text = get_optional_string(args, "input_text").map_err(CallError::InvalidParams)?;
@@ -1294,6 +2776,10 @@ fn run_analyze_input_tool(args: &Map<String, Value>) -> Result<Value, CallError>
     let records_path =
         get_optional_string(args, "records_path").map_err(CallError::InvalidParams)?;
     let max_paths = get_optional_usize(args, "max_paths").map_err(CallError::InvalidParams)?;
+    let max_array_samples =
+        get_optional_usize(args, "max_array_samples").map_err(CallError::InvalidParams)?;
+    let max_array_depth =
+        get_optional_usize(args, "max_array_depth").map_err(CallError::InvalidParams)?;
 
     let input_source_count =
         input_path.is_some() as u8 + input_text.is_some() as u8 + input_json.is_some() as u8;
@@ -1356,10 +2842,22 @@ fn run_analyze_input_tool(args: &Map<String, Value>) -> Result<Value, CallError>
                     errors: Some(vec![parse_error_json(&message, input_path.as_deref())]),
                 }
             })?,
+            InputDataFormat::Ndjson => parse_ndjson_records(&input_text).map_err(|err| {
+                let message = format!("failed to parse input NDJSON: {}", err);
+                CallError::Tool {
+                    message: message.clone(),
+                    errors: Some(vec![parse_error_json(&message, input_path.as_deref())]),
+                }
+            })?,
         }
     };
 
-    let stats = analyze_records(&records, max_paths);
+    let limits = AnalyzeLimits {
+        max_paths,
+        max_array_samples: max_array_samples.unwrap_or(DEFAULT_MAX_ARRAY_SAMPLES),
+        max_array_depth: max_array_depth.unwrap_or(DEFAULT_MAX_ARRAY_DEPTH),
+    };
+    let stats = analyze_records(&records, &limits);
     let paths_json = stats_to_json(&stats);
 
     let summary = json!({
@@ -1485,6 +2983,7 @@ fn run_generate_rules_from_base_tool(args: &Map<String, Value>) -> Result<Value,
         match rule.input.format {
             InputFormat::Csv => InputDataFormat::Csv,
             InputFormat::Json => InputDataFormat::Json,
+            InputFormat::Ndjson => InputDataFormat::Ndjson,
         }
     };
 
@@ -1510,6 +3009,13 @@ fn run_generate_rules_from_base_tool(args: &Map<String, Value>) -> Result<Value,
                 errors: Some(vec![parse_error_json(&message, input_path.as_deref())]),
             }
         })?,
+        (InputDataFormat::Ndjson, _) => parse_ndjson_records(&input_text).map_err(|err| {
+            let message = format!("failed to parse input NDJSON: {}", err);
+            CallError::Tool {
+                message: message.clone(),
+                errors: Some(vec![parse_error_json(&message, input_path.as_deref())]),
+            }
+        })?,
     };
 
     let format_override = if has_input_json {
@@ -1526,7 +3032,7 @@ fn run_generate_rules_from_base_tool(args: &Map<String, Value>) -> Result<Value,
     };
     update_yaml_input_spec(&mut yaml_value, format_for_yaml, records_path.as_deref());
 
-    let stats = analyze_records(&records, None);
+    let stats = analyze_records(&records, &AnalyzeLimits::default());
     let input_paths = build_input_paths(&stats);
     let input_path_set: HashSet<String> =
         input_paths.iter().map(|info| info.path.clone()).collect();
@@ -1539,12 +3045,13 @@ fn run_generate_rules_from_base_tool(args: &Map<String, Value>) -> Result<Value,
     let mut mapped = 0usize;
     let mut with_expr = 0usize;
     let mut with_value = 0usize;
-
-    let mappings = yaml_mappings_sequence_mut(&mut yaml_value)?;
+    let mut with_fallback = 0usize;
+    let mut inherited = Vec::new();
 
     for (index, mapping) in rule.mappings.iter().enumerate() {
         collect_missing_refs(
             &mapping.target,
+            mapping.source.as_ref(),
             mapping.expr.as_ref(),
             mapping.when.as_ref(),
             &input_path_set,
@@ -1559,23 +3066,40 @@ fn run_generate_rules_from_base_tool(args: &Map<String, Value>) -> Result<Value,
             with_value += 1;
             continue;
         }
+        // A fallback list was written by hand, so it is kept as is.
+        if matches!(mapping.source, Some(MappingSource::Fallback(_))) {
+            with_fallback += 1;
+            continue;
+        }
+        let source_hint = mapping.source.as_ref().and_then(MappingSource::as_single);
+        // `const.*` is computed from context, so there is no input path to match.
+        if source_hint.is_some_and(|source| source.starts_with("const.")) {
+            continue;
+        }
+        // Only the file at rules_path is edited; mappings from the files it extends are kept.
+        let Some((group, local_index)) = declared_mapping(&rule, index)? else {
+            inherited.push(mapping.target.clone());
+            continue;
+        };
 
         let target_leaf = leaf_from_path(&mapping.target).unwrap_or_default();
+        let array_target = mapping.target.contains("[]");
         let candidates = select_candidates(
             &target_leaf,
-            mapping.source.as_deref(),
+            array_target,
+            source_hint,
             mapping.value_type.as_deref(),
             &input_paths,
             max_candidates,
         );
-        let selected = candidates.first().cloned();
+        let selected = tied_candidates(&candidates, array_target);
 
-        if let Some(selected) = selected.as_ref() {
-            mapped += 1;
-            update_yaml_mapping(mappings, index, Some(&selected.source))?;
-        } else {
+        let mappings = yaml_mappings_sequence_mut(&mut yaml_value, group.as_deref())?;
+        update_yaml_mapping(mappings, local_index, &selected)?;
+        if selected.is_empty() {
             unmapped.push(mapping.target.clone());
-            update_yaml_mapping(mappings, index, None)?;
+        } else {
+            mapped += 1;
         }
 
         let candidates_json: Vec<Value> = candidates
@@ -1593,20 +3117,38 @@ fn run_generate_rules_from_base_tool(args: &Map<String, Value>) -> Result<Value,
             "target": mapping.target,
             "candidates": candidates_json
         });
-        if let Some(selected) = selected {
-            entry["selected"] = json!(selected.source);
-            entry["confidence"] = json!(selected.confidence);
+        match selected.as_slice() {
+            [] => {}
+            [selected] => entry["selected"] = json!(selected),
+            fallback => entry["selected"] = json!(fallback),
+        }
+        if let Some(top) = candidates.first().filter(|_| !selected.is_empty()) {
+            entry["confidence"] = json!(top.confidence);
         }
         candidates_meta.push(entry);
     }
 
-    let output_text = serde_yaml::to_string(&yaml_value).map_err(|err| {
-        let message = format!("failed to serialize rules yaml: {}", err);
-        CallError::Tool {
-            message: message.clone(),
-            errors: Some(vec![parse_error_json(&message, None)]),
-        }
-    })?;
+    // Edit the base text in place so its comments and layout survive; the whole file is only
+    // serialized again when that cannot give the same rules.
+    let patched = patch_yaml(&yaml, &yaml_value);
+    let formatting_preserved = patched.is_some();
+    let output_text = match patched {
+        Some(text) => text,
+        None => serde_yaml::to_string(&yaml_value).map_err(|err| {
+            let message = format!("failed to serialize rules yaml: {}", err);
+            CallError::Tool {
+                message: message.clone(),
+                errors: Some(vec![parse_error_json(&message, None)]),
+            }
+        })?,
+    };
+    let diff_name = rules_path.as_deref().unwrap_or("rules.yaml");
+    let diff = unified_diff(
+        &yaml,
+        &output_text,
+        &format!("a/{}", diff_name),
+        &format!("b/{}", diff_name),
+    );
 
     let mut meta = serde_json::Map::new();
     meta.insert(
@@ -1616,13 +3158,20 @@ fn run_generate_rules_from_base_tool(args: &Map<String, Value>) -> Result<Value,
             "mapped": mapped,
             "unmapped": unmapped.len(),
             "with_expr": with_expr,
-            "with_value": with_value
+            "with_value": with_value,
+            "with_fallback": with_fallback,
+            "inherited": inherited.len()
         }),
     );
+    meta.insert("diff".to_string(), json!(diff));
+    meta.insert("formatting_preserved".to_string(), json!(formatting_preserved));
     meta.insert("candidates".to_string(), Value::Array(candidates_meta));
     if !unmapped.is_empty() {
         meta.insert("unmapped".to_string(), json!(unmapped));
     }
+    if !inherited.is_empty() {
+        meta.insert("inherited".to_string(), json!(inherited));
+    }
     if !missing_refs.is_empty() {
         meta.insert("missing_refs".to_string(), Value::Array(missing_refs));
     }
@@ -1741,6 +3290,13 @@ fn run_generate_rules_from_dto_tool(args: &Map<String, Value>) -> Result<Value,
                 errors: Some(vec![parse_error_json(&message, input_path.as_deref())]),
             }
         })?,
+        (InputDataFormat::Ndjson, _) => parse_ndjson_records(&input_text).map_err(|err| {
+            let message = format!("failed to parse input NDJSON: {}", err);
+            CallError::Tool {
+                message: message.clone(),
+                errors: Some(vec![parse_error_json(&message, input_path.as_deref())]),
+            }
+        })?,
     };
 
     let schema = parse_dto_schema(&dto_text, dto_language).map_err(|message| {
@@ -1756,7 +3312,7 @@ fn run_generate_rules_from_dto_tool(args: &Map<String, Value>) -> Result<Value,
         }
     })?;
 
-    let stats = analyze_records(&records, None);
+    let stats = analyze_records(&records, &AnalyzeLimits::default());
     let input_paths = build_input_paths(&stats);
     let max_candidates = max_candidates.unwrap_or(3);
 
@@ -1768,7 +3324,14 @@ fn run_generate_rules_from_dto_tool(args: &Map<String, Value>) -> Result<Value,
     for mapping in &generated {
         let target_leaf = leaf_from_path(&mapping.target).unwrap_or_default();
         let candidates =
-            select_candidates(&target_leaf, None, mapping.value_type.as_deref(), &input_paths, max_candidates);
+            select_candidates(
+                &target_leaf,
+                mapping.target.contains("[]"),
+                None,
+                mapping.value_type.as_deref(),
+                &input_paths,
+                max_candidates,
+            );
         let selected = candidates.first().cloned();
 
         let mut mapping_map = YamlMapping::new();
@@ -1778,10 +3341,8 @@ fn run_generate_rules_from_dto_tool(args: &Map<String, Value>) -> Result<Value,
         }
         if let Some(selected) = selected.as_ref() {
             mapped += 1;
-            mapping_map.insert(
-                yaml_key("source"),
-                YamlValue::String(selected.source.clone()),
-            );
+            let (key, value) = candidate_yaml(&selected.source);
+            mapping_map.insert(key, value);
             if mapping.required {
                 mapping_map.insert(yaml_key("required"), YamlValue::Bool(true));
             }
@@ -1826,6 +3387,7 @@ fn run_generate_rules_from_dto_tool(args: &Map<String, Value>) -> Result<Value,
         match parse_format {
             InputDataFormat::Csv => "csv".to_string(),
             InputDataFormat::Json => "json".to_string(),
+            InputDataFormat::Ndjson => "ndjson".to_string(),
         }
     };
 
@@ -1923,6 +3485,36 @@ fn get_optional_usize(args: &Map<String, Value>, key: &str) -> Result<Option<usi
     }
 }
 
+fn get_optional_index(args: &Map<String, Value>, key: &str) -> Result<Option<usize>, String> {
+    match args.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => value
+            .as_u64()
+            .map(|value| Some(usize::try_from(value).unwrap_or(usize::MAX)))
+            .ok_or_else(|| format!("{} must be a non-negative integer", key)),
+    }
+}
+
+fn get_optional_string_array(
+    args: &Map<String, Value>,
+    key: &str,
+) -> Result<Option<Vec<String>>, String> {
+    match args.get(key) {
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|item| {
+                item.as_str()
+                    .map(|value| value.to_string())
+                    .ok_or_else(|| format!("{} must be an array of strings", key))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(Some),
+        Some(Value::Null) => Ok(None),
+        Some(_) => Err(format!("{} must be an array of strings", key)),
+        None => Ok(None),
+    }
+}
+
 fn get_optional_json_value(args: &Map<String, Value>, key: &str) -> Result<Option<Value>, String> {
     match args.get(key) {
         Some(Value::Array(_)) | Some(Value::Object(_)) => Ok(args.get(key).cloned()),
@@ -1947,28 +3539,35 @@ fn load_rule_from_source(
 ) -> Result<(RuleFile, String), CallError> {
     match (rules_path, rules_text) {
         (Some(path), None) => {
-            let yaml = fs::read_to_string(path).map_err(|err| {
-                let message = format!("failed to read rules: {}", err);
-                CallError::Tool {
-                    message: message.clone(),
-                    errors: Some(vec![io_error_json(&message, Some(path))]),
-                }
-            })?;
-            let rule = parse_rule_file(&yaml).map_err(|err| {
-                let message = format!("failed to parse rules: {}", err);
+            let rule = load_rule_file(Path::new(path)).map_err(|err| {
+                let file = err.file.as_deref().unwrap_or(path);
+                let error = match err.code {
+                    ErrorCode::RulesReadFailed => io_error_json(&err.message, Some(file)),
+                    ErrorCode::RulesParseFailed => parse_error_json(&err.message, Some(file)),
+                    _ => validation_error_json(&err),
+                };
                 CallError::Tool {
-                    message: message.clone(),
-                    errors: Some(vec![parse_error_json(&message, Some(path))]),
+                    message: err.message.clone(),
+                    errors: Some(vec![error]),
                 }
             })?;
+            let yaml = rule
+                .origins
+                .as_ref()
+                .and_then(|origins| origins.files.last())
+                .map(|file| file.yaml.clone())
+                .unwrap_or_default();
             Ok((rule, yaml))
         }
         (None, Some(text)) => {
-            let rule = parse_rule_file(text).map_err(|err| {
-                let message = format!("failed to parse rules: {}", err);
+            let rule = parse_rule_file_with_limits(text, &current_limits()).map_err(|err| {
+                let error = match err.code {
+                    ErrorCode::LimitExceeded => limit_error_json(&err.message),
+                    _ => parse_error_json(&err.message, None),
+                };
                 CallError::Tool {
-                    message: message.clone(),
-                    errors: Some(vec![parse_error_json(&message, None)]),
+                    message: err.message.clone(),
+                    errors: Some(vec![error]),
                 }
             })?;
             Ok((rule, text.to_string()))
@@ -1988,8 +3587,11 @@ fn parse_dto_language(value: &str) -> Result<DtoLanguage, String> {
         "java" => Ok(DtoLanguage::Java),
         "kotlin" => Ok(DtoLanguage::Kotlin),
         "swift" => Ok(DtoLanguage::Swift),
-        _ => Err("language must be one of rust, typescript, python, go, java, kotlin, swift"
-            .to_string()),
+        "json-schema" | "jsonschema" => Ok(DtoLanguage::JsonSchema),
+        _ => Err(
+            "language must be one of rust, typescript, python, go, java, kotlin, swift, json-schema"
+                .to_string(),
+        ),
     }
 }
 
@@ -2002,6 +3604,7 @@ fn dto_language_to_str(language: DtoLanguage) -> &'static str {
         DtoLanguage::Java => "java",
         DtoLanguage::Kotlin => "kotlin",
         DtoLanguage::Swift => "swift",
+        DtoLanguage::JsonSchema => "json-schema",
     }
 }
 
@@ -2041,12 +3644,14 @@ fn dto_error_json(message: &str) -> Value {
 enum InputDataFormat {
     Json,
     Csv,
+    Ndjson,
 }
 
 fn normalize_format(format: Option<&str>, input_text: &str) -> InputDataFormat {
     match format.map(|value| value.to_lowercase()) {
         Some(value) if value == "csv" => InputDataFormat::Csv,
         Some(value) if value == "json" => InputDataFormat::Json,
+        Some(value) if value == "ndjson" => InputDataFormat::Ndjson,
         Some(_) => InputDataFormat::Json,
         None => match input_text.trim_start().chars().next() {
             Some('{') | Some('[') => InputDataFormat::Json,
@@ -2060,10 +3665,10 @@ fn json_records_from_value(
     records_path: Option<&str>,
 ) -> Result<Vec<Value>, CallError> {
     let target = if let Some(path) = records_path {
-        let tokens = parse_path_tokens(path).map_err(|message| {
-            CallError::InvalidParams(format!("records_path is invalid: {}", message))
+        let tokens = parse_path(path).map_err(|err| {
+            CallError::InvalidParams(format!("records_path is invalid: {}", err))
         })?;
-        get_value_by_tokens(value, &tokens).ok_or_else(|| {
+        get_path(value, &tokens).ok_or_else(|| {
             CallError::Tool {
                 message: "records_path did not match any value".to_string(),
                 errors: Some(vec![parse_error_json(
@@ -2089,60 +3694,20 @@ fn json_records_from_value(
     }
 }
 
-fn parse_csv_records(text: &str) -> Result<Vec<Value>, String> {
-    let mut reader = ReaderBuilder::new()
-        .has_headers(true)
-        .from_reader(text.as_bytes());
-    let headers = reader
-        .headers()
-        .map_err(|err| err.to_string())?
-        .iter()
-        .enumerate()
-        .map(|(index, name)| {
-            let trimmed = name.trim();
-            if trimmed.is_empty() {
-                format!("column_{}", index + 1)
-            } else {
-                trimmed.to_string()
-            }
-        })
-        .collect::<Vec<_>>();
-
+fn parse_ndjson_records(text: &str) -> Result<Vec<Value>, String> {
     let mut records = Vec::new();
-    for result in reader.records() {
-        let record = result.map_err(|err| err.to_string())?;
-        let mut obj = Map::new();
-        for (index, value) in record.iter().enumerate() {
-            if let Some(key) = headers.get(index) {
-                obj.insert(key.clone(), csv_cell_to_value(value));
-            }
+    for (index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
         }
-        records.push(Value::Object(obj));
-    }
-    Ok(records)
-}
-
-fn csv_cell_to_value(value: &str) -> Value {
-    let trimmed = value.trim();
-    if trimmed.is_empty() {
-        return Value::Null;
-    }
-    let lower = trimmed.to_ascii_lowercase();
-    if lower == "true" {
-        return Value::Bool(true);
-    }
-    if lower == "false" {
-        return Value::Bool(false);
-    }
-    if let Ok(number) = trimmed.parse::<i64>() {
-        return Value::Number(number.into());
-    }
-    if let Ok(number) = trimmed.parse::<f64>() {
-        if let Some(number) = serde_json::Number::from_f64(number) {
-            return Value::Number(number);
+        let value: Value = serde_json::from_str(line)
+            .map_err(|err| format!("line {}: {}", index + 1, err))?;
+        if !value.is_object() {
+            return Err(format!("line {}: expected a JSON object", index + 1));
         }
+        records.push(value);
     }
-    Value::String(trimmed.to_string())
+    Ok(records)
 }
 
 #[derive(Default)]
@@ -2152,35 +3717,80 @@ struct PathStats {
     examples: Vec<Value>,
 }
 
-fn analyze_records(records: &[Value], max_paths: Option<usize>) -> HashMap<String, PathStats> {
+struct AnalyzeLimits {
+    max_paths: Option<usize>,
+    max_array_samples: usize,
+    max_array_depth: usize,
+}
+
+impl Default for AnalyzeLimits {
+    fn default() -> Self {
+        Self {
+            max_paths: None,
+            max_array_samples: DEFAULT_MAX_ARRAY_SAMPLES,
+            max_array_depth: DEFAULT_MAX_ARRAY_DEPTH,
+        }
+    }
+}
+
+fn analyze_records(records: &[Value], limits: &AnalyzeLimits) -> HashMap<String, PathStats> {
     let mut stats = HashMap::new();
     for record in records {
-        collect_path_stats(record, "", &mut stats, max_paths);
+        collect_path_stats(record, "", 0, &mut stats, limits);
     }
     stats
 }
 
+// `depth` is the number of arrays already entered; their items are written as `items[]`.
 fn collect_path_stats(
     value: &Value,
     prefix: &str,
+    depth: usize,
     stats: &mut HashMap<String, PathStats>,
-    max_paths: Option<usize>,
+    limits: &AnalyzeLimits,
 ) {
     match value {
         Value::Object(map) => {
             if map.is_empty() {
-                record_path_value(stats, prefix, value, max_paths);
+                record_path_value(stats, prefix, value, limits.max_paths);
                 return;
             }
             for (key, child) in map {
                 let next = append_path(prefix, key);
-                collect_path_stats(child, &next, stats, max_paths);
+                collect_path_stats(child, &next, depth, stats, limits);
             }
         }
-        Value::Array(_) => {
-            record_path_value(stats, prefix, value, max_paths);
+        Value::Array(items) => {
+            record_path_value(stats, prefix, value, limits.max_paths);
+            collect_array_items(items, prefix, depth, stats, limits);
+        }
+        _ => record_path_value(stats, prefix, value, limits.max_paths),
+    }
+}
+
+fn collect_array_items(
+    items: &[Value],
+    prefix: &str,
+    depth: usize,
+    stats: &mut HashMap<String, PathStats>,
+    limits: &AnalyzeLimits,
+) {
+    if depth >= limits.max_array_depth {
+        return;
+    }
+    let item_path = format!("{}[]", if prefix.is_empty() { "$" } else { prefix });
+    for item in items.iter().take(limits.max_array_samples) {
+        record_path_value(stats, &item_path, item, limits.max_paths);
+        match item {
+            Value::Object(map) => {
+                for (key, child) in map {
+                    let next = append_path(&item_path, key);
+                    collect_path_stats(child, &next, depth + 1, stats, limits);
+                }
+            }
+            Value::Array(inner) => collect_array_items(inner, &item_path, depth + 1, stats, limits),
+            _ => {}
         }
-        _ => record_path_value(stats, prefix, value, max_paths),
     }
 }
 
@@ -2268,7 +3878,7 @@ fn build_input_paths(stats: &HashMap<String, PathStats>) -> Vec<InputPathInfo> {
 }
 
 fn leaf_from_path(path: &str) -> Option<String> {
-    match parse_path_tokens(path) {
+    match parse_path(&path.replace("[]", "")) {
         Ok(tokens) => {
             for token in tokens.iter().rev() {
                 if let PathToken::Key(key) = token {
@@ -2313,8 +3923,11 @@ fn token_similarity(a: &[String], b: &[String]) -> f64 {
     }
 }
 
+// Targets inside an array (`items[].sku`, `tags[]`) only match paths inside one input array,
+// and other targets only match paths outside arrays.
 fn select_candidates(
     target_leaf: &str,
+    array_target: bool,
     source_hint: Option<&str>,
     value_type: Option<&str>,
     input_paths: &[InputPathInfo],
@@ -2329,30 +3942,33 @@ fn select_candidates(
         .unwrap_or_default();
 
     for input in input_paths {
+        let array_markers = input.path.matches("[]").count();
+        if array_markers != usize::from(array_target) || input.path.starts_with('$') {
+            continue;
+        }
         let mut score = 0.0;
         let mut reason = None;
 
-        if let Some(source_hint) = source_hint {
-            if input.path == source_hint {
-                score = 1.0;
-                reason = Some("exact_source");
-            }
+        if let Some(source_hint) = source_hint
+            && input.path == source_hint
+        {
+            score = 1.0;
+            reason = Some("exact_source");
         }
 
-        if reason.is_none() && !target_leaf.is_empty() {
-            if input.leaf.eq_ignore_ascii_case(target_leaf) {
-                score = 0.8;
-                reason = Some("leaf_match");
-            }
+        if reason.is_none() && !target_leaf.is_empty()
+            && input.leaf.eq_ignore_ascii_case(target_leaf)
+        {
+            score = 0.8;
+            reason = Some("leaf_match");
         }
 
-        if reason.is_none() {
-            if let Some(source_leaf) = source_leaf.as_deref() {
-                if input.leaf.eq_ignore_ascii_case(source_leaf) {
-                    score = 0.75;
-                    reason = Some("leaf_match");
-                }
-            }
+        if reason.is_none()
+            && let Some(source_leaf) = source_leaf.as_deref()
+            && input.leaf.eq_ignore_ascii_case(source_leaf)
+        {
+            score = 0.75;
+            reason = Some("leaf_match");
         }
 
         if reason.is_none() {
@@ -2388,10 +4004,27 @@ fn select_candidates(
     candidates
 }
 
+// Candidates scoring within this of the best one are too close to pick between.
+const FALLBACK_SCORE_MARGIN: f64 = 0.05;
+
+// The sources to write for a mapping: the best candidate, or for a target outside arrays, every
+// candidate tied with it in order, so the transform tries them as a `source` fallback list.
+fn tied_candidates(candidates: &[Candidate], array_target: bool) -> Vec<String> {
+    let Some(best) = candidates.first() else { return Vec::new() };
+    if array_target {
+        return vec![best.source.clone()];
+    }
+    candidates
+        .iter()
+        .take_while(|candidate| best.score - candidate.score < FALLBACK_SCORE_MARGIN)
+        .map(|candidate| candidate.source.clone())
+        .collect()
+}
+
 fn type_boost(type_counts: &HashMap<&'static str, usize>, value_type: Option<&str>) -> f64 {
     let Some(value_type) = value_type else { return 0.0 };
     let type_name = match value_type {
-        "string" => "string",
+        "string" | "date" | "datetime" => "string",
         "int" | "float" => "number",
         "bool" => "bool",
         _ => return 0.0,
@@ -2431,6 +4064,7 @@ struct DtoField {
 enum DtoFieldType {
     Primitive(PrimitiveKind),
     Object(String),
+    Array(Box<DtoFieldType>),
     Unknown,
 }
 
@@ -2495,22 +4129,22 @@ fn normalize_typescript_text(text: &str) -> String {
             continue;
         }
 
-        if ch == '/' {
-            if let Some(next) = chars.peek() {
-                if *next == '/' {
-                    out.push(ch);
-                    out.push(*next);
-                    chars.next();
-                    in_line_comment = true;
-                    continue;
-                }
-                if *next == '*' {
-                    out.push(ch);
-                    out.push(*next);
-                    chars.next();
-                    in_block_comment = true;
-                    continue;
-                }
+        if ch == '/'
+            && let Some(next) = chars.peek()
+        {
+            if *next == '/' {
+                out.push(ch);
+                out.push(*next);
+                chars.next();
+                in_line_comment = true;
+                continue;
+            }
+            if *next == '*' {
+                out.push(ch);
+                out.push(*next);
+                chars.next();
+                in_block_comment = true;
+                continue;
             }
         }
 
@@ -2585,24 +4219,24 @@ fn normalize_rust_text(text: &str) -> String {
             continue;
         }
 
-        if ch == '/' {
-            if let Some(next) = chars.peek() {
-                if *next == '/' {
-                    out.push(ch);
-                    out.push(*next);
-                    chars.next();
-                    in_line_comment = true;
-                    last_newline = false;
-                    continue;
-                }
-                if *next == '*' {
-                    out.push(ch);
-                    out.push(*next);
-                    chars.next();
-                    in_block_comment = true;
-                    last_newline = false;
-                    continue;
-                }
+        if ch == '/'
+            && let Some(next) = chars.peek()
+        {
+            if *next == '/' {
+                out.push(ch);
+                out.push(*next);
+                chars.next();
+                in_line_comment = true;
+                last_newline = false;
+                continue;
+            }
+            if *next == '*' {
+                out.push(ch);
+                out.push(*next);
+                chars.next();
+                in_block_comment = true;
+                last_newline = false;
+                continue;
             }
         }
 
@@ -2620,9 +4254,7 @@ fn normalize_rust_text(text: &str) -> String {
                 last_newline = false;
             }
             '>' => {
-                if angle_depth > 0 {
-                    angle_depth -= 1;
-                }
+                angle_depth = angle_depth.saturating_sub(1);
                 out.push(ch);
                 last_newline = false;
             }
@@ -2632,9 +4264,7 @@ fn normalize_rust_text(text: &str) -> String {
                 last_newline = false;
             }
             ')' => {
-                if paren_depth > 0 {
-                    paren_depth -= 1;
-                }
+                paren_depth = paren_depth.saturating_sub(1);
                 out.push(ch);
                 last_newline = false;
             }
@@ -2644,9 +4274,7 @@ fn normalize_rust_text(text: &str) -> String {
                 last_newline = false;
             }
             ']' => {
-                if bracket_depth > 0 {
-                    bracket_depth -= 1;
-                }
+                bracket_depth = bracket_depth.saturating_sub(1);
                 out.push(ch);
                 last_newline = false;
             }
@@ -2768,22 +4396,22 @@ fn normalize_braced_text(text: &str, split_commas_in_parens: bool) -> String {
             continue;
         }
 
-        if ch == '/' {
-            if let Some(next) = chars.peek() {
-                if *next == '/' {
-                    out.push(ch);
-                    out.push(*next);
-                    chars.next();
-                    in_line_comment = true;
-                    continue;
-                }
-                if *next == '*' {
-                    out.push(ch);
-                    out.push(*next);
-                    chars.next();
-                    in_block_comment = true;
-                    continue;
-                }
+        if ch == '/'
+            && let Some(next) = chars.peek()
+        {
+            if *next == '/' {
+                out.push(ch);
+                out.push(*next);
+                chars.next();
+                in_line_comment = true;
+                continue;
+            }
+            if *next == '*' {
+                out.push(ch);
+                out.push(*next);
+                chars.next();
+                in_block_comment = true;
+                continue;
             }
         }
 
@@ -2799,9 +4427,7 @@ fn normalize_braced_text(text: &str, split_commas_in_parens: bool) -> String {
                 out.push(ch);
             }
             '>' => {
-                if angle_depth > 0 {
-                    angle_depth -= 1;
-                }
+                angle_depth = angle_depth.saturating_sub(1);
                 out.push(ch);
             }
             '(' => {
@@ -2809,9 +4435,7 @@ fn normalize_braced_text(text: &str, split_commas_in_parens: bool) -> String {
                 out.push(ch);
             }
             ')' => {
-                if paren_depth > 0 {
-                    paren_depth -= 1;
-                }
+                paren_depth = paren_depth.saturating_sub(1);
                 out.push(ch);
             }
             '[' => {
@@ -2819,9 +4443,7 @@ fn normalize_braced_text(text: &str, split_commas_in_parens: bool) -> String {
                 out.push(ch);
             }
             ']' => {
-                if bracket_depth > 0 {
-                    bracket_depth -= 1;
-                }
+                bracket_depth = bracket_depth.saturating_sub(1);
                 out.push(ch);
             }
             '{' => {
@@ -2866,6 +4488,179 @@ fn normalize_swift_text(text: &str) -> String {
     normalize_braced_text(text, false)
 }
 
+// The item type of a list type: `Vec<T>`, `List<T>`, `list[T]`, `T[]`, `[]T`, `[T]` and the like.
+fn dto_array_item(type_name: &str) -> Option<&str> {
+    let type_name = type_name.trim();
+    if let Some(item) = type_name.strip_suffix("[]") {
+        return Some(item.trim());
+    }
+    if let Some(item) = type_name.strip_prefix("[]") {
+        return Some(item.trim());
+    }
+    if let Some(item) = type_name.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+        return (!item.contains(':')).then_some(item.trim());
+    }
+    let (head, close) = match type_name.find(['<', '[']) {
+        Some(open) if type_name[open..].starts_with('<') => (&type_name[..open], '>'),
+        Some(open) => (&type_name[..open], ']'),
+        None => return None,
+    };
+    let name = head.rsplit(['.', ':']).next().unwrap_or(head).trim();
+    if !matches!(
+        name,
+        "Vec" | "VecDeque" | "List" | "list" | "ArrayList" | "MutableList" | "Array"
+            | "ReadonlyArray" | "Sequence" | "Set" | "set" | "HashSet" | "Collection"
+    ) {
+        return None;
+    }
+    type_name[head.len() + 1..].strip_suffix(close).map(str::trim)
+}
+
+fn typescript_field_type(type_token: &str) -> DtoFieldType {
+    if let Some(item) = dto_array_item(type_token) {
+        return DtoFieldType::Array(Box::new(typescript_field_type(item)));
+    }
+    if type_token.contains('[') {
+        DtoFieldType::Unknown
+    } else {
+        match type_token {
+            "string" => DtoFieldType::Primitive(PrimitiveKind::String),
+            "number" => DtoFieldType::Primitive(PrimitiveKind::Float),
+            "boolean" => DtoFieldType::Primitive(PrimitiveKind::Bool),
+            "unknown" | "any" => DtoFieldType::Unknown,
+            "" => DtoFieldType::Unknown,
+            other => DtoFieldType::Object(other.to_string()),
+        }
+    }
+}
+
+fn python_field_type(type_token: &str) -> DtoFieldType {
+    if let Some(item) = dto_array_item(type_token) {
+        return DtoFieldType::Array(Box::new(python_field_type(item)));
+    }
+    if type_token.contains('[')
+        || type_token.contains("List")
+        || type_token.contains("Dict")
+        || type_token.contains("list")
+        || type_token.contains("dict")
+    {
+        DtoFieldType::Unknown
+    } else {
+        match type_token {
+            "str" | "string" => DtoFieldType::Primitive(PrimitiveKind::String),
+            "int" => DtoFieldType::Primitive(PrimitiveKind::Int),
+            "float" => DtoFieldType::Primitive(PrimitiveKind::Float),
+            "bool" | "boolean" => DtoFieldType::Primitive(PrimitiveKind::Bool),
+            "Any" | "any" => DtoFieldType::Unknown,
+            "" => DtoFieldType::Unknown,
+            other => DtoFieldType::Object(other.to_string()),
+        }
+    }
+}
+
+fn go_field_type(type_token: &str) -> DtoFieldType {
+    if let Some(item) = dto_array_item(type_token) {
+        return DtoFieldType::Array(Box::new(go_field_type(item.trim_start_matches('*'))));
+    }
+    if type_token.contains('[') || type_token.contains("map[") {
+        DtoFieldType::Unknown
+    } else {
+        match type_token {
+            "string" => DtoFieldType::Primitive(PrimitiveKind::String),
+            "bool" => DtoFieldType::Primitive(PrimitiveKind::Bool),
+            "int" | "int8" | "int16" | "int32" | "int64" | "uint" | "uint8" | "uint16"
+            | "uint32" | "uint64" | "uintptr" => DtoFieldType::Primitive(PrimitiveKind::Int),
+            "float32" | "float64" => DtoFieldType::Primitive(PrimitiveKind::Float),
+            "" | "any" | "interface{}" | "json.RawMessage" => DtoFieldType::Unknown,
+            other => DtoFieldType::Object(other.to_string()),
+        }
+    }
+}
+
+fn kotlin_field_type(type_token: &str) -> DtoFieldType {
+    if let Some(item) = dto_array_item(type_token) {
+        return DtoFieldType::Array(Box::new(kotlin_field_type(item.trim_end_matches('?'))));
+    }
+    if type_token.contains('<') {
+        DtoFieldType::Unknown
+    } else {
+        match type_token {
+            "String" => DtoFieldType::Primitive(PrimitiveKind::String),
+            "Boolean" => DtoFieldType::Primitive(PrimitiveKind::Bool),
+            "Int" | "Long" | "Short" | "Byte" => DtoFieldType::Primitive(PrimitiveKind::Int),
+            "Float" | "Double" => DtoFieldType::Primitive(PrimitiveKind::Float),
+            "" | "Any" | "JsonNode" => DtoFieldType::Unknown,
+            other => DtoFieldType::Object(other.to_string()),
+        }
+    }
+}
+
+fn swift_field_type(type_token: &str) -> DtoFieldType {
+    if let Some(item) = dto_array_item(type_token) {
+        return DtoFieldType::Array(Box::new(swift_field_type(item.trim_end_matches('?'))));
+    }
+    if type_token.contains('<') {
+        DtoFieldType::Unknown
+    } else {
+        match type_token {
+            "String" => DtoFieldType::Primitive(PrimitiveKind::String),
+            "Bool" => DtoFieldType::Primitive(PrimitiveKind::Bool),
+            "Int" | "Int8" | "Int16" | "Int32" | "Int64" | "UInt" | "UInt8" | "UInt16"
+            | "UInt32" | "UInt64" => DtoFieldType::Primitive(PrimitiveKind::Int),
+            "Float" | "Double" => DtoFieldType::Primitive(PrimitiveKind::Float),
+            "" | "JSONValue" => DtoFieldType::Unknown,
+            other => DtoFieldType::Object(other.to_string()),
+        }
+    }
+}
+
+fn rust_field_type(type_name: &str) -> DtoFieldType {
+    if let Some(item) = dto_array_item(type_name) {
+        return DtoFieldType::Array(Box::new(rust_field_type(item)));
+    }
+    let type_key = type_name.rsplit("::").next().unwrap_or(type_name);
+    match type_key {
+        "String" => DtoFieldType::Primitive(PrimitiveKind::String),
+        "bool" => DtoFieldType::Primitive(PrimitiveKind::Bool),
+        "i8" | "i16" | "i32" | "i64" | "isize" | "u8" | "u16" | "u32" | "u64" | "usize" => {
+            DtoFieldType::Primitive(PrimitiveKind::Int)
+        }
+        "f32" | "f64" => DtoFieldType::Primitive(PrimitiveKind::Float),
+        _ if type_key.ends_with("Value") => DtoFieldType::Unknown,
+        _ => DtoFieldType::Object(type_key.to_string()),
+    }
+}
+
+fn java_field_type(type_part: &str) -> DtoFieldType {
+    if let Some(inner) = type_part.strip_prefix("Optional<").and_then(|rest| rest.strip_suffix('>')) {
+        return java_field_type(inner);
+    }
+    if let Some(item) = dto_array_item(type_part) {
+        return DtoFieldType::Array(Box::new(java_field_type(item)));
+    }
+    let type_key = type_part
+        .rsplit('.')
+        .next()
+        .unwrap_or(type_part)
+        .trim()
+        .trim_end_matches('>');
+    let type_key = type_key
+        .rsplit('<')
+        .next()
+        .unwrap_or(type_key)
+        .trim();
+    match type_key {
+        "String" => DtoFieldType::Primitive(PrimitiveKind::String),
+        "boolean" | "Boolean" => DtoFieldType::Primitive(PrimitiveKind::Bool),
+        "byte" | "short" | "int" | "long" | "Byte" | "Short" | "Integer" | "Long" => {
+            DtoFieldType::Primitive(PrimitiveKind::Int)
+        }
+        "float" | "double" | "Float" | "Double" => DtoFieldType::Primitive(PrimitiveKind::Float),
+        "" | "Object" | "JsonNode" => DtoFieldType::Unknown,
+        other => DtoFieldType::Object(other.to_string()),
+    }
+}
+
 fn parse_typescript_types(text: &str) -> Result<(HashMap<String, DtoType>, Vec<String>), String> {
     let mut types: HashMap<String, DtoType> = HashMap::new();
     let mut order = Vec::new();
@@ -2932,23 +4727,12 @@ fn parse_typescript_types(text: &str) -> Result<(HashMap<String, DtoType>, Vec<S
         let field_name = name_part.trim_end_matches('?').trim().to_string();
 
         let type_token = type_part
-            .split(|ch| ch == '|' || ch == '&')
+            .split(['|', '&'])
             .next()
             .unwrap_or("")
             .trim()
             .trim_end_matches(';');
-        let field_type = if type_token.contains('[') {
-            DtoFieldType::Unknown
-        } else {
-            match type_token {
-                "string" => DtoFieldType::Primitive(PrimitiveKind::String),
-                "number" => DtoFieldType::Primitive(PrimitiveKind::Float),
-                "boolean" => DtoFieldType::Primitive(PrimitiveKind::Bool),
-                "unknown" | "any" => DtoFieldType::Unknown,
-                "" => DtoFieldType::Unknown,
-                other => DtoFieldType::Object(other.to_string()),
-            }
-        };
+        let field_type = typescript_field_type(type_token);
 
         let json_key = pending_json_key.take().unwrap_or_else(|| field_name.clone());
         if let Some(dto_type) = types.get_mut(&current_name) {
@@ -3040,21 +4824,7 @@ fn parse_rust_types(text: &str) -> Result<(HashMap<String, DtoType>, Vec<String>
             (compact, false)
         };
 
-        let type_key = type_name
-            .rsplit("::")
-            .next()
-            .unwrap_or(&type_name)
-            .to_string();
-        let field_type = match type_key.as_str() {
-            "String" => DtoFieldType::Primitive(PrimitiveKind::String),
-            "bool" => DtoFieldType::Primitive(PrimitiveKind::Bool),
-            "i8" | "i16" | "i32" | "i64" | "isize" | "u8" | "u16" | "u32" | "u64" | "usize" => {
-                DtoFieldType::Primitive(PrimitiveKind::Int)
-            }
-            "f32" | "f64" => DtoFieldType::Primitive(PrimitiveKind::Float),
-            _ if type_key.ends_with("Value") => DtoFieldType::Unknown,
-            _ => DtoFieldType::Object(type_key),
-        };
+        let field_type = rust_field_type(&type_name);
 
         let json_key = pending_json_key.take().unwrap_or_else(|| field_name.to_string());
         if let Some(dto_type) = types.get_mut(&current_name) {
@@ -3072,10 +4842,10 @@ fn parse_rust_types(text: &str) -> Result<(HashMap<String, DtoType>, Vec<String>
 fn parse_first_quoted_value(text: &str) -> Option<String> {
     let mut best: Option<(usize, char)> = None;
     for quote in ['"', '\''] {
-        if let Some(pos) = text.find(quote) {
-            if best.map_or(true, |(best_pos, _)| pos < best_pos) {
-                best = Some((pos, quote));
-            }
+        if let Some(pos) = text.find(quote)
+            && best.is_none_or(|(best_pos, _)| pos < best_pos)
+        {
+            best = Some((pos, quote));
         }
     }
 
@@ -3140,8 +4910,10 @@ fn strip_leading_annotations(
     rest.to_string()
 }
 
+// Pydantic `Field(alias=...)`, or the `metadata={"json_key": ...}` that `generate_dto` writes.
 fn parse_python_alias(line: &str) -> Option<String> {
     parse_named_argument(line, "alias")
+        .or_else(|| parse_quoted_value_after(line, "\"json_key\":"))
 }
 
 fn parse_python_types(text: &str) -> Result<(HashMap<String, DtoType>, Vec<String>), String> {
@@ -3186,11 +4958,11 @@ fn parse_python_types(text: &str) -> Result<(HashMap<String, DtoType>, Vec<Strin
             }
         }
 
-        if let Some(indent_level) = current_indent {
-            if !class_line && indent <= indent_level && !line.is_empty() {
-                current = None;
-                current_indent = None;
-            }
+        if let Some(indent_level) = current_indent
+            && !class_line && indent <= indent_level && !line.is_empty()
+        {
+            current = None;
+            current_indent = None;
         }
 
         let Some(current_name) = current.clone() else { continue };
@@ -3234,7 +5006,8 @@ fn parse_python_types(text: &str) -> Result<(HashMap<String, DtoType>, Vec<Strin
         let mut type_token = rest.trim();
         if let Some(start) = type_token.find("Optional[") {
             let after = &type_token[start + "Optional[".len()..];
-            if let Some(end) = after.find(']') {
+            // The last bracket closes `Optional[`; inner types such as `List[Item]` have their own.
+            if let Some(end) = after.rfind(']') {
                 type_token = after[..end].trim();
             }
         } else if let Some(start) = type_token.find("Union[") {
@@ -3249,35 +5022,17 @@ fn parse_python_types(text: &str) -> Result<(HashMap<String, DtoType>, Vec<Strin
                     type_token = first;
                 }
             }
-        } else if type_token.contains('|') {
-            if let Some(first) = type_token
+        } else if type_token.contains('|')
+            && let Some(first) = type_token
                 .split('|')
                 .map(|item| item.trim())
                 .find(|item| !item.contains("None"))
-            {
-                type_token = first;
-            }
+        {
+            type_token = first;
         }
 
         let type_token = type_token.trim_start_matches("typing.");
-        let field_type = if type_token.contains('[')
-            || type_token.contains("List")
-            || type_token.contains("Dict")
-            || type_token.contains("list")
-            || type_token.contains("dict")
-        {
-            DtoFieldType::Unknown
-        } else {
-            match type_token {
-                "str" | "string" => DtoFieldType::Primitive(PrimitiveKind::String),
-                "int" => DtoFieldType::Primitive(PrimitiveKind::Int),
-                "float" => DtoFieldType::Primitive(PrimitiveKind::Float),
-                "bool" | "boolean" => DtoFieldType::Primitive(PrimitiveKind::Bool),
-                "Any" | "any" => DtoFieldType::Unknown,
-                "" => DtoFieldType::Unknown,
-                other => DtoFieldType::Object(other.to_string()),
-            }
-        };
+        let field_type = python_field_type(type_token);
 
         let json_key = parse_python_alias(line).unwrap_or_else(|| field_name.to_string());
         if let Some(dto_type) = types.get_mut(&current_name) {
@@ -3304,7 +5059,6 @@ fn parse_go_types(text: &str) -> Result<(HashMap<String, DtoType>, Vec<String>),
         index += pos + 5;
         let rest = &text[index..];
         let name = rest
-            .trim_start()
             .split_whitespace()
             .next()
             .unwrap_or("");
@@ -3368,7 +5122,7 @@ fn parse_go_struct_fields(body: &str, dto_type: &mut DtoType) {
         if ch == '/' {
             chars.next();
             if matches!(chars.peek(), Some('/')) {
-                while let Some(next) = chars.next() {
+                for next in chars.by_ref() {
                     if next == '\n' {
                         break;
                     }
@@ -3403,7 +5157,7 @@ fn parse_go_struct_fields(body: &str, dto_type: &mut DtoType) {
         let tag = if matches!(chars.peek(), Some('`')) {
             chars.next();
             let mut tag_value = String::new();
-            while let Some(next) = chars.next() {
+            for next in chars.by_ref() {
                 if next == '`' {
                     break;
                 }
@@ -3426,19 +5180,7 @@ fn parse_go_struct_fields(body: &str, dto_type: &mut DtoType) {
             type_token = stripped.to_string();
         }
 
-        let field_type = if type_token.contains('[') || type_token.contains("map[") {
-            DtoFieldType::Unknown
-        } else {
-            match type_token.as_str() {
-                "string" => DtoFieldType::Primitive(PrimitiveKind::String),
-                "bool" => DtoFieldType::Primitive(PrimitiveKind::Bool),
-                "int" | "int8" | "int16" | "int32" | "int64" | "uint" | "uint8" | "uint16"
-                | "uint32" | "uint64" | "uintptr" => DtoFieldType::Primitive(PrimitiveKind::Int),
-                "float32" | "float64" => DtoFieldType::Primitive(PrimitiveKind::Float),
-                "" => DtoFieldType::Unknown,
-                other => DtoFieldType::Object(other.to_string()),
-            }
-        };
+        let field_type = go_field_type(&type_token);
 
         let json_key = json_key.unwrap_or_else(|| field_name.clone());
         dto_type.fields.push(DtoField {
@@ -3646,8 +5388,8 @@ fn parse_java_field_line(
     loop {
         let mut stripped = None;
         for modifier in modifiers {
-            if rest.starts_with(modifier) {
-                let after = rest[modifier.len()..].trim_start();
+            if let Some(after) = rest.strip_prefix(modifier) {
+                let after = after.trim_start();
                 if after.len() != rest.len() {
                     stripped = Some(after);
                     break;
@@ -3671,27 +5413,7 @@ fn parse_java_field_line(
     let optional = *pending_optional || type_part.replace(' ', "").contains("Optional<");
     *pending_optional = false;
 
-    let type_key = type_part
-        .rsplit('.')
-        .next()
-        .unwrap_or(type_part)
-        .trim()
-        .trim_end_matches('>');
-    let type_key = type_key
-        .rsplit('<')
-        .next()
-        .unwrap_or(type_key)
-        .trim();
-    let field_type = match type_key {
-        "String" => DtoFieldType::Primitive(PrimitiveKind::String),
-        "boolean" | "Boolean" => DtoFieldType::Primitive(PrimitiveKind::Bool),
-        "byte" | "short" | "int" | "long" | "Byte" | "Short" | "Integer" | "Long" => {
-            DtoFieldType::Primitive(PrimitiveKind::Int)
-        }
-        "float" | "double" | "Float" | "Double" => DtoFieldType::Primitive(PrimitiveKind::Float),
-        "" => DtoFieldType::Unknown,
-        other => DtoFieldType::Object(other.to_string()),
-    };
+    let field_type = java_field_type(type_part);
 
     let json_key = pending_json_key.take().unwrap_or_else(|| field_name.to_string());
     if let Some(dto_type) = types.get_mut(current_name) {
@@ -3765,10 +5487,10 @@ fn parse_kotlin_types(text: &str) -> Result<(HashMap<String, DtoType>, Vec<Strin
         let close_parens = line.matches(')').count() as i32;
         let next_depth = param_depth + open_parens - close_parens;
         let mut slice = line;
-        if next_depth <= 0 {
-            if let Some(end) = slice.rfind(')') {
-                slice = slice[..end].trim();
-            }
+        if next_depth <= 0
+            && let Some(end) = slice.rfind(')')
+        {
+            slice = slice[..end].trim();
         }
 
         if param_depth <= 0 && slice.is_empty() {
@@ -3812,18 +5534,7 @@ fn parse_kotlin_types(text: &str) -> Result<(HashMap<String, DtoType>, Vec<Strin
             .unwrap_or(type_part)
             .trim()
             .trim_end_matches('?');
-        let field_type = if type_token.contains('<') {
-            DtoFieldType::Unknown
-        } else {
-            match type_token {
-                "String" => DtoFieldType::Primitive(PrimitiveKind::String),
-                "Boolean" => DtoFieldType::Primitive(PrimitiveKind::Bool),
-                "Int" | "Long" | "Short" | "Byte" => DtoFieldType::Primitive(PrimitiveKind::Int),
-                "Float" | "Double" => DtoFieldType::Primitive(PrimitiveKind::Float),
-                "" => DtoFieldType::Unknown,
-                other => DtoFieldType::Object(other.to_string()),
-            }
-        };
+        let field_type = kotlin_field_type(type_token);
 
         let json_key = pending_json_key.take().unwrap_or_else(|| field_name.to_string());
         if let Some(dto_type) = types.get_mut(&current_name) {
@@ -3871,7 +5582,7 @@ fn parse_swift_types(text: &str) -> Result<(HashMap<String, DtoType>, Vec<String
                 .next()
                 .unwrap_or("");
             let name = name_part
-                .split(|ch: char| ch == ':' || ch == '{')
+                .split([':', '{'])
                 .next()
                 .unwrap_or("")
                 .trim();
@@ -3954,19 +5665,7 @@ fn parse_swift_types(text: &str) -> Result<(HashMap<String, DtoType>, Vec<String
 
         let mut optional = type_part.contains('?');
         let type_token = type_part.trim_end_matches('?');
-        let field_type = if type_token.contains('<') {
-            DtoFieldType::Unknown
-        } else {
-            match type_token {
-                "String" => DtoFieldType::Primitive(PrimitiveKind::String),
-                "Bool" => DtoFieldType::Primitive(PrimitiveKind::Bool),
-                "Int" | "Int8" | "Int16" | "Int32" | "Int64" | "UInt" | "UInt8" | "UInt16"
-                | "UInt32" | "UInt64" => DtoFieldType::Primitive(PrimitiveKind::Int),
-                "Float" | "Double" => DtoFieldType::Primitive(PrimitiveKind::Float),
-                "" => DtoFieldType::Unknown,
-                other => DtoFieldType::Object(other.to_string()),
-            }
-        };
+        let field_type = swift_field_type(type_token);
 
         if type_part.contains("Optional<") {
             optional = true;
@@ -4042,7 +5741,7 @@ fn push_swift_case(cases: &mut Vec<(String, String)>, text: &str) {
     }
     let rename = parts
         .next()
-        .and_then(|value| parse_first_quoted_value(value))
+        .and_then(parse_first_quoted_value)
         .unwrap_or_else(|| name.to_string());
     cases.push((name.to_string(), rename));
 }
@@ -4116,6 +5815,30 @@ fn build_mappings_for_type(
             DtoFieldType::Object(child) => {
                 build_mappings_for_type(schema, child, &target, optional, visiting, out)?;
             }
+            // A target holds one `[]`, so arrays nested in array items map as a whole.
+            DtoFieldType::Array(_) if prefix.contains("[]") => {
+                out.push(GeneratedMapping {
+                    target,
+                    value_type: None,
+                    required: !optional,
+                });
+            }
+            DtoFieldType::Array(item) => match item.as_ref() {
+                DtoFieldType::Primitive(kind) => out.push(GeneratedMapping {
+                    target: format!("{}[]", target),
+                    value_type: primitive_to_value_type(kind),
+                    required: !optional,
+                }),
+                DtoFieldType::Object(child) => {
+                    let target = format!("{}[]", target);
+                    build_mappings_for_type(schema, child, &target, optional, visiting, out)?;
+                }
+                DtoFieldType::Array(_) | DtoFieldType::Unknown => out.push(GeneratedMapping {
+                    target,
+                    value_type: None,
+                    required: !optional,
+                }),
+            },
         }
     }
 
@@ -4169,187 +5892,16 @@ fn is_primitive(value: &Value) -> bool {
 }
 
 fn append_path(prefix: &str, key: &str) -> String {
-    let needs_quote = key
-        .chars()
-        .any(|ch| ch == '.' || ch == '[' || ch == ']' || ch == '"' || ch == '\'' || ch == '\\');
-    let segment = if needs_quote {
-        let escaped = key.replace('\\', "\\\\").replace('"', "\\\"");
-        format!("[\"{}\"]", escaped)
-    } else {
-        key.to_string()
-    };
+    let segment = format_path(&[PathToken::Key(key.to_string())]);
     if prefix.is_empty() {
         segment
-    } else if segment.starts_with('[') {
+    } else if segment.starts_with("[\"") {
         format!("{}{}", prefix, segment)
     } else {
         format!("{}.{}", prefix, segment)
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-enum PathToken {
-    Key(String),
-    Index(usize),
-}
-
-fn parse_path_tokens(path: &str) -> Result<Vec<PathToken>, String> {
-    if path.is_empty() {
-        return Err("path is empty".to_string());
-    }
-
-    let chars: Vec<char> = path.chars().collect();
-    let mut tokens = Vec::new();
-    let mut index = 0;
-
-    while index < chars.len() {
-        if chars[index] == '.' {
-            return Err("path segment is empty".to_string());
-        }
-
-        if chars[index] == '[' {
-            let (token, next) = parse_bracket(&chars, index)?;
-            tokens.push(token);
-            index = next;
-        } else {
-            let start = index;
-            while index < chars.len() && chars[index] != '.' && chars[index] != '[' {
-                index += 1;
-            }
-            if start == index {
-                return Err("path segment is empty".to_string());
-            }
-            let key: String = chars[start..index].iter().collect();
-            if key.is_empty() {
-                return Err("path segment is empty".to_string());
-            }
-            tokens.push(PathToken::Key(key));
-        }
-
-        while index < chars.len() && chars[index] == '[' {
-            let (token, next) = parse_bracket(&chars, index)?;
-            tokens.push(token);
-            index = next;
-        }
-
-        if index < chars.len() {
-            if chars[index] == '.' {
-                index += 1;
-                if index == chars.len() {
-                    return Err("path syntax is invalid".to_string());
-                }
-            } else {
-                return Err("path syntax is invalid".to_string());
-            }
-        }
-    }
-
-    Ok(tokens)
-}
-
-fn parse_bracket(chars: &[char], start: usize) -> Result<(PathToken, usize), String> {
-    if chars.get(start) != Some(&'[') {
-        return Err("path syntax is invalid".to_string());
-    }
-    let index = start + 1;
-    if index >= chars.len() {
-        return Err("path syntax is invalid".to_string());
-    }
-
-    match chars[index] {
-        '"' | '\'' => parse_quoted(chars, index),
-        c if c.is_ascii_digit() => parse_index(chars, index),
-        _ => Err("path syntax is invalid".to_string()),
-    }
-}
-
-fn parse_index(chars: &[char], start: usize) -> Result<(PathToken, usize), String> {
-    let mut index = start;
-    let mut value: usize = 0;
-    let mut has_digit = false;
-
-    while index < chars.len() && chars[index].is_ascii_digit() {
-        has_digit = true;
-        value = value
-            .saturating_mul(10)
-            .saturating_add(chars[index].to_digit(10).unwrap_or(0) as usize);
-        index += 1;
-    }
-
-    if !has_digit {
-        return Err("path syntax is invalid".to_string());
-    }
-    if chars.get(index) != Some(&']') {
-        return Err("path syntax is invalid".to_string());
-    }
-    index += 1;
-    Ok((PathToken::Index(value), index))
-}
-
-fn parse_quoted(chars: &[char], start: usize) -> Result<(PathToken, usize), String> {
-    let quote = chars[start];
-    let mut index = start + 1;
-    let mut value = String::new();
-
-    while index < chars.len() {
-        let ch = chars[index];
-        if ch == '\\' {
-            index += 1;
-            if index >= chars.len() {
-                return Err("path escape is invalid".to_string());
-            }
-            let escaped = chars[index];
-            if escaped == '\\' || escaped == quote {
-                value.push(escaped);
-                index += 1;
-                continue;
-            }
-            return Err("path escape is invalid".to_string());
-        }
-
-        if ch == '[' || ch == ']' {
-            return Err("path syntax is invalid".to_string());
-        }
-
-        if ch == quote {
-            index += 1;
-            break;
-        }
-
-        value.push(ch);
-        index += 1;
-    }
-
-    if value.is_empty() {
-        return Err("path segment is empty".to_string());
-    }
-    if chars.get(index - 1) != Some(&quote) {
-        return Err("path syntax is invalid".to_string());
-    }
-    if chars.get(index) != Some(&']') {
-        return Err("path syntax is invalid".to_string());
-    }
-    index += 1;
-    Ok((PathToken::Key(value), index))
-}
-
-fn get_value_by_tokens<'a>(value: &'a Value, tokens: &[PathToken]) -> Option<&'a Value> {
-    let mut current = value;
-    for token in tokens {
-        match token {
-            PathToken::Key(key) => match current {
-                Value::Object(map) => current = map.get(key)?,
-                _ => return None,
-            },
-            PathToken::Index(index) => match current {
-                Value::Array(items) => current = items.get(*index)?,
-                _ => return None,
-            },
-        }
-    }
-    Some(current)
-}
-
 fn update_yaml_input_spec(
     root: &mut YamlValue,
     format: Option<&str>,
@@ -4380,7 +5932,54 @@ fn update_yaml_input_spec(
     }
 }
 
-fn yaml_mappings_sequence_mut(root: &mut YamlValue) -> Result<&mut Vec<YamlValue>, CallError> {
+// Resolves a flattened mapping index to the YAML sequence holding it, so grouped
+// rules keep their `mapping_groups` layout.
+// The group and index under which the file at rules_path declares mapping `index`, or `None`
+// when a file it extends declares it.
+fn declared_mapping(
+    rule: &RuleFile,
+    index: usize,
+) -> Result<Option<(Option<String>, usize)>, CallError> {
+    let (group, local_index) = match rule.mapping_group(index) {
+        Some((name, local_index)) => (Some(name.to_string()), local_index),
+        None => (None, index),
+    };
+    let Some(origins) = rule.origins.as_ref().filter(|origins| origins.is_composed()) else {
+        return Ok(Some((group, local_index)));
+    };
+    let composed_path = match &group {
+        Some(name) => format!("mapping_groups.{}[{}]", name, local_index),
+        None => format!("mappings[{}]", local_index),
+    };
+    let own_path = match origins.resolve(&composed_path) {
+        Some((file, _)) if file + 1 < origins.files.len() => return Ok(None),
+        resolved => resolved.map(|(_, own_path)| own_path),
+    };
+    let declared = own_path
+        .as_deref()
+        .and_then(|path| path.strip_suffix(']'))
+        .and_then(|path| path.rsplit_once('['))
+        .and_then(|(section, own_index)| {
+            let own_index = own_index.parse().ok()?;
+            match section.strip_prefix("mapping_groups.") {
+                Some(name) => Some((Some(name.to_string()), own_index)),
+                None => (section == "mappings").then_some((None, own_index)),
+            }
+        });
+    let Some(declared) = declared else {
+        let message = format!("cannot find where {} is declared in rules_path", composed_path);
+        return Err(CallError::Tool {
+            message: message.clone(),
+            errors: Some(vec![parse_error_json(&message, None)]),
+        });
+    };
+    Ok(Some(declared))
+}
+
+fn yaml_mappings_sequence_mut<'a>(
+    root: &'a mut YamlValue,
+    group: Option<&str>,
+) -> Result<&'a mut Vec<YamlValue>, CallError> {
     let Some(root_map) = root.as_mapping_mut() else {
         let message = "rules yaml must be a mapping".to_string();
         return Err(CallError::Tool {
@@ -4388,28 +5987,37 @@ fn yaml_mappings_sequence_mut(root: &mut YamlValue) -> Result<&mut Vec<YamlValue
             errors: Some(vec![parse_error_json(&message, None)]),
         });
     };
-    let Some(mappings_value) = root_map.get_mut(&yaml_key("mappings")) else {
-        let message = "rules yaml is missing mappings".to_string();
+    let (mappings_value, label) = match group {
+        Some(name) => (
+            root_map
+                .get_mut(yaml_key("mapping_groups"))
+                .and_then(|groups| groups.get_mut(name)),
+            format!("mapping_groups.{}", name),
+        ),
+        None => (root_map.get_mut(yaml_key("mappings")), "mappings".to_string()),
+    };
+    let Some(mappings_value) = mappings_value else {
+        let message = format!("rules yaml is missing {}", label);
         return Err(CallError::Tool {
             message: message.clone(),
             errors: Some(vec![parse_error_json(&message, None)]),
         });
     };
-    mappings_value
-        .as_sequence_mut()
-        .ok_or_else(|| {
-            let message = "rules yaml mappings must be a sequence".to_string();
-            CallError::Tool {
-                message: message.clone(),
-                errors: Some(vec![parse_error_json(&message, None)]),
-            }
-        })
+    let mappings = mappings_value.as_sequence_mut().ok_or_else(|| {
+        let message = format!("rules yaml {} must be a sequence", label);
+        CallError::Tool {
+            message: message.clone(),
+            errors: Some(vec![parse_error_json(&message, None)]),
+        }
+    })?;
+    Ok(mappings)
 }
 
+// No sources leaves the mapping unmapped; several are written as a `source` fallback list.
 fn update_yaml_mapping(
-    mappings: &mut Vec<YamlValue>,
+    mappings: &mut [YamlValue],
     index: usize,
-    source: Option<&str>,
+    sources: &[String],
 ) -> Result<(), CallError> {
     let Some(mapping_value) = mappings.get_mut(index) else {
         let message = "mapping index out of range".to_string();
@@ -4426,50 +6034,125 @@ fn update_yaml_mapping(
         });
     };
 
-    if let Some(source) = source {
-        mapping_map.insert(yaml_key("source"), YamlValue::String(source.to_string()));
-        mapping_map.remove(&yaml_key("value"));
-        mapping_map.remove(&yaml_key("expr"));
+    if !sources.is_empty() {
+        let (key, value) = match sources {
+            [source] => candidate_yaml(source),
+            _ => (
+                yaml_key("source"),
+                YamlValue::Sequence(
+                    sources.iter().map(|source| fallback_source_yaml(source)).collect(),
+                ),
+            ),
+        };
+        for other in ["source", "value", "expr"] {
+            if key.as_str() != Some(other) {
+                mapping_map.remove(yaml_key(other));
+            }
+        }
+        mapping_map.insert(key, value);
     } else {
-        mapping_map.remove(&yaml_key("source"));
-        mapping_map.remove(&yaml_key("expr"));
+        mapping_map.remove(yaml_key("source"));
+        mapping_map.remove(yaml_key("expr"));
         mapping_map.insert(yaml_key("value"), YamlValue::Null);
         mapping_map.insert(yaml_key("required"), YamlValue::Bool(false));
     }
     Ok(())
 }
 
+// A candidate path as a mapping's `source`, or for a path inside an array (`lines[].sku`),
+// an `expr` mapping the array to that field of each item.
+fn candidate_yaml(path: &str) -> (YamlValue, YamlValue) {
+    let Some((array, item)) = path.split_once("[]") else {
+        return (yaml_key("source"), YamlValue::String(path.to_string()));
+    };
+    let item = item.strip_prefix('.').unwrap_or(item);
+    if item.is_empty() {
+        return (yaml_key("source"), YamlValue::String(array.to_string()));
+    }
+    let namespaced = |namespace: &str, path: &str| {
+        let separator = if path.starts_with('[') { "" } else { "." };
+        let mut reference = YamlMapping::new();
+        reference.insert(
+            yaml_key("ref"),
+            YamlValue::String(format!("{}{}{}", namespace, separator, path)),
+        );
+        YamlValue::Mapping(reference)
+    };
+    let mut expr = YamlMapping::new();
+    expr.insert(yaml_key("op"), YamlValue::String("map".to_string()));
+    expr.insert(
+        yaml_key("args"),
+        YamlValue::Sequence(vec![namespaced("input", array), namespaced("item.value", item)]),
+    );
+    (yaml_key("expr"), YamlValue::Mapping(expr))
+}
+
+// An entry of a fallback list: a top-level key stays bare, a nested path gets `input.` so it is
+// not read as a namespace.
+fn fallback_source_yaml(path: &str) -> YamlValue {
+    if path.contains('.') {
+        YamlValue::String(format!("input.{}", path))
+    } else {
+        YamlValue::String(path.to_string())
+    }
+}
+
 fn yaml_key(key: &str) -> YamlValue {
     YamlValue::String(key.to_string())
 }
 
+// A single `source` is replaced by a candidate, so only the entries of a fallback list count.
 fn collect_missing_refs(
     target: &str,
+    source: Option<&MappingSource>,
     expr: Option<&Expr>,
     when: Option<&Expr>,
     input_paths: &HashSet<String>,
     out: &mut Vec<Value>,
     seen: &mut HashSet<String>,
 ) {
+    let mut refs = Vec::new();
+    if let Some(MappingSource::Fallback(sources)) = source {
+        for source in sources {
+            let Some(path) = source_input_path(source) else { continue };
+            refs.push((source.clone(), path));
+        }
+    }
     for expr in [expr, when] {
         let Some(expr) = expr else { continue };
-        let mut refs = Vec::new();
-        collect_expr_refs(expr, &mut refs);
-        for reference in refs {
+        let mut expr_refs = Vec::new();
+        collect_expr_refs(expr, &mut expr_refs);
+        for reference in expr_refs {
             let Some(path) = input_ref_path(&reference) else { continue };
-            if input_paths.contains(&path) {
-                continue;
-            }
-            let key = format!("{}|{}", target, reference);
-            if seen.insert(key) {
-                out.push(json!({
-                    "target": target,
-                    "ref": reference,
-                    "path": path
-                }));
-            }
+            refs.push((reference, path));
+        }
+    }
+    for (reference, path) in refs {
+        if input_paths.contains(&path) {
+            continue;
         }
+        let key = format!("{}|{}", target, reference);
+        if seen.insert(key) {
+            out.push(json!({
+                "target": target,
+                "ref": reference,
+                "path": path
+            }));
+        }
+    }
+}
+
+// The input path of a `source` entry: `input.<path>`, or a top-level key with no namespace.
+fn source_input_path(source: &str) -> Option<String> {
+    if let Some(path) = input_ref_path(source) {
+        return Some(path);
     }
+    let source = source.trim();
+    let key_end = source.find(['.', '[']).unwrap_or(source.len());
+    let key = &source[..key_end];
+    let namespaced = source[key_end..].starts_with('.')
+        || matches!(key, "input" | "context" | "out" | "const" | "env");
+    (!key.is_empty() && !namespaced).then(|| source.to_string())
 }
 
 fn collect_expr_refs(expr: &Expr, out: &mut Vec<String>) {
@@ -4508,6 +6191,7 @@ fn apply_format_override(rule: &mut RuleFile, format: Option<&str>) -> Result<()
     rule.input.format = match normalized.as_str() {
         "csv" => InputFormat::Csv,
         "json" => InputFormat::Json,
+        "ndjson" => InputFormat::Ndjson,
         _ => return Err(format!("unknown format: {}", format)),
     };
     Ok(())
@@ -4515,33 +6199,146 @@ fn apply_format_override(rule: &mut RuleFile, format: Option<&str>) -> Result<()
 
 fn write_output(path: &str, output: &str) -> Result<(), String> {
     let path = std::path::Path::new(path);
-    if let Some(parent) = path.parent() {
-        if !parent.as_os_str().is_empty() {
-            fs::create_dir_all(parent)
-                .map_err(|err| format!("failed to create output directory: {}", err))?;
-        }
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        fs::create_dir_all(parent)
+            .map_err(|err| format!("failed to create output directory: {}", err))?;
     }
     fs::write(path, output.as_bytes()).map_err(|err| format!("failed to write output: {}", err))
 }
 
-fn transform_to_ndjson(
+struct TraceCollector {
+    options: TraceOptions,
+    events: Vec<Value>,
+}
+
+enum ToolInput {
+    Text(String),
+    Json(Value),
+}
+
+fn open_transform_stream<'a>(
+    rule: &'a RuleFile,
+    input: &'a ToolInput,
+    context: Option<&'a serde_json::Value>,
+    trace: Option<&TraceCollector>,
+    allow_env: bool,
+) -> Result<TransformStream<'a>, CallError> {
+    let limits = current_limits();
+    let stream = match input {
+        ToolInput::Text(text) => transform_stream_with_limits(rule, text, context, &limits),
+        // `input_json` is already parsed, so its records go straight to the mappings.
+        ToolInput::Json(value) => select_json_records(rule, value)
+            .map(|records| transform_values_stream(rule, records, context).with_limits(limits)),
+    }
+    .map_err(|err| CallError::Tool {
+        message: transform_error_to_text(&err),
+        errors: Some(vec![transform_error_json(&err)]),
+    })?
+    .with_env(allow_env);
+    Ok(match trace {
+        Some(trace) => stream.with_trace(trace.options.clone()),
+        None => stream,
+    })
+}
+
+fn transform_to_json(
+    rule: &RuleFile,
+    input: &ToolInput,
+    context: Option<&serde_json::Value>,
+    mut trace: Option<&mut TraceCollector>,
+    control: &CallControl,
+    allow_env: bool,
+) -> Result<(Option<Value>, String, WarningSummary), CallError> {
+    let mut stream = open_transform_stream(rule, input, context, trace.as_deref(), allow_env)?;
+    let mut output_records = Vec::new();
+    let mut warnings = WarningSummary::default();
+    loop {
+        control.check()?;
+        let Some(item) = stream.next() else {
+            break;
+        };
+        if let Some(trace) = trace.as_deref_mut() {
+            trace
+                .events
+                .extend(stream.take_trace().iter().map(|event| event.to_json()));
+        }
+        let item = item.map_err(|err| CallError::Tool {
+            message: transform_error_to_text(&err),
+            errors: Some(vec![transform_error_json(&err)]),
+        })?;
+        warnings.extend(item.warnings);
+        if rule.on_error == OnError::Collect {
+            warnings.extend(item.errors.into_iter().map(TransformWarning::from));
+        }
+        if let Some(output) = item.output {
+            output_records.push(output);
+        }
+    }
+    let output = Value::Array(output_records);
+    let output_text = serde_json::to_string(&output).map_err(|err| {
+        let message = format!("failed to serialize output JSON: {}", err);
+        CallError::Tool {
+            message: message.clone(),
+            errors: Some(vec![parse_error_json(&message, None)]),
+        }
+    })?;
+    Ok((Some(output), output_text, warnings))
+}
+
+fn transform_to_csv(
     rule: &RuleFile,
-    input: &str,
+    input: &ToolInput,
     context: Option<&serde_json::Value>,
-) -> Result<(String, Vec<TransformWarning>), CallError> {
-    let stream = transform_stream(rule, input, context).map_err(|err| CallError::Tool {
+    trace: Option<&mut TraceCollector>,
+    control: &CallControl,
+    allow_env: bool,
+) -> Result<(Option<Value>, String, WarningSummary), CallError> {
+    let (output, _, warnings) = transform_to_json(rule, input, context, trace, control, allow_env)?;
+    let records = output
+        .as_ref()
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let columns = rule.output.as_ref().and_then(|spec| spec.columns.as_deref());
+    let output_text = records_to_csv(records, columns).map_err(|err| CallError::Tool {
         message: transform_error_to_text(&err),
         errors: Some(vec![transform_error_json(&err)]),
     })?;
+    Ok((output, output_text, warnings))
+}
+
+fn transform_to_ndjson(
+    rule: &RuleFile,
+    input: &ToolInput,
+    context: Option<&serde_json::Value>,
+    mut trace: Option<&mut TraceCollector>,
+    control: &CallControl,
+    allow_env: bool,
+) -> Result<(String, WarningSummary), CallError> {
+    let mut stream = open_transform_stream(rule, input, context, trace.as_deref(), allow_env)?;
     let mut output = String::new();
-    let mut warnings = Vec::new();
+    let mut warnings = WarningSummary::default();
 
-    for item in stream {
+    loop {
+        control.check()?;
+        let Some(item) = stream.next() else {
+            break;
+        };
+        if let Some(trace) = trace.as_deref_mut() {
+            trace
+                .events
+                .extend(stream.take_trace().iter().map(|event| event.to_json()));
+        }
         let item = item.map_err(|err| CallError::Tool {
             message: transform_error_to_text(&err),
             errors: Some(vec![transform_error_json(&err)]),
         })?;
         warnings.extend(item.warnings);
+        if rule.on_error == OnError::Collect {
+            warnings.extend(item.errors.into_iter().map(TransformWarning::from));
+        }
         let output_value = match item.output {
             Some(output_value) => output_value,
             None => continue,
@@ -4560,113 +6357,6 @@ fn transform_to_ndjson(
     Ok((output, warnings))
 }
 
-struct RuleWarning {
-    code: &'static str,
-    message: String,
-    path: Option<String>,
-}
-
-fn collect_rule_warnings(rule: &RuleFile) -> Vec<RuleWarning> {
-    let mut warnings = Vec::new();
-    if let Some(expr) = &rule.record_when {
-        collect_expr_warnings(expr, "record_when", &mut warnings);
-    }
-    for (index, mapping) in rule.mappings.iter().enumerate() {
-        let base_path = format!("mappings[{}]", index);
-        if let Some(expr) = &mapping.expr {
-            collect_expr_warnings(expr, &format!("{}.expr", base_path), &mut warnings);
-        }
-        if let Some(expr) = &mapping.when {
-            collect_expr_warnings(expr, &format!("{}.when", base_path), &mut warnings);
-        }
-    }
-    warnings
-}
-
-fn collect_expr_warnings(expr: &Expr, path: &str, warnings: &mut Vec<RuleWarning>) {
-    match expr {
-        Expr::Ref(_) | Expr::Literal(_) => {}
-        Expr::Op(expr_op) => collect_op_warnings(expr_op, path, false, warnings),
-        Expr::Chain(chain) => collect_chain_warnings(chain, path, warnings),
-    }
-}
-
-fn collect_chain_warnings(chain: &ExprChain, path: &str, warnings: &mut Vec<RuleWarning>) {
-    for (index, step) in chain.chain.iter().enumerate() {
-        let step_path = format!("{}.chain[{}]", path, index);
-        if index == 0 {
-            collect_expr_warnings(step, &step_path, warnings);
-            continue;
-        }
-
-        match step {
-            Expr::Op(expr_op) => collect_op_warnings(expr_op, &step_path, true, warnings),
-            _ => collect_expr_warnings(step, &step_path, warnings),
-        }
-    }
-}
-
-fn collect_op_warnings(
-    expr_op: &ExprOp,
-    path: &str,
-    chain_step: bool,
-    warnings: &mut Vec<RuleWarning>,
-) {
-    if expr_op.op == "date_format" {
-        warn_date_format_missing_input_format(expr_op, path, chain_step, warnings);
-    } else if expr_op.op == "to_unixtime" {
-        warnings.push(RuleWarning {
-            code: "to_unixtime_auto_parse",
-            message: "to_unixtime relies on heuristic date parsing; consider normalizing with date_format + input_format.".to_string(),
-            path: Some(path.to_string()),
-        });
-    }
-
-    for (index, arg) in expr_op.args.iter().enumerate() {
-        let arg_path = format!("{}.args[{}]", path, index);
-        collect_expr_warnings(arg, &arg_path, warnings);
-    }
-}
-
-fn warn_date_format_missing_input_format(
-    expr_op: &ExprOp,
-    path: &str,
-    chain_step: bool,
-    warnings: &mut Vec<RuleWarning>,
-) {
-    let input_index = if chain_step { 1 } else { 2 };
-    if expr_op.args.len() <= input_index {
-        warnings.push(RuleWarning {
-            code: "date_format_missing_input_format",
-            message: "date_format without input_format relies on heuristic parsing; consider providing input_format.".to_string(),
-            path: Some(format!("{}.args", path)),
-        });
-        return;
-    }
-
-    if expr_looks_like_timezone(&expr_op.args[input_index]) {
-        warnings.push(RuleWarning {
-            code: "date_format_missing_input_format",
-            message: "date_format without input_format relies on heuristic parsing; consider providing input_format.".to_string(),
-            path: Some(format!("{}.args[{}]", path, input_index)),
-        });
-    }
-}
-
-fn expr_looks_like_timezone(expr: &Expr) -> bool {
-    match expr {
-        Expr::Literal(Value::String(value)) => looks_like_timezone(value),
-        _ => false,
-    }
-}
-
-fn looks_like_timezone(value: &str) -> bool {
-    if value.eq_ignore_ascii_case("utc") || value == "Z" {
-        return true;
-    }
-    matches!(value.chars().next(), Some('+') | Some('-'))
-}
-
 fn validation_errors_to_text(errors: &[RuleError]) -> String {
     let values = validation_errors_to_values(errors);
     serde_json::to_string(&values).unwrap_or_else(|_| "validation error".to_string())
@@ -4686,6 +6376,12 @@ fn validation_error_json(err: &RuleError) -> Value {
     if let Some(path) = &err.path {
         value["path"] = json!(path);
     }
+    if let Some(group_path) = &err.group_path {
+        value["group_path"] = json!(group_path);
+    }
+    if let Some(file) = &err.file {
+        value["file"] = json!(file);
+    }
     if let Some(location) = &err.location {
         value["line"] = json!(location.line);
         value["column"] = json!(location.column);
@@ -4702,12 +6398,22 @@ fn rule_warnings_to_json(warnings: &[RuleWarning]) -> Value {
 fn rule_warning_json(warning: &RuleWarning) -> Value {
     let mut value = json!({
         "type": "warning",
-        "code": warning.code,
+        "code": warning.code.as_str(),
         "message": warning.message,
     });
     if let Some(path) = &warning.path {
         value["path"] = json!(path);
     }
+    if let Some(group_path) = &warning.group_path {
+        value["group_path"] = json!(group_path);
+    }
+    if let Some(file) = &warning.file {
+        value["file"] = json!(file);
+    }
+    if let Some(location) = &warning.location {
+        value["line"] = json!(location.line);
+        value["column"] = json!(location.column);
+    }
     value
 }
 
@@ -4761,35 +6467,92 @@ fn transform_error_to_text(err: &TransformError) -> String {
     serde_json::to_string(&vec![value]).unwrap_or_else(|_| err.message.clone())
 }
 
+// Names the limit a message from `ParseLimits` reports, with its configured value.
+fn limit_error_json(message: &str) -> Value {
+    let mut value = json!({
+        "type": "limit",
+        "message": message,
+    });
+    if let Some(name) = ParseLimits::exceeded(message) {
+        value["limit"] = json!(name);
+        let mut limits = current_limits();
+        if let Some(max) = limit_slot(&mut limits, name).and_then(|slot| *slot) {
+            value["max"] = json!(max);
+        }
+    }
+    value
+}
+
 fn transform_error_json(err: &TransformError) -> Value {
+    if err.kind == TransformErrorKind::InvalidInput && ParseLimits::exceeded(&err.message).is_some()
+    {
+        let mut value = limit_error_json(&err.message);
+        value["code"] = json!(err.code.as_str());
+        insert_record_fields(&mut value, err.record_index, err.line);
+        return value;
+    }
     let mut value = json!({
         "type": "transform",
+        "code": err.code.as_str(),
         "kind": transform_kind_to_str(&err.kind),
         "message": err.message,
     });
     if let Some(path) = &err.path {
         value["path"] = json!(path);
     }
+    if let Some(group_path) = &err.group_path {
+        value["group_path"] = json!(group_path);
+    }
+    insert_record_fields(&mut value, err.record_index, err.line);
     value
 }
 
-fn warnings_to_json(warnings: &[TransformWarning]) -> Value {
-    let values: Vec<_> = warnings.iter().map(transform_warning_json).collect();
+// Identical warnings arrive as one entry with `count` and the first/last record index.
+fn warnings_to_json(warnings: &WarningSummary) -> Value {
+    let values: Vec<_> = warnings
+        .groups
+        .iter()
+        .map(|group| {
+            let mut value = transform_warning_json(&group.warning);
+            value["count"] = json!(group.count);
+            if let Some(first) = group.first_record_index {
+                value["first_record_index"] = json!(first);
+            }
+            if let Some(last) = group.last_record_index {
+                value["last_record_index"] = json!(last);
+            }
+            value
+        })
+        .collect();
     Value::Array(values)
 }
 
 fn transform_warning_json(warning: &TransformWarning) -> Value {
     let mut value = json!({
         "type": "warning",
+        "code": warning.code.as_str(),
         "kind": transform_kind_to_str(&warning.kind),
         "message": warning.message,
     });
     if let Some(path) = &warning.path {
         value["path"] = json!(path);
     }
+    if let Some(group_path) = &warning.group_path {
+        value["group_path"] = json!(group_path);
+    }
+    insert_record_fields(&mut value, warning.record_index, warning.line);
     value
 }
 
+fn insert_record_fields(value: &mut Value, record_index: Option<usize>, line: Option<usize>) {
+    if let Some(record_index) = record_index {
+        value["record_index"] = json!(record_index);
+    }
+    if let Some(line) = line {
+        value["line"] = json!(line);
+    }
+}
+
 fn transform_kind_to_str(kind: &TransformErrorKind) -> &'static str {
     match kind {
         TransformErrorKind::InvalidInput => "InvalidInput",
@@ -4799,5 +6562,6 @@ fn transform_kind_to_str(kind: &TransformErrorKind) -> &'static str {
         TransformErrorKind::MissingRequired => "MissingRequired",
         TransformErrorKind::TypeCastFailed => "TypeCastFailed",
         TransformErrorKind::ExprError => "ExprError",
+        TransformErrorKind::Degraded => "Degraded",
     }
 }
@@ -1,10 +1,13 @@
 use std::fs;
-use std::io::{BufRead, BufReader, Write};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
 use std::process::{Child, ChildStdin, Command, Stdio};
 
 use serde_json::{json, Value};
 use tempfile::tempdir;
-use transform_rules::parse_rule_file;
+use transform_rules::{
+    generate_dto, load_rule_file, parse_rule_file, DtoLanguage, MappingSource, RuleFile,
+};
 
 struct McpServer {
     child: Child,
@@ -14,8 +17,13 @@ struct McpServer {
 
 impl McpServer {
     fn start() -> Self {
+        Self::start_with_env(&[])
+    }
+
+    fn start_with_env(vars: &[(&str, &str)]) -> Self {
         let bin = env!("CARGO_BIN_EXE_transform-rules-mcp");
         let mut child = Command::new(bin)
+            .envs(vars.iter().copied())
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::inherit())
@@ -33,11 +41,19 @@ impl McpServer {
     }
 
     fn send(&mut self, message: &Value) -> Value {
+        self.notify(message);
+        self.receive()
+    }
+
+    // Writes a message without waiting for a response.
+    fn notify(&mut self, message: &Value) {
         let text = serde_json::to_string(message).expect("serialize request");
         let stdin = self.stdin.as_mut().expect("stdin available");
         writeln!(stdin, "{}", text).expect("write request");
         stdin.flush().expect("flush request");
+    }
 
+    fn receive(&mut self) -> Value {
         let mut line = String::new();
         self.stdout
             .read_line(&mut line)
@@ -46,6 +62,28 @@ impl McpServer {
         serde_json::from_str(&line).expect("parse response")
     }
 
+    fn send_content_length(&mut self, body: &[u8]) -> Value {
+        let stdin = self.stdin.as_mut().expect("stdin available");
+        write!(stdin, "Content-Length: {}\r\n\r\n", body.len()).expect("write header");
+        stdin.write_all(body).expect("write body");
+        stdin.flush().expect("flush request");
+
+        let mut length = None;
+        loop {
+            let mut line = String::new();
+            self.stdout.read_line(&mut line).expect("read header");
+            if line == "\r\n" {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("Content-Length:") {
+                length = Some(value.trim().parse::<usize>().expect("parse length"));
+            }
+        }
+        let mut buffer = vec![0u8; length.expect("Content-Length header")];
+        self.stdout.read_exact(&mut buffer).expect("read body");
+        serde_json::from_slice(&buffer).expect("parse response")
+    }
+
     fn shutdown(mut self) {
         self.stdin.take();
         let _ = self.child.wait();
@@ -70,6 +108,10 @@ fn initialize(server: &mut McpServer) {
     assert_eq!(response["result"]["protocolVersion"], "2024-11-05");
 }
 
+fn single_source(rule: &RuleFile, index: usize) -> Option<&str> {
+    rule.mappings[index].source.as_ref().and_then(MappingSource::as_single)
+}
+
 #[test]
 fn initialize_and_list_tools() {
     let mut server = McpServer::start();
@@ -87,12 +129,20 @@ fn initialize_and_list_tools() {
         .expect("tools array");
     let expected = [
         "transform",
+        "fetch_result_page",
+        "preflight",
+        "transform_check",
+        "trace_record",
+        "run_example",
         "validate_rules",
         "generate_dto",
         "list_ops",
+        "list_error_codes",
         "analyze_input",
         "generate_rules_from_base",
         "generate_rules_from_dto",
+        "self_check",
+        "cache_stats",
     ];
     for name in expected {
         assert!(tools.iter().any(|tool| tool["name"] == name));
@@ -309,6 +359,378 @@ mappings:
     server.shutdown();
 }
 
+#[test]
+fn transform_output_format_csv() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let rules_text = r#"version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  - target: "name"
+    source: "name"
+  - target: "tags"
+    source: "tags"
+"#;
+
+    let response = server.send(&json!({
+        "jsonrpc": "2.0",
+        "id": 7,
+        "method": "tools/call",
+        "params": {
+            "name": "transform",
+            "arguments": {
+                "rules_text": rules_text,
+                "input_json": [
+                    { "name": "Alice \"A\"", "tags": ["x"] },
+                    { "name": "Bob" }
+                ],
+                "output_format": "csv"
+            }
+        }
+    }));
+    let output_text = response["result"]["content"][0]["text"]
+        .as_str()
+        .expect("output text");
+    assert_eq!(output_text, "name,tags\n\"Alice \"\"A\"\"\",\"[\"\"x\"\"]\"\nBob,\n");
+
+    let response = server.send(&json!({
+        "jsonrpc": "2.0",
+        "id": 8,
+        "method": "tools/call",
+        "params": {
+            "name": "transform",
+            "arguments": {
+                "rules_text": rules_text,
+                "input_json": [],
+                "output_format": "csv",
+                "ndjson": true
+            }
+        }
+    }));
+    assert!(response["error"]["message"]
+        .as_str()
+        .is_some_and(|message| message.contains("ndjson")));
+
+    server.shutdown();
+}
+
+#[test]
+fn preflight_summarizes_records_and_warnings() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let rules_text = r#"version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  - target: "id"
+    source: "id"
+    type: "int"
+  - target: "name"
+    source: "name"
+    when:
+      op: "=="
+      args: [ { op: "lowercase", args: [ { ref: "input.flag" } ] }, "yes" ]
+"#;
+    let input = json!([
+        { "id": "1", "name": "a", "flag": "yes" },
+        { "id": "2", "name": "b", "flag": 1 },
+        { "id": "3", "name": "c", "flag": 2 },
+        { "id": "4", "name": "d", "flag": "no" },
+        { "id": "5", "name": "e", "flag": 3 }
+    ]);
+    let preflight = |server: &mut McpServer, id: u64, arguments: Value| {
+        server.send(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": "preflight", "arguments": arguments }
+        }))
+    };
+
+    let response = preflight(
+        &mut server,
+        40,
+        json!({ "rules_text": rules_text, "input_json": input }),
+    );
+    let result = &response["result"];
+    assert!(result["isError"].is_null(), "{}", result);
+    assert_eq!(result["content"][0]["text"], "ok: 5 records, 3 warnings");
+    assert_eq!(result["meta"]["records"], 5);
+    assert_eq!(result["meta"]["complete"], true);
+    let warnings = result["meta"]["warnings"].as_array().expect("warnings");
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0]["path"], "mappings[1].when.args[0].args[0]");
+    assert_eq!(warnings[0]["count"], 3);
+    assert_eq!(warnings[0]["record_index"], 1);
+    assert_eq!(warnings[0]["first_record_index"], 1);
+    assert_eq!(warnings[0]["last_record_index"], 4);
+
+    let response = preflight(
+        &mut server,
+        41,
+        json!({ "rules_text": rules_text, "input_json": input, "max_records": 2 }),
+    );
+    assert_eq!(response["result"]["meta"]["records"], 2);
+    assert_eq!(response["result"]["meta"]["complete"], false);
+    assert_eq!(response["result"]["meta"]["warnings"][0]["count"], 1);
+
+    let mut failing = input.clone();
+    failing[3]["id"] = json!("x");
+    let response = preflight(
+        &mut server,
+        42,
+        json!({ "rules_text": rules_text, "input_json": failing }),
+    );
+    let result = &response["result"];
+    assert_eq!(result["isError"], true);
+    assert_eq!(result["meta"]["records"], 3);
+    assert_eq!(result["meta"]["errors"][0]["kind"], "TypeCastFailed");
+    assert_eq!(result["meta"]["errors"][0]["record_index"], 3);
+
+    server.shutdown();
+}
+
+#[test]
+fn transform_reads_named_context_files() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let temp_dir = tempdir().unwrap();
+    let countries = temp_dir.path().join("countries.json");
+    let stores = temp_dir.path().join("stores.csv");
+    fs::write(&countries, r#"[{ "code": "JP", "name": "Japan" }]"#).unwrap();
+    fs::write(&stores, "id,city\n7,Osaka\n8,Kyoto\n").unwrap();
+    let rules_text = r#"version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  - target: "country"
+    expr:
+      op: "lookup_first"
+      args: [ { ref: "context.countries" }, "code", { ref: "input.country" }, "name" ]
+  - target: "store"
+    expr:
+      op: "lookup_first"
+      args: [ { ref: "context.stores" }, "id", { ref: "input.store" }, "city" ]
+"#;
+    let call = |server: &mut McpServer, id: u64, stores: &Path| {
+        server.send(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": {
+                "name": "transform",
+                "arguments": {
+                    "rules_text": rules_text,
+                    "input_json": [{ "country": "JP", "store": 7 }],
+                    "context_files": {
+                        "countries": countries.to_str().unwrap(),
+                        "stores": stores.to_str().unwrap()
+                    }
+                }
+            }
+        }))
+    };
+
+    let response = call(&mut server, 44, &stores);
+    let output_text = response["result"]["content"][0]["text"]
+        .as_str()
+        .expect("output text");
+    let output: Value = serde_json::from_str(output_text).expect("output json");
+    assert_eq!(output, json!([{ "country": "Japan", "store": "Osaka" }]));
+
+    let missing = temp_dir.path().join("missing.csv");
+    let response = call(&mut server, 45, &missing);
+    assert_eq!(response["result"]["isError"], json!(true));
+    let error = &response["result"]["meta"]["errors"][0];
+    assert_eq!(error["type"], "io");
+    assert_eq!(error["path"], missing.to_str().unwrap());
+
+    server.shutdown();
+}
+
+#[test]
+fn cache_stats_reports_and_clears_rule_cache() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let call = |server: &mut McpServer, id: u64, name: &str, arguments: Value| {
+        server.send(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": name, "arguments": arguments }
+        }))
+    };
+    let rules_text = "version: 1\ninput:\n  format: json\n  json: {}\nmappings:\n  - target: \"id\"\n    source: \"id\"\n";
+    call(&mut server, 50, "cache_stats", json!({ "clear": true }));
+    for id in 51..53 {
+        let response = call(
+            &mut server,
+            id,
+            "transform",
+            json!({ "rules_text": rules_text, "input_json": [{ "id": 1 }] }),
+        );
+        assert!(response["result"]["isError"].is_null(), "{}", response);
+    }
+
+    let response = call(&mut server, 53, "cache_stats", json!({ "clear": true }));
+    let meta = &response["result"]["meta"];
+    assert_eq!(meta["hits"], 1);
+    assert_eq!(meta["misses"], 1);
+    assert_eq!(meta["entries"], 1);
+    assert_eq!(meta["cleared"], true);
+
+    let response = call(&mut server, 54, "cache_stats", json!({}));
+    assert_eq!(response["result"]["meta"]["entries"], 0);
+    assert_eq!(response["result"]["meta"]["hits"], 0);
+
+    server.shutdown();
+}
+
+#[test]
+fn transform_warnings_carry_counts() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let rules_text = r#"version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  - target: "name"
+    source: "name"
+    when:
+      op: "=="
+      args: [ { op: "lowercase", args: [ { ref: "input.flag" } ] }, "yes" ]
+"#;
+    let response = server.send(&json!({
+        "jsonrpc": "2.0",
+        "id": 43,
+        "method": "tools/call",
+        "params": {
+            "name": "transform",
+            "arguments": {
+                "rules_text": rules_text,
+                "input_json": [
+                    { "name": "a", "flag": 1 },
+                    { "name": "b", "flag": "yes" },
+                    { "name": "c", "flag": 2 }
+                ]
+            }
+        }
+    }));
+    let warnings = response["result"]["meta"]["warnings"].as_array().expect("warnings");
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0]["count"], 2);
+    assert_eq!(warnings[0]["record_index"], 0);
+    assert_eq!(warnings[0]["first_record_index"], 0);
+    assert_eq!(warnings[0]["last_record_index"], 2);
+
+    server.shutdown();
+}
+
+#[test]
+fn transform_input_json_matches_input_text() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let rules_text = "version: 1\ninput:\n  format: json\n  json:\n    records_path: \"data\"\nmappings:\n  - target: \"id\"\n    source: \"id\"\n    type: \"string\"\n  - target: \"upper\"\n    expr:\n      op: \"uppercase\"\n      args: [ { ref: \"input.name\" } ]\n";
+    let input = json!({ "data": [{ "id": 1, "name": "a" }, { "id": 2, "name": "b" }] });
+    let mut responses = Vec::new();
+    for (id, input_arg) in [
+        (9, json!({ "input_text": input.to_string() })),
+        (10, json!({ "input_json": input })),
+    ] {
+        let mut arguments = json!({
+            "rules_text": rules_text,
+            "return_output_json": true,
+            "trace_targets": ["upper"]
+        });
+        arguments
+            .as_object_mut()
+            .unwrap()
+            .extend(input_arg.as_object().unwrap().clone());
+        responses.push(server.send(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": "transform", "arguments": arguments }
+        })));
+    }
+    assert_eq!(
+        responses[1]["result"]["meta"]["output"],
+        json!([{ "id": "1", "upper": "A" }, { "id": "2", "upper": "B" }])
+    );
+    assert_eq!(responses[1]["result"], responses[0]["result"]);
+
+    let response = server.send(&json!({
+        "jsonrpc": "2.0",
+        "id": 11,
+        "method": "tools/call",
+        "params": {
+            "name": "transform",
+            "arguments": { "rules_text": rules_text, "input_json": { "rows": [] } }
+        }
+    }));
+    assert_eq!(response["result"]["isError"], true);
+    assert_eq!(response["result"]["meta"]["errors"][0]["kind"], "InvalidRecordsPath");
+    assert_eq!(response["result"]["meta"]["errors"][0]["code"], "T1051");
+
+    server.shutdown();
+}
+
+#[test]
+fn transform_ndjson_format_override() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 7,
+        "method": "tools/call",
+        "params": {
+            "name": "transform",
+            "arguments": {
+                "rules_text": "version: 1\ninput:\n  format: json\n  json: {}\nmappings:\n  - target: \"id\"\n    source: \"id\"\n",
+                "input_text": "{\"id\": 1}\n\n{\"id\": 2}\n",
+                "format": "ndjson"
+            }
+        }
+    });
+
+    let response = server.send(&request);
+    let output_text = response["result"]["content"][0]["text"]
+        .as_str()
+        .expect("output text");
+    let output: Value = serde_json::from_str(output_text).expect("output json");
+    assert_eq!(output, json!([{ "id": 1 }, { "id": 2 }]));
+
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 8,
+        "method": "tools/call",
+        "params": {
+            "name": "transform",
+            "arguments": {
+                "rules_text": "version: 1\ninput:\n  format: json\n  json: {}\nmappings:\n  - target: \"id\"\n    source: \"id\"\n",
+                "input_json": [{ "id": 1 }],
+                "format": "ndjson"
+            }
+        }
+    });
+    let response = server.send(&request);
+    assert_eq!(response["error"]["code"], -32602);
+
+    server.shutdown();
+}
+
 #[test]
 fn validate_rules_success() {
     let mut server = McpServer::start();
@@ -336,46 +758,1078 @@ mappings:
     });
 
     let response = server.send(&request);
-    let text = response["result"]["content"][0]["text"]
+    let text = response["result"]["content"][0]["text"]
+        .as_str()
+        .expect("result text");
+    assert_eq!(text, "ok");
+
+    server.shutdown();
+}
+
+#[test]
+fn validate_rules_failure() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let rules_text = r#"version: 1
+input:
+  format: csv
+mappings: []
+"#;
+
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 9,
+        "method": "tools/call",
+        "params": {
+            "name": "validate_rules",
+            "arguments": {
+                "rules_text": rules_text
+            }
+        }
+    });
+
+    let response = server.send(&request);
+    assert_eq!(response["result"]["isError"], true);
+    assert!(response["result"]["meta"]["errors"].is_array());
+
+    server.shutdown();
+}
+
+#[test]
+fn validate_rules_resolves_extends_from_rules_path() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let extends_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("..")
+        .join("transform_rules")
+        .join("tests")
+        .join("fixtures")
+        .join("extends");
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 9,
+        "method": "tools/call",
+        "params": {
+            "name": "validate_rules",
+            "arguments": {
+                "rules_path": extends_dir.join("invalid_tenant.yaml").to_string_lossy()
+            }
+        }
+    });
+
+    let response = server.send(&request);
+    assert_eq!(response["result"]["isError"], true);
+    let errors = response["result"]["meta"]["errors"].as_array().expect("errors");
+    let unknown_op = errors
+        .iter()
+        .find(|error| error["code"] == "UnknownOp")
+        .expect("UnknownOp error");
+    assert!(unknown_op["file"].as_str().unwrap().ends_with("invalid_base.yaml"));
+    assert_eq!(unknown_op["line"], 10);
+
+    // Inline rules have no file to resolve parents against.
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 10,
+        "method": "tools/call",
+        "params": {
+            "name": "validate_rules",
+            "arguments": {
+                "rules_text": "extends: \"base.yaml\"\nversion: 1\ninput:\n  format: json\nmappings: []\n"
+            }
+        }
+    });
+
+    let response = server.send(&request);
+    assert_eq!(response["result"]["isError"], true);
+    let message = response["result"]["content"][0]["text"]
+        .as_str()
+        .expect("error text");
+    assert!(message.contains("`extends` is only supported when rules are loaded from a file"));
+
+    server.shutdown();
+}
+
+#[test]
+fn validate_rules_can_allow_unknown_ops() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let rules_text = r#"version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  - target: "id"
+    expr:
+      op: "future_op"
+      args: [ { ref: "input.id" } ]
+"#;
+
+    let strict = server.send(&json!({
+        "jsonrpc": "2.0",
+        "id": 9,
+        "method": "tools/call",
+        "params": {
+            "name": "validate_rules",
+            "arguments": { "rules_text": rules_text }
+        }
+    }));
+    assert_eq!(strict["result"]["isError"], true);
+    assert_eq!(strict["result"]["meta"]["errors"][0]["code"], "UnknownOp");
+
+    let lenient = server.send(&json!({
+        "jsonrpc": "2.0",
+        "id": 10,
+        "method": "tools/call",
+        "params": {
+            "name": "validate_rules",
+            "arguments": { "rules_text": rules_text, "allow_unknown_ops": true }
+        }
+    }));
+    assert!(lenient["result"]["isError"].is_null());
+    let warning = &lenient["result"]["meta"]["warnings"][0];
+    assert_eq!(warning["code"], "unknown_op");
+    assert_eq!(warning["path"], "mappings[0].expr.op");
+
+    server.shutdown();
+}
+
+#[test]
+fn validate_rules_warns_on_conditional_duplicate_targets() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let rules_text = r#"version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  - target: "status"
+    value: "new"
+    when: { op: "==", args: [ { ref: "input.kind" }, "a" ] }
+  - target: "status"
+    value: "old"
+    when: { op: "==", args: [ { ref: "input.kind" }, "b" ] }
+"#;
+
+    let response = server.send(&json!({
+        "jsonrpc": "2.0",
+        "id": 11,
+        "method": "tools/call",
+        "params": {
+            "name": "validate_rules",
+            "arguments": { "rules_text": rules_text }
+        }
+    }));
+    assert!(response["result"]["isError"].is_null());
+    let warning = &response["result"]["meta"]["warnings"][0];
+    assert_eq!(warning["code"], "conditional_duplicate_target");
+    assert_eq!(warning["path"], "mappings[1].target");
+
+    server.shutdown();
+}
+
+#[test]
+fn generate_dto_typescript() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let rules_text = r#"version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  - target: "id"
+    source: "id"
+"#;
+
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 10,
+        "method": "tools/call",
+        "params": {
+            "name": "generate_dto",
+            "arguments": {
+                "rules_text": rules_text,
+                "language": "typescript"
+            }
+        }
+    });
+
+    let response = server.send(&request);
+    let text = response["result"]["content"][0]["text"]
+        .as_str()
+        .expect("dto text");
+    assert!(text.contains("export interface"));
+
+    server.shutdown();
+}
+
+#[test]
+fn generate_dto_json_schema() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let rules_text = r#"version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  - target: "id"
+    source: "id"
+    type: "int"
+    required: true
+"#;
+
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 10,
+        "method": "tools/call",
+        "params": {
+            "name": "generate_dto",
+            "arguments": {
+                "rules_text": rules_text,
+                "language": "json-schema",
+                "name": "Order"
+            }
+        }
+    });
+
+    let response = server.send(&request);
+    let text = response["result"]["content"][0]["text"]
+        .as_str()
+        .expect("dto text");
+    let schema: serde_json::Value = serde_json::from_str(text).expect("schema json");
+    assert_eq!(schema["title"], "Order");
+    assert_eq!(schema["required"], json!(["id"]));
+    assert_eq!(schema["properties"]["id"]["type"], "integer");
+
+    server.shutdown();
+}
+
+fn large_transform_call(id: u64, timeout_ms: Option<u64>) -> Value {
+    let records: Vec<Value> = (0..50_000)
+        .map(|index| json!({ "id": index, "name": format!("user{}", index) }))
+        .collect();
+    let mut arguments = json!({
+        "rules_text": "version: 1\ninput:\n  format: json\n  json: {}\nmappings:\n  - target: \"id\"\n    source: \"id\"\n  - target: \"name\"\n    expr:\n      op: \"uppercase\"\n      args: [{ ref: \"input.name\" }]\n",
+        "input_json": records
+    });
+    if let Some(timeout_ms) = timeout_ms {
+        arguments["timeout_ms"] = json!(timeout_ms);
+    }
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "tools/call",
+        "params": { "name": "transform", "arguments": arguments }
+    })
+}
+
+#[test]
+fn transform_timeout_is_a_tool_error() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let response = server.send(&large_transform_call(40, Some(1)));
+    assert_eq!(response["id"], 40);
+    assert_eq!(response["result"]["isError"], true);
+    let error = &response["result"]["meta"]["errors"][0];
+    assert_eq!(error["type"], "timeout");
+    assert_eq!(error["timeout_ms"], 1);
+
+    server.shutdown();
+}
+
+#[test]
+fn cancelled_transform_returns_an_error_while_other_requests_are_served() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    server.notify(&large_transform_call(41, None));
+    server.notify(&json!({
+        "jsonrpc": "2.0",
+        "method": "notifications/cancelled",
+        "params": { "requestId": 41, "reason": "user abort" }
+    }));
+    server.notify(&json!({ "jsonrpc": "2.0", "id": 42, "method": "ping" }));
+
+    let mut responses = [server.receive(), server.receive()];
+    responses.sort_by_key(|response| response["id"].as_u64());
+    assert_eq!(responses[0]["id"], 41);
+    assert_eq!(responses[0]["error"]["code"], -32800);
+    assert_eq!(responses[1]["id"], 42);
+    assert_eq!(responses[1]["result"], json!({}));
+
+    server.shutdown();
+}
+
+#[test]
+fn tools_calls_past_the_cap_are_answered_busy() {
+    let mut server = McpServer::start_with_env(&[("TRANSFORM_RULES_MCP_MAX_CALLS", "1")]);
+    initialize(&mut server);
+
+    server.notify(&large_transform_call(43, None));
+    server.notify(&large_transform_call(44, None));
+    let busy = server.receive();
+    assert_eq!(busy["id"], 44);
+    assert_eq!(busy["error"]["code"], -32000);
+    assert!(busy["error"]["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("Server busy: 1 tool calls are running"));
+
+    let done = server.receive();
+    assert_eq!(done["id"], 43);
+    assert!(done.get("result").is_some(), "{}", done);
+    let response = server.send(&large_transform_call(45, Some(1)));
+    assert_eq!(response["id"], 45);
+    assert!(response.get("result").is_some(), "{}", response);
+
+    server.shutdown();
+}
+
+fn transform_text_call(id: u64, rules_text: &str, input_text: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "tools/call",
+        "params": {
+            "name": "transform",
+            "arguments": { "rules_text": rules_text, "input_text": input_text }
+        }
+    })
+}
+
+const LIMIT_RULES: &str = "version: 1\ninput:\n  format: json\n  json: {}\nmappings:\n  - target: \"id\"\n    source: \"id\"\n  - target: \"name\"\n    source: \"name\"\n";
+
+#[test]
+fn initialize_limits_apply_to_rules_text() {
+    let mut server = McpServer::start();
+    let response = server.send(&json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "initialize",
+        "params": {
+            "protocolVersion": "2024-11-05",
+            "capabilities": {},
+            "limits": { "max_mappings": 1 }
+        }
+    }));
+    assert_eq!(response["result"]["protocolVersion"], "2024-11-05");
+
+    let response = server.send(&transform_text_call(2, LIMIT_RULES, r#"[{"id":1}]"#));
+    assert_eq!(response["result"]["isError"], true);
+    let error = &response["result"]["meta"]["errors"][0];
+    assert_eq!(error["type"], "limit");
+    assert_eq!(error["limit"], "max_mappings");
+    assert_eq!(error["max"], 1);
+
+    server.shutdown();
+}
+
+#[test]
+fn env_limits_stop_long_input() {
+    let mut server = McpServer::start_with_env(&[("TRANSFORM_RULES_MCP_MAX_RECORDS", "2")]);
+    initialize(&mut server);
+
+    let response = server.send(&transform_text_call(
+        2,
+        LIMIT_RULES,
+        r#"[{"id":1},{"id":2},{"id":3}]"#,
+    ));
+    assert_eq!(response["result"]["isError"], true);
+    let error = &response["result"]["meta"]["errors"][0];
+    assert_eq!(error["type"], "limit");
+    assert_eq!(error["limit"], "max_records");
+    assert_eq!(error["max"], 2);
+    assert_eq!(error["record_index"], 2);
+
+    let response = server.send(&transform_text_call(3, LIMIT_RULES, r#"[{"id":1},{"id":2}]"#));
+    assert!(response["result"]["isError"].is_null(), "{}", response);
+
+    server.shutdown();
+}
+
+fn paged_transform_call(id: u64, input_text: &str, extra: Value) -> Value {
+    let mut call = transform_text_call(id, LIMIT_RULES, input_text);
+    let args = call["params"]["arguments"].as_object_mut().unwrap();
+    args.extend(extra.as_object().unwrap().clone());
+    call
+}
+
+fn fetch_page_call(id: u64, arguments: Value) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "tools/call",
+        "params": { "name": "fetch_result_page", "arguments": arguments }
+    })
+}
+
+const PAGED_INPUT: &str = r#"[{"id":1},{"id":2},{"id":3},{"id":4},{"id":5}]"#;
+
+#[test]
+fn page_rows_returns_json_pages_to_fetch() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let response = server.send(&paged_transform_call(2, PAGED_INPUT, json!({ "page_rows": 2 })));
+    let result = &response["result"];
+    assert!(result["isError"].is_null(), "{}", response);
+    assert_eq!(result["content"][0]["text"], r#"[{"id":1},{"id":2}]"#);
+    let meta = &result["meta"];
+    assert_eq!(meta["total_rows"], 5);
+    assert_eq!(meta["page"], 0);
+    assert_eq!(meta["has_more"], true);
+    let result_id = meta["result_id"].as_str().expect("result_id").to_string();
+
+    let response = server.send(&fetch_page_call(3, json!({ "result_id": result_id, "page": 1 })));
+    let result = &response["result"];
+    assert_eq!(result["content"][0]["text"], r#"[{"id":3},{"id":4}]"#);
+    assert_eq!(result["meta"]["offset"], 2);
+    assert_eq!(result["meta"]["has_more"], true);
+
+    let response = server.send(&fetch_page_call(
+        4,
+        json!({ "result_id": result_id, "offset": 3, "rows": 5 }),
+    ));
+    let result = &response["result"];
+    let page: Value = serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(page, json!([{ "id": 4 }, { "id": 5 }]));
+    assert_eq!(result["meta"]["rows"], 2);
+    assert_eq!(result["meta"]["has_more"], false);
+
+    let response = server.send(&fetch_page_call(
+        5,
+        json!({ "result_id": result_id, "page": 1, "offset": 2 }),
+    ));
+    assert_eq!(response["error"]["code"], -32602);
+
+    server.shutdown();
+}
+
+#[test]
+fn page_rows_pages_ndjson_by_line() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let response = server.send(&paged_transform_call(
+        2,
+        PAGED_INPUT,
+        json!({ "page_rows": 3, "ndjson": true }),
+    ));
+    let result = &response["result"];
+    assert_eq!(result["content"][0]["text"], "{\"id\":1}\n{\"id\":2}\n{\"id\":3}\n");
+    let result_id = result["meta"]["result_id"].as_str().unwrap().to_string();
+
+    let response = server.send(&fetch_page_call(3, json!({ "result_id": result_id, "page": 1 })));
+    let result = &response["result"];
+    assert_eq!(result["content"][0]["text"], "{\"id\":4}\n{\"id\":5}\n");
+    assert_eq!(result["meta"]["has_more"], false);
+
+    // A result that fits one page is not kept.
+    let response = server.send(&paged_transform_call(4, PAGED_INPUT, json!({ "page_rows": 5 })));
+    let meta = &response["result"]["meta"];
+    assert_eq!(meta["has_more"], false);
+    assert!(meta["result_id"].is_null());
+
+    let response = server.send(&paged_transform_call(
+        5,
+        PAGED_INPUT,
+        json!({ "page_rows": 2, "max_output_bytes": 10 }),
+    ));
+    assert_eq!(response["error"]["code"], -32602);
+
+    server.shutdown();
+}
+
+#[test]
+fn fetching_an_evicted_or_unknown_result_asks_for_a_rerun() {
+    // Each result of PAGED_INPUT holds 40 bytes of rows and 40 of row offsets, so the cache
+    // keeps one at a time.
+    let mut server =
+        McpServer::start_with_env(&[("TRANSFORM_RULES_MCP_RESULT_CACHE_BYTES", "100")]);
+    initialize(&mut server);
+
+    let first = server.send(&paged_transform_call(2, PAGED_INPUT, json!({ "page_rows": 1 })));
+    let first_id = first["result"]["meta"]["result_id"].as_str().unwrap().to_string();
+    let second = server.send(&paged_transform_call(3, PAGED_INPUT, json!({ "page_rows": 1 })));
+    let second_id = second["result"]["meta"]["result_id"].as_str().unwrap().to_string();
+    assert_ne!(first_id, second_id);
+    for id in [&first_id, &second_id] {
+        assert_eq!(id.len(), 33, "{}", id);
+        assert!(id[1..].bytes().all(|byte| byte.is_ascii_hexdigit()), "{}", id);
+    }
+
+    let response = server.send(&fetch_page_call(4, json!({ "result_id": second_id, "page": 4 })));
+    assert_eq!(response["result"]["content"][0]["text"], r#"[{"id":5}]"#);
+
+    for (id, result_id) in [(5, first_id.as_str()), (6, "unknown")] {
+        let response = server.send(&fetch_page_call(id, json!({ "result_id": result_id })));
+        let result = &response["result"];
+        assert_eq!(result["isError"], true);
+        let error = &result["meta"]["errors"][0];
+        assert_eq!(error["type"], "result_expired");
+        assert_eq!(error["result_id"], result_id);
+        assert!(error["message"].as_str().unwrap().contains("page_rows"));
+    }
+
+    let large = format!("[{}]", [r#"{"id":12345}"#; 10].join(","));
+    let response = server.send(&paged_transform_call(7, &large, json!({ "page_rows": 1 })));
+    let error = &response["result"]["meta"]["errors"][0];
+    assert_eq!(error["type"], "limit");
+    assert_eq!(error["limit"], "result_cache_bytes");
+    assert_eq!(error["max"], 100);
+
+    server.shutdown();
+}
+
+#[test]
+fn paged_results_expire_after_their_ttl() {
+    let mut server = McpServer::start_with_env(&[("TRANSFORM_RULES_MCP_RESULT_TTL_SECS", "1")]);
+    initialize(&mut server);
+
+    let response = server.send(&paged_transform_call(2, PAGED_INPUT, json!({ "page_rows": 2 })));
+    let result_id = response["result"]["meta"]["result_id"].as_str().unwrap().to_string();
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+
+    let response = server.send(&fetch_page_call(3, json!({ "result_id": result_id })));
+    assert_eq!(response["result"]["meta"]["errors"][0]["type"], "result_expired");
+
+    server.shutdown();
+}
+
+#[test]
+fn invalid_initialize_limits_are_rejected() {
+    let mut server = McpServer::start();
+    let response = server.send(&json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "initialize",
+        "params": { "limits": { "max_widgets": 3 } }
+    }));
+    assert_eq!(response["error"]["code"], -32602);
+    assert_eq!(response["error"]["message"], "unknown limit: max_widgets");
+
+    server.shutdown();
+}
+
+#[test]
+fn transform_check_returns_structured_diff() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 12,
+        "method": "tools/call",
+        "params": {
+            "name": "transform_check",
+            "arguments": {
+                "rules_text": "version: 1\ninput:\n  format: json\n  json: {}\nmappings:\n  - target: \"id\"\n    source: \"id\"\n  - target: \"name\"\n    source: \"name\"\n",
+                "input_json": [{ "id": 1, "name": "alice" }, { "id": 2, "name": "bob" }],
+                "expected_json": [{ "id": 2, "name": "bob" }, { "id": 1, "name": "alicia" }],
+                "array_key": "id"
+            }
+        }
+    });
+    let response = server.send(&request);
+    let result = &response["result"];
+    assert!(result["isError"].is_null(), "{}", result);
+    assert_eq!(result["meta"]["identical"], false);
+    assert_eq!(
+        result["meta"]["diff"],
+        json!([{
+            "type": "diff",
+            "kind": "changed",
+            "path": "[id=1].name",
+            "expected": "alicia",
+            "actual": "alice"
+        }])
+    );
+
+    let mut request = request;
+    request["id"] = json!(13);
+    request["params"]["arguments"]["expected_json"] =
+        json!([{ "id": 1, "name": "alice" }, { "id": 2, "name": "bob" }]);
+    request["params"]["arguments"].as_object_mut().unwrap().remove("array_key");
+    let response = server.send(&request);
+    assert_eq!(response["result"]["meta"]["identical"], true);
+    assert_eq!(response["result"]["content"][0]["text"], "ok: output matches");
+
+    server.shutdown();
+}
+
+#[test]
+fn trace_record_returns_each_mapping_step() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 12,
+        "method": "tools/call",
+        "params": {
+            "name": "trace_record",
+            "arguments": {
+                "rules_text": "version: 1\ninput:\n  format: json\n  json: {}\nmappings:\n  - target: \"id\"\n    source: \"id\"\n    type: \"int\"\n  - target: \"name\"\n    expr:\n      chain:\n        - { ref: \"input.name\" }\n        - { op: \"trim\" }\n",
+                "input_json": [{ "id": "1", "name": " a " }, { "id": "x", "name": "b" }],
+                "record_index": 0
+            }
+        }
+    });
+    let response = server.send(&request);
+    let result = &response["result"];
+    assert!(result["isError"].is_null(), "{}", result);
+    let trace = &result["meta"]["trace"];
+    assert_eq!(trace["record_index"], 0);
+    assert_eq!(trace["output"], json!({ "id": 1, "name": "a" }));
+    assert_eq!(trace["mappings"][0]["value"], "1");
+    assert_eq!(
+        trace["mappings"][1]["steps"],
+        json!([
+            { "step": 0, "op": null, "kind": "step", "value": "\" a \"" },
+            { "step": 1, "op": "trim", "kind": "step", "value": "\"a\"" }
+        ])
+    );
+
+    let mut request = request;
+    request["id"] = json!(13);
+    request["params"]["arguments"]["record_index"] = json!(1);
+    let response = server.send(&request);
+    let result = &response["result"];
+    assert_eq!(result["isError"], true);
+    assert_eq!(result["meta"]["errors"][0]["kind"], "TypeCastFailed");
+    let steps = &result["meta"]["trace"]["mappings"][0]["steps"];
+    assert_eq!(steps[1]["kind"], "error");
+
+    request["id"] = json!(14);
+    request["params"]["arguments"]["record_index"] = json!(2);
+    let response = server.send(&request);
+    assert_eq!(response["result"]["content"][0]["text"], "input has no record 2");
+
+    server.shutdown();
+}
+
+#[test]
+fn list_ops_success() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 11,
+        "method": "tools/call",
+        "params": {
+            "name": "list_ops",
+            "arguments": {}
+        }
+    });
+
+    let response = server.send(&request);
+    let type_casts = response["result"]["meta"]["ops"]["type_casts"]
+        .as_array()
+        .expect("type_casts array");
+    assert!(type_casts.contains(&json!("datetime")));
+    assert!(type_casts.contains(&json!("decimal(N)")));
+    assert_eq!(
+        response["result"]["meta"]["ops"]["conditional_ops"],
+        json!(["if", "case"])
+    );
+    let signature = &response["result"]["meta"]["ops"]["signatures"]["substring"];
+    assert_eq!(signature["min_args"], 2);
+    assert_eq!(signature["max_args"], 3);
+    assert_eq!(signature["args"], json!(["value", "start", "length?"]));
+    assert!(response["result"]["meta"]["ops"]["categories"]["json_ops"].is_array());
+    assert!(response["result"]["meta"]["ops"]["categories"]["array_ops"].is_array());
+    assert!(response["result"]["meta"]["ops"]["category_docs"]["json_ops"]["examples"].is_array());
+    assert!(response["result"]["meta"]["ops"]["category_docs"]["string_ops"]["examples"].is_array());
+
+    server.shutdown();
+}
+
+#[test]
+fn list_error_codes_lists_the_catalog() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let response = server.send(&json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "tools/call",
+        "params": { "name": "list_error_codes", "arguments": {} }
+    }));
+    let codes = response["result"]["meta"]["codes"]
+        .as_array()
+        .expect("codes array");
+    let arg_count = codes
+        .iter()
+        .find(|code| code["code"] == "T1201")
+        .expect("T1201 is listed");
+    assert_eq!(arg_count["name"], "ARG_COUNT");
+    assert_eq!(arg_count["kind"], "ExprError");
+    assert!(arg_count["description"].as_str().is_some_and(|text| !text.is_empty()));
+    let text = response["result"]["content"][0]["text"].as_str().unwrap();
+    assert!(text.contains("T1405 BAD_TIMEZONE (ExprError): "), "{}", text);
+    assert_eq!(text.lines().count(), codes.len());
+
+    server.shutdown();
+}
+
+#[test]
+fn self_check_success() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 12,
+        "method": "tools/call",
+        "params": {
+            "name": "self_check",
+            "arguments": {}
+        }
+    });
+
+    let response = server.send(&request);
+    let meta = &response["result"]["meta"];
+    assert_eq!(meta["ok"], true);
+    assert!(response["result"]["isError"].is_null());
+    let checks = meta["checks"].as_array().expect("checks array");
+    assert!(checks.iter().all(|check| check["status"] == "pass"));
+    assert!(checks.iter().any(|check| check["name"] == "stdio_framing"));
+    assert!(meta["environment"]["cache_capacities"]["rules"].is_number());
+
+    server.shutdown();
+}
+
+#[test]
+fn analyze_input_json_success() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 12,
+        "method": "tools/call",
+        "params": {
+            "name": "analyze_input",
+            "arguments": {
+                "input_json": {
+                    "id": 1,
+                    "name": "Ada"
+                }
+            }
+        }
+    });
+
+    let response = server.send(&request);
+    let paths = response["result"]["meta"]["paths"]
+        .as_array()
+        .expect("paths array");
+    assert!(paths.iter().any(|item| item["path"] == "id"));
+    assert!(paths.iter().any(|item| item["path"] == "name"));
+
+    server.shutdown();
+}
+
+#[test]
+fn analyze_input_csv_success() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 13,
+        "method": "tools/call",
+        "params": {
+            "name": "analyze_input",
+            "arguments": {
+                "input_text": "id,name\n1,Ada\n2,Bob\n",
+                "format": "csv"
+            }
+        }
+    });
+
+    let response = server.send(&request);
+    let paths = response["result"]["meta"]["paths"]
+        .as_array()
+        .expect("paths array");
+    assert!(paths.iter().any(|item| item["path"] == "id"));
+
+    server.shutdown();
+}
+
+#[test]
+fn analyze_input_describes_array_items() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let input = json!({
+        "order": 1,
+        "items": [
+            { "sku": "A", "qty": 2, "tags": ["x"] },
+            { "sku": "B" },
+            { "sku": "C", "qty": 1 }
+        ]
+    });
+    let analyze = |server: &mut McpServer, id: u64, extra: Value| {
+        let mut arguments = json!({ "input_json": input.clone() });
+        arguments.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+        let response = server.send(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": "analyze_input", "arguments": arguments }
+        }));
+        response["result"]["meta"]["paths"].as_array().expect("paths array").clone()
+    };
+    let find = |paths: &[Value], path: &str| paths.iter().find(|item| item["path"] == path).cloned();
+
+    let paths = analyze(&mut server, 60, json!({}));
+    assert_eq!(find(&paths, "items").unwrap()["types"], json!({ "array": 1 }));
+    assert_eq!(find(&paths, "items[]").unwrap()["types"], json!({ "object": 3 }));
+    let sku = find(&paths, "items[].sku").unwrap();
+    assert_eq!(sku["count"], json!(3));
+    assert_eq!(sku["examples"], json!(["A", "B", "C"]));
+    assert_eq!(find(&paths, "items[].qty").unwrap()["count"], json!(2));
+    assert_eq!(find(&paths, "items[].tags[]").unwrap()["types"], json!({ "string": 1 }));
+
+    let paths = analyze(&mut server, 61, json!({ "max_array_samples": 1, "max_array_depth": 1 }));
+    assert_eq!(find(&paths, "items[].sku").unwrap()["count"], json!(1));
+    assert!(find(&paths, "items[].tags").is_some());
+    assert!(find(&paths, "items[].tags[]").is_none());
+
+    server.shutdown();
+}
+
+#[test]
+fn generate_rules_from_dto_maps_array_items() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let dto_text = "pub struct Order { pub id: i64, pub items: Vec<Item> }\npub struct Item { pub sku: String, pub qty: i64 }";
+    let input = json!({ "id": 7, "lines": [{ "sku": "A", "qty": 2 }, { "sku": "B", "qty": 1 }] });
+    let response = server.send(&json!({
+        "jsonrpc": "2.0",
+        "id": 63,
+        "method": "tools/call",
+        "params": {
+            "name": "generate_rules_from_dto",
+            "arguments": {
+                "dto_text": dto_text,
+                "dto_language": "rust",
+                "input_json": input.clone()
+            }
+        }
+    }));
+    let output_text = response["result"]["content"][0]["text"]
+        .as_str()
+        .expect("output text");
+    let rule = parse_rule_file(output_text).expect("parse output rules");
+    let targets: Vec<_> = rule.mappings.iter().map(|mapping| mapping.target.as_str()).collect();
+    assert_eq!(targets, vec!["id", "items[].sku", "items[].qty"]);
+    assert_eq!(single_source(&rule, 0), Some("id"));
+    assert_eq!(response["result"]["meta"]["candidates"][1]["selected"], json!("lines[].sku"));
+
+    let output = transform_rules::transform(&rule, &input.to_string(), None).expect("transform");
+    assert_eq!(
+        output,
+        json!([{ "id": 7, "items": [{ "sku": "A", "qty": 2 }, { "sku": "B", "qty": 1 }] }])
+    );
+
+    server.shutdown();
+}
+
+#[test]
+fn generate_rules_from_dto_round_trips_keys_with_dots() {
+    let rules_text = r#"version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  - target: "id"
+    source: "id"
+  - target: '["app.kubernetes.io/name"]'
+    source: '["app.kubernetes.io/name"]'
+  - target: 'labels["app.kubernetes.io/part-of"]'
+    source: 'labels["app.kubernetes.io/part-of"]'
+  - target: 'items[]["a.b"]'
+    expr:
+      chain:
+        - { ref: "input.items" }
+        - { op: "map", args: [ { ref: 'item.value["a.b"]' } ] }
+"#;
+    let rule = parse_rule_file(rules_text).expect("parse rules");
+    transform_rules::validate_rule_file(&rule).expect("valid rules");
+    let input = json!({
+        "id": "o-7",
+        "app.kubernetes.io/name": "web",
+        "labels": { "app.kubernetes.io/part-of": "shop" },
+        "items": [{ "a.b": "x" }, { "a.b": "y" }]
+    });
+    let output = transform_rules::transform(&rule, &input.to_string(), None).expect("transform");
+    assert_eq!(output, json!([input.clone()]));
+
+    let mut server = McpServer::start();
+    initialize(&mut server);
+    let languages = [
+        (DtoLanguage::Rust, "rust"),
+        (DtoLanguage::TypeScript, "typescript"),
+        (DtoLanguage::Python, "python"),
+        (DtoLanguage::Go, "go"),
+        (DtoLanguage::Java, "java"),
+        (DtoLanguage::Kotlin, "kotlin"),
+        (DtoLanguage::Swift, "swift"),
+    ];
+    for (index, (language, name)) in languages.into_iter().enumerate() {
+        let dto_text = generate_dto(&rule, language, Some("Record")).expect("generate dto");
+        let response = server.send(&json!({
+            "jsonrpc": "2.0",
+            "id": 900 + index,
+            "method": "tools/call",
+            "params": {
+                "name": "generate_rules_from_dto",
+                "arguments": {
+                    "dto_text": dto_text,
+                    "dto_language": name,
+                    "input_json": input.clone()
+                }
+            }
+        }));
+        let output_text = response["result"]["content"][0]["text"]
+            .as_str()
+            .unwrap_or_else(|| panic!("{}: {}", name, response));
+        let generated = parse_rule_file(output_text)
+            .unwrap_or_else(|err| panic!("{}: {} {}", name, err, output_text));
+        let targets: Vec<_> = generated.mappings.iter().map(|m| m.target.as_str()).collect();
+        assert_eq!(
+            targets,
+            vec![
+                "id",
+                r#"["app.kubernetes.io/name"]"#,
+                r#"labels["app.kubernetes.io/part-of"]"#,
+                r#"items[]["a.b"]"#
+            ],
+            "{}",
+            name
+        );
+        let output = transform_rules::transform(&generated, &input.to_string(), None)
+            .unwrap_or_else(|err| panic!("{}: {:?}", name, err));
+        assert_eq!(output, json!([input.clone()]), "{}", name);
+    }
+    server.shutdown();
+}
+
+#[test]
+fn generate_rules_from_base_success() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let rules_text = r#"version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  - target: "id"
+    source: "old_id"
+  - target: "name"
+    source: "old_name"
+"#;
+
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 14,
+        "method": "tools/call",
+        "params": {
+            "name": "generate_rules_from_base",
+            "arguments": {
+                "rules_text": rules_text,
+                "input_json": {
+                    "id": 1,
+                    "name": "Ada"
+                }
+            }
+        }
+    });
+
+    let response = server.send(&request);
+    let output_text = response["result"]["content"][0]["text"]
         .as_str()
-        .expect("result text");
-    assert_eq!(text, "ok");
+        .expect("output text");
+    let rule = parse_rule_file(output_text).expect("parse output rules");
+    assert_eq!(single_source(&rule, 0), Some("id"));
+    assert_eq!(single_source(&rule, 1), Some("name"));
 
     server.shutdown();
 }
 
 #[test]
-fn validate_rules_failure() {
+fn generate_rules_from_base_edits_only_the_extending_file() {
     let mut server = McpServer::start();
     initialize(&mut server);
 
-    let rules_text = r#"version: 1
+    let dir = tempdir().expect("temp dir");
+    let base_rules = r#"version: 1
 input:
-  format: csv
-mappings: []
+  format: json
+  json: {}
+mappings:
+  - target: "id"
+    source: "old_id"
+  - target: "name"
+    source: "old_name"
 "#;
+    fs::write(dir.path().join("base.yaml"), base_rules).expect("write base");
+    let rules_path = dir.path().join("tenant.yaml");
+    let tenant_rules = r#"extends: "base.yaml"
+mappings:
+  # Overrides the base mapping.
+  - target: "name"
+    source: "old_label"
+  - target: "email"
+    source: "old_email"
+"#;
+    fs::write(&rules_path, tenant_rules).expect("write tenant");
 
-    let request = json!({
+    let response = server.send(&json!({
         "jsonrpc": "2.0",
-        "id": 9,
+        "id": 14,
         "method": "tools/call",
         "params": {
-            "name": "validate_rules",
+            "name": "generate_rules_from_base",
             "arguments": {
-                "rules_text": rules_text
+                "rules_path": rules_path.to_string_lossy(),
+                "input_json": { "id": 1, "name": "Ada", "email": "ada@example.com" }
             }
         }
-    });
-
-    let response = server.send(&request);
-    assert_eq!(response["result"]["isError"], true);
-    assert!(response["result"]["meta"]["errors"].is_array());
+    }));
+    let result = &response["result"];
+    assert!(result["isError"].is_null(), "{}", result);
+    let meta = &result["meta"];
+    assert_eq!(meta["inherited"], json!(["id"]));
+    assert_eq!(meta["summary"]["inherited"], 1);
+    assert_eq!(meta["summary"]["mapped"], 2);
+    let output_text = result["content"][0]["text"].as_str().expect("output text");
+    assert!(output_text.starts_with("extends: \"base.yaml\"\n"), "{}", output_text);
+    assert!(output_text.contains("# Overrides the base mapping."), "{}", output_text);
+    assert!(!output_text.contains("target: \"id\""), "{}", output_text);
+
+    fs::write(&rules_path, output_text).expect("write output");
+    let rule = load_rule_file(&rules_path).expect("load output rules");
+    assert_eq!(single_source(&rule, 0), Some("old_id"));
+    assert_eq!(single_source(&rule, 1), Some("name"));
+    assert_eq!(single_source(&rule, 2), Some("email"));
 
     server.shutdown();
 }
 
 #[test]
-fn generate_dto_typescript() {
+fn generate_rules_from_base_keeps_const_refs() {
     let mut server = McpServer::start();
     initialize(&mut server);
 
@@ -383,117 +1837,206 @@ fn generate_dto_typescript() {
 input:
   format: json
   json: {}
+constants:
+  region: { ref: "context.region" }
 mappings:
   - target: "id"
-    source: "id"
+    source: "old_id"
+  - target: "region"
+    source: "const.region"
+  - target: "label"
+    expr: { op: "concat", args: [ { ref: "const.region" }, "-", { ref: "input.id" } ] }
 "#;
 
     let request = json!({
         "jsonrpc": "2.0",
-        "id": 10,
+        "id": 16,
         "method": "tools/call",
         "params": {
-            "name": "generate_dto",
+            "name": "generate_rules_from_base",
             "arguments": {
                 "rules_text": rules_text,
-                "language": "typescript"
+                "input_json": { "id": 1, "region_code": "eu" }
             }
         }
     });
 
     let response = server.send(&request);
-    let text = response["result"]["content"][0]["text"]
+    let output_text = response["result"]["content"][0]["text"]
         .as_str()
-        .expect("dto text");
-    assert!(text.contains("export interface"));
+        .expect("output text");
+    let rule = parse_rule_file(output_text).expect("parse output rules");
+    assert_eq!(single_source(&rule, 0), Some("id"));
+    assert_eq!(single_source(&rule, 1), Some("const.region"));
+    let meta = &response["result"]["meta"];
+    assert!(meta["missing_refs"].is_null(), "{}", meta);
+    assert_eq!(meta["summary"]["unmapped"], 0);
 
     server.shutdown();
 }
 
 #[test]
-fn list_ops_success() {
+fn generate_rules_from_base_preserves_mapping_groups() {
     let mut server = McpServer::start();
     initialize(&mut server);
 
-    let request = json!({
-        "jsonrpc": "2.0",
-        "id": 11,
-        "method": "tools/call",
-        "params": {
-            "name": "list_ops",
-            "arguments": {}
-        }
-    });
-
-    let response = server.send(&request);
-    assert!(response["result"]["meta"]["ops"]["type_casts"].is_array());
-    assert!(response["result"]["meta"]["ops"]["categories"]["json_ops"].is_array());
-    assert!(response["result"]["meta"]["ops"]["categories"]["array_ops"].is_array());
-    assert!(response["result"]["meta"]["ops"]["category_docs"]["json_ops"]["examples"].is_array());
-    assert!(response["result"]["meta"]["ops"]["category_docs"]["string_ops"]["examples"].is_array());
-
-    server.shutdown();
-}
-
-#[test]
-fn analyze_input_json_success() {
-    let mut server = McpServer::start();
-    initialize(&mut server);
+    let rules_text = r#"version: 1
+input:
+  format: json
+  json: {}
+group_order: [shipping, billing]
+mapping_groups:
+  billing:
+    - target: "total"
+      source: "old_total"
+  shipping:
+    - target: "city"
+      source: "old_city"
+"#;
 
     let request = json!({
         "jsonrpc": "2.0",
-        "id": 12,
+        "id": 15,
         "method": "tools/call",
         "params": {
-            "name": "analyze_input",
+            "name": "generate_rules_from_base",
             "arguments": {
+                "rules_text": rules_text,
                 "input_json": {
-                    "id": 1,
-                    "name": "Ada"
+                    "total": 10,
+                    "city": "Hanoi"
                 }
             }
         }
     });
 
     let response = server.send(&request);
-    let paths = response["result"]["meta"]["paths"]
-        .as_array()
-        .expect("paths array");
-    assert!(paths.iter().any(|item| item["path"] == "id"));
-    assert!(paths.iter().any(|item| item["path"] == "name"));
+    let output_text = response["result"]["content"][0]["text"]
+        .as_str()
+        .expect("output text");
+    let rule = parse_rule_file(output_text).expect("parse output rules");
+    let groups = rule.mapping_groups.as_ref().expect("mapping groups");
+    assert!(!groups.with_mappings);
+    assert_eq!(rule.mapping_group(0), Some(("shipping", 0)));
+    assert_eq!(single_source(&rule, 0), Some("city"));
+    assert_eq!(single_source(&rule, 1), Some("total"));
 
     server.shutdown();
 }
 
 #[test]
-fn analyze_input_csv_success() {
+fn generate_rules_from_base_keeps_comments_and_layout() {
     let mut server = McpServer::start();
     initialize(&mut server);
 
+    let rules_text = r#"# Customer export rules
+version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  # keep the legacy id until the migration ends
+  - target: "id"
+    source: "old_id" # renamed upstream
+    type: &text "string"
+  - { target: "name", source: "old_name" }
+  - target: "notes"
+    value: |
+      Imported by the nightly job.
+      Do not edit by hand.
+  - target: "city"
+    source: "old_city"
+    type: *text
+  - &zip { target: "zip", source: "old_zip" }
+  - *zip
+  - target: "fax"
+    source: "legacy_fax"
+"#;
+
     let request = json!({
         "jsonrpc": "2.0",
-        "id": 13,
+        "id": 18,
         "method": "tools/call",
         "params": {
-            "name": "analyze_input",
+            "name": "generate_rules_from_base",
             "arguments": {
-                "input_text": "id,name\n1,Ada\n2,Bob\n",
-                "format": "csv"
+                "rules_text": rules_text,
+                "input_json": { "id": 1, "name": "Ada", "city": "Hanoi", "zip": "100000" }
             }
         }
     });
 
     let response = server.send(&request);
-    let paths = response["result"]["meta"]["paths"]
-        .as_array()
-        .expect("paths array");
-    assert!(paths.iter().any(|item| item["path"] == "id"));
+    let output_text = response["result"]["content"][0]["text"]
+        .as_str()
+        .expect("output text");
+    assert_eq!(
+        output_text,
+        r#"# Customer export rules
+version: 1
+input:
+  format: json
+  json: {}
+mappings:
+  # keep the legacy id until the migration ends
+  - target: "id"
+    source: "id" # renamed upstream
+    type: &text "string"
+  - { target: "name", source: "name" }
+  - target: "notes"
+    value: |
+      Imported by the nightly job.
+      Do not edit by hand.
+  - target: "city"
+    source: "city"
+    type: *text
+  - &zip { target: "zip", source: "zip" }
+  - { target: "zip", source: "zip" }
+  - target: "fax"
+    value: null
+    required: false
+"#
+    );
+
+    let meta = &response["result"]["meta"];
+    assert_eq!(meta["formatting_preserved"], true);
+    assert_eq!(
+        meta["diff"],
+        r#"--- a/rules.yaml
++++ b/rules.yaml
+@@ -6,17 +6,18 @@
+ mappings:
+   # keep the legacy id until the migration ends
+   - target: "id"
+-    source: "old_id" # renamed upstream
++    source: "id" # renamed upstream
+     type: &text "string"
+-  - { target: "name", source: "old_name" }
++  - { target: "name", source: "name" }
+   - target: "notes"
+     value: |
+       Imported by the nightly job.
+       Do not edit by hand.
+   - target: "city"
+-    source: "old_city"
++    source: "city"
+     type: *text
+-  - &zip { target: "zip", source: "old_zip" }
+-  - *zip
++  - &zip { target: "zip", source: "zip" }
++  - { target: "zip", source: "zip" }
+   - target: "fax"
+-    source: "legacy_fax"
++    value: null
++    required: false
+"#
+    );
 
     server.shutdown();
 }
 
 #[test]
-fn generate_rules_from_base_success() {
+fn generate_rules_from_base_emits_and_keeps_fallback_sources() {
     let mut server = McpServer::start();
     initialize(&mut server);
 
@@ -502,23 +2045,24 @@ input:
   format: json
   json: {}
 mappings:
-  - target: "id"
-    source: "old_id"
+  - target: "email"
+    source: "old_email"
   - target: "name"
-    source: "old_name"
+    source: [ "old_name", "input.profile.name", "context.name" ]
 "#;
 
     let request = json!({
         "jsonrpc": "2.0",
-        "id": 14,
+        "id": 17,
         "method": "tools/call",
         "params": {
             "name": "generate_rules_from_base",
             "arguments": {
                 "rules_text": rules_text,
                 "input_json": {
-                    "id": 1,
-                    "name": "Ada"
+                    "billing": { "email": "billing@example.com" },
+                    "contact": { "email": "contact@example.com" },
+                    "profile": { "nickname": "Ada" }
                 }
             }
         }
@@ -529,8 +2073,32 @@ mappings:
         .as_str()
         .expect("output text");
     let rule = parse_rule_file(output_text).expect("parse output rules");
-    assert_eq!(rule.mappings[0].source.as_deref(), Some("id"));
-    assert_eq!(rule.mappings[1].source.as_deref(), Some("name"));
+    let fallback = |sources: &[&str]| {
+        Some(MappingSource::from(sources.iter().map(|s| s.to_string()).collect::<Vec<_>>()))
+    };
+    assert_eq!(
+        rule.mappings[0].source,
+        fallback(&["input.billing.email", "input.contact.email"])
+    );
+    assert_eq!(
+        rule.mappings[1].source,
+        fallback(&["old_name", "input.profile.name", "context.name"])
+    );
+
+    let meta = &response["result"]["meta"];
+    assert_eq!(meta["summary"]["mapped"], 1);
+    assert_eq!(meta["summary"]["with_fallback"], 1);
+    assert_eq!(
+        meta["candidates"][0]["selected"],
+        json!(["billing.email", "contact.email"])
+    );
+    assert_eq!(
+        meta["missing_refs"],
+        json!([
+            { "target": "name", "ref": "old_name", "path": "old_name" },
+            { "target": "name", "ref": "input.profile.name", "path": "profile.name" }
+        ])
+    );
 
     server.shutdown();
 }
@@ -567,8 +2135,8 @@ fn generate_rules_from_dto_success() {
         .as_str()
         .expect("output text");
     let rule = parse_rule_file(output_text).expect("parse output rules");
-    assert_eq!(rule.mappings[0].source.as_deref(), Some("id"));
-    assert_eq!(rule.mappings[1].source.as_deref(), Some("name"));
+    assert_eq!(single_source(&rule, 0), Some("id"));
+    assert_eq!(single_source(&rule, 1), Some("name"));
 
     server.shutdown();
 }
@@ -602,8 +2170,8 @@ fn generate_rules_from_dto_single_line_interface() {
         .as_str()
         .expect("output text");
     let rule = parse_rule_file(output_text).expect("parse output rules");
-    assert_eq!(rule.mappings[0].source.as_deref(), Some("id"));
-    assert_eq!(rule.mappings[1].source.as_deref(), Some("name"));
+    assert_eq!(single_source(&rule, 0), Some("id"));
+    assert_eq!(single_source(&rule, 1), Some("name"));
 
     server.shutdown();
 }
@@ -638,9 +2206,9 @@ fn generate_rules_from_dto_single_line_rust_struct() {
         .as_str()
         .expect("output text");
     let rule = parse_rule_file(output_text).expect("parse output rules");
-    assert_eq!(rule.mappings[0].source.as_deref(), Some("id"));
-    assert_eq!(rule.mappings[1].source.as_deref(), Some("name"));
-    assert_eq!(rule.mappings[2].source.as_deref(), Some("price"));
+    assert_eq!(single_source(&rule, 0), Some("id"));
+    assert_eq!(single_source(&rule, 1), Some("name"));
+    assert_eq!(single_source(&rule, 2), Some("price"));
 
     server.shutdown();
 }
@@ -681,7 +2249,7 @@ fn generate_rules_from_dto_python_single_line_alias() {
         .iter()
         .find(|mapping| mapping.target == "id")
         .expect("id mapping");
-    assert_eq!(id_mapping.source.as_deref(), Some("id"));
+    assert_eq!(id_mapping.source.as_ref().and_then(MappingSource::as_single), Some("id"));
     assert!(id_mapping.required);
 
     let name_mapping = rule
@@ -689,7 +2257,7 @@ fn generate_rules_from_dto_python_single_line_alias() {
         .iter()
         .find(|mapping| mapping.target == "name")
         .expect("name mapping");
-    assert_eq!(name_mapping.source.as_deref(), Some("name"));
+    assert_eq!(name_mapping.source.as_ref().and_then(MappingSource::as_single), Some("name"));
     assert!(!name_mapping.required);
 
     let price_mapping = rule
@@ -697,7 +2265,7 @@ fn generate_rules_from_dto_python_single_line_alias() {
         .iter()
         .find(|mapping| mapping.target == "price_cents")
         .expect("price mapping");
-    assert_eq!(price_mapping.source.as_deref(), Some("price_cents"));
+    assert_eq!(price_mapping.source, Some(MappingSource::from("price_cents")));
     assert!(price_mapping.required);
 
     server.shutdown();
@@ -739,7 +2307,7 @@ fn generate_rules_from_dto_go_single_line_tags() {
         .iter()
         .find(|mapping| mapping.target == "id")
         .expect("id mapping");
-    assert_eq!(id_mapping.source.as_deref(), Some("id"));
+    assert_eq!(id_mapping.source.as_ref().and_then(MappingSource::as_single), Some("id"));
     assert!(id_mapping.required);
 
     let name_mapping = rule
@@ -747,7 +2315,7 @@ fn generate_rules_from_dto_go_single_line_tags() {
         .iter()
         .find(|mapping| mapping.target == "name")
         .expect("name mapping");
-    assert_eq!(name_mapping.source.as_deref(), Some("name"));
+    assert_eq!(name_mapping.source.as_ref().and_then(MappingSource::as_single), Some("name"));
     assert!(!name_mapping.required);
 
     server.shutdown();
@@ -788,7 +2356,7 @@ fn generate_rules_from_dto_java_single_line_annotations() {
         .iter()
         .find(|mapping| mapping.target == "user_id")
         .expect("id mapping");
-    assert_eq!(id_mapping.source.as_deref(), Some("user_id"));
+    assert_eq!(id_mapping.source.as_ref().and_then(MappingSource::as_single), Some("user_id"));
     assert!(id_mapping.required);
 
     let name_mapping = rule
@@ -796,7 +2364,7 @@ fn generate_rules_from_dto_java_single_line_annotations() {
         .iter()
         .find(|mapping| mapping.target == "full_name")
         .expect("name mapping");
-    assert_eq!(name_mapping.source.as_deref(), Some("full_name"));
+    assert_eq!(name_mapping.source.as_ref().and_then(MappingSource::as_single), Some("full_name"));
     assert!(!name_mapping.required);
 
     server.shutdown();
@@ -838,7 +2406,7 @@ fn generate_rules_from_dto_kotlin_single_line_annotations() {
         .iter()
         .find(|mapping| mapping.target == "user_id")
         .expect("id mapping");
-    assert_eq!(id_mapping.source.as_deref(), Some("user_id"));
+    assert_eq!(id_mapping.source.as_ref().and_then(MappingSource::as_single), Some("user_id"));
     assert!(id_mapping.required);
 
     let name_mapping = rule
@@ -846,7 +2414,7 @@ fn generate_rules_from_dto_kotlin_single_line_annotations() {
         .iter()
         .find(|mapping| mapping.target == "full_name")
         .expect("name mapping");
-    assert_eq!(name_mapping.source.as_deref(), Some("full_name"));
+    assert_eq!(name_mapping.source.as_ref().and_then(MappingSource::as_single), Some("full_name"));
     assert!(!name_mapping.required);
 
     let price_mapping = rule
@@ -854,7 +2422,7 @@ fn generate_rules_from_dto_kotlin_single_line_annotations() {
         .iter()
         .find(|mapping| mapping.target == "price")
         .expect("price mapping");
-    assert_eq!(price_mapping.source.as_deref(), Some("price"));
+    assert_eq!(price_mapping.source, Some(MappingSource::from("price")));
     assert!(price_mapping.required);
 
     server.shutdown();
@@ -896,7 +2464,7 @@ fn generate_rules_from_dto_swift_single_line_coding_keys() {
         .iter()
         .find(|mapping| mapping.target == "user_id")
         .expect("id mapping");
-    assert_eq!(id_mapping.source.as_deref(), Some("user_id"));
+    assert_eq!(id_mapping.source.as_ref().and_then(MappingSource::as_single), Some("user_id"));
     assert!(id_mapping.required);
 
     let name_mapping = rule
@@ -904,7 +2472,7 @@ fn generate_rules_from_dto_swift_single_line_coding_keys() {
         .iter()
         .find(|mapping| mapping.target == "name")
         .expect("name mapping");
-    assert_eq!(name_mapping.source.as_deref(), Some("name"));
+    assert_eq!(name_mapping.source.as_ref().and_then(MappingSource::as_single), Some("name"));
     assert!(!name_mapping.required);
 
     let price_mapping = rule
@@ -912,7 +2480,7 @@ fn generate_rules_from_dto_swift_single_line_coding_keys() {
         .iter()
         .find(|mapping| mapping.target == "price_cents")
         .expect("price mapping");
-    assert_eq!(price_mapping.source.as_deref(), Some("price_cents"));
+    assert_eq!(price_mapping.source, Some(MappingSource::from("price_cents")));
     assert!(price_mapping.required);
 
     server.shutdown();
@@ -951,6 +2519,153 @@ fn resources_list_and_read() {
     server.shutdown();
 }
 
+#[test]
+fn examples_are_listed_and_run_to_their_expected_output() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let index = server.send(&json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "resources/read",
+        "params": { "uri": "transform-rules://examples" }
+    }));
+    let index = index["result"]["contents"][0]["text"]
+        .as_str()
+        .expect("index text");
+    let list = server.send(&json!({
+        "jsonrpc": "2.0",
+        "id": 3,
+        "method": "resources/list"
+    }));
+    let uris: Vec<&str> = list["result"]["resources"]
+        .as_array()
+        .expect("resources array")
+        .iter()
+        .filter_map(|item| item["uri"].as_str())
+        .filter(|uri| uri.starts_with("transform-rules://examples/"))
+        .collect();
+    assert_eq!(uris.len(), 5);
+
+    for (id, uri) in uris.into_iter().enumerate() {
+        let name = uri.trim_start_matches("transform-rules://examples/");
+        assert!(index.contains(uri), "{}", uri);
+
+        let read = server.send(&json!({
+            "jsonrpc": "2.0",
+            "id": 10 + id * 2,
+            "method": "resources/read",
+            "params": { "uri": uri }
+        }));
+        let contents = read["result"]["contents"].as_array().expect("contents");
+        assert_eq!(contents[0]["uri"], uri);
+        assert!(contents[0]["text"].as_str().unwrap().contains("mappings:"));
+        let expected = contents.last().expect("expected output");
+        assert_eq!(expected["uri"], format!("{}/expected.json", uri));
+        let expected: serde_json::Value =
+            serde_json::from_str(expected["text"].as_str().unwrap()).unwrap();
+
+        let run = server.send(&json!({
+            "jsonrpc": "2.0",
+            "id": 11 + id * 2,
+            "method": "tools/call",
+            "params": { "name": "run_example", "arguments": { "name": name } }
+        }));
+        assert_ne!(run["result"]["isError"], true, "{}: {}", name, run);
+        let output: serde_json::Value =
+            serde_json::from_str(run["result"]["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(output, expected, "{}", name);
+    }
+
+    server.shutdown();
+}
+
+#[test]
+fn transform_reads_env_refs_only_with_allow_env() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let call = |id: u64, allow_env: Option<bool>| {
+        let mut arguments = json!({
+            "rules_text": "version: 1\ninput:\n  format: json\n  json: {}\nmappings:\n  - target: \"package\"\n    source: \"env.CARGO_PKG_NAME\"\n",
+            "input_json": [{}]
+        });
+        if let Some(allow_env) = allow_env {
+            arguments["allow_env"] = json!(allow_env);
+        }
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": "transform", "arguments": arguments }
+        })
+    };
+
+    // The server inherits the environment cargo gives this test.
+    let response = server.send(&call(2, Some(true)));
+    assert_eq!(
+        response["result"]["content"][0]["text"],
+        r#"[{"package":"transform_rules_mcp"}]"#
+    );
+
+    for (id, allow_env) in [(3, None), (4, Some(false))] {
+        let response = server.send(&call(id, allow_env));
+        assert_eq!(response["result"]["isError"], true);
+        let error = &response["result"]["meta"]["errors"][0];
+        assert_eq!(error["kind"], "ExprError");
+        assert_eq!(error["code"], "T1206");
+        assert_eq!(error["path"], "mappings[0].source");
+    }
+
+    server.shutdown();
+}
+
+#[test]
+fn run_example_takes_inline_input() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let response = server.send(&json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "tools/call",
+        "params": {
+            "name": "run_example",
+            "arguments": {
+                "name": "context_lookup",
+                "input_json": [{ "user_id": 2 }],
+                "return_output_json": true
+            }
+        }
+    }));
+    assert_eq!(
+        response["result"]["meta"]["output"],
+        json!([{ "team": "docs", "user_id": 2, "user_name": "Lin" }])
+    );
+
+    let response = server.send(&json!({
+        "jsonrpc": "2.0",
+        "id": 3,
+        "method": "tools/call",
+        "params": { "name": "run_example", "arguments": { "name": "missing" } }
+    }));
+    assert_eq!(response["result"]["isError"], true);
+    assert_eq!(response["result"]["content"][0]["text"], "unknown example: missing");
+
+    let response = server.send(&json!({
+        "jsonrpc": "2.0",
+        "id": 4,
+        "method": "tools/call",
+        "params": {
+            "name": "run_example",
+            "arguments": { "name": "csv_to_json", "rules_path": "rules.yaml" }
+        }
+    }));
+    assert!(response["error"].is_object(), "{}", response);
+
+    server.shutdown();
+}
+
 #[test]
 fn prompts_list_and_get() {
     let mut server = McpServer::start();
@@ -986,3 +2701,85 @@ fn prompts_list_and_get() {
 
     server.shutdown();
 }
+
+#[test]
+fn transform_trace_targets() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 30,
+        "method": "tools/call",
+        "params": {
+            "name": "transform",
+            "arguments": {
+                "rules_text": "version: 1\ninput:\n  format: json\n  json: {}\nmappings:\n  - target: \"id\"\n    source: \"id\"\n  - target: \"name\"\n    expr:\n      chain:\n        - { ref: \"input.name\" }\n        - { op: \"uppercase\" }\n",
+                "input_json": [{ "id": 1, "name": "A" }, { "id": 2, "name": "b" }],
+                "trace_targets": ["name"]
+            }
+        }
+    });
+
+    let response = server.send(&request);
+    let trace = response["result"]["meta"]["trace"]
+        .as_array()
+        .expect("trace array");
+    assert_eq!(trace.len(), 2);
+    assert!(trace.iter().all(|event| event["record_index"] == 1));
+    assert_eq!(trace[1]["op"], "uppercase");
+    assert_eq!(trace[1]["value"], "\"B\"");
+
+    server.shutdown();
+}
+
+#[test]
+fn content_length_rejects_invalid_utf8() {
+    let mut server = McpServer::start();
+
+    let response = server.send_content_length(b"{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"ping\xff\"}");
+    assert_eq!(response["id"], Value::Null);
+    assert_eq!(response["error"]["code"], -32700);
+    let message = response["error"]["message"].as_str().expect("error message");
+    assert!(message.contains("byte offset 38"), "unexpected message: {}", message);
+
+    let request = serde_json::to_vec(&json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "tools/list"
+    }))
+    .expect("serialize request");
+    let response = server.send_content_length(&request);
+    assert_eq!(response["id"], 2);
+    assert!(response["result"]["tools"].is_array());
+
+    server.shutdown();
+}
+
+#[test]
+fn transform_error_includes_record_index() {
+    let mut server = McpServer::start();
+    initialize(&mut server);
+
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 7,
+        "method": "tools/call",
+        "params": {
+            "name": "transform",
+            "arguments": {
+                "rules_text": "version: 1\ninput:\n  format: csv\n  csv: {}\nmappings:\n  - target: \"id\"\n    source: \"id\"\n    type: \"int\"\n",
+                "input_text": "id\n1\nx\n"
+            }
+        }
+    });
+
+    let response = server.send(&request);
+    assert_eq!(response["result"]["isError"], true);
+    let error = &response["result"]["meta"]["errors"][0];
+    assert_eq!(error["kind"], "TypeCastFailed");
+    assert_eq!(error["record_index"], 1);
+    assert_eq!(error["line"], 3);
+
+    server.shutdown();
+}